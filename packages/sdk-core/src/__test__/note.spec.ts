@@ -131,7 +131,7 @@ describe('Note class', () => {
     try {
       await Note.generateNote(noteInput);
     } catch (e: any) {
-      expect(e.code).to.equal(42);
+      expect(e.code).to.equal('UnsupportedBackend');
       expect(e.message).to.equal('Circom backend is supported when the secret value is supplied');
     }
   });
@@ -195,7 +195,7 @@ describe('Note class', () => {
     try {
       await Note.deserialize(serialized);
     } catch (e: any) {
-      expect(e.code).to.equal(4);
+      expect(e.code).to.equal('InvalidNoteProtocol');
       expect(e.message).to.equal('Invalid note protocol');
     }
   });
@@ -212,7 +212,7 @@ describe('Note class', () => {
     try {
       await Note.deserialize(serialized);
     } catch (e: any) {
-      expect(e.code).to.equal(5);
+      expect(e.code).to.equal('InvalidNoteVersion');
       expect(e.message).to.equal('Invalid note version');
     }
   });
@@ -229,7 +229,7 @@ describe('Note class', () => {
     try {
       await Note.deserialize(serialized);
     } catch (e: any) {
-      expect(e.code).to.equal(18);
+      expect(e.code).to.equal('InvalidSourceChain');
       expect(e.message).to.equal('Invalid source chain id');
     }
   });
@@ -246,7 +246,7 @@ describe('Note class', () => {
     try {
       await Note.deserialize(serialized);
     } catch (e: any) {
-      expect(e.code).to.equal(19);
+      expect(e.code).to.equal('InvalidTargetChain');
       expect(e.message).to.equal('Invalid target chain id');
     }
   });
@@ -263,7 +263,7 @@ describe('Note class', () => {
     try {
       await Note.deserialize(serialized);
     } catch (e: any) {
-      expect(e.code).to.equal(3);
+      expect(e.code).to.equal('InvalidNoteLength');
       expect(e.message).to.equal('Note length has incorrect parts length: 4');
     }
   });
@@ -281,7 +281,7 @@ describe('Note class', () => {
     try {
       await Note.deserialize(serialized);
     } catch (e: any) {
-      expect(e.code).to.equal(8);
+      expect(e.code).to.equal('InvalidNoteSecrets');
       expect(e.message).to.equal('Invalid note secrets');
     }
   });
@@ -299,7 +299,7 @@ describe('Note class', () => {
     try {
       await Note.deserialize(serialized);
     } catch (e: any) {
-      expect(e.code).to.equal(8);
+      expect(e.code).to.equal('InvalidNoteSecrets');
       expect(e.message).to.equal('Invalid note secrets');
     }
   });
@@ -317,7 +317,7 @@ describe('Note class', () => {
     try {
       await Note.deserialize(serialized);
     } catch (e: any) {
-      expect(e.code).to.equal(3);
+      expect(e.code).to.equal('InvalidNoteLength');
       expect(e.message).to.equal('Invalid note length');
     }
   });
@@ -335,7 +335,7 @@ describe('Note class', () => {
     try {
       await Note.deserialize(serialized);
     } catch (e: any) {
-      expect(e.code).to.equal(3);
+      expect(e.code).to.equal('InvalidNoteLength');
       expect(e.message).to.equal('Invalid note length');
     }
   });
@@ -353,7 +353,7 @@ describe('Note class', () => {
     try {
       await Note.deserialize(serialized);
     } catch (e: any) {
-      expect(e.code).to.equal(8);
+      expect(e.code).to.equal('InvalidNoteSecrets');
       expect(e.message).to.equal('Invalid note secrets');
     }
   });
@@ -371,7 +371,7 @@ describe('Note class', () => {
     try {
       await Note.deserialize(serialized);
     } catch (e: any) {
-      expect(e.code).to.equal(3);
+      expect(e.code).to.equal('InvalidNoteLength');
       expect(e.message).to.equal('Invalid note length');
     }
   });
@@ -406,7 +406,7 @@ describe('Note class', () => {
 
       note.getLeafCommitment();
     } catch (e: any) {
-      expect(e.code).to.equal(8);
+      expect(e.code).to.equal('InvalidNoteSecrets');
       expect(e.message).to.equal('Invalid secret format for protocol vanchor');
     }
 
@@ -435,7 +435,7 @@ describe('Note class', () => {
     try {
       await Note.generateNote(noteInput);
     } catch (e: any) {
-      expect(e.code).to.equal(32);
+      expect(e.code).to.equal('FailedToGenerateTheLeaf');
       expect(e.message).to.equal('No VAnchor leaf setup for curve Bls381, exponentiation 5, and width 5');
     }
   });
@@ -463,7 +463,7 @@ describe('Note class', () => {
     try {
       await Note.generateNote(noteInput);
     } catch (e: any) {
-      expect(e.code).to.equal(8);
+      expect(e.code).to.equal('InvalidNoteSecrets');
       expect(e.message).to.equal('VAnchor secrets length should be 4 in length');
     }
   });
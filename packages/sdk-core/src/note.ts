@@ -100,11 +100,7 @@ export class Note {
 
       return new Note(depositNote);
     } catch (e: any) {
-      return Promise.reject({
-        code: e.code,
-        data: e.data,
-        message: e.error_message
-      });
+      return Promise.reject(e);
     }
   }
 
@@ -187,54 +183,49 @@ export class Note {
   public static async generateNote (noteGenInput: NoteGenInput): Promise<Note> {
     try {
       const wasm = await Note.wasm;
-      const noteBuilderInput = new wasm.JsNoteBuilder();
-
-      noteBuilderInput.protocol(noteGenInput.protocol);
-      noteBuilderInput.version(Note.CURRENT_VERSION);
-      noteBuilderInput.targetChainId(noteGenInput.targetChain);
-      noteBuilderInput.sourceChainId(noteGenInput.sourceChain);
-      noteBuilderInput.backend(noteGenInput.backend);
-      noteBuilderInput.hashFunction(noteGenInput.hashFunction);
-      noteBuilderInput.curve(noteGenInput.curve);
-      noteBuilderInput.tokenSymbol(noteGenInput.tokenSymbol);
-      noteBuilderInput.amount(noteGenInput.amount);
-      noteBuilderInput.denomination(noteGenInput.denomination);
-      noteBuilderInput.width(noteGenInput.width);
-      noteBuilderInput.exponentiation(noteGenInput.exponentiation);
+      let noteBuilderInput = new wasm.JsNoteBuilder()
+        .protocol(noteGenInput.protocol)
+        .version(Note.CURRENT_VERSION)
+        .targetChainId(noteGenInput.targetChain)
+        .sourceChainId(noteGenInput.sourceChain)
+        .backend(noteGenInput.backend)
+        .hashFunction(noteGenInput.hashFunction)
+        .curve(noteGenInput.curve)
+        .tokenSymbol(noteGenInput.tokenSymbol)
+        .amount(noteGenInput.amount)
+        .denomination(noteGenInput.denomination)
+        .width(noteGenInput.width)
+        .exponentiation(noteGenInput.exponentiation);
 
       if (noteGenInput.secrets !== undefined) {
-        noteBuilderInput.setSecrets(noteGenInput.secrets);
+        noteBuilderInput = noteBuilderInput.setSecrets(noteGenInput.secrets);
       }
 
       if (noteGenInput.targetIdentifyingData !== undefined) {
-        noteBuilderInput.targetIdentifyingData(noteGenInput.targetIdentifyingData);
+        noteBuilderInput = noteBuilderInput.targetIdentifyingData(noteGenInput.targetIdentifyingData);
       }
 
       if (noteGenInput.sourceIdentifyingData !== undefined) {
-        noteBuilderInput.sourceIdentifyingData(noteGenInput.sourceIdentifyingData);
+        noteBuilderInput = noteBuilderInput.sourceIdentifyingData(noteGenInput.sourceIdentifyingData);
       }
 
       if (noteGenInput.index !== undefined) {
-        noteBuilderInput.index(String(noteGenInput.index));
+        noteBuilderInput = noteBuilderInput.index(String(noteGenInput.index));
       }
 
       if (noteGenInput.blinding !== undefined) {
-        noteBuilderInput.setBlinding(noteGenInput.blinding);
+        noteBuilderInput = noteBuilderInput.setBlinding(noteGenInput.blinding);
       }
 
       if (noteGenInput.privateKey !== undefined) {
-        noteBuilderInput.setPrivateKey(noteGenInput.privateKey);
+        noteBuilderInput = noteBuilderInput.setPrivateKey(noteGenInput.privateKey);
       }
 
       const depositNote = noteBuilderInput.build();
 
       return new Note(depositNote);
     } catch (e: any) {
-      return Promise.reject({
-        code: e.code,
-        data: e.data,
-        message: e.error_message
-      });
+      return Promise.reject(e);
     }
   }
 }
@@ -71,20 +71,21 @@ export class ArkworksProvingManagerThread {
    **/
   async prove<T extends NoteProtocol> (protocol: T, pmSetupInput: WorkerProvingManagerSetupInput<T>): Promise<WorkerProofInterface<T>> {
     const Manager = await this.proofBuilder;
-    const pm = new Manager(protocol);
+    let pm = new Manager(protocol);
 
     if (protocol === 'mixer') {
       const input = pmSetupInput as MixerPMSetupInput;
       const { note } = await Note.deserialize(input.note);
 
-      pm.setLeaves(input.leaves);
-      pm.setRelayer(input.relayer);
-      pm.setRecipient(input.recipient);
-      pm.setLeafIndex(String(input.leafIndex));
-      pm.setRefund(String(input.refund));
-      pm.setFee(String(input.fee));
-      pm.setPk(u8aToHex(input.provingKey).replace('0x', ''));
-      pm.setNote(note);
+      pm = pm
+        .setLeaves(input.leaves)
+        .setRelayer(input.relayer)
+        .setRecipient(input.recipient)
+        .setLeafIndex(String(input.leafIndex))
+        .setRefund(String(input.refund))
+        .setFee(String(input.fee))
+        .setPk(u8aToHex(input.provingKey).replace('0x', ''))
+        .setNote(note);
 
       const proofInput = pm.build_js();
       const proofOutput = await this.generateProof(proofInput);
@@ -159,13 +160,14 @@ export class ArkworksProvingManagerThread {
       const wasm = await this.wasmBlob;
       const outputUtxos = await Promise.all(input.output.map((utxoString) => Utxo.deserialize(utxoString)));
 
-      pm.setInputUtxos(inputUtxos.map((utxo) => utxo.inner));
-      pm.setIndices(leafIds.map((i) => i.index.toString()) as any);
-      pm.setPk(u8aToHex(input.provingKey).replace('0x', ''));
-      pm.setRoots(input.roots);
-      pm.chain_id(input.chainId);
-      pm.public_amount(input.publicAmount);
-      pm.setOutputUtxos(outputUtxos[0].inner, outputUtxos[1].inner);
+      pm = pm
+        .setInputUtxos(inputUtxos.map((utxo) => utxo.inner))
+        .setIndices(leafIds.map((i) => i.index.toString()) as any)
+        .setPk(u8aToHex(input.provingKey).replace('0x', ''))
+        .setRoots(input.roots)
+        .chain_id(input.chainId)
+        .public_amount(input.publicAmount)
+        .setOutputUtxos(outputUtxos[0].inner, outputUtxos[1].inner);
 
       const extData = new wasm.ExtData(
         input.recipient,
@@ -180,14 +182,14 @@ export class ArkworksProvingManagerThread {
       const dataHash = extData.get_encode();
       const dataHashhex = u8aToHex(dataHash).replace('0x', '');
 
-      pm.setExtDatahash(dataHashhex);
+      pm = pm.setExtDatahash(dataHashhex);
       const leavesMap = new wasm.LeavesMapInput();
 
       for (const key of Object.keys(input.leavesMap)) {
         leavesMap.setChainLeaves(key as any, input.leavesMap[key]);
       }
 
-      pm.setLeavesMap(leavesMap);
+      pm = pm.setLeavesMap(leavesMap);
 
       const proofInput = pm.build_js();
       const proofOutput = await this.generateProof(proofInput);
@@ -0,0 +1,11 @@
+//! Any string must parse to a note or an error, and a parsed note must
+//! serialize to a string that parses to the same note.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use wasm_utils::note::JsNote;
+
+fuzz_target!(|note: &str| {
+	if let Ok(parsed) = note.parse::<JsNote>() {
+		assert_eq!(parsed.to_string().parse::<JsNote>(), Ok(parsed));
+	}
+});
@@ -0,0 +1,61 @@
+//! Notes built from arbitrary field values must survive serialization.
+#![no_main]
+use libfuzzer_sys::arbitrary::{self, Arbitrary};
+use libfuzzer_sys::fuzz_target;
+use wasm_utils::note::metadata::NoteMetadata;
+use wasm_utils::note::JsNote;
+use wasm_utils::types::{Backend, Curve, HashFunction, NoteProtocol, NoteVersion};
+
+#[derive(Arbitrary, Debug)]
+struct NoteFields {
+	vanchor: bool,
+	source_chain_id: u64,
+	target_chain_id: u64,
+	source_identifying_data: String,
+	target_identifying_data: String,
+	secrets: Vec<Vec<u8>>,
+	bls381: Option<bool>,
+	exponentiation: Option<i8>,
+	width: Option<u32>,
+	token_symbol: Option<String>,
+	amount: Option<u128>,
+	denomination: Option<u8>,
+	circom: Option<bool>,
+	mimc: Option<bool>,
+	index: Option<u64>,
+}
+
+fuzz_target!(|fields: NoteFields| {
+	// An empty secrets part reads back as one empty secret
+	if fields.secrets.is_empty() {
+		return;
+	}
+	let note = JsNote {
+		scheme: "webb://".to_string(),
+		protocol: if fields.vanchor { NoteProtocol::VAnchor } else { NoteProtocol::Mixer },
+		version: NoteVersion::V1,
+		source_chain_id: fields.source_chain_id.to_string(),
+		target_chain_id: fields.target_chain_id.to_string(),
+		source_identifying_data: fields.source_identifying_data,
+		target_identifying_data: fields.target_identifying_data,
+		secrets: fields.secrets,
+		curve: fields.bls381.map(|bls381| if bls381 { Curve::Bls381 } else { Curve::Bn254 }),
+		exponentiation: fields.exponentiation,
+		width: fields.width.map(|width| width as usize),
+		token_symbol: fields.token_symbol,
+		amount: fields.amount.map(|amount| amount.to_string()),
+		denomination: fields.denomination,
+		backend: fields.circom.map(|circom| if circom { Backend::Circom } else { Backend::Arkworks }),
+		hash_function: fields.mimc.map(|mimc| {
+			if mimc {
+				HashFunction::MiMC220
+			} else {
+				HashFunction::Poseidon
+			}
+		}),
+		index: fields.index,
+		metadata: NoteMetadata::default(),
+	};
+	let serialized = note.to_string();
+	assert_eq!(serialized.parse::<JsNote>(), Ok(note), "{}", serialized);
+});
@@ -0,0 +1,13 @@
+//! The SCALE decoders of the serialized trees and proof inputs must reject
+//! arbitrary bytes with an error rather than panic.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use wasm_utils::merkle::frontier::FrontierMerkleTree;
+use wasm_utils::merkle::MerkleTree;
+use wasm_utils::proof::ProofInput;
+
+fuzz_target!(|state: &[u8]| {
+	let _ = MerkleTree::deserialize(state);
+	let _ = FrontierMerkleTree::deserialize(state);
+	let _ = ProofInput::deserialize(state);
+});
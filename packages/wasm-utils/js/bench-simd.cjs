@@ -0,0 +1,35 @@
+// Copyright 2022 Webb Technologies Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+// Times the scalar and simd128 builds of `yarn bench:simd` on the same
+// workloads, to check whether the SIMD build is worth loading
+const builds = {
+  scalar: require('../build/bench/scalar/wasm-utils.js'),
+  simd: require('../build/bench/simd/wasm-utils.js')
+};
+const RUNS = 5;
+const LEAVES = 200;
+const TREE_DEPTH = 20;
+
+function median (values) {
+  const sorted = [...values].sort((a, b) => a - b);
+
+  return sorted[Math.floor(sorted.length / 2)];
+}
+
+const results = {};
+
+for (const [name, wasm] of Object.entries(builds)) {
+  const leaves = [];
+  const proofs = [];
+
+  for (let run = 0; run < RUNS; run++) {
+    leaves.push(wasm.benchLeafGeneration(LEAVES, 'mixer', 'Bn254').perLeafMs);
+    proofs.push(wasm.benchProof('mixer', 'Bn254', TREE_DEPTH).proveMs);
+  }
+
+  results[name] = { perLeafMs: median(leaves), proveMs: median(proofs) };
+}
+
+console.table(results);
+console.log(`simd / scalar prove time: ${(results.simd.proveMs / results.scalar.proveMs).toFixed(3)}`);
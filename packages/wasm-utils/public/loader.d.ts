@@ -0,0 +1,13 @@
+// Copyright 2022 Webb Technologies Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+import type { InitOptions } from './wasm-utils';
+
+export function simdSupported (): boolean;
+
+/**
+ * Load the SIMD build of wasm-utils when the engine supports it and the
+ * scalar build otherwise, then initialize it with `options`. The `simd` flag
+ * of `init` tells which build was loaded.
+ */
+export function init (options?: InitOptions): Promise<typeof import('./wasm-utils')>;
@@ -0,0 +1,30 @@
+// Copyright 2022 Webb Technologies Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+// The smallest module using a v128 instruction, it only validates on engines
+// supporting wasm SIMD
+const SIMD_PROBE = new Uint8Array([
+  0, 97, 115, 109, 1, 0, 0, 0, 1, 5, 1, 96, 0, 1, 123, 3, 2, 1, 0, 10, 10, 1, 8, 0, 65, 0, 253, 15, 253, 98, 11
+]);
+
+export function simdSupported () {
+  try {
+    return typeof WebAssembly === 'object' && WebAssembly.validate(SIMD_PROBE);
+  } catch (_) {
+    return false;
+  }
+}
+
+/**
+ * Load the SIMD build of wasm-utils when the engine supports it and the
+ * scalar build otherwise, then initialize it with `options`.
+ */
+export async function init (options) {
+  const wasm = simdSupported()
+    ? await import('./simd/wasm-utils-simd.js')
+    : await import('./wasm-utils.js');
+
+  wasm.init(options);
+
+  return wasm;
+}
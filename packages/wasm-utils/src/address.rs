@@ -0,0 +1,288 @@
+use core::convert::TryInto;
+
+use blake2::{Blake2b, Digest};
+use js_sys::{JsString, Uint8Array};
+use tiny_keccak::{Hasher, Keccak};
+use wasm_bindgen::prelude::*;
+
+use crate::proof::truncate_and_pad;
+use crate::types::{OpStatusCode, OperationError};
+
+/// How an account is laid out in the arbitrary data of a proof, it has to
+/// match the way the verifier of the target chain arranges its public inputs
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AccountEncoding {
+	/// The first 20 bytes of the account followed by zero bytes up to 32,
+	/// shorter accounts are padded the same way
+	Substrate,
+	/// The 20 bytes address as a left padded `uint256`
+	Evm,
+}
+
+impl AccountEncoding {
+	pub fn encode(&self, account: &[u8], code: OpStatusCode) -> Result<Vec<u8>, OperationError> {
+		match self {
+			AccountEncoding::Substrate => Ok(truncate_and_pad(account)),
+			AccountEncoding::Evm => {
+				if account.len() != 20 {
+					let message = format!("Expected a 20 bytes EVM address, got {} bytes", account.len());
+					return Err(OperationError::new_with_message(code, message));
+				}
+				let mut bytes = vec![0u8; 12];
+				bytes.extend_from_slice(account);
+				Ok(bytes)
+			}
+		}
+	}
+}
+
+/// Parse a `0x` prefixed EVM address, mixed case addresses must have a valid
+/// EIP-55 checksum
+pub fn parse_evm_address(address: &str) -> Result<[u8; 20], OperationError> {
+	let invalid = |message: &str| OperationError::new_with_message(OpStatusCode::InvalidAddress, message.to_string());
+	let hex_address = address.strip_prefix("0x").unwrap_or(address);
+	if hex_address.len() != 40 {
+		return Err(invalid("An EVM address has 40 hex characters"));
+	}
+	let bytes: [u8; 20] = hex::decode(hex_address)
+		.map_err(|_| invalid("The EVM address isn't valid hex"))?
+		.try_into()
+		.unwrap();
+
+	let is_mixed_case =
+		hex_address.chars().any(|c| c.is_ascii_lowercase()) && hex_address.chars().any(|c| c.is_ascii_uppercase());
+	if is_mixed_case && to_checksum_address(&bytes) != format!("0x{}", hex_address) {
+		return Err(invalid("The EVM address checksum is invalid"));
+	}
+	Ok(bytes)
+}
+
+/// EIP-55 checksummed representation of an address
+pub fn to_checksum_address(address: &[u8; 20]) -> String {
+	let lower = hex::encode(address);
+	let mut keccak = Keccak::v256();
+	keccak.update(lower.as_bytes());
+	let mut hash = [0u8; 32];
+	keccak.finalize(&mut hash);
+
+	let checksummed: String = lower
+		.chars()
+		.enumerate()
+		.map(|(i, c)| {
+			let nibble = (hash[i / 2] >> (if i % 2 == 0 { 4 } else { 0 })) & 0x0f;
+			if nibble >= 8 {
+				c.to_ascii_uppercase()
+			} else {
+				c
+			}
+		})
+		.collect();
+	format!("0x{}", checksummed)
+}
+
+const BASE58_ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+const SS58_CHECKSUM_PREFIX: &[u8] = b"SS58PRE";
+
+fn base58_encode(input: &[u8]) -> String {
+	let zeros = input.iter().take_while(|b| **b == 0).count();
+	// Little endian base 58 digits
+	let mut digits: Vec<u8> = Vec::new();
+	for byte in &input[zeros..] {
+		let mut carry = *byte as u32;
+		for digit in digits.iter_mut() {
+			carry += (*digit as u32) << 8;
+			*digit = (carry % 58) as u8;
+			carry /= 58;
+		}
+		while carry > 0 {
+			digits.push((carry % 58) as u8);
+			carry /= 58;
+		}
+	}
+	let mut encoded = "1".repeat(zeros);
+	encoded.extend(digits.iter().rev().map(|d| BASE58_ALPHABET[*d as usize] as char));
+	encoded
+}
+
+fn base58_decode(input: &str) -> Option<Vec<u8>> {
+	let zeros = input.chars().take_while(|c| *c == '1').count();
+	// Little endian bytes
+	let mut bytes: Vec<u8> = Vec::new();
+	for c in input.bytes().skip(zeros) {
+		let mut carry = BASE58_ALPHABET.iter().position(|a| *a == c)? as u32;
+		for byte in bytes.iter_mut() {
+			carry += (*byte as u32) * 58;
+			*byte = carry as u8;
+			carry >>= 8;
+		}
+		while carry > 0 {
+			bytes.push(carry as u8);
+			carry >>= 8;
+		}
+	}
+	let mut decoded = vec![0u8; zeros];
+	decoded.extend(bytes.iter().rev());
+	Some(decoded)
+}
+
+fn ss58_checksum(data: &[u8]) -> Vec<u8> {
+	let mut hasher = Blake2b::new();
+	hasher.update(SS58_CHECKSUM_PREFIX);
+	hasher.update(data);
+	hasher.finalize().to_vec()
+}
+
+/// Length of the checksum for a payload, following the SS58 registry
+fn ss58_checksum_len(payload_len: usize) -> Option<usize> {
+	match payload_len {
+		1 | 2 | 4 | 8 => Some(1),
+		32 | 33 => Some(2),
+		_ => None,
+	}
+}
+
+/// Decode an SS58 address into its payload and network prefix, the address
+/// is rejected if the checksum doesn't match or if the prefix isn't the
+/// expected one
+pub fn ss58_decode(address: &str, expected_prefix: Option<u16>) -> Result<(Vec<u8>, u16), OperationError> {
+	let invalid = |message: &str| OperationError::new_with_message(OpStatusCode::InvalidAddress, message.to_string());
+	let data = base58_decode(address).ok_or_else(|| invalid("The SS58 address isn't valid base58"))?;
+	if data.len() < 2 {
+		return Err(invalid("The SS58 address is too short"));
+	}
+
+	let (prefix_len, prefix) = match data[0] {
+		0..=63 => (1, data[0] as u16),
+		64..=127 => {
+			let lower = (data[0] << 2) | (data[1] >> 6);
+			let upper = data[1] & 0b0011_1111;
+			(2, (lower as u16) | ((upper as u16) << 8))
+		}
+		_ => return Err(invalid("The SS58 address prefix is invalid")),
+	};
+	let (payload_len, checksum_len) = [1, 2, 4, 8, 32, 33]
+		.iter()
+		.filter_map(|len| ss58_checksum_len(*len).map(|checksum_len| (*len, checksum_len)))
+		.find(|(len, checksum_len)| prefix_len + len + checksum_len == data.len())
+		.ok_or_else(|| invalid("The SS58 address has an unsupported length"))?;
+
+	let body_len = prefix_len + payload_len;
+	if ss58_checksum(&data[..body_len])[..checksum_len] != data[body_len..] {
+		return Err(invalid("The SS58 address checksum is invalid"));
+	}
+	if let Some(expected_prefix) = expected_prefix {
+		if expected_prefix != prefix {
+			let message = format!("Expected the SS58 prefix {}, got {}", expected_prefix, prefix);
+			return Err(OperationError::new_with_message(OpStatusCode::InvalidAddress, message));
+		}
+	}
+	Ok((data[prefix_len..body_len].to_vec(), prefix))
+}
+
+/// Encode a payload (usually a 32 bytes account id) as an SS58 address for
+/// the network `prefix`
+pub fn ss58_encode(payload: &[u8], prefix: u16) -> Result<String, OperationError> {
+	let checksum_len = ss58_checksum_len(payload.len()).ok_or_else(|| {
+		let message = format!("Can't encode {} bytes as an SS58 address", payload.len());
+		OperationError::new_with_message(OpStatusCode::InvalidAddress, message)
+	})?;
+	let mut data = match prefix {
+		0..=63 => vec![prefix as u8],
+		64..=16_383 => {
+			let first = ((prefix & 0b0000_0000_1111_1100) as u8 >> 2) | 0b0100_0000;
+			let second = ((prefix >> 8) as u8) | (((prefix & 0b0000_0000_0000_0011) as u8) << 6);
+			vec![first, second]
+		}
+		_ => {
+			let message = format!("The SS58 prefix {} is out of range", prefix);
+			return Err(OperationError::new_with_message(OpStatusCode::InvalidAddress, message));
+		}
+	};
+	data.extend_from_slice(payload);
+	let checksum = ss58_checksum(&data);
+	data.extend_from_slice(&checksum[..checksum_len]);
+	Ok(base58_encode(&data))
+}
+
+/// Decode an SS58 address to the raw account bytes, optionally checking the
+/// network prefix
+#[wasm_bindgen]
+pub fn ss58_to_bytes(address: JsString, expected_prefix: Option<u16>) -> Result<Uint8Array, JsValue> {
+	let address: String = address.into();
+	let (payload, _) = ss58_decode(&address, expected_prefix)?;
+	Ok(Uint8Array::from(payload.as_slice()))
+}
+
+/// Encode raw account bytes as an SS58 address for the network `prefix`
+#[wasm_bindgen]
+pub fn bytes_to_ss58(bytes: Uint8Array, prefix: u16) -> Result<JsString, JsValue> {
+	let address = ss58_encode(&bytes.to_vec(), prefix)?;
+	Ok(address.into())
+}
+
+#[cfg(test)]
+mod test {
+	use wasm_bindgen_test::*;
+
+	use super::*;
+
+	const CHECKSUMMED: &str = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+
+	#[wasm_bindgen_test]
+	fn should_validate_evm_address_checksum() {
+		let bytes = parse_evm_address(CHECKSUMMED).unwrap();
+		assert_eq!(to_checksum_address(&bytes), CHECKSUMMED);
+		assert_eq!(parse_evm_address(&CHECKSUMMED.to_lowercase()).unwrap(), bytes);
+
+		let wrong_checksum = CHECKSUMMED.replace("aAeb", "AAeb");
+		assert_eq!(
+			parse_evm_address(&wrong_checksum).unwrap_err().code,
+			OpStatusCode::InvalidAddress
+		);
+		assert!(parse_evm_address("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeA").is_err());
+	}
+
+	#[wasm_bindgen_test]
+	fn should_encode_accounts() {
+		let address = parse_evm_address(CHECKSUMMED).unwrap();
+		let encoded = AccountEncoding::Evm
+			.encode(&address, OpStatusCode::InvalidRecipient)
+			.unwrap();
+		assert_eq!(&encoded[..12], &[0u8; 12]);
+		assert_eq!(&encoded[12..], &address);
+
+		let account = [7u8; 32];
+		let encoded = AccountEncoding::Substrate
+			.encode(&account, OpStatusCode::InvalidRecipient)
+			.unwrap();
+		assert_eq!(encoded, truncate_and_pad(&account));
+		let encoded = AccountEncoding::Substrate
+			.encode(&account[..8], OpStatusCode::InvalidRecipient)
+			.unwrap();
+		assert_eq!(encoded, [&[7u8; 8][..], &[0u8; 24]].concat());
+		assert!(AccountEncoding::Evm
+			.encode(&account, OpStatusCode::InvalidRecipient)
+			.is_err());
+	}
+
+	#[wasm_bindgen_test]
+	fn should_encode_and_decode_ss58() {
+		// Alice on the generic Substrate network
+		let alice = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY";
+		let alice_bytes = hex::decode("d43593c715fdd31c61141abd04a99fd6822c8558854ccde39a5684e7a56da27d").unwrap();
+
+		assert_eq!(ss58_decode(alice, Some(42)).unwrap(), (alice_bytes.clone(), 42));
+		assert_eq!(ss58_encode(&alice_bytes, 42).unwrap(), alice);
+		assert!(ss58_decode(alice, Some(0)).is_err());
+		let tampered = alice.replace('Y', "Z");
+		assert_eq!(
+			ss58_decode(&tampered, None).unwrap_err().code,
+			OpStatusCode::InvalidAddress
+		);
+
+		// Two bytes prefixes
+		let address = ss58_encode(&alice_bytes, 1_110).unwrap();
+		assert_eq!(ss58_decode(&address, Some(1_110)).unwrap().0, alice_bytes);
+		assert!(ss58_encode(&alice_bytes[..20], 42).is_err());
+	}
+}
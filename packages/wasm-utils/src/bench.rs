@@ -0,0 +1,314 @@
+//! Timings of the expensive operations, to compare browsers and devices and
+//! to catch performance regressions from the JS test suite. The keys, secrets
+//! and leaves are random, nothing here should be used for real deposits.
+use std::collections::BTreeMap;
+
+use ark_bls12_381::Bls12_381;
+use ark_bn254::{Bn254, Fr as Bn254Fr};
+use ark_ff::{BigInteger, PrimeField};
+use arkworks_setups::common::verify_unchecked_raw;
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::merkle::compute_root;
+use crate::note::{mixer, vanchor};
+use crate::proof::groth16::parse_curve;
+use crate::proof::mixer::MixerProofInput;
+use crate::proof::vanchor::VAnchorProofInput;
+use crate::proof::{ensure_supported_circuit, ensure_tree_depth, setup_keys_with_tree_depth, ProofOutput, ProverRng};
+use crate::types::{Backend, Curve, HashFunction, NoteProtocol, OpStatusCode, OperationError, Protocol, WasmCurve};
+use crate::TREE_HEIGHT;
+
+/// Exponentiation and width of the mixer leaf hash
+const MIXER_PARAMS: (i8, usize) = (5, 3);
+/// Exponentiation and width of the UTXO commitment hash
+const VANCHOR_PARAMS: (i8, usize) = (5, 5);
+/// Exponentiation and width of the merkle tree hash
+const TREE_PARAMS: (i8, u8) = (5, 3);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LeafBench {
+	pub protocol: String,
+	pub curve: String,
+	pub count: u32,
+	pub total_ms: f64,
+	pub per_leaf_ms: f64,
+}
+
+/// Time spent in every step of a proof, in milliseconds
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ProofBench {
+	pub protocol: String,
+	pub curve: String,
+	pub tree_depth: usize,
+	/// Generating the proving and verifying keys
+	pub setup_ms: f64,
+	/// Generating the secrets and leaves and building the proof input
+	pub input_ms: f64,
+	pub prove_ms: f64,
+	pub verify_ms: f64,
+	pub total_ms: f64,
+	pub verified: bool,
+}
+
+fn parse_protocol(protocol: Option<Protocol>) -> Result<NoteProtocol, OperationError> {
+	match protocol {
+		Some(protocol) => JsValue::from(protocol)
+			.as_string()
+			.ok_or(OpStatusCode::InvalidNoteProtocol)?
+			.parse()
+			.map_err(OperationError::from),
+		None => Ok(NoteProtocol::Mixer),
+	}
+}
+
+/// Time the generation of `count` random deposits: the secrets and the leaf
+pub fn bench_leaves(protocol: NoteProtocol, curve: Curve, count: u32) -> Result<LeafBench, OperationError> {
+	let started_at = js_sys::Date::now();
+	for index in 0..count {
+		match protocol {
+			NoteProtocol::Mixer => {
+				let (exponentiation, width) = MIXER_PARAMS;
+				let secrets = mixer::generate_secrets(exponentiation, width, curve, &mut OsRng)?.concat();
+				mixer::get_leaf_with_private_raw(curve, width, exponentiation, HashFunction::Poseidon, &secrets)?;
+			}
+			NoteProtocol::VAnchor => {
+				let (exponentiation, width) = VANCHOR_PARAMS;
+				vanchor::generate_secrets(0, exponentiation, width, curve, 0, Some(u64::from(index)), &mut OsRng)?;
+			}
+		}
+	}
+	let total_ms = js_sys::Date::now() - started_at;
+	Ok(LeafBench {
+		protocol: protocol.to_string(),
+		curve: curve.to_string(),
+		count,
+		total_ms,
+		per_leaf_ms: if count == 0 { 0.0 } else { total_ms / f64::from(count) },
+	})
+}
+
+fn mixer_input(curve: Curve, tree_depth: usize, pk: Vec<u8>) -> Result<MixerProofInput, OperationError> {
+	let (exponentiation, width) = MIXER_PARAMS;
+	let secrets = mixer::generate_secrets(exponentiation, width, curve, &mut OsRng)?;
+	let leaf =
+		mixer::get_leaf_with_private_raw(curve, width, exponentiation, HashFunction::Poseidon, &secrets.concat())?;
+	Ok(MixerProofInput {
+		exponentiation: Some(exponentiation),
+		width: Some(width),
+		curve: Some(curve),
+		backend: Some(Backend::Arkworks),
+		secret: Some(leaf.secret_bytes),
+		nullifier: Some(leaf.nullifier_bytes),
+		recipient: Some(vec![0; 32]),
+		relayer: Some(vec![0; 32]),
+		pk: Some(pk),
+		refund: Some(0),
+		fee: Some(0),
+		leaves: Some(vec![leaf.leaf_bytes]),
+		leaf_index: Some(0),
+		tree_depth: Some(tree_depth),
+		..Default::default()
+	})
+}
+
+/// Spend two empty UTXOs of the only leaves of the tree into two empty UTXOs
+fn vanchor_input(curve: Curve, tree_depth: usize, pk: Vec<u8>) -> Result<VAnchorProofInput, OperationError> {
+	let (exponentiation, width) = VANCHOR_PARAMS;
+	let utxo = |index: Option<u64>| vanchor::generate_secrets(0, exponentiation, width, curve, 0, index, &mut OsRng);
+	let input_utxos = vec![utxo(Some(0))?, utxo(Some(1))?];
+	let output_utxos = [utxo(None)?, utxo(None)?];
+	let leaves: Vec<Vec<u8>> = input_utxos.iter().map(|utxo| utxo.get_commitment()).collect();
+	let mut padded_leaves = Vec::with_capacity(leaves.len());
+	for leaf in &leaves {
+		padded_leaves.push(<[u8; 32]>::try_from(leaf.as_slice()).map_err(|_| OpStatusCode::InvalidLeaves)?);
+	}
+	let root = compute_root::<Bn254Fr>(TREE_PARAMS.0, TREE_PARAMS.1, &padded_leaves, tree_depth)?
+		.into_repr()
+		.to_bytes_be();
+	Ok(VAnchorProofInput {
+		exponentiation: Some(exponentiation),
+		width: Some(width),
+		curve: Some(curve),
+		backend: Some(Backend::Arkworks),
+		pk: Some(pk),
+		leaves: Some(BTreeMap::from([(0, leaves)])),
+		ext_data_hash: Some(vec![0; 32]),
+		roots: Some(vec![root.clone(), root]),
+		input_utxos: Some(input_utxos),
+		indices: Some(vec![0, 1]),
+		chain_id: Some(0),
+		public_amount: Some(0),
+		output_utxos: Some(output_utxos),
+		tree_depth: Some(tree_depth),
+		..Default::default()
+	})
+}
+
+/// Time a whole proof of `protocol` on `curve` with random keys and inputs:
+/// key setup, input building, proving and verifying
+pub fn bench_proof(protocol: NoteProtocol, curve: Curve, tree_depth: usize) -> Result<ProofBench, OperationError> {
+	ensure_tree_depth(tree_depth)?;
+	let (exponentiation, width) = match protocol {
+		NoteProtocol::Mixer => MIXER_PARAMS,
+		NoteProtocol::VAnchor => VANCHOR_PARAMS,
+	};
+	ensure_supported_circuit(protocol, curve, width, exponentiation)?;
+
+	let started_at = js_sys::Date::now();
+	let keys = setup_keys_with_tree_depth(
+		tree_depth as u32,
+		JsValue::from(protocol.to_string()).into(),
+		Some(JsValue::from(curve.to_string()).into()),
+		None,
+		None,
+		None,
+	)
+	.map_err(|_| OperationError::new_with_message(OpStatusCode::InvalidProvingKey, "Key setup failed".to_string()))?;
+	let setup_done_at = js_sys::Date::now();
+
+	let mut rng = ProverRng::new(None);
+	let (input_done_at, output) = match protocol {
+		NoteProtocol::Mixer => {
+			let payload = mixer_input(curve, tree_depth, keys.pk.clone())?.build()?;
+			let input_done_at = js_sys::Date::now();
+			let proof = crate::proof::mixer::create_proof(payload, &mut rng)?;
+			(input_done_at, ProofOutput::Mixer(proof))
+		}
+		NoteProtocol::VAnchor => {
+			let payload = vanchor_input(curve, tree_depth, keys.pk.clone())?.build()?;
+			let input_done_at = js_sys::Date::now();
+			let proof = crate::proof::vanchor::create_proof(payload, &mut rng)?;
+			(input_done_at, ProofOutput::VAnchor(proof))
+		}
+	};
+	let prove_done_at = js_sys::Date::now();
+
+	let proof = match &output {
+		ProofOutput::Mixer(proof) => &proof.proof,
+		ProofOutput::VAnchor(proof) => &proof.proof,
+	};
+	let verified = match curve {
+		Curve::Bn254 => verify_unchecked_raw::<Bn254>(output.public_inputs(), &keys.vk, proof),
+		Curve::Bls381 => verify_unchecked_raw::<Bls12_381>(output.public_inputs(), &keys.vk, proof),
+	}
+	.map_err(|e| OperationError::new_with_message(OpStatusCode::InvalidProofParameters, e.to_string()))?;
+	let verify_done_at = js_sys::Date::now();
+
+	Ok(ProofBench {
+		protocol: protocol.to_string(),
+		curve: curve.to_string(),
+		tree_depth,
+		setup_ms: setup_done_at - started_at,
+		input_ms: input_done_at - setup_done_at,
+		prove_ms: prove_done_at - input_done_at,
+		verify_ms: verify_done_at - prove_done_at,
+		total_ms: verify_done_at - started_at,
+		verified,
+	})
+}
+
+#[wasm_bindgen(typescript_custom_section)]
+const BENCH: &str = r#"
+export interface LeafBench {
+	protocol: NoteProtocol;
+	curve: Curve;
+	count: number;
+	totalMs: number;
+	perLeafMs: number;
+}
+
+export interface ProofBench {
+	protocol: NoteProtocol;
+	curve: Curve;
+	treeDepth: number;
+	setupMs: number;
+	inputMs: number;
+	proveMs: number;
+	verifyMs: number;
+	totalMs: number;
+	verified: boolean;
+}
+"#;
+
+#[wasm_bindgen]
+extern "C" {
+	#[wasm_bindgen(typescript_type = "LeafBench")]
+	pub type WasmLeafBench;
+
+	#[wasm_bindgen(typescript_type = "ProofBench")]
+	pub type WasmProofBench;
+}
+
+fn to_js<T: Serialize>(value: &T) -> Result<JsValue, OperationError> {
+	JsValue::from_serde(value)
+		.map_err(|e| OperationError::new_with_message(OpStatusCode::SerializationFailed, e.to_string()))
+}
+
+/// Time the generation of `n` random deposits, mixer notes on Bn254 unless
+/// `protocol` and `curve` are given
+#[wasm_bindgen(js_name = benchLeafGeneration)]
+pub fn js_bench_leaf_generation(
+	n: u32,
+	protocol: Option<Protocol>,
+	curve: Option<WasmCurve>,
+) -> Result<WasmLeafBench, JsValue> {
+	let bench = bench_leaves(parse_protocol(protocol)?, parse_curve(curve)?, n)?;
+	Ok(to_js(&bench)?.unchecked_into())
+}
+
+/// Time a proof with random keys and inputs, broken down in key setup, input
+/// building, proving and verifying. The tree depth defaults to 30.
+#[wasm_bindgen(js_name = benchProof)]
+pub fn js_bench_proof(
+	protocol: Protocol,
+	curve: Option<WasmCurve>,
+	tree_depth: Option<u32>,
+) -> Result<WasmProofBench, JsValue> {
+	let tree_depth = tree_depth.map(|depth| depth as usize).unwrap_or(TREE_HEIGHT);
+	let bench = bench_proof(parse_protocol(Some(protocol))?, parse_curve(curve)?, tree_depth)?;
+	Ok(to_js(&bench)?.unchecked_into())
+}
+
+#[cfg(test)]
+mod test {
+	use wasm_bindgen_test::*;
+
+	use super::*;
+
+	#[wasm_bindgen_test]
+	fn should_bench_leaf_generation() {
+		for (protocol, curve) in [
+			(NoteProtocol::Mixer, Curve::Bn254),
+			(NoteProtocol::Mixer, Curve::Bls381),
+			(NoteProtocol::VAnchor, Curve::Bn254),
+		] {
+			let bench = bench_leaves(protocol, curve, 4).unwrap();
+			assert_eq!(bench.count, 4);
+			assert!(bench.total_ms >= 0.0);
+			assert!(bench.per_leaf_ms <= bench.total_ms);
+		}
+		assert_eq!(
+			bench_leaves(NoteProtocol::Mixer, Curve::Bn254, 0).unwrap().per_leaf_ms,
+			0.0
+		);
+		let error = bench_leaves(NoteProtocol::VAnchor, Curve::Bls381, 1).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::SecretGenFailed);
+	}
+
+	#[wasm_bindgen_test]
+	fn should_bench_proofs() {
+		for protocol in [NoteProtocol::Mixer, NoteProtocol::VAnchor] {
+			let bench = bench_proof(protocol, Curve::Bn254, 20).unwrap();
+			assert!(bench.verified);
+			let steps = bench.setup_ms + bench.input_ms + bench.prove_ms + bench.verify_ms;
+			assert!((bench.total_ms - steps).abs() < 1e-6);
+		}
+		assert!(bench_proof(NoteProtocol::VAnchor, Curve::Bls381, 20).is_err());
+		assert!(bench_proof(NoteProtocol::Mixer, Curve::Bn254, 21).is_err());
+	}
+}
@@ -0,0 +1,76 @@
+//! IPFS content identifiers of key artifacts, so the keys the SDK downloads
+//! can be checked against the CIDs of the deployment config without a JS
+//! multihash library. The CID is the one of the artifact as a single raw
+//! block, as `ipfs block put --cid-codec raw` publishes it:
+//!
+//! `"b" || base32(0x01 (CIDv1) || 0x55 (raw) || 0x12 (sha2-256) || 0x20 ||
+//! sha256(bytes))`
+//!
+//! with the lowercase, unpadded RFC 4648 alphabet. `ipfs add` chunks files
+//! larger than a block into a DAG whose CID depends on the chunker, such CIDs
+//! can't be checked this way.
+use js_sys::{JsString, Uint8Array};
+use sha2::{Digest, Sha256};
+use wasm_bindgen::prelude::*;
+
+pub const CID_VERSION_1: u8 = 0x01;
+pub const RAW_CODEC: u8 = 0x55;
+pub const SHA2_256: u8 = 0x12;
+/// Multibase prefix of base32
+pub const BASE32_PREFIX: char = 'b';
+const BASE32_ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+fn base32(bytes: &[u8]) -> String {
+	let mut encoded = String::with_capacity((bytes.len() * 8 + 4) / 5);
+	let mut buffer = 0u16;
+	let mut bits = 0;
+	for byte in bytes {
+		buffer = (buffer << 8) | u16::from(*byte);
+		bits += 8;
+		while bits >= 5 {
+			bits -= 5;
+			encoded.push(BASE32_ALPHABET[usize::from((buffer >> bits) & 31)] as char);
+		}
+	}
+	if bits > 0 {
+		encoded.push(BASE32_ALPHABET[usize::from((buffer << (5 - bits)) & 31)] as char);
+	}
+	encoded
+}
+
+/// Binary CIDv1 of `bytes` as a raw block
+pub fn cid_v1_bytes(bytes: &[u8]) -> Vec<u8> {
+	let mut cid = vec![CID_VERSION_1, RAW_CODEC, SHA2_256, 32];
+	cid.extend_from_slice(&Sha256::digest(bytes));
+	cid
+}
+
+/// Base32 CIDv1 of `bytes` as a raw block, `bafkrei...`
+pub fn cid_v1(bytes: &[u8]) -> String {
+	format!("{}{}", BASE32_PREFIX, base32(&cid_v1_bytes(bytes)))
+}
+
+/// The base32 CIDv1 (raw codec, sha2-256) of `bytes`, as published for key
+/// artifacts
+#[wasm_bindgen(js_name = computeCidV1)]
+pub fn compute_cid_v1(bytes: Uint8Array) -> JsString {
+	cid_v1(&bytes.to_vec()).into()
+}
+
+#[cfg(test)]
+mod test {
+	use wasm_bindgen_test::*;
+
+	use super::*;
+
+	#[wasm_bindgen_test]
+	fn should_compute_raw_cids() {
+		assert_eq!(
+			cid_v1(b""),
+			"bafkreihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku"
+		);
+		assert_eq!(cid_v1_bytes(b"")[..4], [0x01, 0x55, 0x12, 0x20]);
+		assert_eq!(base32(b"foobar"), "mzxw6ytboi");
+		assert_eq!(base32(b"f"), "my");
+	}
+}
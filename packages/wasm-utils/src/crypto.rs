@@ -0,0 +1,232 @@
+use crypto_box::aead::Aead;
+use crypto_box::{generate_nonce, PublicKey, SalsaBox, SecretKey, KEY_SIZE};
+use js_sys::Uint8Array;
+use rand::rngs::OsRng;
+use rand::{CryptoRng, RngCore};
+use wasm_bindgen::prelude::*;
+
+use crate::keypair::JsKeypair;
+use crate::types::{Backend, Curve, OpStatusCode, OperationError};
+use crate::utxo::JsUtxo;
+
+/// Length of the x25519-xsalsa20-poly1305 nonce
+pub const NONCE_LENGTH: usize = 24;
+/// Length of the amount and the blinding in the plaintext
+pub const UTXO_FIELD_LENGTH: usize = 31;
+/// `chain_id (8) || amount (31) || blinding (31)`, the layout of
+/// protocol-solidity's `Utxo.encrypt`
+pub const UTXO_PLAINTEXT_LENGTH: usize = 8 + 2 * UTXO_FIELD_LENGTH;
+/// Length of the base64 string of the plaintext, which is what gets boxed
+pub const UTXO_ENVELOPE_LENGTH: usize = (UTXO_PLAINTEXT_LENGTH + 2) / 3 * 4;
+
+fn decryption_failed(message: &str) -> OperationError {
+	OperationError::new_with_message(OpStatusCode::DecryptionFailed, message.to_string())
+}
+
+/// Encrypt the private parts of `utxo` to the recipient's encryption key, as
+/// protocol-solidity's `Utxo.encrypt` does.
+///
+/// The plaintext is `chain_id (8) || amount (31) || blinding (31)`, boxed as
+/// its base64 string the way eth-sig-util's `encrypt` does. The output is
+/// `nonce (24) || ephemeral public key (32) || ciphertext`, the packed
+/// x25519-xsalsa20-poly1305 message VAnchor contracts emit as encrypted
+/// outputs.
+pub fn encrypt_utxo<R: RngCore + CryptoRng>(
+	utxo: &JsUtxo,
+	encryption_key: &PublicKey,
+	rng: &mut R,
+) -> Result<Vec<u8>, OperationError> {
+	let amount = utxo.get_amount();
+	let blinding = utxo.get_blinding();
+	if blinding[..32 - UTXO_FIELD_LENGTH].iter().any(|byte| *byte != 0) {
+		let message = format!(
+			"The blinding doesn't fit in the {} bytes it's encrypted in",
+			UTXO_FIELD_LENGTH
+		);
+		return Err(OperationError::new_with_message(
+			OpStatusCode::InvalidOutputUtxoConfig,
+			message,
+		));
+	}
+	let mut plaintext = utxo.get_chain_id_raw().to_be_bytes().to_vec();
+	plaintext.extend_from_slice(&amount[32 - UTXO_FIELD_LENGTH..]);
+	plaintext.extend_from_slice(&blinding[32 - UTXO_FIELD_LENGTH..]);
+
+	let ephemeral_secret = SecretKey::generate(rng);
+	let nonce = generate_nonce(rng);
+	let ciphertext = SalsaBox::new(encryption_key, &ephemeral_secret)
+		.encrypt(&nonce, base64::encode(&plaintext).as_bytes())
+		.map_err(|_| OperationError::from(OpStatusCode::SerializationFailed))?;
+
+	let mut encrypted = nonce.to_vec();
+	encrypted.extend_from_slice(ephemeral_secret.public_key().as_bytes());
+	encrypted.extend_from_slice(&ciphertext);
+	Ok(encrypted)
+}
+
+pub(crate) fn keypair_secret(keypair: &JsKeypair) -> Result<(Vec<u8>, SecretKey), OperationError> {
+	let secret_key = keypair.secret_key().ok_or_else(|| {
+		OperationError::new_with_message(
+			OpStatusCode::InvalidKeypair,
+			"Decryption requires the secret key".to_string(),
+		)
+	})?;
+	let mut secret_key_bytes = [0u8; KEY_SIZE];
+	secret_key_bytes.copy_from_slice(&secret_key);
+	Ok((secret_key, SecretKey::from(secret_key_bytes)))
+}
+
+/// Decrypt with an already derived x25519 secret key, so batch scanning only
+/// pays for the key agreement and the authentication check per output.
+pub(crate) fn decrypt_utxo_with(
+	encrypted: &[u8],
+	secret_key: &[u8],
+	encryption_secret: &SecretKey,
+) -> Result<JsUtxo, OperationError> {
+	if encrypted.len() < NONCE_LENGTH + KEY_SIZE {
+		return Err(decryption_failed("Encrypted output is too short"));
+	}
+
+	let (nonce, rest) = encrypted.split_at(NONCE_LENGTH);
+	let (ephemeral_key, ciphertext) = rest.split_at(KEY_SIZE);
+	let mut ephemeral_key_bytes = [0u8; KEY_SIZE];
+	ephemeral_key_bytes.copy_from_slice(ephemeral_key);
+
+	let envelope = SalsaBox::new(&PublicKey::from(ephemeral_key_bytes), encryption_secret)
+		.decrypt(nonce.into(), ciphertext)
+		.map_err(|_| decryption_failed("Encrypted output is not for this keypair"))?;
+	let plaintext = base64::decode(&envelope).map_err(|_| decryption_failed("Encrypted output is not base64"))?;
+	if plaintext.len() != UTXO_PLAINTEXT_LENGTH {
+		return Err(decryption_failed("Unexpected encrypted output length"));
+	}
+
+	let (chain_id, fields) = plaintext.split_at(8);
+	let (amount, blinding) = fields.split_at(UTXO_FIELD_LENGTH);
+	if amount[..UTXO_FIELD_LENGTH - 16].iter().any(|byte| *byte != 0) {
+		return Err(decryption_failed("Encrypted amount does not fit in a u128"));
+	}
+	let mut chain_id_bytes = [0u8; 8];
+	chain_id_bytes.copy_from_slice(chain_id);
+	let mut amount_bytes = [0u8; 16];
+	amount_bytes.copy_from_slice(&amount[UTXO_FIELD_LENGTH - 16..]);
+	let mut blinding_bytes = vec![0u8; 32 - UTXO_FIELD_LENGTH];
+	blinding_bytes.extend_from_slice(blinding);
+
+	JsUtxo::new(
+		Curve::Bn254,
+		Backend::Arkworks,
+		u128::from_be_bytes(amount_bytes),
+		u64::from_be_bytes(chain_id_bytes),
+		Some(blinding_bytes),
+		None,
+		Some(secret_key.to_vec()),
+		None,
+	)
+}
+
+/// Decrypt an encrypted output with the keypair it was sent to, see
+/// [`encrypt_utxo`]. The returned UTXO has no leaf index.
+pub fn decrypt_utxo(encrypted: &[u8], keypair: &JsKeypair) -> Result<JsUtxo, OperationError> {
+	let (secret_key, encryption_secret) = keypair_secret(keypair)?;
+	decrypt_utxo_with(encrypted, &secret_key, &encryption_secret)
+}
+
+#[wasm_bindgen(js_name = encryptUtxo)]
+pub fn js_encrypt_utxo(utxo: &JsUtxo, encryption_key: Uint8Array) -> Result<Uint8Array, JsValue> {
+	let encryption_key: [u8; KEY_SIZE] = encryption_key
+		.to_vec()
+		.try_into()
+		.map_err(|_| OpStatusCode::InvalidArrayLength)?;
+	let encrypted = encrypt_utxo(utxo, &PublicKey::from(encryption_key), &mut OsRng)?;
+	Ok(Uint8Array::from(encrypted.as_slice()))
+}
+
+#[wasm_bindgen(js_name = decryptUtxo)]
+pub fn js_decrypt_utxo(encrypted: Uint8Array, keypair: &JsKeypair) -> Result<JsUtxo, JsValue> {
+	Ok(decrypt_utxo(&encrypted.to_vec(), keypair)?)
+}
+
+#[cfg(test)]
+mod test {
+	use ark_bn254::Fr as Bn254Fr;
+	use ark_ff::PrimeField;
+	use wasm_bindgen_test::*;
+
+	use super::*;
+
+	#[wasm_bindgen_test]
+	fn should_encrypt_and_decrypt_utxo() {
+		let recipient = JsKeypair::new(&mut OsRng);
+		let utxo = JsUtxo::new(
+			Curve::Bn254,
+			Backend::Arkworks,
+			1_000,
+			0x0000_0100_0000_0005,
+			None,
+			None,
+			recipient.secret_key(),
+			None,
+		)
+		.unwrap();
+
+		let encrypted = encrypt_utxo(&utxo, &recipient.encryption_key(), &mut OsRng).unwrap();
+		assert_eq!(encrypted.len(), NONCE_LENGTH + KEY_SIZE + UTXO_ENVELOPE_LENGTH + 16);
+
+		let decrypted = decrypt_utxo(&encrypted, &recipient).unwrap();
+		assert_eq!(decrypted.get_commitment(), utxo.get_commitment());
+		assert_eq!(decrypted.get_chain_id_raw(), 0x0000_0100_0000_0005);
+		assert_eq!(decrypted.get_amount_raw(), 1_000);
+
+		let stranger = JsKeypair::new(&mut OsRng);
+		assert_eq!(
+			decrypt_utxo(&encrypted, &stranger).err().unwrap().code,
+			OpStatusCode::DecryptionFailed
+		);
+		let public_only: JsKeypair = recipient.to_string().parse().unwrap();
+		assert_eq!(
+			decrypt_utxo(&encrypted, &public_only).err().unwrap().code,
+			OpStatusCode::InvalidKeypair
+		);
+
+		// A blinding wider than 31 bytes can't be encrypted
+		let wide_blinding = JsUtxo::new(
+			Curve::Bn254,
+			Backend::Arkworks,
+			1_000,
+			5,
+			Some(vec![1u8; 32]),
+			None,
+			recipient.secret_key(),
+			None,
+		)
+		.unwrap();
+		assert_eq!(
+			encrypt_utxo(&wide_blinding, &recipient.encryption_key(), &mut OsRng)
+				.err()
+				.unwrap()
+				.code,
+			OpStatusCode::InvalidOutputUtxoConfig
+		);
+	}
+
+	#[wasm_bindgen_test]
+	fn should_decrypt_outputs_encrypted_by_protocol_solidity() {
+		let fixture: serde_json::Value =
+			serde_json::from_str(include_str!("test_vectors/utxo_encryption.json")).unwrap();
+		let bytes = |value: &serde_json::Value| hex::decode(value.as_str().unwrap().trim_start_matches("0x")).unwrap();
+		let keypair = JsKeypair::from_secret_key(Bn254Fr::from_be_bytes_mod_order(&bytes(&fixture["privkey"])));
+		assert_eq!(
+			keypair.encryption_key().as_bytes()[..],
+			bytes(&fixture["encryptionKey"])[..]
+		);
+
+		for vector in fixture["vectors"].as_array().unwrap() {
+			let encrypted = bytes(&vector["encrypted"]);
+			assert_eq!(encrypted.len(), NONCE_LENGTH + KEY_SIZE + UTXO_ENVELOPE_LENGTH + 16);
+			let utxo = decrypt_utxo(&encrypted, &keypair).unwrap();
+			assert_eq!(utxo.get_chain_id_bytes(), bytes(&vector["chainId"]));
+			assert_eq!(utxo.get_amount_raw().to_string(), vector["amount"].as_str().unwrap());
+			assert_eq!(utxo.get_blinding()[1..], bytes(&vector["blinding"])[..]);
+		}
+	}
+}
@@ -0,0 +1,185 @@
+//! Preview of the deposit of a note for confirmation screens, built from the
+//! same calldata and call arguments as the deposit itself so what the user
+//! confirms is byte for byte what they sign. No proof is involved, which
+//! leaves out the VAnchor: its deposits are proven `transact` calls, their
+//! preview only has the target and the commitment.
+use parity_scale_codec::Encode;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::address::to_checksum_address;
+use crate::evm::{anchor_deposit, anchor_wrap_and_deposit, ANCHOR_DEPOSIT, ANCHOR_WRAP_AND_DEPOSIT};
+use crate::note::JsNote;
+use crate::substrate::{deposit_args, DepositPallet};
+use crate::types::{IdentifyingData, NoteProtocol, OpStatusCode, OperationError};
+
+/// Substrate call a mixer deposit is made with
+const MIXER_PALLET_DEPOSIT: &str = "mixer.deposit";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DepositPreview {
+	pub protocol: String,
+	pub target_chain_id: String,
+	/// Checksummed anchor address on EVM chains, tree id on Substrate chains
+	/// and `0x` hex resource id otherwise
+	pub target: String,
+	/// `0x` hex leaf commitment
+	pub leaf: String,
+	pub token_symbol: Option<String>,
+	/// Amount in base units
+	pub amount: Option<String>,
+	pub denomination: Option<u8>,
+	pub display_amount: String,
+	/// Base asset wrapped on deposit, and the amount of it to approve
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub wrapped_token: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub wrapping_amount: Option<String>,
+	/// Contract function signature or pallet call of the deposit
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub call: Option<String>,
+	/// `0x` hex calldata of the contract call, or SCALE encoded arguments of
+	/// the pallet call
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub data: Option<String>,
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+	format!("0x{}", hex::encode(bytes))
+}
+
+pub fn preview_deposit(note: &JsNote) -> Result<DepositPreview, OperationError> {
+	let (_, target) = note.identifying_data()?;
+	let leaf = note.get_leaf_and_nullifier()?.commitment().to_vec();
+	let call = match (note.protocol, target) {
+		(NoteProtocol::Mixer, IdentifyingData::ContractAddress(_)) => match note.wrapped_token {
+			Some(_) => Some((ANCHOR_WRAP_AND_DEPOSIT, anchor_wrap_and_deposit(note)?)),
+			None => Some((ANCHOR_DEPOSIT, anchor_deposit(&leaf)?)),
+		},
+		(NoteProtocol::Mixer, IdentifyingData::TreeId(_)) => {
+			let args = deposit_args(note, DepositPallet::Mixer, None)?;
+			Some((MIXER_PALLET_DEPOSIT, args.encode()))
+		}
+		_ => None,
+	};
+	let wrapping_amount = match note.wrapped_token {
+		Some(_) => Some(
+			note.amount_value()?
+				.amount_to_wrap(note.wrapping_fee.unwrap_or_default())?,
+		),
+		None => None,
+	};
+	Ok(DepositPreview {
+		protocol: note.protocol.to_string(),
+		target_chain_id: note.target_chain_id.clone(),
+		target: match target {
+			IdentifyingData::ContractAddress(address) => to_checksum_address(&address),
+			IdentifyingData::TreeId(tree_id) => tree_id.to_string(),
+			IdentifyingData::Resource(resource_id) => to_hex(&resource_id),
+		},
+		leaf: to_hex(&leaf),
+		token_symbol: note.token_symbol.clone(),
+		amount: note.amount.clone(),
+		denomination: note.denomination,
+		display_amount: note.display_amount()?,
+		wrapped_token: note.wrapped_token.clone(),
+		wrapping_amount: wrapping_amount.map(|amount| amount.to_string()),
+		call: call.as_ref().map(|(call, _)| call.to_string()),
+		data: call.map(|(_, data)| to_hex(&data)),
+	})
+}
+
+#[wasm_bindgen(typescript_custom_section)]
+const DEPOSIT_PREVIEW: &str = r#"
+export interface DepositPreview {
+	protocol: NoteProtocol;
+	targetChainId: string;
+	target: string;
+	leaf: string;
+	tokenSymbol?: string;
+	amount?: string;
+	denomination?: number;
+	displayAmount: string;
+	wrappedToken?: string;
+	wrappingAmount?: string;
+	call?: string;
+	data?: string;
+}
+"#;
+
+#[wasm_bindgen]
+extern "C" {
+	#[wasm_bindgen(typescript_type = "DepositPreview")]
+	pub type WasmDepositPreview;
+}
+
+/// What depositing `note` sends where, see `DepositPreview`. The `data` is
+/// the calldata of the anchor call on EVM chains and the SCALE encoded
+/// `mixer.deposit` arguments on Substrate chains
+#[wasm_bindgen(js_name = previewDeposit)]
+pub fn js_preview_deposit(note: &JsNote) -> Result<WasmDepositPreview, JsValue> {
+	let preview = JsValue::from_serde(&preview_deposit(note)?)
+		.map_err(|e| OperationError::new_with_message(OpStatusCode::SerializationFailed, e.to_string()))?;
+	Ok(preview.unchecked_into())
+}
+
+#[cfg(test)]
+mod test {
+	use wasm_bindgen_test::*;
+
+	use super::*;
+	use crate::address::parse_evm_address;
+	use crate::evm::selector;
+	use crate::proof::test_utils::{MIXER_NOTE_V1_X5_5, VANCHOR_NOTE_V1_X5_4};
+	use crate::types::{ChainType, TypedChainId};
+
+	const ANCHOR: &str = "0x8eb24319393716668d768dcec29356ae9cffe285";
+	const TOKEN: &str = "0x7a250d5630b4cf539739df2c5dacb4c659f2488d";
+
+	fn evm_note(misc: &str) -> JsNote {
+		let chain_id = TypedChainId::new(ChainType::Evm, 5).to_string();
+		let note = MIXER_NOTE_V1_X5_5
+			.replacen("2:2/2:2", &format!("{0}:{0}/{1}:{1}", chain_id, ANCHOR), 1)
+			.replace("&index=10", misc);
+		JsNote::deserialize(&note).unwrap()
+	}
+
+	#[wasm_bindgen_test]
+	fn should_preview_evm_deposits() {
+		let note = evm_note("");
+		let preview = preview_deposit(&note).unwrap();
+		let leaf = note.get_leaf_commitment().unwrap().to_vec();
+		let checksummed = |address: &str| to_checksum_address(&parse_evm_address(address).unwrap());
+		assert_eq!(preview.target, checksummed(ANCHOR));
+		assert_eq!(preview.leaf, to_hex(&leaf));
+		assert_eq!(preview.call.as_deref(), Some(ANCHOR_DEPOSIT));
+		let data = [selector(ANCHOR_DEPOSIT).to_vec(), leaf].concat();
+		assert_eq!(preview.data, Some(to_hex(&data)));
+		assert_eq!(preview.display_amount, "0.00000000000000001 EDG");
+		assert_eq!(preview.wrapping_amount, None);
+
+		let note = evm_note(&format!("&wrappedToken={}&wrappingFee=1", TOKEN));
+		let preview = preview_deposit(&note).unwrap();
+		assert_eq!(preview.call.as_deref(), Some(ANCHOR_WRAP_AND_DEPOSIT));
+		assert_eq!(preview.data, Some(to_hex(&anchor_wrap_and_deposit(&note).unwrap())));
+		assert_eq!(preview.wrapped_token, Some(checksummed(TOKEN)));
+		assert!(preview.wrapping_amount.is_some());
+	}
+
+	#[wasm_bindgen_test]
+	fn should_preview_substrate_and_vanchor_deposits() {
+		let note = JsNote::deserialize(&MIXER_NOTE_V1_X5_5.replacen("2:2/2:2", "2:2/2:7", 1)).unwrap();
+		let preview = preview_deposit(&note).unwrap();
+		let args = deposit_args(&note, DepositPallet::Mixer, None).unwrap();
+		assert_eq!(preview.target, "7");
+		assert_eq!(preview.call.as_deref(), Some(MIXER_PALLET_DEPOSIT));
+		assert_eq!(preview.data, Some(to_hex(&args.encode())));
+
+		let note = JsNote::deserialize(VANCHOR_NOTE_V1_X5_4).unwrap();
+		let preview = preview_deposit(&note).unwrap();
+		assert_eq!(preview.leaf, to_hex(&note.get_leaf_commitment().unwrap().to_vec()));
+		assert_eq!((preview.call, preview.data), (None, None));
+	}
+}
@@ -0,0 +1,162 @@
+//! Entropy of the host mixed into the RNG of note secrets, for wallets whose
+//! policy requires secrets derived from a hardware RNG. Once a source is set
+//! with `setEntropySource`, every generation of secrets asks it for 32 bytes
+//! and seeds ChaCha20 with
+//!
+//! `blake2b-256(os entropy (32) || host entropy (32))`
+//!
+//! so the secrets are as unpredictable as the better of the two sources, a
+//! weak or hostile source can't make them worse than `OsRng` alone. A source
+//! that fails or returns anything but 32 bytes fails the generation rather
+//! than falling back to `OsRng` silently.
+use std::cell::RefCell;
+
+use blake2::digest::{Update, VariableOutput};
+use blake2::VarBlake2b;
+use js_sys::{Function, Uint8Array};
+use rand::rngs::OsRng;
+use rand::{CryptoRng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::types::{OpStatusCode, OperationError};
+
+/// Bytes of entropy asked of the host for every generation
+pub const HOST_ENTROPY_LENGTH: usize = 32;
+
+thread_local! {
+	static SOURCE: RefCell<Option<Function>> = RefCell::new(None);
+}
+
+fn secret_gen_failed(message: String) -> OperationError {
+	OperationError::new_with_message(OpStatusCode::SecretGenFailed, message)
+}
+
+fn host_entropy(source: &Function) -> Result<[u8; HOST_ENTROPY_LENGTH], OperationError> {
+	let entropy = source
+		.call1(&JsValue::NULL, &JsValue::from(HOST_ENTROPY_LENGTH as u32))
+		.map_err(|e| secret_gen_failed(format!("The entropy source failed: {:?}", e)))?;
+	let entropy = entropy
+		.dyn_into::<Uint8Array>()
+		.map_err(|_| secret_gen_failed("The entropy source didn't return a Uint8Array".to_string()))?
+		.to_vec();
+	entropy.as_slice().try_into().map_err(|_| {
+		secret_gen_failed(format!(
+			"The entropy source returned {} bytes instead of {}",
+			entropy.len(),
+			HOST_ENTROPY_LENGTH
+		))
+	})
+}
+
+/// Seed of `SecretRng` from OS and host entropy
+pub fn mix_entropy(os_entropy: &[u8], host_entropy: &[u8]) -> [u8; 32] {
+	let mut hasher = VarBlake2b::new(32).expect("32 bytes is a valid Blake2b output size");
+	hasher.update(os_entropy);
+	hasher.update(host_entropy);
+	let mut seed = [0u8; 32];
+	hasher.finalize_variable(|hash| seed.copy_from_slice(hash));
+	seed
+}
+
+/// RNG of note secrets, `OsRng` unless the host set an entropy source
+pub enum SecretRng {
+	Os(OsRng),
+	Mixed(Box<ChaCha20Rng>),
+}
+
+impl SecretRng {
+	pub fn new() -> Result<Self, OperationError> {
+		let source = SOURCE.with(|source| source.borrow().clone());
+		let source = match source {
+			Some(source) => source,
+			None => return Ok(SecretRng::Os(OsRng)),
+		};
+		let mut os_entropy = [0u8; 32];
+		OsRng.fill_bytes(&mut os_entropy);
+		let seed = mix_entropy(&os_entropy, &host_entropy(&source)?);
+		Ok(SecretRng::Mixed(Box::new(ChaCha20Rng::from_seed(seed))))
+	}
+}
+
+impl RngCore for SecretRng {
+	fn next_u32(&mut self) -> u32 {
+		match self {
+			SecretRng::Os(rng) => rng.next_u32(),
+			SecretRng::Mixed(rng) => rng.next_u32(),
+		}
+	}
+
+	fn next_u64(&mut self) -> u64 {
+		match self {
+			SecretRng::Os(rng) => rng.next_u64(),
+			SecretRng::Mixed(rng) => rng.next_u64(),
+		}
+	}
+
+	fn fill_bytes(&mut self, dest: &mut [u8]) {
+		match self {
+			SecretRng::Os(rng) => rng.fill_bytes(dest),
+			SecretRng::Mixed(rng) => rng.fill_bytes(dest),
+		}
+	}
+
+	fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+		match self {
+			SecretRng::Os(rng) => rng.try_fill_bytes(dest),
+			SecretRng::Mixed(rng) => rng.try_fill_bytes(dest),
+		}
+	}
+}
+
+impl CryptoRng for SecretRng {}
+
+#[wasm_bindgen]
+extern "C" {
+	#[wasm_bindgen(typescript_type = "(length: number) => Uint8Array")]
+	pub type EntropySource;
+}
+
+/// Mix the bytes `source(32)` returns into the RNG of every note secret,
+/// keypair and UTXO generated from now on, or stop when not given. The source
+/// is called synchronously, once per generation
+#[wasm_bindgen(js_name = setEntropySource)]
+pub fn set_entropy_source(source: Option<EntropySource>) {
+	let source = source.map(|source| source.unchecked_into::<Function>());
+	SOURCE.with(|current| *current.borrow_mut() = source);
+}
+
+#[cfg(test)]
+mod test {
+	use wasm_bindgen_test::*;
+
+	use super::*;
+
+	fn set_source(body: &str) {
+		let source = Function::new_with_args("length", body);
+		set_entropy_source(Some(JsValue::from(source).unchecked_into()));
+	}
+
+	#[wasm_bindgen_test]
+	fn should_mix_host_entropy() {
+		assert_ne!(mix_entropy(&[1; 32], &[2; 32]), mix_entropy(&[1; 32], &[3; 32]));
+		assert!(matches!(SecretRng::new().unwrap(), SecretRng::Os(_)));
+
+		set_source("globalThis.entropyCalls = (globalThis.entropyCalls || 0) + 1; return new Uint8Array(length)");
+		let mut rng = SecretRng::new().unwrap();
+		assert!(matches!(rng, SecretRng::Mixed(_)));
+		// A constant source still gives fresh secrets
+		assert_ne!(rng.next_u64(), SecretRng::new().unwrap().next_u64());
+		let calls = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("entropyCalls")).unwrap();
+		assert_eq!(calls.as_f64(), Some(2.0));
+
+		// A failing source fails the generation
+		set_source("return new Uint8Array(16)");
+		assert_eq!(SecretRng::new().err().unwrap().code, OpStatusCode::SecretGenFailed);
+		set_source("throw new Error('HSM unavailable')");
+		assert_eq!(SecretRng::new().err().unwrap().code, OpStatusCode::SecretGenFailed);
+		set_entropy_source(None);
+		assert!(matches!(SecretRng::new().unwrap(), SecretRng::Os(_)));
+	}
+}
@@ -0,0 +1,189 @@
+//! Leaves of the deposit events, for light clients that build the leaf set
+//! of a tree from raw logs rather than from an indexer. The EVM anchors emit
+//!
+//! `Deposit(bytes32 indexed commitment, uint32 leafIndex, uint256 timestamp)`
+//!
+//! or `Insertion` with the same fields, the Substrate pallets insert their
+//! leaves through the merkle tree pallet which emits
+//!
+//! `LeafInsertion { tree_id: u32, leaf_index: u32, leaf: Element }`
+//!
+//! for the mixer and anchor deposits alike.
+use core::convert::TryInto;
+
+use ethabi::{decode, ParamType};
+use js_sys::{Array, Uint8Array};
+use parity_scale_codec::{Decode, Encode};
+use wasm_bindgen::prelude::*;
+
+use crate::evm::keccak256;
+use crate::types::{Leaves, OpStatusCode, OperationError};
+
+pub const EVM_DEPOSIT_EVENT: &str = "Deposit(bytes32,uint32,uint256)";
+pub const EVM_INSERTION_EVENT: &str = "Insertion(bytes32,uint32,uint256)";
+
+fn invalid_event(message: String) -> OperationError {
+	OperationError::new_with_message(OpStatusCode::InvalidEvent, message)
+}
+
+/// Fields of the merkle tree pallet's `LeafInsertion` event, in order
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LeafInsertion {
+	pub tree_id: u32,
+	pub leaf_index: u32,
+	pub leaf: [u8; 32],
+}
+
+/// A leaf inserted by a deposit
+#[wasm_bindgen]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DepositEvent {
+	#[wasm_bindgen(skip)]
+	pub leaf: [u8; 32],
+	#[wasm_bindgen(skip)]
+	pub leaf_index: u32,
+	/// Tree of a Substrate deposit, EVM logs come from the contract's tree
+	#[wasm_bindgen(skip)]
+	pub tree_id: Option<u32>,
+}
+
+/// Decode the `Deposit` or `Insertion` log of an EVM anchor from its topics
+/// and data
+pub fn decode_evm_deposit(topics: &[Vec<u8>], data: &[u8]) -> Result<DepositEvent, OperationError> {
+	let signature = topics
+		.first()
+		.ok_or_else(|| invalid_event("The log has no topics".to_string()))?;
+	if *signature != keccak256(EVM_DEPOSIT_EVENT.as_bytes()) && *signature != keccak256(EVM_INSERTION_EVENT.as_bytes())
+	{
+		return Err(invalid_event(format!(
+			"The log 0x{} isn't a deposit",
+			hex::encode(signature)
+		)));
+	}
+	let leaf: [u8; 32] = match topics {
+		[_, leaf] => leaf.as_slice().try_into().ok(),
+		_ => None,
+	}
+	.ok_or_else(|| invalid_event(format!("A deposit log has 2 topics of 32 bytes, not {}", topics.len())))?;
+	// `leafIndex` and `timestamp`, the index being a `uint32`
+	let leaf_index = decode(&[ParamType::Uint(32), ParamType::Uint(256)], data)
+		.ok()
+		.and_then(|tokens| tokens[0].clone().into_uint())
+		.filter(|index| data.len() == 64 && index.bits() <= 32)
+		.ok_or_else(|| invalid_event(format!("A deposit log has 64 bytes of data, not {}", data.len())))?
+		.as_u32();
+	Ok(DepositEvent {
+		leaf,
+		leaf_index,
+		tree_id: None,
+	})
+}
+
+/// Decode the SCALE encoded fields of a `LeafInsertion` event, as given by
+/// `event.data.toU8a()`
+pub fn decode_substrate_deposit(data: &[u8]) -> Result<DepositEvent, OperationError> {
+	let mut input = data;
+	let event = LeafInsertion::decode(&mut input)
+		.ok()
+		.filter(|_| input.is_empty())
+		.ok_or_else(|| invalid_event(format!("A LeafInsertion event is 40 bytes, not {}", data.len())))?;
+	Ok(DepositEvent {
+		leaf: event.leaf,
+		leaf_index: event.leaf_index,
+		tree_id: Some(event.tree_id),
+	})
+}
+
+#[allow(clippy::unused_unit)]
+#[wasm_bindgen]
+impl DepositEvent {
+	/// Decode an EVM deposit log, `topics` being the 32 bytes topics
+	#[wasm_bindgen(js_name = fromEvmLog)]
+	pub fn from_evm_log(topics: Leaves, data: Uint8Array) -> Result<DepositEvent, JsValue> {
+		let topics: Vec<Vec<u8>> = Array::from(&topics)
+			.iter()
+			.map(|topic| Uint8Array::new(&topic).to_vec())
+			.collect();
+		Ok(decode_evm_deposit(&topics, &data.to_vec())?)
+	}
+
+	/// Decode the fields of a Substrate `LeafInsertion` event
+	#[wasm_bindgen(js_name = fromSubstrateEvent)]
+	pub fn from_substrate_event(data: Uint8Array) -> Result<DepositEvent, JsValue> {
+		Ok(decode_substrate_deposit(&data.to_vec())?)
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn leaf(&self) -> Uint8Array {
+		Uint8Array::from(self.leaf.as_slice())
+	}
+
+	#[wasm_bindgen(getter, js_name = leafIndex)]
+	pub fn leaf_index(&self) -> u32 {
+		self.leaf_index
+	}
+
+	#[wasm_bindgen(getter, js_name = treeId)]
+	pub fn tree_id(&self) -> Option<u32> {
+		self.tree_id
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use ethabi::{encode, Token, Uint};
+	use wasm_bindgen_test::*;
+
+	use super::*;
+
+	fn evm_log(event: &str, leaf: [u8; 32], leaf_index: u32) -> (Vec<Vec<u8>>, Vec<u8>) {
+		let topics = vec![keccak256(event.as_bytes()).to_vec(), leaf.to_vec()];
+		let data = encode(&[
+			Token::Uint(Uint::from(leaf_index)),
+			Token::Uint(Uint::from(1_650_000_000u64)),
+		]);
+		(topics, data)
+	}
+
+	#[wasm_bindgen_test]
+	fn should_decode_evm_deposit_logs() {
+		for event in [EVM_DEPOSIT_EVENT, EVM_INSERTION_EVENT] {
+			let (topics, data) = evm_log(event, [5u8; 32], 7);
+			let deposit = decode_evm_deposit(&topics, &data).unwrap();
+			assert_eq!(deposit, DepositEvent {
+				leaf: [5u8; 32],
+				leaf_index: 7,
+				tree_id: None
+			});
+		}
+
+		let (topics, data) = evm_log("Withdrawal(address,bytes32,address,uint256)", [5u8; 32], 7);
+		let error = decode_evm_deposit(&topics, &data).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::InvalidEvent);
+		let (topics, data) = evm_log(EVM_DEPOSIT_EVENT, [5u8; 32], 7);
+		assert!(decode_evm_deposit(&topics[..1], &data).is_err());
+		assert!(decode_evm_deposit(&topics, &data[..32]).is_err());
+		let mut large_index = data;
+		large_index[27] = 1;
+		assert!(decode_evm_deposit(&topics, &large_index).is_err());
+	}
+
+	#[wasm_bindgen_test]
+	fn should_decode_substrate_leaf_insertions() {
+		let event = LeafInsertion {
+			tree_id: 3,
+			leaf_index: 9,
+			leaf: [6u8; 32],
+		};
+		let data = event.encode();
+		assert_eq!(&data[..8], [3, 0, 0, 0, 9, 0, 0, 0]);
+		assert_eq!(decode_substrate_deposit(&data).unwrap(), DepositEvent {
+			leaf: [6u8; 32],
+			leaf_index: 9,
+			tree_id: Some(3)
+		});
+		let error = decode_substrate_deposit(&data[..39]).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::InvalidEvent);
+		assert!(decode_substrate_deposit(&[data.as_slice(), &[0]].concat()).is_err());
+	}
+}
@@ -0,0 +1,313 @@
+//! ABI encoded calldata of the contract calls made with a note or a proof,
+//! function selector included, to be sent as the `data` of a transaction
+//! through any provider.
+//!
+//! The anchor proxy is the Tornado style proxy in front of the fixed amount
+//! anchors, the VAnchor takes deposits, transfers and withdrawals alike
+//! through `transact`. Anchors of a wrapped bridge token also take the base
+//! asset with `wrapAndDeposit` and pay it back with `withdrawAndUnwrap`, the
+//! token wrapper keeping its fee on the way in.
+use core::convert::TryInto;
+
+use ethabi::{encode, Address, Token, Uint};
+use js_sys::{JsString, Uint8Array};
+use tiny_keccak::{Hasher, Keccak};
+use wasm_bindgen::prelude::*;
+
+use crate::address::parse_evm_address;
+use crate::note::amount::parse_amount_field;
+use crate::note::JsNote;
+use crate::proof::ext_data::ExtData;
+use crate::proof::groth16::JsGroth16Proof;
+use crate::proof::mixer::MixerProof;
+use crate::proof::vanchor::VAnchorProof;
+use crate::types::{Curve, OpStatusCode, OperationError};
+
+pub const ANCHOR_DEPOSIT: &str = "deposit(bytes32)";
+pub const ANCHOR_PROXY_DEPOSIT: &str = "deposit(address,bytes32,bytes)";
+pub const ANCHOR_PROXY_WITHDRAW: &str = "withdraw(address,bytes,bytes32,bytes32,address,address,uint256,uint256)";
+pub const ANCHOR_WRAP_AND_DEPOSIT: &str = "wrapAndDeposit(address,bytes32)";
+pub const ANCHOR_WITHDRAW_AND_UNWRAP: &str =
+	"withdrawAndUnwrap(bytes,bytes32,bytes32,address,address,uint256,uint256,address)";
+/// `transact(Proof, ExtData)`, the tuples being the Solidity structs
+pub const VANCHOR_TRANSACT: &str = "transact((bytes,bytes,bytes32[],bytes32[2],uint256,bytes32),(address,int256,address,uint256,uint256,address,bytes,bytes))";
+
+pub fn keccak256(bytes: &[u8]) -> [u8; 32] {
+	let mut keccak = Keccak::v256();
+	keccak.update(bytes);
+	let mut hash = [0u8; 32];
+	keccak.finalize(&mut hash);
+	hash
+}
+
+/// First 4 bytes of the `keccak256` of the function signature
+pub fn selector(signature: &str) -> [u8; 4] {
+	keccak256(signature.as_bytes())[..4].try_into().unwrap()
+}
+
+pub fn calldata(signature: &str, args: &[Token]) -> Vec<u8> {
+	let mut calldata = selector(signature).to_vec();
+	calldata.extend(encode(args));
+	calldata
+}
+
+fn bytes32(bytes: &[u8], code: OpStatusCode) -> Result<Token, OperationError> {
+	if bytes.len() != 32 {
+		let message = format!("Expected 32 bytes, got {} bytes", bytes.len());
+		return Err(OperationError::new_with_message(code, message));
+	}
+	Ok(Token::FixedBytes(bytes.to_vec()))
+}
+
+fn evm_proof(proof: &[u8]) -> Result<Token, OperationError> {
+	let proof = JsGroth16Proof::from_compressed(Curve::Bn254, proof)?;
+	Ok(Token::Bytes(proof.to_evm_bytes()?))
+}
+
+/// `deposit` call of a commitment made to the anchor itself
+pub fn anchor_deposit(commitment: &[u8]) -> Result<Vec<u8>, OperationError> {
+	let args = [bytes32(commitment, OpStatusCode::InvalidLeaves)?];
+	Ok(calldata(ANCHOR_DEPOSIT, &args))
+}
+
+pub fn anchor_proxy_deposit(
+	anchor: &[u8; 20],
+	commitment: &[u8],
+	encrypted_note: &[u8],
+) -> Result<Vec<u8>, OperationError> {
+	let args = [
+		Token::Address(Address::from(anchor)),
+		bytes32(commitment, OpStatusCode::InvalidLeaves)?,
+		Token::Bytes(encrypted_note.to_vec()),
+	];
+	Ok(calldata(ANCHOR_PROXY_DEPOSIT, &args))
+}
+
+pub fn anchor_proxy_withdraw(
+	anchor: &[u8; 20],
+	proof: &MixerProof,
+	recipient: &[u8; 20],
+	relayer: &[u8; 20],
+	fee: u128,
+	refund: u128,
+) -> Result<Vec<u8>, OperationError> {
+	let args = [
+		Token::Address(Address::from(anchor)),
+		evm_proof(&proof.proof)?,
+		bytes32(&proof.root, OpStatusCode::InvalidProof)?,
+		bytes32(&proof.nullifier_hash, OpStatusCode::InvalidProof)?,
+		Token::Address(Address::from(recipient)),
+		Token::Address(Address::from(relayer)),
+		Token::Uint(Uint::from(fee)),
+		Token::Uint(Uint::from(refund)),
+	];
+	Ok(calldata(ANCHOR_PROXY_WITHDRAW, &args))
+}
+
+/// `wrapAndDeposit` call of a note wrapping its base asset into the bridge
+/// token, made to the anchor itself
+pub fn anchor_wrap_and_deposit(note: &JsNote) -> Result<Vec<u8>, OperationError> {
+	let token = note.wrapped_token.as_deref().ok_or_else(|| {
+		let message = "The note doesn't wrap a base asset".to_string();
+		OperationError::new_with_message(OpStatusCode::InvalidAddress, message)
+	})?;
+	let leaf = note.get_leaf_and_nullifier()?.commitment().to_vec();
+	let args = [
+		Token::Address(Address::from(parse_evm_address(token)?)),
+		bytes32(&leaf, OpStatusCode::InvalidLeaves)?,
+	];
+	Ok(calldata(ANCHOR_WRAP_AND_DEPOSIT, &args))
+}
+
+/// `withdrawAndUnwrap` call of a mixer proof, paying `token` out of the
+/// bridge token
+pub fn anchor_withdraw_and_unwrap(
+	proof: &MixerProof,
+	recipient: &[u8; 20],
+	relayer: &[u8; 20],
+	fee: u128,
+	refund: u128,
+	token: &[u8; 20],
+) -> Result<Vec<u8>, OperationError> {
+	let args = [
+		evm_proof(&proof.proof)?,
+		bytes32(&proof.root, OpStatusCode::InvalidProof)?,
+		bytes32(&proof.nullifier_hash, OpStatusCode::InvalidProof)?,
+		Token::Address(Address::from(recipient)),
+		Token::Address(Address::from(relayer)),
+		Token::Uint(Uint::from(fee)),
+		Token::Uint(Uint::from(refund)),
+		Token::Address(Address::from(token)),
+	];
+	Ok(calldata(ANCHOR_WITHDRAW_AND_UNWRAP, &args))
+}
+
+/// `transact` call of a VAnchor proof. The public inputs are laid out as
+/// `publicAmount, extDataHash, nullifiers, commitments (2), chainId, roots`.
+pub fn vanchor_transact(proof: &VAnchorProof, ext_data: &ExtData) -> Result<Vec<u8>, OperationError> {
+	let ins = proof.input_utxos.len();
+	let inputs = &proof.public_inputs;
+	if inputs.len() < ins + 6 {
+		let message = format!(
+			"Expected at least {} public inputs for {} inputs, got {}",
+			ins + 6,
+			ins,
+			inputs.len()
+		);
+		return Err(OperationError::new_with_message(OpStatusCode::InvalidProof, message));
+	}
+	let word = |input: &Vec<u8>| bytes32(input, OpStatusCode::InvalidProof);
+	let nullifiers = inputs[2..2 + ins].iter().map(word).collect::<Result<_, _>>()?;
+	let commitments = inputs[2 + ins..4 + ins].iter().map(word).collect::<Result<_, _>>()?;
+	let roots = inputs[5 + ins..].concat();
+
+	let proof_args = vec![
+		evm_proof(&proof.proof)?,
+		Token::Bytes(roots),
+		Token::Array(nullifiers),
+		Token::FixedArray(commitments),
+		Token::Uint(Uint::from_big_endian(&inputs[0])),
+		word(&inputs[1])?,
+	];
+	let args = [Token::Tuple(proof_args), ext_data.evm_abi_token()?];
+	Ok(calldata(VANCHOR_TRANSACT, &args))
+}
+
+fn to_hex(calldata: Vec<u8>) -> JsString {
+	format!("0x{}", hex::encode(calldata)).into()
+}
+
+/// Calldata of `AnchorProxy.deposit(anchor, commitment, encryptedNote)`
+#[wasm_bindgen(js_name = anchorProxyDepositCalldata)]
+pub fn js_anchor_proxy_deposit(
+	anchor: JsString,
+	commitment: Uint8Array,
+	encrypted_note: Option<Uint8Array>,
+) -> Result<JsString, JsValue> {
+	let anchor = parse_evm_address(&String::from(anchor))?;
+	let encrypted_note = encrypted_note.map(|note| note.to_vec()).unwrap_or_default();
+	Ok(to_hex(anchor_proxy_deposit(
+		&anchor,
+		&commitment.to_vec(),
+		&encrypted_note,
+	)?))
+}
+
+/// Calldata of `AnchorProxy.withdraw` for a mixer proof, the recipient,
+/// relayer, fee and refund have to be the ones the proof was generated with
+#[wasm_bindgen(js_name = anchorProxyWithdrawCalldata)]
+pub fn js_anchor_proxy_withdraw(
+	anchor: JsString,
+	proof: &MixerProof,
+	recipient: JsString,
+	relayer: JsString,
+	fee: JsString,
+	refund: JsString,
+) -> Result<JsString, JsValue> {
+	let anchor = parse_evm_address(&String::from(anchor))?;
+	let recipient = parse_evm_address(&String::from(recipient))?;
+	let relayer = parse_evm_address(&String::from(relayer))?;
+	let fee = parse_amount_field(&String::from(fee), None, OpStatusCode::InvalidFee)?;
+	let refund = parse_amount_field(&String::from(refund), None, OpStatusCode::InvalidRefund)?;
+	let calldata = anchor_proxy_withdraw(&anchor, proof, &recipient, &relayer, fee, refund)?;
+	Ok(to_hex(calldata))
+}
+
+/// Calldata of `Anchor.wrapAndDeposit` for a note with a wrapped token, the
+/// anchor has to be approved for `note.wrappingAmount()` of that token
+#[wasm_bindgen(js_name = anchorWrapAndDepositCalldata)]
+pub fn js_anchor_wrap_and_deposit(note: &JsNote) -> Result<JsString, JsValue> {
+	Ok(to_hex(anchor_wrap_and_deposit(note)?))
+}
+
+/// Calldata of `Anchor.withdrawAndUnwrap` for a mixer proof, paying the
+/// recipient in `token` rather than in the bridge token
+#[wasm_bindgen(js_name = anchorWithdrawAndUnwrapCalldata)]
+pub fn js_anchor_withdraw_and_unwrap(
+	proof: &MixerProof,
+	recipient: JsString,
+	relayer: JsString,
+	fee: JsString,
+	refund: JsString,
+	token: JsString,
+) -> Result<JsString, JsValue> {
+	let recipient = parse_evm_address(&String::from(recipient))?;
+	let relayer = parse_evm_address(&String::from(relayer))?;
+	let fee = parse_amount_field(&String::from(fee), None, OpStatusCode::InvalidFee)?;
+	let refund = parse_amount_field(&String::from(refund), None, OpStatusCode::InvalidRefund)?;
+	let token = parse_evm_address(&String::from(token))?;
+	let calldata = anchor_withdraw_and_unwrap(proof, &recipient, &relayer, fee, refund, &token)?;
+	Ok(to_hex(calldata))
+}
+
+/// Calldata of `VAnchor.transact` for a proof and the ext data it commits to
+#[wasm_bindgen(js_name = vanchorTransactCalldata)]
+pub fn js_vanchor_transact(proof: &VAnchorProof, ext_data: &ExtData) -> Result<JsString, JsValue> {
+	Ok(to_hex(vanchor_transact(proof, ext_data)?))
+}
+
+#[cfg(test)]
+mod test {
+	use ethabi::{decode, ParamType};
+	use wasm_bindgen_test::*;
+
+	use super::*;
+
+	#[wasm_bindgen_test]
+	fn should_compute_selectors() {
+		// ERC-20 `transfer`, as a known value
+		assert_eq!(hex::encode(selector("transfer(address,uint256)")), "a9059cbb");
+		// Tornado proxy `deposit`
+		assert_eq!(hex::encode(selector(ANCHOR_PROXY_DEPOSIT)), "13d98d13");
+	}
+
+	#[wasm_bindgen_test]
+	fn should_encode_anchor_proxy_deposit() {
+		let anchor = [1u8; 20];
+		let calldata = anchor_proxy_deposit(&anchor, &[2u8; 32], &[3u8; 5]).unwrap();
+		assert_eq!(calldata[..4], selector(ANCHOR_PROXY_DEPOSIT));
+
+		let types = [ParamType::Address, ParamType::FixedBytes(32), ParamType::Bytes];
+		let args = decode(&types, &calldata[4..]).unwrap();
+		assert_eq!(args[0], Token::Address(Address::from(&anchor)));
+		assert_eq!(args[1], Token::FixedBytes(vec![2u8; 32]));
+		assert_eq!(args[2], Token::Bytes(vec![3u8; 5]));
+
+		let error = anchor_proxy_deposit(&anchor, &[2u8; 31], &[]).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::InvalidLeaves);
+	}
+
+	#[wasm_bindgen_test]
+	fn should_encode_wrapped_anchor_calls() {
+		let token = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+		let note = format!(
+			"webb://v1:mixer/2:2/2:2/fd717cfe463b3ffec71ee6b7606bbd0179170510abf41c9f16c1d20ca9923f0e:18b6b080e6a43262f00f6fb3da0d2409c4871b8f26d89d5c8836358e1af5a41c/?curve=Bn254&width=3&exp=5&hf=Poseidon&backend=Arkworks&token=EDG&denom=18&amount=99&wrappedToken={}&wrappingFee=1",
+			token.to_lowercase()
+		);
+		let note = JsNote::deserialize(&note).unwrap();
+		assert_eq!(note.wrapped_token.as_deref(), Some(token));
+		assert!(note
+			.to_string()
+			.ends_with(&format!("&wrappedToken={}&wrappingFee=1", token)));
+		assert_eq!(note.wrapping_amount().unwrap().inner, 100);
+
+		let calldata = anchor_wrap_and_deposit(&note).unwrap();
+		assert_eq!(calldata[..4], selector(ANCHOR_WRAP_AND_DEPOSIT));
+		let args = decode(&[ParamType::Address, ParamType::FixedBytes(32)], &calldata[4..]).unwrap();
+		assert_eq!(
+			args[0],
+			Token::Address(Address::from(parse_evm_address(token).unwrap()))
+		);
+		assert_eq!(args[1], Token::FixedBytes(note.get_leaf_commitment().unwrap().to_vec()));
+		let unwrapped = JsNote {
+			wrapped_token: None,
+			..note
+		};
+		let error = anchor_wrap_and_deposit(&unwrapped).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::InvalidAddress);
+
+		let error =
+			JsNote::deserialize(&unwrapped.to_string().replace("wrappingFee=1", "wrappingFee=100")).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::InvalidFee);
+	}
+}
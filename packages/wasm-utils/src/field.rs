@@ -0,0 +1,388 @@
+//! Scalar field elements of the supported curves, as 32 bytes big endian
+//! unless said otherwise.
+//!
+//! EVM tooling writes field elements as big endian decimal or hex, arkworks
+//! serializes them little endian and keeps them in Montgomery form in
+//! memory. The codec converts between all of them and rejects values at or
+//! above the modulus instead of reducing them, so bytes in the wrong order
+//! are mostly caught rather than read as another element.
+use ark_bls12_381::Fr as Bls381Fr;
+use ark_bn254::Fr as Bn254Fr;
+use ark_ff::{BigInteger, FpParameters, PrimeField};
+use js_sys::{JsString, Uint8Array};
+use num_bigint::BigUint;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use wasm_bindgen::prelude::*;
+
+use crate::proof::groth16::parse_curve;
+use crate::types::{Curve, OpStatusCode, OperationError, WasmCurve};
+
+pub const FIELD_ELEMENT_SIZE: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endianness {
+	Little,
+	Big,
+}
+
+impl Endianness {
+	fn of_js(little_endian: Option<bool>) -> Self {
+		if little_endian.unwrap_or(false) {
+			Endianness::Little
+		} else {
+			Endianness::Big
+		}
+	}
+}
+
+fn invalid_field_element(message: String) -> OperationError {
+	OperationError::new_with_message(OpStatusCode::InvalidFieldElement, message)
+}
+
+/// Read an element of `F` from its 32 bytes in `endianness` order
+pub fn field_element_from_bytes<F: PrimeField>(bytes: &[u8], endianness: Endianness) -> Result<F, OperationError> {
+	if bytes.len() != FIELD_ELEMENT_SIZE {
+		return Err(invalid_field_element(format!(
+			"Field elements are {} bytes, not {}",
+			FIELD_ELEMENT_SIZE,
+			bytes.len()
+		)));
+	}
+	let mut be_bytes = bytes.to_vec();
+	if endianness == Endianness::Little {
+		be_bytes.reverse();
+	}
+	if !is_canonical::<F>(&be_bytes) {
+		let message = format!("0x{} is above the field modulus", hex::encode(&be_bytes));
+		return Err(invalid_field_element(message));
+	}
+	Ok(F::from_be_bytes_mod_order(&be_bytes))
+}
+
+pub fn field_element_to_bytes<F: PrimeField>(value: &F, endianness: Endianness) -> Vec<u8> {
+	match endianness {
+		Endianness::Little => value.into_repr().to_bytes_le(),
+		Endianness::Big => value.into_repr().to_bytes_be(),
+	}
+}
+
+/// Parse `value` as `0x` prefixed big endian hex or as decimal
+pub fn parse_field_element<F: PrimeField>(value: &str) -> Result<F, OperationError> {
+	let invalid = || invalid_field_element(format!("Invalid field element: {:?}", value));
+	if let Some(digits) = value.strip_prefix("0x") {
+		if digits.is_empty() || digits.len() > 2 * FIELD_ELEMENT_SIZE {
+			return Err(invalid());
+		}
+		let padded = format!("{:0>64}", digits);
+		let bytes = hex::decode(padded).map_err(|_| invalid())?;
+		return field_element_from_bytes(&bytes, Endianness::Big);
+	}
+	if value.is_empty() || !value.bytes().all(|byte| byte.is_ascii_digit()) {
+		return Err(invalid());
+	}
+	let number = BigUint::parse_bytes(value.as_bytes(), 10).ok_or_else(invalid)?;
+	let modulus: BigUint = F::Params::MODULUS.into();
+	if number >= modulus {
+		return Err(invalid_field_element(format!("{} is above the field modulus", value)));
+	}
+	let repr = F::BigInt::try_from(number).map_err(|_| invalid())?;
+	F::from_repr(repr).ok_or_else(invalid)
+}
+
+/// `0x` prefixed big endian hex of `value`
+pub fn field_element_to_hex<F: PrimeField>(value: &F) -> String {
+	format!("0x{}", hex::encode(value.into_repr().to_bytes_be()))
+}
+
+pub fn field_element_to_decimal<F: PrimeField>(value: &F) -> String {
+	let number: BigUint = value.into_repr().into();
+	number.to_string()
+}
+
+/// Montgomery form `value * R mod p` arkworks keeps elements in, little
+/// endian as in memory
+pub fn to_montgomery<F: PrimeField>(value: &F) -> Vec<u8> {
+	let r = F::from_repr(F::Params::R).expect("R is reduced");
+	(*value * r).into_repr().to_bytes_le()
+}
+
+/// The element whose Montgomery form is `montgomery`, little endian
+pub fn from_montgomery<F: PrimeField>(montgomery: &[u8]) -> Result<F, OperationError> {
+	let r = F::from_repr(F::Params::R).expect("R is reduced");
+	let r_inverse = r.inverse().expect("R is invertible");
+	Ok(field_element_from_bytes::<F>(montgomery, Endianness::Little)? * r_inverse)
+}
+
+macro_rules! with_field {
+	($curve:expr, $field:ident => $body:expr) => {
+		match $curve {
+			Curve::Bn254 => {
+				type $field = Bn254Fr;
+				$body
+			}
+			Curve::Bls381 => {
+				type $field = Bls381Fr;
+				$body
+			}
+		}
+	};
+}
+
+/// Whether `bytes` is the big endian encoding of an element of `F`, below
+/// the modulus
+pub fn is_canonical<F: PrimeField>(bytes: &[u8]) -> bool {
+	F::from_be_bytes_mod_order(bytes).into_repr().to_bytes_be() == bytes
+}
+
+/// Uniformly random element of `F`. Draws above the modulus are rejected
+/// rather than reduced, which would favor the small elements
+pub fn random_field_element<F: PrimeField, R: RngCore + ?Sized>(rng: &mut R) -> F {
+	let excess_bits = 256 - F::size_in_bits();
+	loop {
+		let mut bytes = [0u8; 32];
+		rng.fill_bytes(&mut bytes);
+		// Bits above the modulus' would only make more draws fail
+		bytes[0] &= 0xff >> excess_bits;
+		if is_canonical::<F>(&bytes) {
+			return F::from_be_bytes_mod_order(&bytes);
+		}
+	}
+}
+
+/// Random field element of `curve`, big endian
+pub fn random_field_bytes<R: RngCore + ?Sized>(curve: Curve, rng: &mut R) -> [u8; 32] {
+	let bytes = match curve {
+		Curve::Bn254 => random_field_element::<Bn254Fr, _>(rng).into_repr().to_bytes_be(),
+		Curve::Bls381 => random_field_element::<Bls381Fr, _>(rng).into_repr().to_bytes_be(),
+	};
+	bytes.try_into().unwrap()
+}
+
+/// Random non-zero field element of `curve` to blind a commitment with
+pub fn random_blinding_bytes<R: RngCore + ?Sized>(curve: Curve, rng: &mut R) -> [u8; 32] {
+	loop {
+		let bytes = random_field_bytes(curve, rng);
+		if bytes != [0; 32] {
+			return bytes;
+		}
+	}
+}
+
+fn curve_of_js(curve: WasmCurve) -> Result<Curve, JsValue> {
+	let curve: Curve = JsValue::from(curve)
+		.as_string()
+		.ok_or(OpStatusCode::InvalidCurve)?
+		.parse()?;
+	Ok(curve)
+}
+
+/// Random element of `curve`'s scalar field, 32 bytes big endian
+#[wasm_bindgen(js_name = generateFieldElement)]
+pub fn generate_field_element(curve: WasmCurve) -> Result<Uint8Array, JsValue> {
+	let bytes = random_field_bytes(curve_of_js(curve)?, &mut OsRng);
+	Ok(Uint8Array::from(bytes.as_ref()))
+}
+
+/// Random non-zero element of `curve`'s scalar field for blindings, 32 bytes
+/// big endian
+#[wasm_bindgen(js_name = generateBlinding)]
+pub fn generate_blinding(curve: WasmCurve) -> Result<Uint8Array, JsValue> {
+	let bytes = random_blinding_bytes(curve_of_js(curve)?, &mut OsRng);
+	Ok(Uint8Array::from(bytes.as_ref()))
+}
+
+/// Bytes of the field element `value`, given as `0x` prefixed big endian hex
+/// or as decimal. Big endian unless `littleEndian`, on Bn254 by default
+#[wasm_bindgen(js_name = fieldElementToBytes)]
+pub fn js_field_element_to_bytes(
+	value: JsString,
+	curve: Option<WasmCurve>,
+	little_endian: Option<bool>,
+) -> Result<Uint8Array, JsValue> {
+	let value = String::from(value);
+	let endianness = Endianness::of_js(little_endian);
+	let bytes = with_field!(parse_curve(curve)?, F => {
+		field_element_to_bytes(&parse_field_element::<F>(&value)?, endianness)
+	});
+	Ok(Uint8Array::from(bytes.as_slice()))
+}
+
+/// `0x` prefixed big endian hex of the field element `bytes`
+#[wasm_bindgen(js_name = fieldElementToHex)]
+pub fn js_field_element_to_hex(
+	bytes: Uint8Array,
+	curve: Option<WasmCurve>,
+	little_endian: Option<bool>,
+) -> Result<JsString, JsValue> {
+	let endianness = Endianness::of_js(little_endian);
+	let hex = with_field!(parse_curve(curve)?, F => {
+		field_element_to_hex(&field_element_from_bytes::<F>(&bytes.to_vec(), endianness)?)
+	});
+	Ok(hex.into())
+}
+
+/// Decimal string of the field element `bytes`, as EVM tooling prints them
+#[wasm_bindgen(js_name = fieldElementToDecimal)]
+pub fn js_field_element_to_decimal(
+	bytes: Uint8Array,
+	curve: Option<WasmCurve>,
+	little_endian: Option<bool>,
+) -> Result<JsString, JsValue> {
+	let endianness = Endianness::of_js(little_endian);
+	let decimal = with_field!(parse_curve(curve)?, F => {
+		field_element_to_decimal(&field_element_from_bytes::<F>(&bytes.to_vec(), endianness)?)
+	});
+	Ok(decimal.into())
+}
+
+/// Montgomery form of the field element `bytes`, always little endian
+#[wasm_bindgen(js_name = fieldElementToMontgomery)]
+pub fn js_field_element_to_montgomery(
+	bytes: Uint8Array,
+	curve: Option<WasmCurve>,
+	little_endian: Option<bool>,
+) -> Result<Uint8Array, JsValue> {
+	let endianness = Endianness::of_js(little_endian);
+	let montgomery = with_field!(parse_curve(curve)?, F => {
+		to_montgomery(&field_element_from_bytes::<F>(&bytes.to_vec(), endianness)?)
+	});
+	Ok(Uint8Array::from(montgomery.as_slice()))
+}
+
+/// Bytes of the field element whose little endian Montgomery form is
+/// `montgomery`, big endian unless `littleEndian`
+#[wasm_bindgen(js_name = fieldElementFromMontgomery)]
+pub fn js_field_element_from_montgomery(
+	montgomery: Uint8Array,
+	curve: Option<WasmCurve>,
+	little_endian: Option<bool>,
+) -> Result<Uint8Array, JsValue> {
+	let endianness = Endianness::of_js(little_endian);
+	let bytes = with_field!(parse_curve(curve)?, F => {
+		field_element_to_bytes(&from_montgomery::<F>(&montgomery.to_vec())?, endianness)
+	});
+	Ok(Uint8Array::from(bytes.as_slice()))
+}
+
+#[cfg(test)]
+mod test {
+	use rand::SeedableRng;
+	use rand_chacha::ChaCha20Rng;
+	use wasm_bindgen_test::*;
+
+	use super::*;
+
+	/// All ones for the first `ones` bytes, then the inner rng
+	struct SaturatedRng {
+		ones: usize,
+		inner: ChaCha20Rng,
+	}
+
+	impl RngCore for SaturatedRng {
+		fn next_u32(&mut self) -> u32 {
+			self.inner.next_u32()
+		}
+
+		fn next_u64(&mut self) -> u64 {
+			self.inner.next_u64()
+		}
+
+		fn fill_bytes(&mut self, dest: &mut [u8]) {
+			for byte in dest.iter_mut() {
+				*byte = if self.ones > 0 {
+					self.ones -= 1;
+					0xff
+				} else {
+					self.inner.next_u32() as u8
+				};
+			}
+		}
+
+		fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+			self.fill_bytes(dest);
+			Ok(())
+		}
+	}
+
+	#[wasm_bindgen_test]
+	fn should_generate_canonical_field_elements() {
+		let mut rng = ChaCha20Rng::seed_from_u64(582);
+		for _ in 0..64 {
+			assert!(is_canonical::<Bn254Fr>(&random_field_bytes(Curve::Bn254, &mut rng)));
+			assert!(is_canonical::<Bls381Fr>(&random_field_bytes(Curve::Bls381, &mut rng)));
+		}
+
+		// Two draws of all ones are above both moduli and get rejected
+		for curve in [Curve::Bn254, Curve::Bls381] {
+			let mut rng = SaturatedRng {
+				ones: 64,
+				inner: ChaCha20Rng::seed_from_u64(582),
+			};
+			let bytes = random_blinding_bytes(curve, &mut rng);
+			assert_eq!(rng.ones, 0);
+			assert_ne!(bytes, [0xff; 32]);
+			assert_ne!(bytes, [0; 32]);
+		}
+
+		let curve: WasmCurve = JsValue::from("Bls381").into();
+		assert_eq!(generate_field_element(curve).unwrap().length(), 32);
+		let curve: WasmCurve = JsValue::from("Ed25519").into();
+		assert!(generate_blinding(curve).is_err());
+	}
+
+	#[wasm_bindgen_test]
+	fn should_convert_field_elements() {
+		// The Bn254 modulus minus one
+		let decimal = "21888242871839275222246405745257275088548364400416034343698204186575808495616";
+		let hex = "0x30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000000";
+		let value = parse_field_element::<Bn254Fr>(decimal).unwrap();
+		assert_eq!(value, -Bn254Fr::from(1u64));
+		assert_eq!(parse_field_element::<Bn254Fr>(hex).unwrap(), value);
+		assert_eq!(field_element_to_hex(&value), hex);
+		assert_eq!(field_element_to_decimal(&value), decimal);
+		assert_eq!(parse_field_element::<Bn254Fr>("0x5").unwrap(), Bn254Fr::from(5u64));
+		assert_eq!(field_element_to_decimal(&Bn254Fr::from(0u64)), "0");
+
+		let be = field_element_to_bytes(&value, Endianness::Big);
+		let le = field_element_to_bytes(&value, Endianness::Little);
+		assert_eq!(be.iter().rev().copied().collect::<Vec<_>>(), le);
+		assert_eq!(
+			field_element_from_bytes::<Bn254Fr>(&le, Endianness::Little).unwrap(),
+			value
+		);
+		// The little endian bytes of small elements are above the modulus
+		// when read big endian
+		let small = field_element_to_bytes(&Bn254Fr::from(255u64), Endianness::Little);
+		let error = field_element_from_bytes::<Bn254Fr>(&small, Endianness::Big).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::InvalidFieldElement);
+
+		// Montgomery form is the in-memory limbs
+		let montgomery = to_montgomery(&value);
+		let limbs: Vec<u8> = value.0 .0.iter().flat_map(|limb| limb.to_le_bytes()).collect();
+		assert_eq!(montgomery, limbs);
+		assert_eq!(from_montgomery::<Bn254Fr>(&montgomery).unwrap(), value);
+
+		for invalid in [
+			"21888242871839275222246405745257275088548364400416034343698204186575808495617",
+			"0x30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000001",
+			"-1",
+			"0x",
+			"",
+			"1e3",
+			"0xzz",
+		] {
+			assert!(parse_field_element::<Bn254Fr>(invalid).is_err(), "{}", invalid);
+		}
+		assert!(parse_field_element::<Bls381Fr>(decimal).is_ok());
+
+		let bytes = js_field_element_to_bytes(decimal.into(), None, Some(true)).unwrap();
+		assert_eq!(bytes.to_vec(), le);
+		let curve: WasmCurve = JsValue::from("Bls381").into();
+		let hex = js_field_element_to_hex(Uint8Array::from(be.as_slice()), Some(curve), None).unwrap();
+		assert_eq!(
+			String::from(hex),
+			"0x30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000000"
+		);
+	}
+}
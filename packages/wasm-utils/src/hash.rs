@@ -0,0 +1,270 @@
+use core::cell::RefCell;
+use core::convert::TryInto;
+use std::collections::BTreeMap;
+use std::thread::LocalKey;
+
+use ark_bls12_381::Fr as Bls381Fr;
+use ark_bn254::Fr as Bn254Fr;
+use ark_crypto_primitives::CRH as CRHTrait;
+use ark_ff::{BigInteger, PrimeField, Zero};
+use arkworks_native_gadgets::mimc::{MiMCParameters, Rounds, CRH};
+use arkworks_native_gadgets::poseidon::{FieldHasher, Poseidon, PoseidonParameters};
+use arkworks_setups::common::setup_params;
+use arkworks_setups::Curve as ArkCurve;
+use arkworks_utils::bytes_vec_to_f;
+use arkworks_utils::mimc_params::setup_mimc_params;
+use js_sys::Uint8Array;
+use wasm_bindgen::prelude::*;
+
+use crate::merkle::leaves_from_js;
+use crate::types::{Curve, Leaves, OpStatusCode, OperationError, WasmCurve};
+
+/// Check that Poseidon parameters for the combination are built into the
+/// crate, `setup_params` panics for any other combination
+pub fn ensure_poseidon_params(curve: Curve, exp: i8, width: u8) -> Result<(), OperationError> {
+	match (curve, exp, width) {
+		(Curve::Bn254, 5, 2..=5) | (Curve::Bls381, 5, 3) => Ok(()),
+		_ => {
+			let message = format!(
+				"No Poseidon parameters for curve {}, exponentiation {}, and width {}",
+				curve, exp, width
+			);
+			Err(OperationError::new_with_message(
+				OpStatusCode::UnsupportedParameterCombination,
+				message,
+			))
+		}
+	}
+}
+
+type ParamsCache<F> = RefCell<BTreeMap<(i8, u8), PoseidonParameters<F>>>;
+
+thread_local! {
+	static BN254_PARAMS: ParamsCache<Bn254Fr> = RefCell::new(BTreeMap::new());
+	static BLS381_PARAMS: ParamsCache<Bls381Fr> = RefCell::new(BTreeMap::new());
+	static MIMC_PARAMS: RefCell<Option<MiMCParameters<Bn254Fr>>> = RefCell::new(None);
+}
+
+/// Scalar field of a curve with built in Poseidon parameters
+pub trait PoseidonField: PrimeField {
+	const CURVE: Curve;
+
+	fn params_cache() -> &'static LocalKey<ParamsCache<Self>>;
+}
+
+impl PoseidonField for Bn254Fr {
+	const CURVE: Curve = Curve::Bn254;
+
+	fn params_cache() -> &'static LocalKey<ParamsCache<Self>> {
+		&BN254_PARAMS
+	}
+}
+
+impl PoseidonField for Bls381Fr {
+	const CURVE: Curve = Curve::Bls381;
+
+	fn params_cache() -> &'static LocalKey<ParamsCache<Self>> {
+		&BLS381_PARAMS
+	}
+}
+
+/// Poseidon parameters of `F`'s curve, decoded on first use and cached for the
+/// lifetime of the module. Panics like `setup_params` for combinations
+/// rejected by [`ensure_poseidon_params`].
+pub fn poseidon_params<F: PoseidonField>(exp: i8, width: u8) -> PoseidonParameters<F> {
+	F::params_cache().with(|cache| {
+		cache
+			.borrow_mut()
+			.entry((exp, width))
+			.or_insert_with(|| setup_params::<F>(F::CURVE.into(), exp, width))
+			.clone()
+	})
+}
+
+/// Decode every Poseidon parameter set of `curve` ahead of time, returns the
+/// `(exp, width)` combinations loaded
+pub fn preload_poseidon_params(curve: Curve) -> Vec<(i8, u8)> {
+	let combinations: Vec<(i8, u8)> = match curve {
+		Curve::Bn254 => (2..=5).map(|width| (5, width)).collect(),
+		Curve::Bls381 => vec![(5, 3)],
+	};
+	for (exp, width) in &combinations {
+		match curve {
+			Curve::Bn254 => drop(poseidon_params::<Bn254Fr>(*exp, *width)),
+			Curve::Bls381 => drop(poseidon_params::<Bls381Fr>(*exp, *width)),
+		}
+	}
+	combinations
+}
+
+fn hash_with<F: PoseidonField>(exp: i8, width: u8, inputs: &[[u8; 32]]) -> Result<[u8; 32], OperationError> {
+	let hasher = Poseidon::new(poseidon_params::<F>(exp, width));
+	let inputs: Vec<F> = inputs.iter().map(|input| F::from_be_bytes_mod_order(input)).collect();
+	let hash = hasher
+		.hash(&inputs)
+		.map_err(|e| OperationError::new_with_message(OpStatusCode::InvalidArrayLength, e.to_string()))?;
+	Ok(hash.into_repr().to_bytes_be().try_into().unwrap())
+}
+
+/// Poseidon hash of 32 bytes big endian field elements, a `width` hasher takes
+/// up to `width - 1` inputs
+pub fn poseidon_hash_raw(curve: Curve, exp: i8, width: u8, inputs: &[[u8; 32]]) -> Result<[u8; 32], OperationError> {
+	ensure_poseidon_params(curve, exp, width)?;
+	if inputs.len() >= width as usize {
+		let message = format!(
+			"Poseidon with width {} takes up to {} inputs, got {}",
+			width,
+			width - 1,
+			inputs.len()
+		);
+		return Err(OperationError::new_with_message(
+			OpStatusCode::InvalidArrayLength,
+			message,
+		));
+	}
+	match curve {
+		Curve::Bn254 => hash_with::<Bn254Fr>(exp, width, inputs),
+		Curve::Bls381 => hash_with::<Bls381Fr>(exp, width, inputs),
+	}
+}
+
+/// Hash field elements with the same Poseidon parameters as the circuits, to
+/// compute commitments, nullifiers and tree nodes from JS
+#[wasm_bindgen]
+pub fn poseidon_hash(inputs: Leaves, curve: WasmCurve, width: u8, exp: i8) -> Result<Uint8Array, JsValue> {
+	let curve: Curve = JsValue::from(curve)
+		.as_string()
+		.ok_or(OpStatusCode::InvalidCurve)?
+		.parse()?;
+	let inputs = leaves_from_js(&inputs)?;
+	let hash = poseidon_hash_raw(curve, exp, width, &inputs)?;
+	Ok(Uint8Array::from(hash.as_ref()))
+}
+
+/// Decode the Poseidon parameters of `curve` now instead of on the first leaf,
+/// hash or proof using them. Returns the number of parameter sets loaded.
+#[wasm_bindgen]
+pub fn warmup(curve: WasmCurve) -> Result<usize, JsValue> {
+	let curve: Curve = JsValue::from(curve)
+		.as_string()
+		.ok_or(OpStatusCode::InvalidCurve)?
+		.parse()?;
+	Ok(preload_poseidon_params(curve).len())
+}
+
+/// MiMC with 220 rounds and the arkworks-utils constants. It is neither
+/// circomlib's `MiMCSponge` nor the Pedersen commitment of Tornado Cash, its
+/// hashes only match other arkworks MiMC hashers
+#[derive(Default, Clone)]
+struct MiMCRounds220;
+
+impl Rounds for MiMCRounds220 {
+	const ROUNDS: u16 = 220;
+	const WIDTH: u8 = 3;
+}
+
+type MiMC220 = CRH<Bn254Fr, MiMCRounds220>;
+
+/// MiMC parameters, decoded on first use and cached like the Poseidon ones
+fn mimc_params() -> MiMCParameters<Bn254Fr> {
+	MIMC_PARAMS.with(|cache| cache.borrow_mut().get_or_insert_with(decode_mimc_params).clone())
+}
+
+fn decode_mimc_params() -> MiMCParameters<Bn254Fr> {
+	let mimc_data = setup_mimc_params(ArkCurve::Bn254, MiMCRounds220::ROUNDS, MiMCRounds220::WIDTH).unwrap();
+	MiMCParameters::new(
+		Bn254Fr::zero(),
+		mimc_data.rounds as usize,
+		mimc_data.width as usize,
+		mimc_data.width as usize,
+		bytes_vec_to_f(&mimc_data.constants),
+	)
+}
+
+/// MiMC hash of up to 3 Bn254 field elements (32 bytes big endian), the
+/// inputs are padded with zeros to the sponge width
+pub fn mimc_hash_raw(inputs: &[[u8; 32]]) -> Result<[u8; 32], OperationError> {
+	if inputs.len() > MiMCRounds220::WIDTH as usize {
+		let message = format!("MiMC takes up to {} inputs, got {}", MiMCRounds220::WIDTH, inputs.len());
+		return Err(OperationError::new_with_message(
+			OpStatusCode::InvalidArrayLength,
+			message,
+		));
+	}
+	// Reduce the inputs first, the sponge rejects non canonical field elements
+	let bytes: Vec<u8> = inputs
+		.iter()
+		.flat_map(|input| Bn254Fr::from_be_bytes_mod_order(input).into_repr().to_bytes_be())
+		.collect();
+	let hash = <MiMC220 as CRHTrait>::evaluate(&mimc_params(), &bytes)
+		.map_err(|e| OperationError::new_with_message(OpStatusCode::InvalidArrayLength, e.to_string()))?;
+	Ok(hash.into_repr().to_bytes_be().try_into().unwrap())
+}
+
+/// Hash Bn254 field elements with the arkworks MiMC parameters, see
+/// [`mimc_hash_raw`]
+#[wasm_bindgen]
+pub fn mimc_hash(inputs: Leaves) -> Result<Uint8Array, JsValue> {
+	let inputs = leaves_from_js(&inputs)?;
+	let hash = mimc_hash_raw(&inputs)?;
+	Ok(Uint8Array::from(hash.as_ref()))
+}
+
+#[cfg(test)]
+mod test {
+	use js_sys::Array;
+	use wasm_bindgen_test::*;
+
+	use super::*;
+	use crate::merkle::bn254_tree_hasher;
+
+	#[wasm_bindgen_test]
+	fn should_match_the_tree_hasher() {
+		let left = Bn254Fr::from(1u64);
+		let right = Bn254Fr::from(2u64);
+		let inputs: Array = [left, right]
+			.iter()
+			.map(|f| Uint8Array::from(f.into_repr().to_bytes_be().as_slice()))
+			.collect();
+		let curve: WasmCurve = JsValue::from(Curve::Bn254.to_string()).into();
+
+		let hash = poseidon_hash(Leaves::from(JsValue::from(inputs)), curve, 3, 5).unwrap();
+		let expected = bn254_tree_hasher().hash_two(&left, &right).unwrap();
+		assert_eq!(hash.to_vec(), expected.into_repr().to_bytes_be());
+	}
+
+	#[wasm_bindgen_test]
+	fn should_cache_params_on_warmup() {
+		let curve: WasmCurve = JsValue::from(Curve::Bls381.to_string()).into();
+		assert_eq!(warmup(curve).unwrap(), 1);
+		BLS381_PARAMS.with(|cache| assert!(cache.borrow().contains_key(&(5, 3))));
+		let cached = poseidon_params::<Bls381Fr>(5, 3);
+		let decoded = setup_params::<Bls381Fr>(ArkCurve::Bls381, 5, 3);
+		assert_eq!(cached.round_keys, decoded.round_keys);
+		assert_eq!(cached.mds_matrix, decoded.mds_matrix);
+
+		let curve: WasmCurve = JsValue::from("Ed25519").into();
+		assert!(warmup(curve).is_err());
+	}
+
+	#[wasm_bindgen_test]
+	fn should_reject_unsupported_params_and_too_many_inputs() {
+		let inputs = [[1u8; 32], [2u8; 32], [3u8; 32]];
+		assert!(poseidon_hash_raw(Curve::Bn254, 5, 4, &inputs).is_ok());
+		assert!(poseidon_hash_raw(Curve::Bn254, 5, 3, &inputs).is_err());
+		assert!(poseidon_hash_raw(Curve::Bls381, 5, 5, &inputs).is_err());
+		assert!(poseidon_hash_raw(Curve::Bn254, 17, 5, &inputs).is_err());
+	}
+
+	#[wasm_bindgen_test]
+	fn should_hash_with_mimc() {
+		let one = Bn254Fr::from(1u64).into_repr().to_bytes_be().try_into().unwrap();
+		let two = Bn254Fr::from(2u64).into_repr().to_bytes_be().try_into().unwrap();
+
+		let hash = mimc_hash_raw(&[one, two]).unwrap();
+		assert_eq!(hash, mimc_hash_raw(&[one, two, [0u8; 32]]).unwrap());
+		assert_ne!(hash, mimc_hash_raw(&[two, one]).unwrap());
+		assert_ne!(hash, poseidon_hash_raw(Curve::Bn254, 5, 3, &[one, two]).unwrap());
+		assert!(mimc_hash_raw(&[one; 4]).is_err());
+	}
+}
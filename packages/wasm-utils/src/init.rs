@@ -0,0 +1,208 @@
+//! Explicit module setup for JS hosts. `init` installs the panic hook, which
+//! the start function also does, and moves the one off costs, like decoding
+//! Poseidon parameters, out of the first note or proof call.
+use core::str::FromStr;
+
+use js_sys::Array;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::hash::preload_poseidon_params;
+use crate::logging::{log_level, set_log_level, LogLevel};
+use crate::proof::SUPPORTED_CIRCUITS;
+use crate::types::{Curve, NoteProtocol, OpStatusCode, OperationError, Protocol, StringArray};
+
+/// Version of the exported JS API, bumped when an export is removed or
+/// changes in a way older callers can't handle. Additions don't bump it.
+pub const ABI_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct InitOptions {
+	/// Console log level, see `setLogLevel`. Logging stays as it is if unset
+	pub log_level: Option<String>,
+	/// Curves to decode the Poseidon parameters of, `Bn254` or `Bls381`
+	#[serde(default)]
+	pub preload_curves: Vec<String>,
+}
+
+/// What `init` did, along with the features this build was compiled with
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct InitReport {
+	pub version: String,
+	pub abi_version: u32,
+	/// Built with the `parallel` feature, so proving uses the thread pool
+	/// started by `initThreadPool`
+	pub threads: bool,
+	/// Built with the wasm simd128 instructions
+	pub simd: bool,
+	pub log_level: String,
+	pub preloaded_curves: Vec<String>,
+}
+
+pub fn init(options: &InitOptions) -> Result<InitReport, OperationError> {
+	console_error_panic_hook::set_once();
+
+	let level = options
+		.log_level
+		.as_deref()
+		.map(LogLevel::from_str)
+		.transpose()?
+		.unwrap_or_else(log_level);
+	let curves = options
+		.preload_curves
+		.iter()
+		.map(|curve| curve.parse())
+		.collect::<Result<Vec<Curve>, _>>()?;
+	for curve in &curves {
+		preload_poseidon_params(*curve);
+	}
+	set_log_level(level);
+
+	let report = InitReport {
+		version: env!("CARGO_PKG_VERSION").to_string(),
+		abi_version: ABI_VERSION,
+		threads: cfg!(feature = "parallel"),
+		simd: cfg!(target_feature = "simd128"),
+		log_level: level.to_string(),
+		preloaded_curves: curves.iter().map(Curve::to_string).collect(),
+	};
+	tracing::info!(?report, "initialized");
+	Ok(report)
+}
+
+#[wasm_bindgen(typescript_custom_section)]
+const INIT_TYPES: &str = r#"
+export interface InitOptions {
+	logLevel?: LogLevel;
+	preloadCurves?: Array<Curve>;
+}
+
+export interface InitReport {
+	version: string;
+	abiVersion: number;
+	threads: boolean;
+	simd: boolean;
+	logLevel: LogLevel;
+	preloadedCurves: Array<Curve>;
+}
+"#;
+
+#[wasm_bindgen]
+extern "C" {
+	#[wasm_bindgen(typescript_type = "InitOptions")]
+	pub type WasmInitOptions;
+
+	#[wasm_bindgen(typescript_type = "InitReport")]
+	pub type WasmInitReport;
+}
+
+/// Set the module up before the first call, see `InitOptions`. Calling it
+/// again only applies the new options.
+// The `parallel` build targets `web`, where `init` is the wasm loader
+#[cfg_attr(not(feature = "parallel"), wasm_bindgen(js_name = init))]
+#[cfg_attr(feature = "parallel", wasm_bindgen(js_name = initWasmUtils))]
+pub fn js_init(options: Option<WasmInitOptions>) -> Result<WasmInitReport, JsValue> {
+	let options = match options {
+		Some(options) => JsValue::from(options)
+			.into_serde()
+			.map_err(|e| OperationError::new_with_message(OpStatusCode::DeserializationFailed, e.to_string()))?,
+		None => InitOptions::default(),
+	};
+	let report = init(&options)?;
+	let report = JsValue::from_serde(&report)
+		.map_err(|e| OperationError::new_with_message(OpStatusCode::SerializationFailed, e.to_string()))?;
+	Ok(report.unchecked_into())
+}
+
+/// Protocols this build can prove, in the order of `SUPPORTED_CIRCUITS`
+pub fn supported_protocols() -> Vec<NoteProtocol> {
+	let mut protocols = Vec::new();
+	for (protocol, ..) in SUPPORTED_CIRCUITS {
+		if !protocols.contains(&protocol) {
+			protocols.push(protocol);
+		}
+	}
+	protocols
+}
+
+/// Curves this build can prove on, for `protocol` or for any protocol
+pub fn supported_curves(protocol: Option<NoteProtocol>) -> Vec<Curve> {
+	let mut curves = Vec::new();
+	for (circuit_protocol, curve, ..) in SUPPORTED_CIRCUITS {
+		if protocol.map_or(true, |protocol| protocol == circuit_protocol) && !curves.contains(&curve) {
+			curves.push(curve);
+		}
+	}
+	curves
+}
+
+fn to_string_array<T: ToString>(values: &[T]) -> StringArray {
+	let values: Array = values.iter().map(|value| JsValue::from(value.to_string())).collect();
+	values.unchecked_into()
+}
+
+#[wasm_bindgen(js_name = abiVersion)]
+pub fn abi_version() -> u32 {
+	ABI_VERSION
+}
+
+#[wasm_bindgen(js_name = supportedProtocols)]
+pub fn js_supported_protocols() -> StringArray {
+	to_string_array(&supported_protocols())
+}
+
+/// Curves proofs can be generated on, only those of `protocol` when given
+#[wasm_bindgen(js_name = supportedCurves)]
+pub fn js_supported_curves(protocol: Option<Protocol>) -> Result<StringArray, JsValue> {
+	let protocol = protocol
+		.map(|protocol| {
+			JsValue::from(protocol)
+				.as_string()
+				.ok_or(OpStatusCode::InvalidNoteProtocol)?
+				.parse::<NoteProtocol>()
+		})
+		.transpose()?;
+	Ok(to_string_array(&supported_curves(protocol)))
+}
+
+#[cfg(test)]
+mod test {
+	use wasm_bindgen_test::*;
+
+	use super::*;
+
+	#[wasm_bindgen_test]
+	fn should_init_with_options() {
+		let report = init(&InitOptions {
+			log_level: Some("warn".to_string()),
+			preload_curves: vec!["Bn254".to_string(), "Bls381".to_string()],
+		})
+		.unwrap();
+		assert_eq!(report.version, env!("CARGO_PKG_VERSION"));
+		assert_eq!(report.abi_version, ABI_VERSION);
+		assert_eq!(report.preloaded_curves, vec!["Bn254", "Bls381"]);
+		assert!(!report.threads);
+		assert_eq!(log_level(), LogLevel::Warn);
+		set_log_level(LogLevel::Off);
+
+		let options = InitOptions {
+			preload_curves: vec!["Ed25519".to_string()],
+			..Default::default()
+		};
+		assert_eq!(init(&options).err().unwrap().code, OpStatusCode::InvalidCurve);
+	}
+
+	#[wasm_bindgen_test]
+	fn should_report_capabilities() {
+		assert_eq!(supported_protocols(), [NoteProtocol::Mixer, NoteProtocol::VAnchor]);
+		assert_eq!(supported_curves(None), [Curve::Bn254, Curve::Bls381]);
+		assert_eq!(supported_curves(Some(NoteProtocol::VAnchor)), [Curve::Bn254]);
+
+		let curves = js_supported_curves(Some(JsValue::from("mixer").into())).unwrap();
+		assert_eq!(curves.to_vec(), [JsValue::from("Bn254"), JsValue::from("Bls381")]);
+		assert!(js_supported_curves(Some(JsValue::from("tornado").into())).is_err());
+	}
+}
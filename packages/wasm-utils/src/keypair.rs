@@ -0,0 +1,249 @@
+use core::fmt;
+use core::str::FromStr;
+
+use ark_bn254::Fr as Bn254Fr;
+use ark_ff::{BigInteger, PrimeField};
+use ark_std::UniformRand;
+use arkworks_native_gadgets::poseidon::Poseidon;
+use arkworks_setups::keypair::Keypair;
+use crypto_box::{PublicKey, SecretKey, KEY_SIZE};
+use js_sys::{JsString, Uint8Array};
+use rand::{CryptoRng, RngCore};
+use wasm_bindgen::prelude::*;
+
+use crate::entropy::SecretRng;
+use crate::hash::poseidon_params;
+use crate::types::{OpStatusCode, OperationError};
+
+/// VAnchor keypair for the Bn254 circuits.
+///
+/// The public key is `poseidon(secret_key)`, as checked by the circuit, and the
+/// encryption key is the x25519 public key of the big endian secret key bytes,
+/// matching the circom/Solidity `Keypair`.
+#[wasm_bindgen(js_name = Keypair)]
+#[derive(Clone)]
+pub struct JsKeypair {
+	#[wasm_bindgen(skip)]
+	pub inner: Keypair<Bn254Fr, Poseidon<Bn254Fr>>,
+	#[wasm_bindgen(skip)]
+	pub encryption_key: [u8; KEY_SIZE],
+}
+
+fn invalid_keypair(message: &str) -> OperationError {
+	OperationError::new_with_message(OpStatusCode::InvalidKeypair, message.to_string())
+}
+
+fn decode_key(bytes: &[u8]) -> Result<[u8; KEY_SIZE], OperationError> {
+	bytes.try_into().map_err(|_| {
+		let message = format!("Keys should be {} bytes", KEY_SIZE);
+		OperationError::new_with_message(OpStatusCode::InvalidArrayLength, message)
+	})
+}
+
+impl JsKeypair {
+	pub fn new<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+		Self::from_secret_key(Bn254Fr::rand(rng))
+	}
+
+	pub fn from_secret_key(secret_key: Bn254Fr) -> Self {
+		let hasher2 = Poseidon::<Bn254Fr>::new(poseidon_params(5, 2));
+		let inner = Keypair::new(secret_key, &hasher2);
+		let secret_key_bytes = decode_key(&secret_key.into_repr().to_bytes_be()).unwrap();
+		let encryption_key = *SecretKey::from(secret_key_bytes).public_key().as_bytes();
+
+		Self { inner, encryption_key }
+	}
+
+	/// Keypair from big endian secret key bytes
+	pub fn from_secret(secret_key: &[u8]) -> Result<Self, OperationError> {
+		let secret_key = decode_key(secret_key)?;
+		Ok(Self::from_secret_key(Bn254Fr::from_be_bytes_mod_order(&secret_key)))
+	}
+
+	pub fn secret_key(&self) -> Option<Vec<u8>> {
+		self.inner.secret_key.map(|key| key.into_repr().to_bytes_be())
+	}
+
+	pub fn public_key(&self) -> Vec<u8> {
+		self.inner.public_key.into_repr().to_bytes_be()
+	}
+
+	pub fn encryption_key(&self) -> PublicKey {
+		PublicKey::from(self.encryption_key)
+	}
+
+	/// `poseidon(secret_key, commitment, index)`, the signature the VAnchor
+	/// circuit requires to spend a UTXO owned by this keypair
+	pub fn signature(&self, commitment: &[u8], index: u64) -> Result<Vec<u8>, OperationError> {
+		if self.inner.secret_key.is_none() {
+			return Err(invalid_keypair("Signing requires the secret key"));
+		}
+		let hasher4 = Poseidon::<Bn254Fr>::new(poseidon_params(5, 4));
+		let commitment = Bn254Fr::from_be_bytes_mod_order(commitment);
+		let signature = self
+			.inner
+			.signature(&commitment, &Bn254Fr::from(index), &hasher4)
+			.map_err(|e| invalid_keypair(&e.to_string()))?;
+
+		Ok(signature.into_repr().to_bytes_be())
+	}
+}
+
+impl PartialEq for JsKeypair {
+	fn eq(&self, other: &Self) -> bool {
+		self.inner.secret_key == other.inner.secret_key
+			&& self.inner.public_key == other.inner.public_key
+			&& self.encryption_key == other.encryption_key
+	}
+}
+
+impl fmt::Debug for JsKeypair {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "JsKeypair({})", self)
+	}
+}
+
+/// `0x` followed by the hex public key and encryption key, the format the
+/// circom/Solidity `Keypair.toString()` uses to share a keypair's public half.
+impl fmt::Display for JsKeypair {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"0x{}{}",
+			hex::encode(self.public_key()),
+			hex::encode(self.encryption_key)
+		)
+	}
+}
+
+/// Parse the public half of a keypair, see the `Display` implementation
+impl FromStr for JsKeypair {
+	type Err = OperationError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let bytes = hex::decode(s.trim_start_matches("0x")).map_err(|_| invalid_keypair("Invalid keypair hex"))?;
+		if bytes.len() != 2 * KEY_SIZE {
+			return Err(invalid_keypair("Keypair string should hold two 32 byte keys"));
+		}
+		let (public_key, encryption_key) = bytes.split_at(KEY_SIZE);
+		let inner = Keypair::new_from_public_key(Bn254Fr::from_be_bytes_mod_order(public_key));
+
+		Ok(Self {
+			inner,
+			encryption_key: decode_key(encryption_key)?,
+		})
+	}
+}
+
+#[allow(clippy::unused_unit)]
+#[wasm_bindgen(js_class = Keypair)]
+impl JsKeypair {
+	/// Random keypair
+	#[wasm_bindgen(constructor)]
+	pub fn js_new() -> Result<JsKeypair, JsValue> {
+		Ok(JsKeypair::new(&mut SecretRng::new()?))
+	}
+
+	#[wasm_bindgen(js_name = fromSecret)]
+	pub fn js_from_secret(secret_key: Uint8Array) -> Result<JsKeypair, JsValue> {
+		Ok(JsKeypair::from_secret(&secret_key.to_vec())?)
+	}
+
+	/// Parse the public half of a keypair as produced by `toString`
+	#[wasm_bindgen(js_name = fromString)]
+	pub fn js_from_string(keypair: JsString) -> Result<JsKeypair, JsValue> {
+		let keypair: String = keypair.into();
+		Ok(keypair.parse::<JsKeypair>()?)
+	}
+
+	#[wasm_bindgen(js_name = secretKey)]
+	pub fn js_secret_key(&self) -> Option<JsString> {
+		self.secret_key().map(|key| hex::encode(key).into())
+	}
+
+	#[wasm_bindgen(js_name = publicKey)]
+	pub fn js_public_key(&self) -> JsString {
+		hex::encode(self.public_key()).into()
+	}
+
+	#[wasm_bindgen(js_name = encryptionKey)]
+	pub fn js_encryption_key(&self) -> JsString {
+		hex::encode(self.encryption_key).into()
+	}
+
+	#[wasm_bindgen(js_name = signature)]
+	pub fn js_signature(&self, commitment: Uint8Array, index: u64) -> Result<JsString, JsValue> {
+		let signature = self.signature(&commitment.to_vec(), index)?;
+		Ok(hex::encode(signature).into())
+	}
+
+	#[wasm_bindgen(js_name = toString)]
+	pub fn to_js_string(&self) -> JsString {
+		self.to_string().into()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use arkworks_native_gadgets::poseidon::FieldHasher;
+	use rand::rngs::OsRng;
+	use wasm_bindgen_test::*;
+
+	use super::*;
+	use crate::types::{Backend, Curve};
+	use crate::utxo::JsUtxo;
+
+	#[wasm_bindgen_test]
+	fn should_match_utxo_keys_and_signature() {
+		let keypair = JsKeypair::new(&mut OsRng);
+		let secret_key = keypair.secret_key().unwrap();
+		let utxo = JsUtxo::new(
+			Curve::Bn254,
+			Backend::Arkworks,
+			10,
+			1,
+			None,
+			None,
+			Some(secret_key.clone()),
+			Some(3),
+		)
+		.unwrap();
+
+		assert_eq!(JsKeypair::from_secret(&secret_key).unwrap(), keypair);
+		assert_eq!(utxo.get_public_key(), keypair.public_key());
+
+		// The nullifier is `poseidon(commitment, index, signature)`
+		let signature = keypair.signature(&utxo.get_commitment(), 3).unwrap();
+		let hasher4 = Poseidon::<Bn254Fr>::new(poseidon_params(5, 4));
+		let nullifier = hasher4
+			.hash(&[
+				Bn254Fr::from_be_bytes_mod_order(&utxo.get_commitment()),
+				Bn254Fr::from(3u64),
+				Bn254Fr::from_be_bytes_mod_order(&signature),
+			])
+			.unwrap();
+		assert_eq!(nullifier.into_repr().to_bytes_be(), utxo.get_nullifier().unwrap());
+	}
+
+	#[wasm_bindgen_test]
+	fn should_share_public_keypair_string() {
+		let secret_key = hex::decode("0d34bf5bbdebc6b3ed8c26b4ff5e55b0e0b0233c93fdec0bfd8c6a3f0a5b4e1a").unwrap();
+		let keypair = JsKeypair::from_secret(&secret_key).unwrap();
+		let encoded = keypair.to_string();
+		assert_eq!(encoded.len(), 2 + 128);
+
+		let public: JsKeypair = encoded.parse().unwrap();
+		assert_eq!(public.secret_key(), None);
+		assert_eq!(public.public_key(), keypair.public_key());
+		assert_eq!(public.encryption_key(), keypair.encryption_key());
+		assert_eq!(public.to_string(), encoded);
+		assert_eq!(
+			public.signature(&[0u8; 32], 0).err().unwrap().code,
+			OpStatusCode::InvalidKeypair
+		);
+		assert_eq!(
+			JsKeypair::from_secret(&secret_key[..31]).err().unwrap().code,
+			OpStatusCode::InvalidArrayLength
+		);
+	}
+}
@@ -9,11 +9,39 @@ extern crate wasm_bindgen;
 #[global_allocator]
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
+// Exported to JS as `initThreadPool(n)`, which has to resolve before proving
+// with the `parallel` feature
+#[cfg(feature = "parallel")]
+pub use wasm_bindgen_rayon::init_thread_pool;
+
+pub mod address;
+pub mod bench;
+pub mod cid;
+pub mod crypto;
+pub mod deposit;
+pub mod entropy;
+pub mod events;
+pub mod evm;
+pub mod field;
+pub mod hash;
+pub mod init;
+pub mod keypair;
+pub mod logging;
+pub mod memory;
+pub mod merkle;
 pub mod note;
+pub mod nullifier;
+// `with_tree_depth!` is used outside the module by the test vectors
+#[macro_use]
 pub mod proof;
+pub mod scanner;
+pub mod substrate;
+#[cfg(feature = "testvectors")]
+pub mod test_vectors;
 pub mod types;
 mod utils;
 mod utxo;
+pub mod worker;
 
 use ark_bls12_381::Bls12_381;
 use ark_bn254::Bn254;
@@ -29,6 +57,8 @@ pub const TREE_HEIGHT: usize = 30;
 pub const ANCHOR_COUNT: usize = 2;
 
 pub const ANCHOR_COUNT_2: usize = 2;
+pub const ANCHOR_COUNT_4: usize = 4;
+pub const ANCHOR_COUNT_8: usize = 8;
 pub const ANCHOR_COUNT_16: usize = 16;
 pub const ANCHOR_COUNT_32: usize = 32;
 
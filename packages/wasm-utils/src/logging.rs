@@ -0,0 +1,284 @@
+//! `tracing` subscriber forwarding to the JS console. Events go to the
+//! console method of their level, spans are logged with their duration when
+//! they are exited, which is what the proof generation spans are for.
+use core::cell::RefCell;
+use core::fmt::{self, Write};
+use core::str::FromStr;
+use core::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::collections::BTreeMap;
+
+use tracing::field::{Field, Visit};
+use tracing::level_filters::LevelFilter;
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::{Interest, Subscriber};
+use tracing::{Event, Level, Metadata};
+use wasm_bindgen::prelude::*;
+use web_sys::console;
+
+use crate::types::{OpStatusCode, OperationError};
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum LogLevel {
+	Off,
+	Error,
+	Warn,
+	Info,
+	Debug,
+	Trace,
+}
+
+impl LogLevel {
+	fn filter(&self) -> LevelFilter {
+		match self {
+			LogLevel::Off => LevelFilter::OFF,
+			LogLevel::Error => LevelFilter::ERROR,
+			LogLevel::Warn => LevelFilter::WARN,
+			LogLevel::Info => LevelFilter::INFO,
+			LogLevel::Debug => LevelFilter::DEBUG,
+			LogLevel::Trace => LevelFilter::TRACE,
+		}
+	}
+
+	fn from_u8(level: u8) -> Self {
+		match level {
+			1 => LogLevel::Error,
+			2 => LogLevel::Warn,
+			3 => LogLevel::Info,
+			4 => LogLevel::Debug,
+			5 => LogLevel::Trace,
+			_ => LogLevel::Off,
+		}
+	}
+}
+
+impl fmt::Display for LogLevel {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let level = match self {
+			LogLevel::Off => "off",
+			LogLevel::Error => "error",
+			LogLevel::Warn => "warn",
+			LogLevel::Info => "info",
+			LogLevel::Debug => "debug",
+			LogLevel::Trace => "trace",
+		};
+		write!(f, "{}", level)
+	}
+}
+
+impl FromStr for LogLevel {
+	type Err = OperationError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"off" => Ok(LogLevel::Off),
+			"error" => Ok(LogLevel::Error),
+			"warn" => Ok(LogLevel::Warn),
+			"info" => Ok(LogLevel::Info),
+			"debug" => Ok(LogLevel::Debug),
+			"trace" => Ok(LogLevel::Trace),
+			_ => {
+				let message = format!("Unknown log level {}", s);
+				Err(OperationError::new_with_message(OpStatusCode::InvalidLogLevel, message))
+			}
+		}
+	}
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Off as u8);
+static NEXT_SPAN_ID: AtomicU64 = AtomicU64::new(1);
+
+struct SpanState {
+	name: &'static str,
+	level: Level,
+	fields: String,
+	entered_at: Option<f64>,
+	refs: usize,
+}
+
+thread_local! {
+	static SPANS: RefCell<BTreeMap<u64, SpanState>> = RefCell::new(BTreeMap::new());
+}
+
+// Message first, then the other fields as `key=value`
+#[derive(Default)]
+struct FieldFormatter {
+	message: String,
+	fields: String,
+}
+
+impl Visit for FieldFormatter {
+	fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+		if field.name() == "message" {
+			let _ = write!(self.message, "{:?}", value);
+		} else {
+			let _ = write!(self.fields, " {}={:?}", field.name(), value);
+		}
+	}
+}
+
+fn console_log(level: &Level, line: &str) {
+	let line = JsValue::from_str(line);
+	match *level {
+		Level::ERROR => console::error_1(&line),
+		Level::WARN => console::warn_1(&line),
+		Level::INFO => console::info_1(&line),
+		_ => console::debug_1(&line),
+	}
+}
+
+struct ConsoleSubscriber;
+
+impl Subscriber for ConsoleSubscriber {
+	// The level can change at runtime, so callsites can't be cached as always or
+	// never enabled
+	fn register_callsite(&self, _metadata: &'static Metadata<'static>) -> Interest {
+		Interest::sometimes()
+	}
+
+	fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+		*metadata.level() <= log_level().filter()
+	}
+
+	fn new_span(&self, span: &Attributes<'_>) -> Id {
+		let id = NEXT_SPAN_ID.fetch_add(1, Ordering::Relaxed);
+		let mut formatter = FieldFormatter::default();
+		span.record(&mut formatter);
+		let state = SpanState {
+			name: span.metadata().name(),
+			level: *span.metadata().level(),
+			fields: formatter.fields,
+			entered_at: None,
+			refs: 1,
+		};
+		SPANS.with(|spans| spans.borrow_mut().insert(id, state));
+		Id::from_u64(id)
+	}
+
+	fn record(&self, span: &Id, values: &Record<'_>) {
+		let mut formatter = FieldFormatter::default();
+		values.record(&mut formatter);
+		SPANS.with(|spans| {
+			if let Some(state) = spans.borrow_mut().get_mut(&span.into_u64()) {
+				state.fields.push_str(&formatter.fields);
+			}
+		});
+	}
+
+	fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+	fn event(&self, event: &Event<'_>) {
+		let mut formatter = FieldFormatter::default();
+		event.record(&mut formatter);
+		let metadata = event.metadata();
+		let line = format!("{} {}{}", metadata.target(), formatter.message, formatter.fields);
+		console_log(metadata.level(), &line);
+	}
+
+	fn enter(&self, span: &Id) {
+		SPANS.with(|spans| {
+			if let Some(state) = spans.borrow_mut().get_mut(&span.into_u64()) {
+				state.entered_at = Some(js_sys::Date::now());
+			}
+		});
+	}
+
+	fn exit(&self, span: &Id) {
+		SPANS.with(|spans| {
+			if let Some(state) = spans.borrow_mut().get_mut(&span.into_u64()) {
+				if let Some(entered_at) = state.entered_at.take() {
+					let elapsed = js_sys::Date::now() - entered_at;
+					let line = format!("{}{} took {}ms", state.name, state.fields, elapsed);
+					console_log(&state.level, &line);
+				}
+			}
+		});
+	}
+
+	fn clone_span(&self, span: &Id) -> Id {
+		SPANS.with(|spans| {
+			if let Some(state) = spans.borrow_mut().get_mut(&span.into_u64()) {
+				state.refs += 1;
+			}
+		});
+		span.clone()
+	}
+
+	fn try_close(&self, span: Id) -> bool {
+		SPANS.with(|spans| {
+			let mut spans = spans.borrow_mut();
+			let closed = match spans.get_mut(&span.into_u64()) {
+				Some(state) => {
+					state.refs -= 1;
+					state.refs == 0
+				}
+				None => false,
+			};
+			if closed {
+				spans.remove(&span.into_u64());
+			}
+			closed
+		})
+	}
+}
+
+pub fn log_level() -> LogLevel {
+	LogLevel::from_u8(LEVEL.load(Ordering::Relaxed))
+}
+
+/// Set the level of the messages forwarded to the console, installing the
+/// console subscriber the first time logging is turned on
+pub fn set_log_level(level: LogLevel) {
+	LEVEL.store(level as u8, Ordering::Relaxed);
+	if level != LogLevel::Off {
+		// Fails if a subscriber is already installed, which is either ours or
+		// one the host application chose
+		let _ = tracing::subscriber::set_global_default(ConsoleSubscriber);
+	}
+}
+
+#[wasm_bindgen(typescript_custom_section)]
+const LOG_LEVEL: &str = "export type LogLevel = 'off' | 'error' | 'warn' | 'info' | 'debug' | 'trace';";
+
+#[wasm_bindgen]
+extern "C" {
+	#[wasm_bindgen(typescript_type = "LogLevel")]
+	pub type WasmLogLevel;
+}
+
+/// Forward messages up to `level` to the console, `off` disables logging
+#[wasm_bindgen(js_name = setLogLevel)]
+pub fn js_set_log_level(level: WasmLogLevel) -> Result<(), JsValue> {
+	let level: LogLevel = JsValue::from(level)
+		.as_string()
+		.ok_or(OpStatusCode::InvalidLogLevel)?
+		.parse()?;
+	set_log_level(level);
+	Ok(())
+}
+
+#[cfg(test)]
+mod test {
+	use wasm_bindgen_test::*;
+
+	use super::*;
+
+	#[wasm_bindgen_test]
+	fn should_filter_by_log_level() {
+		assert_eq!("debug".parse::<LogLevel>().unwrap(), LogLevel::Debug);
+		assert_eq!(LogLevel::Warn.to_string(), "warn");
+		assert!("verbose".parse::<LogLevel>().is_err());
+
+		set_log_level(LogLevel::Info);
+		assert_eq!(log_level(), LogLevel::Info);
+		assert!(tracing::enabled!(Level::INFO));
+		assert!(!tracing::enabled!(Level::DEBUG));
+		{
+			let _span = tracing::info_span!("should_filter_by_log_level", step = 1).entered();
+			tracing::info!(answer = 42, "logged from a test");
+		}
+		SPANS.with(|spans| assert!(spans.borrow().is_empty()));
+
+		set_log_level(LogLevel::Off);
+		assert!(!tracing::enabled!(Level::ERROR));
+	}
+}
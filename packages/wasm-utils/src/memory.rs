@@ -0,0 +1,149 @@
+//! Accounting of the wasm heap and of the large objects handed to JS.
+//!
+//! Objects returned to JS live until their `free()` method is called, the
+//! wasm heap never shrinks, so long running processes should free proving
+//! keys and trees they are done with and watch `memoryStats()`.
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::types::{OpStatusCode, OperationError};
+
+const WASM_PAGE_SIZE: usize = 65536;
+
+static PROVING_KEYS: AtomicUsize = AtomicUsize::new(0);
+static PROVING_KEY_BYTES: AtomicUsize = AtomicUsize::new(0);
+static MERKLE_TREES: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ObjectKind {
+	ProvingKeys,
+	MerkleTree,
+}
+
+/// Counts the object holding it as live until it is dropped
+#[derive(Debug, PartialEq, Eq)]
+pub struct LiveObject {
+	kind: ObjectKind,
+	bytes: usize,
+}
+
+impl LiveObject {
+	pub fn new(kind: ObjectKind, bytes: usize) -> Self {
+		match kind {
+			ObjectKind::ProvingKeys => {
+				PROVING_KEYS.fetch_add(1, Ordering::Relaxed);
+				PROVING_KEY_BYTES.fetch_add(bytes, Ordering::Relaxed);
+			}
+			ObjectKind::MerkleTree => {
+				MERKLE_TREES.fetch_add(1, Ordering::Relaxed);
+			}
+		}
+		Self { kind, bytes }
+	}
+}
+
+impl Clone for LiveObject {
+	fn clone(&self) -> Self {
+		Self::new(self.kind, self.bytes)
+	}
+}
+
+impl Drop for LiveObject {
+	fn drop(&mut self) {
+		match self.kind {
+			ObjectKind::ProvingKeys => {
+				PROVING_KEYS.fetch_sub(1, Ordering::Relaxed);
+				PROVING_KEY_BYTES.fetch_sub(self.bytes, Ordering::Relaxed);
+			}
+			ObjectKind::MerkleTree => {
+				MERKLE_TREES.fetch_sub(1, Ordering::Relaxed);
+			}
+		}
+	}
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryStats {
+	/// Size of the wasm linear memory, which only grows
+	pub heap_bytes: usize,
+	pub proving_keys: usize,
+	/// Bytes held by the live proving and verifying keys, circom zkeys included
+	pub proving_key_bytes: usize,
+	/// Live `JsMerkleTree`s and `JsFrontierMerkleTree`s
+	pub merkle_trees: usize,
+}
+
+fn heap_bytes() -> usize {
+	#[cfg(target_arch = "wasm32")]
+	return core::arch::wasm32::memory_size::<0>() * WASM_PAGE_SIZE;
+	#[cfg(not(target_arch = "wasm32"))]
+	return 0;
+}
+
+pub fn memory_stats() -> MemoryStats {
+	MemoryStats {
+		heap_bytes: heap_bytes(),
+		proving_keys: PROVING_KEYS.load(Ordering::Relaxed),
+		proving_key_bytes: PROVING_KEY_BYTES.load(Ordering::Relaxed),
+		merkle_trees: MERKLE_TREES.load(Ordering::Relaxed),
+	}
+}
+
+#[wasm_bindgen(typescript_custom_section)]
+const MEMORY_STATS: &str = r#"
+export interface MemoryStats {
+	heapBytes: number;
+	provingKeys: number;
+	provingKeyBytes: number;
+	merkleTrees: number;
+}
+"#;
+
+#[wasm_bindgen]
+extern "C" {
+	#[wasm_bindgen(typescript_type = "MemoryStats")]
+	pub type WasmMemoryStats;
+}
+
+#[wasm_bindgen(js_name = memoryStats)]
+pub fn js_memory_stats() -> Result<WasmMemoryStats, JsValue> {
+	let stats = JsValue::from_serde(&memory_stats())
+		.map_err(|e| OperationError::new_with_message(OpStatusCode::SerializationFailed, e.to_string()))?;
+	Ok(stats.unchecked_into())
+}
+
+#[cfg(test)]
+mod test {
+	use wasm_bindgen_test::*;
+
+	use super::*;
+	use crate::merkle::JsMerkleTree;
+
+	#[wasm_bindgen_test]
+	fn should_count_live_objects() {
+		let before = memory_stats();
+		assert!(before.heap_bytes > 0);
+		assert_eq!(before.heap_bytes % WASM_PAGE_SIZE, 0);
+
+		let keys = LiveObject::new(ObjectKind::ProvingKeys, 1000);
+		let tree = LiveObject::new(ObjectKind::MerkleTree, 0);
+		let tree_copy = tree.clone();
+		let stats = memory_stats();
+		assert_eq!(stats.proving_keys, before.proving_keys + 1);
+		assert_eq!(stats.proving_key_bytes, before.proving_key_bytes + 1000);
+		assert_eq!(stats.merkle_trees, before.merkle_trees + 2);
+
+		drop((keys, tree, tree_copy));
+		let merkle_tree = JsMerkleTree::new(None).unwrap();
+		assert_eq!(memory_stats().merkle_trees, before.merkle_trees + 1);
+		drop(merkle_tree);
+		let after = memory_stats();
+		assert_eq!(after.proving_keys, before.proving_keys);
+		assert_eq!(after.proving_key_bytes, before.proving_key_bytes);
+		assert_eq!(after.merkle_trees, before.merkle_trees);
+	}
+}
@@ -0,0 +1,464 @@
+use core::convert::{TryFrom, TryInto};
+
+use ark_bn254::Fr as Bn254Fr;
+use ark_ff::{BigInteger, PrimeField};
+use arkworks_native_gadgets::poseidon::{FieldHasher, Poseidon};
+use js_sys::{Array, JsString, Uint8Array};
+use parity_scale_codec::{Decode, Encode};
+use wasm_bindgen::__rt::std::collections::btree_map::BTreeMap;
+use wasm_bindgen::prelude::*;
+
+use crate::memory::{LiveObject, ObjectKind};
+use crate::merkle::{bn254_tree_hasher, calculate_root, leaves_from_js, JsMerklePath};
+use crate::proof::ensure_tree_depth;
+use crate::types::{Leaves, OpStatusCode, OperationError, Uint8Arrayx32};
+use crate::{DEFAULT_LEAF, TREE_HEIGHT};
+
+fn to_bytes(f: &Bn254Fr) -> [u8; 32] {
+	f.into_repr().to_bytes_be().try_into().unwrap()
+}
+
+fn from_bytes(bytes: &[u8; 32]) -> Bn254Fr {
+	Bn254Fr::from_be_bytes_mod_order(bytes)
+}
+
+/// A leaf whose membership path is kept up to date while the tree grows
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackedLeaf {
+	pub leaf: Bn254Fr,
+	pub path_elements: Vec<Bn254Fr>,
+}
+
+#[derive(Encode, Decode)]
+struct FrontierMerkleTreeState {
+	next_index: u64,
+	filled_subtrees: Vec<[u8; 32]>,
+	root: [u8; 32],
+	tracked: Vec<(u64, [u8; 32], Vec<[u8; 32]>)>,
+}
+
+/// Incremental merkle tree that only stores the frontier (the last left node
+/// of every level) and the paths of the tracked leaves.
+///
+/// Its memory use doesn't depend on the number of leaves, so it can follow
+/// trees with millions of leaves while still producing membership paths for
+/// the user's own leaves. The depth is the one of the tree it follows, one
+/// of the depths proofs can be generated for.
+pub struct FrontierMerkleTree {
+	pub depth: usize,
+	pub next_index: u64,
+	pub filled_subtrees: Vec<Bn254Fr>,
+	pub root: Bn254Fr,
+	pub tracked: BTreeMap<u64, TrackedLeaf>,
+	zeros: Vec<Bn254Fr>,
+	hasher: Poseidon<Bn254Fr>,
+}
+
+impl Default for FrontierMerkleTree {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl FrontierMerkleTree {
+	/// Empty tree of the default height
+	pub fn new() -> Self {
+		Self::empty(TREE_HEIGHT)
+	}
+
+	/// Empty tree of `depth` levels
+	pub fn with_depth(depth: usize) -> Result<Self, OperationError> {
+		ensure_tree_depth(depth)?;
+		Ok(Self::empty(depth))
+	}
+
+	fn empty(depth: usize) -> Self {
+		let hasher = bn254_tree_hasher();
+		let mut zeros = vec![Bn254Fr::from_be_bytes_mod_order(&DEFAULT_LEAF)];
+		for level in 0..depth {
+			zeros.push(hasher.hash_two(&zeros[level], &zeros[level]).unwrap());
+		}
+		Self {
+			depth,
+			next_index: 0,
+			filled_subtrees: zeros[..depth].to_vec(),
+			root: zeros[depth],
+			tracked: BTreeMap::new(),
+			zeros,
+			hasher,
+		}
+	}
+
+	pub fn capacity(&self) -> u64 {
+		1u64 << self.depth
+	}
+
+	/// Resume a tree of `depth` levels from its frontier, as stored by the
+	/// on-chain contracts (`nextIndex`, `filledSubtrees` and the last root)
+	pub fn from_frontier(
+		depth: usize,
+		next_index: u64,
+		filled_subtrees: Vec<Bn254Fr>,
+		root: Bn254Fr,
+	) -> Result<Self, OperationError> {
+		let mut tree = Self::with_depth(depth)?;
+		if filled_subtrees.len() != depth {
+			let message = format!(
+				"Frontier has {} nodes while the tree depth is {}",
+				filled_subtrees.len(),
+				depth
+			);
+			return Err(OperationError::new_with_message(OpStatusCode::InvalidLeaves, message));
+		}
+		if next_index > tree.capacity() {
+			return Err(OpStatusCode::InvalidLeafIndex.into());
+		}
+		tree.next_index = next_index;
+		tree.filled_subtrees = filled_subtrees;
+		tree.root = root;
+		Ok(tree)
+	}
+
+	/// Append a leaf, when `track` is set its membership path is maintained
+	/// for the following insertions
+	pub fn insert(&mut self, leaf: Bn254Fr, track: bool) -> Result<u64, OperationError> {
+		let index = self.next_index;
+		if index >= self.capacity() {
+			let message = format!("The tree is full with {} leaves", index);
+			return Err(OperationError::new_with_message(OpStatusCode::InvalidLeaves, message));
+		}
+
+		let mut path_elements = Vec::with_capacity(self.depth);
+		let mut node = leaf;
+		for level in 0..self.depth {
+			// Any tracked leaf whose sibling at this level is on the new leaf's path
+			let sibling_index = index >> level;
+			for (tracked_index, tracked) in self.tracked.iter_mut() {
+				if (tracked_index >> level) ^ 1 == sibling_index {
+					tracked.path_elements[level] = node;
+				}
+			}
+
+			let (left, right) = if sibling_index & 1 == 0 {
+				self.filled_subtrees[level] = node;
+				path_elements.push(self.zeros[level]);
+				(node, self.zeros[level])
+			} else {
+				path_elements.push(self.filled_subtrees[level]);
+				(self.filled_subtrees[level], node)
+			};
+			node = self
+				.hasher
+				.hash_two(&left, &right)
+				.map_err(|e| OperationError::new_with_message(OpStatusCode::InvalidLeaves, e.to_string()))?;
+		}
+
+		self.root = node;
+		self.next_index += 1;
+		if track {
+			self.tracked.insert(index, TrackedLeaf { leaf, path_elements });
+		}
+		Ok(index)
+	}
+
+	/// Track a leaf that is already in the tree, `path_elements` must lead to
+	/// the current root
+	pub fn track_leaf(&mut self, index: u64, leaf: Bn254Fr, path_elements: Vec<Bn254Fr>) -> Result<(), OperationError> {
+		if index >= self.next_index || path_elements.len() != self.depth {
+			return Err(OpStatusCode::InvalidLeafIndex.into());
+		}
+		let path_indices: Vec<u8> = (0..self.depth).map(|level| ((index >> level) & 1) as u8).collect();
+		let root = calculate_root(&self.hasher, leaf, &path_elements, &path_indices)?;
+		if root != self.root {
+			let message = format!("Path of leaf {} doesn't lead to the current root", index);
+			return Err(OperationError::new_with_message(OpStatusCode::InvalidRoots, message));
+		}
+		self.tracked.insert(index, TrackedLeaf { leaf, path_elements });
+		Ok(())
+	}
+
+	pub fn untrack_leaf(&mut self, index: u64) -> Option<TrackedLeaf> {
+		self.tracked.remove(&index)
+	}
+
+	pub fn path(&self, index: u64) -> Result<JsMerklePath, OperationError> {
+		let tracked = self.tracked.get(&index).ok_or_else(|| {
+			let message = format!("Leaf {} isn't tracked by the tree", index);
+			OperationError::new_with_message(OpStatusCode::InvalidLeafIndex, message)
+		})?;
+		Ok(JsMerklePath::from_path_elements(
+			index,
+			tracked.leaf,
+			self.root,
+			&tracked.path_elements,
+		))
+	}
+
+	pub fn serialize(&self) -> Vec<u8> {
+		FrontierMerkleTreeState {
+			next_index: self.next_index,
+			filled_subtrees: self.filled_subtrees.iter().map(to_bytes).collect(),
+			root: to_bytes(&self.root),
+			tracked: self
+				.tracked
+				.iter()
+				.map(|(index, tracked)| {
+					let path_elements = tracked.path_elements.iter().map(to_bytes).collect();
+					(*index, to_bytes(&tracked.leaf), path_elements)
+				})
+				.collect(),
+		}
+		.encode()
+	}
+
+	/// Tree of [`Self::serialize`], the depth being the number of nodes of
+	/// its frontier
+	pub fn deserialize(state: &[u8]) -> Result<Self, OperationError> {
+		let state = FrontierMerkleTreeState::decode(&mut &*state)
+			.map_err(|e| OperationError::new_with_message(OpStatusCode::DeserializationFailed, e.to_string()))?;
+		let depth = state.filled_subtrees.len();
+		let filled_subtrees = state.filled_subtrees.iter().map(from_bytes).collect();
+		let mut tree = Self::from_frontier(depth, state.next_index, filled_subtrees, from_bytes(&state.root))?;
+		for (index, leaf, path_elements) in state.tracked {
+			if path_elements.len() != depth {
+				return Err(OpStatusCode::DeserializationFailed.into());
+			}
+			tree.tracked.insert(index, TrackedLeaf {
+				leaf: from_bytes(&leaf),
+				path_elements: path_elements.iter().map(from_bytes).collect(),
+			});
+		}
+		Ok(tree)
+	}
+}
+
+/// Merkle tree for large trees, see `FrontierMerkleTree`
+#[wasm_bindgen]
+pub struct JsFrontierMerkleTree {
+	#[wasm_bindgen(skip)]
+	pub inner: FrontierMerkleTree,
+	#[wasm_bindgen(skip)]
+	pub live: LiveObject,
+}
+
+impl Default for JsFrontierMerkleTree {
+	fn default() -> Self {
+		Self {
+			inner: FrontierMerkleTree::new(),
+			live: LiveObject::new(ObjectKind::MerkleTree, 0),
+		}
+	}
+}
+
+#[allow(clippy::unused_unit)]
+#[wasm_bindgen]
+impl JsFrontierMerkleTree {
+	/// Empty tree of `treeDepth` levels, 30 by default
+	#[wasm_bindgen(constructor)]
+	pub fn js_new(tree_depth: Option<u32>) -> Result<JsFrontierMerkleTree, JsValue> {
+		let inner = match tree_depth {
+			Some(tree_depth) => FrontierMerkleTree::with_depth(tree_depth as usize)?,
+			None => FrontierMerkleTree::new(),
+		};
+		Ok(Self {
+			inner,
+			live: LiveObject::new(ObjectKind::MerkleTree, 0),
+		})
+	}
+
+	/// Resume a tree from the frontier of a contract, the tree depth is the
+	/// contract's and defaults to 30
+	#[wasm_bindgen(js_name = fromFrontier)]
+	pub fn from_frontier(
+		next_index: JsString,
+		filled_subtrees: Leaves,
+		root: Uint8Array,
+		tree_depth: Option<u32>,
+	) -> Result<JsFrontierMerkleTree, JsValue> {
+		let next_index: String = next_index.into();
+		let next_index: u64 = next_index.parse().map_err(|_| OpStatusCode::InvalidLeafIndex)?;
+		let filled_subtrees = leaves_from_js(&filled_subtrees)?.iter().map(from_bytes).collect();
+		let root = Uint8Arrayx32::try_from(root).map_err(|_| OpStatusCode::InvalidRoots)?;
+		let depth = tree_depth.map_or(TREE_HEIGHT, |tree_depth| tree_depth as usize);
+		let inner = FrontierMerkleTree::from_frontier(depth, next_index, filled_subtrees, from_bytes(&root.0))?;
+		Ok(Self {
+			inner,
+			live: LiveObject::new(ObjectKind::MerkleTree, 0),
+		})
+	}
+
+	/// Append a leaf and return its index, pass `track` for the user's own
+	/// leaves to be able to generate their paths
+	pub fn insert(&mut self, leaf: Uint8Array, track: Option<bool>) -> Result<JsString, JsValue> {
+		let leaf = Uint8Arrayx32::try_from(leaf).map_err(|_| OpStatusCode::InvalidLeaves)?;
+		let index = self.inner.insert(from_bytes(&leaf.0), track.unwrap_or(false))?;
+		Ok(JsString::from(index.to_string()))
+	}
+
+	/// Append untracked leaves and return the index of the first one
+	#[wasm_bindgen(js_name = insertBatch)]
+	pub fn insert_batch(&mut self, leaves: Leaves) -> Result<JsString, JsValue> {
+		let index = self.inner.next_index;
+		for leaf in leaves_from_js(&leaves)? {
+			self.inner.insert(from_bytes(&leaf), false)?;
+		}
+		Ok(JsString::from(index.to_string()))
+	}
+
+	#[wasm_bindgen(js_name = trackLeaf)]
+	pub fn track_leaf(&mut self, index: JsString, leaf: Uint8Array, path_elements: Leaves) -> Result<(), JsValue> {
+		let index: String = index.into();
+		let index: u64 = index.parse().map_err(|_| OpStatusCode::InvalidLeafIndex)?;
+		let leaf = Uint8Arrayx32::try_from(leaf).map_err(|_| OpStatusCode::InvalidLeaves)?;
+		let path_elements = leaves_from_js(&path_elements)?.iter().map(from_bytes).collect();
+		self.inner.track_leaf(index, from_bytes(&leaf.0), path_elements)?;
+		Ok(())
+	}
+
+	#[wasm_bindgen(js_name = untrackLeaf)]
+	pub fn untrack_leaf(&mut self, index: JsString) -> Result<bool, JsValue> {
+		let index: String = index.into();
+		let index: u64 = index.parse().map_err(|_| OpStatusCode::InvalidLeafIndex)?;
+		Ok(self.inner.untrack_leaf(index).is_some())
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn root(&self) -> Uint8Array {
+		Uint8Array::from(to_bytes(&self.inner.root).as_ref())
+	}
+
+	#[wasm_bindgen(getter)]
+	#[wasm_bindgen(js_name = treeDepth)]
+	pub fn tree_depth(&self) -> u32 {
+		self.inner.depth as u32
+	}
+
+	#[wasm_bindgen(getter)]
+	#[wasm_bindgen(js_name = nextIndex)]
+	pub fn next_index(&self) -> JsString {
+		JsString::from(self.inner.next_index.to_string())
+	}
+
+	#[wasm_bindgen(getter)]
+	#[wasm_bindgen(js_name = filledSubtrees)]
+	pub fn filled_subtrees(&self) -> Leaves {
+		let nodes: Array = self
+			.inner
+			.filled_subtrees
+			.iter()
+			.map(|node| Uint8Array::from(to_bytes(node).as_ref()))
+			.collect();
+		Leaves::from(JsValue::from(nodes))
+	}
+
+	#[wasm_bindgen(js_name = generatePath)]
+	pub fn generate_path(&self, index: JsString) -> Result<JsMerklePath, JsValue> {
+		let index: String = index.into();
+		let index: u64 = index.parse().map_err(|_| OpStatusCode::InvalidLeafIndex)?;
+		Ok(self.inner.path(index)?)
+	}
+
+	pub fn serialize(&self) -> Uint8Array {
+		Uint8Array::from(self.inner.serialize().as_slice())
+	}
+
+	pub fn deserialize(state: Uint8Array) -> Result<JsFrontierMerkleTree, JsValue> {
+		let inner = FrontierMerkleTree::deserialize(&state.to_vec())?;
+		Ok(Self {
+			inner,
+			live: LiveObject::new(ObjectKind::MerkleTree, 0),
+		})
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use wasm_bindgen_test::*;
+
+	use super::*;
+	use crate::merkle::{Bn254MerklePath, MerkleTree};
+
+	fn path_elements(index: u64, path: &Bn254MerklePath) -> Vec<Bn254Fr> {
+		path.path
+			.iter()
+			.enumerate()
+			.map(|(level, (left, right))| if (index >> level) & 1 == 1 { *left } else { *right })
+			.collect()
+	}
+
+	#[wasm_bindgen_test]
+	fn should_track_paths_like_the_full_tree() {
+		let leaves: Vec<Bn254Fr> = (1..=11u64).map(Bn254Fr::from).collect();
+		let mut full_tree = MerkleTree::new();
+		let mut frontier_tree = FrontierMerkleTree::new();
+		for (i, leaf) in leaves.iter().enumerate() {
+			full_tree.insert_batch(&[*leaf]).unwrap();
+			frontier_tree.insert(*leaf, i == 2 || i == 7).unwrap();
+			assert_eq!(frontier_tree.root, full_tree.root());
+		}
+
+		for index in [2, 7] {
+			let expected = path_elements(index, &full_tree.path(index).unwrap());
+			assert_eq!(frontier_tree.tracked[&index].path_elements, expected);
+			let path = frontier_tree.path(index).unwrap();
+			assert_eq!(path.root, full_tree.root().into_repr().to_bytes_be());
+		}
+		assert!(frontier_tree.path(3).is_err());
+	}
+
+	#[wasm_bindgen_test]
+	fn should_resume_from_frontier_and_tracked_leaf() {
+		let leaves: Vec<Bn254Fr> = (1..=9u64).map(Bn254Fr::from).collect();
+		let mut full_tree = MerkleTree::new();
+		let mut synced_tree = FrontierMerkleTree::new();
+		full_tree.insert_batch(&leaves[..6]).unwrap();
+		for leaf in &leaves[..6] {
+			synced_tree.insert(*leaf, false).unwrap();
+		}
+
+		// Resume from a checkpoint and track an existing leaf using an indexer path
+		let mut tree =
+			FrontierMerkleTree::from_frontier(TREE_HEIGHT, 6, synced_tree.filled_subtrees.clone(), synced_tree.root)
+				.unwrap();
+		let indexer_path = path_elements(4, &full_tree.path(4).unwrap());
+		assert!(tree.track_leaf(4, leaves[3], indexer_path.clone()).is_err());
+		tree.track_leaf(4, leaves[4], indexer_path).unwrap();
+
+		for leaf in &leaves[6..] {
+			full_tree.insert_batch(&[*leaf]).unwrap();
+			tree.insert(*leaf, false).unwrap();
+		}
+		assert_eq!(tree.root, full_tree.root());
+		assert_eq!(
+			tree.tracked[&4].path_elements,
+			path_elements(4, &full_tree.path(4).unwrap())
+		);
+
+		let restored = FrontierMerkleTree::deserialize(&tree.serialize()).unwrap();
+		assert_eq!(restored.root, tree.root);
+		assert_eq!(restored.next_index, 9);
+		assert_eq!(restored.tracked, tree.tracked);
+	}
+
+	#[wasm_bindgen_test]
+	fn should_follow_trees_of_other_depths() {
+		let mut tree = FrontierMerkleTree::with_depth(20).unwrap();
+		assert_eq!(tree.capacity(), 1 << 20);
+		for leaf in 1..=5u64 {
+			tree.insert(Bn254Fr::from(leaf), leaf == 3).unwrap();
+		}
+		let path = &tree.tracked[&2].path_elements;
+		assert_eq!(path.len(), 20);
+		let path_indices: Vec<u8> = (0..20).map(|level| ((2u64 >> level) & 1) as u8).collect();
+		let root = calculate_root(&bn254_tree_hasher(), Bn254Fr::from(3u64), path, &path_indices).unwrap();
+		assert_eq!(root, tree.root);
+		assert_ne!(tree.root, FrontierMerkleTree::new().root);
+
+		let restored = FrontierMerkleTree::deserialize(&tree.serialize()).unwrap();
+		assert_eq!((restored.depth, restored.root), (20, tree.root));
+		let resumed = FrontierMerkleTree::from_frontier(20, 5, tree.filled_subtrees.clone(), tree.root).unwrap();
+		assert_eq!(resumed.capacity(), tree.capacity());
+		assert!(FrontierMerkleTree::from_frontier(30, 5, tree.filled_subtrees.clone(), tree.root).is_err());
+		assert!(FrontierMerkleTree::with_depth(24).is_err());
+	}
+}
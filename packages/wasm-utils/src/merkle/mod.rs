@@ -0,0 +1,887 @@
+use core::convert::{TryFrom, TryInto};
+
+use ark_bls12_381::Fr as Bls381Fr;
+use ark_bn254::Fr as Bn254Fr;
+use ark_ff::{BigInteger, PrimeField};
+use arkworks_native_gadgets::merkle_tree::{Path, SparseMerkleTree};
+use arkworks_native_gadgets::poseidon::{FieldHasher, Poseidon};
+use js_sys::{Array, JsString, Uint8Array};
+use parity_scale_codec::{Decode, Encode};
+use wasm_bindgen::__rt::std::collections::btree_map::BTreeMap;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::field::{field_element_from_bytes, Endianness};
+use crate::hash::{ensure_poseidon_params, poseidon_params, PoseidonField};
+use crate::memory::{LiveObject, ObjectKind};
+use crate::types::{Curve, Indices, Leaves, OpStatusCode, OperationError, Uint8Arrayx32, WasmCurve};
+use crate::{DEFAULT_LEAF, TREE_HEIGHT};
+
+pub mod frontier;
+
+pub type Bn254MerkleTree = SparseMerkleTree<Bn254Fr, Poseidon<Bn254Fr>, TREE_HEIGHT>;
+pub type Bn254MerklePath = Path<Bn254Fr, Poseidon<Bn254Fr>, TREE_HEIGHT>;
+
+/// Poseidon hasher used for the nodes of the Bn254 merkle trees
+pub fn bn254_tree_hasher() -> Poseidon<Bn254Fr> {
+	Poseidon::new(poseidon_params::<Bn254Fr>(5, 3))
+}
+
+/// Most leaves accepted from JS at once. Each leaf is copied into its own
+/// allocation, more would take most of the 4 GiB a wasm heap can grow to
+pub const MAX_LEAVES: usize = 1 << 24;
+pub const LEAF_SIZE: usize = 32;
+/// Packed leaves are copied into wasm this many bytes at a time
+const PACKED_LEAVES_CHUNK: usize = 1 << 20;
+
+fn limit_exceeded(message: String) -> OperationError {
+	OperationError::new_with_message(OpStatusCode::LimitExceeded, message)
+}
+
+pub fn ensure_leaf_count(count: usize) -> Result<(), OperationError> {
+	if count > MAX_LEAVES {
+		return Err(limit_exceeded(format!(
+			"{} leaves exceed the limit of {}",
+			count, MAX_LEAVES
+		)));
+	}
+	Ok(())
+}
+
+/// Leaves packed back to back in `bytes`, `leaf_size` bytes each. The bytes
+/// are copied in chunks, never as a whole, and running out of memory is an
+/// error rather than an abort
+pub fn leaves_from_packed(bytes: &Uint8Array, leaf_size: usize) -> Result<Vec<Vec<u8>>, OperationError> {
+	if leaf_size != LEAF_SIZE {
+		let message = format!("Leaves are {} bytes, not {}", LEAF_SIZE, leaf_size);
+		return Err(OperationError::new_with_message(OpStatusCode::InvalidLeaves, message));
+	}
+	let length = bytes.length() as usize;
+	if length % leaf_size != 0 {
+		let message = format!("{} bytes aren't a whole number of {} byte leaves", length, leaf_size);
+		return Err(OperationError::new_with_message(OpStatusCode::InvalidLeaves, message));
+	}
+	let count = length / leaf_size;
+	ensure_leaf_count(count)?;
+
+	let mut leaves = Vec::new();
+	leaves
+		.try_reserve_exact(count)
+		.map_err(|_| limit_exceeded(format!("Not enough memory for {} leaves", count)))?;
+	let mut chunk = vec![0u8; PACKED_LEAVES_CHUNK.min(length)];
+	let mut offset = 0;
+	while offset < length {
+		let end = (offset + PACKED_LEAVES_CHUNK).min(length);
+		let chunk = &mut chunk[..end - offset];
+		bytes.subarray(offset as u32, end as u32).copy_to(chunk);
+		leaves.extend(chunk.chunks_exact(leaf_size).map(<[u8]>::to_vec));
+		offset = end;
+	}
+	Ok(leaves)
+}
+
+/// Convert a JS array of 32 bytes `Uint8Array`s into raw leaves
+pub fn leaves_from_js(leaves: &Leaves) -> Result<Vec<[u8; 32]>, OperationError> {
+	let leaves = Array::from(leaves);
+	ensure_leaf_count(leaves.length() as usize)?;
+	let leaves = leaves
+		.to_vec()
+		.into_iter()
+		.map(|v| Uint8Array::new_with_byte_offset_and_length(&v, 0, 32))
+		.map(Uint8Arrayx32::try_from)
+		.collect::<Result<Vec<_>, _>>()
+		.map_err(|_| OpStatusCode::InvalidLeaves)?
+		.into_iter()
+		.map(|v| v.0)
+		.collect();
+	Ok(leaves)
+}
+
+/// Serialized form of `MerkleTree`, the leaves count and every non empty
+/// node of the sparse tree
+#[derive(Encode, Decode)]
+struct MerkleTreeState {
+	leaves_count: u64,
+	nodes: Vec<(u64, [u8; 32])>,
+}
+
+/// Append only Poseidon merkle tree that keeps its nodes between insertions
+pub struct MerkleTree {
+	pub tree: Bn254MerkleTree,
+	pub leaves_count: u64,
+	hasher: Poseidon<Bn254Fr>,
+}
+
+impl Default for MerkleTree {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl MerkleTree {
+	pub fn new() -> Self {
+		let hasher = bn254_tree_hasher();
+		let tree = Bn254MerkleTree::new_sequential(&[], &hasher, &DEFAULT_LEAF).expect("empty tree can't fail");
+		Self {
+			tree,
+			leaves_count: 0,
+			hasher,
+		}
+	}
+
+	pub fn capacity() -> u64 {
+		1u64 << TREE_HEIGHT
+	}
+
+	/// Append the leaves to the tree, returns the index of the first inserted
+	/// leaf
+	pub fn insert_batch(&mut self, leaves: &[Bn254Fr]) -> Result<u64, OperationError> {
+		let start_index = self.leaves_count;
+		if start_index + leaves.len() as u64 > Self::capacity() {
+			let message = format!(
+				"Inserting {} leaves exceeds the tree capacity of {} leaves",
+				leaves.len(),
+				Self::capacity()
+			);
+			return Err(OperationError::new_with_message(OpStatusCode::InvalidLeaves, message));
+		}
+		let batch: BTreeMap<u32, Bn254Fr> = leaves
+			.iter()
+			.enumerate()
+			.map(|(i, leaf)| ((start_index + i as u64) as u32, *leaf))
+			.collect();
+		self.tree
+			.insert_batch(&batch, &self.hasher)
+			.map_err(|e| OperationError::new_with_message(OpStatusCode::InvalidLeaves, e.to_string()))?;
+		self.leaves_count += leaves.len() as u64;
+		Ok(start_index)
+	}
+
+	pub fn root(&self) -> Bn254Fr {
+		self.tree.root()
+	}
+
+	/// Insert the leaves one by one, returning the root after every insertion
+	pub fn insert_with_roots(&mut self, leaves: &[Bn254Fr]) -> Result<Vec<Bn254Fr>, OperationError> {
+		leaves
+			.iter()
+			.map(|leaf| {
+				self.insert_batch(&[*leaf])?;
+				Ok(self.root())
+			})
+			.collect()
+	}
+
+	pub fn path(&self, index: u64) -> Result<Bn254MerklePath, OperationError> {
+		if index >= self.leaves_count {
+			let message = format!(
+				"Leaf index {} is out of bounds for a tree with {} leaves",
+				index, self.leaves_count
+			);
+			return Err(OperationError::new_with_message(
+				OpStatusCode::InvalidLeafIndex,
+				message,
+			));
+		}
+		Ok(self.tree.generate_membership_proof(index))
+	}
+
+	pub fn serialize(&self) -> Vec<u8> {
+		let nodes = self
+			.tree
+			.tree
+			.iter()
+			.map(|(index, node)| {
+				let node: [u8; 32] = node.into_repr().to_bytes_be().try_into().unwrap();
+				(*index, node)
+			})
+			.collect();
+		MerkleTreeState {
+			leaves_count: self.leaves_count,
+			nodes,
+		}
+		.encode()
+	}
+
+	/// Restore a tree from `serialize`. The state comes from outside, so the
+	/// leaves count, the node indices and values are checked and every leaf
+	/// below the count must be present
+	pub fn deserialize(state: &[u8]) -> Result<Self, OperationError> {
+		let invalid = |message: String| OperationError::new_with_message(OpStatusCode::DeserializationFailed, message);
+		let state = MerkleTreeState::decode(&mut &*state).map_err(|e| invalid(e.to_string()))?;
+		if state.leaves_count > Self::capacity() {
+			return Err(invalid(format!(
+				"{} leaves exceed the tree capacity of {} leaves",
+				state.leaves_count,
+				Self::capacity()
+			)));
+		}
+		let first_leaf = Self::capacity() - 1;
+		let mut nodes = BTreeMap::new();
+		for (index, node) in &state.nodes {
+			if *index > first_leaf + Self::capacity() - 1 {
+				return Err(invalid(format!("Node index {} is outside the tree", index)));
+			}
+			if *index >= first_leaf + state.leaves_count {
+				return Err(invalid(format!(
+					"Leaf {} is past the {} leaves of the tree",
+					index - first_leaf,
+					state.leaves_count
+				)));
+			}
+			let node = field_element_from_bytes::<Bn254Fr>(node, Endianness::Big)
+				.map_err(|e| invalid(format!("Node {}: {}", index, e.error_message)))?;
+			if nodes.insert(*index, node).is_some() {
+				return Err(invalid(format!("Node {} is repeated", index)));
+			}
+		}
+		let leaves_present = nodes.range(first_leaf..).count() as u64;
+		if leaves_present != state.leaves_count {
+			return Err(invalid(format!(
+				"{} of the {} leaves are missing",
+				state.leaves_count - leaves_present,
+				state.leaves_count
+			)));
+		}
+		let mut merkle_tree = Self::new();
+		merkle_tree.tree.tree = nodes;
+		merkle_tree.leaves_count = state.leaves_count;
+		Ok(merkle_tree)
+	}
+
+	/// The leaf at `index`, which must be below the leaves count
+	pub fn leaf(&self, index: u64) -> Result<Bn254Fr, OperationError> {
+		self.tree
+			.tree
+			.get(&(index + Self::capacity() - 1))
+			.filter(|_| index < self.leaves_count)
+			.copied()
+			.ok_or_else(|| {
+				let message = format!(
+					"Leaf index {} is out of bounds for a tree with {} leaves",
+					index, self.leaves_count
+				);
+				OperationError::new_with_message(OpStatusCode::InvalidLeafIndex, message)
+			})
+	}
+}
+
+/// Membership path of a leaf, in the `pathElements`/`pathIndices` layout
+/// used by the circuits
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct JsMerklePath {
+	#[wasm_bindgen(skip)]
+	pub index: u64,
+	#[wasm_bindgen(skip)]
+	pub leaf: Vec<u8>,
+	#[wasm_bindgen(skip)]
+	pub root: Vec<u8>,
+	#[wasm_bindgen(skip)]
+	pub path_elements: Vec<Vec<u8>>,
+	#[wasm_bindgen(skip)]
+	pub path_indices: Vec<u8>,
+}
+
+impl JsMerklePath {
+	pub fn new(index: u64, leaf: Bn254Fr, root: Bn254Fr, path: &Bn254MerklePath) -> Self {
+		let path_elements: Vec<Bn254Fr> = path
+			.path
+			.iter()
+			.enumerate()
+			.map(|(level, (left, right))| if (index >> level) & 1 == 1 { *left } else { *right })
+			.collect();
+		Self::from_path_elements(index, leaf, root, &path_elements)
+	}
+
+	/// Build the path from the sibling of each level, ordered from the leaf
+	/// up to the root
+	pub fn from_path_elements(index: u64, leaf: Bn254Fr, root: Bn254Fr, path_elements: &[Bn254Fr]) -> Self {
+		Self {
+			index,
+			leaf: leaf.into_repr().to_bytes_be(),
+			root: root.into_repr().to_bytes_be(),
+			path_elements: path_elements.iter().map(|e| e.into_repr().to_bytes_be()).collect(),
+			path_indices: (0..path_elements.len())
+				.map(|level| ((index >> level) & 1) as u8)
+				.collect(),
+		}
+	}
+}
+
+#[wasm_bindgen]
+impl JsMerklePath {
+	#[wasm_bindgen(getter)]
+	pub fn index(&self) -> JsString {
+		JsString::from(self.index.to_string())
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn leaf(&self) -> Uint8Array {
+		Uint8Array::from(self.leaf.as_slice())
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn root(&self) -> Uint8Array {
+		Uint8Array::from(self.root.as_slice())
+	}
+
+	#[wasm_bindgen(getter)]
+	#[wasm_bindgen(js_name = pathElements)]
+	pub fn path_elements(&self) -> Leaves {
+		let elements: Array = self
+			.path_elements
+			.iter()
+			.map(|element| Uint8Array::from(element.as_slice()))
+			.collect();
+		Leaves::from(JsValue::from(elements))
+	}
+
+	#[wasm_bindgen(getter)]
+	#[wasm_bindgen(js_name = pathIndices)]
+	pub fn path_indices(&self) -> Indices {
+		let indices: Array = self.path_indices.iter().map(|i| JsValue::from(*i)).collect();
+		indices.unchecked_into()
+	}
+}
+
+/// Persistent merkle tree for the JS side, leaves are inserted incrementally
+/// and the state can be serialized instead of rebuilding the tree from the
+/// leaves list for every proof
+#[wasm_bindgen]
+pub struct JsMerkleTree {
+	#[wasm_bindgen(skip)]
+	pub inner: MerkleTree,
+	#[wasm_bindgen(skip)]
+	pub live: LiveObject,
+}
+
+#[allow(clippy::unused_unit)]
+#[wasm_bindgen]
+impl JsMerkleTree {
+	#[wasm_bindgen(constructor)]
+	pub fn new(initial_leaves: Option<Leaves>) -> Result<JsMerkleTree, JsValue> {
+		let mut inner = MerkleTree::new();
+		if let Some(leaves) = initial_leaves {
+			let leaves: Vec<Bn254Fr> = leaves_from_js(&leaves)?
+				.iter()
+				.map(|leaf| Bn254Fr::from_be_bytes_mod_order(leaf))
+				.collect();
+			inner.insert_batch(&leaves)?;
+		}
+		Ok(JsMerkleTree {
+			inner,
+			live: LiveObject::new(ObjectKind::MerkleTree, 0),
+		})
+	}
+
+	/// Append a leaf and return its index
+	pub fn insert(&mut self, leaf: Uint8Array) -> Result<JsString, JsValue> {
+		let leaf = Uint8Arrayx32::try_from(leaf).map_err(|_| OpStatusCode::InvalidLeaves)?;
+		let index = self.inner.insert_batch(&[Bn254Fr::from_be_bytes_mod_order(&leaf.0)])?;
+		Ok(JsString::from(index.to_string()))
+	}
+
+	/// Append the leaves and return the index of the first one
+	#[wasm_bindgen(js_name = insertBatch)]
+	pub fn insert_batch(&mut self, leaves: Leaves) -> Result<JsString, JsValue> {
+		let leaves: Vec<Bn254Fr> = leaves_from_js(&leaves)?
+			.iter()
+			.map(|leaf| Bn254Fr::from_be_bytes_mod_order(leaf))
+			.collect();
+		let index = self.inner.insert_batch(&leaves)?;
+		Ok(JsString::from(index.to_string()))
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn root(&self) -> Uint8Array {
+		Uint8Array::from(self.inner.root().into_repr().to_bytes_be().as_slice())
+	}
+
+	#[wasm_bindgen(getter)]
+	#[wasm_bindgen(js_name = leavesCount)]
+	pub fn leaves_count(&self) -> JsString {
+		JsString::from(self.inner.leaves_count.to_string())
+	}
+
+	#[wasm_bindgen(js_name = generatePath)]
+	pub fn generate_path(&self, index: JsString) -> Result<JsMerklePath, JsValue> {
+		let index: String = index.into();
+		let index: u64 = index.parse().map_err(|_| OpStatusCode::InvalidLeafIndex)?;
+		let path = self.inner.path(index)?;
+		let leaf = self.inner.leaf(index)?;
+		Ok(JsMerklePath::new(index, leaf, self.inner.root(), &path))
+	}
+
+	pub fn serialize(&self) -> Uint8Array {
+		Uint8Array::from(self.inner.serialize().as_slice())
+	}
+
+	pub fn deserialize(state: Uint8Array) -> Result<JsMerkleTree, JsValue> {
+		let inner = MerkleTree::deserialize(&state.to_vec())?;
+		Ok(JsMerkleTree {
+			inner,
+			live: LiveObject::new(ObjectKind::MerkleTree, 0),
+		})
+	}
+}
+
+/// Roots of the tree holding `existing_leaves` after each of `new_leaves` is
+/// appended, in insertion order. Used to check the on-chain root history.
+#[wasm_bindgen]
+pub fn compute_roots_after_inserts(existing_leaves: Leaves, new_leaves: Leaves) -> Result<Leaves, JsValue> {
+	let to_field = |leaves: &Leaves| -> Result<Vec<Bn254Fr>, OperationError> {
+		Ok(leaves_from_js(leaves)?
+			.iter()
+			.map(|leaf| Bn254Fr::from_be_bytes_mod_order(leaf))
+			.collect())
+	};
+	let mut tree = MerkleTree::new();
+	tree.insert_batch(&to_field(&existing_leaves)?)?;
+	let roots: Array = tree
+		.insert_with_roots(&to_field(&new_leaves)?)?
+		.iter()
+		.map(|root| Uint8Array::from(root.into_repr().to_bytes_be().as_slice()))
+		.collect();
+	Ok(Leaves::from(JsValue::from(roots)))
+}
+
+/// Hash a merkle path from `leaf` up to the root, `path_indices[i]` is `1`
+/// when the node at level `i` is the right child
+pub fn calculate_root<F: PrimeField, H: FieldHasher<F>>(
+	hasher: &H,
+	leaf: F,
+	path_elements: &[F],
+	path_indices: &[u8],
+) -> Result<F, OperationError> {
+	if path_elements.len() != path_indices.len() {
+		let message = format!(
+			"Path has {} elements but {} indices",
+			path_elements.len(),
+			path_indices.len()
+		);
+		return Err(OperationError::new_with_message(
+			OpStatusCode::InvalidProofParameters,
+			message,
+		));
+	}
+	let mut node = leaf;
+	for (sibling, is_right) in path_elements.iter().zip(path_indices) {
+		node = match is_right {
+			0 => hasher.hash_two(&node, sibling),
+			1 => hasher.hash_two(sibling, &node),
+			_ => return Err(OpStatusCode::InvalidIndices.into()),
+		}
+		.map_err(|e| OperationError::new_with_message(OpStatusCode::InvalidProofParameters, e.to_string()))?;
+	}
+	Ok(node)
+}
+
+fn verify_path<F: PoseidonField>(
+	exp: i8,
+	width: u8,
+	leaf: &[u8],
+	path_elements: &[[u8; 32]],
+	path_indices: &[u8],
+	root: &[u8],
+) -> Result<bool, OperationError> {
+	// Non canonical encodings would be reduced and verify as another element
+	let leaf = field_element_from_bytes::<F>(leaf, Endianness::Big)?;
+	let root = field_element_from_bytes::<F>(root, Endianness::Big)?;
+	let path_elements = path_elements
+		.iter()
+		.map(|e| field_element_from_bytes::<F>(e, Endianness::Big))
+		.collect::<Result<Vec<F>, _>>()?;
+	let hasher = Poseidon::new(poseidon_params::<F>(exp, width));
+	let computed_root = calculate_root(&hasher, leaf, &path_elements, path_indices)?;
+	Ok(computed_root == root)
+}
+
+/// Verify that `leaf` is included in the tree with `root`, using the same
+/// Poseidon parameters as the prover
+#[wasm_bindgen]
+pub fn verify_merkle_proof(
+	leaf: Uint8Array,
+	path_elements: Leaves,
+	path_indices: Indices,
+	root: Uint8Array,
+	curve: WasmCurve,
+	exp: i8,
+	width: u8,
+) -> Result<bool, JsValue> {
+	let curve: Curve = JsValue::from(curve)
+		.as_string()
+		.ok_or(OpStatusCode::InvalidCurve)?
+		.parse()?;
+	let leaf = Uint8Arrayx32::try_from(leaf).map_err(|_| OpStatusCode::InvalidLeaves)?;
+	let root = Uint8Arrayx32::try_from(root).map_err(|_| OpStatusCode::InvalidRoots)?;
+	let path_elements = leaves_from_js(&path_elements)?;
+	let path_indices = Array::from(&path_indices)
+		.iter()
+		.map(|i| match i.as_f64() {
+			Some(i) if i == 0.0 || i == 1.0 => Ok(i as u8),
+			_ => Err(OpStatusCode::InvalidIndices),
+		})
+		.collect::<Result<Vec<u8>, _>>()?;
+
+	ensure_poseidon_params(curve, exp, width)?;
+	let is_valid = match curve {
+		Curve::Bn254 => verify_path::<Bn254Fr>(exp, width, &leaf.0, &path_elements, &path_indices, &root.0),
+		Curve::Bls381 => verify_path::<Bls381Fr>(exp, width, &leaf.0, &path_elements, &path_indices, &root.0),
+	}?;
+	Ok(is_valid)
+}
+
+/// Root of the tree of `depth` levels holding `leaves` from index 0, the
+/// empty leaves being `DEFAULT_LEAF`
+pub fn compute_root<F: PoseidonField>(
+	exp: i8,
+	width: u8,
+	leaves: &[[u8; 32]],
+	depth: usize,
+) -> Result<F, OperationError> {
+	if depth == 0 || depth > 64 || (depth < 64 && leaves.len() as u64 > 1 << depth) {
+		let message = format!("{} leaves don't fit in a tree of depth {}", leaves.len(), depth);
+		return Err(OperationError::new_with_message(OpStatusCode::InvalidLeaves, message));
+	}
+	let hasher = Poseidon::new(poseidon_params::<F>(exp, width));
+	let hash = |left: &F, right: &F| {
+		hasher
+			.hash_two(left, right)
+			.map_err(|e| OperationError::new_with_message(OpStatusCode::InvalidProofParameters, e.to_string()))
+	};
+	let mut zero = F::from_be_bytes_mod_order(&DEFAULT_LEAF);
+	let mut nodes: Vec<F> = leaves.iter().map(|leaf| F::from_be_bytes_mod_order(leaf)).collect();
+	for _ in 0..depth {
+		nodes = nodes
+			.chunks(2)
+			.map(|pair| hash(&pair[0], pair.get(1).unwrap_or(&zero)))
+			.collect::<Result<_, _>>()?;
+		zero = hash(&zero, &zero)?;
+	}
+	Ok(nodes.pop().unwrap_or(zero))
+}
+
+/// Check that `leaves`, in insertion order, build the tree with `root`.
+/// Catches a leaf set that was tampered with or is out of date before
+/// spending seconds on a proof that can't verify.
+#[wasm_bindgen(js_name = verifyLeavesAgainstRoot)]
+pub fn verify_leaves_against_root(
+	leaves: Leaves,
+	root: Uint8Array,
+	curve: WasmCurve,
+	exp: i8,
+	width: u8,
+	depth: usize,
+) -> Result<bool, JsValue> {
+	let curve: Curve = JsValue::from(curve)
+		.as_string()
+		.ok_or(OpStatusCode::InvalidCurve)?
+		.parse()?;
+	let root = Uint8Arrayx32::try_from(root).map_err(|_| OpStatusCode::InvalidRoots)?;
+	let leaves = leaves_from_js(&leaves)?;
+
+	ensure_poseidon_params(curve, exp, width)?;
+	let is_valid = match curve {
+		Curve::Bn254 => {
+			compute_root::<Bn254Fr>(exp, width, &leaves, depth)? == Bn254Fr::from_be_bytes_mod_order(&root.0)
+		}
+		Curve::Bls381 => {
+			compute_root::<Bls381Fr>(exp, width, &leaves, depth)? == Bls381Fr::from_be_bytes_mod_order(&root.0)
+		}
+	};
+	Ok(is_valid)
+}
+
+#[cfg(test)]
+mod test {
+	use ark_ff::FpParameters;
+	use arkworks_setups::common::setup_tree_and_create_path;
+	use num_bigint::BigUint;
+	use wasm_bindgen_test::*;
+
+	use super::*;
+
+	fn leaves(count: u64) -> Vec<Bn254Fr> {
+		(1..=count).map(Bn254Fr::from).collect()
+	}
+
+	fn js_leaves(leaves: &[Bn254Fr]) -> Leaves {
+		let leaves: Array = leaves
+			.iter()
+			.map(|leaf| Uint8Array::from(leaf.into_repr().to_bytes_be().as_slice()))
+			.collect();
+		Leaves::from(JsValue::from(leaves))
+	}
+
+	#[wasm_bindgen_test]
+	fn should_read_packed_leaves() {
+		let leaves = leaves(3);
+		let expected: Vec<Vec<u8>> = leaves_from_js(&js_leaves(&leaves))
+			.unwrap()
+			.iter()
+			.map(|leaf| leaf.to_vec())
+			.collect();
+		let packed = Uint8Array::from(expected.concat().as_slice());
+		assert_eq!(leaves_from_packed(&packed, LEAF_SIZE).unwrap(), expected);
+
+		// More than one chunk, the last one partial
+		let count = PACKED_LEAVES_CHUNK / LEAF_SIZE + 3;
+		let bytes: Vec<u8> = (0..count * LEAF_SIZE).map(|i| (i / LEAF_SIZE) as u8).collect();
+		let packed = leaves_from_packed(&Uint8Array::from(bytes.as_slice()), LEAF_SIZE).unwrap();
+		assert_eq!(packed.len(), count);
+		assert!(packed.iter().enumerate().all(|(i, leaf)| leaf == &[i as u8; LEAF_SIZE]));
+
+		let error = leaves_from_packed(&Uint8Array::new_with_length(64), 16).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::InvalidLeaves);
+		let error = leaves_from_packed(&Uint8Array::new_with_length(33), LEAF_SIZE).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::InvalidLeaves);
+		assert!(leaves_from_packed(&Uint8Array::new_with_length(0), LEAF_SIZE)
+			.unwrap()
+			.is_empty());
+		assert!(ensure_leaf_count(MAX_LEAVES).is_ok());
+		assert_eq!(
+			ensure_leaf_count(MAX_LEAVES + 1).unwrap_err().code,
+			OpStatusCode::LimitExceeded
+		);
+	}
+
+	#[wasm_bindgen_test]
+	fn should_match_the_tree_built_from_all_leaves() {
+		let hasher = bn254_tree_hasher();
+		let leaves = leaves(5);
+		let (expected_tree, expected_path) =
+			setup_tree_and_create_path::<Bn254Fr, Poseidon<Bn254Fr>, TREE_HEIGHT>(&hasher, &leaves, 3, &DEFAULT_LEAF)
+				.unwrap();
+
+		let mut tree = MerkleTree::new();
+		assert_eq!(tree.insert_batch(&leaves[..2]).unwrap(), 0);
+		assert_eq!(tree.insert_batch(&leaves[2..3]).unwrap(), 2);
+		assert_eq!(tree.insert_batch(&leaves[3..]).unwrap(), 3);
+
+		assert_eq!(tree.root(), expected_tree.root());
+		assert_eq!(tree.path(3).unwrap().path, expected_path.path);
+		assert!(tree.path(5).is_err());
+	}
+
+	#[wasm_bindgen_test]
+	fn should_generate_a_valid_js_path() {
+		let hasher = bn254_tree_hasher();
+		let mut tree = MerkleTree::new();
+		tree.insert_batch(&leaves(3)).unwrap();
+		let path = JsMerklePath::new(2, Bn254Fr::from(3u64), tree.root(), &tree.path(2).unwrap());
+
+		assert_eq!(path.path_indices[..3], [0, 1, 0]);
+		let path_elements: Vec<Bn254Fr> = path
+			.path_elements
+			.iter()
+			.map(|e| Bn254Fr::from_be_bytes_mod_order(e))
+			.collect();
+		let root = calculate_root(&hasher, Bn254Fr::from(3u64), &path_elements, &path.path_indices).unwrap();
+		assert_eq!(root.into_repr().to_bytes_be(), path.root);
+	}
+
+	#[wasm_bindgen_test]
+	fn should_verify_merkle_proof() {
+		let mut tree = JsMerkleTree::new(Some(js_leaves(&leaves(3)))).unwrap();
+		tree.insert(Uint8Array::from(
+			Bn254Fr::from(9u64).into_repr().to_bytes_be().as_slice(),
+		))
+		.unwrap();
+		let path = tree.generate_path(JsString::from("3")).unwrap();
+		let curve: WasmCurve = JsValue::from(Curve::Bn254.to_string()).into();
+		let indices = || Indices::from(JsValue::from(path.path_indices()));
+		let code = |error: &JsValue| {
+			js_sys::Reflect::get(error, &JsValue::from_str("code"))
+				.unwrap()
+				.as_string()
+				.unwrap()
+		};
+
+		let is_valid = verify_merkle_proof(
+			path.leaf(),
+			path.path_elements(),
+			indices(),
+			tree.root(),
+			curve.clone(),
+			5,
+			3,
+		)
+		.unwrap();
+		assert!(is_valid);
+
+		let wrong_leaf = Uint8Array::from(Bn254Fr::from(8u64).into_repr().to_bytes_be().as_slice());
+		let is_valid = verify_merkle_proof(
+			wrong_leaf,
+			path.path_elements(),
+			indices(),
+			tree.root(),
+			curve.clone(),
+			5,
+			3,
+		)
+		.unwrap();
+		assert!(!is_valid);
+
+		assert!(verify_merkle_proof(
+			path.leaf(),
+			path.path_elements(),
+			indices(),
+			tree.root(),
+			curve.clone(),
+			17,
+			3
+		)
+		.is_err());
+
+		// The leaf plus the modulus reduces to the same element
+		let mut shifted = BigUint::from_bytes_be(&path.leaf().to_vec());
+		let modulus: BigUint = <Bn254Fr as PrimeField>::Params::MODULUS.into();
+		shifted += modulus;
+		let shifted = Uint8Array::from(shifted.to_bytes_be().as_slice());
+		let error = verify_merkle_proof(
+			shifted,
+			path.path_elements(),
+			indices(),
+			tree.root(),
+			curve.clone(),
+			5,
+			3,
+		)
+		.unwrap_err();
+		assert_eq!(code(&error), "InvalidFieldElement");
+
+		let fractional = Array::from(&path.path_indices());
+		fractional.set(0, JsValue::from(0.5f64));
+		let error = verify_merkle_proof(
+			path.leaf(),
+			path.path_elements(),
+			Indices::from(JsValue::from(fractional)),
+			tree.root(),
+			curve,
+			5,
+			3,
+		)
+		.unwrap_err();
+		assert_eq!(code(&error), "InvalidIndices");
+	}
+
+	#[wasm_bindgen_test]
+	fn should_compute_roots_after_inserts() {
+		let all_leaves = leaves(4);
+		let roots = compute_roots_after_inserts(js_leaves(&all_leaves[..1]), js_leaves(&all_leaves[1..])).unwrap();
+		let roots: Vec<Vec<u8>> = Array::from(&roots)
+			.iter()
+			.map(|root| Uint8Array::from(root).to_vec())
+			.collect();
+		assert_eq!(roots.len(), 3);
+
+		for (i, root) in roots.iter().enumerate() {
+			let mut tree = MerkleTree::new();
+			tree.insert_batch(&all_leaves[..i + 2]).unwrap();
+			assert_eq!(*root, tree.root().into_repr().to_bytes_be());
+		}
+	}
+
+	#[wasm_bindgen_test]
+	fn should_restore_serialized_tree() {
+		let mut tree = MerkleTree::new();
+		tree.insert_batch(&leaves(4)).unwrap();
+		let mut restored = MerkleTree::deserialize(&tree.serialize()).unwrap();
+		assert_eq!(restored.root(), tree.root());
+		assert_eq!(restored.leaves_count, 4);
+
+		tree.insert_batch(&leaves(2)).unwrap();
+		restored.insert_batch(&leaves(2)).unwrap();
+		assert_eq!(restored.root(), tree.root());
+		assert!(MerkleTree::deserialize(&[1, 2, 3]).is_err());
+	}
+
+	#[wasm_bindgen_test]
+	fn should_reject_invalid_serialized_tree() {
+		let mut tree = MerkleTree::new();
+		tree.insert_batch(&leaves(3)).unwrap();
+		let MerkleTreeState { leaves_count, nodes } = MerkleTreeState::decode(&mut &*tree.serialize()).unwrap();
+		let first_leaf = MerkleTree::capacity() - 1;
+		let deserialize = |leaves_count: u64, nodes: Vec<(u64, [u8; 32])>| {
+			let state = MerkleTreeState { leaves_count, nodes }.encode();
+			MerkleTree::deserialize(&state).err().unwrap().code
+		};
+
+		// A count above the leaves held used to panic in generatePath
+		assert_eq!(
+			deserialize(leaves_count + 1, nodes.clone()),
+			OpStatusCode::DeserializationFailed
+		);
+		assert_eq!(
+			deserialize(MerkleTree::capacity() + 1, vec![]),
+			OpStatusCode::DeserializationFailed
+		);
+		let missing: Vec<_> = nodes.iter().filter(|(i, _)| *i != first_leaf + 1).cloned().collect();
+		assert_eq!(deserialize(leaves_count, missing), OpStatusCode::DeserializationFailed);
+		let mut past_count = nodes.clone();
+		past_count.push((first_leaf + leaves_count, [0; 32]));
+		assert_eq!(
+			deserialize(leaves_count, past_count),
+			OpStatusCode::DeserializationFailed
+		);
+		let mut outside = nodes.clone();
+		outside.push((2 * MerkleTree::capacity() - 1, [0; 32]));
+		assert_eq!(deserialize(leaves_count, outside), OpStatusCode::DeserializationFailed);
+		let mut above_modulus = nodes.clone();
+		above_modulus[0].1 = [0xff; 32];
+		assert_eq!(
+			deserialize(leaves_count, above_modulus),
+			OpStatusCode::DeserializationFailed
+		);
+		let mut repeated = nodes;
+		repeated.push(repeated[0]);
+		assert_eq!(deserialize(leaves_count, repeated), OpStatusCode::DeserializationFailed);
+
+		let js_tree = JsMerkleTree::new(Some(js_leaves(&leaves(2)))).unwrap();
+		assert!(js_tree.generate_path(JsString::from("2")).is_err());
+	}
+
+	#[wasm_bindgen_test]
+	fn should_append_leaves_to_legacy_tree() {
+		let all_leaves = leaves(5);
+		let mut legacy = crate::proof::MTBn254X5::new(js_leaves(&all_leaves[..2]), JsString::from("0")).unwrap();
+		legacy.insert(js_leaves(&all_leaves[2..])).unwrap();
+
+		let mut tree = MerkleTree::new();
+		tree.insert_batch(&all_leaves).unwrap();
+		assert_eq!(legacy.inner.root(), tree.root());
+	}
+
+	#[wasm_bindgen_test]
+	fn should_verify_leaves_against_root() {
+		let all_leaves = leaves(5);
+		let mut tree = MerkleTree::new();
+		tree.insert_batch(&all_leaves).unwrap();
+		let root = Uint8Array::from(tree.root().into_repr().to_bytes_be().as_slice());
+		let verify = |leaves: &[Bn254Fr]| {
+			let curve = JsValue::from("Bn254").into();
+			verify_leaves_against_root(js_leaves(leaves), root.clone(), curve, 5, 3, TREE_HEIGHT).unwrap()
+		};
+		assert!(verify(&all_leaves));
+		// Stale and tampered leaf sets
+		assert!(!verify(&all_leaves[..4]));
+		let mut tampered = all_leaves.clone();
+		tampered[1] = Bn254Fr::from(9u64);
+		assert!(!verify(&tampered));
+
+		let bls_leaves: Vec<Bls381Fr> = (1..=3u64).map(Bls381Fr::from).collect();
+		let hasher = Poseidon::new(poseidon_params::<Bls381Fr>(5, 3));
+		let (expected, _) =
+			setup_tree_and_create_path::<Bls381Fr, Poseidon<Bls381Fr>, 3>(&hasher, &bls_leaves, 0, &DEFAULT_LEAF)
+				.unwrap();
+		let raw: Vec<[u8; 32]> = bls_leaves
+			.iter()
+			.map(|leaf| leaf.into_repr().to_bytes_be().try_into().unwrap())
+			.collect();
+		assert_eq!(compute_root::<Bls381Fr>(5, 3, &raw, 3).unwrap(), expected.root());
+
+		let error = compute_root::<Bn254Fr>(5, 3, &[DEFAULT_LEAF; 3], 1).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::InvalidLeaves);
+	}
+}
@@ -0,0 +1,525 @@
+use core::convert::TryFrom;
+use core::fmt;
+use core::str::FromStr;
+
+use js_sys::JsString;
+use wasm_bindgen::prelude::*;
+
+use crate::types::{OpStatusCode, OperationError};
+
+/// Token amount in base units (wei, planck, ...). All arithmetic is checked,
+/// so JS callers never have to go through floating point.
+#[wasm_bindgen(js_name = NoteAmount)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct NoteAmount {
+	#[wasm_bindgen(skip)]
+	pub inner: u128,
+}
+
+fn overflow() -> OperationError {
+	OperationError::new_with_message(
+		OpStatusCode::AmountOverflow,
+		"Amount does not fit in a u128".to_string(),
+	)
+}
+
+fn unit(decimals: u8) -> Result<u128, OperationError> {
+	10u128.checked_pow(decimals.into()).ok_or_else(overflow)
+}
+
+/// How an amount moved to fewer decimals is rounded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+	/// Fail with `AmountPrecisionLoss` rather than drop any dust
+	Exact,
+	Down,
+	Up,
+	/// Half way amounts go to the even neighbour, banker's rounding
+	HalfEven,
+}
+
+impl fmt::Display for Rounding {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Rounding::Exact => write!(f, "exact"),
+			Rounding::Down => write!(f, "down"),
+			Rounding::Up => write!(f, "up"),
+			Rounding::HalfEven => write!(f, "halfEven"),
+		}
+	}
+}
+
+impl FromStr for Rounding {
+	type Err = OperationError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"exact" => Ok(Rounding::Exact),
+			"down" => Ok(Rounding::Down),
+			"up" => Ok(Rounding::Up),
+			"halfEven" => Ok(Rounding::HalfEven),
+			_ => {
+				let message = format!("Unknown rounding {}", s);
+				Err(OperationError::new_with_message(OpStatusCode::InvalidAmount, message))
+			}
+		}
+	}
+}
+
+impl NoteAmount {
+	pub fn new(inner: u128) -> Self {
+		Self { inner }
+	}
+
+	/// Parse a human readable amount such as `1.5` into base units for a token
+	/// with `decimals` decimals.
+	pub fn from_decimal(value: &str, decimals: u8) -> Result<Self, OperationError> {
+		let invalid = || {
+			OperationError::new_with_message(OpStatusCode::InvalidAmount, format!("Invalid decimal amount {}", value))
+		};
+		let (whole, fraction) = match value.split_once('.') {
+			Some((whole, fraction)) => (whole, fraction),
+			None => (value, ""),
+		};
+		let all_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+		if (whole.is_empty() && fraction.is_empty()) || !all_digits(whole) || !all_digits(fraction) {
+			return Err(invalid());
+		}
+		// Trailing zeros don't carry precision, `1.50` fits 1 decimal
+		let fraction = fraction.trim_end_matches('0');
+		if fraction.len() > usize::from(decimals) {
+			let message = format!("Amount {} has more than {} decimals", value, decimals);
+			return Err(OperationError::new_with_message(
+				OpStatusCode::AmountPrecisionLoss,
+				message,
+			));
+		}
+
+		// Both scales are at most `10^decimals`, so only the whole part can overflow
+		let whole_scale = unit(decimals)?;
+		let fraction_scale = unit(decimals - fraction.len() as u8)?;
+		let whole: u128 = match whole {
+			"" => 0,
+			whole => whole.parse().map_err(|_| overflow())?,
+		};
+		let fraction: u128 = match fraction {
+			"" => 0,
+			fraction => fraction.parse().map_err(|_| invalid())?,
+		};
+		let inner = whole
+			.checked_mul(whole_scale)
+			.and_then(|whole| whole.checked_add(fraction * fraction_scale))
+			.ok_or_else(overflow)?;
+
+		Ok(Self { inner })
+	}
+
+	/// Parse an amount entered by a user or a relayer: `0x` prefixed hex is
+	/// always in base units, decimal strings are base units unless the token
+	/// `decimals` are given, in which case they are token amounts like `0.25`
+	pub fn parse_with_decimals(value: &str, decimals: Option<u8>) -> Result<Self, OperationError> {
+		if let Some(hex) = value.strip_prefix("0x") {
+			if hex.is_empty() || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+				let message = format!("Invalid hex amount {}", value);
+				return Err(OperationError::new_with_message(OpStatusCode::InvalidAmount, message));
+			}
+			return u128::from_str_radix(hex, 16)
+				.map(NoteAmount::new)
+				.map_err(|_| overflow());
+		}
+		match decimals {
+			Some(decimals) => NoteAmount::from_decimal(value, decimals),
+			None => value.parse(),
+		}
+	}
+
+	/// Render the amount with `decimals` decimals, dropping trailing zeros
+	pub fn format(&self, decimals: u8) -> Result<String, OperationError> {
+		let unit = unit(decimals)?;
+		let whole = self.inner / unit;
+		let fraction = self.inner % unit;
+		if fraction == 0 {
+			return Ok(whole.to_string());
+		}
+
+		let fraction = format!("{:0width$}", fraction, width = usize::from(decimals));
+		Ok(format!("{}.{}", whole, fraction.trim_end_matches('0')))
+	}
+
+	pub fn checked_add(&self, other: &NoteAmount) -> Result<NoteAmount, OperationError> {
+		self.inner
+			.checked_add(other.inner)
+			.map(NoteAmount::new)
+			.ok_or_else(overflow)
+	}
+
+	/// Subtract `other`, erroring when it is larger than `self` (e.g. when
+	/// the outputs of a VAnchor transaction exceed its inputs)
+	pub fn checked_sub(&self, other: &NoteAmount) -> Result<NoteAmount, OperationError> {
+		self.inner.checked_sub(other.inner).map(NoteAmount::new).ok_or_else(|| {
+			let message = format!("Cannot subtract {} from {}", other, self);
+			OperationError::new_with_message(OpStatusCode::AmountOverflow, message)
+		})
+	}
+
+	/// The amount in base units of a token with `from` decimals as base units
+	/// of the same amount with `to` decimals, e.g. a bridged asset of 18
+	/// decimals on one chain and 12 on the other. Going to fewer decimals
+	/// drops the dust below the new unit as `rounding` says, `Exact` fails
+	/// instead with the amount rounded down and the dust as error data
+	pub fn convert_decimals(&self, from: u8, to: u8, rounding: Rounding) -> Result<NoteAmount, OperationError> {
+		if to >= from {
+			return self
+				.inner
+				.checked_mul(unit(to - from)?)
+				.map(NoteAmount::new)
+				.ok_or_else(overflow);
+		}
+		// A scale above `u128::MAX` leaves nothing of any amount
+		let (down, dust, above_half, half) = match unit(from - to) {
+			Ok(scale) => {
+				let dust = self.inner % scale;
+				(self.inner / scale, dust, dust > scale - dust, dust == scale - dust)
+			}
+			Err(_) => (0, self.inner, false, false),
+		};
+		if dust == 0 {
+			return Ok(NoteAmount::new(down));
+		}
+		let up = match rounding {
+			Rounding::Exact => {
+				let message = format!("Converting {} from {} to {} decimals loses {}", self, from, to, dust);
+				let mut error = OperationError::new_with_message(OpStatusCode::AmountPrecisionLoss, message);
+				error.data = Some(format!("{{\"amount\":\"{}\",\"dust\":\"{}\"}}", down, dust));
+				return Err(error);
+			}
+			Rounding::Down => false,
+			Rounding::Up => true,
+			Rounding::HalfEven => above_half || (half && down % 2 == 1),
+		};
+		// `down` is at most `u128::MAX / 10`, one more can't overflow
+		Ok(NoteAmount::new(if up { down + 1 } else { down }))
+	}
+
+	/// Base asset to wrap for `self` of the bridge token once the token
+	/// wrapper keeps `fee_percentage`, rounded down as `getAmountToWrap` of
+	/// the wrapper contract
+	pub fn amount_to_wrap(&self, fee_percentage: u8) -> Result<NoteAmount, OperationError> {
+		let fee_percentage = check_wrapping_fee(fee_percentage)?;
+		let amount = self.inner.checked_mul(100).ok_or_else(overflow)?;
+		Ok(NoteAmount::new(amount / u128::from(100 - fee_percentage)))
+	}
+}
+
+/// A wrapping fee keeping the whole amount leaves nothing to deposit
+pub fn check_wrapping_fee(fee_percentage: u8) -> Result<u8, OperationError> {
+	if fee_percentage >= 100 {
+		let message = format!("The wrapping fee is {}%, it should be below 100%", fee_percentage);
+		return Err(OperationError::new_with_message(OpStatusCode::InvalidFee, message));
+	}
+	Ok(fee_percentage)
+}
+
+/// Report malformed amounts with the code of the field they were given for,
+/// overflow and precision loss keep their own codes
+pub fn amount_field_error(code: OpStatusCode) -> impl Fn(OperationError) -> OperationError {
+	move |mut error| {
+		if error.code == OpStatusCode::InvalidAmount {
+			error.code = code.clone();
+		}
+		error
+	}
+}
+
+/// Parse the unsigned amount of a field such as the fee, malformed values
+/// are reported with `code`
+pub fn parse_amount_field(value: &str, decimals: Option<u8>, code: OpStatusCode) -> Result<u128, OperationError> {
+	NoteAmount::parse_with_decimals(value, decimals)
+		.map(|amount| amount.inner)
+		.map_err(amount_field_error(code))
+}
+
+/// Parse the amount given to the note builder. Integers such as `10` or
+/// `0x0a` are base units, amounts with a decimal point or a unit such as
+/// `10.5` or `10.5 WEBB` are token amounts scaled by the token `decimals`.
+/// Returns the unit too, to check against the token symbol
+pub fn parse_note_amount(value: &str, decimals: Option<u8>) -> Result<(NoteAmount, Option<&str>), OperationError> {
+	let value = value.trim();
+	let (number, unit) = match value.split_once(char::is_whitespace) {
+		Some((number, unit)) => (number, Some(unit.trim_start())),
+		None => (value, None),
+	};
+	if unit.map_or(false, |unit| unit.contains(char::is_whitespace)) {
+		let message = format!("Invalid amount {}", value);
+		return Err(OperationError::new_with_message(OpStatusCode::InvalidAmount, message));
+	}
+	if unit.is_none() && !number.contains('.') {
+		return Ok((NoteAmount::parse_with_decimals(number, None)?, None));
+	}
+	let decimals = decimals.ok_or_else(|| {
+		let message = format!("Token amount {} needs the token denomination", value);
+		OperationError::new_with_message(OpStatusCode::InvalidDenomination, message)
+	})?;
+	Ok((NoteAmount::from_decimal(number, decimals)?, unit))
+}
+
+/// Parse a signed amount, such as the VAnchor `extAmount` or public amount,
+/// a leading `-` makes it a withdrawal. See
+/// [`NoteAmount::parse_with_decimals`].
+pub fn parse_signed_amount(value: &str, decimals: Option<u8>) -> Result<i128, OperationError> {
+	let (negative, magnitude) = match value.strip_prefix('-') {
+		Some(magnitude) => (true, magnitude),
+		None => (false, value),
+	};
+	let magnitude = NoteAmount::parse_with_decimals(magnitude, decimals)?.inner;
+	let overflow = || {
+		OperationError::new_with_message(
+			OpStatusCode::AmountOverflow,
+			"Amount does not fit in an i128".to_string(),
+		)
+	};
+	if negative {
+		// `i128::MIN` has no positive counterpart
+		if magnitude == i128::MIN.unsigned_abs() {
+			return Ok(i128::MIN);
+		}
+		i128::try_from(magnitude)
+			.map(|magnitude| -magnitude)
+			.map_err(|_| overflow())
+	} else {
+		i128::try_from(magnitude).map_err(|_| overflow())
+	}
+}
+
+impl FromStr for NoteAmount {
+	type Err = OperationError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+			let message = format!("Invalid amount {}", s);
+			return Err(OperationError::new_with_message(OpStatusCode::InvalidAmount, message));
+		}
+		s.parse().map(NoteAmount::new).map_err(|_| overflow())
+	}
+}
+
+impl fmt::Display for NoteAmount {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.inner)
+	}
+}
+
+impl From<u128> for NoteAmount {
+	fn from(inner: u128) -> Self {
+		Self { inner }
+	}
+}
+
+#[allow(clippy::unused_unit)]
+#[wasm_bindgen(js_class = NoteAmount)]
+impl NoteAmount {
+	/// Amount from a base unit integer string
+	#[wasm_bindgen(constructor)]
+	pub fn js_new(amount: JsString) -> Result<NoteAmount, JsValue> {
+		let amount: String = amount.into();
+		Ok(amount.parse()?)
+	}
+
+	/// Convert a decimal amount (e.g. `"1.5"`) into base units for
+	/// `denomination` decimals
+	#[wasm_bindgen(js_name = toWei)]
+	pub fn to_wei(value: JsString, denomination: u8) -> Result<NoteAmount, JsValue> {
+		let value: String = value.into();
+		Ok(NoteAmount::from_decimal(&value, denomination)?)
+	}
+
+	#[wasm_bindgen(js_name = format)]
+	pub fn js_format(&self, decimals: u8) -> Result<JsString, JsValue> {
+		Ok(self.format(decimals)?.into())
+	}
+
+	pub fn add(&self, other: &NoteAmount) -> Result<NoteAmount, JsValue> {
+		Ok(self.checked_add(other)?)
+	}
+
+	pub fn sub(&self, other: &NoteAmount) -> Result<NoteAmount, JsValue> {
+		Ok(self.checked_sub(other)?)
+	}
+
+	#[wasm_bindgen(js_name = toString)]
+	pub fn to_js_string(&self) -> JsString {
+		self.to_string().into()
+	}
+}
+
+#[wasm_bindgen(typescript_custom_section)]
+const ROUNDING: &str = "export type Rounding = 'exact' | 'down' | 'up' | 'halfEven';";
+
+#[wasm_bindgen]
+extern "C" {
+	#[wasm_bindgen(typescript_type = "Rounding")]
+	pub type WasmRounding;
+}
+
+/// Convert `amount`, in base units of a token with `fromDecimals` decimals,
+/// to base units of `toDecimals` decimals. The dust lost going to fewer
+/// decimals fails the conversion with `AmountPrecisionLoss` unless a
+/// `rounding` other than `exact` is given
+#[wasm_bindgen(js_name = convertAmountBetweenDecimals)]
+pub fn convert_amount_between_decimals(
+	amount: JsString,
+	from_decimals: u8,
+	to_decimals: u8,
+	rounding: Option<WasmRounding>,
+) -> Result<NoteAmount, JsValue> {
+	let amount = NoteAmount::parse_with_decimals(&String::from(amount), None)?;
+	let rounding = match rounding {
+		Some(rounding) => JsValue::from(rounding)
+			.as_string()
+			.ok_or(OpStatusCode::InvalidAmount)?
+			.parse()?,
+		None => Rounding::Exact,
+	};
+	Ok(amount.convert_decimals(from_decimals, to_decimals, rounding)?)
+}
+
+#[cfg(test)]
+mod test {
+	use wasm_bindgen_test::*;
+
+	use super::*;
+
+	#[wasm_bindgen_test]
+	fn should_convert_decimal_amounts() {
+		assert_eq!(
+			NoteAmount::from_decimal("1.5", 18).unwrap().inner,
+			1_500_000_000_000_000_000
+		);
+		assert_eq!(NoteAmount::from_decimal("10", 12).unwrap().inner, 10_000_000_000_000);
+		assert_eq!(NoteAmount::from_decimal(".25", 2).unwrap().inner, 25);
+		assert_eq!(
+			NoteAmount::from_decimal("0.1", 0).err().unwrap().code,
+			OpStatusCode::AmountPrecisionLoss
+		);
+		assert_eq!(NoteAmount::from_decimal("2.50", 1).unwrap().inner, 25);
+		assert_eq!(
+			NoteAmount::from_decimal("1e18", 18).err().unwrap().code,
+			OpStatusCode::InvalidAmount
+		);
+		assert_eq!(
+			NoteAmount::from_decimal(".", 18).err().unwrap().code,
+			OpStatusCode::InvalidAmount
+		);
+		assert_eq!(
+			NoteAmount::from_decimal("1000000000000000000000", 18)
+				.err()
+				.unwrap()
+				.code,
+			OpStatusCode::AmountOverflow
+		);
+
+		// 1% of 1000 wrapped leaves 990 of the bridge token
+		assert_eq!(NoteAmount::new(990).amount_to_wrap(1).unwrap().inner, 1000);
+		assert_eq!(NoteAmount::new(990).amount_to_wrap(0).unwrap().inner, 990);
+		assert_eq!(
+			NoteAmount::new(1).amount_to_wrap(100).err().unwrap().code,
+			OpStatusCode::InvalidFee
+		);
+
+		let amount = NoteAmount::from_decimal("1.05", 18).unwrap();
+		assert_eq!(amount.format(18).unwrap(), "1.05");
+		assert_eq!(amount.format(20).unwrap(), "0.0105");
+		assert_eq!(NoteAmount::new(7_000).format(3).unwrap(), "7");
+		assert_eq!(
+			"-1".parse::<NoteAmount>().err().unwrap().code,
+			OpStatusCode::InvalidAmount
+		);
+	}
+
+	#[wasm_bindgen_test]
+	fn should_parse_hex_and_signed_amounts() {
+		let parse = |value: &str, decimals| NoteAmount::parse_with_decimals(value, decimals).map(|amount| amount.inner);
+		assert_eq!(parse("0x10", None).unwrap(), 16);
+		assert_eq!(parse("0x10", Some(18)).unwrap(), 16);
+		assert_eq!(parse("100", None).unwrap(), 100);
+		assert_eq!(parse("0.5", Some(2)).unwrap(), 50);
+		assert_eq!(parse("0.5", None).err().unwrap().code, OpStatusCode::InvalidAmount);
+		assert_eq!(parse("0x", None).err().unwrap().code, OpStatusCode::InvalidAmount);
+		assert_eq!(parse("0xfg", None).err().unwrap().code, OpStatusCode::InvalidAmount);
+		assert_eq!(
+			parse(&format!("0x1{}", "0".repeat(32)), None).err().unwrap().code,
+			OpStatusCode::AmountOverflow
+		);
+
+		assert_eq!(parse_signed_amount("-0x10", None).unwrap(), -16);
+		assert_eq!(parse_signed_amount("-1.5", Some(1)).unwrap(), -15);
+		assert_eq!(parse_signed_amount("7", None).unwrap(), 7);
+		assert_eq!(parse_signed_amount(&i128::MIN.to_string(), None).unwrap(), i128::MIN);
+		assert_eq!(
+			parse_signed_amount(&u128::MAX.to_string(), None).err().unwrap().code,
+			OpStatusCode::AmountOverflow
+		);
+		assert_eq!(
+			parse_signed_amount("--1", None).err().unwrap().code,
+			OpStatusCode::InvalidAmount
+		);
+	}
+
+	#[wasm_bindgen_test]
+	fn should_convert_amounts_between_decimals() {
+		let convert = |amount: u128, from, to, rounding| {
+			NoteAmount::new(amount)
+				.convert_decimals(from, to, rounding)
+				.map(|amount| amount.inner)
+		};
+		// 1.5 of an 18 decimals asset is 1.5 with 12 decimals, and back
+		assert_eq!(
+			convert(1_500_000_000_000_000_000, 18, 12, Rounding::Exact).unwrap(),
+			1_500_000_000_000
+		);
+		assert_eq!(
+			convert(1_500_000_000_000, 12, 18, Rounding::Exact).unwrap(),
+			1_500_000_000_000_000_000
+		);
+		assert_eq!(
+			convert(u128::MAX, 0, 1, Rounding::Exact).unwrap_err().code,
+			OpStatusCode::AmountOverflow
+		);
+
+		let error = convert(1_234_567, 6, 3, Rounding::Exact).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::AmountPrecisionLoss);
+		assert_eq!(error.data.as_deref(), Some("{\"amount\":\"1234\",\"dust\":\"567\"}"));
+		assert_eq!(convert(1_234_567, 6, 3, Rounding::Down).unwrap(), 1234);
+		assert_eq!(convert(1_234_001, 6, 3, Rounding::Up).unwrap(), 1235);
+		// Banker's rounding sends ties to the even neighbour
+		for (amount, rounded) in [
+			(1_234_500, 1234),
+			(1_235_500, 1236),
+			(1_234_501, 1235),
+			(1_234_499, 1234),
+		] {
+			assert_eq!(convert(amount, 6, 3, Rounding::HalfEven).unwrap(), rounded);
+		}
+		// Scales beyond u128 leave nothing but dust
+		assert_eq!(convert(u128::MAX, 60, 0, Rounding::HalfEven).unwrap(), 0);
+		assert_eq!(convert(u128::MAX, 60, 0, Rounding::Up).unwrap(), 1);
+		assert!("nearest".parse::<Rounding>().is_err());
+	}
+
+	#[wasm_bindgen_test]
+	fn should_check_amount_arithmetic() {
+		let input: NoteAmount = "10".parse().unwrap();
+		let output: NoteAmount = "4".parse().unwrap();
+
+		assert_eq!(input.checked_sub(&output).unwrap().inner, 6);
+		assert_eq!(input.checked_add(&output).unwrap().inner, 14);
+		assert_eq!(
+			output.checked_sub(&input).err().unwrap().code,
+			OpStatusCode::AmountOverflow
+		);
+		assert_eq!(
+			NoteAmount::new(u128::MAX).checked_add(&output).err().unwrap().code,
+			OpStatusCode::AmountOverflow
+		);
+	}
+}
@@ -0,0 +1,287 @@
+//! Password protected backup of a wallet's notes, the file behind a
+//! "download backup" button. The container is
+//!
+//! `"WNBK" || version (1) || kdf (1) || memory KiB (4) || passes (4) ||
+//! lanes (4) || salt (16) || nonce (24) || ciphertext || tag (16)`
+//!
+//! integers little endian. The key is the Argon2id hash of the password with
+//! the salt and the KDF parameters of the header, the notes are sealed with
+//! XChaCha20-Poly1305 with the whole header as associated data, so neither
+//! the parameters nor the version can be changed without failing the tag.
+//! The plaintext is one note backup per line, metadata included, as in
+//! encrypted note stores.
+use argon2::{Algorithm, Argon2, Version};
+use chacha20poly1305::aead::{Aead, NewAead, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use js_sys::{Array, JsString, Uint8Array};
+use rand::rngs::OsRng;
+use rand::{CryptoRng, RngCore};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::note::JsNote;
+use crate::proof::js_note_of_jsval;
+use crate::types::{JsNoteArray, OpStatusCode, OperationError};
+
+pub const BACKUP_MAGIC: &[u8; 4] = b"WNBK";
+pub const BACKUP_VERSION: u8 = 1;
+pub const KDF_ARGON2ID: u8 = 1;
+pub const SALT_LENGTH: usize = 16;
+pub const NONCE_LENGTH: usize = 24;
+pub const HEADER_LENGTH: usize = 4 + 1 + 1 + 12 + SALT_LENGTH + NONCE_LENGTH;
+const TAG_LENGTH: usize = 16;
+const KEY_LENGTH: usize = 32;
+
+/// Argon2id parameters of a backup, stored in its header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfParams {
+	/// Memory in KiB, at least 8 per lane
+	pub memory_kib: u32,
+	pub passes: u32,
+	pub lanes: u32,
+}
+
+/// Upper bounds on the KDF parameters a backup can ask for, a crafted file
+/// shouldn't get the wallet to allocate gigabytes or hash for minutes
+pub const MAX_MEMORY_KIB: u32 = 1 << 18;
+pub const MAX_PASSES: u32 = 16;
+pub const MAX_LANES: u32 = 16;
+
+/// The OWASP recommendation for Argon2id: 19 MiB, 2 passes, 1 lane
+pub const DEFAULT_KDF_PARAMS: KdfParams = KdfParams {
+	memory_kib: 19 * 1024,
+	passes: 2,
+	lanes: 1,
+};
+
+fn invalid_backup(message: String) -> OperationError {
+	OperationError::new_with_message(OpStatusCode::InvalidBackup, message)
+}
+
+impl KdfParams {
+	pub fn validate(&self) -> Result<(), OperationError> {
+		if self.lanes == 0 || self.lanes > MAX_LANES {
+			return Err(invalid_backup(format!(
+				"The KDF uses 1 to {} lanes, not {}",
+				MAX_LANES, self.lanes
+			)));
+		}
+		if self.passes == 0 || self.passes > MAX_PASSES {
+			return Err(invalid_backup(format!(
+				"The KDF makes 1 to {} passes, not {}",
+				MAX_PASSES, self.passes
+			)));
+		}
+		if self.memory_kib < 8 * self.lanes || self.memory_kib > MAX_MEMORY_KIB {
+			return Err(invalid_backup(format!(
+				"The KDF uses {} to {} KiB of memory, not {}",
+				8 * self.lanes,
+				MAX_MEMORY_KIB,
+				self.memory_kib
+			)));
+		}
+		Ok(())
+	}
+
+	fn argon2(&self) -> Result<Argon2<'static>, OperationError> {
+		let params = argon2::Params::new(self.memory_kib, self.passes, self.lanes, Some(KEY_LENGTH))
+			.map_err(|e| invalid_backup(format!("Invalid KDF parameters: {}", e)))?;
+		Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+	}
+}
+
+fn cipher(password: &str, salt: &[u8], params: KdfParams) -> Result<XChaCha20Poly1305, OperationError> {
+	let mut key = [0u8; KEY_LENGTH];
+	params
+		.argon2()?
+		.hash_password_into(password.as_bytes(), salt, &mut key)
+		.map_err(|e| invalid_backup(format!("Key derivation failed: {}", e)))?;
+	Ok(XChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+/// Backup of `notes` encrypted with a key derived from `password`
+pub fn export_backup<R: RngCore + CryptoRng>(
+	notes: &[JsNote],
+	password: &str,
+	params: KdfParams,
+	rng: &mut R,
+) -> Result<Vec<u8>, OperationError> {
+	params.validate()?;
+	let mut salt = [0u8; SALT_LENGTH];
+	rng.fill_bytes(&mut salt);
+	let mut nonce = [0u8; NONCE_LENGTH];
+	rng.fill_bytes(&mut nonce);
+
+	let mut backup = Vec::with_capacity(HEADER_LENGTH);
+	backup.extend_from_slice(BACKUP_MAGIC);
+	backup.push(BACKUP_VERSION);
+	backup.push(KDF_ARGON2ID);
+	backup.extend_from_slice(&params.memory_kib.to_le_bytes());
+	backup.extend_from_slice(&params.passes.to_le_bytes());
+	backup.extend_from_slice(&params.lanes.to_le_bytes());
+	backup.extend_from_slice(&salt);
+	backup.extend_from_slice(&nonce);
+
+	let plaintext = notes
+		.iter()
+		.map(JsNote::to_backup)
+		.collect::<Result<Vec<_>, _>>()?
+		.join("\n");
+	let payload = Payload {
+		msg: plaintext.as_bytes(),
+		aad: &backup,
+	};
+	let ciphertext = cipher(password, &salt, params)?
+		.encrypt(XNonce::from_slice(&nonce), payload)
+		.map_err(|_| OperationError::from(OpStatusCode::SerializationFailed))?;
+	backup.extend_from_slice(&ciphertext);
+	Ok(backup)
+}
+
+/// Notes of a backup from [`export_backup`]. A wrong password and a modified
+/// backup both fail decryption, the two can't be told apart
+pub fn import_backup(backup: &[u8], password: &str) -> Result<Vec<JsNote>, OperationError> {
+	if backup.len() < HEADER_LENGTH + TAG_LENGTH {
+		return Err(invalid_backup(format!(
+			"A backup is at least {} bytes, not {}",
+			HEADER_LENGTH + TAG_LENGTH,
+			backup.len()
+		)));
+	}
+	let (header, ciphertext) = backup.split_at(HEADER_LENGTH);
+	if &header[..4] != BACKUP_MAGIC {
+		return Err(invalid_backup("Not a note backup".to_string()));
+	}
+	if header[4] != BACKUP_VERSION {
+		return Err(invalid_backup(format!("Unsupported backup version {}", header[4])));
+	}
+	if header[5] != KDF_ARGON2ID {
+		return Err(invalid_backup(format!("Unsupported backup KDF {}", header[5])));
+	}
+	let u32_at = |offset: usize| u32::from_le_bytes(header[offset..offset + 4].try_into().unwrap());
+	let params = KdfParams {
+		memory_kib: u32_at(6),
+		passes: u32_at(10),
+		lanes: u32_at(14),
+	};
+	params.validate()?;
+	let salt = &header[18..18 + SALT_LENGTH];
+	let nonce = &header[18 + SALT_LENGTH..];
+
+	let payload = Payload {
+		msg: ciphertext,
+		aad: header,
+	};
+	let plaintext = cipher(password, salt, params)?
+		.decrypt(XNonce::from_slice(nonce), payload)
+		.map_err(|_| {
+			OperationError::new_with_message(
+				OpStatusCode::DecryptionFailed,
+				"Wrong password or corrupted backup".to_string(),
+			)
+		})?;
+	let plaintext = String::from_utf8(plaintext).map_err(|_| OperationError::from(OpStatusCode::DecryptionFailed))?;
+	plaintext
+		.lines()
+		.filter(|line| !line.is_empty())
+		.map(JsNote::from_backup)
+		.collect()
+}
+
+/// Encrypt `notes` with `password` into a backup file, see the module
+/// documentation for the format
+#[wasm_bindgen(js_name = exportBackup)]
+pub fn js_export_backup(notes: Array, password: JsString) -> Result<Uint8Array, JsValue> {
+	let notes = notes
+		.iter()
+		.map(|note| js_note_of_jsval(note).ok_or(OpStatusCode::InvalidNoteSecrets))
+		.collect::<Result<Vec<_>, _>>()?;
+	let backup = export_backup(&notes, &String::from(password), DEFAULT_KDF_PARAMS, &mut OsRng)?;
+	Ok(Uint8Array::from(backup.as_slice()))
+}
+
+/// Notes of a backup file from `exportBackup`
+#[wasm_bindgen(js_name = importBackup)]
+pub fn js_import_backup(backup: Uint8Array, password: JsString) -> Result<JsNoteArray, JsValue> {
+	let notes = import_backup(&backup.to_vec(), &String::from(password))?;
+	let notes: Array = notes.into_iter().map(JsValue::from).collect();
+	Ok(notes.unchecked_into())
+}
+
+#[cfg(test)]
+mod test {
+	use rand::rngs::OsRng;
+	use wasm_bindgen_test::*;
+
+	use super::*;
+	use crate::proof::test_utils::{MIXER_NOTE_V1_X5_5, VANCHOR_NOTE_V1_X5_4};
+
+	const PARAMS: KdfParams = KdfParams {
+		memory_kib: 64,
+		passes: 1,
+		lanes: 1,
+	};
+
+	fn notes() -> Vec<JsNote> {
+		let mut mixer_note = JsNote::deserialize(MIXER_NOTE_V1_X5_5).unwrap();
+		mixer_note.metadata.label = Some("savings".to_string());
+		vec![mixer_note, JsNote::deserialize(VANCHOR_NOTE_V1_X5_4).unwrap()]
+	}
+
+	#[wasm_bindgen_test]
+	fn should_export_and_import_a_backup() {
+		let notes = notes();
+		let backup = export_backup(&notes, "correct horse", PARAMS, &mut OsRng).unwrap();
+		assert_eq!(&backup[..6], b"WNBK\x01\x01");
+		assert_eq!(&backup[6..18], [64, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0]);
+		assert_eq!(import_backup(&backup, "correct horse").unwrap(), notes);
+
+		let error = import_backup(&backup, "battery staple").unwrap_err();
+		assert_eq!(error.code, OpStatusCode::DecryptionFailed);
+		// The header is authenticated, weaker parameters don't decrypt
+		let mut downgraded = backup;
+		downgraded[10] = 2;
+		let error = import_backup(&downgraded, "correct horse").unwrap_err();
+		assert_eq!(error.code, OpStatusCode::DecryptionFailed);
+
+		let empty = export_backup(&[], "correct horse", PARAMS, &mut OsRng).unwrap();
+		assert_eq!(empty.len(), HEADER_LENGTH + TAG_LENGTH);
+		assert!(import_backup(&empty, "correct horse").unwrap().is_empty());
+	}
+
+	#[wasm_bindgen_test]
+	fn should_reject_invalid_backups() {
+		let backup = export_backup(&notes(), "correct horse", PARAMS, &mut OsRng).unwrap();
+		let invalid = |backup: &[u8]| import_backup(backup, "correct horse").unwrap_err().code;
+		assert_eq!(invalid(&backup[..HEADER_LENGTH]), OpStatusCode::InvalidBackup);
+		let mut other = backup.clone();
+		other[0] = b'X';
+		assert_eq!(invalid(&other), OpStatusCode::InvalidBackup);
+		let mut other = backup.clone();
+		other[4] = 2;
+		assert_eq!(invalid(&other), OpStatusCode::InvalidBackup);
+		// A crafted backup can't ask for more memory than the limit
+		let mut other = backup;
+		other[6..10].copy_from_slice(&(MAX_MEMORY_KIB + 1).to_le_bytes());
+		assert_eq!(invalid(&other), OpStatusCode::InvalidBackup);
+
+		let params = KdfParams { lanes: 0, ..PARAMS };
+		let error = export_backup(&[], "correct horse", params, &mut OsRng).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::InvalidBackup);
+	}
+
+	#[wasm_bindgen_test]
+	fn should_match_the_rfc_9106_argon2id_test_vector() {
+		let mut builder = argon2::ParamsBuilder::new();
+		builder.m_cost(32).unwrap().t_cost(3).unwrap().p_cost(4).unwrap();
+		builder.data(&[4; 12]).unwrap().output_len(32).unwrap();
+		let params = builder.params().unwrap();
+		let argon2 = Argon2::new_with_secret(&[3; 8], Algorithm::Argon2id, Version::V0x13, params).unwrap();
+		let mut tag = [0u8; 32];
+		argon2.hash_password_into(&[1; 32], &[2; 16], &mut tag).unwrap();
+		assert_eq!(
+			hex::encode(tag),
+			"0d640df58d78766c08c037a34a8b53c9d01ef0452d75b65eb52520e96b01e659"
+		);
+	}
+}
@@ -0,0 +1,75 @@
+//! Leaf commitments of many notes in one call, for wallets listing their
+//! notes. The hash parameters are decoded once and shared by every note, and
+//! the leaves are packed back to back in a single `Uint8Array` rather than
+//! handed to JS one by one.
+use js_sys::{Array, Uint8Array};
+use wasm_bindgen::prelude::*;
+
+use crate::merkle::{ensure_leaf_count, LEAF_SIZE};
+use crate::note::{JsLeafInner, JsNote};
+use crate::types::{OpStatusCode, OperationError, StringArray};
+
+/// The leaf commitments of `notes` packed in order, `LEAF_SIZE` bytes each.
+/// Fails on the first note that doesn't parse or hash, with its position as
+/// the `index` of the error data
+pub fn compute_leaves<S: AsRef<str>>(notes: &[S]) -> Result<Vec<u8>, OperationError> {
+	ensure_leaf_count(notes.len())?;
+	let mut leaves = Vec::with_capacity(notes.len() * LEAF_SIZE);
+	for (index, note) in notes.iter().enumerate() {
+		let leaf = JsNote::deserialize(note.as_ref())
+			.and_then(|note| note.get_leaf_and_nullifier())
+			.map_err(|mut error| {
+				error.error_message = format!("Note {}: {}", index, error.error_message);
+				error.data = Some(format!("{{\"index\":{}}}", index));
+				error
+			})?;
+		match leaf.inner {
+			JsLeafInner::Mixer(leaf) => leaves.extend_from_slice(&leaf.leaf_bytes),
+			JsLeafInner::VAnchor(utxo) => leaves.extend_from_slice(&utxo.get_commitment()),
+		}
+	}
+	Ok(leaves)
+}
+
+/// The leaf commitments of the serialized `notes`, packed back to back in
+/// order, 32 bytes each
+#[wasm_bindgen(js_name = computeLeaves)]
+pub fn js_compute_leaves(notes: StringArray) -> Result<Uint8Array, JsValue> {
+	let notes = Array::from(&notes)
+		.iter()
+		.enumerate()
+		.map(|(index, note)| {
+			note.as_string().ok_or_else(|| {
+				let message = format!("Note {} isn't a string", index);
+				OperationError::new_with_message(OpStatusCode::InvalidNoteLength, message)
+			})
+		})
+		.collect::<Result<Vec<String>, _>>()?;
+	let leaves = compute_leaves(&notes)?;
+	Ok(Uint8Array::from(leaves.as_slice()))
+}
+
+#[cfg(test)]
+mod test {
+	use wasm_bindgen_test::*;
+
+	use super::*;
+	use crate::proof::test_utils::{MIXER_NOTE_V1_X5_5, VANCHOR_NOTE_V1_X5_4};
+
+	#[wasm_bindgen_test]
+	fn should_compute_the_leaves_of_many_notes() {
+		let mimc = MIXER_NOTE_V1_X5_5.replace("hf=Poseidon", "hf=MiMC220");
+		let notes = [MIXER_NOTE_V1_X5_5, VANCHOR_NOTE_V1_X5_4, mimc.as_str()];
+		let leaves = compute_leaves(&notes).unwrap();
+		assert_eq!(leaves.len(), notes.len() * LEAF_SIZE);
+		for (note, leaf) in notes.iter().zip(leaves.chunks_exact(LEAF_SIZE)) {
+			let note = JsNote::deserialize(note).unwrap();
+			assert_eq!(note.get_leaf_commitment().unwrap().to_vec(), leaf);
+		}
+		assert!(compute_leaves::<&str>(&[]).unwrap().is_empty());
+
+		let error = compute_leaves(&[MIXER_NOTE_V1_X5_5, "webb://v1:mixer"]).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::InvalidNoteLength);
+		assert_eq!(error.data.as_deref(), Some("{\"index\":1}"));
+	}
+}
@@ -0,0 +1,160 @@
+//! Scheme and authority namespace of note URIs. Forks writing notes like
+//!
+//! `myproto://acme:v1:mixer/..`
+//!
+//! set them once with `setNoteFormat` rather than patching the codec: notes
+//! are parsed and built with the format set, and a note is written back with
+//! the format it was read or built with. The namespace is an extra authority
+//! segment before the version, notes of another scheme or namespace don't
+//! parse.
+use std::cell::RefCell;
+
+use wasm_bindgen::prelude::*;
+
+use crate::types::{OpStatusCode, OperationError};
+
+/// Scheme of upstream notes
+pub const DEFAULT_SCHEME: &str = "webb";
+
+thread_local! {
+	static FORMAT: RefCell<NoteFormatConfig> = RefCell::new(NoteFormatConfig::default());
+}
+
+fn invalid_format(message: String) -> OperationError {
+	OperationError::new_with_message(OpStatusCode::InvalidNoteFormat, message)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NoteFormatConfig {
+	/// URI scheme, without `://`
+	pub scheme: String,
+	/// Authority segment before the note version
+	pub namespace: Option<String>,
+}
+
+impl Default for NoteFormatConfig {
+	fn default() -> Self {
+		Self {
+			scheme: DEFAULT_SCHEME.to_string(),
+			namespace: None,
+		}
+	}
+}
+
+impl NoteFormatConfig {
+	/// The scheme is an RFC 3986 scheme, read in lowercase, and the namespace
+	/// is made of unreserved URI characters so it can't be confused with the
+	/// other authority segments
+	pub fn new(scheme: &str, namespace: Option<&str>) -> Result<Self, OperationError> {
+		let scheme = scheme.to_ascii_lowercase();
+		let valid_scheme = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+			&& scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c));
+		if !valid_scheme {
+			return Err(invalid_format(format!("Invalid note scheme: {:?}", scheme)));
+		}
+		if let Some(namespace) = namespace {
+			let valid_namespace = !namespace.is_empty()
+				&& namespace
+					.chars()
+					.all(|c| c.is_ascii_alphanumeric() || "-._~".contains(c));
+			if !valid_namespace {
+				return Err(invalid_format(format!("Invalid note namespace: {:?}", namespace)));
+			}
+		}
+		Ok(Self {
+			scheme,
+			namespace: namespace.map(str::to_string),
+		})
+	}
+
+	/// The format notes are parsed and built with
+	pub fn current() -> Self {
+		FORMAT.with(|format| format.borrow().clone())
+	}
+
+	pub fn set(config: NoteFormatConfig) {
+		FORMAT.with(|format| *format.borrow_mut() = config);
+	}
+
+	/// Scheme of the notes of this format as `JsNote` holds it
+	pub fn scheme_prefix(&self) -> String {
+		format!("{}://", self.scheme)
+	}
+
+	/// The note after the prefix of this format
+	pub fn strip<'a>(&self, note: &'a str) -> Result<&'a str, OperationError> {
+		let (scheme, rest) = note.split_once("://").ok_or_else(|| {
+			OperationError::new_with_message(OpStatusCode::InvalidNoteLength, "Note has no scheme".to_string())
+		})?;
+		if !scheme.eq_ignore_ascii_case(&self.scheme) {
+			let message = format!("Unknown note scheme: {}", scheme);
+			return Err(OperationError::new_with_message(
+				OpStatusCode::InvalidNoteLength,
+				message,
+			));
+		}
+		match &self.namespace {
+			Some(namespace) => rest
+				.strip_prefix(namespace.as_str())
+				.and_then(|rest| rest.strip_prefix(':'))
+				.ok_or_else(|| {
+					let message = format!("Note isn't in the {} namespace", namespace);
+					OperationError::new_with_message(OpStatusCode::InvalidNoteLength, message)
+				}),
+			None => Ok(rest),
+		}
+	}
+}
+
+/// Parse and build notes with the URI `scheme` and authority `namespace`
+/// from now on, or the upstream `webb://` format when no scheme is given.
+/// Notes already parsed keep their format
+#[wasm_bindgen(js_name = setNoteFormat)]
+pub fn set_note_format(scheme: Option<String>, namespace: Option<String>) -> Result<(), JsValue> {
+	let config = match scheme {
+		Some(scheme) => NoteFormatConfig::new(&scheme, namespace.as_deref())?,
+		None => NoteFormatConfig::default(),
+	};
+	NoteFormatConfig::set(config);
+	Ok(())
+}
+
+#[cfg(test)]
+mod test {
+	use wasm_bindgen_test::*;
+
+	use super::*;
+	use crate::note::JsNote;
+	use crate::proof::test_utils::MIXER_NOTE_V1_X5_5;
+
+	#[wasm_bindgen_test]
+	fn should_parse_and_write_notes_of_a_custom_format() {
+		let forked = MIXER_NOTE_V1_X5_5.replace("webb://", "myproto://acme:");
+		set_note_format(Some("MyProto".to_string()), Some("acme".to_string())).unwrap();
+		let note = JsNote::deserialize(&forked);
+		let upstream = JsNote::deserialize(MIXER_NOTE_V1_X5_5);
+		let other_namespace = JsNote::deserialize(&forked.replace("acme:", "other:"));
+		set_note_format(None, None).unwrap();
+
+		let note = note.unwrap();
+		assert_eq!(note.scheme, "myproto://");
+		assert_eq!(note.namespace.as_deref(), Some("acme"));
+		// The note is written back in its format once the default is restored
+		assert_eq!(note.to_string(), forked);
+		let upstream_note = JsNote::deserialize(MIXER_NOTE_V1_X5_5).unwrap();
+		assert!(note.secrets_eq(&upstream_note));
+		assert_eq!(upstream.unwrap_err().code, OpStatusCode::InvalidNoteLength);
+		assert_eq!(other_namespace.unwrap_err().code, OpStatusCode::InvalidNoteLength);
+
+		for (scheme, namespace) in [
+			("1webb", None),
+			("we b", None),
+			("webb", Some("")),
+			("webb", Some("a:b")),
+		] {
+			let error = NoteFormatConfig::new(scheme, namespace).unwrap_err();
+			assert_eq!(error.code, OpStatusCode::InvalidNoteFormat);
+		}
+		assert!(JsNote::deserialize(&forked).is_err());
+	}
+}
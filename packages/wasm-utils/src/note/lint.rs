@@ -0,0 +1,219 @@
+//! Warnings about notes that parse but are likely to cause trouble, so relayer
+//! dashboards can flag notes without rejecting them. Notes that don't parse
+//! are still errors.
+use core::fmt::Display;
+
+use js_sys::{Array, JsString};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::note::JsNote;
+use crate::proof::ensure_supported_circuit;
+use crate::types::{NoteProtocol, NoteVersion, OpStatusCode, OperationError, StringArray};
+
+/// Note versions still read but no longer written
+pub const DEPRECATED_VERSIONS: &[NoteVersion] = &[];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum LintCode {
+	/// A circuit parameter is missing or differs from the defaults of the
+	/// note version
+	NonStandardParameters,
+	/// No circuit matches the parameters, the note can't be withdrawn
+	UnsupportedCircuit,
+	DeprecatedVersion,
+	/// The token symbol is missing or not one of the known tokens
+	UnknownTokenSymbol,
+	/// Secrets that repeat or are all zeros, as written by a broken generator
+	SuspiciousSecrets,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct NoteWarning {
+	pub code: LintCode,
+	pub message: String,
+}
+
+impl NoteWarning {
+	fn new(code: LintCode, message: String) -> Self {
+		Self { code, message }
+	}
+}
+
+fn compare<T: PartialEq + Display>(name: &str, value: Option<T>, default: T, differences: &mut Vec<String>) {
+	match value {
+		None => differences.push(format!("no {}", name)),
+		Some(value) if value != default => differences.push(format!("{} {} instead of {}", name, value, default)),
+		Some(_) => {}
+	}
+}
+
+impl JsNote {
+	/// Warnings about the note. The token symbol is checked against
+	/// `known_tokens` when given, only for its presence otherwise
+	pub fn lint(&self, known_tokens: Option<&[String]>) -> Vec<NoteWarning> {
+		let mut warnings = Vec::new();
+		if DEPRECATED_VERSIONS.contains(&self.version) {
+			let message = format!("Note version {} is deprecated", self.version);
+			warnings.push(NoteWarning::new(LintCode::DeprecatedVersion, message));
+		}
+
+		let defaults = self.defaults();
+		let mut differences = Vec::new();
+		compare("curve", self.curve, defaults.curve, &mut differences);
+		compare("width", self.width, defaults.width, &mut differences);
+		compare(
+			"exponentiation",
+			self.exponentiation,
+			defaults.exponentiation,
+			&mut differences,
+		);
+		compare(
+			"hash function",
+			self.hash_function,
+			defaults.hash_function,
+			&mut differences,
+		);
+		if !differences.is_empty() {
+			let message = format!("Non-standard {} note: {}", self.protocol, differences.join(", "));
+			warnings.push(NoteWarning::new(LintCode::NonStandardParameters, message));
+		}
+		if let Err(e) = ensure_supported_circuit(
+			self.protocol,
+			self.curve.unwrap_or(defaults.curve),
+			self.width.unwrap_or(defaults.width),
+			self.exponentiation.unwrap_or(defaults.exponentiation),
+		) {
+			warnings.push(NoteWarning::new(LintCode::UnsupportedCircuit, e.error_message));
+		}
+
+		match (&self.token_symbol, known_tokens) {
+			(None, _) => {
+				let message = "Note has no token symbol".to_string();
+				warnings.push(NoteWarning::new(LintCode::UnknownTokenSymbol, message));
+			}
+			(Some(symbol), Some(known_tokens)) if !known_tokens.contains(symbol) => {
+				let message = format!("Unknown token {}", symbol);
+				warnings.push(NoteWarning::new(LintCode::UnknownTokenSymbol, message));
+			}
+			_ => {}
+		}
+
+		// The chain id and amount of a VAnchor note aren't secret
+		let secrets = match self.protocol {
+			NoteProtocol::Mixer => &self.secrets[..],
+			NoteProtocol::VAnchor => self.secrets.get(2..).unwrap_or_default(),
+		};
+		if secrets.iter().any(|secret| secret.iter().all(|byte| *byte == 0)) {
+			let message = "A note secret is all zeros".to_string();
+			warnings.push(NoteWarning::new(LintCode::SuspiciousSecrets, message));
+		}
+		let repeats = secrets
+			.iter()
+			.enumerate()
+			.any(|(i, secret)| secrets[i + 1..].contains(secret));
+		if repeats {
+			let message = "The note secrets repeat".to_string();
+			warnings.push(NoteWarning::new(LintCode::SuspiciousSecrets, message));
+		}
+		warnings
+	}
+}
+
+#[wasm_bindgen(typescript_custom_section)]
+const NOTE_WARNING: &str = r#"
+export type LintCode =
+	| 'nonStandardParameters'
+	| 'unsupportedCircuit'
+	| 'deprecatedVersion'
+	| 'unknownTokenSymbol'
+	| 'suspiciousSecrets';
+
+export interface NoteWarning {
+	code: LintCode;
+	message: string;
+}
+"#;
+
+#[wasm_bindgen]
+extern "C" {
+	#[wasm_bindgen(typescript_type = "NoteWarning[]")]
+	pub type NoteWarningArray;
+}
+
+/// Warnings about the note `note`, for notes that should be flagged rather
+/// than rejected. `knownTokens` are the token symbols to expect. Notes that
+/// don't parse are rejected with their error
+#[wasm_bindgen(js_name = lintNote)]
+pub fn lint_note(note: JsString, known_tokens: Option<StringArray>) -> Result<NoteWarningArray, JsValue> {
+	let note = JsNote::deserialize(&String::from(note))?;
+	let known_tokens = known_tokens.map(|known_tokens| {
+		Array::from(&known_tokens)
+			.iter()
+			.filter_map(|token| token.as_string())
+			.collect::<Vec<_>>()
+	});
+	let warnings = JsValue::from_serde(&note.lint(known_tokens.as_deref()))
+		.map_err(|e| OperationError::new_with_message(OpStatusCode::SerializationFailed, e.to_string()))?;
+	Ok(warnings.unchecked_into())
+}
+
+#[cfg(test)]
+mod test {
+	use wasm_bindgen_test::*;
+
+	use super::*;
+	use crate::proof::test_utils::{MIXER_NOTE_V1_X5_5, VANCHOR_NOTE_V1_X5_4};
+
+	fn codes(warnings: &[NoteWarning]) -> Vec<LintCode> {
+		warnings.iter().map(|warning| warning.code).collect()
+	}
+
+	#[wasm_bindgen_test]
+	fn should_not_warn_about_standard_notes() {
+		let known_tokens = ["EDG".to_string()];
+		for note in [MIXER_NOTE_V1_X5_5, VANCHOR_NOTE_V1_X5_4] {
+			let note = JsNote::deserialize(note).unwrap();
+			assert_eq!(note.lint(Some(&known_tokens)), []);
+		}
+	}
+
+	#[wasm_bindgen_test]
+	fn should_warn_about_problematic_notes() {
+		let mut note = JsNote::deserialize(MIXER_NOTE_V1_X5_5).unwrap();
+		let known_tokens = ["WEBB".to_string()];
+		assert_eq!(codes(&note.lint(Some(&known_tokens))), [LintCode::UnknownTokenSymbol]);
+		note.token_symbol = None;
+		assert_eq!(codes(&note.lint(None)), [LintCode::UnknownTokenSymbol]);
+
+		// Bls381 has a mixer circuit, width 4 has none
+		let mut note = JsNote::deserialize(MIXER_NOTE_V1_X5_5).unwrap();
+		note.curve = Some(crate::types::Curve::Bls381);
+		assert_eq!(codes(&note.lint(None)), [LintCode::NonStandardParameters]);
+		note.width = Some(4);
+		let warnings = note.lint(None);
+		assert_eq!(codes(&warnings), [
+			LintCode::NonStandardParameters,
+			LintCode::UnsupportedCircuit
+		]);
+		assert!(
+			warnings[0].message.contains("width 4 instead of 3"),
+			"{}",
+			warnings[0].message
+		);
+		note.exponentiation = None;
+		assert!(note.lint(None)[0].message.contains("no exponentiation"));
+
+		let mut note = JsNote::deserialize(VANCHOR_NOTE_V1_X5_4).unwrap();
+		note.secrets[3] = note.secrets[2].clone();
+		assert_eq!(codes(&note.lint(None)), [LintCode::SuspiciousSecrets]);
+		note.secrets[2] = vec![0; 32];
+		note.secrets[3] = vec![0; 32];
+		assert_eq!(codes(&note.lint(None)), [
+			LintCode::SuspiciousSecrets,
+			LintCode::SuspiciousSecrets
+		]);
+	}
+}
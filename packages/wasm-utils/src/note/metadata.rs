@@ -0,0 +1,91 @@
+//! Wallet data attached to a note, such as a label or the deposit
+//! transaction. It isn't part of the note URI and never enters the leaf, it
+//! travels with the note in the JSON backup envelope
+//! `{ "note": "webb://..", "metadata": { .. } }`.
+use serde::{Deserialize, Serialize};
+
+use crate::note::JsNote;
+use crate::types::{OpStatusCode, OperationError};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct NoteMetadata {
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub label: Option<String>,
+	/// Unix timestamp in milliseconds
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub created_at: Option<u64>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub deposit_tx_hash: Option<String>,
+}
+
+impl NoteMetadata {
+	pub fn is_empty(&self) -> bool {
+		*self == NoteMetadata::default()
+	}
+}
+
+#[derive(Serialize, Deserialize)]
+struct NoteEnvelope {
+	note: String,
+	#[serde(default, skip_serializing_if = "NoteMetadata::is_empty")]
+	metadata: NoteMetadata,
+}
+
+impl JsNote {
+	/// The note URI wrapped with its metadata, a single line of JSON
+	pub fn to_backup(&self) -> Result<String, OperationError> {
+		let envelope = NoteEnvelope {
+			note: self.to_string(),
+			metadata: self.metadata.clone(),
+		};
+		serde_json::to_string(&envelope)
+			.map_err(|e| OperationError::new_with_message(OpStatusCode::SerializationFailed, e.to_string()))
+	}
+
+	/// Read a note from [`Self::to_backup`], a bare note URI is read as a note
+	/// without metadata
+	pub fn from_backup(backup: &str) -> Result<JsNote, OperationError> {
+		let backup = backup.trim();
+		if !backup.starts_with('{') {
+			return JsNote::deserialize(backup);
+		}
+		let envelope: NoteEnvelope = serde_json::from_str(backup)
+			.map_err(|e| OperationError::new_with_message(OpStatusCode::InvalidNoteMetadata, e.to_string()))?;
+		let mut note = JsNote::deserialize(&envelope.note)?;
+		note.metadata = envelope.metadata;
+		Ok(note)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use wasm_bindgen_test::*;
+
+	use super::*;
+
+	const NOTE: &str = "webb://v1:mixer/2:2/2:2/fd717cfe463b3ffec71ee6b7606bbd0179170510abf41c9f16c1d20ca9923f0e:18b6b080e6a43262f00f6fb3da0d2409c4871b8f26d89d5c8836358e1af5a41c/?curve=Bn254&width=3&exp=5&hf=Poseidon&backend=Arkworks&token=EDG&denom=18&amount=10";
+
+	#[wasm_bindgen_test]
+	fn should_keep_metadata_out_of_the_note() {
+		let mut note = JsNote::deserialize(NOTE).unwrap();
+		let leaf = note.get_leaf_commitment().unwrap().to_vec();
+		assert_eq!(note.to_backup().unwrap(), format!(r#"{{"note":"{}"}}"#, NOTE));
+
+		note.metadata = NoteMetadata {
+			label: Some("Rent \"March\"".to_string()),
+			created_at: Some(1_650_000_000_000),
+			deposit_tx_hash: Some("0xabcd".to_string()),
+		};
+		assert_eq!(note.to_string(), NOTE);
+		assert_eq!(note.get_leaf_commitment().unwrap().to_vec(), leaf);
+
+		let backup = note.to_backup().unwrap();
+		assert!(!backup.contains('\n'));
+		assert_eq!(JsNote::from_backup(&backup).unwrap(), note);
+		assert!(JsNote::from_backup(NOTE).unwrap().metadata.is_empty());
+
+		let error = JsNote::from_backup(r#"{"note": 1}"#).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::InvalidNoteMetadata);
+	}
+}
@@ -1,16 +1,23 @@
-use ark_std::rand::rngs::OsRng;
+use core::convert::TryInto;
+
+use ark_bls12_381::Fr as Bls381Fr;
+use ark_bn254::Fr as Bn254Fr;
+use ark_ff::BigInteger;
+use arkworks_native_gadgets::poseidon::{FieldHasher, Poseidon};
 use arkworks_setups::common::Leaf;
+use rand::{CryptoRng, RngCore};
 
 use crate::{MixerR1CSProverBls381_30, MixerR1CSProverBn254_30};
 use arkworks_setups::{Curve as ArkCurve, MixerProver};
 
-use crate::types::{Curve, OpStatusCode, OperationError};
+use crate::hash::{mimc_hash_raw, poseidon_params, PoseidonField};
+use crate::types::{Curve, HashFunction, OpStatusCode, OperationError};
 
-pub fn generate_secrets(
+pub fn generate_secrets<R: RngCore + CryptoRng>(
 	exponentiation: i8,
 	width: usize,
 	curve: Curve,
-	rng: &mut OsRng,
+	rng: &mut R,
 ) -> Result<[Vec<u8>; 2], OperationError> {
 	let sec = match (curve, exponentiation, width) {
 		(Curve::Bls381, 5, 3) => MixerR1CSProverBls381_30::create_random_leaf(ArkCurve::Bls381, rng),
@@ -30,38 +37,108 @@ pub fn generate_secrets(
 	Ok(secrets)
 }
 
+/// Leaf and nullifier hash of a `MiMC220` mixer note, hashed the same
+/// way as the Poseidon leaves
+fn get_mimc_leaf(curve: Curve, secret: [u8; 32], nullifier: [u8; 32]) -> Result<Leaf, OperationError> {
+	if curve != Curve::Bn254 {
+		let message = format!("No MiMC parameters for curve {}", curve);
+		return Err(OperationError::new_with_message(
+			OpStatusCode::FailedToGenerateTheLeaf,
+			message,
+		));
+	}
+	let leaf = mimc_hash_raw(&[secret, nullifier])?;
+	let nullifier_hash = mimc_hash_raw(&[nullifier, nullifier])?;
+	Ok(Leaf {
+		chain_id_bytes: None,
+		secret_bytes: secret.to_vec(),
+		nullifier_bytes: nullifier.to_vec(),
+		leaf_bytes: leaf.to_vec(),
+		nullifier_hash_bytes: nullifier_hash.to_vec(),
+	})
+}
+
+/// Same leaf as `MixerProver::create_leaf_with_privates`, with the cached
+/// Poseidon parameters instead of decoding them for every leaf
+fn get_poseidon_leaf<F: PoseidonField>(secret: &[u8], nullifier: &[u8]) -> Result<Leaf, OperationError> {
+	let secret = F::from_be_bytes_mod_order(secret);
+	let nullifier = F::from_be_bytes_mod_order(nullifier);
+	let poseidon = Poseidon::new(poseidon_params::<F>(5, 3));
+	let hash_two = |left: &F, right: &F| {
+		poseidon
+			.hash_two(left, right)
+			.map_err(|e| OperationError::new_with_message(OpStatusCode::FailedToGenerateTheLeaf, e.to_string()))
+	};
+	let leaf = hash_two(&secret, &nullifier)?;
+	let nullifier_hash = hash_two(&nullifier, &nullifier)?;
+	Ok(Leaf {
+		chain_id_bytes: None,
+		secret_bytes: secret.into_repr().to_bytes_be(),
+		nullifier_bytes: nullifier.into_repr().to_bytes_be(),
+		leaf_bytes: leaf.into_repr().to_bytes_be(),
+		nullifier_hash_bytes: nullifier_hash.into_repr().to_bytes_be(),
+	})
+}
+
 pub fn get_leaf_with_private_raw(
 	curve: Curve,
 	width: usize,
 	exponentiation: i8,
+	hash_function: HashFunction,
 	raw: &[u8],
 ) -> Result<Leaf, OperationError> {
 	if raw.len() < 64 {
 		return Err(OpStatusCode::InvalidNoteSecrets.into());
 	}
 
-	let secrets = raw[..32].to_vec();
-	let nullifer = raw[32..64].to_vec();
-	// (leaf_bytes, nullifier_hash_bytes)
-	let sec = match (curve, exponentiation, width) {
-		(Curve::Bls381, 5, 3) => {
-			MixerR1CSProverBls381_30::create_leaf_with_privates(ArkCurve::Bls381, secrets, nullifer)
-		}
-		(Curve::Bn254, 5, 3) => MixerR1CSProverBn254_30::create_leaf_with_privates(ArkCurve::Bn254, secrets, nullifer),
+	if hash_function == HashFunction::MiMC220 {
+		return get_mimc_leaf(curve, raw[..32].try_into().unwrap(), raw[32..64].try_into().unwrap());
+	}
+	match (curve, exponentiation, width) {
+		(Curve::Bls381, 5, 3) => get_poseidon_leaf::<Bls381Fr>(&raw[..32], &raw[32..64]),
+		(Curve::Bn254, 5, 3) => get_poseidon_leaf::<Bn254Fr>(&raw[..32], &raw[32..64]),
 		_ => {
 			let message = format!(
 				"No Mixer leaf setup for curve {}, exponentiation {}, and width {}",
 				curve, exponentiation, width
 			);
-			return Err(OperationError::new_with_message(
+			Err(OperationError::new_with_message(
 				OpStatusCode::FailedToGenerateTheLeaf,
 				message,
-			));
+			))
 		}
 	}
-	.map_err(|e| OperationError::new_with_message(OpStatusCode::FailedToGenerateTheLeaf, e.to_string()))?;
-	Ok(sec)
 }
 
 #[cfg(test)]
-mod test {}
+mod test {
+	use wasm_bindgen_test::*;
+
+	use super::*;
+
+	#[wasm_bindgen_test]
+	fn should_match_the_prover_leaf() {
+		let secret = [7u8; 32].to_vec();
+		let nullifier = [9u8; 32].to_vec();
+		let raw = [secret.clone(), nullifier.clone()].concat();
+		for curve in [Curve::Bn254, Curve::Bls381] {
+			let leaf = get_leaf_with_private_raw(curve, 3, 5, HashFunction::Poseidon, &raw).unwrap();
+			let expected = match curve {
+				Curve::Bn254 => MixerR1CSProverBn254_30::create_leaf_with_privates(
+					ArkCurve::Bn254,
+					secret.clone(),
+					nullifier.clone(),
+				),
+				Curve::Bls381 => MixerR1CSProverBls381_30::create_leaf_with_privates(
+					ArkCurve::Bls381,
+					secret.clone(),
+					nullifier.clone(),
+				),
+			}
+			.unwrap();
+			assert_eq!(leaf.leaf_bytes, expected.leaf_bytes);
+			assert_eq!(leaf.nullifier_hash_bytes, expected.nullifier_hash_bytes);
+			assert_eq!(leaf.secret_bytes, expected.secret_bytes);
+		}
+	}
+}
@@ -3,17 +3,36 @@ use core::str::FromStr;
 
 use arkworks_setups::common::Leaf;
 use js_sys::{JsString, Uint8Array};
-use rand::rngs::OsRng;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsValue;
 
+use crate::address::{parse_evm_address, to_checksum_address};
+use crate::entropy::SecretRng;
+use crate::note::amount::{check_wrapping_fee, parse_note_amount, NoteAmount};
+use crate::note::format::NoteFormatConfig;
+use crate::note::metadata::NoteMetadata;
+use crate::note::versioning::{note_checksum, percent_encode, NoteDefaults};
+use crate::proof::ensure_supported_circuit;
 use crate::types::{
-	Backend, Curve, HashFunction, NoteProtocol, NoteVersion, OpStatusCode, OperationError, Protocol, Version,
-	WasmCurve, BE, HF,
+	parse_chain_id, Backend, Curve, HashFunction, IdentifyingData, JsResourceId, JsTypedChainId, NoteProtocol,
+	NoteVersion, OpStatusCode, OperationError, Protocol, ResourceId, TargetSystem, TypedChainId, Version, WasmCurve,
+	BE, HF,
 };
+use crate::utils::ct_eq;
 use crate::utxo::JsUtxo;
 
+pub mod amount;
+pub mod backup;
+pub mod bulk;
+pub mod format;
+pub mod lint;
+pub mod metadata;
 pub mod mixer;
+pub mod preimage;
+pub mod share;
+pub mod store;
+pub mod summary;
+pub mod tornado;
 pub mod vanchor;
 pub mod versioning;
 
@@ -88,6 +107,87 @@ impl JsNote {
 		note.parse().map_err(Into::into)
 	}
 
+	/// The note URI with the `check` checksum of the rest of it as its last
+	/// misc value, so truncated or mistyped copies fail to parse
+	pub fn to_string_with_checksum(&self) -> String {
+		let note = self.to_string();
+		let separator = if note.ends_with('?') { "" } else { "&" };
+		format!("{}{}check={}", note, separator, note_checksum(&note))
+	}
+
+	/// Whether both notes hold the same secrets. Every pair of secrets is
+	/// compared in full, the time taken doesn't tell how much of them match
+	pub fn secrets_eq(&self, other: &JsNote) -> bool {
+		let same_count = self.secrets.len() == other.secrets.len();
+		self.secrets
+			.iter()
+			.zip(&other.secrets)
+			.fold(same_count, |eq, (secret, other)| ct_eq(secret, other) & eq)
+	}
+
+	/// Parse the source and target identifying data and check each one fits
+	/// the type of its chain, e.g. no EVM address for a Substrate target
+	pub fn identifying_data(&self) -> Result<(IdentifyingData, IdentifyingData), OperationError> {
+		let parse = |data: &str, chain_id: &str, code: OpStatusCode| {
+			let data: IdentifyingData = data.parse()?;
+			let chain_id = parse_chain_id(chain_id).map_err(|_| code)?;
+			// Chain ids of older notes may not be typed, they have no chain
+			// type to check against
+			let typed_chain_id = TypedChainId::from_u64(chain_id).ok();
+			data.ensure_chain_type(typed_chain_id.and_then(|typed_chain_id| typed_chain_id.chain_type))?;
+			if let IdentifyingData::Resource(bytes) = data {
+				let resource_id = ResourceId::from_bytes(bytes)?;
+				if Some(resource_id.typed_chain_id) != typed_chain_id {
+					let message = format!("Resource id {} is for another chain than {}", resource_id, chain_id);
+					return Err(OperationError::new_with_message(
+						OpStatusCode::InvalidIdentifyingData,
+						message,
+					));
+				}
+			}
+			Ok::<_, OperationError>(data)
+		};
+		Ok((
+			parse(
+				&self.source_identifying_data,
+				&self.source_chain_id,
+				OpStatusCode::InvalidSourceChain,
+			)?,
+			parse(
+				&self.target_identifying_data,
+				&self.target_chain_id,
+				OpStatusCode::InvalidTargetChain,
+			)?,
+		))
+	}
+
+	/// Resource ids of the source and target, read from the identifying data
+	/// or made of the identifying data and its typed chain id. Untyped chain
+	/// ids have none
+	pub fn resource_ids(&self) -> Result<(Option<ResourceId>, Option<ResourceId>), OperationError> {
+		let resource_id = |data: IdentifyingData, chain_id: &str| {
+			let typed_chain_id: TypedChainId = chain_id.parse()?;
+			let target_system = match data {
+				IdentifyingData::Resource(bytes) => return ResourceId::from_bytes(bytes).map(Some),
+				_ if typed_chain_id.chain_type.is_none() => return Ok(None),
+				IdentifyingData::ContractAddress(address) => TargetSystem::ContractAddress(address),
+				IdentifyingData::TreeId(tree_id) => TargetSystem::TreeId(tree_id),
+			};
+			ResourceId::new(target_system, typed_chain_id).map(Some)
+		};
+		let (source, target) = self.identifying_data()?;
+		Ok((
+			resource_id(source, &self.source_chain_id)?,
+			resource_id(target, &self.target_chain_id)?,
+		))
+	}
+
+	/// Whether `leaf` is the commitment of the note, compared in constant time
+	pub fn commitment_eq(&self, leaf: &[u8]) -> Result<bool, OperationError> {
+		let commitment = self.get_leaf_and_nullifier()?.commitment().to_vec();
+		Ok(ct_eq(&commitment, leaf))
+	}
+
 	pub fn mutate_index(&mut self, index: u64) -> Result<(), OperationError> {
 		match self.protocol {
 			NoteProtocol::VAnchor => {}
@@ -102,7 +202,46 @@ impl JsNote {
 		Ok(())
 	}
 
+	/// Parameters of the note's version and protocol for the misc values it
+	/// leaves out
+	pub fn defaults(&self) -> NoteDefaults {
+		NoteDefaults::of(self.version, self.protocol)
+	}
+
+	/// The hashing parameters recorded in the note. Errors instead of guessing
+	/// when the curve, width or exponentiation is missing, since a guessed
+	/// parameter set silently produces the wrong leaf.
+	pub fn leaf_params(&self) -> Result<LeafParams, OperationError> {
+		let curve = self.curve.ok_or_else(|| {
+			OperationError::new_with_message(OpStatusCode::InvalidCurve, "Note has no curve".to_string())
+		})?;
+		let width = self.width.ok_or_else(|| {
+			OperationError::new_with_message(OpStatusCode::InvalidWidth, "Note has no width".to_string())
+		})?;
+		let exponentiation = self.exponentiation.ok_or_else(|| {
+			OperationError::new_with_message(
+				OpStatusCode::InvalidExponentiation,
+				"Note has no exponentiation".to_string(),
+			)
+		})?;
+		let hash_function = self.hash_function.unwrap_or(self.defaults().hash_function);
+
+		Ok(LeafParams {
+			curve,
+			width,
+			exponentiation,
+			hash_function,
+		})
+	}
+
 	pub fn get_leaf_and_nullifier(&self) -> Result<JsLeaf, OperationError> {
+		let params = self.leaf_params()?;
+		self.get_leaf_with(&params)
+	}
+
+	/// Recompute the leaf from the note secrets using explicit parameters,
+	/// ignoring whatever the note itself records.
+	pub fn get_leaf_with(&self, params: &LeafParams) -> Result<JsLeaf, OperationError> {
 		match self.protocol {
 			NoteProtocol::Mixer => {
 				let raw = match self.version {
@@ -115,9 +254,10 @@ impl JsNote {
 				};
 
 				let mixer_leaf = mixer::get_leaf_with_private_raw(
-					self.curve.unwrap_or(Curve::Bn254),
-					self.width.unwrap_or(5),
-					self.exponentiation.unwrap_or(5),
+					params.curve,
+					params.width,
+					params.exponentiation,
+					params.hash_function,
 					&raw,
 				)?;
 
@@ -127,52 +267,40 @@ impl JsNote {
 			}
 			NoteProtocol::VAnchor => match self.version {
 				NoteVersion::V1 => {
-					if self.secrets.len() == 4 {
-						let chain_id = self.secrets[0].clone();
-
-						let amount = self.secrets[1].clone();
-						let secret_key = self.secrets[2].clone();
-						let blinding = self.secrets[3].clone();
-						let index = self.index;
-
-						let mut amount_slice = [0u8; 16];
-						amount_slice.copy_from_slice(amount[16..32].to_vec().as_slice());
-						let amount = u128::from_be_bytes(amount_slice);
-
-						let mut chain_id_slice = [0u8; 8];
-						chain_id_slice.copy_from_slice(chain_id[chain_id.len() - 8..].to_vec().as_slice());
-						let chain_id = u64::from_be_bytes(chain_id_slice);
-
-						let curve = self.curve.unwrap_or(Curve::Bn254);
-						let width = self.width.unwrap_or(2);
-						let exponentiation = self.exponentiation.unwrap_or(5);
-
-						let utxo = vanchor::get_leaf_with_private_raw(
-							curve,
-							width,
-							exponentiation,
-							Some(secret_key),
-							Some(blinding),
-							chain_id,
-							amount,
-							index,
-						)?;
-
-						Ok(JsLeaf {
-							inner: JsLeafInner::VAnchor(utxo),
-						})
-					} else {
-						let message = format!("Invalid secret format for protocol {}", self.protocol);
-						Err(OperationError::new_with_message(
-							OpStatusCode::InvalidNoteSecrets,
-							message,
-						))
-					}
+					let utxo = JsUtxo::from_note_secrets(
+						params.curve,
+						params.width,
+						params.exponentiation,
+						&self.secrets,
+						self.index,
+					)?;
+
+					Ok(JsLeaf {
+						inner: JsLeafInner::VAnchor(utxo),
+					})
 				}
 			},
 		}
 	}
 
+	/// See [`JsNote::js_display_amount`]
+	pub fn display_amount(&self) -> Result<String, OperationError> {
+		let amount = self.amount_value()?;
+		let amount = match self.denomination {
+			Some(denomination) => amount.format(denomination)?,
+			None => amount.to_string(),
+		};
+		Ok(match &self.token_symbol {
+			Some(symbol) => format!("{} {}", amount, symbol),
+			None => amount,
+		})
+	}
+
+	/// The note amount in base units, notes without an amount hold nothing
+	pub fn amount_value(&self) -> Result<NoteAmount, OperationError> {
+		self.amount.as_deref().unwrap_or("0").parse()
+	}
+
 	pub fn get_js_utxo(&self) -> Result<JsUtxo, OperationError> {
 		let leaf = self.get_leaf_and_nullifier()?;
 		match leaf.inner {
@@ -184,16 +312,20 @@ impl JsNote {
 
 impl fmt::Display for JsNote {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-		// Note URI scheme
-		let scheme = "webb://";
+		// Note URI scheme, followed by the namespace of forks as the first
+		// authority segment
+		let scheme = match &self.namespace {
+			Some(namespace) => format!("{}{}:", self.scheme, namespace),
+			None => self.scheme.clone(),
+		};
 		// Note URI authority
 		let authority = vec![self.version.to_string(), self.protocol.to_string()].join(":");
 		// Note URI chain IDs
 		let chain_ids = vec![self.source_chain_id.to_string(), self.target_chain_id.to_string()].join(":");
 		// Note URI chain identifying data (smart contracts, tree IDs)
 		let chain_identifying_data = vec![
-			self.source_identifying_data.to_string(),
-			self.target_identifying_data.to_string(),
+			percent_encode(&self.source_identifying_data),
+			percent_encode(&self.target_identifying_data),
 		]
 		.join(":");
 
@@ -228,7 +360,7 @@ impl fmt::Display for JsNote {
 				"".to_string()
 			},
 			if self.token_symbol.is_some() {
-				format!("token={}", self.token_symbol.clone().unwrap())
+				format!("token={}", percent_encode(self.token_symbol.as_ref().unwrap()))
 			} else {
 				"".to_string()
 			},
@@ -238,7 +370,7 @@ impl fmt::Display for JsNote {
 				"".to_string()
 			},
 			if self.amount.is_some() {
-				format!("amount={}", self.amount.clone().unwrap())
+				format!("amount={}", percent_encode(self.amount.as_ref().unwrap()))
 			} else {
 				"".to_string()
 			},
@@ -247,6 +379,16 @@ impl fmt::Display for JsNote {
 			} else {
 				"".to_string()
 			},
+			if self.wrapped_token.is_some() {
+				format!("wrappedToken={}", self.wrapped_token.as_ref().unwrap())
+			} else {
+				"".to_string()
+			},
+			if self.wrapping_fee.is_some() {
+				format!("wrappingFee={}", self.wrapping_fee.unwrap())
+			} else {
+				"".to_string()
+			},
 		]
 		.iter()
 		.filter(|v| !v.is_empty())
@@ -258,7 +400,7 @@ impl fmt::Display for JsNote {
 
 		let parts: Vec<String> = vec![authority, chain_ids, chain_identifying_data, secrets.to_string(), misc];
 		// Join the parts with `/` and connect to the scheme as is
-		let note = vec![scheme.to_string(), parts.join("/")].join("");
+		let note = vec![scheme, parts.join("/")].join("");
 		write!(f, "{}", note)
 	}
 }
@@ -276,6 +418,9 @@ impl FromStr for JsNote {
 pub struct JsNote {
 	#[wasm_bindgen(skip)]
 	pub scheme: String,
+	/// Authority namespace of forks, see `NoteFormatConfig`
+	#[wasm_bindgen(skip)]
+	pub namespace: Option<String>,
 	#[wasm_bindgen(skip)]
 	pub protocol: NoteProtocol,
 	#[wasm_bindgen(skip)]
@@ -307,6 +452,13 @@ pub struct JsNote {
 	pub amount: Option<String>,
 	#[wasm_bindgen(skip)]
 	pub denomination: Option<u8>,
+	/// Base asset wrapped into the bridge token on deposit, an EIP-55
+	/// address
+	#[wasm_bindgen(skip)]
+	pub wrapped_token: Option<String>,
+	/// Percentage of the base asset the token wrapper keeps
+	#[wasm_bindgen(skip)]
+	pub wrapping_fee: Option<u8>,
 
 	#[wasm_bindgen(skip)]
 	pub backend: Option<Backend>,
@@ -315,8 +467,66 @@ pub struct JsNote {
 
 	#[wasm_bindgen(skip)]
 	pub index: Option<u64>,
+
+	/// Wallet data, not part of the note URI
+	#[wasm_bindgen(skip)]
+	pub metadata: NoteMetadata,
+}
+
+/// Hashing parameters used to recompute a note leaf
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeafParams {
+	#[wasm_bindgen(skip)]
+	pub curve: Curve,
+	#[wasm_bindgen(skip)]
+	pub width: usize,
+	#[wasm_bindgen(skip)]
+	pub exponentiation: i8,
+	#[wasm_bindgen(skip)]
+	pub hash_function: HashFunction,
+}
+
+#[allow(clippy::unused_unit)]
+#[wasm_bindgen]
+impl LeafParams {
+	#[wasm_bindgen(constructor)]
+	pub fn new(
+		curve: WasmCurve,
+		width: JsString,
+		exponentiation: JsString,
+		hash_function: Option<HF>,
+	) -> Result<LeafParams, JsValue> {
+		let curve: String = JsValue::from(&curve).as_string().ok_or(OpStatusCode::InvalidCurve)?;
+		let curve: Curve = curve.parse().map_err(|_| OpStatusCode::InvalidCurve)?;
+		let width: String = width.into();
+		let width = width.parse().map_err(|_| OpStatusCode::InvalidWidth)?;
+		let exponentiation: String = exponentiation.into();
+		let exponentiation = exponentiation
+			.parse()
+			.map_err(|_| OpStatusCode::InvalidExponentiation)?;
+		let hash_function = match hash_function {
+			Some(hash_function) => {
+				let hash_function: String = JsValue::from(&hash_function)
+					.as_string()
+					.ok_or(OpStatusCode::InvalidHasFunction)?;
+				hash_function.parse().map_err(|_| OpStatusCode::InvalidHasFunction)?
+			}
+			None => HashFunction::Poseidon,
+		};
+
+		Ok(LeafParams {
+			curve,
+			width,
+			exponentiation,
+			hash_function,
+		})
+	}
 }
 
+/// Builder of a `JsNote`. The setters take the builder and give it back so
+/// they chain, as in `new JsNoteBuilder().protocol(p).curve(c).build()`. A
+/// setter that throws consumes the builder
 #[wasm_bindgen]
 #[derive(Default)]
 pub struct JsNoteBuilder {
@@ -338,6 +548,10 @@ pub struct JsNoteBuilder {
 	#[wasm_bindgen(skip)]
 	pub denomination: Option<u8>,
 	#[wasm_bindgen(skip)]
+	pub wrapped_token: Option<String>,
+	#[wasm_bindgen(skip)]
+	pub wrapping_fee: Option<u8>,
+	#[wasm_bindgen(skip)]
 	pub secrets: Option<Vec<Vec<u8>>>,
 
 	// Misc - zkp related items
@@ -370,7 +584,7 @@ impl JsNoteBuilder {
 		Self::default()
 	}
 
-	pub fn protocol(&mut self, protocol: Protocol) -> Result<(), JsValue> {
+	pub fn protocol(mut self, protocol: Protocol) -> Result<JsNoteBuilder, JsValue> {
 		let protocol: String = JsValue::from(&protocol)
 			.as_string()
 			.ok_or(OpStatusCode::InvalidNoteProtocol)?;
@@ -379,100 +593,133 @@ impl JsNoteBuilder {
 			.parse()
 			.map_err(|_| OpStatusCode::InvalidNoteProtocol)?;
 		self.protocol = Some(note_protocol);
-		Ok(())
+		Ok(self)
 	}
 
-	pub fn version(&mut self, version: Version) -> Result<(), JsValue> {
+	pub fn version(mut self, version: Version) -> Result<JsNoteBuilder, JsValue> {
 		let version: String = JsValue::from(&version)
 			.as_string()
 			.ok_or(OpStatusCode::InvalidNoteVersion)?;
 		let note_version: NoteVersion = version.as_str().parse().map_err(|_| OpStatusCode::InvalidNoteVersion)?;
 		self.version = Some(note_version);
-		Ok(())
+		Ok(self)
 	}
 
 	#[wasm_bindgen(js_name = sourceChainId)]
-	pub fn source_chain_id(&mut self, source_chain_id: JsString) {
+	pub fn source_chain_id(mut self, source_chain_id: JsString) -> JsNoteBuilder {
 		self.source_chain_id = Some(source_chain_id.into());
+		self
 	}
 
 	#[wasm_bindgen(js_name = targetChainId)]
-	pub fn target_chain_id(&mut self, target_chain_id: JsString) {
+	pub fn target_chain_id(mut self, target_chain_id: JsString) -> JsNoteBuilder {
 		self.target_chain_id = Some(target_chain_id.into());
+		self
 	}
 
 	#[wasm_bindgen(js_name = sourceIdentifyingData)]
-	pub fn source_identifying_data(&mut self, source_identifying_data: JsString) {
+	pub fn source_identifying_data(mut self, source_identifying_data: JsString) -> JsNoteBuilder {
 		self.source_identifying_data = Some(source_identifying_data.into());
+		self
 	}
 
 	#[wasm_bindgen(js_name = targetIdentifyingData)]
-	pub fn target_identifying_data(&mut self, target_identifying_data: JsString) {
+	pub fn target_identifying_data(mut self, target_identifying_data: JsString) -> JsNoteBuilder {
 		self.target_identifying_data = Some(target_identifying_data.into());
+		self
+	}
+
+	/// Target the resource, its id becomes the target identifying data and
+	/// its chain the target chain
+	#[wasm_bindgen(js_name = targetResourceId)]
+	pub fn target_resource_id(mut self, resource_id: &JsResourceId) -> JsNoteBuilder {
+		self.target_identifying_data = Some(resource_id.inner.to_string());
+		self.target_chain_id = Some(resource_id.inner.typed_chain_id.to_string());
+		self
 	}
 
-	pub fn backend(&mut self, backend: BE) {
+	pub fn backend(mut self, backend: BE) -> JsNoteBuilder {
 		let c: String = JsValue::from(&backend).as_string().unwrap();
 		let backend: Backend = c.parse().unwrap();
 		self.backend = Some(backend);
+		self
 	}
 
 	#[wasm_bindgen(js_name = hashFunction)]
-	pub fn hash_function(&mut self, hash_function: HF) -> Result<(), JsValue> {
+	pub fn hash_function(mut self, hash_function: HF) -> Result<JsNoteBuilder, JsValue> {
 		let hash_function: String = JsValue::from(&hash_function)
 			.as_string()
 			.ok_or(OpStatusCode::InvalidHasFunction)?;
 		let hash_function: HashFunction = hash_function.parse().map_err(|_| OpStatusCode::InvalidHasFunction)?;
 		self.hash_function = Some(hash_function);
-		Ok(())
+		Ok(self)
 	}
 
-	pub fn curve(&mut self, curve: WasmCurve) -> Result<(), JsValue> {
+	pub fn curve(mut self, curve: WasmCurve) -> Result<JsNoteBuilder, JsValue> {
 		let curve: String = JsValue::from(&curve).as_string().ok_or(OpStatusCode::InvalidCurve)?;
 		let curve: Curve = curve.parse().map_err(|_| OpStatusCode::InvalidCurve)?;
 		self.curve = Some(curve);
-		Ok(())
+		Ok(self)
 	}
 
 	#[wasm_bindgen(js_name = tokenSymbol)]
-	pub fn token_symbol(&mut self, token_symbol: JsString) {
+	pub fn token_symbol(mut self, token_symbol: JsString) -> JsNoteBuilder {
 		self.token_symbol = Some(token_symbol.into());
+		self
 	}
 
-	pub fn amount(&mut self, amount: JsString) {
+	pub fn amount(mut self, amount: JsString) -> JsNoteBuilder {
 		self.amount = Some(amount.into());
+		self
 	}
 
-	pub fn denomination(&mut self, denomination: JsString) -> Result<(), JsValue> {
+	pub fn denomination(mut self, denomination: JsString) -> Result<JsNoteBuilder, JsValue> {
 		let den: String = denomination.into();
 		let denomination = den.parse().map_err(|_| OpStatusCode::InvalidDenomination)?;
 		self.denomination = Some(denomination);
-		Ok(())
+		Ok(self)
+	}
+
+	/// Address of the base asset the deposit wraps into the note token
+	#[wasm_bindgen(js_name = wrappedToken)]
+	pub fn wrapped_token(mut self, wrapped_token: JsString) -> Result<JsNoteBuilder, JsValue> {
+		let address = parse_evm_address(&String::from(wrapped_token))?;
+		self.wrapped_token = Some(to_checksum_address(&address));
+		Ok(self)
+	}
+
+	/// Percentage of the base asset the token wrapper keeps, below 100
+	#[wasm_bindgen(js_name = wrappingFee)]
+	pub fn wrapping_fee(mut self, wrapping_fee: JsString) -> Result<JsNoteBuilder, JsValue> {
+		let wrapping_fee: String = wrapping_fee.into();
+		let wrapping_fee = wrapping_fee.parse().map_err(|_| OpStatusCode::InvalidFee)?;
+		self.wrapping_fee = Some(check_wrapping_fee(wrapping_fee)?);
+		Ok(self)
 	}
 
-	pub fn index(&mut self, index: JsString) -> Result<(), JsValue> {
+	pub fn index(mut self, index: JsString) -> Result<JsNoteBuilder, JsValue> {
 		let index: String = index.into();
 		let index: u64 = index.parse().map_err(|_| OpStatusCode::InvalidUTXOIndex)?;
 		self.index = Some(index);
-		Ok(())
+		Ok(self)
 	}
 
-	pub fn exponentiation(&mut self, exponentiation: JsString) -> Result<(), JsValue> {
+	pub fn exponentiation(mut self, exponentiation: JsString) -> Result<JsNoteBuilder, JsValue> {
 		let exp: String = exponentiation.into();
 		let exponentiation = exp.parse().map_err(|_| OpStatusCode::InvalidExponentiation)?;
 		self.exponentiation = Some(exponentiation);
-		Ok(())
+		Ok(self)
 	}
 
-	pub fn width(&mut self, width: JsString) -> Result<(), JsValue> {
+	pub fn width(mut self, width: JsString) -> Result<JsNoteBuilder, JsValue> {
 		let width: String = width.into();
 		let width = width.parse().map_err(|_| OpStatusCode::InvalidWidth)?;
 		self.width = Some(width);
-		Ok(())
+		Ok(self)
 	}
 
 	#[wasm_bindgen(js_name = setSecrets)]
-	pub fn set_secrets(&mut self, secrets: JsString) -> Result<(), JsValue> {
+	pub fn set_secrets(mut self, secrets: JsString) -> Result<JsNoteBuilder, JsValue> {
 		let secrets_string: String = secrets.into();
 		let secrets_parts: Vec<String> = secrets_string.split(':').map(String::from).collect();
 		let secs = secrets_parts
@@ -480,19 +727,19 @@ impl JsNoteBuilder {
 			.map(|v| hex::decode(v.replace("0x", "")).unwrap_or_default())
 			.collect();
 		self.secrets = Some(secs);
-		Ok(())
+		Ok(self)
 	}
 
 	#[wasm_bindgen(js_name = setPrivateKey)]
-	pub fn set_private_key(&mut self, private_key: Uint8Array) -> Result<(), JsValue> {
+	pub fn set_private_key(mut self, private_key: Uint8Array) -> Result<JsNoteBuilder, JsValue> {
 		self.private_key = Some(private_key.to_vec());
-		Ok(())
+		Ok(self)
 	}
 
 	#[wasm_bindgen(js_name = setBlinding)]
-	pub fn set_blinding(&mut self, blinding: Uint8Array) -> Result<(), JsValue> {
+	pub fn set_blinding(mut self, blinding: Uint8Array) -> Result<JsNoteBuilder, JsValue> {
 		self.blinding = Some(blinding.to_vec());
-		Ok(())
+		Ok(self)
 	}
 
 	pub fn build(self) -> Result<JsNote, JsValue> {
@@ -501,23 +748,51 @@ impl JsNoteBuilder {
 		let protocol = self.protocol.ok_or(OpStatusCode::InvalidNoteProtocol)?;
 
 		// Chain Ids
-		let source_chain_id = self.source_chain_id.ok_or(OpStatusCode::InvalidSourceChain)?;
-		let _: u64 = source_chain_id.parse().map_err(|_| OpStatusCode::InvalidSourceChain)?;
-		let target_chain_id = self.target_chain_id.ok_or(OpStatusCode::InvalidTargetChain)?;
-		let chain_id: u64 = target_chain_id.parse().map_err(|_| OpStatusCode::InvalidTargetChain)?;
+		// Hex and `<chain type>:<id>` chain ids are written to the note in decimal
+		let chain_id_of = |chain_id: Option<String>, code: OpStatusCode| {
+			let chain_id = chain_id.ok_or_else(|| OperationError::from(code.clone()))?;
+			parse_chain_id(&chain_id).map_err(|e| OperationError::new_with_message(code, e.error_message))
+		};
+		let source_chain_id = chain_id_of(self.source_chain_id, OpStatusCode::InvalidSourceChain)?.to_string();
+		let chain_id = chain_id_of(self.target_chain_id, OpStatusCode::InvalidTargetChain)?;
+		let target_chain_id = chain_id.to_string();
+
+		// Amount
+		// Token amounts like `10.5 WEBB` are stored in base units, the display
+		// value is recovered with the denomination
+		let mut token_symbol = self.token_symbol;
+		let amount = match self.amount.as_deref() {
+			Some(amount) => {
+				let (amount, unit) = parse_note_amount(amount, self.denomination)?;
+				match (unit, token_symbol.as_deref()) {
+					(Some(unit), Some(symbol)) if unit != symbol => {
+						let message = format!("Amount in {} for a {} note", unit, symbol);
+						return Err(OperationError::new_with_message(OpStatusCode::InvalidAmount, message).into());
+					}
+					(Some(unit), None) => token_symbol = Some(unit.to_string()),
+					_ => {}
+				}
+				Some(amount)
+			}
+			None => None,
+		};
 
 		// Chain identifying data
 		let source_identifying_data = self.source_identifying_data.ok_or_else(|| "".to_string())?;
 		let target_identifying_data = self.target_identifying_data.ok_or_else(|| "".to_string())?;
 
 		// Misc
-		let exponentiation = self.exponentiation;
-		let width = self.width;
-		let curve = self.curve;
-		let amount = self.amount.clone();
+		// Record the defaults actually used so the leaf can be recomputed later
+		let defaults = NoteDefaults::of(version, protocol);
+		let exponentiation = self.exponentiation.unwrap_or(defaults.exponentiation);
+		let width = self.width.unwrap_or(defaults.width);
+		let curve = self.curve.unwrap_or(defaults.curve);
 		let index = self.index;
 		let backend = self.backend.unwrap_or(Backend::Arkworks);
 
+		// Reject notes no circuit can spend before generating their secrets
+		ensure_supported_circuit(protocol, curve, width, exponentiation)?;
+
 		if backend == Backend::Circom && self.secrets.is_none() {
 			let message = "Circom backend is supported when the secret value is supplied".to_string();
 			let operation_error = OperationError::new_with_message(OpStatusCode::UnsupportedBackend, message);
@@ -527,12 +802,7 @@ impl JsNoteBuilder {
 		let secrets = match self.secrets {
 			None => match protocol {
 				NoteProtocol::Mixer => {
-					let secrets = mixer::generate_secrets(
-						exponentiation.unwrap_or(5),
-						width.unwrap_or(5),
-						curve.unwrap_or(Curve::Bn254),
-						&mut OsRng,
-					)?;
+					let secrets = mixer::generate_secrets(exponentiation, width, curve, &mut SecretRng::new()?)?;
 
 					secrets.to_vec()
 				}
@@ -540,23 +810,17 @@ impl JsNoteBuilder {
 					let blinding = self.blinding;
 					let private_key = self.private_key;
 					let utxo = vanchor::get_leaf_with_private_raw(
-						curve.unwrap_or(Curve::Bn254),
-						width.unwrap_or(5),
-						exponentiation.unwrap_or(5),
+						curve,
+						width,
+						exponentiation,
 						private_key,
 						blinding,
 						chain_id,
-						amount.unwrap_or_else(|| "0".to_string()).parse().unwrap(),
+						amount.as_ref().map_or(0, |amount| amount.inner),
 						index,
 					)?;
 
-					let chain_id = utxo.get_chain_id_bytes();
-					let amount = utxo.get_amount();
-					let blinding = utxo.get_blinding();
-					let secret_key = utxo.get_secret_key().unwrap();
-
-					// secrets
-					vec![chain_id, amount, blinding, secret_key]
+					utxo.to_note_secrets()?
 				}
 			},
 			Some(secrets) => {
@@ -585,13 +849,13 @@ impl JsNoteBuilder {
 
 		let backend = self.backend;
 		let hash_function = self.hash_function;
-		let token_symbol = self.token_symbol;
-		let amount = self.amount.clone();
+		let amount = amount.map(|amount| amount.to_string());
 		let denomination = self.denomination;
 
-		let scheme = "webb://".to_string();
+		let format = NoteFormatConfig::current();
 		let note = JsNote {
-			scheme,
+			scheme: format.scheme_prefix(),
+			namespace: format.namespace,
 			protocol,
 			version,
 			source_chain_id,
@@ -600,14 +864,17 @@ impl JsNoteBuilder {
 			target_identifying_data,
 			backend,
 			hash_function,
-			curve,
+			curve: Some(curve),
 			token_symbol,
 			amount,
 			denomination,
-			exponentiation,
-			width,
+			wrapped_token: self.wrapped_token,
+			wrapping_fee: self.wrapping_fee,
+			exponentiation: Some(exponentiation),
+			width: Some(width),
 			secrets,
 			index,
+			metadata: NoteMetadata::default(),
 		};
 		Ok(note)
 	}
@@ -635,8 +902,34 @@ impl JsNote {
 		Ok(leaf.commitment())
 	}
 
-	pub fn serialize(&self) -> JsString {
-		JsString::from(self.to_string())
+	#[wasm_bindgen(js_name = getLeafCommitmentWith)]
+	pub fn get_leaf_commitment_with(&self, params: &LeafParams) -> Result<Uint8Array, JsValue> {
+		let leaf = self.get_leaf_with(params)?;
+
+		Ok(leaf.commitment())
+	}
+
+	/// Compare the secrets with `other`'s without handing them to JS, to
+	/// detect duplicate notes
+	#[wasm_bindgen(js_name = equalsSecret)]
+	pub fn equals_secret(&self, other: &JsNote) -> bool {
+		self.secrets_eq(other)
+	}
+
+	/// Whether `leaf` is the commitment of the note
+	#[wasm_bindgen(js_name = commitmentEquals)]
+	pub fn commitment_equals(&self, leaf: Uint8Array) -> Result<bool, JsValue> {
+		Ok(self.commitment_eq(&leaf.to_vec())?)
+	}
+
+	/// The note URI, ending with a `check` checksum parsing verifies when
+	/// `checksum` is set
+	pub fn serialize(&self, checksum: Option<bool>) -> JsString {
+		if checksum.unwrap_or(false) {
+			JsString::from(self.to_string_with_checksum())
+		} else {
+			JsString::from(self.to_string())
+		}
 	}
 
 	#[wasm_bindgen(getter)]
@@ -673,6 +966,36 @@ impl JsNote {
 		self.source_identifying_data.clone().into()
 	}
 
+	#[wasm_bindgen(js_name = targetTypedChainId)]
+	#[wasm_bindgen(getter)]
+	pub fn target_typed_chain_id(&self) -> Result<JsTypedChainId, JsValue> {
+		let typed_chain_id: TypedChainId = self.target_chain_id.parse()?;
+		Ok(typed_chain_id.into())
+	}
+
+	#[wasm_bindgen(js_name = sourceTypedChainId)]
+	#[wasm_bindgen(getter)]
+	pub fn source_typed_chain_id(&self) -> Result<JsTypedChainId, JsValue> {
+		let typed_chain_id: TypedChainId = self.source_chain_id.parse()?;
+		Ok(typed_chain_id.into())
+	}
+
+	/// Resource id of the tree or contract the note targets, none for an
+	/// untyped target chain id
+	#[wasm_bindgen(js_name = targetResourceId)]
+	#[wasm_bindgen(getter)]
+	pub fn target_resource_id(&self) -> Result<Option<JsResourceId>, JsValue> {
+		let (_, target) = self.resource_ids()?;
+		Ok(target.map(|inner| JsResourceId { inner }))
+	}
+
+	#[wasm_bindgen(js_name = sourceResourceId)]
+	#[wasm_bindgen(getter)]
+	pub fn source_resource_id(&self) -> Result<Option<JsResourceId>, JsValue> {
+		let (source, _) = self.resource_ids()?;
+		Ok(source.map(|inner| JsResourceId { inner }))
+	}
+
 	#[wasm_bindgen(getter)]
 	pub fn backend(&self) -> BE {
 		self.backend.unwrap_or(Backend::Circom).into()
@@ -706,12 +1029,43 @@ impl JsNote {
 		self.amount.clone().unwrap_or_default().into()
 	}
 
+	/// The note amount in base units, for checked arithmetic in JS
+	#[wasm_bindgen(js_name = noteAmount)]
+	pub fn note_amount(&self) -> Result<NoteAmount, JsValue> {
+		Ok(self.amount_value()?)
+	}
+
+	/// The note amount in tokens with the token symbol, such as `10.5 WEBB`,
+	/// or in base units when the note has no denomination
+	#[wasm_bindgen(js_name = displayAmount)]
+	pub fn js_display_amount(&self) -> Result<JsString, JsValue> {
+		Ok(self.display_amount()?.into())
+	}
+
 	#[wasm_bindgen(getter)]
 	pub fn denomination(&self) -> JsString {
 		let denomination = self.denomination.unwrap_or_default().to_string();
 		denomination.into()
 	}
 
+	#[wasm_bindgen(getter, js_name = wrappedToken)]
+	pub fn js_wrapped_token(&self) -> Option<String> {
+		self.wrapped_token.clone()
+	}
+
+	#[wasm_bindgen(getter, js_name = wrappingFee)]
+	pub fn js_wrapping_fee(&self) -> Option<u8> {
+		self.wrapping_fee
+	}
+
+	/// Base units of the wrapped token to approve for the deposit, the note
+	/// amount grossed up by the wrapping fee
+	#[wasm_bindgen(js_name = wrappingAmount)]
+	pub fn wrapping_amount(&self) -> Result<NoteAmount, JsValue> {
+		let fee = self.wrapping_fee.unwrap_or_default();
+		Ok(self.amount_value()?.amount_to_wrap(fee)?)
+	}
+
 	#[wasm_bindgen(getter)]
 	pub fn width(&self) -> JsString {
 		let width = self.width.unwrap_or_default().to_string();
@@ -724,6 +1078,62 @@ impl JsNote {
 		exp.into()
 	}
 
+	#[wasm_bindgen(getter)]
+	pub fn label(&self) -> Option<String> {
+		self.metadata.label.clone()
+	}
+
+	#[wasm_bindgen(setter)]
+	pub fn set_label(&mut self, label: Option<String>) {
+		self.metadata.label = label;
+	}
+
+	/// Creation time in milliseconds since the epoch, as `Date.now()`
+	#[wasm_bindgen(getter, js_name = createdAt)]
+	pub fn created_at(&self) -> Option<f64> {
+		self.metadata.created_at.map(|created_at| created_at as f64)
+	}
+
+	#[wasm_bindgen(setter, js_name = createdAt)]
+	pub fn set_created_at(&mut self, created_at: Option<f64>) {
+		self.metadata.created_at = created_at.map(|created_at| created_at as u64);
+	}
+
+	#[wasm_bindgen(getter, js_name = depositTxHash)]
+	pub fn deposit_tx_hash(&self) -> Option<String> {
+		self.metadata.deposit_tx_hash.clone()
+	}
+
+	#[wasm_bindgen(setter, js_name = depositTxHash)]
+	pub fn set_deposit_tx_hash(&mut self, deposit_tx_hash: Option<String>) {
+		self.metadata.deposit_tx_hash = deposit_tx_hash;
+	}
+
+	/// JSON envelope of the note URI and its metadata, for wallet backups
+	#[wasm_bindgen(js_name = toBackup)]
+	pub fn js_to_backup(&self) -> Result<JsString, JsValue> {
+		Ok(self.to_backup()?.into())
+	}
+
+	/// Read a note from `toBackup` or from a bare note URI
+	#[wasm_bindgen(js_name = fromBackup)]
+	pub fn js_from_backup(backup: JsString) -> Result<JsNote, JsValue> {
+		Ok(JsNote::from_backup(&String::from(backup))?)
+	}
+
+	/// Read a Bn254 mixer note from a Tornado-style JSON deposit. Tornado
+	/// Cash deposits, committed with Pedersen, fail with `IncompatibleDeposit`
+	#[wasm_bindgen(js_name = fromTornadoDeposit)]
+	pub fn js_from_tornado_deposit(json: JsString) -> Result<JsNote, JsValue> {
+		Ok(JsNote::from_tornado_deposit(&String::from(json))?)
+	}
+
+	/// Tornado-style JSON deposit of a Bn254 mixer note
+	#[wasm_bindgen(js_name = toTornadoDeposit)]
+	pub fn js_to_tornado_deposit(&self) -> Result<JsString, JsValue> {
+		Ok(self.to_tornado_deposit()?.into())
+	}
+
 	#[wasm_bindgen(js_name = mutateIndex)]
 	pub fn js_mutate_index(&mut self, index: JsString) -> Result<(), JsValue> {
 		let index: String = index.into();
@@ -736,6 +1146,7 @@ impl JsNote {
 	pub fn default_utxo_note(note: &JsNote) -> Result<JsNote, OperationError> {
 		let mut new_note = JsNote {
 			scheme: note.scheme.clone(),
+			namespace: note.namespace.clone(),
 			protocol: note.protocol,
 			version: note.version,
 			source_chain_id: note.source_chain_id.clone(),
@@ -749,23 +1160,28 @@ impl JsNote {
 			token_symbol: note.token_symbol.clone(),
 			amount: Some("0".to_string()),
 			denomination: note.denomination,
+			wrapped_token: note.wrapped_token.clone(),
+			wrapping_fee: note.wrapping_fee,
 			backend: note.backend,
 			hash_function: note.hash_function,
 			index: Some(0),
+			metadata: note.metadata.clone(),
 		};
-		let chain_id: u64 = new_note
+		let chain_id = new_note
 			.target_chain_id
-			.parse()
-			.map_err(|_| OpStatusCode::InvalidTargetChain)?;
+			.parse::<TypedChainId>()
+			.map_err(|_| OpStatusCode::InvalidTargetChain)?
+			.to_u64();
 
+		let defaults = new_note.defaults();
 		let utxo = vanchor::generate_secrets(
 			0,
-			new_note.exponentiation.unwrap_or(5),
-			new_note.width.unwrap_or(5),
-			new_note.curve.unwrap_or(Curve::Bn254),
+			new_note.exponentiation.unwrap_or(defaults.exponentiation),
+			new_note.width.unwrap_or(defaults.width),
+			new_note.curve.unwrap_or(defaults.curve),
 			chain_id,
 			Some(0),
-			&mut OsRng,
+			&mut SecretRng::new()?,
 		)?;
 		new_note.update_vanchor_utxo(utxo)?;
 		Ok(new_note)
@@ -784,12 +1200,8 @@ impl JsNote {
 
 	// for test and internal usage
 	pub fn update_vanchor_utxo(&mut self, utxo: JsUtxo) -> Result<(), OperationError> {
-		let chain_id = utxo.get_chain_id_bytes();
-		let amount = utxo.get_amount();
-		let blinding = utxo.get_blinding();
-		let secret_key = utxo.get_secret_key().unwrap();
+		self.secrets = utxo.to_note_secrets()?;
 		self.amount = Some(utxo.get_amount_raw().to_string());
-		self.secrets = vec![chain_id, amount, blinding, secret_key];
 		Ok(())
 	}
 
@@ -804,10 +1216,14 @@ impl JsNote {
 
 #[cfg(test)]
 mod test {
+	use core::convert::TryInto;
+
 	use ark_bn254;
+	use ark_ff::{BigInteger, PrimeField};
 	use wasm_bindgen_test::*;
 
 	use super::*;
+	use crate::hash::mimc_hash_raw;
 
 	type Bn254Fr = ark_bn254::Fr;
 
@@ -820,22 +1236,22 @@ mod test {
 		let hash_function: HF = JsValue::from(HashFunction::Poseidon.to_string()).into();
 		let curve: WasmCurve = JsValue::from(Curve::Bn254.to_string()).into();
 
-		note_builder.protocol(protocol).unwrap();
-		note_builder.version(version).unwrap();
-		note_builder.source_chain_id(JsString::from("2"));
-		note_builder.target_chain_id(JsString::from("2"));
-		note_builder.source_identifying_data(JsString::from("2"));
-		note_builder.target_identifying_data(JsString::from("2"));
-
-		note_builder.width(JsString::from("3")).unwrap();
-		note_builder.exponentiation(JsString::from("5")).unwrap();
-		note_builder.denomination(JsString::from("18")).unwrap();
-		note_builder.amount(JsString::from("10"));
-		note_builder.token_symbol(JsString::from("EDG"));
-		note_builder.curve(curve).unwrap();
-		note_builder.hash_function(hash_function).unwrap();
-		note_builder.backend(backend);
-		note_builder.index(JsString::from("10")).unwrap();
+		note_builder = note_builder.protocol(protocol).unwrap();
+		note_builder = note_builder.version(version).unwrap();
+		note_builder = note_builder.source_chain_id(JsString::from("2"));
+		note_builder = note_builder.target_chain_id(JsString::from("2"));
+		note_builder = note_builder.source_identifying_data(JsString::from("2"));
+		note_builder = note_builder.target_identifying_data(JsString::from("2"));
+
+		note_builder = note_builder.width(JsString::from("3")).unwrap();
+		note_builder = note_builder.exponentiation(JsString::from("5")).unwrap();
+		note_builder = note_builder.denomination(JsString::from("18")).unwrap();
+		note_builder = note_builder.amount(JsString::from("10"));
+		note_builder = note_builder.token_symbol(JsString::from("EDG"));
+		note_builder = note_builder.curve(curve).unwrap();
+		note_builder = note_builder.hash_function(hash_function).unwrap();
+		note_builder = note_builder.backend(backend);
+		note_builder = note_builder.index(JsString::from("10")).unwrap();
 
 		let mixer_note = note_builder.build().unwrap();
 		let note_string = mixer_note.to_string();
@@ -850,17 +1266,112 @@ mod test {
 
 		// Asserting that with serialization and deserialization lead to the same note
 		assert_eq!(note_string, js_note_2_string);
+		assert_eq!(String::from(js_note_2.source_identifying_data()), "2");
+		assert_eq!(String::from(js_note_2.target_identifying_data()), "2");
+		// Untyped chain ids give no resource id
+		assert_eq!(js_note_2.target_typed_chain_id().unwrap().inner.chain_type, None);
+		assert!(js_note_2.target_resource_id().unwrap().is_none());
 		assert_eq!(mixer_note.secrets.len(), 2);
 		assert_eq!(hex::encode(leaf_vec), hex::encode(leaf_2_vec));
 	}
 
+	#[wasm_bindgen_test]
+	fn should_target_a_resource_id() {
+		let mut note_builder = JsNoteBuilder::new();
+		let protocol: Protocol = JsValue::from(NoteProtocol::Mixer.to_string()).into();
+		let version: Version = JsValue::from(NoteVersion::V1.to_string()).into();
+		let evm = JsTypedChainId::from_evm(5);
+		let address = "0x38e7aa90c77f86747fab355eecaa0c2e4c3a463d";
+		let resource_id = JsResourceId::from_contract_address(address.into(), &evm).unwrap();
+
+		note_builder = note_builder.protocol(protocol).unwrap();
+		note_builder = note_builder.version(version).unwrap();
+		note_builder = note_builder.source_chain_id(evm.to_js_string());
+		note_builder = note_builder.source_identifying_data(JsString::from(address));
+		note_builder = note_builder.target_resource_id(&resource_id);
+		note_builder = note_builder.width(JsString::from("3")).unwrap();
+		note_builder = note_builder.exponentiation(JsString::from("5")).unwrap();
+		let note = note_builder.build().unwrap();
+
+		assert_eq!(note.target_chain_id, evm.inner.to_string());
+		assert_eq!(note.target_identifying_data, resource_id.inner.to_string());
+		let (_, target) = note.identifying_data().unwrap();
+		assert_eq!(target, IdentifyingData::Resource(resource_id.inner.to_bytes()));
+
+		// The typed getters read the same through a round trip
+		let parsed = JsNote::deserialize(&note.to_string()).unwrap();
+		assert_eq!(String::from(parsed.source_identifying_data()), address);
+		assert_eq!(parsed.target_resource_id().unwrap().unwrap().inner, resource_id.inner);
+		let source = parsed.source_resource_id().unwrap().unwrap();
+		assert_eq!(source.inner.target_system, resource_id.inner.target_system);
+		assert_eq!(parsed.source_typed_chain_id().unwrap().inner, evm.inner);
+		assert_eq!(parsed.target_typed_chain_id().unwrap().inner, evm.inner);
+
+		// The resource id of another chain
+		let mut note = JsNote::deserialize(&note.to_string()).unwrap();
+		note.target_chain_id = JsTypedChainId::from_evm(4).inner.to_string();
+		let error = note.identifying_data().unwrap_err();
+		assert_eq!(error.code, OpStatusCode::InvalidIdentifyingData);
+	}
+
+	#[wasm_bindgen_test]
+	fn generate_bls381_mixer_note() {
+		let mut note_builder = JsNoteBuilder::new();
+		let protocol: Protocol = JsValue::from(NoteProtocol::Mixer.to_string()).into();
+		let version: Version = JsValue::from(NoteVersion::V1.to_string()).into();
+		let curve: WasmCurve = JsValue::from(Curve::Bls381.to_string()).into();
+
+		note_builder = note_builder.protocol(protocol).unwrap();
+		note_builder = note_builder.version(version).unwrap();
+		note_builder = note_builder.source_chain_id(JsString::from("2"));
+		note_builder = note_builder.target_chain_id(JsString::from("2"));
+		note_builder = note_builder.source_identifying_data(JsString::from("2"));
+		note_builder = note_builder.target_identifying_data(JsString::from("2"));
+		note_builder = note_builder.width(JsString::from("3")).unwrap();
+		note_builder = note_builder.exponentiation(JsString::from("5")).unwrap();
+		note_builder = note_builder.curve(curve).unwrap();
+
+		let mixer_note = note_builder.build().unwrap();
+		let leaf = mixer_note.get_leaf_and_nullifier().unwrap().mixer_leaf().unwrap();
+
+		let js_note_2 = JsNote::deserialize(&mixer_note.to_string()).unwrap();
+		let leaf_2 = js_note_2.get_leaf_and_nullifier().unwrap().mixer_leaf().unwrap();
+
+		assert_eq!(js_note_2.curve, Some(Curve::Bls381));
+		assert_eq!(hex::encode(leaf.leaf_bytes), hex::encode(leaf_2.leaf_bytes));
+		assert_eq!(
+			hex::encode(leaf.nullifier_hash_bytes),
+			hex::encode(leaf_2.nullifier_hash_bytes)
+		);
+	}
+
 	#[wasm_bindgen_test]
 	fn should_deserialize_mixer_note() {
 		let mixer_note = "webb://v1:mixer/2:2/2:2/fd717cfe463b3ffec71ee6b7606bbd0179170510abf41c9f16c1d20ca9923f0e:18b6b080e6a43262f00f6fb3da0d2409c4871b8f26d89d5c8836358e1af5a41c/?curve=Bn254&width=3&exp=5&hf=Poseidon&backend=Arkworks&token=EDG&denom=18&amount=10&index=10";
 		let note = JsNote::deserialize(mixer_note).unwrap();
 		// Generate leaf to trigger any errors
 		note.get_leaf_commitment().unwrap();
-		assert_eq!(note.serialize(), mixer_note);
+		assert_eq!(note.serialize(None), mixer_note);
+	}
+
+	#[wasm_bindgen_test]
+	fn should_generate_mimc_mixer_leaf() {
+		let poseidon_note = "webb://v1:mixer/2:2/2:2/fd717cfe463b3ffec71ee6b7606bbd0179170510abf41c9f16c1d20ca9923f0e:18b6b080e6a43262f00f6fb3da0d2409c4871b8f26d89d5c8836358e1af5a41c/?curve=Bn254&width=3&exp=5&hf=Poseidon&backend=Arkworks&token=EDG&denom=18&amount=10&index=10";
+		let mimc_note = JsNote::deserialize(&poseidon_note.replace("hf=Poseidon", "hf=MiMC220")).unwrap();
+		let poseidon_note = JsNote::deserialize(poseidon_note).unwrap();
+
+		let leaf = mimc_note.get_leaf_and_nullifier().unwrap().mixer_leaf().unwrap();
+		let secret: [u8; 32] = mimc_note.secrets[0].clone().try_into().unwrap();
+		let nullifier: [u8; 32] = mimc_note.secrets[1].clone().try_into().unwrap();
+		assert_eq!(leaf.leaf_bytes, mimc_hash_raw(&[secret, nullifier]).unwrap().to_vec());
+		assert_eq!(
+			leaf.nullifier_hash_bytes,
+			mimc_hash_raw(&[nullifier, nullifier]).unwrap().to_vec()
+		);
+		assert_ne!(
+			mimc_note.get_leaf_commitment().unwrap().to_vec(),
+			poseidon_note.get_leaf_commitment().unwrap().to_vec()
+		);
 	}
 
 	#[wasm_bindgen_test]
@@ -872,22 +1383,22 @@ mod test {
 		let hash_function: HF = JsValue::from(HashFunction::Poseidon.to_string()).into();
 		let curve: WasmCurve = JsValue::from(Curve::Bn254.to_string()).into();
 
-		note_builder.protocol(protocol).unwrap();
-		note_builder.version(version).unwrap();
-		note_builder.source_chain_id(JsString::from("2"));
-		note_builder.target_chain_id(JsString::from("3"));
-		note_builder.source_identifying_data(JsString::from("2"));
-		note_builder.target_identifying_data(JsString::from("3"));
-
-		note_builder.width(JsString::from("5")).unwrap();
-		note_builder.exponentiation(JsString::from("5")).unwrap();
-		note_builder.denomination(JsString::from("18")).unwrap();
-		note_builder.amount(JsString::from("10"));
-		note_builder.token_symbol(JsString::from("EDG"));
-		note_builder.curve(curve).unwrap();
-		note_builder.hash_function(hash_function).unwrap();
-		note_builder.backend(backend);
-		note_builder.index(JsString::from("10")).unwrap();
+		note_builder = note_builder.protocol(protocol).unwrap();
+		note_builder = note_builder.version(version).unwrap();
+		note_builder = note_builder.source_chain_id(JsString::from("2"));
+		note_builder = note_builder.target_chain_id(JsString::from("3"));
+		note_builder = note_builder.source_identifying_data(JsString::from("2"));
+		note_builder = note_builder.target_identifying_data(JsString::from("3"));
+
+		note_builder = note_builder.width(JsString::from("5")).unwrap();
+		note_builder = note_builder.exponentiation(JsString::from("5")).unwrap();
+		note_builder = note_builder.denomination(JsString::from("18")).unwrap();
+		note_builder = note_builder.amount(JsString::from("10"));
+		note_builder = note_builder.token_symbol(JsString::from("EDG"));
+		note_builder = note_builder.curve(curve).unwrap();
+		note_builder = note_builder.hash_function(hash_function).unwrap();
+		note_builder = note_builder.backend(backend);
+		note_builder = note_builder.index(JsString::from("10")).unwrap();
 
 		let vanchor_note = note_builder.build().unwrap();
 		let note_string = vanchor_note.to_string();
@@ -906,12 +1417,274 @@ mod test {
 		assert_eq!(hex::encode(leaf_vec), hex::encode(leaf_2_vec));
 	}
 
+	#[wasm_bindgen_test]
+	fn should_use_typed_chain_ids() {
+		let evm_chain_id = JsTypedChainId::from_evm(5);
+		assert_eq!(evm_chain_id.inner.to_u64(), 0x0000_0100_0000_0005);
+		assert_eq!(String::from(evm_chain_id.to_js_string()), "1099511627781");
+		assert_eq!(
+			JsTypedChainId::parse(evm_chain_id.to_js_string()).unwrap().inner,
+			evm_chain_id.inner
+		);
+		assert_eq!(
+			"0x0000040000000005".parse::<TypedChainId>().unwrap(),
+			JsTypedChainId::from_cosmos(5).inner
+		);
+		assert_eq!("2".parse::<TypedChainId>().unwrap().chain_type, None);
+		// Unknown chain type and bytes above the chain type
+		assert!("0x0000090000000005".parse::<TypedChainId>().is_err());
+		assert!("0x0001010000000005".parse::<TypedChainId>().is_err());
+
+		let mut note_builder = JsNoteBuilder::new();
+		let protocol: Protocol = JsValue::from(NoteProtocol::VAnchor.to_string()).into();
+		let version: Version = JsValue::from(NoteVersion::V1.to_string()).into();
+		note_builder = note_builder.protocol(protocol).unwrap();
+		note_builder = note_builder.version(version).unwrap();
+		note_builder = note_builder.source_chain_id(JsTypedChainId::from_substrate(1080).to_js_string());
+		note_builder = note_builder.target_chain_id(JsString::from("Ethereum:5"));
+		note_builder = note_builder.source_identifying_data(JsString::from("2"));
+		note_builder = note_builder.target_identifying_data(JsString::from("3"));
+		assert!(note_builder.build().is_err());
+	}
+
+	#[wasm_bindgen_test]
+	fn should_parse_chain_ids_with_explicit_errors() {
+		assert_eq!(
+			"Evm:5".parse::<TypedChainId>().unwrap(),
+			JsTypedChainId::from_evm(5).inner
+		);
+		let error = "0x0001010000000005".parse::<TypedChainId>().unwrap_err();
+		assert_eq!(error.code, OpStatusCode::InvalidChainId);
+		assert!(error.error_message.contains("48 bits"), "{}", error.error_message);
+		let error = "0x0000090000000005".parse::<TypedChainId>().unwrap_err();
+		assert!(error.error_message.contains("0x0900"), "{}", error.error_message);
+		for invalid in [
+			"",
+			"evm",
+			"Evm:",
+			"Evm:4294967296",
+			"Ethereum:5",
+			"0x",
+			"-1",
+			"18446744073709551616",
+		] {
+			let error = invalid.parse::<TypedChainId>().unwrap_err();
+			assert_eq!(error.code, OpStatusCode::InvalidChainId, "{}", invalid);
+		}
+
+		let builder = |target_chain_id: &str, amount: &str| {
+			let mut note_builder = JsNoteBuilder::new();
+			let protocol: Protocol = JsValue::from(NoteProtocol::VAnchor.to_string()).into();
+			let version: Version = JsValue::from(NoteVersion::V1.to_string()).into();
+			note_builder = note_builder.protocol(protocol).unwrap();
+			note_builder = note_builder.version(version).unwrap();
+			note_builder = note_builder.source_chain_id(JsString::from("Substrate:1080"));
+			note_builder = note_builder.target_chain_id(JsString::from(target_chain_id));
+			note_builder = note_builder.source_identifying_data(JsString::from("2"));
+			note_builder = note_builder.target_identifying_data(JsString::from("3"));
+			note_builder = note_builder.amount(JsString::from(amount));
+			note_builder.build()
+		};
+		// Chain ids are written to the note in decimal, whatever their input
+		let note = builder("Evm:5", "10").unwrap();
+		assert_eq!(note.source_chain_id, "2199023256632");
+		assert_eq!(note.target_chain_id, "1099511627781");
+		assert_eq!(
+			builder("0x0000010000000005", "10").unwrap().target_chain_id,
+			"1099511627781"
+		);
+		assert!(builder("Evm-5", "10").is_err());
+		assert!(builder("Evm:5", "ten").is_err());
+		assert!(builder("Evm:4294967296", "10").is_err());
+		// Untyped chain ids are kept as they are
+		assert_eq!(
+			builder("0x0000090000000005", "10").unwrap().target_chain_id,
+			"9895604649989"
+		);
+
+		// Notes written before typed chain ids hold u64 chain ids of any value
+		for legacy in ["3804000000200000", "18446744073709551615"] {
+			let note = crate::proof::test_utils::VANCHOR_NOTE_V1_X5_4.replace("/2:3/", &format!("/2:{}/", legacy));
+			assert_eq!(JsNote::deserialize(&note).unwrap().target_chain_id, legacy);
+		}
+		let note = crate::proof::test_utils::VANCHOR_NOTE_V1_X5_4.replace("/2:3/", "/2:18446744073709551616/");
+		let error = JsNote::deserialize(&note).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::InvalidTargetChain);
+	}
+
+	#[wasm_bindgen_test]
+	fn should_normalize_amounts_with_units() {
+		let builder = |amount: &str, denomination: Option<&str>, token_symbol: Option<&str>| {
+			let mut note_builder = JsNoteBuilder::new();
+			let protocol: Protocol = JsValue::from(NoteProtocol::VAnchor.to_string()).into();
+			let version: Version = JsValue::from(NoteVersion::V1.to_string()).into();
+			note_builder = note_builder.protocol(protocol).unwrap();
+			note_builder = note_builder.version(version).unwrap();
+			note_builder = note_builder.source_chain_id(JsString::from("2"));
+			note_builder = note_builder.target_chain_id(JsString::from("3"));
+			note_builder = note_builder.source_identifying_data(JsString::from("2"));
+			note_builder = note_builder.target_identifying_data(JsString::from("3"));
+			if let Some(denomination) = denomination {
+				note_builder = note_builder.denomination(JsString::from(denomination)).unwrap();
+			}
+			if let Some(token_symbol) = token_symbol {
+				note_builder = note_builder.token_symbol(JsString::from(token_symbol));
+			}
+			note_builder = note_builder.amount(JsString::from(amount));
+			note_builder.build()
+		};
+		let note = builder("10.5 WEBB", Some("18"), None).unwrap();
+		assert_eq!(note.amount.as_deref(), Some("10500000000000000000"));
+		assert_eq!(note.token_symbol.as_deref(), Some("WEBB"));
+		assert_eq!(note.display_amount().unwrap(), "10.5 WEBB");
+		// The leaf commits to the base units
+		let leaf = note.get_leaf_and_nullifier().unwrap();
+		match leaf.inner {
+			JsLeafInner::VAnchor(utxo) => assert_eq!(utxo.get_amount_raw(), 10_500_000_000_000_000_000),
+			JsLeafInner::Mixer(_) => unreachable!(),
+		}
+
+		let note = builder("10.5", Some("6"), Some("USDC")).unwrap();
+		assert_eq!(note.amount.as_deref(), Some("10500000"));
+		assert_eq!(note.display_amount().unwrap(), "10.5 USDC");
+		// Integers without a unit stay base units
+		assert_eq!(builder("10", Some("18"), None).unwrap().amount.as_deref(), Some("10"));
+		assert_eq!(builder("0x0a", None, None).unwrap().amount.as_deref(), Some("10"));
+		assert_eq!(builder("10", None, None).unwrap().display_amount().unwrap(), "10");
+
+		let invalid = |amount: &str, denomination: Option<&str>, token_symbol: Option<&str>| {
+			let error = builder(amount, denomination, token_symbol).unwrap_err();
+			js_sys::Reflect::get(&error, &JsValue::from_str("code")).unwrap()
+		};
+		assert_eq!(invalid("10.5", None, None), "InvalidDenomination");
+		assert_eq!(invalid("10 WEBB", None, None), "InvalidDenomination");
+		assert_eq!(invalid("10.5 WEBB", Some("18"), Some("EDG")), "InvalidAmount");
+		assert_eq!(invalid("10.5 WE BB", Some("18"), None), "InvalidAmount");
+		assert_eq!(invalid("0.0000001", Some("6"), None), "AmountPrecisionLoss");
+	}
+
+	#[wasm_bindgen_test]
+	fn should_reject_notes_without_circuit() {
+		let mixer_builder = |width: Option<&str>| {
+			let mut note_builder = JsNoteBuilder::new();
+			let protocol: Protocol = JsValue::from(NoteProtocol::Mixer.to_string()).into();
+			let version: Version = JsValue::from(NoteVersion::V1.to_string()).into();
+			note_builder = note_builder.protocol(protocol).unwrap();
+			note_builder = note_builder.version(version).unwrap();
+			note_builder = note_builder.source_chain_id(JsString::from("2"));
+			note_builder = note_builder.target_chain_id(JsString::from("2"));
+			note_builder = note_builder.source_identifying_data(JsString::from("2"));
+			note_builder = note_builder.target_identifying_data(JsString::from("2"));
+			if let Some(width) = width {
+				note_builder = note_builder.width(JsString::from(width)).unwrap();
+			}
+			note_builder
+		};
+
+		// Mixer notes default to the width of the mixer circuit
+		let note = mixer_builder(None).build().unwrap();
+		assert_eq!(note.width, Some(3));
+		assert!(mixer_builder(Some("4")).build().is_err());
+
+		let error = ensure_supported_circuit(NoteProtocol::Mixer, Curve::Bn254, 4, 5).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::UnsupportedParameterCombination);
+		assert!(error.error_message.contains("Bn254 x3^5, Bls381 x3^5"));
+		assert!(ensure_supported_circuit(NoteProtocol::VAnchor, Curve::Bls381, 5, 5).is_err());
+	}
+
 	#[wasm_bindgen_test]
 	fn should_deserialize_vanchor_note() {
 		let vanchor_note_str = "webb://v1:vanchor/2:3/2:3/0300000000000000000000000000000000000000000000000000000000000000:0a00000000000000000000000000000000000000000000000000000000000000:7798d054444ec463be7d41ad834147b5b2c468182c7cd6a601aec29a273fca05:bf5d780608f5b8a8db1dc87356a225a0324a1db61903540daaedd54ab10a4124/?curve=Bn254&width=5&exp=5&hf=Poseidon&backend=Arkworks&token=EDG&denom=18&amount=10&index=10";
 		let note = JsNote::deserialize(vanchor_note_str).unwrap();
 		// Generate leaf to trigger any errors
 		note.get_leaf_commitment().unwrap();
-		assert_eq!(note.serialize(), vanchor_note_str);
+		assert_eq!(note.serialize(None), vanchor_note_str);
+	}
+
+	#[wasm_bindgen_test]
+	fn should_require_leaf_params() {
+		let mixer_note = "webb://v1:mixer/2:2/2:2/fd717cfe463b3ffec71ee6b7606bbd0179170510abf41c9f16c1d20ca9923f0e:18b6b080e6a43262f00f6fb3da0d2409c4871b8f26d89d5c8836358e1af5a41c/?curve=Bn254&width=3&exp=5&hf=Poseidon&backend=Arkworks&token=EDG&denom=18&amount=10&index=10";
+		let note = JsNote::deserialize(mixer_note).unwrap();
+		let bare_note = JsNote::deserialize(&mixer_note.replace("curve=Bn254&width=3&exp=5&", "")).unwrap();
+
+		let error = bare_note.get_leaf_and_nullifier().err().unwrap();
+		assert_eq!(error.code, OpStatusCode::InvalidCurve);
+
+		let params = LeafParams::new(
+			JsValue::from(Curve::Bn254.to_string()).into(),
+			JsString::from("3"),
+			JsString::from("5"),
+			None,
+		)
+		.unwrap();
+		assert_eq!(note.leaf_params().unwrap(), params);
+		assert_eq!(
+			bare_note.get_leaf_commitment_with(&params).unwrap().to_vec(),
+			note.get_leaf_commitment().unwrap().to_vec()
+		);
+	}
+
+	#[wasm_bindgen_test]
+	fn should_keep_vanchor_secrets_in_utxo_layout() {
+		let private_key = Bn254Fr::from(7u64).into_repr().to_bytes_be();
+		let blinding = Bn254Fr::from(11u64).into_repr().to_bytes_be();
+
+		let mut note_builder = JsNoteBuilder::new();
+		let protocol: Protocol = JsValue::from(NoteProtocol::VAnchor.to_string()).into();
+		let version: Version = JsValue::from(NoteVersion::V1.to_string()).into();
+		note_builder = note_builder.protocol(protocol).unwrap();
+		note_builder = note_builder.version(version).unwrap();
+		note_builder = note_builder.source_chain_id(JsString::from("2"));
+		note_builder = note_builder.target_chain_id(JsString::from("3"));
+		note_builder = note_builder.source_identifying_data(JsString::from("2"));
+		note_builder = note_builder.target_identifying_data(JsString::from("3"));
+		note_builder = note_builder.amount(JsString::from("10"));
+		note_builder = note_builder.index(JsString::from("4")).unwrap();
+		note_builder = note_builder
+			.set_private_key(Uint8Array::from(private_key.as_slice()))
+			.unwrap();
+		note_builder = note_builder
+			.set_blinding(Uint8Array::from(blinding.as_slice()))
+			.unwrap();
+		let note = note_builder.build().unwrap();
+
+		let expected = JsUtxo::new(
+			Curve::Bn254,
+			Backend::Arkworks,
+			10,
+			3,
+			Some(blinding.clone()),
+			None,
+			Some(private_key.clone()),
+			Some(4),
+		)
+		.unwrap();
+		let utxo = note.get_utxo().unwrap();
+		assert_eq!(utxo.get_secret_key(), Some(private_key));
+		assert_eq!(utxo.get_blinding(), blinding);
+		assert_eq!(utxo.get_commitment(), expected.get_commitment());
+		assert_eq!(utxo.get_nullifier().unwrap(), expected.get_nullifier().unwrap());
+		assert_eq!(expected.to_note_secrets().unwrap(), note.secrets);
+	}
+
+	#[wasm_bindgen_test]
+	fn should_compare_secrets_and_commitments() {
+		let mixer_note = "webb://v1:mixer/2:2/2:2/fd717cfe463b3ffec71ee6b7606bbd0179170510abf41c9f16c1d20ca9923f0e:18b6b080e6a43262f00f6fb3da0d2409c4871b8f26d89d5c8836358e1af5a41c/?curve=Bn254&width=3&exp=5&hf=Poseidon&backend=Arkworks&token=EDG&denom=18&amount=10";
+		let note = JsNote::deserialize(mixer_note).unwrap();
+		// Same secrets in a note for another chain
+		let copy = JsNote::deserialize(&mixer_note.replace("2:2/2:2", "3:3/4:4")).unwrap();
+		assert!(note.equals_secret(&copy));
+
+		let mut other = note.clone();
+		other.secrets[1][31] ^= 1;
+		assert!(!note.equals_secret(&other));
+		other.secrets = vec![note.secrets[0].clone()];
+		assert!(!note.equals_secret(&other));
+
+		let leaf = note.get_leaf_commitment().unwrap();
+		assert!(note.commitment_equals(leaf.clone()).unwrap());
+		assert!(!note.commitment_equals(Uint8Array::from(&leaf.to_vec()[..31])).unwrap());
+		let other_leaf = JsNote::deserialize(&mixer_note.replace("fd71", "fd72")).unwrap();
+		assert!(!other_leaf.commitment_equals(leaf).unwrap());
 	}
 }
@@ -0,0 +1,312 @@
+//! Preimage of a note leaf for auditors, who re-derive the commitment with
+//! their own hash implementation. Every hash the leaf and the nullifier go
+//! through is listed in order, with its parameters and named inputs, field
+//! elements being `0x` prefixed 32 bytes big endian:
+//!
+//! - mixer: `leaf = H(secret, nullifier)`, `nullifierHash = H(nullifier,
+//!   nullifier)`
+//! - vanchor: `publicKey = H(privateKey)`, `commitment = H(chainId, amount,
+//!   publicKey, blinding)`, `signature = H(privateKey, commitment, index)`,
+//!   `nullifier = H(commitment, index, signature)`
+//!
+//! `H` is Poseidon of the note curve with width `inputs + 1` and exponent 5,
+//! or for MiMC mixer notes the 220 rounds MiMC sponge of width 3 with the
+//! inputs padded with zeros. The VAnchor steps after the commitment need the
+//! leaf index of the note.
+use ark_bls12_381::Fr as Bls381Fr;
+use ark_bn254::Fr as Bn254Fr;
+use ark_ff::{BigInteger, PrimeField};
+use arkworks_native_gadgets::poseidon::{FieldHasher, Poseidon};
+use arkworks_setups::utxo::Utxo;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::field::field_element_to_hex;
+use crate::hash::{mimc_hash_raw, poseidon_params, PoseidonField};
+use crate::note::{JsLeafInner, JsNote};
+use crate::types::{Curve, HashFunction, OpStatusCode, OperationError};
+use crate::utxo::JsUtxoInner;
+
+/// Poseidon exponent of every note hash
+const POSEIDON_EXPONENTIATION: i8 = 5;
+/// Width of the MiMC sponge the mixer leaves are hashed with
+const MIMC_WIDTH: usize = 3;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PreimageInput {
+	pub name: String,
+	pub value: String,
+}
+
+/// A hash of the derivation, its inputs in the order they're hashed
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PreimageHash {
+	/// Name of the value the hash gives, an input of later hashes
+	pub output: String,
+	pub hash_function: String,
+	pub width: usize,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub exponentiation: Option<i8>,
+	pub inputs: Vec<PreimageInput>,
+	pub value: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct LeafPreimage {
+	pub protocol: String,
+	pub curve: String,
+	/// Hashes in derivation order, the leaf being the output of `leaf` or
+	/// `commitment`
+	pub hashes: Vec<PreimageHash>,
+}
+
+/// Hashes of a derivation, with the values of the outputs so far
+struct Derivation<F: PoseidonField> {
+	hashes: Vec<PreimageHash>,
+	values: Vec<(&'static str, F)>,
+}
+
+impl<F: PoseidonField> Derivation<F> {
+	fn new(inputs: &[(&'static str, F)]) -> Self {
+		Self {
+			hashes: Vec::new(),
+			values: inputs.to_vec(),
+		}
+	}
+
+	fn value(&self, name: &str) -> F {
+		self.values
+			.iter()
+			.find(|(value_name, _)| *value_name == name)
+			.map(|(_, value)| *value)
+			.expect("inputs are named before they're hashed")
+	}
+
+	fn record(&mut self, output: &'static str, hash_function: HashFunction, inputs: &[&'static str], value: F) {
+		let (width, exponentiation) = match hash_function {
+			HashFunction::Poseidon => (inputs.len() + 1, Some(POSEIDON_EXPONENTIATION)),
+			HashFunction::MiMC220 => (MIMC_WIDTH, None),
+		};
+		self.hashes.push(PreimageHash {
+			output: output.to_string(),
+			hash_function: hash_function.to_string(),
+			width,
+			exponentiation,
+			inputs: inputs
+				.iter()
+				.map(|name| PreimageInput {
+					name: name.to_string(),
+					value: field_element_to_hex(&self.value(name)),
+				})
+				.collect(),
+			value: field_element_to_hex(&value),
+		});
+		self.values.push((output, value));
+	}
+
+	fn poseidon(&mut self, output: &'static str, inputs: &[&'static str]) -> Result<F, OperationError> {
+		let values: Vec<F> = inputs.iter().map(|name| self.value(name)).collect();
+		let hasher = Poseidon::new(poseidon_params::<F>(POSEIDON_EXPONENTIATION, inputs.len() as u8 + 1));
+		let value = hasher
+			.hash(&values)
+			.map_err(|e| OperationError::new_with_message(OpStatusCode::FailedToGenerateTheLeaf, e.to_string()))?;
+		self.record(output, HashFunction::Poseidon, inputs, value);
+		Ok(value)
+	}
+}
+
+impl Derivation<Bn254Fr> {
+	fn mimc(&mut self, output: &'static str, inputs: &[&'static str]) -> Result<Bn254Fr, OperationError> {
+		let bytes: Vec<[u8; 32]> = inputs
+			.iter()
+			.map(|name| {
+				let value = self.value(name).into_repr().to_bytes_be();
+				value.try_into().unwrap()
+			})
+			.collect();
+		let value = Bn254Fr::from_be_bytes_mod_order(&mimc_hash_raw(&bytes)?);
+		self.record(output, HashFunction::MiMC220, inputs, value);
+		Ok(value)
+	}
+}
+
+fn mixer_hashes<F: PoseidonField>(secrets: &[Vec<u8>]) -> Result<Vec<PreimageHash>, OperationError> {
+	let mut derivation = Derivation::new(&[
+		("secret", F::from_be_bytes_mod_order(&secrets[0])),
+		("nullifier", F::from_be_bytes_mod_order(&secrets[1])),
+	]);
+	derivation.poseidon("leaf", &["secret", "nullifier"])?;
+	derivation.poseidon("nullifierHash", &["nullifier", "nullifier"])?;
+	Ok(derivation.hashes)
+}
+
+fn mimc_mixer_hashes(secrets: &[Vec<u8>]) -> Result<Vec<PreimageHash>, OperationError> {
+	let mut derivation = Derivation::new(&[
+		("secret", Bn254Fr::from_be_bytes_mod_order(&secrets[0])),
+		("nullifier", Bn254Fr::from_be_bytes_mod_order(&secrets[1])),
+	]);
+	derivation.mimc("leaf", &["secret", "nullifier"])?;
+	derivation.mimc("nullifierHash", &["nullifier", "nullifier"])?;
+	Ok(derivation.hashes)
+}
+
+fn vanchor_hashes(utxo: &Utxo<Bn254Fr>) -> Result<Vec<PreimageHash>, OperationError> {
+	let private_key = utxo.keypair.secret_key.ok_or_else(|| {
+		let message = "The note holds no private key".to_string();
+		OperationError::new_with_message(OpStatusCode::InvalidNoteSecrets, message)
+	})?;
+	let mut derivation = Derivation::new(&[
+		("privateKey", private_key),
+		("chainId", utxo.chain_id),
+		("amount", utxo.amount),
+		("blinding", utxo.blinding),
+	]);
+	derivation.poseidon("publicKey", &["privateKey"])?;
+	derivation.poseidon("commitment", &["chainId", "amount", "publicKey", "blinding"])?;
+	if let Some(index) = utxo.index {
+		derivation.values.push(("index", Bn254Fr::from(index)));
+		derivation.poseidon("signature", &["privateKey", "commitment", "index"])?;
+		derivation.poseidon("nullifier", &["commitment", "index", "signature"])?;
+	}
+	Ok(derivation.hashes)
+}
+
+impl JsNote {
+	/// Every hash from the note secrets to its leaf and nullifier, checked to
+	/// give the leaf the note is deposited with
+	pub fn preimage(&self) -> Result<LeafPreimage, OperationError> {
+		let params = self.leaf_params()?;
+		let leaf = self.get_leaf_with(&params)?;
+		let (hashes, expected) = match &leaf.inner {
+			JsLeafInner::Mixer(mixer_leaf) => {
+				let hashes = match (params.hash_function, params.curve) {
+					(HashFunction::MiMC220, _) => mimc_mixer_hashes(&self.secrets)?,
+					(HashFunction::Poseidon, Curve::Bn254) => mixer_hashes::<Bn254Fr>(&self.secrets)?,
+					(HashFunction::Poseidon, Curve::Bls381) => mixer_hashes::<Bls381Fr>(&self.secrets)?,
+				};
+				(hashes, mixer_leaf.leaf_bytes.clone())
+			}
+			JsLeafInner::VAnchor(utxo) => match &utxo.inner {
+				JsUtxoInner::Bn254(utxo) => (vanchor_hashes(utxo)?, utxo.commitment.into_repr().to_bytes_be()),
+			},
+		};
+		let expected = format!("0x{}", hex::encode(expected));
+		if !hashes.iter().any(|hash| hash.value == expected) {
+			let message = "The preimage doesn't give the leaf of the note".to_string();
+			return Err(OperationError::new_with_message(
+				OpStatusCode::FailedToGenerateTheLeaf,
+				message,
+			));
+		}
+		Ok(LeafPreimage {
+			protocol: self.protocol.to_string(),
+			curve: params.curve.to_string(),
+			hashes,
+		})
+	}
+}
+
+#[wasm_bindgen(typescript_custom_section)]
+const LEAF_PREIMAGE: &str = r#"
+export interface PreimageInput {
+	name: string;
+	value: string;
+}
+
+export interface PreimageHash {
+	output: string;
+	hashFunction: HashFunction;
+	width: number;
+	exponentiation?: number;
+	inputs: PreimageInput[];
+	value: string;
+}
+
+export interface LeafPreimage {
+	protocol: NoteProtocol;
+	curve: Curve;
+	hashes: PreimageHash[];
+}
+"#;
+
+#[wasm_bindgen]
+extern "C" {
+	#[wasm_bindgen(typescript_type = "LeafPreimage")]
+	pub type WasmLeafPreimage;
+}
+
+#[allow(clippy::unused_unit)]
+#[wasm_bindgen]
+impl JsNote {
+	/// The hashes deriving the leaf and nullifier from the secrets, with
+	/// their inputs in order, see `LeafPreimage`. The output carries the
+	/// note secrets
+	#[wasm_bindgen(js_name = exportPreimage)]
+	pub fn export_preimage(&self) -> Result<WasmLeafPreimage, JsValue> {
+		let preimage = JsValue::from_serde(&self.preimage()?)
+			.map_err(|e| OperationError::new_with_message(OpStatusCode::SerializationFailed, e.to_string()))?;
+		Ok(preimage.unchecked_into())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use wasm_bindgen_test::*;
+
+	use super::*;
+	use crate::proof::test_utils::{MIXER_NOTE_V1_X5_5, VANCHOR_NOTE_V1_X5_4};
+
+	fn output<'a>(preimage: &'a LeafPreimage, name: &str) -> &'a PreimageHash {
+		preimage.hashes.iter().find(|hash| hash.output == name).unwrap()
+	}
+
+	#[wasm_bindgen_test]
+	fn should_export_the_mixer_preimage() {
+		let note = JsNote::deserialize(MIXER_NOTE_V1_X5_5).unwrap();
+		let preimage = note.preimage().unwrap();
+		let leaf = output(&preimage, "leaf");
+		assert_eq!((leaf.width, leaf.exponentiation), (3, Some(5)));
+		let names: Vec<&str> = leaf.inputs.iter().map(|input| input.name.as_str()).collect();
+		assert_eq!(names, ["secret", "nullifier"]);
+		// Secrets are reduced into the field before they're hashed
+		let secret = Bn254Fr::from_be_bytes_mod_order(&note.secrets[0]);
+		assert_eq!(leaf.inputs[0].value, field_element_to_hex(&secret));
+		assert_eq!(
+			leaf.value,
+			format!("0x{}", hex::encode(note.get_leaf_commitment().unwrap().to_vec()))
+		);
+
+		let mimc = JsNote::deserialize(&MIXER_NOTE_V1_X5_5.replace("hf=Poseidon", "hf=MiMC220")).unwrap();
+		let preimage = mimc.preimage().unwrap();
+		assert_eq!(output(&preimage, "leaf").hash_function, "MiMC220");
+		assert_eq!(output(&preimage, "nullifierHash").exponentiation, None);
+	}
+
+	#[wasm_bindgen_test]
+	fn should_export_the_vanchor_preimage() {
+		let note = JsNote::deserialize(VANCHOR_NOTE_V1_X5_4).unwrap();
+		let preimage = note.preimage().unwrap();
+		let outputs: Vec<&str> = preimage.hashes.iter().map(|hash| hash.output.as_str()).collect();
+		let utxo = note.get_js_utxo().unwrap();
+		let commitment = output(&preimage, "commitment");
+		assert_eq!(commitment.width, 5);
+		assert_eq!(commitment.value, format!("0x{}", hex::encode(utxo.get_commitment())));
+		assert_eq!(
+			commitment.inputs[2].value,
+			format!("0x{}", hex::encode(utxo.get_public_key()))
+		);
+		match utxo.get_nullifier() {
+			Ok(nullifier) => {
+				assert_eq!(outputs, ["publicKey", "commitment", "signature", "nullifier"]);
+				assert_eq!(
+					output(&preimage, "nullifier").value,
+					format!("0x{}", hex::encode(nullifier))
+				);
+			}
+			Err(_) => assert_eq!(outputs, ["publicKey", "commitment"]),
+		}
+	}
+}
@@ -0,0 +1,228 @@
+//! Links to hand a note to its recipient with hints on how to withdraw it.
+//! The hints follow the note URI in the fragment,
+//!
+//! `webb://..?..#untrusted&relayer=<url>&relayer=<url>&expires=<unix seconds>`
+//!
+//! and aren't signed: whoever passes the link along can change them, so the
+//! fragment starts with `untrusted` and wallets should treat the relayers as
+//! suggestions, never as endorsed. The note itself parses the same with or
+//! without them, `#` is escaped everywhere in the note URI.
+use js_sys::{Array, JsString};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::note::versioning::{percent_decode, percent_encode};
+use crate::note::JsNote;
+use crate::types::{OpStatusCode, OperationError, StringArray};
+
+pub const UNTRUSTED_HINTS: &str = "untrusted";
+
+fn invalid_hint(message: String) -> OperationError {
+	OperationError::new_with_message(OpStatusCode::InvalidNoteMiscData, message)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RelayerHints {
+	/// http(s) endpoints of relayers that can withdraw the note
+	pub relayers: Vec<String>,
+	/// Unix timestamp in seconds after which the sender no longer vouches for
+	/// the hints
+	pub expires_at: Option<u64>,
+}
+
+impl RelayerHints {
+	pub fn new(relayers: Vec<String>, expires_at: Option<u64>) -> Result<Self, OperationError> {
+		for relayer in &relayers {
+			if !(relayer.starts_with("https://") || relayer.starts_with("http://")) {
+				return Err(invalid_hint(format!(
+					"Relayer hints are http(s) URLs, not {:?}",
+					relayer
+				)));
+			}
+		}
+		Ok(Self { relayers, expires_at })
+	}
+
+	pub fn is_empty(&self) -> bool {
+		*self == RelayerHints::default()
+	}
+
+	/// Whether the hints expired at unix time `now`, in seconds
+	pub fn is_expired(&self, now: u64) -> bool {
+		self.expires_at.map_or(false, |expires_at| now >= expires_at)
+	}
+}
+
+/// A note with the relayer hints sent along with it
+#[wasm_bindgen(js_name = NoteShareLink)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareLink {
+	#[wasm_bindgen(skip)]
+	pub note: JsNote,
+	#[wasm_bindgen(skip)]
+	pub hints: RelayerHints,
+}
+
+impl ShareLink {
+	pub fn new(note: JsNote, hints: RelayerHints) -> Self {
+		Self { note, hints }
+	}
+
+	/// Read a link, a bare note URI is a link without hints
+	pub fn parse(link: &str) -> Result<Self, OperationError> {
+		let (note, fragment) = link.split_once('#').unwrap_or((link, ""));
+		let note = JsNote::deserialize(note)?;
+		if fragment.is_empty() {
+			return Ok(Self::new(note, RelayerHints::default()));
+		}
+
+		let mut parts = fragment.split('&');
+		if parts.next() != Some(UNTRUSTED_HINTS) {
+			return Err(invalid_hint("Relayer hints should be marked untrusted".to_string()));
+		}
+		let mut relayers = Vec::new();
+		let mut expires_at = None;
+		for part in parts {
+			let (key, value) = part
+				.split_once('=')
+				.ok_or_else(|| invalid_hint(format!("Invalid relayer hint: {:?}", part)))?;
+			match key {
+				"relayer" => relayers.push(percent_decode(value)?),
+				"expires" if expires_at.is_none() => {
+					let seconds = value
+						.parse()
+						.map_err(|_| invalid_hint(format!("Invalid hint expiry: {:?}", value)))?;
+					expires_at = Some(seconds);
+				}
+				_ => return Err(invalid_hint(format!("Unknown or duplicate hint: {}", key))),
+			}
+		}
+		Ok(Self::new(note, RelayerHints::new(relayers, expires_at)?))
+	}
+}
+
+impl core::fmt::Display for ShareLink {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		write!(f, "{}", self.note)?;
+		if self.hints.is_empty() {
+			return Ok(());
+		}
+		write!(f, "#{}", UNTRUSTED_HINTS)?;
+		for relayer in &self.hints.relayers {
+			write!(f, "&relayer={}", percent_encode(relayer))?;
+		}
+		if let Some(expires_at) = self.hints.expires_at {
+			write!(f, "&expires={}", expires_at)?;
+		}
+		Ok(())
+	}
+}
+
+#[wasm_bindgen(js_class = NoteShareLink)]
+impl ShareLink {
+	/// Link to `note` suggesting `relayers`, optionally until the unix time
+	/// `expiresAt` in seconds
+	#[wasm_bindgen(constructor)]
+	pub fn js_new(note: &JsNote, relayers: StringArray, expires_at: Option<u64>) -> Result<ShareLink, JsValue> {
+		let relayers = Array::from(&relayers)
+			.iter()
+			.map(|relayer| relayer.as_string().ok_or(OpStatusCode::InvalidNoteMiscData))
+			.collect::<Result<Vec<_>, _>>()?;
+		Ok(ShareLink::new(note.clone(), RelayerHints::new(relayers, expires_at)?))
+	}
+
+	#[wasm_bindgen(js_name = parse)]
+	pub fn js_parse(link: JsString) -> Result<ShareLink, JsValue> {
+		Ok(ShareLink::parse(&String::from(link))?)
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn note(&self) -> JsNote {
+		self.note.clone()
+	}
+
+	/// Relayers suggested by whoever shared the link, unverified
+	#[wasm_bindgen(getter, js_name = untrustedRelayers)]
+	pub fn untrusted_relayers(&self) -> StringArray {
+		let relayers: Array = self
+			.hints
+			.relayers
+			.iter()
+			.map(|relayer| JsString::from(relayer.as_str()))
+			.collect();
+		relayers.unchecked_into()
+	}
+
+	#[wasm_bindgen(getter, js_name = expiresAt)]
+	pub fn expires_at(&self) -> Option<u64> {
+		self.hints.expires_at
+	}
+
+	/// Whether the hints expired, at the unix time `now` in seconds or at
+	/// the current time
+	#[wasm_bindgen(js_name = isExpired)]
+	pub fn js_is_expired(&self, now: Option<u64>) -> bool {
+		let now = now.unwrap_or_else(|| (js_sys::Date::now() / 1000.0) as u64);
+		self.hints.is_expired(now)
+	}
+
+	#[wasm_bindgen(js_name = toString)]
+	pub fn js_to_string(&self) -> JsString {
+		JsString::from(self.to_string())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use wasm_bindgen_test::*;
+
+	use super::*;
+	use crate::proof::test_utils::VANCHOR_NOTE_V1_X5_4;
+
+	#[wasm_bindgen_test]
+	fn should_round_trip_share_links() {
+		let note = JsNote::deserialize(VANCHOR_NOTE_V1_X5_4).unwrap();
+		let relayers = vec![
+			"https://relayer.webb.tools".to_string(),
+			"http://127.0.0.1:9955/?a=b&c#d".to_string(),
+		];
+		let link = ShareLink::new(note.clone(), RelayerHints::new(relayers, Some(1_700_000_000)).unwrap());
+		let serialized = link.to_string();
+		assert!(serialized.starts_with(&format!("{}#untrusted&relayer=https%3A%2F%2F", note)));
+		assert!(serialized.ends_with("&expires=1700000000"));
+		assert_eq!(ShareLink::parse(&serialized).unwrap(), link);
+		assert!(!link.hints.is_expired(1_699_999_999));
+		assert!(link.hints.is_expired(1_700_000_000));
+
+		// A bare note is a link without hints, and serializes as the note
+		let bare = ShareLink::parse(VANCHOR_NOTE_V1_X5_4).unwrap();
+		assert!(bare.hints.is_empty());
+		assert_eq!(bare.to_string(), note.to_string());
+		assert!(!bare.hints.is_expired(u64::MAX));
+	}
+
+	#[wasm_bindgen_test]
+	fn should_reject_invalid_hints() {
+		let invalid = |fragment: &str| {
+			ShareLink::parse(&format!("{}#{}", VANCHOR_NOTE_V1_X5_4, fragment))
+				.unwrap_err()
+				.code
+		};
+		assert_eq!(invalid("relayer=https%3A%2F%2Fa"), OpStatusCode::InvalidNoteMiscData);
+		assert_eq!(
+			invalid("signed&relayer=https%3A%2F%2Fa"),
+			OpStatusCode::InvalidNoteMiscData
+		);
+		assert_eq!(
+			invalid("untrusted&relayer=javascript%3Aalert(1)"),
+			OpStatusCode::InvalidNoteMiscData
+		);
+		assert_eq!(invalid("untrusted&expires=soon"), OpStatusCode::InvalidNoteMiscData);
+		assert_eq!(
+			invalid("untrusted&expires=1&expires=2"),
+			OpStatusCode::InvalidNoteMiscData
+		);
+		assert_eq!(invalid("untrusted&fee=1"), OpStatusCode::InvalidNoteMiscData);
+		assert!(ShareLink::parse(&format!("{}#untrusted", VANCHOR_NOTE_V1_X5_4)).is_ok());
+	}
+}
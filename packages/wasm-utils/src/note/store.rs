@@ -0,0 +1,319 @@
+use js_sys::{Array, JsString, Uint8Array};
+use rand::rngs::OsRng;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use xsalsa20poly1305::aead::{Aead, NewAead};
+use xsalsa20poly1305::{generate_nonce, Key, Nonce, XSalsa20Poly1305, KEY_SIZE, NONCE_SIZE};
+
+use crate::note::amount::NoteAmount;
+use crate::note::JsNote;
+use crate::proof::vanchor::SUPPORTED_INPUT_COUNT;
+use crate::types::{JsNoteArray, NoteProtocol, OpStatusCode, OperationError, TypedChainId};
+
+/// Most notes a VAnchor transaction spends, smaller sets are padded to 2 or
+/// 16 inputs
+const MAX_INPUT_COUNT: usize = SUPPORTED_INPUT_COUNT[SUPPORTED_INPUT_COUNT.len() - 1];
+
+/// A set of notes held by a wallet
+#[wasm_bindgen(js_name = NoteStore)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct JsNoteStore {
+	#[wasm_bindgen(skip)]
+	pub notes: Vec<JsNote>,
+}
+
+fn cipher(key: &[u8]) -> Result<XSalsa20Poly1305, OperationError> {
+	if key.len() != KEY_SIZE {
+		let message = format!("Store encryption key should be {} bytes", KEY_SIZE);
+		return Err(OperationError::new_with_message(
+			OpStatusCode::InvalidArrayLength,
+			message,
+		));
+	}
+	Ok(XSalsa20Poly1305::new(Key::from_slice(key)))
+}
+
+// Chain ids may be decimal or hex, so compare them as typed chain ids
+fn same_chain(a: &str, b: &str) -> bool {
+	match (a.parse::<TypedChainId>(), b.parse::<TypedChainId>()) {
+		(Ok(a), Ok(b)) => a == b,
+		_ => a == b,
+	}
+}
+
+impl JsNoteStore {
+	pub fn new(notes: Vec<JsNote>) -> Self {
+		Self { notes }
+	}
+
+	pub fn filter(&self, target_chain_id: Option<&str>, token_symbol: Option<&str>) -> JsNoteStore {
+		let notes = self
+			.notes
+			.iter()
+			.filter(|note| target_chain_id.map_or(true, |chain_id| same_chain(&note.target_chain_id, chain_id)))
+			.filter(|note| token_symbol.map_or(true, |symbol| note.token_symbol.as_deref() == Some(symbol)))
+			.cloned()
+			.collect();
+		JsNoteStore::new(notes)
+	}
+
+	/// Balance of the notes spendable on `target_chain_id` in `token_symbol`,
+	/// amounts of other chains or tokens don't add up
+	pub fn total_balance(&self, target_chain_id: &str, token_symbol: &str) -> Result<NoteAmount, OperationError> {
+		self.filter(Some(target_chain_id), Some(token_symbol))
+			.notes
+			.iter()
+			.try_fold(NoteAmount::default(), |total, note| {
+				total.checked_add(&note.amount_value()?)
+			})
+	}
+
+	/// Pick VAnchor notes of `token_symbol` spendable on `target_chain_id`
+	/// covering `amount`, largest first so spends use as few inputs as
+	/// possible. A transaction spends at most 16 inputs
+	pub fn select_inputs_for_amount(
+		&self,
+		amount: &NoteAmount,
+		target_chain_id: &str,
+		token_symbol: &str,
+	) -> Result<JsNoteStore, OperationError> {
+		let mut candidates = Vec::new();
+		for note in self
+			.filter(Some(target_chain_id), Some(token_symbol))
+			.notes
+			.into_iter()
+			.filter(|note| note.protocol == NoteProtocol::VAnchor)
+		{
+			candidates.push((note.amount_value()?, note));
+		}
+		candidates.sort_by(|a, b| b.0.cmp(&a.0));
+
+		let mut selected = Vec::new();
+		let mut total = NoteAmount::default();
+		for (value, note) in &candidates {
+			if total >= *amount {
+				break;
+			}
+			total = total.checked_add(value)?;
+			selected.push(note.clone());
+		}
+
+		if total < *amount {
+			let message = format!("Notes hold {} but {} is required", total, amount);
+			return Err(OperationError::new_with_message(
+				OpStatusCode::InsufficientBalance,
+				message,
+			));
+		}
+		if selected.len() > MAX_INPUT_COUNT {
+			let message = format!(
+				"Covering {} takes {} notes, more than the {} inputs of a transaction",
+				amount,
+				selected.len(),
+				MAX_INPUT_COUNT
+			);
+			let mut error = OperationError::new_with_message(OpStatusCode::InvalidInputUtxoConfig, message);
+			error.data = Some(format!(
+				"{{\"inputCount\":{},\"maxInputCount\":{}}}",
+				selected.len(),
+				MAX_INPUT_COUNT
+			));
+			return Err(error);
+		}
+		Ok(JsNoteStore::new(selected))
+	}
+
+	/// Encrypt the note backups, metadata included, with a 32 byte key, the
+	/// nonce is prepended to the ciphertext
+	pub fn export_encrypted(&self, key: &[u8]) -> Result<Vec<u8>, OperationError> {
+		let cipher = cipher(key)?;
+		let nonce = generate_nonce(&mut OsRng);
+		let plaintext = self
+			.notes
+			.iter()
+			.map(JsNote::to_backup)
+			.collect::<Result<Vec<_>, _>>()?
+			.join("\n");
+		let ciphertext = cipher
+			.encrypt(&nonce, plaintext.as_bytes())
+			.map_err(|_| OperationError::from(OpStatusCode::SerializationFailed))?;
+
+		let mut exported = nonce.to_vec();
+		exported.extend_from_slice(&ciphertext);
+		Ok(exported)
+	}
+
+	pub fn import_encrypted(key: &[u8], data: &[u8]) -> Result<JsNoteStore, OperationError> {
+		let cipher = cipher(key)?;
+		if data.len() < NONCE_SIZE {
+			return Err(OpStatusCode::DecryptionFailed.into());
+		}
+		let (nonce, ciphertext) = data.split_at(NONCE_SIZE);
+		let plaintext = cipher
+			.decrypt(Nonce::from_slice(nonce), ciphertext)
+			.map_err(|_| OperationError::from(OpStatusCode::DecryptionFailed))?;
+		let plaintext =
+			String::from_utf8(plaintext).map_err(|_| OperationError::from(OpStatusCode::DecryptionFailed))?;
+
+		let notes = plaintext
+			.lines()
+			.filter(|line| !line.is_empty())
+			.map(JsNote::from_backup)
+			.collect::<Result<Vec<_>, _>>()?;
+		Ok(JsNoteStore::new(notes))
+	}
+}
+
+#[allow(clippy::unused_unit)]
+#[wasm_bindgen(js_class = NoteStore)]
+impl JsNoteStore {
+	#[wasm_bindgen(constructor)]
+	pub fn js_new() -> JsNoteStore {
+		JsNoteStore::default()
+	}
+
+	pub fn add(&mut self, note: &JsNote) {
+		self.notes.push(note.clone());
+	}
+
+	#[wasm_bindgen(js_name = addFromString)]
+	pub fn add_from_string(&mut self, note: JsString) -> Result<(), JsValue> {
+		let note: String = note.into();
+		self.notes.push(JsNote::deserialize(&note)?);
+		Ok(())
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn length(&self) -> usize {
+		self.notes.len()
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn notes(&self) -> JsNoteArray {
+		let notes: Array = self.notes.iter().cloned().map(JsValue::from).collect();
+		notes.unchecked_into()
+	}
+
+	#[wasm_bindgen(js_name = filter)]
+	pub fn js_filter(&self, target_chain_id: Option<String>, token_symbol: Option<String>) -> JsNoteStore {
+		self.filter(target_chain_id.as_deref(), token_symbol.as_deref())
+	}
+
+	#[wasm_bindgen(js_name = totalBalance)]
+	pub fn js_total_balance(&self, target_chain_id: String, token_symbol: String) -> Result<NoteAmount, JsValue> {
+		Ok(self.total_balance(&target_chain_id, &token_symbol)?)
+	}
+
+	#[wasm_bindgen(js_name = selectInputsForAmount)]
+	pub fn js_select_inputs_for_amount(
+		&self,
+		amount: &NoteAmount,
+		target_chain_id: String,
+		token_symbol: String,
+	) -> Result<JsNoteStore, JsValue> {
+		Ok(self.select_inputs_for_amount(amount, &target_chain_id, &token_symbol)?)
+	}
+
+	#[wasm_bindgen(js_name = exportEncrypted)]
+	pub fn js_export_encrypted(&self, key: Uint8Array) -> Result<Uint8Array, JsValue> {
+		let exported = self.export_encrypted(&key.to_vec())?;
+		Ok(Uint8Array::from(exported.as_slice()))
+	}
+
+	#[wasm_bindgen(js_name = importEncrypted)]
+	pub fn js_import_encrypted(key: Uint8Array, data: Uint8Array) -> Result<JsNoteStore, JsValue> {
+		Ok(JsNoteStore::import_encrypted(&key.to_vec(), &data.to_vec())?)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use wasm_bindgen_test::*;
+
+	use super::*;
+
+	const MIXER_NOTE: &str = "webb://v1:mixer/2:2/2:2/fd717cfe463b3ffec71ee6b7606bbd0179170510abf41c9f16c1d20ca9923f0e:18b6b080e6a43262f00f6fb3da0d2409c4871b8f26d89d5c8836358e1af5a41c/?curve=Bn254&width=3&exp=5&hf=Poseidon&backend=Arkworks&token=EDG&denom=18&amount=10&index=10";
+	const VANCHOR_NOTE: &str = "webb://v1:vanchor/2:3/2:3/0300000000000000000000000000000000000000000000000000000000000000:0a00000000000000000000000000000000000000000000000000000000000000:7798d054444ec463be7d41ad834147b5b2c468182c7cd6a601aec29a273fca05:bf5d780608f5b8a8db1dc87356a225a0324a1db61903540daaedd54ab10a4124/?curve=Bn254&width=5&exp=5&hf=Poseidon&backend=Arkworks&token=EDG&denom=18&amount=10&index=10";
+
+	fn store() -> JsNoteStore {
+		let mut store = JsNoteStore::default();
+		store.add_from_string(MIXER_NOTE.into()).unwrap();
+		store.add_from_string(VANCHOR_NOTE.into()).unwrap();
+		store
+			.add_from_string(VANCHOR_NOTE.replace("amount=10", "amount=25").into())
+			.unwrap();
+		store
+			.add_from_string(VANCHOR_NOTE.replace("token=EDG", "token=WEBB").into())
+			.unwrap();
+		store
+	}
+
+	#[wasm_bindgen_test]
+	fn should_filter_and_select_notes() {
+		let store = store();
+		assert_eq!(store.total_balance("3", "EDG").unwrap().inner, 35);
+		assert_eq!(store.total_balance("0x3", "WEBB").unwrap().inner, 10);
+		assert_eq!(store.total_balance("4", "EDG").unwrap().inner, 0);
+		assert_eq!(store.filter(Some("3"), None).length(), 3);
+		assert_eq!(store.filter(Some("0x3"), Some("EDG")).length(), 2);
+		assert_eq!(store.filter(None, Some("WEBB")).length(), 1);
+
+		let selected = store
+			.select_inputs_for_amount(&NoteAmount::new(30), "3", "EDG")
+			.unwrap();
+		assert_eq!(selected.length(), 2);
+		assert_eq!(selected.notes[0].amount.as_deref(), Some("25"));
+		assert!(selected.notes.iter().all(|note| note.protocol == NoteProtocol::VAnchor));
+
+		// The WEBB note doesn't cover an EDG spend
+		let error = store
+			.select_inputs_for_amount(&NoteAmount::new(36), "3", "EDG")
+			.err()
+			.unwrap();
+		assert_eq!(error.code, OpStatusCode::InsufficientBalance);
+		let error = store
+			.select_inputs_for_amount(&NoteAmount::new(1), "4", "EDG")
+			.err()
+			.unwrap();
+		assert_eq!(error.code, OpStatusCode::InsufficientBalance);
+	}
+
+	#[wasm_bindgen_test]
+	fn should_select_at_most_16_inputs() {
+		let mut store = JsNoteStore::default();
+		for _ in 0..17 {
+			store
+				.add_from_string(VANCHOR_NOTE.replace("amount=10", "amount=1").into())
+				.unwrap();
+		}
+		let selected = store
+			.select_inputs_for_amount(&NoteAmount::new(16), "3", "EDG")
+			.unwrap();
+		assert_eq!(selected.length(), 16);
+
+		let error = store
+			.select_inputs_for_amount(&NoteAmount::new(17), "3", "EDG")
+			.err()
+			.unwrap();
+		assert_eq!(error.code, OpStatusCode::InvalidInputUtxoConfig);
+		assert_eq!(error.data.as_deref(), Some("{\"inputCount\":17,\"maxInputCount\":16}"));
+	}
+
+	#[wasm_bindgen_test]
+	fn should_export_and_import_encrypted_store() {
+		let mut store = store();
+		store.notes[0].metadata.label = Some("savings".to_string());
+		let key = [7u8; 32];
+		let exported = store.export_encrypted(&key).unwrap();
+
+		assert_eq!(JsNoteStore::import_encrypted(&key, &exported).unwrap(), store);
+		assert_eq!(
+			JsNoteStore::import_encrypted(&[8u8; 32], &exported).err().unwrap().code,
+			OpStatusCode::DecryptionFailed
+		);
+		assert_eq!(
+			store.export_encrypted(&key[..16]).err().unwrap().code,
+			OpStatusCode::InvalidArrayLength
+		);
+	}
+}
@@ -0,0 +1,177 @@
+//! Summary of a note for confirmation dialogs. Texts are given as translation
+//! keys with their parameters, e.g. `{ key: 'note.amount', params: { amount:
+//! '1.5', token: 'WEBB' } }`, so the UI renders them in the user's language.
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::note::JsNote;
+use crate::types::{Chain, NoteProtocol, OpStatusCode, OperationError, TypedChainId};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+	pub key: String,
+	#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+	pub params: BTreeMap<String, String>,
+}
+
+impl Message {
+	fn new(key: &str, params: &[(&str, String)]) -> Self {
+		Self {
+			key: key.to_string(),
+			params: params.iter().map(|(k, v)| (k.to_string(), v.clone())).collect(),
+		}
+	}
+}
+
+/// What decides which deposits a note hides among: the tree it is in, and
+/// for the mixer the fixed amount of the pool
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AnonymitySet {
+	pub target_chain_id: String,
+	pub target_identifying_data: String,
+	pub token_symbol: Option<String>,
+	/// Only set for mixer notes, VAnchor pools take any amount
+	pub amount: Option<String>,
+	pub curve: Option<String>,
+	pub hash_function: Option<String>,
+	pub width: Option<usize>,
+	pub exponentiation: Option<i8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct NoteSummary {
+	/// `note.protocol.mixer` or `note.protocol.vanchor`
+	pub protocol: Message,
+	/// `note.amount` with the `amount` in tokens and the `token`
+	pub amount: Message,
+	/// `chain.<name>` for the known chains, `chain.unknown` with the
+	/// `chainType` and `chainId` otherwise
+	pub source_chain: Message,
+	pub target_chain: Message,
+	pub anonymity_set: AnonymitySet,
+}
+
+fn chain_message(chain_id: &str) -> Result<Message, OperationError> {
+	let typed_chain_id: TypedChainId = chain_id.parse()?;
+	Ok(match Chain::from_typed_chain_id(typed_chain_id) {
+		Some(chain) => Message::new(&format!("chain.{}", chain.key()), &[]),
+		None => {
+			let chain_type = typed_chain_id
+				.chain_type
+				.map(|chain_type| chain_type.to_string())
+				.unwrap_or_default();
+			Message::new("chain.unknown", &[
+				("chainType", chain_type),
+				("chainId", typed_chain_id.id.to_string()),
+			])
+		}
+	})
+}
+
+impl JsNote {
+	pub fn summary(&self) -> Result<NoteSummary, OperationError> {
+		let amount = self.amount_value()?;
+		let amount = match self.denomination {
+			Some(decimals) => amount.format(decimals)?,
+			None => amount.to_string(),
+		};
+		let token = self.token_symbol.clone().unwrap_or_default();
+		Ok(NoteSummary {
+			protocol: Message::new(&format!("note.protocol.{}", self.protocol), &[]),
+			amount: Message::new("note.amount", &[("amount", amount), ("token", token)]),
+			source_chain: chain_message(&self.source_chain_id)?,
+			target_chain: chain_message(&self.target_chain_id)?,
+			anonymity_set: AnonymitySet {
+				target_chain_id: self.target_chain_id.clone(),
+				target_identifying_data: self.target_identifying_data.clone(),
+				token_symbol: self.token_symbol.clone(),
+				amount: match self.protocol {
+					NoteProtocol::Mixer => self.amount.clone(),
+					NoteProtocol::VAnchor => None,
+				},
+				curve: self.curve.map(|curve| curve.to_string()),
+				hash_function: self.hash_function.map(|hash_function| hash_function.to_string()),
+				width: self.width,
+				exponentiation: self.exponentiation,
+			},
+		})
+	}
+}
+
+#[wasm_bindgen(typescript_custom_section)]
+const NOTE_SUMMARY: &str = r#"
+export interface Message {
+	key: string;
+	params?: Record<string, string>;
+}
+
+export interface AnonymitySet {
+	targetChainId: string;
+	targetIdentifyingData: string;
+	tokenSymbol?: string;
+	amount?: string;
+	curve?: Curve;
+	hashFunction?: HashFunction;
+	width?: number;
+	exponentiation?: number;
+}
+
+export interface NoteSummary {
+	protocol: Message;
+	amount: Message;
+	sourceChain: Message;
+	targetChain: Message;
+	anonymitySet: AnonymitySet;
+}
+"#;
+
+#[wasm_bindgen]
+extern "C" {
+	#[wasm_bindgen(typescript_type = "NoteSummary")]
+	pub type WasmNoteSummary;
+}
+
+#[allow(clippy::unused_unit)]
+#[wasm_bindgen]
+impl JsNote {
+	/// Summary of the note for display, see `NoteSummary`
+	pub fn describe(&self) -> Result<WasmNoteSummary, JsValue> {
+		let summary = JsValue::from_serde(&self.summary()?)
+			.map_err(|e| OperationError::new_with_message(OpStatusCode::SerializationFailed, e.to_string()))?;
+		Ok(summary.unchecked_into())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use wasm_bindgen_test::*;
+
+	use super::*;
+	use crate::types::ChainType;
+
+	#[wasm_bindgen_test]
+	fn should_summarize_notes_with_translation_keys() {
+		let rinkeby = TypedChainId::new(ChainType::Evm, 4).to_string();
+		let substrate = TypedChainId::new(ChainType::Substrate, 7).to_string();
+		let note = format!(
+			"webb://v1:mixer/{}:{}/0:3/fd717cfe463b3ffec71ee6b7606bbd0179170510abf41c9f16c1d20ca9923f0e:18b6b080e6a43262f00f6fb3da0d2409c4871b8f26d89d5c8836358e1af5a41c/?curve=Bn254&width=3&exp=5&hf=Poseidon&backend=Arkworks&token=WEBB&denom=18&amount=1500000000000000000",
+			rinkeby, substrate
+		);
+		let summary = JsNote::deserialize(&note).unwrap().summary().unwrap();
+
+		assert_eq!(summary.protocol.key, "note.protocol.mixer");
+		assert_eq!(summary.amount.params["amount"], "1.5");
+		assert_eq!(summary.amount.params["token"], "WEBB");
+		assert_eq!(summary.source_chain, Message::new("chain.rinkeby", &[]));
+		assert_eq!(summary.target_chain.key, "chain.unknown");
+		assert_eq!(summary.target_chain.params["chainType"], "Substrate");
+		assert_eq!(summary.target_chain.params["chainId"], "7");
+		assert_eq!(summary.anonymity_set.target_identifying_data, "3");
+		assert_eq!(summary.anonymity_set.amount.as_deref(), Some("1500000000000000000"));
+	}
+}
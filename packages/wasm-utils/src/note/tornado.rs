@@ -0,0 +1,351 @@
+//! Mixer secrets in the deposit JSON of Tornado-style tools, the format
+//! `tornado-cli` writes and snarkjs inputs read:
+//!
+//! ```text
+//! { "nullifier": "..", "secret": "..", "preimage": "0x..", "commitment": "..", "nullifierHash": ".." }
+//! ```
+//!
+//! No Tornado Cash deposit imports: Tornado Cash commits with a Pedersen hash
+//! Webb circuits don't implement, and its commitments fail with
+//! `IncompatibleDeposit`. Only the format is shared, the nullifier and secret
+//! map to the secrets of a Bn254 mixer note, the preimage being their 31
+//! bytes little endian, nullifier first, and the commitment has to be the
+//! Poseidon or `MiMC220` leaf of the secrets.
+use ark_bn254::Fr as Bn254Fr;
+use ark_ff::{BigInteger, PrimeField};
+use serde::{Deserialize, Serialize};
+
+use crate::field::{field_element_to_decimal, field_element_to_hex, parse_field_element};
+use crate::note::amount::NoteAmount;
+use crate::note::format::NoteFormatConfig;
+use crate::note::metadata::NoteMetadata;
+use crate::note::{mixer, JsNote};
+use crate::types::{
+	Backend, ChainType, Curve, HashFunction, NoteProtocol, NoteVersion, OpStatusCode, OperationError, TypedChainId,
+};
+
+/// Bytes of the nullifier and the secret in a Tornado preimage
+pub const TORNADO_SECRET_LENGTH: usize = 31;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TornadoDeposit {
+	/// Decimal or `0x` prefixed hex, read from the preimage when missing
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub nullifier: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub secret: Option<String>,
+	/// `0x` prefixed nullifier and secret, 31 bytes little endian each
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub preimage: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub commitment: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub commitment_hex: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub nullifier_hash: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub nullifier_hex: Option<String>,
+	/// `MiMC220` or `Poseidon`, found from the commitment when missing
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub hash_function: Option<String>,
+	/// Lower case token symbol, such as `eth`
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub currency: Option<String>,
+	/// Token amount, such as `0.1`
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub amount: Option<String>,
+	/// EVM chain id
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub net_id: Option<u32>,
+}
+
+fn incompatible(message: String) -> OperationError {
+	OperationError::new_with_message(OpStatusCode::IncompatibleDeposit, message)
+}
+
+/// Decimals of the Tornado Cash currencies, 18 for the others
+fn currency_decimals(currency: &str) -> u8 {
+	match currency.to_lowercase().as_str() {
+		"usdc" | "usdt" => 6,
+		"cdai" | "wbtc" => 8,
+		_ => 18,
+	}
+}
+
+/// Nullifier and secret of a preimage, as field elements
+fn split_preimage(preimage: &str) -> Result<(Bn254Fr, Bn254Fr), OperationError> {
+	let invalid = || {
+		let message = format!(
+			"The preimage should be {} bytes of 0x prefixed hex",
+			2 * TORNADO_SECRET_LENGTH
+		);
+		OperationError::new_with_message(OpStatusCode::InvalidNoteSecrets, message)
+	};
+	let bytes = hex::decode(preimage.strip_prefix("0x").ok_or_else(invalid)?).map_err(|_| invalid())?;
+	if bytes.len() != 2 * TORNADO_SECRET_LENGTH {
+		return Err(invalid());
+	}
+	let (nullifier, secret) = bytes.split_at(TORNADO_SECRET_LENGTH);
+	Ok((
+		Bn254Fr::from_le_bytes_mod_order(nullifier),
+		Bn254Fr::from_le_bytes_mod_order(secret),
+	))
+}
+
+/// The value as 31 bytes little endian, if it fits
+fn preimage_part(value: &Bn254Fr) -> Option<Vec<u8>> {
+	let bytes = value.into_repr().to_bytes_le();
+	if bytes[TORNADO_SECRET_LENGTH..].iter().any(|byte| *byte != 0) {
+		return None;
+	}
+	Some(bytes[..TORNADO_SECRET_LENGTH].to_vec())
+}
+
+fn parse_secret(value: &str, name: &str) -> Result<Bn254Fr, OperationError> {
+	parse_field_element(value).map_err(|e| {
+		let message = format!("Invalid {}: {}", name, e.error_message);
+		OperationError::new_with_message(OpStatusCode::InvalidNoteSecrets, message)
+	})
+}
+
+/// The value a deposit gives in decimal or in hex, checked to agree
+fn parse_hash(decimal: &Option<String>, hex: &Option<String>, name: &str) -> Result<Option<[u8; 32]>, OperationError> {
+	let mut value = None;
+	for given in decimal.iter().chain(hex.iter()) {
+		let parsed: [u8; 32] = parse_secret(given, name)?.into_repr().to_bytes_be().try_into().unwrap();
+		if value.map_or(false, |value| value != parsed) {
+			return Err(incompatible(format!("The decimal and hex {} differ", name)));
+		}
+		value = Some(parsed);
+	}
+	Ok(value)
+}
+
+fn mixer_leaf(hash_function: HashFunction, raw: &[u8]) -> Result<arkworks_setups::common::Leaf, OperationError> {
+	mixer::get_leaf_with_private_raw(Curve::Bn254, 3, 5, hash_function, raw)
+}
+
+impl JsNote {
+	/// Bn254 mixer note of a Tornado-style deposit
+	pub fn from_tornado_deposit(json: &str) -> Result<JsNote, OperationError> {
+		let deposit: TornadoDeposit = serde_json::from_str(json)
+			.map_err(|e| OperationError::new_with_message(OpStatusCode::DeserializationFailed, e.to_string()))?;
+
+		let from_preimage = deposit.preimage.as_deref().map(split_preimage).transpose()?;
+		let nullifier = match (&deposit.nullifier, from_preimage) {
+			(Some(nullifier), _) => parse_secret(nullifier, "nullifier")?,
+			(None, Some((nullifier, _))) => nullifier,
+			(None, None) => return Err(OpStatusCode::InvalidNoteSecrets.into()),
+		};
+		let secret = match (&deposit.secret, from_preimage) {
+			(Some(secret), _) => parse_secret(secret, "secret")?,
+			(None, Some((_, secret))) => secret,
+			(None, None) => return Err(OpStatusCode::InvalidNoteSecrets.into()),
+		};
+		if from_preimage.map_or(false, |preimage| preimage != (nullifier, secret)) {
+			return Err(incompatible(
+				"The preimage doesn't hold the nullifier and the secret".to_string(),
+			));
+		}
+		let secrets = vec![secret.into_repr().to_bytes_be(), nullifier.into_repr().to_bytes_be()];
+		let raw = secrets.concat();
+
+		// The leaf hash, as given or as the commitment shows
+		let commitment = parse_hash(&deposit.commitment, &deposit.commitment_hex, "commitment")?;
+		let hash_function = match (deposit.hash_function.as_deref(), commitment) {
+			(Some(hash_function), _) => hash_function.parse::<HashFunction>()?,
+			(None, Some(commitment)) => [HashFunction::Poseidon, HashFunction::MiMC220]
+				.iter()
+				.copied()
+				.find(|hash_function| {
+					mixer_leaf(*hash_function, &raw).map_or(false, |leaf| leaf.leaf_bytes == commitment)
+				})
+				.ok_or_else(|| {
+					incompatible(
+						"Tornado Cash deposits commit with Pedersen and can't be imported, the commitment is \
+						 neither the Poseidon nor the MiMC220 leaf of the secrets"
+							.to_string(),
+					)
+				})?,
+			(None, None) => {
+				return Err(incompatible(
+					"The deposit has neither a commitment nor a hash function to tell its leaf from".to_string(),
+				))
+			}
+		};
+		let leaf = mixer_leaf(hash_function, &raw)?;
+		if commitment.map_or(false, |commitment| leaf.leaf_bytes != commitment) {
+			let message = format!("The commitment isn't the {} leaf of the secrets", hash_function);
+			return Err(incompatible(message));
+		}
+		let nullifier_hash = parse_hash(&deposit.nullifier_hash, &deposit.nullifier_hex, "nullifier hash")?;
+		if nullifier_hash.map_or(false, |nullifier_hash| leaf.nullifier_hash_bytes != nullifier_hash) {
+			let message = format!("The nullifier hash isn't the {} hash of the nullifier", hash_function);
+			return Err(incompatible(message));
+		}
+
+		let chain_id = deposit.net_id.ok_or_else(|| {
+			OperationError::new_with_message(
+				OpStatusCode::InvalidTargetChain,
+				"The deposit has no netId to place the note on".to_string(),
+			)
+		})?;
+		let chain_id = TypedChainId::new(ChainType::Evm, chain_id).to_string();
+		let denomination = deposit.currency.as_deref().map(currency_decimals);
+		let amount = match (&deposit.amount, denomination) {
+			(Some(amount), Some(denomination)) => Some(NoteAmount::from_decimal(amount, denomination)?.to_string()),
+			(Some(_), None) => {
+				return Err(OperationError::new_with_message(
+					OpStatusCode::InvalidDenomination,
+					"The deposit has an amount but no currency".to_string(),
+				))
+			}
+			(None, _) => None,
+		};
+
+		let format = NoteFormatConfig::current();
+		Ok(JsNote {
+			scheme: format.scheme_prefix(),
+			namespace: format.namespace,
+			protocol: NoteProtocol::Mixer,
+			version: NoteVersion::V1,
+			source_chain_id: chain_id.clone(),
+			target_chain_id: chain_id,
+			source_identifying_data: String::new(),
+			target_identifying_data: String::new(),
+			secrets,
+			curve: Some(Curve::Bn254),
+			exponentiation: Some(5),
+			width: Some(3),
+			token_symbol: deposit.currency.map(|currency| currency.to_uppercase()),
+			amount,
+			denomination,
+			wrapped_token: None,
+			wrapping_fee: None,
+			backend: Some(Backend::Arkworks),
+			hash_function: Some(hash_function),
+			index: None,
+			metadata: NoteMetadata::default(),
+		})
+	}
+
+	/// Tornado-style deposit of a Bn254 mixer note. The preimage is left out
+	/// when the secrets don't fit in 31 bytes, as with secrets Webb generates,
+	/// Tornado circuits can't spend them
+	pub fn to_tornado_deposit(&self) -> Result<String, OperationError> {
+		if self.protocol != NoteProtocol::Mixer || self.curve.unwrap_or(Curve::Bn254) != Curve::Bn254 {
+			return Err(incompatible(
+				"Only Bn254 mixer notes have a Tornado-style deposit".to_string(),
+			));
+		}
+		let leaf = self.get_leaf_and_nullifier()?.mixer_leaf()?;
+		let secret = Bn254Fr::from_be_bytes_mod_order(&leaf.secret_bytes);
+		let nullifier = Bn254Fr::from_be_bytes_mod_order(&leaf.nullifier_bytes);
+		let commitment = Bn254Fr::from_be_bytes_mod_order(&leaf.leaf_bytes);
+		let nullifier_hash = Bn254Fr::from_be_bytes_mod_order(&leaf.nullifier_hash_bytes);
+		let preimage = preimage_part(&nullifier)
+			.zip(preimage_part(&secret))
+			.map(|(nullifier, secret)| format!("0x{}{}", hex::encode(nullifier), hex::encode(secret)));
+
+		let currency = self.token_symbol.as_ref().map(|symbol| symbol.to_lowercase());
+		let amount = match (&self.amount, &currency) {
+			(Some(_), Some(currency)) => {
+				let denomination = self.denomination.unwrap_or_else(|| currency_decimals(currency));
+				Some(self.amount_value()?.format(denomination)?)
+			}
+			_ => None,
+		};
+		let net_id = self
+			.target_chain_id
+			.parse::<TypedChainId>()
+			.ok()
+			.filter(|chain_id| {
+				chain_id
+					.chain_type
+					.map_or(true, |chain_type| chain_type == ChainType::Evm)
+			})
+			.map(|chain_id| chain_id.id);
+		let hash_function = self.hash_function.unwrap_or(HashFunction::Poseidon);
+
+		let deposit = TornadoDeposit {
+			nullifier: Some(field_element_to_decimal(&nullifier)),
+			secret: Some(field_element_to_decimal(&secret)),
+			preimage,
+			commitment: Some(field_element_to_decimal(&commitment)),
+			commitment_hex: Some(field_element_to_hex(&commitment)),
+			nullifier_hash: Some(field_element_to_decimal(&nullifier_hash)),
+			nullifier_hex: Some(field_element_to_hex(&nullifier_hash)),
+			hash_function: Some(hash_function.to_string()),
+			currency,
+			amount,
+			net_id,
+		};
+		serde_json::to_string(&deposit)
+			.map_err(|e| OperationError::new_with_message(OpStatusCode::SerializationFailed, e.to_string()))
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use wasm_bindgen_test::*;
+
+	use super::*;
+	use crate::hash::mimc_hash_raw;
+
+	fn le_bytes(value: u8) -> [u8; TORNADO_SECRET_LENGTH] {
+		let mut bytes = [0u8; TORNADO_SECRET_LENGTH];
+		bytes[0] = value;
+		bytes[30] = 1;
+		bytes
+	}
+
+	#[wasm_bindgen_test]
+	fn should_import_a_deposit_in_the_tornado_format() {
+		let preimage = format!("0x{}{}", hex::encode(le_bytes(7)), hex::encode(le_bytes(9)));
+		let (nullifier, secret) = split_preimage(&preimage).unwrap();
+		let be = |value: &Bn254Fr| -> [u8; 32] { value.into_repr().to_bytes_be().try_into().unwrap() };
+		let commitment = mimc_hash_raw(&[be(&secret), be(&nullifier)]).unwrap();
+
+		let json = format!(
+			r#"{{"preimage":"{}","commitment":"0x{}","currency":"eth","amount":"0.1","netId":5}}"#,
+			preimage,
+			hex::encode(commitment)
+		);
+		let note = JsNote::from_tornado_deposit(&json).unwrap();
+		assert_eq!(note.hash_function, Some(HashFunction::MiMC220));
+		assert_eq!(note.secrets, [be(&secret).to_vec(), be(&nullifier).to_vec()]);
+		assert_eq!(note.get_leaf_commitment().unwrap().to_vec(), commitment);
+		assert_eq!(note.display_amount().unwrap(), "0.1 ETH");
+		assert_eq!(note.target_chain_id, TypedChainId::new(ChainType::Evm, 5).to_string());
+
+		// The deposit exports back with the same preimage and commitment
+		let exported: TornadoDeposit = serde_json::from_str(&note.to_tornado_deposit().unwrap()).unwrap();
+		assert_eq!(exported.preimage, Some(preimage));
+		assert_eq!(exported.commitment_hex, Some(format!("0x{}", hex::encode(commitment))));
+		assert_eq!((exported.amount.as_deref(), exported.net_id), (Some("0.1"), Some(5)));
+		let exported = serde_json::to_string(&exported).unwrap();
+		assert_eq!(JsNote::from_tornado_deposit(&exported).unwrap(), note);
+
+		// A Tornado Cash deposit, committed with Pedersen, has no Webb leaf
+		let pedersen = json.replace(&hex::encode(commitment), &hex::encode([1u8; 31]));
+		let error = JsNote::from_tornado_deposit(&pedersen).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::IncompatibleDeposit);
+		let error = JsNote::from_tornado_deposit(r#"{"nullifier":"1","secret":"2","netId":5}"#).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::IncompatibleDeposit);
+	}
+
+	#[wasm_bindgen_test]
+	fn should_leave_out_the_preimage_of_wide_secrets() {
+		let json = r#"{"nullifier":"0x2000000000000000000000000000000000000000000000000000000000000001","secret":"2","hashFunction":"Poseidon","netId":1}"#;
+		let note = JsNote::from_tornado_deposit(json).unwrap();
+		let exported: TornadoDeposit = serde_json::from_str(&note.to_tornado_deposit().unwrap()).unwrap();
+		assert_eq!(exported.preimage, None);
+		assert_eq!(exported.hash_function.as_deref(), Some("Poseidon"));
+		assert_eq!(exported.secret.as_deref(), Some("2"));
+		assert_eq!(
+			JsNote::from_tornado_deposit(&serde_json::to_string(&exported).unwrap()).unwrap(),
+			note
+		);
+	}
+}
@@ -1,26 +1,52 @@
 use ark_bn254::Fr as Bn254Fr;
 use ark_ff::{BigInteger, PrimeField};
 use ark_std::UniformRand;
-use arkworks_setups::{Curve as ArkCurve, VAnchorProver};
-use rand::rngs::OsRng;
+use arkworks_native_gadgets::poseidon::Poseidon;
+use arkworks_setups::utxo::Utxo;
+use rand::{CryptoRng, RngCore};
 
+use crate::entropy::SecretRng;
+use crate::hash::{poseidon_params, PoseidonField};
 use crate::types::{Curve, OpStatusCode, OperationError};
 use crate::utxo::JsUtxo;
-use crate::VAnchorR1CSProverBn254_30_2_2_2;
 
-pub fn generate_secrets(
+/// Same UTXO as `VAnchorProver::create_leaf_with_privates`, with the cached
+/// Poseidon parameters instead of decoding them for every leaf
+fn create_utxo<F: PoseidonField>(
+	chain_id: u64,
+	amount: u128,
+	index: Option<u64>,
+	private_key: &[u8],
+	blinding: &[u8],
+) -> Result<Utxo<F>, OperationError> {
+	let hasher2 = Poseidon::new(poseidon_params::<F>(5, 2));
+	let hasher5 = Poseidon::new(poseidon_params::<F>(5, 5));
+	Utxo::new_with_privates(
+		chain_id,
+		F::from(amount),
+		index,
+		F::from_be_bytes_mod_order(private_key),
+		F::from_be_bytes_mod_order(blinding),
+		&hasher2,
+		&hasher5,
+	)
+	.map_err(|e| OperationError::new_with_message(OpStatusCode::FailedToGenerateTheLeaf, e.to_string()))
+}
+
+pub fn generate_secrets<R: RngCore + CryptoRng>(
 	amount: u128,
 	exponentiation: i8,
 	width: usize,
 	curve: Curve,
 	chain_id: u64,
 	index: Option<u64>,
-	rng: &mut OsRng,
+	rng: &mut R,
 ) -> Result<JsUtxo, OperationError> {
 	let utxo: JsUtxo = match (curve, exponentiation, width) {
 		(Curve::Bn254, 5, 5) => {
-			VAnchorR1CSProverBn254_30_2_2_2::create_random_leaf(ArkCurve::Bn254, chain_id, amount, index, rng)
-				.map(JsUtxo::new_from_bn254_utxo)
+			let private_key = Bn254Fr::rand(rng).into_repr().to_bytes_be();
+			let blinding = Bn254Fr::rand(rng).into_repr().to_bytes_be();
+			create_utxo::<Bn254Fr>(chain_id, amount, index, &private_key, &blinding).map(JsUtxo::new_from_bn254_utxo)
 		}
 		_ => {
 			let message = format!(
@@ -30,7 +56,7 @@ pub fn generate_secrets(
 			return Err(OperationError::new_with_message(OpStatusCode::SecretGenFailed, message));
 		}
 	}
-	.map_err(|e| OperationError::new_with_message(OpStatusCode::SecretGenFailed, e.to_string()))?;
+	.map_err(|e| OperationError::new_with_message(OpStatusCode::SecretGenFailed, e.error_message))?;
 
 	Ok(utxo)
 }
@@ -47,17 +73,17 @@ pub fn get_leaf_with_private_raw(
 ) -> Result<JsUtxo, OperationError> {
 	let utxo: JsUtxo = match (curve, exponentiation, width) {
 		(Curve::Bn254, 5, 5) => {
-			let private_key = private_key.unwrap_or_else(|| Bn254Fr::rand(&mut OsRng).into_repr().to_bytes_be());
-			let blinding = blinding.unwrap_or_else(|| Bn254Fr::rand(&mut OsRng).into_repr().to_bytes_be());
-			VAnchorR1CSProverBn254_30_2_2_2::create_leaf_with_privates(
-				ArkCurve::Bn254,
-				chain_id,
-				amount,
-				index,
-				private_key.to_vec(),
-				blinding.to_vec(),
-			)
-			.map(JsUtxo::new_from_bn254_utxo)
+			// Only ask for entropy when a secret is missing, leaves of known
+			// secrets are computed in bulk
+			let (private_key, blinding) = match (private_key, blinding) {
+				(Some(private_key), Some(blinding)) => (private_key, blinding),
+				(private_key, blinding) => {
+					let mut rng = SecretRng::new()?;
+					let mut random = || Bn254Fr::rand(&mut rng).into_repr().to_bytes_be();
+					(private_key.unwrap_or_else(&mut random), blinding.unwrap_or_else(random))
+				}
+			};
+			create_utxo::<Bn254Fr>(chain_id, amount, index, &private_key, &blinding).map(JsUtxo::new_from_bn254_utxo)
 		}
 		_ => {
 			let message = format!(
@@ -69,8 +95,7 @@ pub fn get_leaf_with_private_raw(
 				message,
 			));
 		}
-	}
-	.map_err(|e| OperationError::new_with_message(OpStatusCode::FailedToGenerateTheLeaf, e.to_string()))?;
+	}?;
 
 	Ok(utxo)
 }
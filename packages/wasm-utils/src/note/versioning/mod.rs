@@ -1 +1,152 @@
+use blake2::digest::{Update, VariableOutput};
+use blake2::VarBlake2b;
+
+use crate::types::{Curve, HashFunction, NoteProtocol, NoteVersion, OpStatusCode, OperationError};
+
 pub mod v1;
+
+/// Circuit parameters of a note whose misc data leaves them out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoteDefaults {
+	pub curve: Curve,
+	pub width: usize,
+	pub exponentiation: i8,
+	pub hash_function: HashFunction,
+}
+
+impl NoteDefaults {
+	/// The defaults of each note version and protocol. A version moving a
+	/// protocol to other circuits gets its own row, reading its notes with
+	/// another version's parameters would give the wrong leaves
+	pub fn of(version: NoteVersion, protocol: NoteProtocol) -> Self {
+		match (version, protocol) {
+			(NoteVersion::V1, NoteProtocol::Mixer) => NoteDefaults {
+				curve: Curve::Bn254,
+				width: 3,
+				exponentiation: 5,
+				hash_function: HashFunction::Poseidon,
+			},
+			(NoteVersion::V1, NoteProtocol::VAnchor) => NoteDefaults {
+				curve: Curve::Bn254,
+				width: 5,
+				exponentiation: 5,
+				hash_function: HashFunction::Poseidon,
+			},
+		}
+	}
+}
+
+/// Bytes of the `check` misc value
+pub const CHECKSUM_LENGTH: usize = 4;
+
+/// The `check` misc value of `note`, a note written without it: the hex of
+/// its Blake2b hash of `CHECKSUM_LENGTH` bytes
+pub fn note_checksum(note: &str) -> String {
+	let mut hasher = VarBlake2b::new(CHECKSUM_LENGTH).expect("the checksum length is a valid Blake2b output size");
+	hasher.update(note.as_bytes());
+	let mut checksum = String::new();
+	hasher.finalize_variable(|hash| checksum = hex::encode(hash));
+	checksum
+}
+
+const HEX: &[u8; 16] = b"0123456789ABCDEF";
+
+/// RFC 3986 percent-encoding of the UTF-8 bytes of `value`, everything but
+/// the unreserved characters is escaped so no URI delimiter is left
+pub fn percent_encode(value: &str) -> String {
+	let mut encoded = String::with_capacity(value.len());
+	for byte in value.bytes() {
+		if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+			encoded.push(byte as char);
+		} else {
+			encoded.push('%');
+			encoded.push(HEX[usize::from(byte >> 4)] as char);
+			encoded.push(HEX[usize::from(byte & 0xf)] as char);
+		}
+	}
+	encoded
+}
+
+/// Decode the `%XX` escapes of `value`, other characters are kept as they are
+pub fn percent_decode(value: &str) -> Result<String, OperationError> {
+	let invalid = || {
+		let message = format!("Invalid percent-encoding: {:?}", value);
+		OperationError::new_with_message(OpStatusCode::InvalidNoteMiscData, message)
+	};
+	let bytes = value.as_bytes();
+	let mut decoded = Vec::with_capacity(bytes.len());
+	let mut i = 0;
+	while i < bytes.len() {
+		if bytes[i] == b'%' {
+			let hex = bytes.get(i + 1..i + 3).ok_or_else(invalid)?;
+			let hex = core::str::from_utf8(hex).map_err(|_| invalid())?;
+			decoded.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())?);
+			i += 3;
+		} else {
+			decoded.push(bytes[i]);
+			i += 1;
+		}
+	}
+	String::from_utf8(decoded).map_err(|_| invalid())
+}
+
+#[cfg(test)]
+mod test {
+	use wasm_bindgen_test::*;
+
+	use super::*;
+	use crate::note::JsNote;
+	use crate::proof::test_utils::{MIXER_NOTE_V1_X5_5, VANCHOR_NOTE_V1_X5_4};
+
+	#[wasm_bindgen_test]
+	fn should_percent_encode_delimiters_and_unicode() {
+		assert_eq!(percent_encode("WEBB"), "WEBB");
+		assert_eq!(percent_encode("a&b:c/d?e=f%"), "a%26b%3Ac%2Fd%3Fe%3Df%25");
+		assert_eq!(percent_encode("ΞTH"), "%CE%9ETH");
+		for value in ["", "ΞTH", "🦀/🕸", "a b+c", "100%"] {
+			assert_eq!(percent_decode(&percent_encode(value)).unwrap(), value);
+		}
+		// Lowercase escapes and unescaped characters are read as well
+		assert_eq!(percent_decode("%ce%9eTH").unwrap(), "ΞTH");
+
+		for invalid in ["%", "%4", "%zz", "%FF", "%C3%"] {
+			let error = percent_decode(invalid).unwrap_err();
+			assert_eq!(error.code, OpStatusCode::InvalidNoteMiscData);
+		}
+	}
+
+	#[wasm_bindgen_test]
+	fn should_detect_corrupted_notes_with_a_checksum() {
+		let note = JsNote::deserialize(MIXER_NOTE_V1_X5_5).unwrap();
+		let checked = note.to_string_with_checksum();
+		assert_eq!(checked, format!("{}&check={}", note, note_checksum(&note.to_string())));
+		assert_eq!(JsNote::deserialize(&checked).unwrap(), note);
+
+		// A swapped secret character, and a truncated amount
+		let swapped = checked.replacen("fd717c", "fd71c7", 1);
+		let truncated = checked.replacen("amount=10", "amount=1", 1);
+		for corrupted in [swapped, truncated] {
+			let error = JsNote::deserialize(&corrupted).unwrap_err();
+			assert_eq!(error.code, OpStatusCode::NoteChecksumMismatch);
+		}
+		// Notes without a checksum parse as before
+		assert_eq!(JsNote::deserialize(&note.to_string()).unwrap(), note);
+	}
+
+	#[wasm_bindgen_test]
+	fn should_resolve_defaults_by_version_and_protocol() {
+		let mixer = NoteDefaults::of(NoteVersion::V1, NoteProtocol::Mixer);
+		assert_eq!((mixer.width, mixer.exponentiation), (3, 5));
+		let vanchor = NoteDefaults::of(NoteVersion::V1, NoteProtocol::VAnchor);
+		assert_eq!((vanchor.width, vanchor.exponentiation), (5, 5));
+
+		// The fixtures use the default circuits of their protocol
+		for note in [MIXER_NOTE_V1_X5_5, VANCHOR_NOTE_V1_X5_4] {
+			let note = JsNote::deserialize(note).unwrap();
+			let defaults = note.defaults();
+			assert_eq!(note.width, Some(defaults.width));
+			assert_eq!(note.exponentiation, Some(defaults.exponentiation));
+			assert_eq!(note.curve, Some(defaults.curve));
+		}
+	}
+}
@@ -1,31 +1,68 @@
+use wasm_bindgen::__rt::std::collections::btree_map::BTreeMap;
+
+use crate::address::{parse_evm_address, to_checksum_address};
+use crate::note::amount::check_wrapping_fee;
+use crate::note::format::NoteFormatConfig;
+use crate::note::metadata::NoteMetadata;
+use crate::note::versioning::{note_checksum, percent_decode};
 use crate::note::*;
 use crate::types::{OpStatusCode, OperationError};
 
+/// Keys of the misc section, in the order notes are written with
+const MISC_KEYS: &[&str] = &[
+	"curve",
+	"width",
+	"exp",
+	"hf",
+	"backend",
+	"token",
+	"denom",
+	"amount",
+	"index",
+	"wrappedToken",
+	"wrappingFee",
+	"check",
+];
+
+fn structure_error(message: String) -> OperationError {
+	OperationError::new_with_message(OpStatusCode::InvalidNoteLength, message)
+}
+
+fn parse_value<T: FromStr>(value: Option<&str>, code: OpStatusCode) -> Result<Option<T>, OperationError> {
+	value
+		.map(|value| {
+			value
+				.parse::<T>()
+				.map_err(|_| OperationError::new_with_message(code.clone(), format!("Invalid note value: {:?}", value)))
+		})
+		.transpose()
+}
+
 pub fn note_from_str(s: &str) -> Result<JsNote, OperationError> {
-	let scheme_and_parts: Vec<&str> = s.split("://").collect();
-	let scheme = scheme_and_parts[0];
-
-	let parts: Vec<&str> = scheme_and_parts[1].split('/').collect();
-	if parts.len() < 5 {
-		return Err(OperationError::new_with_message(
-			OpStatusCode::InvalidNoteLength,
-			format!("Note length has incorrect parts length: {}", parts.len()),
-		));
+	let format = NoteFormatConfig::current();
+	let rest = format.strip(s)?;
+
+	let parts: Vec<&str> = rest.split('/').collect();
+	if parts.len() != 5 {
+		return Err(structure_error(format!(
+			"Note length has incorrect parts length: {}",
+			parts.len()
+		)));
 	}
 	// Raw parts
 	let authority = parts[0];
 	let chain_ids = parts[1];
 	let chain_identifying_data = parts[2];
 	let secrets = parts[3];
-	let misc = parts[4].replace('?', "");
+	let misc = parts[4].strip_prefix('?').unwrap_or(parts[4]);
 
 	// Authority parsing
 	let authority_parts: Vec<&str> = authority.split(':').collect();
 	if authority_parts.len() != 2 {
-		return Err(OperationError::new_with_message(
-			OpStatusCode::InvalidNoteLength,
-			format!("Invalid authority parts length: {}", authority_parts.len()),
-		));
+		return Err(structure_error(format!(
+			"Invalid authority parts length: {}",
+			authority_parts.len()
+		)));
 	}
 
 	let version = NoteVersion::from_str(authority_parts[0])?;
@@ -34,43 +71,36 @@ pub fn note_from_str(s: &str) -> Result<JsNote, OperationError> {
 	// Chain IDs parsing
 	let chain_ids_parts: Vec<&str> = chain_ids.split(':').collect();
 	if chain_ids_parts.len() != 2 {
-		return Err(OperationError::new_with_message(
-			OpStatusCode::InvalidNoteLength,
-			format!("Invalid chain IDs parts length: {}", chain_ids_parts.len()),
-		));
+		return Err(structure_error(format!(
+			"Invalid chain IDs parts length: {}",
+			chain_ids_parts.len()
+		)));
 	}
+	// Notes hold chain ids in decimal, typed or, for older notes, any u64
+	let check_chain_id = |chain_id: &str, code: OpStatusCode| {
+		chain_id
+			.parse::<u64>()
+			.map_err(|_| OperationError::new_with_message(code, format!("Invalid chain id {:?}", chain_id)))
+	};
 	let source_chain_id = chain_ids_parts[0];
-	let _: u64 = source_chain_id.parse().map_err(|_| OpStatusCode::InvalidSourceChain)?;
+	check_chain_id(source_chain_id, OpStatusCode::InvalidSourceChain)?;
 	let target_chain_id = chain_ids_parts[1];
-	let _: u64 = target_chain_id.parse().map_err(|_| OpStatusCode::InvalidTargetChain)?;
+	check_chain_id(target_chain_id, OpStatusCode::InvalidTargetChain)?;
 
 	// Chain Identifying Data parsing
 	let chain_identifying_data_parts: Vec<&str> = chain_identifying_data.split(':').collect();
 	if chain_identifying_data_parts.len() != 2 {
-		return Err(OperationError::new_with_message(
-			OpStatusCode::InvalidNoteLength,
-			format!(
-				"Invalid chain identifying data parts length: {}",
-				chain_identifying_data_parts.len()
-			),
-		));
+		return Err(structure_error(format!(
+			"Invalid chain identifying data parts length: {}",
+			chain_identifying_data_parts.len()
+		)));
 	}
 	let source_identifying_data = chain_identifying_data_parts[0];
 	let target_identifying_data = chain_identifying_data_parts[1];
 
-	// Misc data parsing
-	let misc_parts: Vec<&str> = misc.split('&').collect();
-	let mut curve = None;
-	let mut width = None;
-	let mut exponentiation = None;
-	let mut hash_function = None;
-	let mut backend = None;
-	let mut token_symbol = None;
-	let mut denomination = None;
-	let mut amount = None;
-	let mut index = None;
-
-	for part in misc_parts {
+	// Misc data parsing, a note without any misc value ends with a bare `?`
+	let mut misc_values = BTreeMap::new();
+	for part in misc.split('&').filter(|part| !part.is_empty()) {
 		let part_parts: Vec<&str> = part.split('=').collect();
 		if part_parts.len() != 2 {
 			return Err(OperationError::new_with_message(
@@ -80,48 +110,250 @@ pub fn note_from_str(s: &str) -> Result<JsNote, OperationError> {
 		}
 		let key = part_parts[0];
 		let value = part_parts[1];
-		println!("{}={}", key, value);
-		match key {
-			"curve" => curve = Some(value),
-			"width" => width = Some(value),
-			"exp" => exponentiation = Some(value),
-			"hf" => hash_function = Some(value),
-			"backend" => backend = Some(value),
-			"token" => token_symbol = Some(value),
-			"denom" => denomination = Some(value),
-			"amount" => amount = Some(value),
-			"index" => index = Some(value),
-			_ => {
-				return Err(OperationError::new_with_message(
-					OpStatusCode::InvalidNoteMiscData,
-					format!("Unknown miscellaneous key: {}", key),
-				))
-			}
+		tracing::trace!(key, value, "note misc data");
+		if !MISC_KEYS.contains(&key) {
+			return Err(OperationError::new_with_message(
+				OpStatusCode::InvalidNoteMiscData,
+				format!("Unknown miscellaneous key: {}", key),
+			));
+		}
+		if misc_values.insert(key, value).is_some() {
+			return Err(OperationError::new_with_message(
+				OpStatusCode::InvalidNoteMiscData,
+				format!("Duplicate miscellaneous key: {}", key),
+			));
 		}
 	}
+	let misc_value = |key: &str| misc_values.get(key).copied();
 
 	let secret_parts: Vec<Vec<u8>> = secrets
 		.split(':')
-		.map(|v| hex::decode(v).unwrap_or_default())
-		.collect::<Vec<Vec<u8>>>();
+		.map(hex::decode)
+		.collect::<Result<_, _>>()
+		.map_err(|e| OperationError::new_with_message(OpStatusCode::InvalidNoteSecrets, e.to_string()))?;
 
-	Ok(JsNote {
-		scheme: scheme.to_string(),
+	let note = JsNote {
+		scheme: format.scheme_prefix(),
+		namespace: format.namespace,
 		protocol,
 		version,
 		target_chain_id: target_chain_id.to_string(),
 		source_chain_id: source_chain_id.to_string(),
-		source_identifying_data: source_identifying_data.to_string(),
-		target_identifying_data: target_identifying_data.to_string(),
-		token_symbol: token_symbol.map(|v| v.to_string()),
-		curve: curve.map(|v| v.parse::<Curve>().unwrap()),
-		hash_function: hash_function.map(|v| HashFunction::from_str(v).unwrap()),
-		backend: backend.map(|b| b.parse().unwrap()),
-		denomination: denomination.map(|v| v.parse::<u8>().unwrap()),
-		amount: amount.map(|v| v.parse::<String>().unwrap()),
-		exponentiation: exponentiation.map(|v| v.parse::<i8>().unwrap()),
-		width: width.map(|v| v.parse::<usize>().unwrap()),
+		source_identifying_data: percent_decode(source_identifying_data)?,
+		target_identifying_data: percent_decode(target_identifying_data)?,
+		token_symbol: misc_value("token").map(percent_decode).transpose()?,
+		curve: parse_value(misc_value("curve"), OpStatusCode::InvalidCurve)?,
+		hash_function: parse_value(misc_value("hf"), OpStatusCode::InvalidHasFunction)?,
+		backend: parse_value(misc_value("backend"), OpStatusCode::InvalidBackend)?,
+		denomination: parse_value(misc_value("denom"), OpStatusCode::InvalidDenomination)?,
+		amount: misc_value("amount").map(percent_decode).transpose()?,
+		exponentiation: parse_value(misc_value("exp"), OpStatusCode::InvalidExponentiation)?,
+		width: parse_value(misc_value("width"), OpStatusCode::InvalidWidth)?,
 		secrets: secret_parts,
-		index: index.map(|v| v.parse().unwrap()),
-	})
+		index: parse_value(misc_value("index"), OpStatusCode::InvalidUTXOIndex)?,
+		wrapped_token: misc_value("wrappedToken")
+			.map(|address| parse_evm_address(address).map(|address| to_checksum_address(&address)))
+			.transpose()?,
+		wrapping_fee: parse_value(misc_value("wrappingFee"), OpStatusCode::InvalidFee)?
+			.map(check_wrapping_fee)
+			.transpose()?,
+		metadata: NoteMetadata::default(),
+	};
+	// The checksum is of the note as it's written, which the note parsed
+	// without its checksum is
+	if let Some(check) = misc_value("check") {
+		let checksum = note_checksum(&note.to_string());
+		if !check.eq_ignore_ascii_case(&checksum) {
+			let message = format!("The note checksum is {} but the note gives {}", check, checksum);
+			return Err(OperationError::new_with_message(
+				OpStatusCode::NoteChecksumMismatch,
+				message,
+			));
+		}
+	}
+	Ok(note)
+}
+
+#[cfg(test)]
+mod test {
+	use rand::{Rng, SeedableRng};
+	use rand_chacha::ChaCha20Rng;
+	use wasm_bindgen_test::*;
+
+	use super::*;
+	use crate::types::ChainType;
+
+	const CASES: usize = 256;
+	const SAFE_CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789_.-";
+	const DELIMITERS: &[u8] = b"/:?&=#%";
+
+	/// Any text, delimiters and multi-byte characters included
+	fn free_form(rng: &mut ChaCha20Rng, max_len: usize) -> String {
+		const UNICODE: &[char] = &['€', 'Ξ', 'ß', '₿', '🦀', ' ', '+'];
+		let len = rng.gen_range(0..=max_len);
+		(0..len)
+			.map(|_| match rng.gen_range(0..3) {
+				0 => DELIMITERS[rng.gen_range(0..DELIMITERS.len())] as char,
+				1 => UNICODE[rng.gen_range(0..UNICODE.len())],
+				_ => SAFE_CHARS[rng.gen_range(0..SAFE_CHARS.len())] as char,
+			})
+			.collect()
+	}
+
+	fn maybe<T>(rng: &mut ChaCha20Rng, value: impl FnOnce(&mut ChaCha20Rng) -> T) -> Option<T> {
+		if rng.gen_bool(0.5) {
+			Some(value(rng))
+		} else {
+			None
+		}
+	}
+
+	/// Typed chain id of any chain type, or untyped
+	fn arbitrary_chain_id(rng: &mut ChaCha20Rng) -> String {
+		const CHAIN_TYPES: &[ChainType] = &[ChainType::Evm, ChainType::Substrate, ChainType::Cosmos];
+		let id: u32 = rng.gen();
+		match rng.gen_range(0..=CHAIN_TYPES.len()) {
+			0 => u64::from(id).to_string(),
+			i => TypedChainId::new(CHAIN_TYPES[i - 1], id).to_string(),
+		}
+	}
+
+	/// Any note the codec should carry
+	fn arbitrary_note(rng: &mut ChaCha20Rng) -> JsNote {
+		let secrets = (0..rng.gen_range(1..=4))
+			.map(|_| {
+				let len = rng.gen_range(0..=32);
+				(0..len).map(|_| rng.gen()).collect()
+			})
+			.collect();
+		JsNote {
+			scheme: "webb://".to_string(),
+			namespace: None,
+			protocol: if rng.gen() {
+				NoteProtocol::Mixer
+			} else {
+				NoteProtocol::VAnchor
+			},
+			version: NoteVersion::V1,
+			source_chain_id: arbitrary_chain_id(rng),
+			target_chain_id: arbitrary_chain_id(rng),
+			source_identifying_data: free_form(rng, 42),
+			target_identifying_data: free_form(rng, 42),
+			secrets,
+			curve: maybe(rng, |rng| if rng.gen() { Curve::Bn254 } else { Curve::Bls381 }),
+			exponentiation: maybe(rng, |rng| rng.gen()),
+			width: maybe(rng, |rng| rng.gen::<u32>() as usize),
+			token_symbol: maybe(rng, |rng| free_form(rng, 8)),
+			amount: maybe(rng, |rng| rng.gen::<u128>().to_string()),
+			denomination: maybe(rng, |rng| rng.gen()),
+			backend: maybe(rng, |rng| if rng.gen() { Backend::Arkworks } else { Backend::Circom }),
+			hash_function: maybe(rng, |rng| {
+				if rng.gen() {
+					HashFunction::Poseidon
+				} else {
+					HashFunction::MiMC220
+				}
+			}),
+			index: maybe(rng, |rng| rng.gen()),
+			wrapped_token: maybe(rng, |rng| to_checksum_address(&rng.gen())),
+			wrapping_fee: maybe(rng, |rng| rng.gen_range(0..100)),
+			metadata: NoteMetadata::default(),
+		}
+	}
+
+	/// Delete, replace or insert characters, delimiters included
+	fn corrupt(rng: &mut ChaCha20Rng, note: &str) -> String {
+		let mut chars: Vec<char> = note.chars().collect();
+		for _ in 0..rng.gen_range(1..=3) {
+			let at = rng.gen_range(0..=chars.len());
+			let c = if rng.gen() {
+				DELIMITERS[rng.gen_range(0..DELIMITERS.len())] as char
+			} else {
+				SAFE_CHARS[rng.gen_range(0..SAFE_CHARS.len())] as char
+			};
+			match rng.gen_range(0..3) {
+				0 if at < chars.len() => {
+					chars.remove(at);
+				}
+				1 if at < chars.len() => chars[at] = c,
+				_ => chars.insert(at, c),
+			}
+		}
+		chars.into_iter().collect()
+	}
+
+	#[wasm_bindgen_test]
+	fn should_round_trip_generated_notes() {
+		let mut rng = ChaCha20Rng::seed_from_u64(577);
+		for _ in 0..CASES {
+			let note = arbitrary_note(&mut rng);
+			let serialized = note.to_string();
+			assert_eq!(note_from_str(&serialized).as_ref(), Ok(&note), "{}", serialized);
+		}
+	}
+
+	#[wasm_bindgen_test]
+	fn should_parse_corrupted_notes_without_panicking() {
+		let mut rng = ChaCha20Rng::seed_from_u64(578);
+		for _ in 0..CASES {
+			let note = arbitrary_note(&mut rng).to_string();
+			let corrupted = corrupt(&mut rng, &note);
+			// Whatever parses has to serialize back to a note that parses the same
+			if let Ok(note) = note_from_str(&corrupted) {
+				assert_eq!(note_from_str(&note.to_string()), Ok(note), "{}", corrupted);
+			}
+		}
+	}
+
+	#[wasm_bindgen_test]
+	fn should_escape_unicode_token_symbols() {
+		let mut note = note_from_str("webb://v1:mixer/2:2/2:2/fd71:18b6/?token=EDG&amount=10").unwrap();
+		note.token_symbol = Some("ΞTH/€:W&B".to_string());
+		note.target_identifying_data = "0x38e7…:tree".to_string();
+
+		let serialized = note.to_string();
+		assert_eq!(
+			serialized,
+			"webb://v1:mixer/2:2/2:0x38e7%E2%80%A6%3Atree/fd71:18b6/?token=%CE%9ETH%2F%E2%82%AC%3AW%26B&amount=10"
+		);
+		assert_eq!(note_from_str(&serialized), Ok(note));
+
+		// Unescaped multi-byte characters are read as they are
+		let note = note_from_str("webb://v1:mixer/2:2/2:2/fd71:18b6/?token=ΞTH").unwrap();
+		assert_eq!(note.token_symbol.as_deref(), Some("ΞTH"));
+		assert_eq!(
+			note_from_str("webb://v1:mixer/2:2/2:2/fd71:18b6/?token=%E2%82")
+				.unwrap_err()
+				.code,
+			OpStatusCode::InvalidNoteMiscData
+		);
+	}
+
+	#[wasm_bindgen_test]
+	fn should_reject_malformed_notes() {
+		let note = "webb://v1:mixer/2:2/2:2/fd71:18b6/?curve=Bn254&amount=10";
+		assert!(note_from_str(note).is_ok());
+
+		let code = |note: &str| note_from_str(note).unwrap_err().code;
+		assert_eq!(code("v1:mixer/2:2/2:2/fd71/?"), OpStatusCode::InvalidNoteLength);
+		assert_eq!(code("ipfs://v1:mixer/2:2/2:2/fd71/?"), OpStatusCode::InvalidNoteLength);
+		assert_eq!(
+			code("webb://v1:mixer/2:2/2/2:2/fd71/?"),
+			OpStatusCode::InvalidNoteLength
+		);
+		assert_eq!(code("webb://v1:mixer/2:2/2:2/fd7z/?"), OpStatusCode::InvalidNoteSecrets);
+		assert_eq!(
+			code("webb://v1:mixer/2:2/2:2/fd71/?curve=Ed25519"),
+			OpStatusCode::InvalidCurve
+		);
+		assert_eq!(
+			code("webb://v1:mixer/2:2/2:2/fd71/?exp=300"),
+			OpStatusCode::InvalidExponentiation
+		);
+		assert_eq!(
+			code("webb://v1:mixer/2:2/2:2/fd71/?amount=1&amount=2"),
+			OpStatusCode::InvalidNoteMiscData
+		);
+		assert_eq!(note_from_str("webb://v1:mixer/2:2/2:2/fd71/?").unwrap().curve, None);
+	}
 }
@@ -0,0 +1,386 @@
+//! Spent nullifiers of a pool, to tell which notes are spent without asking
+//! the chain for every note. The set holds either the full list of spent
+//! nullifiers, or a bloom filter snapshot of it which is much smaller but
+//! answers "maybe spent" for a few unspent notes.
+//!
+//! A bloom filter of `m` bits has bit `i` at `bits[i / 8] >> (i % 8) & 1`.
+//! A nullifier sets the `k` bits `(h1 + j * h2) mod m` for `j < k`, where
+//! `h1` and `h2` are the big endian `u64`s of its last 8 bytes and of the 8
+//! bytes before. Nullifiers are hashes, their bytes are already uniform.
+use std::collections::HashSet;
+
+use js_sys::Uint8Array;
+use wasm_bindgen::prelude::*;
+
+use crate::merkle::leaves_from_js;
+use crate::note::{JsLeafInner, JsNote};
+use crate::types::{
+	IdentifyingData, JsResourceId, Leaves, NoteProtocol, OpStatusCode, OperationError, ResourceId, TargetSystem,
+	TypedChainId,
+};
+
+/// More hashes only make the filter slower, the best count for a filter
+/// sized for a 1e-9 false positive rate is 30
+pub const MAX_BLOOM_HASHES: u32 = 32;
+
+fn invalid_nullifier(message: String) -> OperationError {
+	OperationError::new_with_message(OpStatusCode::InvalidNullifer, message)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BloomFilter {
+	pub bits: Vec<u8>,
+	pub hash_count: u32,
+}
+
+impl BloomFilter {
+	pub fn new(bits: Vec<u8>, hash_count: u32) -> Result<Self, OperationError> {
+		if bits.is_empty() {
+			return Err(invalid_nullifier("The bloom filter is empty".to_string()));
+		}
+		if hash_count == 0 || hash_count > MAX_BLOOM_HASHES {
+			let message = format!(
+				"A bloom filter uses 1 to {} hashes, not {}",
+				MAX_BLOOM_HASHES, hash_count
+			);
+			return Err(invalid_nullifier(message));
+		}
+		Ok(Self { bits, hash_count })
+	}
+
+	fn positions(&self, nullifier: &[u8; 32]) -> impl Iterator<Item = usize> {
+		let bit_count = self.bits.len() as u64 * 8;
+		let h1 = u64::from_be_bytes(nullifier[24..].try_into().unwrap());
+		let h2 = u64::from_be_bytes(nullifier[16..24].try_into().unwrap());
+		(0..u64::from(self.hash_count)).map(move |j| (h1.wrapping_add(j.wrapping_mul(h2)) % bit_count) as usize)
+	}
+
+	pub fn insert(&mut self, nullifier: &[u8; 32]) {
+		for position in self.positions(nullifier) {
+			self.bits[position / 8] |= 1 << (position % 8);
+		}
+	}
+
+	pub fn contains(&self, nullifier: &[u8; 32]) -> bool {
+		self.positions(nullifier)
+			.all(|position| self.bits[position / 8] >> (position % 8) & 1 == 1)
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpentNullifiers {
+	Exact(HashSet<[u8; 32]>),
+	Bloom(BloomFilter),
+}
+
+/// Nullifier the pool records when `note` is spent: the nullifier hash of a
+/// mixer note, the nullifier of a VAnchor UTXO, which needs its leaf index
+pub fn note_nullifier(note: &JsNote) -> Result<[u8; 32], OperationError> {
+	let nullifier = match note.get_leaf_and_nullifier()?.inner {
+		JsLeafInner::Mixer(leaf) => leaf.nullifier_hash_bytes,
+		JsLeafInner::VAnchor(utxo) => utxo.get_nullifier()?,
+	};
+	nullifier
+		.try_into()
+		.map_err(|_| OperationError::from(OpStatusCode::InvalidNullifer))
+}
+
+/// Nullifier the pool at `resource_id` records when `note` is spent there,
+/// with the leaf index of a VAnchor note if the note doesn't hold it. The
+/// resource id isn't hashed in, the chain id is through the VAnchor
+/// commitment, but the note is checked to be spendable at that pool: the
+/// nullifier of a note in another pool would never show up
+pub fn contextual_nullifier(
+	note: &JsNote,
+	resource_id: &ResourceId,
+	leaf_index: Option<u64>,
+) -> Result<[u8; 32], OperationError> {
+	let target_chain_id: TypedChainId = note
+		.target_chain_id
+		.parse()
+		.map_err(|_| OperationError::from(OpStatusCode::InvalidTargetChain))?;
+	if target_chain_id != resource_id.typed_chain_id {
+		let message = format!(
+			"The note is spent on chain {}, not {}",
+			target_chain_id, resource_id.typed_chain_id
+		);
+		return Err(OperationError::new_with_message(
+			OpStatusCode::InvalidTargetChain,
+			message,
+		));
+	}
+	let (_, target) = note.identifying_data()?;
+	let same_pool = match (target, resource_id.target_system) {
+		(IdentifyingData::Resource(bytes), _) => bytes == resource_id.to_bytes(),
+		(IdentifyingData::ContractAddress(address), TargetSystem::ContractAddress(other)) => address == other,
+		(IdentifyingData::TreeId(tree_id), TargetSystem::TreeId(other)) => tree_id == other,
+		_ => false,
+	};
+	if !same_pool {
+		let message = format!("The note is for pool {}, not {}", target, resource_id);
+		return Err(OperationError::new_with_message(
+			OpStatusCode::InvalidIdentifyingData,
+			message,
+		));
+	}
+
+	match (note.protocol, leaf_index, note.index) {
+		(NoteProtocol::VAnchor, Some(leaf_index), Some(index)) if leaf_index != index => {
+			let message = format!("The note is at leaf {}, not {}", index, leaf_index);
+			Err(OperationError::new_with_message(
+				OpStatusCode::InvalidUTXOIndex,
+				message,
+			))
+		}
+		(NoteProtocol::VAnchor, Some(leaf_index), None) => {
+			let mut note = note.clone();
+			note.index = Some(leaf_index);
+			note_nullifier(&note)
+		}
+		(NoteProtocol::VAnchor, None, None) => {
+			let message = "The nullifier of a VAnchor note depends on its leaf index".to_string();
+			Err(OperationError::new_with_message(
+				OpStatusCode::InvalidUTXOIndex,
+				message,
+			))
+		}
+		_ => note_nullifier(note),
+	}
+}
+
+/// The nullifier the pool at `resourceId` will record when `note` is spent,
+/// to cache for spent tracking. VAnchor notes without an index need their
+/// `leafIndex`
+#[wasm_bindgen(js_name = deriveNullifier)]
+pub fn js_derive_nullifier(
+	note: &JsNote,
+	resource_id: &JsResourceId,
+	leaf_index: Option<u64>,
+) -> Result<Uint8Array, JsValue> {
+	let nullifier = contextual_nullifier(note, &resource_id.inner, leaf_index)?;
+	Ok(Uint8Array::from(nullifier.as_slice()))
+}
+
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct NullifierSet {
+	#[wasm_bindgen(skip)]
+	pub inner: SpentNullifiers,
+}
+
+impl NullifierSet {
+	pub fn exact<I: IntoIterator<Item = [u8; 32]>>(nullifiers: I) -> Self {
+		Self {
+			inner: SpentNullifiers::Exact(nullifiers.into_iter().collect()),
+		}
+	}
+
+	pub fn bloom(filter: BloomFilter) -> Self {
+		Self {
+			inner: SpentNullifiers::Bloom(filter),
+		}
+	}
+
+	pub fn insert(&mut self, nullifier: [u8; 32]) {
+		match &mut self.inner {
+			SpentNullifiers::Exact(nullifiers) => {
+				nullifiers.insert(nullifier);
+			}
+			SpentNullifiers::Bloom(filter) => filter.insert(&nullifier),
+		}
+	}
+
+	/// Whether `nullifier` is spent. A bloom filter can answer `true` for a
+	/// nullifier that isn't, never `false` for one that is
+	pub fn contains(&self, nullifier: &[u8; 32]) -> bool {
+		match &self.inner {
+			SpentNullifiers::Exact(nullifiers) => nullifiers.contains(nullifier),
+			SpentNullifiers::Bloom(filter) => filter.contains(nullifier),
+		}
+	}
+
+	pub fn is_spent(&self, note: &JsNote) -> Result<bool, OperationError> {
+		Ok(self.contains(&note_nullifier(note)?))
+	}
+
+	/// Bloom filter of `bit_count` bits holding the nullifiers of an exact
+	/// set, to publish as a snapshot
+	pub fn to_bloom_filter(&self, bit_count: usize, hash_count: u32) -> Result<BloomFilter, OperationError> {
+		let nullifiers = match &self.inner {
+			SpentNullifiers::Exact(nullifiers) => nullifiers,
+			SpentNullifiers::Bloom(_) => return Err(invalid_nullifier("A bloom filter can't be resized".to_string())),
+		};
+		let mut filter = BloomFilter::new(vec![0; (bit_count + 7) / 8], hash_count)?;
+		for nullifier in nullifiers {
+			filter.insert(nullifier);
+		}
+		Ok(filter)
+	}
+}
+
+fn nullifiers_from_js(nullifiers: &Leaves) -> Result<Vec<[u8; 32]>, OperationError> {
+	leaves_from_js(nullifiers).map_err(|_| invalid_nullifier("Nullifiers should be 32 bytes".to_string()))
+}
+
+#[wasm_bindgen]
+impl NullifierSet {
+	/// Set of the spent nullifiers listed by the chain
+	#[wasm_bindgen(constructor)]
+	pub fn js_new(nullifiers: Leaves) -> Result<NullifierSet, JsValue> {
+		Ok(NullifierSet::exact(nullifiers_from_js(&nullifiers)?))
+	}
+
+	/// Set of the spent nullifiers from a bloom filter snapshot, see the
+	/// module documentation for the layout
+	#[wasm_bindgen(js_name = fromBloomFilter)]
+	pub fn js_from_bloom_filter(bits: Uint8Array, hash_count: u32) -> Result<NullifierSet, JsValue> {
+		Ok(NullifierSet::bloom(BloomFilter::new(bits.to_vec(), hash_count)?))
+	}
+
+	/// Add nullifiers spent since the set was built
+	pub fn add(&mut self, nullifiers: Leaves) -> Result<(), JsValue> {
+		for nullifier in nullifiers_from_js(&nullifiers)? {
+			self.insert(nullifier);
+		}
+		Ok(())
+	}
+
+	/// Whether `note` is spent. Always right for a set of the nullifiers, a
+	/// bloom filter can mark a few unspent notes as spent
+	#[wasm_bindgen(js_name = isSpent)]
+	pub fn js_is_spent(&self, note: &JsNote) -> Result<bool, JsValue> {
+		Ok(self.is_spent(note)?)
+	}
+
+	#[wasm_bindgen(js_name = isSpentNullifier)]
+	pub fn js_is_spent_nullifier(&self, nullifier: Uint8Array) -> Result<bool, JsValue> {
+		let nullifier: [u8; 32] = nullifier
+			.to_vec()
+			.try_into()
+			.map_err(|_| invalid_nullifier("Nullifiers should be 32 bytes".to_string()))?;
+		Ok(self.contains(&nullifier))
+	}
+
+	/// Whether answers can be false positives
+	#[wasm_bindgen(getter, js_name = isBloomFilter)]
+	pub fn is_bloom_filter(&self) -> bool {
+		matches!(self.inner, SpentNullifiers::Bloom(_))
+	}
+
+	/// Bloom filter snapshot of the set, `bitCount` bits with `hashCount`
+	/// hashes per nullifier
+	#[wasm_bindgen(js_name = toBloomFilter)]
+	pub fn js_to_bloom_filter(&self, bit_count: usize, hash_count: u32) -> Result<Uint8Array, JsValue> {
+		let filter = self.to_bloom_filter(bit_count, hash_count)?;
+		Ok(Uint8Array::from(filter.bits.as_slice()))
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use js_sys::JsString;
+	use wasm_bindgen_test::*;
+
+	use super::*;
+	use crate::note::JsNoteBuilder;
+	use crate::proof::test_utils::{MIXER_NOTE_V1_X5_5, VANCHOR_NOTE_V1_X5_4};
+	use crate::types::ChainType;
+
+	fn nullifier(byte: u8) -> [u8; 32] {
+		let mut nullifier = [byte; 32];
+		nullifier[..16].copy_from_slice(&[0; 16]);
+		nullifier[31] = byte.wrapping_mul(31);
+		nullifier
+	}
+
+	#[wasm_bindgen_test]
+	fn should_tell_spent_notes() {
+		let mixer_note = JsNote::deserialize(MIXER_NOTE_V1_X5_5).unwrap();
+		let vanchor_note = JsNote::deserialize(VANCHOR_NOTE_V1_X5_4).unwrap();
+		let mut set = NullifierSet::exact([nullifier(1), nullifier(2)]);
+		assert!(!set.is_spent(&mixer_note).unwrap());
+		set.insert(note_nullifier(&mixer_note).unwrap());
+		assert!(set.is_spent(&mixer_note).unwrap());
+		assert!(!set.is_spent(&vanchor_note).unwrap());
+		set.insert(note_nullifier(&vanchor_note).unwrap());
+		assert!(set.is_spent(&vanchor_note).unwrap());
+
+		// The snapshot has every spent nullifier
+		let filter = set.to_bloom_filter(1 << 12, 8).unwrap();
+		let snapshot = NullifierSet::bloom(filter);
+		assert!(snapshot.is_spent(&mixer_note).unwrap());
+		assert!(snapshot.is_spent(&vanchor_note).unwrap());
+		assert!(snapshot.contains(&nullifier(1)));
+		let false_positives = (3..=255).filter(|byte| snapshot.contains(&nullifier(*byte))).count();
+		assert!(false_positives < 4);
+		assert!(snapshot.to_bloom_filter(64, 1).is_err());
+	}
+
+	#[wasm_bindgen_test]
+	fn should_reject_invalid_bloom_filters() {
+		assert_eq!(
+			BloomFilter::new(vec![], 3).unwrap_err().code,
+			OpStatusCode::InvalidNullifer
+		);
+		assert!(BloomFilter::new(vec![0; 8], 0).is_err());
+		assert!(BloomFilter::new(vec![0; 8], MAX_BLOOM_HASHES + 1).is_err());
+
+		// Bit 0 is the low bit of the first byte
+		let mut filter = BloomFilter::new(vec![0; 2], 1).unwrap();
+		let mut first_bit = [0u8; 32];
+		first_bit[31] = 16;
+		filter.insert(&first_bit);
+		assert_eq!(filter.bits, [1, 0]);
+		assert!(filter.contains(&first_bit));
+	}
+
+	#[wasm_bindgen_test]
+	fn should_derive_the_nullifier_of_a_pool() {
+		let contract = [7u8; 20];
+		let evm = TypedChainId::new(ChainType::Evm, 5);
+		let resource_id = ResourceId::new(TargetSystem::ContractAddress(contract), evm).unwrap();
+		let vanchor_note = |index: Option<&str>| {
+			let mut note_builder = JsNoteBuilder::new();
+			note_builder = note_builder.protocol(JsValue::from("vanchor").into()).unwrap();
+			note_builder = note_builder.version(JsValue::from("v1").into()).unwrap();
+			note_builder = note_builder.source_chain_id(JsString::from(evm.to_string()));
+			note_builder = note_builder.target_chain_id(JsString::from(evm.to_string()));
+			note_builder = note_builder.source_identifying_data(JsString::from(format!("0x{}", hex::encode(contract))));
+			note_builder = note_builder.target_identifying_data(JsString::from(format!("0x{}", hex::encode(contract))));
+			note_builder = note_builder.amount(JsString::from("10"));
+			if let Some(index) = index {
+				note_builder = note_builder.index(JsString::from(index)).unwrap();
+			}
+			note_builder.build().unwrap()
+		};
+		let note = vanchor_note(Some("3"));
+		let nullifier = contextual_nullifier(&note, &resource_id, None).unwrap();
+		assert_eq!(nullifier, note_nullifier(&note).unwrap());
+		assert_eq!(contextual_nullifier(&note, &resource_id, Some(3)).unwrap(), nullifier);
+		let error = contextual_nullifier(&note, &resource_id, Some(4)).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::InvalidUTXOIndex);
+
+		// The leaf index of a note without one is given
+		let mut unindexed = note.clone();
+		unindexed.index = None;
+		assert_eq!(
+			contextual_nullifier(&unindexed, &resource_id, Some(3)).unwrap(),
+			nullifier
+		);
+		assert_ne!(
+			contextual_nullifier(&unindexed, &resource_id, Some(4)).unwrap(),
+			nullifier
+		);
+		let error = contextual_nullifier(&unindexed, &resource_id, None).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::InvalidUTXOIndex);
+
+		// Only the pool of the note is accepted
+		let other_chain = TypedChainId::new(ChainType::Evm, 4);
+		let other = ResourceId::new(TargetSystem::ContractAddress(contract), other_chain).unwrap();
+		let error = contextual_nullifier(&note, &other, None).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::InvalidTargetChain);
+		let other = ResourceId::new(TargetSystem::ContractAddress([8u8; 20]), evm).unwrap();
+		let error = contextual_nullifier(&note, &other, None).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::InvalidIdentifyingData);
+	}
+}
@@ -0,0 +1,381 @@
+//! Withdrawal of several mixer notes to one recipient, one proof per note.
+//!
+//! The fee is given for the whole withdrawal and split between the notes in
+//! proportion to their amounts, the refund is paid once with the first
+//! withdrawal. The withdrawals are ordered by leaf index, oldest deposit
+//! first, and should be submitted in that order.
+use ethabi::Uint;
+use js_sys::{Array, JsString};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::merkle::leaves_from_js;
+use crate::note::amount::{parse_amount_field, NoteAmount};
+use crate::note::JsNote;
+use crate::proof::mixer::{self, MixerProof};
+use crate::proof::{js_note_of_jsval, JsProofInputBuilder, ProofInput, ProofInputBuilder, ProverRng};
+use crate::types::{NoteProtocol, OpStatusCode, OperationError};
+
+/// One note of the withdrawal, before its proof is generated
+#[derive(Debug, Clone)]
+pub struct PlannedWithdrawal {
+	pub note: JsNote,
+	pub leaf_index: u64,
+	pub amount: u128,
+	pub fee: u128,
+	pub refund: u128,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WithdrawalSummary {
+	pub notes: usize,
+	/// Sum of the note amounts, in base units
+	pub amount: String,
+	pub fee: String,
+	pub refund: String,
+	/// What the recipient receives, the amount minus the fee
+	pub payout: String,
+	pub token_symbol: Option<String>,
+	pub denomination: Option<u8>,
+}
+
+/// Split `fee` in proportion to `amounts`, the rounding remainder is taken
+/// one unit at a time from the first notes
+pub fn split_fee(fee: u128, amounts: &[u128]) -> Result<Vec<u128>, OperationError> {
+	let total = amounts
+		.iter()
+		.try_fold(NoteAmount::new(0), |total, amount| {
+			total.checked_add(&NoteAmount::new(*amount))
+		})?
+		.inner;
+	if fee > total {
+		let message = format!("The fee {} exceeds the withdrawn amount {}", fee, total);
+		return Err(OperationError::new_with_message(
+			OpStatusCode::InsufficientBalance,
+			message,
+		));
+	}
+	if fee == 0 {
+		return Ok(vec![0; amounts.len()]);
+	}
+	// The product can overflow a u128, the share never does as `fee <= total`
+	let mut fees: Vec<u128> = amounts
+		.iter()
+		.map(|amount| (Uint::from(fee) * Uint::from(*amount) / Uint::from(total)).as_u128())
+		.collect();
+	let mut remainder = fee - fees.iter().sum::<u128>();
+	for (share, amount) in fees.iter_mut().zip(amounts) {
+		if remainder == 0 {
+			break;
+		}
+		if *share < *amount {
+			*share += 1;
+			remainder -= 1;
+		}
+	}
+	Ok(fees)
+}
+
+/// Order the notes, find their leaves and split the fee. All the notes have
+/// to be mixer notes of the tree holding `leaves`, with the same token and
+/// circuit parameters since they share a proving key.
+pub fn plan_withdrawals(
+	notes: &[JsNote],
+	leaves: &[Vec<u8>],
+	fee: u128,
+	refund: u128,
+) -> Result<(Vec<PlannedWithdrawal>, WithdrawalSummary), OperationError> {
+	let first = notes.first().ok_or_else(|| {
+		OperationError::new_with_message(OpStatusCode::InvalidNoteSecrets, "No notes to withdraw".to_string())
+	})?;
+	let mut planned = Vec::with_capacity(notes.len());
+	for (i, note) in notes.iter().enumerate() {
+		if note.protocol != NoteProtocol::Mixer {
+			let message = format!(
+				"Note {} is a {} note, only mixer notes can be withdrawn",
+				i, note.protocol
+			);
+			return Err(OperationError::new_with_message(
+				OpStatusCode::InvalidNoteProtocol,
+				message,
+			));
+		}
+		let same_tree = note.target_chain_id == first.target_chain_id
+			&& note.target_identifying_data == first.target_identifying_data;
+		let same_circuit =
+			note.curve == first.curve && note.width == first.width && note.exponentiation == first.exponentiation;
+		if !same_tree || !same_circuit || note.token_symbol != first.token_symbol {
+			let message = format!("Note {} isn't on the same tree, circuit and token as the first note", i);
+			return Err(OperationError::new_with_message(
+				OpStatusCode::UnsupportedParameterCombination,
+				message,
+			));
+		}
+		let leaf = note.get_leaf_and_nullifier()?.mixer_leaf()?.leaf_bytes;
+		let leaf_index = leaves.iter().position(|l| *l == leaf).ok_or_else(|| {
+			let message = format!("The leaf of note {} is not in the tree", i);
+			OperationError::new_with_message(OpStatusCode::InvalidLeaves, message)
+		})? as u64;
+		if planned.iter().any(|p: &PlannedWithdrawal| p.leaf_index == leaf_index) {
+			let message = format!("Note {} is given twice", i);
+			return Err(OperationError::new_with_message(OpStatusCode::InvalidLeaves, message));
+		}
+		planned.push(PlannedWithdrawal {
+			note: note.clone(),
+			leaf_index,
+			amount: note.amount_value()?.inner,
+			fee: 0,
+			refund: 0,
+		});
+	}
+	planned.sort_by_key(|p| p.leaf_index);
+
+	let amounts: Vec<u128> = planned.iter().map(|p| p.amount).collect();
+	for (p, fee) in planned.iter_mut().zip(split_fee(fee, &amounts)?) {
+		p.fee = fee;
+	}
+	planned[0].refund = refund;
+
+	let amount = amounts.iter().sum::<u128>();
+	let summary = WithdrawalSummary {
+		notes: planned.len(),
+		amount: amount.to_string(),
+		fee: fee.to_string(),
+		refund: refund.to_string(),
+		payout: (amount - fee).to_string(),
+		token_symbol: first.token_symbol.clone(),
+		denomination: first.denomination,
+	};
+	Ok((planned, summary))
+}
+
+fn prove(
+	planned: &PlannedWithdrawal,
+	leaves: &[Vec<u8>],
+	recipient: &[u8],
+	relayer: &[u8],
+	pk: &[u8],
+	tree_depth: Option<usize>,
+) -> Result<MixerProof, OperationError> {
+	let mut builder = JsProofInputBuilder {
+		inner: ProofInputBuilder::Mixer(Default::default()),
+		partial_withdrawal: None,
+	};
+	builder.set_meta_data(&planned.note)?;
+	builder
+		.inner
+		.secrets(planned.note.get_leaf_and_nullifier()?.mixer_leaf()?)?;
+	builder.inner.leaves_list(leaves.to_vec())?;
+	builder.inner.leaf_index(planned.leaf_index)?;
+	builder.inner.recipient(recipient.to_vec())?;
+	builder.inner.relayer(relayer.to_vec())?;
+	builder.inner.fee(planned.fee)?;
+	builder.inner.refund(planned.refund)?;
+	builder.inner.pk(pk.to_vec())?;
+	if let Some(tree_depth) = tree_depth {
+		builder.inner.tree_depth(tree_depth)?;
+	}
+	let payload = match builder.build()? {
+		ProofInput::Mixer(payload) => *payload,
+		_ => return Err(OpStatusCode::InvalidNoteProtocol.into()),
+	};
+	let mut rng = ProverRng::new(payload.rng_seed);
+	mixer::create_proof(payload, &mut rng)
+}
+
+/// A note of the withdrawal with its share of the fee and its proof
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct NoteWithdrawal {
+	#[wasm_bindgen(skip)]
+	pub note: JsNote,
+	#[wasm_bindgen(skip)]
+	pub leaf_index: u64,
+	#[wasm_bindgen(skip)]
+	pub fee: u128,
+	#[wasm_bindgen(skip)]
+	pub refund: u128,
+	#[wasm_bindgen(skip)]
+	pub proof: MixerProof,
+}
+
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct AggregateWithdrawal {
+	#[wasm_bindgen(skip)]
+	pub withdrawals: Vec<NoteWithdrawal>,
+	#[wasm_bindgen(skip)]
+	pub summary: WithdrawalSummary,
+}
+
+impl AggregateWithdrawal {
+	#[allow(clippy::too_many_arguments)]
+	pub fn generate(
+		notes: &[JsNote],
+		leaves: &[Vec<u8>],
+		recipient: &[u8],
+		relayer: &[u8],
+		fee: u128,
+		refund: u128,
+		pk: &[u8],
+		tree_depth: Option<usize>,
+	) -> Result<Self, OperationError> {
+		let (planned, summary) = plan_withdrawals(notes, leaves, fee, refund)?;
+		let withdrawals = planned
+			.into_iter()
+			.map(|planned| {
+				let proof = prove(&planned, leaves, recipient, relayer, pk, tree_depth)?;
+				Ok(NoteWithdrawal {
+					note: planned.note,
+					leaf_index: planned.leaf_index,
+					fee: planned.fee,
+					refund: planned.refund,
+					proof,
+				})
+			})
+			.collect::<Result<_, OperationError>>()?;
+		Ok(Self { withdrawals, summary })
+	}
+}
+
+#[wasm_bindgen(typescript_custom_section)]
+const WITHDRAWAL_SUMMARY: &str = r#"
+export interface WithdrawalSummary {
+	notes: number;
+	amount: string;
+	fee: string;
+	refund: string;
+	payout: string;
+	tokenSymbol?: string;
+	denomination?: number;
+}
+"#;
+
+#[wasm_bindgen]
+extern "C" {
+	#[wasm_bindgen(typescript_type = "WithdrawalSummary")]
+	pub type WasmWithdrawalSummary;
+
+	#[wasm_bindgen(extends = Array, typescript_type = "Array<NoteWithdrawal>")]
+	pub type NoteWithdrawalArray;
+}
+
+#[allow(clippy::unused_unit)]
+#[wasm_bindgen]
+impl NoteWithdrawal {
+	#[wasm_bindgen(getter)]
+	pub fn note(&self) -> JsNote {
+		self.note.clone()
+	}
+
+	#[wasm_bindgen(getter, js_name = leafIndex)]
+	pub fn leaf_index(&self) -> JsString {
+		self.leaf_index.to_string().into()
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn fee(&self) -> JsString {
+		self.fee.to_string().into()
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn refund(&self) -> JsString {
+		self.refund.to_string().into()
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn proof(&self) -> MixerProof {
+		self.proof.clone()
+	}
+}
+
+#[allow(clippy::unused_unit)]
+#[wasm_bindgen]
+impl AggregateWithdrawal {
+	/// The withdrawals in submission order
+	#[wasm_bindgen(getter)]
+	pub fn withdrawals(&self) -> NoteWithdrawalArray {
+		let withdrawals: Array = self.withdrawals.iter().cloned().map(JsValue::from).collect();
+		withdrawals.unchecked_into()
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn summary(&self) -> Result<WasmWithdrawalSummary, JsValue> {
+		let summary = JsValue::from_serde(&self.summary)
+			.map_err(|e| OperationError::new_with_message(OpStatusCode::SerializationFailed, e.to_string()))?;
+		Ok(summary.unchecked_into())
+	}
+}
+
+/// Withdraw every note in `notes` to `recipient` in one call. The addresses
+/// and the proving key are hex encoded, as for `JsProofInputBuilder`, the
+/// fee and refund are the totals of the withdrawal.
+#[wasm_bindgen(js_name = withdrawAll)]
+#[allow(clippy::too_many_arguments)]
+pub fn withdraw_all(
+	notes: Array,
+	leaves: crate::types::Leaves,
+	recipient: JsString,
+	relayer: JsString,
+	fee: JsString,
+	refund: JsString,
+	pk: JsString,
+	tree_depth: Option<usize>,
+) -> Result<AggregateWithdrawal, JsValue> {
+	let notes = notes
+		.iter()
+		.map(|note| js_note_of_jsval(note).ok_or(OpStatusCode::InvalidNoteSecrets))
+		.collect::<Result<Vec<_>, _>>()?;
+	let leaves: Vec<Vec<u8>> = leaves_from_js(&leaves)?.iter().map(|leaf| leaf.to_vec()).collect();
+	let decode = |value: JsString, code: OpStatusCode| hex::decode(String::from(value)).map_err(|_| code);
+	let recipient = decode(recipient, OpStatusCode::InvalidRecipient)?;
+	let relayer = decode(relayer, OpStatusCode::InvalidRelayer)?;
+	let pk = decode(pk, OpStatusCode::InvalidProvingKey)?;
+	let fee = parse_amount_field(&String::from(fee), None, OpStatusCode::InvalidFee)?;
+	let refund = parse_amount_field(&String::from(refund), None, OpStatusCode::InvalidRefund)?;
+	let withdrawal =
+		AggregateWithdrawal::generate(&notes, &leaves, &recipient, &relayer, fee, refund, &pk, tree_depth)?;
+	Ok(withdrawal)
+}
+
+#[cfg(test)]
+mod test {
+	use wasm_bindgen_test::*;
+
+	use super::*;
+	use crate::proof::refresh::RefreshCommitment;
+	use crate::proof::test_utils::MIXER_NOTE_V1_X5_5;
+
+	#[wasm_bindgen_test]
+	fn should_split_the_fee_by_amount() {
+		assert_eq!(split_fee(10, &[10, 30]).unwrap(), [3, 7]);
+		// A third of 7 rounds down to 2, the remainder goes to the first note
+		assert_eq!(split_fee(7, &[10, 10, 10]).unwrap(), [3, 2, 2]);
+		assert_eq!(split_fee(u128::MAX, &[u128::MAX, 0]).unwrap(), [u128::MAX, 0]);
+		let error = split_fee(5, &[1, 1]).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::InsufficientBalance);
+	}
+
+	#[wasm_bindgen_test]
+	fn should_order_the_notes_by_leaf_index() {
+		let first = JsNote::deserialize(MIXER_NOTE_V1_X5_5).unwrap();
+		let mut second = RefreshCommitment::generate(&first, None).unwrap().note;
+		second.amount = Some("30".to_string());
+		let leaf = |note: &JsNote| note.get_leaf_and_nullifier().unwrap().mixer_leaf().unwrap().leaf_bytes;
+		let leaves = vec![vec![0u8; 32], leaf(&second), leaf(&first)];
+
+		let (planned, summary) = plan_withdrawals(&[first.clone(), second.clone()], &leaves, 8, 1).unwrap();
+		let order: Vec<(u64, u128, u128)> = planned.iter().map(|p| (p.leaf_index, p.fee, p.refund)).collect();
+		assert_eq!(order, [(1, 6, 1), (2, 2, 0)]);
+		assert_eq!(summary.payout, "32");
+		assert_eq!(summary.token_symbol.as_deref(), Some("EDG"));
+
+		let error = plan_withdrawals(&[first.clone(), first.clone()], &leaves, 0, 0).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::InvalidLeaves);
+		second.token_symbol = Some("WEBB".to_string());
+		let error = plan_withdrawals(&[first, second], &leaves, 0, 0).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::UnsupportedParameterCombination);
+	}
+}
@@ -0,0 +1,538 @@
+//! Withdrawals from the fixed deposit anchors. They're proven with the anchor
+//! circuit: the leaf `Poseidon(chain_id, nullifier, secret)` ties the deposit
+//! to its chain, and the root of its tree has to be one of the roots of the
+//! linked anchors.
+use crate::address::AccountEncoding;
+use crate::evm::keccak256;
+use crate::merkle::compute_root;
+use crate::proof::witness::Witness;
+use crate::proof::{
+	encode_public_inputs, ensure_proving_backend, ensure_tree_depth, prover_error_cause, validate_roots,
+};
+use crate::types::{AnchorMode, Backend, Curve, OpStatusCode, OperationError, TargetRuntime};
+use crate::utils::ct_eq;
+use crate::{ANCHOR_COUNT, DEFAULT_LEAF, TREE_HEIGHT};
+use ark_bn254::{Bn254, Fr as Bn254Fr};
+use ark_ff::{BigInteger, PrimeField};
+use arkworks_native_gadgets::poseidon::Poseidon;
+use arkworks_setups::common::{prove_unchecked, setup_params, setup_tree_and_create_path, Leaf};
+use arkworks_setups::r1cs::anchor::{AnchorR1CSProver, PoseidonAnchorCircuit};
+use arkworks_setups::{AnchorProver, Curve as ArkCurve};
+use parity_scale_codec::Encode;
+use rand::{CryptoRng, RngCore};
+
+#[derive(Debug, Clone)]
+pub struct AnchorProof {
+	pub proof: Vec<u8>,
+	pub nullifier_hash: Vec<u8>,
+	pub roots: Vec<Vec<u8>>,
+	/// `[nullifier_hash, ext_data_hash, chain_id, roots..]`
+	pub public_inputs: Vec<Vec<u8>>,
+	pub leaf: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AnchorProofPayload {
+	pub backend: Backend,
+	pub secret: Vec<u8>,
+	pub nullifier: Vec<u8>,
+	pub recipient: Vec<u8>,
+	pub relayer: Vec<u8>,
+	pub refresh_commitment: [u8; 32],
+	pub pk: Vec<u8>,
+	pub refund: u128,
+	pub fee: u128,
+	/// Typed chain id of the anchor the note was deposited in
+	pub chain_id: u64,
+	pub leaves: Vec<Vec<u8>>,
+	pub leaf_index: u64,
+	/// The local root first, then the roots of the linked anchors
+	pub roots: Vec<Vec<u8>>,
+	pub tree_depth: usize,
+	/// Seed of the prover's RNG, `OsRng` is used when it's not set
+	pub rng_seed: Option<[u8; 32]>,
+	pub anchor_mode: AnchorMode,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AnchorProofInput {
+	pub curve: Option<Curve>,
+	pub backend: Option<Backend>,
+	pub secret: Option<Vec<u8>>,
+	pub nullifier: Option<Vec<u8>>,
+	pub recipient: Option<Vec<u8>>,
+	pub relayer: Option<Vec<u8>>,
+	/// Leaf of the note the withdrawal is refreshed into, zero when the
+	/// whole deposit is withdrawn
+	pub refresh_commitment: Option<Vec<u8>>,
+	pub pk: Option<Vec<u8>>,
+	pub refund: Option<u128>,
+	pub fee: Option<u128>,
+	pub chain_id: Option<u64>,
+	pub leaves: Option<Vec<Vec<u8>>>,
+	pub leaf_index: Option<u64>,
+	pub roots: Option<Vec<Vec<u8>>>,
+	pub tree_depth: Option<usize>,
+	pub rng_seed: Option<[u8; 32]>,
+	/// How the external data is hashed, `Substrate` when it's not set.
+	/// `EvmCompat` takes 20 bytes EVM addresses for both accounts
+	pub anchor_mode: Option<AnchorMode>,
+}
+
+impl AnchorProofInput {
+	pub fn build(self) -> Result<AnchorProofPayload, OperationError> {
+		let pk = self.pk.ok_or(OpStatusCode::InvalidProvingKey)?;
+		let recipient = self.recipient.ok_or(OpStatusCode::InvalidRecipient)?;
+		let relayer = self.relayer.ok_or(OpStatusCode::InvalidRelayer)?;
+		let secret = self.secret.ok_or(OpStatusCode::InvalidNoteSecrets)?;
+		let nullifier = self.nullifier.ok_or(OpStatusCode::InvalidNoteSecrets)?;
+		let leaves = self.leaves.ok_or(OpStatusCode::InvalidLeaves)?;
+		let leaf_index = self.leaf_index.ok_or(OpStatusCode::InvalidLeafIndex)?;
+		let roots = self.roots.ok_or(OpStatusCode::RootsNotSet)?;
+		let chain_id = self.chain_id.ok_or(OpStatusCode::InvalidChainId)?;
+		let fee = self.fee.ok_or(OpStatusCode::InvalidFee)?;
+		let refund = self.refund.ok_or(OpStatusCode::InvalidRefund)?;
+		let backend = self.backend.unwrap_or(Backend::Arkworks);
+		let tree_depth = self.tree_depth.unwrap_or(TREE_HEIGHT);
+		ensure_tree_depth(tree_depth)?;
+
+		// arkworks only has the 4 inputs Poseidon of the leaves for Bn254
+		let curve = self.curve.unwrap_or(Curve::Bn254);
+		if curve != Curve::Bn254 {
+			let message = format!("The anchor circuit is only set up for Bn254, not {}", curve);
+			return Err(OperationError::new_with_message(
+				OpStatusCode::UnsupportedParameterCombination,
+				message,
+			));
+		}
+		validate_roots(&roots, ANCHOR_COUNT, curve)?;
+		let refresh_commitment = match self.refresh_commitment {
+			Some(commitment) => commitment.try_into().map_err(|commitment: Vec<u8>| {
+				let message = format!("Expected a 32 bytes refresh commitment, got {} bytes", commitment.len());
+				OperationError::new_with_message(OpStatusCode::CommitmentNotSet, message)
+			})?,
+			None => [0u8; 32],
+		};
+
+		let anchor_mode = self.anchor_mode.unwrap_or(AnchorMode::Substrate);
+		// The Solidity anchors only take `address`es
+		let encoding = match anchor_mode {
+			AnchorMode::EvmCompat => AccountEncoding::Evm,
+			AnchorMode::Substrate => AccountEncoding::Substrate,
+		};
+		let recipient = encoding.encode(&recipient, OpStatusCode::InvalidRecipient)?;
+		let relayer = encoding.encode(&relayer, OpStatusCode::InvalidRelayer)?;
+
+		Ok(AnchorProofPayload {
+			backend,
+			secret,
+			nullifier,
+			recipient,
+			relayer,
+			refresh_commitment,
+			pk,
+			refund,
+			fee,
+			chain_id,
+			leaves,
+			leaf_index,
+			roots,
+			tree_depth,
+			rng_seed: self.rng_seed,
+			anchor_mode,
+		})
+	}
+}
+
+impl AnchorProof {
+	/// Public inputs as protocol-solidity's `FixedDepositAnchor` takes them,
+	/// big endian `uint256` words in circuit order. It's the only verifier
+	/// of the anchor circuit
+	pub fn public_inputs_for(&self, target: TargetRuntime) -> Result<Vec<[u8; 32]>, OperationError> {
+		if target != TargetRuntime::EvmAnchor {
+			let message = format!("{} doesn't verify anchor proofs, EvmAnchor does", target);
+			return Err(OperationError::new_with_message(
+				OpStatusCode::InvalidTargetRuntime,
+				message,
+			));
+		}
+		encode_public_inputs(&self.public_inputs, target)
+	}
+}
+
+/// Hash of the external data that is reduced into the `ext_data_hash`
+/// public input, the accounts are already encoded as
+/// `AnchorProofInput::build` encodes them.
+///
+/// `EvmCompat` hashes `abi.encode` of the `ExtData` struct of
+/// protocol-solidity's `FixedDepositAnchor`: `_refreshCommitment`,
+/// `_recipient`, `_relayer`, `_fee` and `_refund`, each a 32 bytes word.
+/// `Substrate` hashes the accounts, the SCALE encoded fee and refund and the
+/// refresh commitment, as `AnchorR1CSProver::create_proof` does.
+pub fn ext_data_hash(
+	mode: AnchorMode,
+	recipient: &[u8],
+	relayer: &[u8],
+	fee: u128,
+	refund: u128,
+	refresh_commitment: &[u8; 32],
+) -> [u8; 32] {
+	let mut bytes = Vec::with_capacity(160);
+	match mode {
+		AnchorMode::Substrate => {
+			bytes.extend_from_slice(recipient);
+			bytes.extend_from_slice(relayer);
+			bytes.extend(fee.encode());
+			bytes.extend(refund.encode());
+			bytes.extend_from_slice(refresh_commitment);
+		}
+		AnchorMode::EvmCompat => {
+			bytes.extend_from_slice(refresh_commitment);
+			bytes.extend_from_slice(recipient);
+			bytes.extend_from_slice(relayer);
+			// `uint256` words, big endian
+			for amount in [fee, refund] {
+				bytes.extend_from_slice(&[0u8; 16]);
+				bytes.extend_from_slice(&amount.to_be_bytes());
+			}
+		}
+	}
+	keccak256(&bytes)
+}
+
+/// Leaf of a deposit of the secrets in the anchor of `chain_id`
+pub fn anchor_leaf(chain_id: u64, secret: &[u8], nullifier: &[u8]) -> Result<Leaf, OperationError> {
+	AnchorR1CSProver::<Bn254, TREE_HEIGHT, ANCHOR_COUNT>::create_leaf_with_privates(
+		ArkCurve::Bn254,
+		chain_id,
+		secret.to_vec(),
+		nullifier.to_vec(),
+	)
+	.map_err(|e| OperationError::new_with_message(OpStatusCode::InvalidNoteSecrets, e.to_string()))
+}
+
+/// The leaf of the secrets has to be the one at `leaf_index`, and the root of
+/// the leaves one of the roots, else the proof doesn't verify
+fn ensure_leaf_in_tree(payload: &AnchorProofPayload) -> Result<(), OperationError> {
+	let leaf_index = payload.leaf_index;
+	let leaf = anchor_leaf(payload.chain_id, &payload.secret, &payload.nullifier)?;
+	if payload
+		.leaves
+		.get(leaf_index as usize)
+		.map(|l| ct_eq(l, &leaf.leaf_bytes))
+		!= Some(true)
+	{
+		let message = format!(
+			"The leaf at index {} isn't the leaf of the deposit on chain {}",
+			leaf_index, payload.chain_id
+		);
+		let mut error = OperationError::new_with_message(OpStatusCode::LeafNotInTree, message);
+		error.data = Some(format!(
+			"{{\"leafIndex\":{},\"leafCount\":{}}}",
+			leaf_index,
+			payload.leaves.len()
+		));
+		return Err(error);
+	}
+	let leaves: Vec<[u8; 32]> = payload
+		.leaves
+		.iter()
+		.map(|leaf| {
+			Bn254Fr::from_be_bytes_mod_order(leaf)
+				.into_repr()
+				.to_bytes_be()
+				.try_into()
+				.expect("proof::anchor: Bn254 elements are 32 bytes")
+		})
+		.collect();
+	let root = compute_root::<Bn254Fr>(5, 3, &leaves, payload.tree_depth)?;
+	if !payload
+		.roots
+		.iter()
+		.any(|r| Bn254Fr::from_be_bytes_mod_order(r) == root)
+	{
+		let root = hex::encode(root.into_repr().to_bytes_be());
+		let message = format!(
+			"The tree of the leaves has root 0x{}, which isn't one of the roots",
+			root
+		);
+		let mut error = OperationError::new_with_message(OpStatusCode::RootMismatch, message);
+		error.data = Some(format!("{{\"root\":\"0x{}\"}}", root));
+		return Err(error);
+	}
+	Ok(())
+}
+
+/// Circuit of the withdrawal with its leaf and public inputs. The circuit is
+/// the one `AnchorR1CSProver::create_proof` proves, with the external data
+/// hashed as `anchor_mode` hashes it
+#[allow(clippy::type_complexity)]
+fn anchor_circuit<const DEPTH: usize>(
+	payload: &AnchorProofPayload,
+) -> Result<(PoseidonAnchorCircuit<Bn254Fr, DEPTH, ANCHOR_COUNT>, Leaf, Vec<Vec<u8>>), ark_crypto_primitives::Error> {
+	type Prover<const DEPTH: usize> = AnchorR1CSProver<Bn254, DEPTH, ANCHOR_COUNT>;
+	let leaf = Prover::<DEPTH>::create_leaf_with_privates(
+		ArkCurve::Bn254,
+		payload.chain_id,
+		payload.secret.clone(),
+		payload.nullifier.clone(),
+	)?;
+	let tree_hasher = Poseidon::<Bn254Fr>::new(setup_params(ArkCurve::Bn254, 5, 3));
+	let leaves: Vec<Bn254Fr> = payload
+		.leaves
+		.iter()
+		.map(|leaf| Bn254Fr::from_be_bytes_mod_order(leaf))
+		.collect();
+	let (_, path) = setup_tree_and_create_path::<Bn254Fr, Poseidon<Bn254Fr>, DEPTH>(
+		&tree_hasher,
+		&leaves,
+		payload.leaf_index,
+		&DEFAULT_LEAF,
+	)?;
+	let mut roots = [Bn254Fr::from(0u64); ANCHOR_COUNT];
+	for (root, bytes) in roots.iter_mut().zip(&payload.roots) {
+		*root = Bn254Fr::from_be_bytes_mod_order(bytes);
+	}
+	let ext_data_hash = ext_data_hash(
+		payload.anchor_mode,
+		&payload.recipient,
+		&payload.relayer,
+		payload.fee,
+		payload.refund,
+		&payload.refresh_commitment,
+	);
+	let arbitrary_input = Bn254Fr::from_be_bytes_mod_order(&ext_data_hash);
+	let chain_id = Bn254Fr::from(payload.chain_id);
+	let nullifier_hash = Bn254Fr::from_be_bytes_mod_order(&leaf.nullifier_hash_bytes);
+	let circuit = Prover::<DEPTH>::create_circuit(
+		ArkCurve::Bn254,
+		arbitrary_input,
+		Bn254Fr::from_be_bytes_mod_order(&leaf.secret_bytes),
+		Bn254Fr::from_be_bytes_mod_order(&leaf.nullifier_bytes),
+		chain_id,
+		path,
+		roots,
+		nullifier_hash,
+	);
+	let public_inputs = Prover::<DEPTH>::construct_public_inputs(chain_id, nullifier_hash, roots, arbitrary_input)
+		.iter()
+		.map(|input| input.into_repr().to_bytes_be())
+		.collect();
+	Ok((circuit, leaf, public_inputs))
+}
+
+pub fn create_proof<R: RngCore + CryptoRng>(
+	payload: AnchorProofPayload,
+	rng: &mut R,
+) -> Result<AnchorProof, OperationError> {
+	let pk_len = payload.pk.len();
+	let proof_error = |e: ark_crypto_primitives::Error| {
+		prover_error_cause(&e, pk_len).unwrap_or_else(|| {
+			let mut error: OperationError = OpStatusCode::InvalidProofParameters.into();
+			error.data = Some(e.to_string());
+			error
+		})
+	};
+	ensure_proving_backend(payload.backend)?;
+	ensure_tree_depth(payload.tree_depth)?;
+	ensure_leaf_in_tree(&payload)?;
+	let _span = tracing::info_span!("anchor_proof", payload.tree_depth, %payload.anchor_mode).entered();
+
+	let (proof, leaf, public_inputs) = with_tree_depth!(payload.tree_depth, DEPTH => {
+		anchor_circuit::<DEPTH>(&payload).and_then(|(circuit, leaf, public_inputs)| {
+			let proof = prove_unchecked::<Bn254, _, _>(circuit, &payload.pk, rng)?;
+			Ok((proof, leaf, public_inputs))
+		})
+	})
+	.map_err(proof_error)?;
+
+	Ok(AnchorProof {
+		proof,
+		nullifier_hash: leaf.nullifier_hash_bytes,
+		roots: public_inputs[3..].to_vec(),
+		public_inputs,
+		leaf: leaf.leaf_bytes,
+	})
+}
+
+/// Witness of the withdrawal, from the circuit `create_proof` proves
+pub fn create_witness(payload: AnchorProofPayload) -> Result<Witness, OperationError> {
+	ensure_proving_backend(payload.backend)?;
+	ensure_tree_depth(payload.tree_depth)?;
+	ensure_leaf_in_tree(&payload)?;
+	with_tree_depth!(payload.tree_depth, DEPTH => {
+		let (circuit, ..) = anchor_circuit::<DEPTH>(&payload)
+			.map_err(|e| OperationError::new_with_message(OpStatusCode::WitnessGenerationFailed, e.to_string()))?;
+		Witness::synthesize::<Bn254Fr, _>(circuit)
+	})
+}
+
+#[cfg(test)]
+mod test {
+	use ark_std::UniformRand;
+	use arkworks_setups::common::{setup_keys_unchecked, verify_unchecked_raw};
+	use ethabi::{encode, Address, Token, Uint};
+	use rand::rngs::OsRng;
+	use serde_json::Value;
+	use wasm_bindgen_test::*;
+
+	use super::*;
+	use crate::types::{ChainType, TypedChainId};
+
+	/// Vectors of an ABI encoder and Keccak-256 written apart from this crate
+	const EVM_ANCHOR_VECTORS: &str = include_str!("test_vectors/evm_anchor.json");
+
+	fn bytes(vector: &Value, field: &str) -> Vec<u8> {
+		hex::decode(&vector[field].as_str().unwrap()[2..]).unwrap()
+	}
+
+	fn amount(vector: &Value, field: &str) -> u128 {
+		vector[field].as_str().unwrap().parse().unwrap()
+	}
+
+	#[wasm_bindgen_test]
+	fn should_hash_ext_data_as_the_fixed_deposit_anchor() {
+		let vectors: Value = serde_json::from_str(EVM_ANCHOR_VECTORS).unwrap();
+		for vector in vectors["vectors"].as_array().unwrap() {
+			let name = vector["name"].as_str().unwrap();
+			let recipient = AccountEncoding::Evm
+				.encode(&bytes(vector, "recipient"), OpStatusCode::InvalidRecipient)
+				.unwrap();
+			let relayer = AccountEncoding::Evm
+				.encode(&bytes(vector, "relayer"), OpStatusCode::InvalidRelayer)
+				.unwrap();
+			let (fee, refund) = (amount(vector, "fee"), amount(vector, "refund"));
+			let refresh_commitment: [u8; 32] = bytes(vector, "refreshCommitment").try_into().unwrap();
+
+			// The words of `ExtData`, in the order of its fields
+			let abi_encoded = encode(&[
+				Token::FixedBytes(refresh_commitment.to_vec()),
+				Token::Address(Address::from_slice(&bytes(vector, "recipient"))),
+				Token::Address(Address::from_slice(&bytes(vector, "relayer"))),
+				Token::Uint(Uint::from(fee)),
+				Token::Uint(Uint::from(refund)),
+			]);
+			assert_eq!(abi_encoded, bytes(vector, "abiEncoded"), "{}", name);
+
+			let hash = ext_data_hash(
+				AnchorMode::EvmCompat,
+				&recipient,
+				&relayer,
+				fee,
+				refund,
+				&refresh_commitment,
+			);
+			assert_eq!(hash.to_vec(), bytes(vector, "hash"), "{}", name);
+			let reduced = Bn254Fr::from_be_bytes_mod_order(&hash).into_repr().to_bytes_be();
+			assert_eq!(reduced, bytes(vector, "extDataHash"), "{}", name);
+
+			let hash = ext_data_hash(
+				AnchorMode::Substrate,
+				&recipient,
+				&relayer,
+				fee,
+				refund,
+				&refresh_commitment,
+			);
+			assert_eq!(hash.to_vec(), bytes(vector, "substrateHash"), "{}", name);
+		}
+	}
+
+	/// A deposit on an EVM chain that is the only leaf of its tree, with the
+	/// input of its withdrawal to the recipient of the `refresh commitment`
+	/// vector
+	fn evm_withdrawal(pk: Vec<u8>) -> (AnchorProofInput, Value) {
+		let vectors: Value = serde_json::from_str(EVM_ANCHOR_VECTORS).unwrap();
+		let vector = vectors["vectors"][2].clone();
+		let chain_id = TypedChainId::new(ChainType::Evm, 5).to_u64();
+		let secret = Bn254Fr::rand(&mut OsRng).into_repr().to_bytes_be();
+		let nullifier = Bn254Fr::rand(&mut OsRng).into_repr().to_bytes_be();
+		let leaf = anchor_leaf(chain_id, &secret, &nullifier).unwrap().leaf_bytes;
+		let root = compute_root::<Bn254Fr>(5, 3, &[leaf.clone().try_into().unwrap()], TREE_HEIGHT).unwrap();
+		let input = AnchorProofInput {
+			secret: Some(secret),
+			nullifier: Some(nullifier),
+			recipient: Some(bytes(&vector, "recipient")),
+			relayer: Some(bytes(&vector, "relayer")),
+			refresh_commitment: Some(bytes(&vector, "refreshCommitment")),
+			pk: Some(pk),
+			fee: Some(amount(&vector, "fee")),
+			refund: Some(amount(&vector, "refund")),
+			chain_id: Some(chain_id),
+			leaves: Some(vec![leaf]),
+			leaf_index: Some(0),
+			roots: Some(vec![
+				root.into_repr().to_bytes_be(),
+				Bn254Fr::rand(&mut OsRng).into_repr().to_bytes_be(),
+			]),
+			anchor_mode: Some(AnchorMode::EvmCompat),
+			..Default::default()
+		};
+		(input, vector)
+	}
+
+	#[wasm_bindgen_test]
+	fn should_prove_an_evm_compat_withdrawal() {
+		let (circuit, ..) = AnchorR1CSProver::<Bn254, TREE_HEIGHT, ANCHOR_COUNT>::setup_random_circuit(
+			ArkCurve::Bn254,
+			DEFAULT_LEAF,
+			&mut OsRng,
+		)
+		.unwrap();
+		let (pk, vk) = setup_keys_unchecked::<Bn254, _, _>(circuit, &mut OsRng).unwrap();
+		let (input, vector) = evm_withdrawal(pk);
+		let payload = input.build().unwrap();
+		assert_eq!(
+			payload.recipient,
+			[&[0u8; 12][..], &bytes(&vector, "recipient")].concat()
+		);
+
+		let proof = create_proof(payload.clone(), &mut OsRng).unwrap();
+		assert_eq!(proof.public_inputs[0], proof.nullifier_hash);
+		assert_eq!(proof.public_inputs[1], bytes(&vector, "extDataHash"));
+		assert_eq!(
+			proof.public_inputs[2],
+			Bn254Fr::from(payload.chain_id).into_repr().to_bytes_be()
+		);
+		assert_eq!(proof.roots, payload.roots);
+		let words = proof.public_inputs_for(TargetRuntime::EvmAnchor).unwrap();
+		assert_eq!(words.concat(), proof.public_inputs.concat());
+		let error = proof.public_inputs_for(TargetRuntime::EvmVAnchor).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::InvalidTargetRuntime);
+		assert!(verify_unchecked_raw::<Bn254>(&proof.public_inputs, &vk, &proof.proof).unwrap());
+		assert!(create_witness(payload).unwrap().num_constraints > 0);
+	}
+
+	#[wasm_bindgen_test]
+	fn should_check_the_withdrawal_against_the_tree_and_the_roots() {
+		let (input, _) = evm_withdrawal(vec![0u8]);
+
+		// A Substrate account is no address of the Solidity anchors
+		let mut wide_recipient = input.clone();
+		wide_recipient.recipient = Some(vec![5u8; 32]);
+		assert_eq!(wide_recipient.build().unwrap_err().code, OpStatusCode::InvalidRecipient);
+
+		let mut one_root = input.clone();
+		one_root.roots.as_mut().unwrap().truncate(1);
+		assert_eq!(one_root.build().unwrap_err().code, OpStatusCode::InvalidRoots);
+
+		let mut bls = input.clone();
+		bls.curve = Some(Curve::Bls381);
+		assert_eq!(
+			bls.build().unwrap_err().code,
+			OpStatusCode::UnsupportedParameterCombination
+		);
+
+		// The leaf commits to the chain of the deposit
+		let mut other_chain = input.clone().build().unwrap();
+		other_chain.chain_id = TypedChainId::new(ChainType::Evm, 4).to_u64();
+		let error = create_proof(other_chain, &mut OsRng).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::LeafNotInTree);
+
+		let mut unknown_root = input.build().unwrap();
+		unknown_root.roots.swap_remove(0);
+		unknown_root.roots.push(vec![0u8; 32]);
+		let error = create_proof(unknown_root, &mut OsRng).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::RootMismatch);
+	}
+}
@@ -0,0 +1,320 @@
+//! Verification of the phase 2 transcript of a Groth16 trusted setup, the
+//! arkworks equivalent of `snarkjs zkey verify`. Every contribution of the
+//! ceremony multiplies `delta` by the contributor's secret and divides the
+//! `h` and `l` queries by it, leaving the rest of the key as phase 1 and the
+//! circuit made it. The proving keys of the transcript are checked one after
+//! the other,
+//!
+//! - the parts of the key that only depend on phase 1 and the circuit are those
+//!   of the previous key,
+//! - `delta` in G1 and G2 changed by the same ratio, the arkworks setup uses
+//!   random generators so it can't be compared to fixed ones,
+//! - random linear combinations of the `h` and `l` queries changed by the
+//!   inverse of that ratio,
+//!
+//! so the last key of a transcript is the distributed proving key only if it
+//! went through every contribution. The first key is the deterministic start
+//! of phase 2 published with the transcript, it isn't derived from the
+//! circuit here, and the proofs of knowledge of the contributors aren't
+//! checked: they attribute contributions, the key is only as sound as the
+//! contributions it went through.
+use ark_bls12_381::Bls12_381;
+use ark_bn254::Bn254;
+use ark_ec::msm::VariableBaseMSM;
+use ark_ec::{AffineCurve, PairingEngine};
+use ark_ff::{PrimeField, UniformRand, Zero};
+use ark_groth16::ProvingKey;
+use ark_serialize::CanonicalDeserialize;
+use js_sys::{Array, JsString, Uint8Array};
+use rand::rngs::OsRng;
+use rand::{CryptoRng, RngCore};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::evm::keccak256;
+use crate::proof::groth16::parse_curve;
+use crate::types::{Curve, OpStatusCode, OperationError, StringArray, WasmCurve};
+
+fn invalid_contribution(message: String) -> OperationError {
+	OperationError::new_with_message(OpStatusCode::InvalidContribution, message)
+}
+
+// Points are checked to be in their subgroup, the pairing checks don't hold
+// for points outside of it. Keys from `setupKeys` are uncompressed
+fn parse_key<E: PairingEngine>(key: &[u8]) -> Result<ProvingKey<E>, OperationError> {
+	ProvingKey::<E>::deserialize(key)
+		.or_else(|_| ProvingKey::<E>::deserialize_uncompressed(key))
+		.map_err(|e| OperationError::new_with_message(OpStatusCode::InvalidProvingKey, e.to_string()))
+}
+
+fn same_ratio<E: PairingEngine>(a1: E::G1Affine, b2: E::G2Affine, b1: E::G1Affine, a2: E::G2Affine) -> bool {
+	E::pairing(a1, b2) == E::pairing(b1, a2)
+}
+
+/// `sum(r_i * points_i)` with the same random `r_i` for both sets of points
+fn random_combinations<G: AffineCurve, R: RngCore + CryptoRng>(previous: &[G], next: &[G], rng: &mut R) -> (G, G) {
+	let scalars: Vec<_> = previous.iter().map(|_| G::ScalarField::rand(rng).into_repr()).collect();
+	let previous = VariableBaseMSM::multi_scalar_mul(previous, &scalars);
+	let next = VariableBaseMSM::multi_scalar_mul(next, &scalars);
+	(previous.into(), next.into())
+}
+
+/// Check that `next` is `previous` after one contribution
+pub fn verify_contribution<E: PairingEngine, R: RngCore + CryptoRng>(
+	previous: &ProvingKey<E>,
+	next: &ProvingKey<E>,
+	rng: &mut R,
+) -> Result<(), OperationError> {
+	let unchanged = previous.vk.alpha_g1 == next.vk.alpha_g1
+		&& previous.vk.beta_g2 == next.vk.beta_g2
+		&& previous.vk.gamma_g2 == next.vk.gamma_g2
+		&& previous.vk.gamma_abc_g1 == next.vk.gamma_abc_g1
+		&& previous.beta_g1 == next.beta_g1
+		&& previous.a_query == next.a_query
+		&& previous.b_g1_query == next.b_g1_query
+		&& previous.b_g2_query == next.b_g2_query;
+	if !unchanged {
+		return Err(invalid_contribution(
+			"The contribution changed more than delta, the key is for another circuit or setup".to_string(),
+		));
+	}
+	if next.delta_g1.is_zero() || next.vk.delta_g2.is_zero() {
+		return Err(invalid_contribution("The contribution zeroed delta".to_string()));
+	}
+	if !same_ratio::<E>(next.delta_g1, previous.vk.delta_g2, previous.delta_g1, next.vk.delta_g2) {
+		return Err(invalid_contribution(
+			"Delta changed by different secrets in G1 and G2".to_string(),
+		));
+	}
+
+	for (name, previous_query, next_query) in [
+		("h", &previous.h_query, &next.h_query),
+		("l", &previous.l_query, &next.l_query),
+	] {
+		if previous_query.len() != next_query.len() {
+			return Err(invalid_contribution(format!(
+				"The {} query has {} points instead of {}",
+				name,
+				next_query.len(),
+				previous_query.len()
+			)));
+		}
+		// `next_i * delta' = previous_i * delta` for every point
+		let (previous_sum, next_sum) = random_combinations(previous_query, next_query, rng);
+		if !same_ratio::<E>(next_sum, next.vk.delta_g2, previous_sum, previous.vk.delta_g2) {
+			return Err(invalid_contribution(format!(
+				"The {} query wasn't divided by the contributed secret",
+				name
+			)));
+		}
+	}
+	Ok(())
+}
+
+#[derive(Clone)]
+enum CeremonyKey {
+	Bn254(Box<ProvingKey<Bn254>>),
+	Bls381(Box<ProvingKey<Bls12_381>>),
+}
+
+impl CeremonyKey {
+	fn parse(curve: Curve, key: &[u8]) -> Result<Self, OperationError> {
+		Ok(match curve {
+			Curve::Bn254 => CeremonyKey::Bn254(Box::new(parse_key(key)?)),
+			Curve::Bls381 => CeremonyKey::Bls381(Box::new(parse_key(key)?)),
+		})
+	}
+
+	fn curve(&self) -> Curve {
+		match self {
+			CeremonyKey::Bn254(_) => Curve::Bn254,
+			CeremonyKey::Bls381(_) => Curve::Bls381,
+		}
+	}
+}
+
+/// A phase 2 transcript checked so far. Only the last key is kept, the keys
+/// of a transcript are added one at a time as they are downloaded
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct CeremonyVerifier {
+	last_key: CeremonyKey,
+	/// `keccak256` of the keys as given, the initial key first
+	fingerprints: Vec<[u8; 32]>,
+}
+
+impl CeremonyVerifier {
+	/// Start from the initial phase 2 key of the ceremony
+	pub fn new(curve: Curve, initial_key: &[u8]) -> Result<Self, OperationError> {
+		Ok(Self {
+			last_key: CeremonyKey::parse(curve, initial_key)?,
+			fingerprints: vec![keccak256(initial_key)],
+		})
+	}
+
+	/// Check the key after the next contribution, returning its index, the
+	/// first contribution being 1
+	pub fn add_contribution<R: RngCore + CryptoRng>(
+		&mut self,
+		key: &[u8],
+		rng: &mut R,
+	) -> Result<usize, OperationError> {
+		let next = CeremonyKey::parse(self.last_key.curve(), key)?;
+		let index = self.fingerprints.len();
+		match (&self.last_key, &next) {
+			(CeremonyKey::Bn254(previous), CeremonyKey::Bn254(next)) => verify_contribution(previous, next, rng),
+			(CeremonyKey::Bls381(previous), CeremonyKey::Bls381(next)) => verify_contribution(previous, next, rng),
+			_ => unreachable!("keys are parsed for the curve of the ceremony"),
+		}
+		.map_err(|mut e| {
+			e.error_message = format!("Contribution {}: {}", index, e.error_message);
+			e.data = Some(format!("{{\"contribution\":{}}}", index));
+			e
+		})?;
+		self.last_key = next;
+		self.fingerprints.push(keccak256(key));
+		Ok(index)
+	}
+
+	pub fn contribution_count(&self) -> usize {
+		self.fingerprints.len() - 1
+	}
+
+	/// Whether `key` is the key the checked contributions led to, in any
+	/// encoding
+	pub fn matches(&self, key: &[u8]) -> Result<bool, OperationError> {
+		let key = CeremonyKey::parse(self.last_key.curve(), key)?;
+		Ok(match (&self.last_key, &key) {
+			(CeremonyKey::Bn254(last), CeremonyKey::Bn254(key)) => last == key,
+			(CeremonyKey::Bls381(last), CeremonyKey::Bls381(key)) => last == key,
+			_ => false,
+		})
+	}
+}
+
+#[allow(clippy::unused_unit)]
+#[wasm_bindgen]
+impl CeremonyVerifier {
+	/// Start checking a transcript from its initial key, Bn254 unless
+	/// `curve` is set
+	#[wasm_bindgen(constructor)]
+	pub fn js_new(initial_key: Uint8Array, curve: Option<WasmCurve>) -> Result<CeremonyVerifier, JsValue> {
+		Ok(CeremonyVerifier::new(parse_curve(curve)?, &initial_key.to_vec())?)
+	}
+
+	/// Check the key after the next contribution of the transcript. Fails with
+	/// `InvalidContribution`, the error data holding the contribution index
+	#[wasm_bindgen(js_name = addContribution)]
+	pub fn js_add_contribution(&mut self, key: Uint8Array) -> Result<usize, JsValue> {
+		Ok(self.add_contribution(&key.to_vec(), &mut OsRng)?)
+	}
+
+	#[wasm_bindgen(getter, js_name = contributionCount)]
+	pub fn js_contribution_count(&self) -> usize {
+		self.contribution_count()
+	}
+
+	/// Hex `keccak256` of the keys of the transcript, to compare with the
+	/// published hashes
+	#[wasm_bindgen(getter)]
+	pub fn fingerprints(&self) -> StringArray {
+		let fingerprints: Array = self
+			.fingerprints
+			.iter()
+			.map(|fingerprint| JsString::from(hex::encode(fingerprint)))
+			.collect();
+		fingerprints.unchecked_into()
+	}
+
+	/// Whether the distributed proving key `key` is the result of the
+	/// checked transcript
+	#[wasm_bindgen(js_name = matches)]
+	pub fn js_matches(&self, key: Uint8Array) -> Result<bool, JsValue> {
+		Ok(self.matches(&key.to_vec())?)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use ark_bn254::Fr;
+	use ark_ff::Field;
+	use ark_groth16::generate_random_parameters;
+	use ark_relations::lc;
+	use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+	use ark_serialize::CanonicalSerialize;
+	use wasm_bindgen_test::*;
+
+	use super::*;
+
+	/// `x * x = y`, with `y` public
+	struct Square;
+
+	impl ConstraintSynthesizer<Fr> for Square {
+		fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+			let x = cs.new_witness_variable(|| Ok(Fr::from(3u64)))?;
+			let y = cs.new_input_variable(|| Ok(Fr::from(9u64)))?;
+			cs.enforce_constraint(lc!() + x, lc!() + x, lc!() + y)?;
+			cs.enforce_constraint(lc!() + x, lc!() + x, lc!() + y)
+		}
+	}
+
+	fn contribute(key: &ProvingKey<Bn254>) -> ProvingKey<Bn254> {
+		let secret = Fr::rand(&mut OsRng);
+		let inverse = secret.inverse().unwrap();
+		let mut next = key.clone();
+		next.delta_g1 = key.delta_g1.mul(secret).into();
+		next.vk.delta_g2 = key.vk.delta_g2.mul(secret).into();
+		next.h_query = key.h_query.iter().map(|point| point.mul(inverse).into()).collect();
+		next.l_query = key.l_query.iter().map(|point| point.mul(inverse).into()).collect();
+		next
+	}
+
+	fn bytes(key: &ProvingKey<Bn254>) -> Vec<u8> {
+		let mut bytes = Vec::new();
+		key.serialize_uncompressed(&mut bytes).unwrap();
+		bytes
+	}
+
+	#[wasm_bindgen_test]
+	fn should_verify_a_transcript() {
+		let initial = generate_random_parameters::<Bn254, _, _>(Square, &mut OsRng).unwrap();
+		let first = contribute(&initial);
+		let second = contribute(&first);
+
+		let mut verifier = CeremonyVerifier::new(Curve::Bn254, &bytes(&initial)).unwrap();
+		assert_eq!(verifier.add_contribution(&bytes(&first), &mut OsRng).unwrap(), 1);
+		let mut compressed = Vec::new();
+		second.serialize(&mut compressed).unwrap();
+		assert_eq!(verifier.add_contribution(&compressed, &mut OsRng).unwrap(), 2);
+		assert_eq!(verifier.contribution_count(), 2);
+		assert_eq!(verifier.fingerprints[2], keccak256(&compressed));
+		assert!(verifier.matches(&bytes(&second)).unwrap());
+		assert!(!verifier.matches(&bytes(&first)).unwrap());
+	}
+
+	#[wasm_bindgen_test]
+	fn should_reject_invalid_contributions() {
+		let initial = generate_random_parameters::<Bn254, _, _>(Square, &mut OsRng).unwrap();
+		let verifier = CeremonyVerifier::new(Curve::Bn254, &bytes(&initial)).unwrap();
+		let invalid = |key: ProvingKey<Bn254>| {
+			let error = verifier.clone().add_contribution(&bytes(&key), &mut OsRng).unwrap_err();
+			assert_eq!(error.code, OpStatusCode::InvalidContribution);
+			assert_eq!(error.data.as_deref(), Some(r#"{"contribution":1}"#));
+			error.error_message
+		};
+
+		// A point of the h query left out of the contribution
+		let mut key = contribute(&initial);
+		key.h_query[0] = initial.h_query[0];
+		assert!(invalid(key).contains("h query"));
+		let mut key = contribute(&initial);
+		key.vk.delta_g2 = initial.vk.delta_g2;
+		assert!(invalid(key).contains("Delta"));
+		let mut key = contribute(&initial);
+		key.vk.alpha_g1 = key.delta_g1;
+		assert!(invalid(key).contains("more than delta"));
+		// A key from another setup of the circuit
+		let other = generate_random_parameters::<Bn254, _, _>(Square, &mut OsRng).unwrap();
+		invalid(other);
+	}
+}
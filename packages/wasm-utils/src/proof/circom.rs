@@ -0,0 +1,698 @@
+//! Groth16 proving of circuits compiled with Circom and set up with snarkjs,
+//! the circuits of the Circom backend. The witness is computed by the host
+//! with the witness calculator Circom builds next to the circuit, and passed
+//! in the iden3 `.wtns` format. The proving key is the snarkjs `.zkey`, so the
+//! proofs verify against the Solidity verifiers exported from the same key.
+//!
+//! snarkjs keys don't use the arkworks QAP reduction, `h` is evaluated on the
+//! odd powers of a root of unity of twice the domain size, which is what the
+//! `H` section of the zkey commits to.
+use ark_bn254::{Bn254, Fq, Fq2, Fr, G1Affine, G1Projective, G2Affine, G2Projective};
+use ark_ec::msm::VariableBaseMSM;
+use ark_ec::{AffineCurve, ProjectiveCurve};
+use ark_ff::{BigInteger, BigInteger256, FpParameters, One, PrimeField, UniformRand, Zero};
+use ark_groth16::{prepare_verifying_key, verify_proof, Proof, ProvingKey, VerifyingKey};
+use ark_poly::{EvaluationDomain, Radix2EvaluationDomain};
+use js_sys::{Array, JsString, Uint8Array};
+use rand::rngs::OsRng;
+use rand::{CryptoRng, RngCore};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::memory::{LiveObject, ObjectKind};
+use crate::proof::groth16::{Groth16ProofInner, JsGroth16Proof};
+use crate::proof::witness::{WTNS_DATA_SECTION, WTNS_HEADER_SECTION, WTNS_MAGIC};
+use crate::types::{OpStatusCode, OperationError, StringArray};
+
+const ZKEY_MAGIC: &[u8; 4] = b"zkey";
+const ZKEY_GROTH16: u32 = 1;
+const ZKEY_HEADER_SECTION: u32 = 1;
+const ZKEY_GROTH16_HEADER_SECTION: u32 = 2;
+const ZKEY_IC_SECTION: u32 = 3;
+const ZKEY_COEFFS_SECTION: u32 = 4;
+const ZKEY_A_SECTION: u32 = 5;
+const ZKEY_B1_SECTION: u32 = 6;
+const ZKEY_B2_SECTION: u32 = 7;
+const ZKEY_C_SECTION: u32 = 8;
+const ZKEY_H_SECTION: u32 = 9;
+
+/// `(coefficient, signal)` pairs of a constraint
+type Row = Vec<(Fr, usize)>;
+
+fn invalid_key(message: String) -> OperationError {
+	OperationError::new_with_message(OpStatusCode::InvalidProvingKey, message)
+}
+
+fn invalid_witness(message: String) -> OperationError {
+	OperationError::new_with_message(OpStatusCode::WitnessGenerationFailed, message)
+}
+
+/// Reads the little endian values of an iden3 binary file
+struct Reader<'a> {
+	bytes: &'a [u8],
+	error: fn(String) -> OperationError,
+}
+
+impl<'a> Reader<'a> {
+	fn take(&mut self, len: usize) -> Result<&'a [u8], OperationError> {
+		if self.bytes.len() < len {
+			return Err((self.error)("Unexpected end of file".to_string()));
+		}
+		let (taken, rest) = self.bytes.split_at(len);
+		self.bytes = rest;
+		Ok(taken)
+	}
+
+	fn u32(&mut self) -> Result<u32, OperationError> {
+		Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+	}
+
+	fn u64(&mut self) -> Result<u64, OperationError> {
+		Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+	}
+
+	fn usize(&mut self) -> Result<usize, OperationError> {
+		Ok(self.u32()? as usize)
+	}
+
+	fn big_integer(&mut self) -> Result<BigInteger256, OperationError> {
+		let bytes = self.take(32)?;
+		let mut limbs = [0u64; 4];
+		for (limb, bytes) in limbs.iter_mut().zip(bytes.chunks(8)) {
+			*limb = u64::from_le_bytes(bytes.try_into().unwrap());
+		}
+		Ok(BigInteger256::new(limbs))
+	}
+
+	/// Checks that the file's prime is `P`'s modulus
+	fn modulus<P: FpParameters<BigInt = BigInteger256>>(&mut self, name: &str) -> Result<(), OperationError> {
+		if self.usize()? != 32 || self.big_integer()? != P::MODULUS {
+			return Err((self.error)(format!("The {} field isn't Bn254's", name)));
+		}
+		Ok(())
+	}
+
+	/// The sections of the file, indexed by id
+	fn sections(&mut self, magic: &[u8; 4]) -> Result<Vec<Option<&'a [u8]>>, OperationError> {
+		if self.take(4)? != magic {
+			let message = format!("Not a .{} file", String::from_utf8_lossy(magic));
+			return Err((self.error)(message));
+		}
+		let _version = self.u32()?;
+		let mut sections = vec![None; 11];
+		for _ in 0..self.u32()? {
+			let id = self.usize()?;
+			let len = self.u64()? as usize;
+			let section = self.take(len)?;
+			if id >= sections.len() {
+				continue;
+			}
+			if sections[id].replace(section).is_some() {
+				return Err((self.error)(format!("Section {} is repeated", id)));
+			}
+		}
+		Ok(sections)
+	}
+}
+
+fn section<'a>(
+	sections: &[Option<&'a [u8]>],
+	id: u32,
+	error: fn(String) -> OperationError,
+) -> Result<Reader<'a>, OperationError> {
+	match sections[id as usize] {
+		Some(bytes) => Ok(Reader { bytes, error }),
+		None => Err(error(format!("Section {} is missing", id))),
+	}
+}
+
+// Coordinates are stored in Montgomery form, the point at infinity as zeros
+impl<'a> Reader<'a> {
+	fn fq(&mut self) -> Result<Fq, OperationError> {
+		let repr = self.big_integer()?;
+		if repr >= <Fq as PrimeField>::Params::MODULUS {
+			return Err(invalid_key("Coordinate is not a field element".to_string()));
+		}
+		Ok(Fq::new(repr))
+	}
+
+	fn g1(&mut self) -> Result<G1Affine, OperationError> {
+		let (x, y) = (self.fq()?, self.fq()?);
+		if x.is_zero() && y.is_zero() {
+			return Ok(G1Affine::zero());
+		}
+		let point = G1Affine::new(x, y, false);
+		if !point.is_on_curve() {
+			return Err(invalid_key("Point is not on the curve".to_string()));
+		}
+		Ok(point)
+	}
+
+	fn g2(&mut self) -> Result<G2Affine, OperationError> {
+		let x = Fq2::new(self.fq()?, self.fq()?);
+		let y = Fq2::new(self.fq()?, self.fq()?);
+		if x.is_zero() && y.is_zero() {
+			return Ok(G2Affine::zero());
+		}
+		let point = G2Affine::new(x, y, false);
+		if !point.is_on_curve() {
+			return Err(invalid_key("Point is not on the curve".to_string()));
+		}
+		Ok(point)
+	}
+
+	fn g1s(&mut self, len: usize) -> Result<Vec<G1Affine>, OperationError> {
+		(0..len).map(|_| self.g1()).collect()
+	}
+
+	fn g2s(&mut self, len: usize) -> Result<Vec<G2Affine>, OperationError> {
+		(0..len).map(|_| self.g2()).collect()
+	}
+}
+
+/// A Bn254 Groth16 proving key exported by snarkjs, with the `A` and `B`
+/// constraints the prover needs
+#[wasm_bindgen(js_name = CircomProvingKey)]
+pub struct CircomProvingKey {
+	#[wasm_bindgen(skip)]
+	pub proving_key: ProvingKey<Bn254>,
+	/// Number of public signals, outputs included
+	#[wasm_bindgen(skip)]
+	pub num_public: usize,
+	/// Number of signals, the constant one included
+	#[wasm_bindgen(skip)]
+	pub num_vars: usize,
+	#[wasm_bindgen(skip)]
+	pub domain_size: usize,
+	#[wasm_bindgen(skip)]
+	pub a: Vec<Row>,
+	#[wasm_bindgen(skip)]
+	pub b: Vec<Row>,
+	#[wasm_bindgen(skip)]
+	pub live: LiveObject,
+}
+
+impl CircomProvingKey {
+	/// Parse a snarkjs Groth16 `.zkey`
+	pub fn from_zkey(zkey: &[u8]) -> Result<Self, OperationError> {
+		let sections = Reader {
+			bytes: zkey,
+			error: invalid_key,
+		}
+		.sections(ZKEY_MAGIC)?;
+		if section(&sections, ZKEY_HEADER_SECTION, invalid_key)?.u32()? != ZKEY_GROTH16 {
+			return Err(invalid_key("Only Groth16 keys are supported".to_string()));
+		}
+
+		let mut header = section(&sections, ZKEY_GROTH16_HEADER_SECTION, invalid_key)?;
+		header.modulus::<<Fq as PrimeField>::Params>("base")?;
+		header.modulus::<<Fr as PrimeField>::Params>("scalar")?;
+		let num_vars = header.usize()?;
+		let num_public = header.usize()?;
+		let domain_size = header.usize()?;
+		if num_public >= num_vars || !domain_size.is_power_of_two() {
+			return Err(invalid_key("Invalid key header".to_string()));
+		}
+		let alpha_g1 = header.g1()?;
+		let beta_g1 = header.g1()?;
+		let beta_g2 = header.g2()?;
+		let gamma_g2 = header.g2()?;
+		let delta_g1 = header.g1()?;
+		let delta_g2 = header.g2()?;
+
+		let (a, b) = Self::read_constraints(
+			section(&sections, ZKEY_COEFFS_SECTION, invalid_key)?,
+			num_vars,
+			num_public,
+			domain_size,
+		)?;
+
+		let vk = VerifyingKey {
+			alpha_g1,
+			beta_g2,
+			gamma_g2,
+			delta_g2,
+			gamma_abc_g1: section(&sections, ZKEY_IC_SECTION, invalid_key)?.g1s(num_public + 1)?,
+		};
+		let proving_key = ProvingKey {
+			vk,
+			beta_g1,
+			delta_g1,
+			a_query: section(&sections, ZKEY_A_SECTION, invalid_key)?.g1s(num_vars)?,
+			b_g1_query: section(&sections, ZKEY_B1_SECTION, invalid_key)?.g1s(num_vars)?,
+			b_g2_query: section(&sections, ZKEY_B2_SECTION, invalid_key)?.g2s(num_vars)?,
+			h_query: section(&sections, ZKEY_H_SECTION, invalid_key)?.g1s(domain_size)?,
+			l_query: section(&sections, ZKEY_C_SECTION, invalid_key)?.g1s(num_vars - num_public - 1)?,
+		};
+		Ok(Self {
+			proving_key,
+			num_public,
+			num_vars,
+			domain_size,
+			a,
+			b,
+			live: LiveObject::new(ObjectKind::ProvingKeys, zkey.len()),
+		})
+	}
+
+	// The coefficients are in Montgomery form twice. snarkjs appends a
+	// `signal * 1 = 0` constraint for the constant and every public signal,
+	// which the prover adds back itself, so they're dropped here
+	fn read_constraints(
+		mut coeffs: Reader,
+		num_vars: usize,
+		num_public: usize,
+		domain_size: usize,
+	) -> Result<(Vec<Row>, Vec<Row>), OperationError> {
+		let mut matrices = vec![vec![Row::new(); domain_size]; 2];
+		let mut last_constraint = 0;
+		for _ in 0..coeffs.u32()? {
+			let matrix = coeffs.usize()?;
+			let constraint = coeffs.usize()?;
+			let signal = coeffs.usize()?;
+			let value = Fr::new(Fr::new(coeffs.big_integer()?).into_repr());
+			if matrix > 1 || constraint >= domain_size || signal >= num_vars {
+				return Err(invalid_key("Coefficient out of the key's bounds".to_string()));
+			}
+			last_constraint = last_constraint.max(constraint);
+			matrices[matrix][constraint].push((value, signal));
+		}
+		let num_constraints = last_constraint.saturating_sub(num_public);
+		if num_constraints + num_public + 1 > domain_size {
+			return Err(invalid_key("The constraints don't fit the key's domain".to_string()));
+		}
+		let b = matrices.pop().unwrap();
+		let a = matrices.pop().unwrap();
+		Ok((
+			a.into_iter().take(num_constraints).collect(),
+			b.into_iter().take(num_constraints).collect(),
+		))
+	}
+
+	/// `h` on the odd powers of the root of unity of twice the domain size,
+	/// which is `(A * B - C) / Z` up to a constant folded into the `H` points
+	fn witness_map(&self, witness: &[Fr]) -> Vec<Fr> {
+		let domain = Radix2EvaluationDomain::<Fr>::new(self.domain_size).unwrap();
+		let evaluate = |row: &Row| -> Fr { row.iter().map(|(coeff, signal)| *coeff * witness[*signal]).sum() };
+
+		let mut a = vec![Fr::zero(); self.domain_size];
+		let mut b = vec![Fr::zero(); self.domain_size];
+		for (i, (a_row, b_row)) in self.a.iter().zip(&self.b).enumerate() {
+			a[i] = evaluate(a_row);
+			b[i] = evaluate(b_row);
+		}
+		let num_constraints = self.a.len();
+		a[num_constraints..num_constraints + self.num_public + 1].copy_from_slice(&witness[..self.num_public + 1]);
+		let mut c: Vec<Fr> = a.iter().zip(&b).map(|(a, b)| *a * b).collect();
+
+		let shift = Radix2EvaluationDomain::<Fr>::new(2 * self.domain_size)
+			.unwrap()
+			.element(1);
+		for evals in [&mut a, &mut b, &mut c] {
+			domain.ifft_in_place(evals);
+			Radix2EvaluationDomain::distribute_powers(evals, shift);
+			domain.fft_in_place(evals);
+		}
+		a.iter().zip(&b).zip(&c).map(|((a, b), c)| *a * b - c).collect()
+	}
+
+	/// Prove with `witness`, every signal of the circuit in Circom's order.
+	/// Fails if the witness isn't the circuit's or doesn't satisfy it
+	pub fn prove<R: RngCore + CryptoRng>(&self, witness: &[Fr], rng: &mut R) -> Result<CircomProof, OperationError> {
+		if witness.len() != self.num_vars {
+			let message = format!(
+				"The witness has {} signals, the key's circuit {}",
+				witness.len(),
+				self.num_vars
+			);
+			return Err(invalid_witness(message));
+		}
+		if !witness[0].is_one() {
+			return Err(invalid_witness("The first signal of a witness is one".to_string()));
+		}
+		let pk = &self.proving_key;
+		let h = self.witness_map(witness);
+		let h = h.iter().map(|x| x.into_repr()).collect::<Vec<_>>();
+		let assignment = witness.iter().map(|x| x.into_repr()).collect::<Vec<_>>();
+		let aux_assignment = &assignment[self.num_public + 1..];
+
+		let r = Fr::rand(rng);
+		let s = Fr::rand(rng);
+		let g_a = pk.delta_g1.mul(r)
+			+ G1Projective::from(pk.vk.alpha_g1)
+			+ VariableBaseMSM::multi_scalar_mul(&pk.a_query, &assignment);
+		let g1_b = pk.delta_g1.mul(s)
+			+ G1Projective::from(pk.beta_g1)
+			+ VariableBaseMSM::multi_scalar_mul(&pk.b_g1_query, &assignment);
+		let g2_b = pk.vk.delta_g2.mul(s)
+			+ G2Projective::from(pk.vk.beta_g2)
+			+ VariableBaseMSM::multi_scalar_mul(&pk.b_g2_query, &assignment);
+		let g_c = g_a.mul(s.into_repr()) + g1_b.mul(r.into_repr()) - pk.delta_g1.mul(r * s)
+			+ VariableBaseMSM::multi_scalar_mul(&pk.l_query, aux_assignment)
+			+ VariableBaseMSM::multi_scalar_mul(&pk.h_query, &h);
+		let proof = Proof {
+			a: g_a.into_affine(),
+			b: g2_b.into_affine(),
+			c: g_c.into_affine(),
+		};
+
+		let public_inputs = &witness[1..=self.num_public];
+		let verified = verify_proof(&prepare_verifying_key(&pk.vk), &proof, public_inputs).unwrap_or(false);
+		if !verified {
+			return Err(invalid_witness(
+				"The witness doesn't satisfy the circuit's constraints".to_string(),
+			));
+		}
+		Ok(CircomProof {
+			proof: JsGroth16Proof {
+				inner: Groth16ProofInner::Bn254(proof),
+			},
+			public_inputs: public_inputs.iter().map(|x| x.into_repr().to_bytes_be()).collect(),
+		})
+	}
+}
+
+/// Parse a `.wtns` file, the witness calculators of Circom output them
+pub fn parse_wtns(wtns: &[u8]) -> Result<Vec<Fr>, OperationError> {
+	let sections = Reader {
+		bytes: wtns,
+		error: invalid_witness,
+	}
+	.sections(WTNS_MAGIC)?;
+	let mut header = section(&sections, WTNS_HEADER_SECTION, invalid_witness)?;
+	header.modulus::<<Fr as PrimeField>::Params>("scalar")?;
+	let len = header.usize()?;
+	let mut data = section(&sections, WTNS_DATA_SECTION, invalid_witness)?;
+	(0..len)
+		.map(|_| {
+			Fr::from_repr(data.big_integer()?)
+				.ok_or_else(|| invalid_witness("Signal is not a field element".to_string()))
+		})
+		.collect()
+}
+
+/// A proof of a Circom circuit with its public signals
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct CircomProof {
+	#[wasm_bindgen(skip)]
+	pub proof: JsGroth16Proof,
+	/// Public signals in circuit order, big endian
+	#[wasm_bindgen(skip)]
+	pub public_inputs: Vec<Vec<u8>>,
+}
+
+#[wasm_bindgen]
+impl CircomProof {
+	#[wasm_bindgen(getter)]
+	pub fn proof(&self) -> JsGroth16Proof {
+		self.proof.clone()
+	}
+
+	#[wasm_bindgen(getter)]
+	#[wasm_bindgen(js_name = publicInputs)]
+	pub fn js_public_inputs(&self) -> StringArray {
+		let inputs: Array = self
+			.public_inputs
+			.iter()
+			.map(|x| JsString::from(hex::encode(x)))
+			.collect();
+		inputs.unchecked_into()
+	}
+}
+
+#[allow(clippy::unused_unit)]
+#[wasm_bindgen(js_class = CircomProvingKey)]
+impl CircomProvingKey {
+	/// Parse the `.zkey` snarkjs exports, parse it once and prove any number
+	/// of witnesses with it
+	#[wasm_bindgen(js_name = fromZkey)]
+	pub fn js_from_zkey(zkey: Uint8Array) -> Result<CircomProvingKey, JsValue> {
+		Ok(CircomProvingKey::from_zkey(&zkey.to_vec())?)
+	}
+
+	#[wasm_bindgen(getter, js_name = numPublicInputs)]
+	pub fn js_num_public_inputs(&self) -> usize {
+		self.num_public
+	}
+
+	/// Prove with the `.wtns` file of the circuit's witness calculator
+	#[wasm_bindgen(js_name = prove)]
+	pub fn js_prove(&self, wtns: Uint8Array) -> Result<CircomProof, JsValue> {
+		let witness = parse_wtns(&wtns.to_vec())?;
+		Ok(self.prove(&witness, &mut OsRng)?)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use ark_bn254::FrParameters;
+	use ark_ff::Field;
+	use ark_groth16::Proof;
+	use wasm_bindgen_test::*;
+
+	use super::*;
+	use crate::memory::memory_stats;
+	use crate::proof::witness::Witness;
+
+	// `x * x = x2, x2 * x = out` over the signals `[1, out, x, x2]`
+	const NUM_PUBLIC: usize = 1;
+	const NUM_VARS: usize = 4;
+	const DOMAIN_SIZE: usize = 4;
+
+	fn constraints() -> Vec<[Row; 3]> {
+		let one = Fr::one();
+		vec![[vec![(one, 2)], vec![(one, 2)], vec![(one, 3)]], [
+			vec![(one, 3)],
+			vec![(one, 2)],
+			vec![(one, 1)],
+		]]
+	}
+
+	fn witness(x: u64) -> Vec<Fr> {
+		let x = Fr::from(x);
+		vec![Fr::one(), x * x * x, x, x * x]
+	}
+
+	fn fr_bytes(value: Fr) -> Vec<u8> {
+		value.0.to_bytes_le()
+	}
+
+	fn fq_bytes(value: Fq) -> Vec<u8> {
+		value.0.to_bytes_le()
+	}
+
+	fn g1_bytes(point: G1Affine) -> Vec<u8> {
+		if point.infinity {
+			return vec![0; 64];
+		}
+		[fq_bytes(point.x), fq_bytes(point.y)].concat()
+	}
+
+	fn g2_bytes(point: G2Affine) -> Vec<u8> {
+		if point.infinity {
+			return vec![0; 128];
+		}
+		[point.x.c0, point.x.c1, point.y.c0, point.y.c1]
+			.into_iter()
+			.flat_map(fq_bytes)
+			.collect()
+	}
+
+	fn binary_file(magic: &[u8; 4], sections: Vec<(u32, Vec<u8>)>) -> Vec<u8> {
+		let mut bytes = magic.to_vec();
+		bytes.extend(1u32.to_le_bytes());
+		bytes.extend((sections.len() as u32).to_le_bytes());
+		for (id, section) in sections {
+			bytes.extend(id.to_le_bytes());
+			bytes.extend((section.len() as u64).to_le_bytes());
+			bytes.extend(section);
+		}
+		bytes
+	}
+
+	/// The zkey snarkjs would output for the circuit, with a fresh setup. The
+	/// `H` points are the Lagrange basis of twice the domain on its odd powers,
+	/// as read from the powers of tau, and the public signals get a
+	/// `signal * 1 = 0` constraint each
+	fn zkey() -> (Vec<u8>, VerifyingKey<Bn254>) {
+		let [tau, alpha, beta, gamma, delta] = [(); 5].map(|_| Fr::rand(&mut OsRng));
+		let g1 = G1Affine::prime_subgroup_generator();
+		let g2 = G2Affine::prime_subgroup_generator();
+		let g1_mul = |x: Fr| g1.mul(x).into_affine();
+		let g2_mul = |x: Fr| g2.mul(x).into_affine();
+
+		let mut rows = constraints();
+		let num_constraints = rows.len();
+		for signal in 0..=NUM_PUBLIC {
+			rows.push([vec![(Fr::one(), signal)], vec![], vec![]]);
+		}
+		let lagrange = Radix2EvaluationDomain::<Fr>::new(DOMAIN_SIZE)
+			.unwrap()
+			.evaluate_all_lagrange_coefficients(tau);
+		let mut polys = vec![[Fr::zero(); 3]; NUM_VARS];
+		for (row, l) in rows.iter().zip(&lagrange) {
+			for (matrix, terms) in row.iter().enumerate() {
+				for (coeff, signal) in terms {
+					polys[*signal][matrix] += *coeff * l;
+				}
+			}
+		}
+		let lc = |[a, b, c]: [Fr; 3]| beta * a + alpha * b + c;
+
+		let r = Fr::from_repr(FrParameters::R).unwrap();
+		let mut coeffs = Vec::new();
+		let mut num_coeffs = 0u32;
+		for (constraint, row) in rows.iter().enumerate() {
+			for (matrix, terms) in row[..2].iter().enumerate() {
+				for (coeff, signal) in terms {
+					coeffs.extend((matrix as u32).to_le_bytes());
+					coeffs.extend((constraint as u32).to_le_bytes());
+					coeffs.extend((*signal as u32).to_le_bytes());
+					coeffs.extend(fr_bytes(*coeff * r));
+					num_coeffs += 1;
+				}
+			}
+		}
+		assert_eq!(rows.len(), num_constraints + NUM_PUBLIC + 1);
+
+		let mut header = Vec::new();
+		header.extend(32u32.to_le_bytes());
+		header.extend(<Fq as PrimeField>::Params::MODULUS.to_bytes_le());
+		header.extend(32u32.to_le_bytes());
+		header.extend(FrParameters::MODULUS.to_bytes_le());
+		for value in [NUM_VARS, NUM_PUBLIC, DOMAIN_SIZE] {
+			header.extend((value as u32).to_le_bytes());
+		}
+		header.extend(g1_bytes(g1_mul(alpha)));
+		header.extend(g1_bytes(g1_mul(beta)));
+		header.extend(g2_bytes(g2_mul(beta)));
+		header.extend(g2_bytes(g2_mul(gamma)));
+		header.extend(g1_bytes(g1_mul(delta)));
+		header.extend(g2_bytes(g2_mul(delta)));
+
+		let ic: Vec<G1Affine> = polys[..=NUM_PUBLIC]
+			.iter()
+			.map(|p| g1_mul(lc(*p) * gamma.inverse().unwrap()))
+			.collect();
+		let l_query = polys[NUM_PUBLIC + 1..]
+			.iter()
+			.map(|p| g1_mul(lc(*p) * delta.inverse().unwrap()));
+		let h_query = Radix2EvaluationDomain::<Fr>::new(2 * DOMAIN_SIZE)
+			.unwrap()
+			.evaluate_all_lagrange_coefficients(tau)
+			.into_iter()
+			.skip(1)
+			.step_by(2)
+			.map(|l| g1_mul(l * delta.inverse().unwrap()));
+		let sections = vec![
+			(ZKEY_HEADER_SECTION, ZKEY_GROTH16.to_le_bytes().to_vec()),
+			(ZKEY_GROTH16_HEADER_SECTION, header),
+			(ZKEY_IC_SECTION, ic.iter().copied().flat_map(g1_bytes).collect()),
+			(
+				ZKEY_COEFFS_SECTION,
+				[num_coeffs.to_le_bytes().to_vec(), coeffs].concat(),
+			),
+			(
+				ZKEY_A_SECTION,
+				polys.iter().flat_map(|p| g1_bytes(g1_mul(p[0]))).collect(),
+			),
+			(
+				ZKEY_B1_SECTION,
+				polys.iter().flat_map(|p| g1_bytes(g1_mul(p[1]))).collect(),
+			),
+			(
+				ZKEY_B2_SECTION,
+				polys.iter().flat_map(|p| g2_bytes(g2_mul(p[1]))).collect(),
+			),
+			(ZKEY_C_SECTION, l_query.flat_map(g1_bytes).collect()),
+			(ZKEY_H_SECTION, h_query.flat_map(g1_bytes).collect()),
+		];
+		let vk = VerifyingKey {
+			alpha_g1: g1_mul(alpha),
+			beta_g2: g2_mul(beta),
+			gamma_g2: g2_mul(gamma),
+			delta_g2: g2_mul(delta),
+			gamma_abc_g1: ic,
+		};
+		(binary_file(ZKEY_MAGIC, sections), vk)
+	}
+
+	fn wtns(witness: &[Fr]) -> Vec<u8> {
+		Witness {
+			modulus: FrParameters::MODULUS.to_bytes_le(),
+			num_instance: NUM_PUBLIC + 1,
+			assignment: witness.iter().map(|x| x.into_repr().to_bytes_le()).collect(),
+			num_constraints: 2,
+		}
+		.to_wtns()
+	}
+
+	#[wasm_bindgen_test]
+	fn should_prove_with_a_zkey_and_wtns() {
+		let (zkey, vk) = zkey();
+		let key = CircomProvingKey::from_zkey(&zkey).unwrap();
+		assert_eq!(key.num_public, NUM_PUBLIC);
+		assert_eq!(key.a.len(), constraints().len());
+
+		let witness = parse_wtns(&wtns(&witness(3))).unwrap();
+		assert_eq!(witness, self::witness(3));
+		let proof = key.prove(&witness, &mut OsRng).unwrap();
+		assert_eq!(proof.public_inputs, vec![Fr::from(27u64).into_repr().to_bytes_be()]);
+
+		let groth16_proof: &Proof<Bn254> = match &proof.proof.inner {
+			Groth16ProofInner::Bn254(proof) => proof,
+			Groth16ProofInner::Bls381(_) => unreachable!(),
+		};
+		let pvk = prepare_verifying_key(&vk);
+		assert!(verify_proof(&pvk, groth16_proof, &[Fr::from(27u64)]).unwrap());
+		assert!(!verify_proof(&pvk, groth16_proof, &[Fr::from(28u64)]).unwrap());
+		assert!(proof.proof.to_evm_bytes().is_ok());
+	}
+
+	#[wasm_bindgen_test]
+	fn should_count_the_key_in_memory_stats() {
+		let (zkey, _) = zkey();
+		let before = memory_stats();
+		let key = CircomProvingKey::from_zkey(&zkey).unwrap();
+		let stats = memory_stats();
+		assert_eq!(stats.proving_keys, before.proving_keys + 1);
+		assert_eq!(stats.proving_key_bytes, before.proving_key_bytes + zkey.len());
+
+		drop(key);
+		let after = memory_stats();
+		assert_eq!(after.proving_keys, before.proving_keys);
+		assert_eq!(after.proving_key_bytes, before.proving_key_bytes);
+	}
+
+	#[wasm_bindgen_test]
+	fn should_reject_a_witness_that_isnt_the_circuits() {
+		let (zkey, _) = zkey();
+		let key = CircomProvingKey::from_zkey(&zkey).unwrap();
+
+		let mut unsatisfied = witness(3);
+		unsatisfied[1] += Fr::one();
+		let error = key.prove(&unsatisfied, &mut OsRng).err().unwrap();
+		assert_eq!(error.code, OpStatusCode::WitnessGenerationFailed);
+
+		let error = key.prove(&witness(3)[..3], &mut OsRng).err().unwrap();
+		assert_eq!(error.code, OpStatusCode::WitnessGenerationFailed);
+
+		let mut wtns = wtns(&witness(3));
+		let last = wtns.len() - 1;
+		wtns[last] = 0xff;
+		assert_eq!(
+			parse_wtns(&wtns).err().unwrap().code,
+			OpStatusCode::WitnessGenerationFailed
+		);
+	}
+
+	#[wasm_bindgen_test]
+	fn should_reject_an_invalid_zkey() {
+		let (zkey, _) = zkey();
+		for invalid in [&zkey[..zkey.len() - 1], &zkey[4..], b"wtns".as_ref()] {
+			let error = CircomProvingKey::from_zkey(invalid).err().unwrap();
+			assert_eq!(error.code, OpStatusCode::InvalidProvingKey);
+		}
+	}
+}
@@ -0,0 +1,261 @@
+//! Proof input builder from one plain object instead of a dozen setter
+//! calls. Every field is read and checked in one pass and all the problems
+//! come back together, rather than the first failing setter hiding the rest.
+use js_sys::Array;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::address::parse_evm_address;
+use crate::note::amount::{amount_field_error, parse_amount_field, parse_signed_amount};
+use crate::note::JsNote;
+use crate::proof::{JsProofInputBuilder, ProofInputBuilder};
+use crate::types::{parse_chain_id, NoteProtocol, OpStatusCode, OperationError, OperationErrorArray};
+
+/// Fields of the proof input, named as the setters they replace. Byte
+/// fields are hex with or without `0x`, amounts are read as `setFee` reads
+/// them
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ProofInputConfig {
+	pub protocol: String,
+	/// Serialized note whose curve, width, chain and secrets are used, as
+	/// with `setNote`
+	pub note: Option<String>,
+	pub leaves: Option<Vec<String>>,
+	pub leaf_index: Option<u64>,
+	#[serde(default)]
+	pub discover_leaf_index: bool,
+	pub roots: Option<Vec<String>>,
+	/// `0x` prefixed EVM address, checked against its EIP-55 checksum, or
+	/// the hex of the account bytes
+	pub recipient: Option<String>,
+	pub relayer: Option<String>,
+	pub fee: Option<String>,
+	pub refund: Option<String>,
+	/// Read in the native token of the target chain like `setMaxRefund`
+	pub max_refund: Option<String>,
+	/// Decimals of the token `fee`, `refund` and `publicAmount` are given in
+	pub denomination: Option<u8>,
+	#[serde(default)]
+	pub native_token_pool: bool,
+	pub tree_depth: Option<usize>,
+	pub pk: Option<String>,
+	pub chain_id: Option<String>,
+	pub public_amount: Option<String>,
+	pub ext_data_hash: Option<String>,
+}
+
+fn decode_hex(value: &str, code: OpStatusCode) -> Result<Vec<u8>, OperationError> {
+	let digits = value.strip_prefix("0x").unwrap_or(value);
+	hex::decode(digits).map_err(|e| OperationError::new_with_message(code, e.to_string()))
+}
+
+/// EVM addresses are told apart from other accounts by their `0x` and length
+fn is_evm_address(value: &str) -> bool {
+	value.starts_with("0x") && value.len() == 42
+}
+
+/// Names the config field an error comes from, in its message and as the
+/// `field` of its data unless the error already has data
+fn in_field(field: &str, mut error: OperationError) -> OperationError {
+	error.error_message = format!("`{}`: {}", field, error.error_message);
+	if error.data.is_none() {
+		error.data = Some(format!("{{\"field\":\"{}\"}}", field));
+	}
+	error
+}
+
+impl ProofInputConfig {
+	/// Builder with every field of the config set. The errors are those of
+	/// each field that fails to parse or doesn't apply to the protocol,
+	/// followed for the mixer by the `validate` errors of the resulting
+	/// builder that no field already reported. VAnchor builders still take
+	/// their UTXOs from `setInputUtxos` and `setOutputUtxos` and aren't
+	/// validated here
+	pub fn to_builder(&self) -> Result<JsProofInputBuilder, Vec<OperationError>> {
+		let protocol: NoteProtocol = self.protocol.parse().map_err(|_| {
+			let message = format!("Unknown protocol {:?}", self.protocol);
+			vec![in_field(
+				"protocol",
+				OperationError::new_with_message(OpStatusCode::InvalidNoteProtocol, message),
+			)]
+		})?;
+		let mut builder = JsProofInputBuilder {
+			inner: match protocol {
+				NoteProtocol::Mixer => ProofInputBuilder::Mixer(Default::default()),
+				NoteProtocol::VAnchor => ProofInputBuilder::VAnchor(Default::default()),
+			},
+			partial_withdrawal: None,
+		};
+		let mut errors = Vec::new();
+		let mut check = |field: &str, result: Result<(), OperationError>| {
+			if let Err(error) = result {
+				errors.push(in_field(field, error));
+			}
+		};
+
+		// First, the chain of the note gives the native decimals of `maxRefund`
+		if let Some(note) = &self.note {
+			check(
+				"note",
+				JsNote::deserialize(note).and_then(|note| builder.apply_note(&note)),
+			);
+		}
+		if let Some(leaves) = &self.leaves {
+			let leaves = leaves
+				.iter()
+				.map(|leaf| decode_hex(leaf, OpStatusCode::InvalidLeaves))
+				.collect::<Result<Vec<_>, _>>();
+			check("leaves", leaves.and_then(|leaves| builder.inner.leaves_list(leaves)));
+		}
+		if let Some(leaf_index) = self.leaf_index {
+			check("leafIndex", builder.inner.leaf_index(leaf_index));
+		}
+		if self.discover_leaf_index {
+			check("discoverLeafIndex", builder.inner.discover_leaf_index());
+		}
+		if let Some(roots) = &self.roots {
+			let roots = roots
+				.iter()
+				.map(|root| decode_hex(root, OpStatusCode::InvalidRoots))
+				.collect::<Result<Vec<_>, _>>();
+			check("roots", roots.and_then(|roots| builder.inner.roots(roots)));
+		}
+		if let Some(recipient) = &self.recipient {
+			let result = if is_evm_address(recipient) {
+				parse_evm_address(recipient).and_then(|address| builder.inner.recipient_evm(address))
+			} else {
+				decode_hex(recipient, OpStatusCode::InvalidRecipient)
+					.and_then(|recipient| builder.inner.recipient(recipient))
+			};
+			check("recipient", result);
+		}
+		if let Some(relayer) = &self.relayer {
+			let result = if is_evm_address(relayer) {
+				parse_evm_address(relayer).and_then(|address| builder.inner.relayer_evm(address))
+			} else {
+				decode_hex(relayer, OpStatusCode::InvalidRelayer).and_then(|relayer| builder.inner.relayer(relayer))
+			};
+			check("relayer", result);
+		}
+		if let Some(fee) = &self.fee {
+			let fee = parse_amount_field(fee, self.denomination, OpStatusCode::InvalidFee);
+			check("fee", fee.and_then(|fee| builder.inner.fee(fee)));
+		}
+		if let Some(refund) = &self.refund {
+			let refund = parse_amount_field(refund, self.denomination, OpStatusCode::InvalidRefund);
+			check("refund", refund.and_then(|refund| builder.inner.refund(refund)));
+		}
+		if let Some(max_refund) = &self.max_refund {
+			let decimals = builder.inner.native_decimals();
+			let max_refund = parse_amount_field(max_refund, decimals, OpStatusCode::InvalidRefund);
+			check(
+				"maxRefund",
+				max_refund.and_then(|max_refund| builder.inner.max_refund(max_refund)),
+			);
+		}
+		if self.native_token_pool {
+			check("nativeTokenPool", builder.inner.native_token_pool());
+		}
+		if let Some(tree_depth) = self.tree_depth {
+			check("treeDepth", builder.inner.tree_depth(tree_depth));
+		}
+		if let Some(pk) = &self.pk {
+			let pk = decode_hex(pk, OpStatusCode::InvalidProvingKey);
+			check("pk", pk.and_then(|pk| builder.inner.pk(pk)));
+		}
+		if let Some(chain_id) = &self.chain_id {
+			let chain_id = parse_chain_id(chain_id);
+			check(
+				"chainId",
+				chain_id.and_then(|chain_id| builder.inner.chain_id(u128::from(chain_id))),
+			);
+		}
+		if let Some(public_amount) = &self.public_amount {
+			let public_amount = parse_signed_amount(public_amount, self.denomination)
+				.map_err(amount_field_error(OpStatusCode::InvalidPublicAmount));
+			check(
+				"publicAmount",
+				public_amount.and_then(|public_amount| builder.inner.public_amount(public_amount)),
+			);
+		}
+		if let Some(ext_data_hash) = &self.ext_data_hash {
+			let ext_data_hash = decode_hex(ext_data_hash, OpStatusCode::InvalidExtDataHash);
+			check(
+				"extDataHash",
+				ext_data_hash.and_then(|ext_data_hash| builder.inner.ext_data_hash(ext_data_hash)),
+			);
+		}
+
+		if let ProofInputBuilder::Mixer(_) = builder.inner {
+			// A field that failed to parse is also reported missing, once is enough
+			let reported: Vec<OpStatusCode> = errors.iter().map(|error| error.code.clone()).collect();
+			errors.extend(
+				builder
+					.inner
+					.validate()
+					.into_iter()
+					.filter(|error| !reported.contains(&error.code)),
+			);
+		}
+		if errors.is_empty() {
+			Ok(builder)
+		} else {
+			Err(errors)
+		}
+	}
+}
+
+#[wasm_bindgen(typescript_custom_section)]
+const PROOF_INPUT_CONFIG: &str = r#"
+export interface ProofInputConfig {
+	protocol: Protocol;
+	note?: string;
+	leaves?: string[];
+	leafIndex?: number;
+	discoverLeafIndex?: boolean;
+	roots?: string[];
+	recipient?: string;
+	relayer?: string;
+	fee?: string;
+	refund?: string;
+	maxRefund?: string;
+	denomination?: number;
+	nativeTokenPool?: boolean;
+	treeDepth?: number;
+	pk?: string;
+	chainId?: string;
+	publicAmount?: string;
+	extDataHash?: string;
+}
+"#;
+
+#[wasm_bindgen]
+extern "C" {
+	#[wasm_bindgen(typescript_type = "ProofInputConfig")]
+	pub type WasmProofInputConfig;
+}
+
+fn error_array(errors: Vec<OperationError>) -> JsValue {
+	let errors: Array = errors.into_iter().map(JsValue::from).collect();
+	errors.unchecked_into::<OperationErrorArray>().into()
+}
+
+#[allow(clippy::unused_unit)]
+#[wasm_bindgen]
+impl JsProofInputBuilder {
+	/// Builder from a `ProofInputConfig` object, an alternative to calling
+	/// each setter. Throws an array of `OperationError`s, one per invalid or
+	/// missing field, unknown fields included
+	#[wasm_bindgen(js_name = fromObject)]
+	pub fn from_object(config: WasmProofInputConfig) -> Result<JsProofInputBuilder, JsValue> {
+		let config: ProofInputConfig = JsValue::from(config).into_serde().map_err(|e| {
+			error_array(vec![OperationError::new_with_message(
+				OpStatusCode::DeserializationFailed,
+				e.to_string(),
+			)])
+		})?;
+		config.to_builder().map_err(error_array)
+	}
+}
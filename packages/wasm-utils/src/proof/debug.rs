@@ -0,0 +1,169 @@
+//! Debug bundle of the last proof generated with `generate_proof_js`, for bug
+//! reports. It holds the parameters, the public part of the inputs, the
+//! versions, how long proving took and its outcome. Secrets, amounts of the
+//! UTXOs and the RNG seed are redacted, and the proving key is only
+//! referred to by its hash. The bundle is a single line of JSON.
+use core::cell::RefCell;
+use std::collections::BTreeMap;
+
+use blake2::{Blake2b, Digest};
+use js_sys::Uint8Array;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use wasm_bindgen::prelude::*;
+
+use crate::init::ABI_VERSION;
+use crate::proof::{ProofInput, ProofOutput};
+use crate::types::{OpStatusCode, OperationError};
+
+pub const DEBUG_BUNDLE_FORMAT: u32 = 1;
+const REDACTED: &str = "<redacted>";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DebugBundle {
+	pub format: u32,
+	pub version: String,
+	pub abi_version: u32,
+	pub protocol: String,
+	pub parameters: Value,
+	pub inputs: Value,
+	/// Blake2b hash of the proving key
+	pub pk_hash: String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub prove_ms: Option<f64>,
+	/// Public inputs of the proof, hex encoded
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub public_inputs: Option<Vec<String>>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub error: Option<String>,
+}
+
+thread_local! {
+	static LAST_BUNDLE: RefCell<Option<DebugBundle>> = RefCell::new(None);
+}
+
+pub(crate) fn pk_hash(pk: &[u8]) -> String {
+	hex::encode(Blake2b::digest(pk))
+}
+
+fn hex_list(values: &[Vec<u8>]) -> Vec<String> {
+	values.iter().map(hex::encode).collect()
+}
+
+impl DebugBundle {
+	/// Bundle of `input` before it is proven, without timings or outcome
+	pub fn new(input: &ProofInput) -> Self {
+		let (protocol, parameters, inputs, pk) = match input {
+			ProofInput::Mixer(input) => (
+				"mixer",
+				json!({
+					"curve": input.curve.to_string(),
+					"width": input.width,
+					"exponentiation": input.exponentiation,
+					"backend": input.backend.to_string(),
+					"treeDepth": input.tree_depth,
+					"chainId": input.chain_id.to_string(),
+				}),
+				json!({
+					"secret": REDACTED,
+					"nullifier": REDACTED,
+					"recipient": hex::encode(&input.recipient),
+					"relayer": hex::encode(&input.relayer),
+					"fee": input.fee.to_string(),
+					"refund": input.refund.to_string(),
+					"leaves": hex_list(&input.leaves),
+					"leafIndex": input.leaf_index,
+					"rngSeed": input.rng_seed.map(|_| REDACTED),
+				}),
+				&input.pk,
+			),
+			ProofInput::VAnchor(input) => {
+				let utxos = |utxos: &[crate::utxo::JsUtxo]| -> Vec<Value> {
+					utxos
+						.iter()
+						.map(|utxo| {
+							json!({
+								"commitment": hex::encode(utxo.get_commitment()),
+								"chainId": utxo.get_chain_id_raw().to_string(),
+								"index": utxo.get_index(),
+								"amount": REDACTED,
+							})
+						})
+						.collect()
+				};
+				let leaves: BTreeMap<String, Vec<String>> = input
+					.leaves
+					.iter()
+					.map(|(chain_id, leaves)| (chain_id.to_string(), hex_list(leaves)))
+					.collect();
+				(
+					"vanchor",
+					json!({
+						"curve": input.curve.to_string(),
+						"width": input.width,
+						"exponentiation": input.exponentiation,
+						"backend": input.backend.to_string(),
+						"treeDepth": input.tree_depth,
+						"chainId": input.chain_id.to_string(),
+					}),
+					json!({
+						"inputUtxos": utxos(&input.input_utxos),
+						"outputUtxos": utxos(&input.output_utxos),
+						"indices": input.indices,
+						"leaves": leaves,
+						"roots": hex_list(&input.roots),
+						"extDataHash": hex::encode(&input.ext_data_hash),
+						"publicAmount": input.public_amount.to_string(),
+						"rngSeed": input.rng_seed.map(|_| REDACTED),
+					}),
+					&input.pk,
+				)
+			}
+		};
+		DebugBundle {
+			format: DEBUG_BUNDLE_FORMAT,
+			version: env!("CARGO_PKG_VERSION").to_string(),
+			abi_version: ABI_VERSION,
+			protocol: protocol.to_string(),
+			parameters,
+			inputs,
+			pk_hash: pk_hash(pk),
+			prove_ms: None,
+			public_inputs: None,
+			error: None,
+		}
+	}
+
+	/// Record how proving went
+	pub fn finish(&mut self, prove_ms: f64, outcome: &Result<ProofOutput, OperationError>) {
+		self.prove_ms = Some(prove_ms);
+		match outcome {
+			Ok(output) => self.public_inputs = Some(hex_list(output.public_inputs())),
+			Err(e) => self.error = Some(format!("{:?}: {}", e.code, e.error_message)),
+		}
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, OperationError> {
+		serde_json::to_vec(self)
+			.map_err(|e| OperationError::new_with_message(OpStatusCode::SerializationFailed, e.to_string()))
+	}
+}
+
+pub fn record_last_bundle(bundle: DebugBundle) {
+	LAST_BUNDLE.with(|last| *last.borrow_mut() = Some(bundle));
+}
+
+pub fn last_bundle() -> Option<DebugBundle> {
+	LAST_BUNDLE.with(|last| last.borrow().clone())
+}
+
+/// Debug bundle of the last proof generated by `generate_proof_js`, as UTF-8
+/// JSON to attach to bug reports. `undefined` before the first proof.
+#[wasm_bindgen(js_name = exportDebugBundle)]
+pub fn export_debug_bundle() -> Result<Option<Uint8Array>, JsValue> {
+	match last_bundle() {
+		Some(bundle) => Ok(Some(Uint8Array::from(bundle.to_bytes()?.as_slice()))),
+		None => Ok(None),
+	}
+}
@@ -0,0 +1,240 @@
+//! Delegating the Groth16 step to a remote prover, for devices too slow to
+//! prove themselves. The witness is computed locally and sent in a request
+//! boxed (x25519-xsalsa20-poly1305) with the device's key to the prover's key:
+//! the witness holds the note secrets so only the prover can read it, and the
+//! box authenticates the device to the prover. The proving key isn't sent,
+//! only its hash so the prover picks the matching one.
+//!
+//! The returned proof isn't trusted, it is accepted only if it verifies
+//! against the verifying key and the public inputs of the pending request.
+use ark_bls12_381::Bls12_381;
+use ark_bn254::{Bn254, Fr as Bn254Fr};
+use ark_ff::{BigInteger, PrimeField};
+use arkworks_setups::common::verify_unchecked_raw;
+use crypto_box::aead::Aead;
+use crypto_box::{generate_nonce, PublicKey, SalsaBox, SecretKey, KEY_SIZE};
+use js_sys::Uint8Array;
+use rand::rngs::OsRng;
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::crypto::NONCE_LENGTH;
+use crate::proof::debug::pk_hash;
+use crate::proof::mixer::MixerProof;
+use crate::proof::vanchor::VAnchorProof;
+use crate::proof::witness::create_witness;
+use crate::proof::{JsProofInput, JsProofOutput, ProofInput, ProofOutput};
+use crate::types::{Curve, OpStatusCode, OperationError};
+
+pub const DELEGATED_REQUEST_FORMAT: u32 = 1;
+
+/// What the prover gets, once unboxed. Field elements are hex encoded, big
+/// endian for the public inputs
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DelegatedRequest {
+	pub format: u32,
+	/// Echoed back in the response
+	pub id: String,
+	pub protocol: String,
+	pub curve: String,
+	/// Blake2b hash of the proving key to prove with
+	pub pk_hash: String,
+	pub public_inputs: Vec<String>,
+	/// The assignment in the iden3 `.wtns` format
+	pub witness: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DelegatedResponse {
+	pub id: String,
+	/// Serialized Groth16 proof, hex encoded
+	pub proof: String,
+}
+
+#[derive(Debug, Clone)]
+struct PendingRequest {
+	id: String,
+	input: ProofInput,
+	public_inputs: Vec<Vec<u8>>,
+}
+
+fn invalid_proof(message: String) -> OperationError {
+	OperationError::new_with_message(OpStatusCode::InvalidDelegatedProof, message)
+}
+
+#[wasm_bindgen]
+pub struct DelegatedProver {
+	#[wasm_bindgen(skip)]
+	pub vk: Vec<u8>,
+	#[wasm_bindgen(skip)]
+	pub prover_key: PublicKey,
+	#[wasm_bindgen(skip)]
+	pub device_key: SecretKey,
+	pending: Option<PendingRequest>,
+}
+
+impl DelegatedProver {
+	/// Delegate to the prover with x25519 public key `prover_key`, checking
+	/// the proofs with `vk`. `device_key` is the x25519 secret key the prover
+	/// knows this device by
+	pub fn new(vk: Vec<u8>, prover_key: [u8; KEY_SIZE], device_key: SecretKey) -> Self {
+		Self {
+			vk,
+			prover_key: PublicKey::from(prover_key),
+			device_key,
+			pending: None,
+		}
+	}
+
+	/// Boxed request to prove `input`: `nonce (24) || device public key (32)
+	/// || ciphertext`. Replaces the pending request, if any
+	pub fn request<R: RngCore + CryptoRng>(
+		&mut self,
+		input: ProofInput,
+		rng: &mut R,
+	) -> Result<Vec<u8>, OperationError> {
+		let witness = create_witness(input.clone())?;
+		let (protocol, curve, pk) = match &input {
+			ProofInput::Mixer(input) => ("mixer", input.curve, &input.pk),
+			ProofInput::VAnchor(input) => ("vanchor", input.curve, &input.pk),
+		};
+		let mut id = [0u8; 16];
+		rng.fill_bytes(&mut id);
+		let request = DelegatedRequest {
+			format: DELEGATED_REQUEST_FORMAT,
+			id: hex::encode(id),
+			protocol: protocol.to_string(),
+			curve: curve.to_string(),
+			pk_hash: pk_hash(pk),
+			public_inputs: witness.public_inputs().iter().map(hex::encode).collect(),
+			witness: hex::encode(witness.to_wtns()),
+		};
+		let plaintext = serde_json::to_vec(&request)
+			.map_err(|e| OperationError::new_with_message(OpStatusCode::SerializationFailed, e.to_string()))?;
+
+		let nonce = generate_nonce(rng);
+		let ciphertext = SalsaBox::new(&self.prover_key, &self.device_key)
+			.encrypt(&nonce, plaintext.as_slice())
+			.map_err(|_| OperationError::from(OpStatusCode::SerializationFailed))?;
+		let mut boxed = Vec::with_capacity(NONCE_LENGTH + KEY_SIZE + ciphertext.len());
+		boxed.extend_from_slice(&nonce);
+		boxed.extend_from_slice(self.device_key.public_key().as_bytes());
+		boxed.extend_from_slice(&ciphertext);
+
+		self.pending = Some(PendingRequest {
+			id: request.id,
+			input,
+			public_inputs: witness.public_inputs(),
+		});
+		Ok(boxed)
+	}
+
+	/// Check the proof of the pending request and turn it into the output
+	/// `generate_proof_js` would have given. The request stays pending if
+	/// the response is rejected
+	pub fn accept(&mut self, response: &DelegatedResponse) -> Result<ProofOutput, OperationError> {
+		let pending = self
+			.pending
+			.as_ref()
+			.ok_or_else(|| invalid_proof("No request is pending".to_string()))?;
+		if response.id != pending.id {
+			return Err(invalid_proof(format!(
+				"The response is for request {}, not {}",
+				response.id, pending.id
+			)));
+		}
+		let proof = hex::decode(&response.proof).map_err(|e| invalid_proof(e.to_string()))?;
+		let curve = match &pending.input {
+			ProofInput::Mixer(input) => input.curve,
+			ProofInput::VAnchor(input) => input.curve,
+		};
+		let verified = match curve {
+			Curve::Bn254 => verify_unchecked_raw::<Bn254>(&pending.public_inputs, &self.vk, &proof),
+			Curve::Bls381 => verify_unchecked_raw::<Bls12_381>(&pending.public_inputs, &self.vk, &proof),
+		}
+		.map_err(|e| invalid_proof(e.to_string()))?;
+		if !verified {
+			let mut error = OperationError::new_with_message(
+				OpStatusCode::ProofVerificationFailed,
+				"The proof doesn't verify".to_string(),
+			);
+			error.data = Some(format!("{{\"id\":\"{}\"}}", pending.id));
+			return Err(error);
+		}
+
+		let PendingRequest {
+			input, public_inputs, ..
+		} = self.pending.take().expect("checked above");
+		Ok(match input {
+			ProofInput::Mixer(input) => ProofOutput::Mixer(MixerProof {
+				proof,
+				nullifier_hash: public_inputs[0].clone(),
+				root: public_inputs[1].clone(),
+				leaf: input.leaves[input.leaf_index as usize].clone(),
+				public_inputs,
+			}),
+			ProofInput::VAnchor(input) => ProofOutput::VAnchor(VAnchorProof {
+				proof,
+				public_inputs,
+				output_utxos: input.output_utxos.to_vec(),
+				input_utxos: input.input_utxos,
+				public_amount: Bn254Fr::from(input.public_amount)
+					.into_repr()
+					.to_bytes_be()
+					.try_into()
+					.expect("field elements are 32 bytes"),
+			}),
+		})
+	}
+}
+
+#[wasm_bindgen]
+impl DelegatedProver {
+	/// Delegate to the prover with the x25519 public key `proverKey`,
+	/// verifying its proofs with `vk`. `deviceKey` is the x25519 secret key
+	/// the prover knows this device by, a new one is generated if not given
+	#[wasm_bindgen(constructor)]
+	pub fn js_new(
+		vk: Uint8Array,
+		prover_key: Uint8Array,
+		device_key: Option<Uint8Array>,
+	) -> Result<DelegatedProver, JsValue> {
+		let key = |key: Uint8Array| -> Result<[u8; KEY_SIZE], OperationError> {
+			key.to_vec().try_into().map_err(|_| {
+				let message = format!("Keys should be {} bytes", KEY_SIZE);
+				OperationError::new_with_message(OpStatusCode::InvalidArrayLength, message)
+			})
+		};
+		let device_key = match device_key {
+			Some(device_key) => SecretKey::from(key(device_key)?),
+			None => SecretKey::generate(&mut OsRng),
+		};
+		Ok(DelegatedProver::new(vk.to_vec(), key(prover_key)?, device_key))
+	}
+
+	/// x25519 public key of the device, to register with the prover
+	#[wasm_bindgen(getter, js_name = devicePublicKey)]
+	pub fn device_public_key(&self) -> Uint8Array {
+		Uint8Array::from(self.device_key.public_key().as_bytes().as_ref())
+	}
+
+	/// Boxed proving request for `proof_input`, to send to the prover
+	#[wasm_bindgen(js_name = createRequest)]
+	pub fn js_create_request(&mut self, proof_input: JsProofInput) -> Result<Uint8Array, JsValue> {
+		let request = self.request(proof_input.inner, &mut OsRng)?;
+		Ok(Uint8Array::from(request.as_slice()))
+	}
+
+	/// Accept the prover's `{ id, proof }` response once the proof verifies
+	#[wasm_bindgen(js_name = acceptProof)]
+	pub fn js_accept_proof(&mut self, response: JsValue) -> Result<JsProofOutput, JsValue> {
+		let response: DelegatedResponse = response
+			.into_serde()
+			.map_err(|e| OperationError::new_with_message(OpStatusCode::DeserializationFailed, e.to_string()))?;
+		let inner = self.accept(&response)?;
+		Ok(JsProofOutput::new(inner))
+	}
+}
@@ -1,13 +1,18 @@
 use ark_bn254::Fr as Bn254Fr;
 use ark_ff::{BigInteger, PrimeField};
-use ethabi::{encode, Token};
+use ethabi::{encode, Address, Int, Token, Uint};
 use js_sys::{JsString, Uint8Array};
 use parity_scale_codec::{Decode, Encode};
 use tiny_keccak::{Hasher, Keccak};
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsValue;
 
-#[derive(Encode, Decode, Default)]
+use crate::note::amount::{parse_amount_field, parse_signed_amount};
+use crate::types::{ExtDataEncoding, OpStatusCode, OperationError, WasmExtDataEncoding};
+
+/// The `ExtData` of a VAnchor transaction, hashed into the `extDataHash`
+/// public input and sent along with the proof
+#[derive(Encode, Decode, Default, Debug, Clone, PartialEq, Eq)]
 #[wasm_bindgen]
 pub struct ExtData {
 	#[wasm_bindgen(skip)]
@@ -29,6 +34,8 @@ pub struct ExtData {
 }
 #[wasm_bindgen]
 impl ExtData {
+	/// Amounts are in base units, decimal or `0x` hex, the `extAmount` is
+	/// negative for withdrawals
 	#[wasm_bindgen(constructor)]
 	#[allow(clippy::too_many_arguments)]
 	pub fn new(
@@ -40,38 +47,244 @@ impl ExtData {
 		token: Uint8Array,
 		encrypted_output1: Uint8Array,
 		encrypted_output2: Uint8Array,
-	) -> ExtData {
-		let fee: u128 = JsValue::from(fee).as_string().unwrap().parse().unwrap();
-		let ext_amount: i128 = JsValue::from(ext_amount).as_string().unwrap().parse().unwrap();
-		let recipient = recipient.to_vec();
-		let relayer = relayer.to_vec();
-		let refund: u128 = JsValue::from(refund).as_string().unwrap().parse().unwrap();
-		let token = token.to_vec();
-		let encrypted_output1 = encrypted_output1.to_vec();
-		let encrypted_output2 = encrypted_output2.to_vec();
-		ExtData {
+	) -> Result<ExtData, JsValue> {
+		let ext_amount = parse_signed_amount(&String::from(ext_amount), None)?;
+		let fee = parse_amount_field(&String::from(fee), None, OpStatusCode::InvalidFee)?;
+		let refund = parse_amount_field(&String::from(refund), None, OpStatusCode::InvalidRefund)?;
+		Ok(ExtData {
 			fee,
 			ext_amount,
-			recipient,
-			relayer,
+			recipient: recipient.to_vec(),
+			relayer: relayer.to_vec(),
 			refund,
-			token,
-			encrypted_output1,
-			encrypted_output2,
-		}
+			token: token.to_vec(),
+			encrypted_output1: encrypted_output1.to_vec(),
+			encrypted_output2: encrypted_output2.to_vec(),
+		})
 	}
 
 	pub fn get_encode(&self) -> Uint8Array {
-		let codec = self.encode_abi();
-		let mut keccak = Keccak::v256();
-		keccak.update(codec.as_slice());
-		let mut output = [0u8; 32];
-		keccak.finalize(&mut output);
-		let field_res = Bn254Fr::from_be_bytes_mod_order(&output);
-		let value = field_res.into_repr().to_bytes_be();
-
+		let value = hash_to_field(&self.encode_abi());
 		Uint8Array::from(value.as_slice())
 	}
+
+	/// ABI encoding of the Solidity `ExtData` struct
+	#[wasm_bindgen(js_name = encodeAbi)]
+	pub fn js_encode_abi(&self) -> Result<Uint8Array, JsValue> {
+		Ok(Uint8Array::from(self.encode_evm_abi()?.as_slice()))
+	}
+
+	/// SCALE encoding, the `ext_data` argument of the pallet's `transact` call
+	#[wasm_bindgen(js_name = encodeScale)]
+	pub fn js_encode_scale(&self) -> Uint8Array {
+		Uint8Array::from(self.encode().as_slice())
+	}
+
+	/// The `extDataHash` the Solidity VAnchor computes, from `encodeAbi()`
+	#[wasm_bindgen(js_name = evmHash)]
+	pub fn evm_hash(&self) -> Result<Uint8Array, JsValue> {
+		let value = self.hash_with_encoding(ExtDataEncoding::Evm)?;
+		Ok(Uint8Array::from(value.as_slice()))
+	}
+
+	/// The `extDataHash` the VAnchor pallet computes. The pallet hashes its
+	/// own ABI encoding of the fields as bytes, not the SCALE encoding
+	#[wasm_bindgen(js_name = substrateHash)]
+	pub fn substrate_hash(&self) -> Uint8Array {
+		self.get_encode()
+	}
+
+	/// The `extDataHash` public input for the verifier on the target chain
+	pub fn hash(&self, encoding: WasmExtDataEncoding) -> Result<Uint8Array, JsValue> {
+		let encoding: ExtDataEncoding = JsValue::from(encoding)
+			.as_string()
+			.ok_or(OpStatusCode::InvalidExtDataHash)?
+			.parse()?;
+		let value = self.hash_with_encoding(encoding)?;
+		Ok(Uint8Array::from(value.as_slice()))
+	}
+}
+
+impl ExtData {
+	/// ABI encoding of the `ExtData` struct of the Solidity VAnchor, the
+	/// recipient, relayer and token are addresses
+	pub fn encode_evm_abi(&self) -> Result<Vec<u8>, OperationError> {
+		Ok(encode(&[self.evm_abi_token()?]))
+	}
+
+	/// The `ExtData` tuple of [`Self::encode_evm_abi`]
+	pub fn evm_abi_token(&self) -> Result<Token, OperationError> {
+		let address = |bytes: &[u8], code: OpStatusCode| {
+			if bytes.len() != 20 {
+				let message = format!("Expected a 20 bytes address, got {} bytes", bytes.len());
+				return Err(OperationError::new_with_message(code, message));
+			}
+			Ok(Token::Address(Address::from_slice(bytes)))
+		};
+		// Two's complement of the amount as an `int256`
+		let ext_amount = if self.ext_amount < 0 {
+			let (value, _) = (!Int::from(self.ext_amount.unsigned_abs())).overflowing_add(Int::one());
+			value
+		} else {
+			Int::from(self.ext_amount as u128)
+		};
+		let ext_data_args = vec![
+			address(&self.recipient, OpStatusCode::InvalidRecipient)?,
+			Token::Int(ext_amount),
+			address(&self.relayer, OpStatusCode::InvalidRelayer)?,
+			Token::Uint(Uint::from(self.fee)),
+			Token::Uint(Uint::from(self.refund)),
+			address(&self.token, OpStatusCode::InvalidAddress)?,
+			Token::Bytes(self.encrypted_output1.clone()),
+			Token::Bytes(self.encrypted_output2.clone()),
+		];
+		Ok(Token::Tuple(ext_data_args))
+	}
+
+	pub fn hash_with_encoding(&self, encoding: ExtDataEncoding) -> Result<Vec<u8>, OperationError> {
+		let encoded = match encoding {
+			ExtDataEncoding::Evm => self.encode_evm_abi()?,
+			ExtDataEncoding::Substrate => self.encode_abi(),
+		};
+		Ok(hash_to_field(&encoded))
+	}
+}
+
+/// Builder of [`ExtData`], the fee and refund default to zero
+#[wasm_bindgen]
+#[derive(Debug, Default, Clone)]
+pub struct ExtDataBuilder {
+	recipient: Option<Vec<u8>>,
+	relayer: Option<Vec<u8>>,
+	ext_amount: Option<i128>,
+	fee: u128,
+	refund: u128,
+	token: Option<Vec<u8>>,
+	encrypted_outputs: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+#[wasm_bindgen]
+impl ExtDataBuilder {
+	#[wasm_bindgen(constructor)]
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Account receiving the withdrawn amount, a 20 bytes address for EVM
+	/// chains and a 32 bytes public key for Substrate
+	#[wasm_bindgen(js_name = setRecipient)]
+	pub fn set_recipient(mut self, recipient: Uint8Array) -> ExtDataBuilder {
+		self.recipient = Some(recipient.to_vec());
+		self
+	}
+
+	#[wasm_bindgen(js_name = setRelayer)]
+	pub fn set_relayer(mut self, relayer: Uint8Array) -> ExtDataBuilder {
+		self.relayer = Some(relayer.to_vec());
+		self
+	}
+
+	/// Amount deposited into the pool in base units, negative for
+	/// withdrawals, decimal or `0x` hex
+	#[wasm_bindgen(js_name = setExtAmount)]
+	pub fn set_ext_amount(mut self, ext_amount: JsString) -> Result<ExtDataBuilder, JsValue> {
+		self.ext_amount = Some(parse_signed_amount(&String::from(ext_amount), None)?);
+		Ok(self)
+	}
+
+	#[wasm_bindgen(js_name = setFee)]
+	pub fn set_fee(mut self, fee: JsString) -> Result<ExtDataBuilder, JsValue> {
+		self.fee = parse_amount_field(&String::from(fee), None, OpStatusCode::InvalidFee)?;
+		Ok(self)
+	}
+
+	#[wasm_bindgen(js_name = setRefund)]
+	pub fn set_refund(mut self, refund: JsString) -> Result<ExtDataBuilder, JsValue> {
+		self.refund = parse_amount_field(&String::from(refund), None, OpStatusCode::InvalidRefund)?;
+		Ok(self)
+	}
+
+	/// Address of the token wrapped by the pool, or the asset id on Substrate
+	#[wasm_bindgen(js_name = setToken)]
+	pub fn set_token(mut self, token: Uint8Array) -> ExtDataBuilder {
+		self.token = Some(token.to_vec());
+		self
+	}
+
+	/// The output UTXOs encrypted for their owners
+	#[wasm_bindgen(js_name = setEncryptedOutputs)]
+	pub fn set_encrypted_outputs(
+		mut self,
+		encrypted_output1: Uint8Array,
+		encrypted_output2: Uint8Array,
+	) -> ExtDataBuilder {
+		self.encrypted_outputs = Some((encrypted_output1.to_vec(), encrypted_output2.to_vec()));
+		self
+	}
+
+	pub fn build(self) -> Result<ExtData, JsValue> {
+		let missing = |code: OpStatusCode, field: &str| {
+			OperationError::new_with_message(code, format!("The ExtData {} isn't set", field))
+		};
+		let (encrypted_output1, encrypted_output2) = self
+			.encrypted_outputs
+			.ok_or_else(|| missing(OpStatusCode::InvalidOutputUtxoConfig, "encrypted outputs"))?;
+		Ok(ExtData {
+			recipient: self
+				.recipient
+				.ok_or_else(|| missing(OpStatusCode::InvalidRecipient, "recipient"))?,
+			relayer: self
+				.relayer
+				.ok_or_else(|| missing(OpStatusCode::InvalidRelayer, "relayer"))?,
+			ext_amount: self
+				.ext_amount
+				.ok_or_else(|| missing(OpStatusCode::InvalidAmount, "extAmount"))?,
+			fee: self.fee,
+			refund: self.refund,
+			token: self
+				.token
+				.ok_or_else(|| missing(OpStatusCode::InvalidAddress, "token"))?,
+			encrypted_output1,
+			encrypted_output2,
+		})
+	}
+}
+
+/// Keccak-256 of `bytes` reduced to a Bn254 scalar, as the verifiers do
+fn hash_to_field(bytes: &[u8]) -> Vec<u8> {
+	let mut keccak = Keccak::v256();
+	keccak.update(bytes);
+	let mut output = [0u8; 32];
+	keccak.finalize(&mut output);
+	Bn254Fr::from_be_bytes_mod_order(&output).into_repr().to_bytes_be()
+}
+
+/// Compute the `extDataHash` of a VAnchor transaction exactly as the EVM
+/// contract or the Substrate pallet does
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn compute_ext_data_hash(
+	recipient: Uint8Array,
+	relayer: Uint8Array,
+	fee: JsString,
+	refund: JsString,
+	ext_amount: JsString,
+	token: Uint8Array,
+	encrypted_output1: Uint8Array,
+	encrypted_output2: Uint8Array,
+	encoding: WasmExtDataEncoding,
+) -> Result<Uint8Array, JsValue> {
+	let ext_data = ExtData::new(
+		recipient,
+		relayer,
+		ext_amount,
+		fee,
+		refund,
+		token,
+		encrypted_output1,
+		encrypted_output2,
+	)?;
+	ext_data.hash(encoding)
 }
 #[allow(clippy::wrong_self_convention)]
 pub trait IntoAbiToken {
@@ -134,3 +347,119 @@ impl IntoAbiToken for ExtData {
 		Token::Tuple(ext_data_args)
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use wasm_bindgen_test::*;
+
+	use super::*;
+
+	fn ext_data(ext_amount: i128) -> ExtData {
+		ExtData {
+			recipient: vec![1u8; 20],
+			relayer: vec![2u8; 20],
+			ext_amount,
+			fee: 5,
+			refund: 0,
+			token: vec![3u8; 20],
+			encrypted_output1: vec![4u8; 40],
+			encrypted_output2: vec![5u8; 40],
+		}
+	}
+
+	#[wasm_bindgen_test]
+	fn should_abi_encode_ext_data_for_evm() {
+		let encoded = ext_data(-10).encode_evm_abi().unwrap();
+		// Offset of the dynamic tuple, then its head: recipient, extAmount, relayer
+		assert_eq!(encoded[31], 0x20);
+		assert_eq!(&encoded[32 + 12..64], &[1u8; 20]);
+		let mut minus_ten = [0xffu8; 32];
+		minus_ten[31] = 0xf6;
+		assert_eq!(&encoded[64..96], &minus_ten);
+		assert_eq!(&encoded[96 + 12..128], &[2u8; 20]);
+
+		let mut address_too_long = ext_data(10);
+		address_too_long.recipient = vec![1u8; 32];
+		let error = address_too_long.hash_with_encoding(ExtDataEncoding::Evm).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::InvalidRecipient);
+	}
+
+	#[wasm_bindgen_test]
+	fn should_compute_ext_data_hash() {
+		let data = ext_data(10);
+		let encoding = |encoding: ExtDataEncoding| WasmExtDataEncoding::from(JsValue::from(encoding.to_string()));
+		let hash = |encoding| {
+			compute_ext_data_hash(
+				Uint8Array::from(data.recipient.as_slice()),
+				Uint8Array::from(data.relayer.as_slice()),
+				JsString::from("5"),
+				JsString::from("0"),
+				JsString::from("10"),
+				Uint8Array::from(data.token.as_slice()),
+				Uint8Array::from(data.encrypted_output1.as_slice()),
+				Uint8Array::from(data.encrypted_output2.as_slice()),
+				encoding,
+			)
+			.unwrap()
+			.to_vec()
+		};
+
+		let from_hex = ExtData::new(
+			Uint8Array::from(data.recipient.as_slice()),
+			Uint8Array::from(data.relayer.as_slice()),
+			JsString::from("0xa"),
+			JsString::from("0x5"),
+			JsString::from("0"),
+			Uint8Array::from(data.token.as_slice()),
+			Uint8Array::from(data.encrypted_output1.as_slice()),
+			Uint8Array::from(data.encrypted_output2.as_slice()),
+		)
+		.unwrap();
+		assert_eq!(from_hex.encode(), data.encode());
+
+		let substrate_hash = hash(encoding(ExtDataEncoding::Substrate));
+		assert_eq!(substrate_hash, data.get_encode().to_vec());
+		let evm_hash = hash(encoding(ExtDataEncoding::Evm));
+		assert_eq!(evm_hash, data.hash_with_encoding(ExtDataEncoding::Evm).unwrap());
+		assert_ne!(evm_hash, substrate_hash);
+	}
+
+	#[wasm_bindgen_test]
+	fn should_build_and_encode_ext_data() {
+		let data = ext_data(-10);
+		let builder = ExtDataBuilder::new()
+			.set_recipient(Uint8Array::from(data.recipient.as_slice()))
+			.set_relayer(Uint8Array::from(data.relayer.as_slice()))
+			.set_ext_amount(JsString::from("-10"))
+			.unwrap()
+			.set_fee(JsString::from("0x5"))
+			.unwrap();
+		assert!(builder.clone().set_refund(JsString::from("-1")).is_err());
+		let code = |error: JsValue| js_sys::Reflect::get(&error, &JsValue::from_str("code")).unwrap();
+		let incomplete = builder.clone().build().unwrap_err();
+		assert_eq!(code(incomplete), "InvalidOutputUtxoConfig");
+		let builder = builder.set_encrypted_outputs(
+			Uint8Array::from(data.encrypted_output1.as_slice()),
+			Uint8Array::from(data.encrypted_output2.as_slice()),
+		);
+		let without_token = builder.clone().build().unwrap_err();
+		assert_eq!(code(without_token), "InvalidAddress");
+		let built = builder
+			.set_token(Uint8Array::from(data.token.as_slice()))
+			.build()
+			.unwrap();
+		assert_eq!(built, data);
+
+		assert_eq!(built.js_encode_abi().unwrap().to_vec(), data.encode_evm_abi().unwrap());
+		let scale = built.js_encode_scale().to_vec();
+		assert_eq!(ExtData::decode(&mut scale.as_slice()).unwrap(), data);
+		assert_eq!(
+			built.evm_hash().unwrap().to_vec(),
+			data.hash_with_encoding(ExtDataEncoding::Evm).unwrap()
+		);
+		assert_eq!(
+			built.substrate_hash().to_vec(),
+			data.hash_with_encoding(ExtDataEncoding::Substrate).unwrap()
+		);
+	}
+}
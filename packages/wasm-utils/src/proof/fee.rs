@@ -0,0 +1,287 @@
+//! Relayer fee quotes. A relayer charges a share of the withdrawn amount in
+//! basis points, a flat fee and the gas it pays for the transaction, the
+//! quote gives the fee and refund in base units, ready for `setFee` and
+//! `setRefund`.
+use js_sys::JsString;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::note::amount::{amount_field_error, NoteAmount};
+use crate::note::JsNote;
+use crate::types::{OpStatusCode, OperationError};
+
+const MAX_BPS: u32 = 10_000;
+
+/// Fee model of a relayer. Amounts are token amounts such as `0.01`, or base
+/// units when `0x` prefixed
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayerFeeConfig {
+	/// Share of the withdrawn amount, in basis points
+	#[serde(default)]
+	pub fee_bps: u32,
+	#[serde(default)]
+	pub flat_fee: Option<String>,
+	/// Cost of the relay transaction, in the withdrawn token
+	#[serde(default)]
+	pub gas_estimate: Option<String>,
+	/// Lowest fee the relayer takes, smaller fees are raised to it
+	#[serde(default)]
+	pub min_fee: Option<String>,
+	/// Highest fee the user accepts, larger fees are an error
+	#[serde(default)]
+	pub max_fee: Option<String>,
+	/// Native tokens sent along to the recipient
+	#[serde(default)]
+	pub refund: Option<String>,
+}
+
+/// Fee, refund and what the recipient gets, in base units
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct FeeQuote {
+	pub fee: String,
+	pub refund: String,
+	pub payout: String,
+}
+
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct FeeCalculator {
+	#[wasm_bindgen(skip)]
+	pub config: RelayerFeeConfig,
+	#[wasm_bindgen(skip)]
+	pub denomination: u8,
+}
+
+impl FeeCalculator {
+	pub fn new(config: RelayerFeeConfig, denomination: u8) -> Result<Self, OperationError> {
+		if config.fee_bps > MAX_BPS {
+			let message = format!("Fee of {} bps is more than the whole amount", config.fee_bps);
+			return Err(OperationError::new_with_message(OpStatusCode::InvalidFee, message));
+		}
+		let calculator = Self { config, denomination };
+		// Report malformed amounts when the config is given, not on the first quote
+		let min_fee = calculator.amount(&calculator.config.min_fee)?;
+		let max_fee = calculator.amount(&calculator.config.max_fee)?;
+		if let (Some(min_fee), Some(max_fee)) = (min_fee, max_fee) {
+			if min_fee > max_fee {
+				let message = format!("Minimum fee {} is above the maximum fee {}", min_fee, max_fee);
+				return Err(OperationError::new_with_message(OpStatusCode::InvalidFee, message));
+			}
+		}
+		calculator.amount(&calculator.config.flat_fee)?;
+		calculator.amount(&calculator.config.gas_estimate)?;
+		calculator.amount(&calculator.config.refund)?;
+		Ok(calculator)
+	}
+
+	fn amount(&self, value: &Option<String>) -> Result<Option<u128>, OperationError> {
+		value
+			.as_deref()
+			.map(|value| NoteAmount::parse_with_decimals(value, Some(self.denomination)).map(|amount| amount.inner))
+			.transpose()
+			.map_err(amount_field_error(OpStatusCode::InvalidFee))
+	}
+
+	/// Quote the withdrawal of `amount` base units
+	pub fn quote(&self, amount: u128) -> Result<FeeQuote, OperationError> {
+		// `amount * bps` can overflow, the remainder is scaled on its own and
+		// rounded up so the relayer never gets less than its share
+		let bps = u128::from(self.config.fee_bps);
+		let max_bps = u128::from(MAX_BPS);
+		let share = amount / max_bps * bps + (amount % max_bps * bps + max_bps - 1) / max_bps;
+		let mut fee = NoteAmount::new(share);
+		for extra in [&self.config.flat_fee, &self.config.gas_estimate] {
+			fee = fee.checked_add(&NoteAmount::new(self.amount(extra)?.unwrap_or_default()))?;
+		}
+		let mut fee = fee.inner;
+		if let Some(min_fee) = self.amount(&self.config.min_fee)? {
+			fee = fee.max(min_fee);
+		}
+		if let Some(max_fee) = self.amount(&self.config.max_fee)? {
+			if fee > max_fee {
+				let message = format!("Fee {} is above the maximum fee {}", fee, max_fee);
+				return Err(OperationError::new_with_message(OpStatusCode::InvalidFee, message));
+			}
+		}
+		let payout = amount.checked_sub(fee).ok_or_else(|| {
+			let message = format!("Fee {} is more than the amount {}", fee, amount);
+			OperationError::new_with_message(OpStatusCode::InsufficientBalance, message)
+		})?;
+		let refund = self.amount(&self.config.refund)?.unwrap_or_default();
+		Ok(FeeQuote {
+			fee: fee.to_string(),
+			refund: refund.to_string(),
+			payout: payout.to_string(),
+		})
+	}
+
+	/// Quote the withdrawal of the whole amount of `note`
+	pub fn quote_note(&self, note: &JsNote) -> Result<FeeQuote, OperationError> {
+		if let Some(denomination) = note.denomination {
+			if denomination != self.denomination {
+				let message = format!(
+					"The note has {} decimals, the fees are for {}",
+					denomination, self.denomination
+				);
+				return Err(OperationError::new_with_message(
+					OpStatusCode::InvalidDenomination,
+					message,
+				));
+			}
+		}
+		self.quote(note.amount_value()?.inner)
+	}
+}
+
+#[wasm_bindgen(typescript_custom_section)]
+const FEE_QUOTE: &str = r#"
+export interface RelayerFeeConfig {
+	feeBps?: number;
+	flatFee?: string;
+	gasEstimate?: string;
+	minFee?: string;
+	maxFee?: string;
+	refund?: string;
+}
+
+export interface FeeQuote {
+	fee: string;
+	refund: string;
+	payout: string;
+}
+"#;
+
+#[wasm_bindgen]
+extern "C" {
+	#[wasm_bindgen(typescript_type = "RelayerFeeConfig")]
+	pub type WasmRelayerFeeConfig;
+
+	#[wasm_bindgen(typescript_type = "FeeQuote")]
+	pub type WasmFeeQuote;
+}
+
+fn to_js_quote(quote: &FeeQuote) -> Result<WasmFeeQuote, JsValue> {
+	let quote = JsValue::from_serde(quote)
+		.map_err(|e| OperationError::new_with_message(OpStatusCode::SerializationFailed, e.to_string()))?;
+	Ok(quote.unchecked_into())
+}
+
+#[allow(clippy::unused_unit)]
+#[wasm_bindgen]
+impl FeeCalculator {
+	/// Fees of a relayer for a token with `denomination` decimals
+	#[wasm_bindgen(constructor)]
+	pub fn js_new(config: WasmRelayerFeeConfig, denomination: u8) -> Result<FeeCalculator, JsValue> {
+		let config: RelayerFeeConfig = JsValue::from(config)
+			.into_serde()
+			.map_err(|e| OperationError::new_with_message(OpStatusCode::DeserializationFailed, e.to_string()))?;
+		Ok(FeeCalculator::new(config, denomination)?)
+	}
+
+	/// Quote the withdrawal of `amount`, in base units or as a token amount
+	/// like the fee
+	#[wasm_bindgen(js_name = quote)]
+	pub fn js_quote(&self, amount: JsString) -> Result<WasmFeeQuote, JsValue> {
+		let amount = NoteAmount::parse_with_decimals(&String::from(amount), None)?;
+		to_js_quote(&self.quote(amount.inner)?)
+	}
+
+	#[wasm_bindgen(js_name = quoteNote)]
+	pub fn js_quote_note(&self, note: &JsNote) -> Result<WasmFeeQuote, JsValue> {
+		to_js_quote(&self.quote_note(note)?)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use wasm_bindgen_test::*;
+
+	use super::*;
+
+	fn calculator(config: RelayerFeeConfig) -> FeeCalculator {
+		FeeCalculator::new(config, 18).unwrap()
+	}
+
+	#[wasm_bindgen_test]
+	fn should_quote_percentage_and_flat_fees() {
+		let config = RelayerFeeConfig {
+			fee_bps: 50,
+			flat_fee: Some("0.01".to_string()),
+			gas_estimate: Some("0x100".to_string()),
+			refund: Some("0.002".to_string()),
+			..Default::default()
+		};
+		let quote = calculator(config).quote(10u128.pow(18)).unwrap();
+		// 0.5% of 1 + 0.01 + 256 wei
+		assert_eq!(quote.fee, "15000000000000256");
+		assert_eq!(quote.refund, "2000000000000000");
+		assert_eq!(quote.payout, (10u128.pow(18) - 15000000000000256).to_string());
+
+		// The share is rounded up and doesn't overflow
+		let share = |bps: u32, amount: u128| {
+			let config = RelayerFeeConfig {
+				fee_bps: bps,
+				..Default::default()
+			};
+			calculator(config).quote(amount).unwrap().fee
+		};
+		assert_eq!(share(1, 1), "1");
+		assert_eq!(share(0, 1), "0");
+		assert_eq!(share(10_000, u128::MAX), u128::MAX.to_string());
+	}
+
+	#[wasm_bindgen_test]
+	fn should_validate_fee_bounds() {
+		let config = RelayerFeeConfig {
+			fee_bps: 10,
+			min_fee: Some("0.05".to_string()),
+			max_fee: Some("0.1".to_string()),
+			..Default::default()
+		};
+		let fee_calculator = calculator(config);
+		// Raised to the minimum
+		assert_eq!(fee_calculator.quote(10u128.pow(18)).unwrap().fee, "50000000000000000");
+		let error = fee_calculator.quote(1000 * 10u128.pow(18)).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::InvalidFee);
+		let error = fee_calculator.quote(10u128.pow(16)).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::InsufficientBalance);
+
+		let invalid = |config: RelayerFeeConfig| FeeCalculator::new(config, 18).unwrap_err().code;
+		let bps = RelayerFeeConfig {
+			fee_bps: 10_001,
+			..Default::default()
+		};
+		assert_eq!(invalid(bps), OpStatusCode::InvalidFee);
+		let bounds = RelayerFeeConfig {
+			min_fee: Some("2".to_string()),
+			max_fee: Some("1".to_string()),
+			..Default::default()
+		};
+		assert_eq!(invalid(bounds), OpStatusCode::InvalidFee);
+		let flat_fee = RelayerFeeConfig {
+			flat_fee: Some("1.x".to_string()),
+			..Default::default()
+		};
+		assert_eq!(invalid(flat_fee), OpStatusCode::InvalidFee);
+	}
+
+	#[wasm_bindgen_test]
+	fn should_quote_notes() {
+		let note = JsNote::deserialize("webb://v1:mixer/2:2/2:2/fd71:18b6/?denom=6&amount=2000000").unwrap();
+		let config = RelayerFeeConfig {
+			fee_bps: 100,
+			..Default::default()
+		};
+		let quote = FeeCalculator::new(config.clone(), 6)
+			.unwrap()
+			.quote_note(&note)
+			.unwrap();
+		assert_eq!(quote.fee, "20000");
+		assert_eq!(quote.payout, "1980000");
+		let error = calculator(config).quote_note(&note).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::InvalidDenomination);
+	}
+}
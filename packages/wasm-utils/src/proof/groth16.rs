@@ -0,0 +1,248 @@
+use ark_bls12_381::Bls12_381;
+use ark_bn254::{Bn254, Fq, Fq2, G1Affine, G2Affine};
+use ark_ec::short_weierstrass_jacobian::GroupAffine;
+use ark_ec::{PairingEngine, SWModelParameters};
+use ark_ff::{BigInteger, PrimeField, Zero};
+use ark_groth16::Proof;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use js_sys::{Array, JsString, Uint8Array};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::types::{Curve, OpStatusCode, OperationError, StringArray, WasmCurve};
+
+/// Number of `uint256` words of a proof on the EVM, `a (2) || b (4) || c (2)`
+pub const EVM_PROOF_WORDS: usize = 8;
+
+fn invalid_proof(message: &str) -> OperationError {
+	OperationError::new_with_message(OpStatusCode::InvalidProof, message.to_string())
+}
+
+pub(crate) fn fq_word(fq: &Fq) -> [u8; 32] {
+	let mut word = [0u8; 32];
+	word.copy_from_slice(&fq.into_repr().to_bytes_be());
+	word
+}
+
+fn word_fq(word: &[u8; 32]) -> Result<Fq, OperationError> {
+	let fq = Fq::from_be_bytes_mod_order(word);
+	if fq_word(&fq) != *word {
+		return Err(invalid_proof("Coordinate is not a field element"));
+	}
+	Ok(fq)
+}
+
+// The EVM encodes the point at infinity as `(0, 0)`
+pub(crate) fn g1_words(point: &G1Affine) -> Vec<[u8; 32]> {
+	if point.infinity {
+		return vec![[0u8; 32]; 2];
+	}
+	vec![fq_word(&point.x), fq_word(&point.y)]
+}
+
+// The EVM pairing precompile takes the imaginary part of G2 coordinates first
+pub(crate) fn g2_words(point: &G2Affine) -> Vec<[u8; 32]> {
+	if point.infinity {
+		return vec![[0u8; 32]; 4];
+	}
+	let fq2_words = |fq2: &Fq2| vec![fq_word(&fq2.c1), fq_word(&fq2.c0)];
+	[fq2_words(&point.x), fq2_words(&point.y)].concat()
+}
+
+fn words_point<P: SWModelParameters>(x: P::BaseField, y: P::BaseField) -> Result<GroupAffine<P>, OperationError> {
+	if x.is_zero() && y.is_zero() {
+		return Ok(GroupAffine::zero());
+	}
+	let point = GroupAffine::new(x, y, false);
+	if !point.is_on_curve() || !point.is_in_correct_subgroup_assuming_on_curve() {
+		return Err(invalid_proof("Point is not in the curve's subgroup"));
+	}
+	Ok(point)
+}
+
+fn words_g1(words: &[[u8; 32]]) -> Result<G1Affine, OperationError> {
+	words_point(word_fq(&words[0])?, word_fq(&words[1])?)
+}
+
+fn words_g2(words: &[[u8; 32]]) -> Result<G2Affine, OperationError> {
+	let x = Fq2::new(word_fq(&words[1])?, word_fq(&words[0])?);
+	let y = Fq2::new(word_fq(&words[3])?, word_fq(&words[2])?);
+	words_point(x, y)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Groth16ProofInner {
+	Bn254(Proof<Bn254>),
+	Bls381(Proof<Bls12_381>),
+}
+
+/// A Groth16 proof, convertible between the arkworks encodings Substrate
+/// verifiers take and the `uint256` words EVM verifiers take
+#[wasm_bindgen(js_name = Groth16Proof)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct JsGroth16Proof {
+	#[wasm_bindgen(skip)]
+	pub inner: Groth16ProofInner,
+}
+
+fn deserialize<E: PairingEngine>(proof: &[u8], compressed: bool) -> Result<Proof<E>, OperationError> {
+	let proof = if compressed {
+		Proof::<E>::deserialize(proof)
+	} else {
+		Proof::<E>::deserialize_uncompressed(proof)
+	};
+	proof.map_err(|e| invalid_proof(&e.to_string()))
+}
+
+impl JsGroth16Proof {
+	/// Parse the compressed arkworks encoding, which is what the provers of
+	/// this crate output
+	pub fn from_compressed(curve: Curve, proof: &[u8]) -> Result<Self, OperationError> {
+		let inner = match curve {
+			Curve::Bn254 => Groth16ProofInner::Bn254(deserialize(proof, true)?),
+			Curve::Bls381 => Groth16ProofInner::Bls381(deserialize(proof, true)?),
+		};
+		Ok(Self { inner })
+	}
+
+	pub fn from_uncompressed(curve: Curve, proof: &[u8]) -> Result<Self, OperationError> {
+		let inner = match curve {
+			Curve::Bn254 => Groth16ProofInner::Bn254(deserialize(proof, false)?),
+			Curve::Bls381 => Groth16ProofInner::Bls381(deserialize(proof, false)?),
+		};
+		Ok(Self { inner })
+	}
+
+	/// Parse the big endian `(a, b, c)` words of a Bn254 proof, as packed by
+	/// [`Self::to_evm_bytes`]
+	pub fn from_evm_bytes(proof: &[u8]) -> Result<Self, OperationError> {
+		if proof.len() != EVM_PROOF_WORDS * 32 {
+			let message = format!("EVM proofs should be {} bytes", EVM_PROOF_WORDS * 32);
+			return Err(OperationError::new_with_message(
+				OpStatusCode::InvalidArrayLength,
+				message,
+			));
+		}
+		let words: Vec<[u8; 32]> = proof.chunks(32).map(|word| word.try_into().unwrap()).collect();
+		let proof = Proof {
+			a: words_g1(&words[0..2])?,
+			b: words_g2(&words[2..6])?,
+			c: words_g1(&words[6..8])?,
+		};
+		Ok(Self {
+			inner: Groth16ProofInner::Bn254(proof),
+		})
+	}
+
+	pub fn curve(&self) -> Curve {
+		match self.inner {
+			Groth16ProofInner::Bn254(_) => Curve::Bn254,
+			Groth16ProofInner::Bls381(_) => Curve::Bls381,
+		}
+	}
+
+	pub fn to_compressed(&self) -> Vec<u8> {
+		let mut bytes = Vec::new();
+		match &self.inner {
+			Groth16ProofInner::Bn254(proof) => proof.serialize(&mut bytes),
+			Groth16ProofInner::Bls381(proof) => proof.serialize(&mut bytes),
+		}
+		.expect("serializing to a vec can't fail");
+		bytes
+	}
+
+	pub fn to_uncompressed(&self) -> Vec<u8> {
+		let mut bytes = Vec::new();
+		match &self.inner {
+			Groth16ProofInner::Bn254(proof) => proof.serialize_uncompressed(&mut bytes),
+			Groth16ProofInner::Bls381(proof) => proof.serialize_uncompressed(&mut bytes),
+		}
+		.expect("serializing to a vec can't fail");
+		bytes
+	}
+
+	/// `a.x, a.y, b.x, b.y, c.x, c.y` as big endian `uint256` words, with the
+	/// imaginary part of each `b` coordinate first. Only Bn254 proofs can be
+	/// verified on the EVM.
+	pub fn to_evm(&self) -> Result<Vec<[u8; 32]>, OperationError> {
+		let proof = match &self.inner {
+			Groth16ProofInner::Bn254(proof) => proof,
+			Groth16ProofInner::Bls381(_) => {
+				let message = "EVM verifiers only support Bn254".to_string();
+				return Err(OperationError::new_with_message(OpStatusCode::InvalidCurve, message));
+			}
+		};
+		let mut words = g1_words(&proof.a);
+		words.extend(g2_words(&proof.b));
+		words.extend(g1_words(&proof.c));
+		Ok(words)
+	}
+
+	/// The packed words of [`Self::to_evm`], `abi.encode(uint256[8])`
+	pub fn to_evm_bytes(&self) -> Result<Vec<u8>, OperationError> {
+		Ok(self.to_evm()?.concat())
+	}
+}
+
+pub(crate) fn parse_curve(curve: Option<WasmCurve>) -> Result<Curve, OperationError> {
+	match curve {
+		Some(curve) => JsValue::from(curve)
+			.as_string()
+			.and_then(|curve| curve.parse().ok())
+			.ok_or_else(|| OpStatusCode::InvalidCurve.into()),
+		None => Ok(Curve::Bn254),
+	}
+}
+
+#[allow(clippy::unused_unit)]
+#[wasm_bindgen(js_class = Groth16Proof)]
+impl JsGroth16Proof {
+	/// Parse compressed arkworks proof bytes, Bn254 unless `curve` is set
+	#[wasm_bindgen(js_name = fromCompressed)]
+	pub fn js_from_compressed(proof: Uint8Array, curve: Option<WasmCurve>) -> Result<JsGroth16Proof, JsValue> {
+		Ok(JsGroth16Proof::from_compressed(parse_curve(curve)?, &proof.to_vec())?)
+	}
+
+	#[wasm_bindgen(js_name = fromUncompressed)]
+	pub fn js_from_uncompressed(proof: Uint8Array, curve: Option<WasmCurve>) -> Result<JsGroth16Proof, JsValue> {
+		Ok(JsGroth16Proof::from_uncompressed(parse_curve(curve)?, &proof.to_vec())?)
+	}
+
+	#[wasm_bindgen(js_name = fromEvmBytes)]
+	pub fn js_from_evm_bytes(proof: Uint8Array) -> Result<JsGroth16Proof, JsValue> {
+		Ok(JsGroth16Proof::from_evm_bytes(&proof.to_vec())?)
+	}
+
+	#[wasm_bindgen(getter, js_name = curve)]
+	pub fn js_curve(&self) -> WasmCurve {
+		self.curve().into()
+	}
+
+	#[wasm_bindgen(js_name = toCompressed)]
+	pub fn js_to_compressed(&self) -> Uint8Array {
+		Uint8Array::from(self.to_compressed().as_slice())
+	}
+
+	#[wasm_bindgen(js_name = toUncompressed)]
+	pub fn js_to_uncompressed(&self) -> Uint8Array {
+		Uint8Array::from(self.to_uncompressed().as_slice())
+	}
+
+	/// `0x` prefixed `uint256` words `[a.x, a.y, b.x1, b.x0, b.y1, b.y0, c.x,
+	/// c.y]`
+	#[wasm_bindgen(js_name = toEvm)]
+	pub fn js_to_evm(&self) -> Result<StringArray, JsValue> {
+		let words = self.to_evm()?;
+		let words: Array = words
+			.iter()
+			.map(|word| JsValue::from(format!("0x{}", hex::encode(word))))
+			.collect();
+		Ok(words.unchecked_into())
+	}
+
+	/// Hex of the packed words, the `bytes proof` argument of the contracts
+	#[wasm_bindgen(js_name = toEvmBytes)]
+	pub fn js_to_evm_bytes(&self) -> Result<JsString, JsValue> {
+		Ok(format!("0x{}", hex::encode(self.to_evm_bytes()?)).into())
+	}
+}
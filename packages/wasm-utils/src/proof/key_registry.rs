@@ -0,0 +1,375 @@
+//! Content addressed proving and verifying keys. The keys are too large to
+//! ship with the SDK, it fetches them from IPFS or a CDN by circuit and checks
+//! them against the `keccak256` hashes registered for the circuit before
+//! proving with them, so a compromised mirror can't swap the keys.
+use core::fmt;
+
+use js_sys::Uint8Array;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::evm::keccak256;
+use crate::proof::verifying_key::JsVerifyingKey;
+use crate::proof::{ensure_supported_circuit, ensure_tree_depth};
+use crate::types::{Curve, NoteProtocol, OpStatusCode, OperationError};
+
+/// The circuit a pair of keys was set up for
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CircuitParams {
+	pub protocol: String,
+	pub curve: String,
+	pub width: usize,
+	pub exponentiation: i8,
+	pub tree_depth: usize,
+	/// Number of anchors, `m`, 1 for the mixer
+	pub anchor_count: usize,
+	/// Inputs of a VAnchor transaction, there is a circuit for 2 and one for 16
+	#[serde(default)]
+	pub input_count: Option<usize>,
+}
+
+impl CircuitParams {
+	/// Protocol and curve of a supported circuit
+	pub fn parse(&self) -> Result<(NoteProtocol, Curve), OperationError> {
+		let protocol: NoteProtocol = self.protocol.parse()?;
+		let curve: Curve = self.curve.parse()?;
+		ensure_supported_circuit(protocol, curve, self.width, self.exponentiation)?;
+		ensure_tree_depth(self.tree_depth)?;
+		let anchors_and_inputs = match protocol {
+			NoteProtocol::Mixer => self.anchor_count == 1 && self.input_count.is_none(),
+			NoteProtocol::VAnchor => self.anchor_count > 0 && self.input_count.is_some(),
+		};
+		if !anchors_and_inputs {
+			let message = format!(
+				"A {} circuit with {} anchors and {:?} inputs",
+				protocol, self.anchor_count, self.input_count
+			);
+			return Err(OperationError::new_with_message(
+				OpStatusCode::UnsupportedParameterCombination,
+				message,
+			));
+		}
+		Ok((protocol, curve))
+	}
+}
+
+/// `vanchor-Bn254-x5-5-d30-m2-i16`, usable as a path on a key mirror
+impl fmt::Display for CircuitParams {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"{}-{}-x{}-{}-d{}-m{}",
+			self.protocol, self.curve, self.width, self.exponentiation, self.tree_depth, self.anchor_count
+		)?;
+		if let Some(input_count) = self.input_count {
+			write!(f, "-i{}", input_count)?;
+		}
+		Ok(())
+	}
+}
+
+/// `0x` prefixed `keccak256` hashes of the keys of a circuit
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyHashes {
+	pub proving_key: String,
+	pub verifying_key: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum KeyKind {
+	ProvingKey,
+	VerifyingKey,
+}
+
+fn parse_hash(hash: &str, code: OpStatusCode) -> Result<[u8; 32], OperationError> {
+	let bytes = hex::decode(hash.trim_start_matches("0x")).unwrap_or_default();
+	bytes
+		.try_into()
+		.map_err(|_| OperationError::new_with_message(code, format!("Invalid key hash {:?}", hash)))
+}
+
+#[derive(Debug, Clone)]
+struct RegistryEntry {
+	params: CircuitParams,
+	proving_key_hash: [u8; 32],
+	verifying_key_hash: [u8; 32],
+	proving_key: Option<Vec<u8>>,
+	verifying_key: Option<Vec<u8>>,
+}
+
+/// Expected key hashes by circuit, and the keys checked against them
+#[wasm_bindgen]
+#[derive(Debug, Clone, Default)]
+pub struct KeyRegistry {
+	entries: Vec<RegistryEntry>,
+}
+
+impl KeyRegistry {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	fn entry(&self, params: &CircuitParams) -> Result<Option<&RegistryEntry>, OperationError> {
+		params.parse()?;
+		Ok(self.entries.iter().find(|entry| entry.params == *params))
+	}
+
+	/// Expect the keys of the circuit `params` to hash to `hashes`, replacing
+	/// the hashes and dropping the keys registered for it before
+	pub fn add(&mut self, params: &CircuitParams, hashes: &KeyHashes) -> Result<(), OperationError> {
+		params.parse()?;
+		let entry = RegistryEntry {
+			proving_key_hash: parse_hash(&hashes.proving_key, OpStatusCode::InvalidProvingKey)?,
+			verifying_key_hash: parse_hash(&hashes.verifying_key, OpStatusCode::InvalidVerifyingKey)?,
+			proving_key: None,
+			verifying_key: None,
+			params: params.clone(),
+		};
+		self.entries.retain(|other| other.params != entry.params);
+		self.entries.push(entry);
+		Ok(())
+	}
+
+	pub fn lookup(&self, params: &CircuitParams) -> Result<Option<KeyHashes>, OperationError> {
+		Ok(self.entry(params)?.map(|entry| KeyHashes {
+			proving_key: format!("0x{}", hex::encode(entry.proving_key_hash)),
+			verifying_key: format!("0x{}", hex::encode(entry.verifying_key_hash)),
+		}))
+	}
+
+	/// Keep `key` for the circuit `params` if it hashes to one of the hashes
+	/// expected for it, a verifying key must also parse
+	pub fn register(&mut self, params: &CircuitParams, key: Vec<u8>) -> Result<KeyKind, OperationError> {
+		let (_, curve) = params.parse()?;
+		let entry = self
+			.entries
+			.iter_mut()
+			.find(|entry| entry.params == *params)
+			.ok_or_else(|| {
+				let message = format!("No keys are expected for the circuit {}", params);
+				OperationError::new_with_message(OpStatusCode::UnsupportedParameterCombination, message)
+			})?;
+		let hash = keccak256(&key);
+		if hash == entry.proving_key_hash {
+			entry.proving_key = Some(key);
+			Ok(KeyKind::ProvingKey)
+		} else if hash == entry.verifying_key_hash {
+			JsVerifyingKey::new(curve, &key)?;
+			entry.verifying_key = Some(key);
+			Ok(KeyKind::VerifyingKey)
+		} else {
+			let message = format!(
+				"The key hashes to 0x{}, which isn't a key of the circuit {}",
+				hex::encode(hash),
+				params
+			);
+			Err(OperationError::new_with_message(
+				OpStatusCode::InvalidProvingKey,
+				message,
+			))
+		}
+	}
+
+	pub fn proving_key(&self, params: &CircuitParams) -> Result<Option<&[u8]>, OperationError> {
+		Ok(self.entry(params)?.and_then(|entry| entry.proving_key.as_deref()))
+	}
+
+	pub fn verifying_key(&self, params: &CircuitParams) -> Result<Option<&[u8]>, OperationError> {
+		Ok(self.entry(params)?.and_then(|entry| entry.verifying_key.as_deref()))
+	}
+}
+
+#[wasm_bindgen(typescript_custom_section)]
+const KEY_REGISTRY: &str = r#"
+export interface CircuitParams {
+	protocol: NoteProtocol;
+	curve: Curve;
+	width: number;
+	exponentiation: number;
+	treeDepth: number;
+	anchorCount: number;
+	inputCount?: number;
+}
+
+export interface KeyHashes {
+	provingKey: string;
+	verifyingKey: string;
+}
+
+export type KeyKind = 'provingKey' | 'verifyingKey';
+"#;
+
+#[wasm_bindgen]
+extern "C" {
+	#[wasm_bindgen(typescript_type = "CircuitParams")]
+	pub type WasmCircuitParams;
+
+	#[wasm_bindgen(typescript_type = "KeyHashes")]
+	pub type WasmKeyHashes;
+
+	#[wasm_bindgen(typescript_type = "KeyKind")]
+	pub type WasmKeyKind;
+}
+
+fn from_js<T: for<'a> Deserialize<'a>>(value: JsValue) -> Result<T, OperationError> {
+	value
+		.into_serde()
+		.map_err(|e| OperationError::new_with_message(OpStatusCode::DeserializationFailed, e.to_string()))
+}
+
+fn to_js<T: Serialize>(value: &T) -> Result<JsValue, OperationError> {
+	JsValue::from_serde(value)
+		.map_err(|e| OperationError::new_with_message(OpStatusCode::SerializationFailed, e.to_string()))
+}
+
+#[allow(clippy::unused_unit)]
+#[wasm_bindgen]
+impl KeyRegistry {
+	#[wasm_bindgen(constructor)]
+	pub fn js_new() -> KeyRegistry {
+		KeyRegistry::new()
+	}
+
+	/// Expect the keys of the circuit `params` to have the `keccak256`
+	/// `hashes`
+	#[wasm_bindgen(js_name = add)]
+	pub fn js_add(&mut self, params: WasmCircuitParams, hashes: WasmKeyHashes) -> Result<(), JsValue> {
+		let params: CircuitParams = from_js(params.into())?;
+		let hashes: KeyHashes = from_js(hashes.into())?;
+		Ok(self.add(&params, &hashes)?)
+	}
+
+	/// Hashes of the keys to fetch for the circuit `params`
+	#[wasm_bindgen(js_name = lookup)]
+	pub fn js_lookup(&self, params: WasmCircuitParams) -> Result<Option<WasmKeyHashes>, JsValue> {
+		let hashes = self.lookup(&from_js(params.into())?)?;
+		match hashes {
+			Some(hashes) => Ok(Some(to_js(&hashes)?.unchecked_into())),
+			None => Ok(None),
+		}
+	}
+
+	/// Keep fetched key bytes after checking them against the hashes of the
+	/// circuit `params`, which kind of key they are is returned
+	#[wasm_bindgen(js_name = register)]
+	pub fn js_register(&mut self, params: WasmCircuitParams, key: Uint8Array) -> Result<WasmKeyKind, JsValue> {
+		let kind = self.register(&from_js(params.into())?, key.to_vec())?;
+		Ok(to_js(&kind)?.unchecked_into())
+	}
+
+	/// Proving key of the circuit `params` if it was registered
+	#[wasm_bindgen(js_name = provingKey)]
+	pub fn js_proving_key(&self, params: WasmCircuitParams) -> Result<Option<Uint8Array>, JsValue> {
+		Ok(self.proving_key(&from_js(params.into())?)?.map(Uint8Array::from))
+	}
+
+	/// Verifying key of the circuit `params` if it was registered
+	#[wasm_bindgen(js_name = verifyingKey)]
+	pub fn js_verifying_key(&self, params: WasmCircuitParams) -> Result<Option<Uint8Array>, JsValue> {
+		Ok(self.verifying_key(&from_js(params.into())?)?.map(Uint8Array::from))
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use ark_bn254::Bn254;
+	use ark_groth16::VerifyingKey;
+	use ark_serialize::CanonicalSerialize;
+	use wasm_bindgen_test::*;
+
+	use super::*;
+
+	fn vanchor_params() -> CircuitParams {
+		CircuitParams {
+			protocol: "vanchor".to_string(),
+			curve: "Bn254".to_string(),
+			width: 5,
+			exponentiation: 5,
+			tree_depth: 30,
+			anchor_count: 2,
+			input_count: Some(2),
+		}
+	}
+
+	#[wasm_bindgen_test]
+	fn should_register_keys_matching_their_hashes() {
+		let proving_key = vec![7u8; 100];
+		let mut verifying_key = Vec::new();
+		let vk = VerifyingKey::<Bn254> {
+			gamma_abc_g1: vec![Default::default()],
+			..Default::default()
+		};
+		vk.serialize(&mut verifying_key).unwrap();
+		let hashes = KeyHashes {
+			proving_key: format!("0x{}", hex::encode(keccak256(&proving_key))),
+			verifying_key: hex::encode(keccak256(&verifying_key)),
+		};
+
+		let mut registry = KeyRegistry::new();
+		let params = vanchor_params();
+		assert_eq!(registry.lookup(&params).unwrap(), None);
+		registry.add(&params, &hashes).unwrap();
+		assert_eq!(params.to_string(), "vanchor-Bn254-x5-5-d30-m2-i2");
+		let expected = registry.lookup(&params).unwrap().unwrap();
+		assert_eq!(expected.verifying_key, format!("0x{}", hashes.verifying_key));
+		assert_eq!(registry.proving_key(&params).unwrap(), None);
+
+		assert_eq!(
+			registry.register(&params, proving_key.clone()).unwrap(),
+			KeyKind::ProvingKey
+		);
+		assert_eq!(
+			registry.register(&params, verifying_key.clone()).unwrap(),
+			KeyKind::VerifyingKey
+		);
+		assert_eq!(registry.proving_key(&params).unwrap(), Some(&proving_key[..]));
+		assert_eq!(registry.verifying_key(&params).unwrap(), Some(&verifying_key[..]));
+
+		// Other bytes and other circuits are rejected
+		let error = registry.register(&params, vec![8u8; 100]).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::InvalidProvingKey);
+		let other_circuit = CircuitParams {
+			input_count: Some(16),
+			..vanchor_params()
+		};
+		let error = registry.register(&other_circuit, proving_key).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::UnsupportedParameterCombination);
+	}
+
+	#[wasm_bindgen_test]
+	fn should_reject_unsupported_circuit_params() {
+		let invalid = |params: CircuitParams| params.parse().unwrap_err().code;
+		let width = CircuitParams {
+			width: 3,
+			..vanchor_params()
+		};
+		assert_eq!(invalid(width), OpStatusCode::UnsupportedParameterCombination);
+		let depth = CircuitParams {
+			tree_depth: 31,
+			..vanchor_params()
+		};
+		assert_eq!(invalid(depth), OpStatusCode::UnsupportedParameterCombination);
+		let inputs = CircuitParams {
+			input_count: None,
+			..vanchor_params()
+		};
+		assert_eq!(invalid(inputs), OpStatusCode::UnsupportedParameterCombination);
+		let protocol = CircuitParams {
+			protocol: "tornado".to_string(),
+			..vanchor_params()
+		};
+		assert_eq!(invalid(protocol), OpStatusCode::InvalidNoteProtocol);
+
+		let mut registry = KeyRegistry::new();
+		let hashes = KeyHashes {
+			proving_key: "0x1234".to_string(),
+			verifying_key: format!("0x{}", hex::encode([0u8; 32])),
+		};
+		let error = registry.add(&vanchor_params(), &hashes).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::InvalidProvingKey);
+	}
+}
@@ -1,10 +1,22 @@
-use crate::proof::truncate_and_pad;
-use crate::types::{Backend, Curve, OpStatusCode, OperationError};
-use crate::{MixerR1CSProverBls381_30, MixerR1CSProverBn254_30, DEFAULT_LEAF};
+use crate::address::AccountEncoding;
+use crate::note::mixer::get_leaf_with_private_raw;
+use crate::note::versioning::NoteDefaults;
+use crate::proof::witness::Witness;
+use crate::proof::{ensure_proving_backend, ensure_supported_circuit, ensure_tree_depth, prover_error_cause};
+use crate::types::{
+	Backend, ChainType, Curve, HashFunction, NoteProtocol, NoteVersion, OpStatusCode, OperationError, StringArray,
+};
+use crate::utils::ct_eq;
+use crate::{DEFAULT_LEAF, TREE_HEIGHT};
+use ark_bls12_381::{Bls12_381, Fr as Bls381Fr};
+use ark_bn254::{Bn254, Fr as Bn254Fr};
+use arkworks_setups::r1cs::mixer::MixerR1CSProver;
 use arkworks_setups::{Curve as ArkCurve, MixerProver};
 use js_sys::{Array, JsString, Uint8Array};
-use rand::rngs::OsRng;
+use parity_scale_codec::{Decode, Encode};
+use rand::{CryptoRng, RngCore};
 use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
 
 #[allow(clippy::unused_unit)]
 #[wasm_bindgen]
@@ -44,13 +56,13 @@ impl MixerProof {
 
 	#[wasm_bindgen(getter)]
 	#[wasm_bindgen(js_name = publicInputs)]
-	pub fn public_inputs_raw(&self) -> Array {
+	pub fn public_inputs_raw(&self) -> StringArray {
 		let inputs: Array = self
 			.public_inputs
 			.iter()
 			.map(|x| JsString::from(hex::encode(x)))
 			.collect();
-		inputs
+		inputs.unchecked_into()
 	}
 
 	#[wasm_bindgen(getter)]
@@ -77,6 +89,76 @@ pub struct MixerProofPayload {
 	pub chain_id: u128,
 	pub leaves: Vec<Vec<u8>>,
 	pub leaf_index: u64,
+	pub tree_depth: usize,
+	/// Seed of the prover's RNG, `OsRng` is used when it's not set
+	pub rng_seed: Option<[u8; 32]>,
+}
+
+/// SCALE encoded form of [`MixerProofPayload`]
+#[derive(Encode, Decode)]
+pub(crate) struct MixerProofPayloadState {
+	exponentiation: i8,
+	width: u64,
+	curve: Curve,
+	backend: Backend,
+	secret: Vec<u8>,
+	nullifier: Vec<u8>,
+	recipient: Vec<u8>,
+	relayer: Vec<u8>,
+	pk: Vec<u8>,
+	refund: u128,
+	fee: u128,
+	chain_id: u128,
+	leaves: Vec<Vec<u8>>,
+	leaf_index: u64,
+	tree_depth: u64,
+	rng_seed: Option<[u8; 32]>,
+}
+
+impl From<&MixerProofPayload> for MixerProofPayloadState {
+	fn from(payload: &MixerProofPayload) -> Self {
+		Self {
+			exponentiation: payload.exponentiation,
+			width: payload.width as u64,
+			curve: payload.curve,
+			backend: payload.backend,
+			secret: payload.secret.clone(),
+			nullifier: payload.nullifier.clone(),
+			recipient: payload.recipient.clone(),
+			relayer: payload.relayer.clone(),
+			pk: payload.pk.clone(),
+			refund: payload.refund,
+			fee: payload.fee,
+			chain_id: payload.chain_id,
+			leaves: payload.leaves.clone(),
+			leaf_index: payload.leaf_index,
+			tree_depth: payload.tree_depth as u64,
+			rng_seed: payload.rng_seed,
+		}
+	}
+}
+
+impl From<MixerProofPayloadState> for MixerProofPayload {
+	fn from(state: MixerProofPayloadState) -> Self {
+		Self {
+			exponentiation: state.exponentiation,
+			width: state.width as usize,
+			curve: state.curve,
+			backend: state.backend,
+			secret: state.secret,
+			nullifier: state.nullifier,
+			recipient: state.recipient,
+			relayer: state.relayer,
+			pk: state.pk,
+			refund: state.refund,
+			fee: state.fee,
+			chain_id: state.chain_id,
+			leaves: state.leaves,
+			leaf_index: state.leaf_index,
+			tree_depth: state.tree_depth as usize,
+			rng_seed: state.rng_seed,
+		}
+	}
 }
 
 #[derive(Debug, Clone, Default)]
@@ -95,27 +177,167 @@ pub struct MixerProofInput {
 	pub chain_id: Option<u128>,
 	pub leaves: Option<Vec<Vec<u8>>>,
 	pub leaf_index: Option<u64>,
+	pub tree_depth: Option<usize>,
+	pub rng_seed: Option<[u8; 32]>,
+	pub recipient_encoding: Option<AccountEncoding>,
+	pub relayer_encoding: Option<AccountEncoding>,
+	/// Type of the target chain, picks the default account encoding
+	pub chain_type: Option<ChainType>,
+	/// Leaf of the note being spent, set along with the secrets
+	pub commitment: Option<Vec<u8>>,
+	/// Find the leaf index by looking the commitment up in the leaves, used
+	/// when `leaf_index` isn't set
+	pub discover_leaf_index: bool,
+	/// Highest refund the relayer policy allows, in the native token
+	pub max_refund: Option<u128>,
+	/// The pool pays out the native token itself, its contract takes no
+	/// refund
+	pub native_token_pool: bool,
+}
+
+/// Check the refund, in base units of the native token, against the cap of
+/// the contract and the relayer policy
+pub fn check_refund(refund: u128, max_refund: Option<u128>, native_token_pool: bool) -> Result<(), OperationError> {
+	if native_token_pool && refund != 0 {
+		let message = format!("Pools of the native token take no refund, got a refund of {}", refund);
+		let mut error = OperationError::new_with_message(OpStatusCode::InvalidRefund, message);
+		error.data = Some("{\"maxRefund\":\"0\"}".to_string());
+		return Err(error);
+	}
+	match max_refund {
+		Some(max_refund) if refund > max_refund => {
+			let message = format!("The refund {} exceeds the relayer maximum of {}", refund, max_refund);
+			let mut error = OperationError::new_with_message(OpStatusCode::InvalidRefund, message);
+			error.data = Some(format!("{{\"maxRefund\":\"{}\"}}", max_refund));
+			Err(error)
+		}
+		_ => Ok(()),
+	}
+}
+
+/// Position of `commitment` in `leaves`, every leaf is compared in constant
+/// time so the lookup doesn't tell which deposit is being spent
+pub fn find_leaf_index(leaves: &[Vec<u8>], commitment: &[u8]) -> Result<u64, OperationError> {
+	let mut found = None;
+	for (index, leaf) in leaves.iter().enumerate() {
+		if ct_eq(leaf, commitment) && found.is_none() {
+			found = Some(index as u64);
+		}
+	}
+	found.ok_or_else(|| {
+		OperationError::new_with_message(
+			OpStatusCode::NotFoundInLeaves,
+			format!("The note's leaf isn't one of the {} leaves", leaves.len()),
+		)
+	})
 }
 
 impl MixerProofInput {
+	/// Checks the input without building it, collecting an error for every
+	/// missing or invalid field instead of failing on the first one
+	pub fn validate(&self) -> Vec<OperationError> {
+		let mut errors: Vec<OperationError> = Vec::new();
+		let mut missing = |is_set: bool, code: OpStatusCode, field: &str| {
+			if !is_set {
+				errors.push(OperationError::new_with_message(
+					code,
+					format!("`{}` is not set", field),
+				));
+			}
+		};
+		missing(self.pk.is_some(), OpStatusCode::InvalidProvingKey, "pk");
+		missing(self.recipient.is_some(), OpStatusCode::InvalidRecipient, "recipient");
+		missing(self.relayer.is_some(), OpStatusCode::InvalidRelayer, "relayer");
+		missing(
+			self.leaf_index.is_some() || self.discover_leaf_index,
+			OpStatusCode::InvalidLeafIndex,
+			"leafIndex",
+		);
+		missing(self.secret.is_some(), OpStatusCode::InvalidNoteSecrets, "secret");
+		missing(self.nullifier.is_some(), OpStatusCode::InvalidNoteSecrets, "nullifier");
+		missing(self.leaves.is_some(), OpStatusCode::InvalidLeaves, "leaves");
+		missing(self.fee.is_some(), OpStatusCode::InvalidFee, "fee");
+		missing(self.refund.is_some(), OpStatusCode::InvalidRefund, "refund");
+		if let Some(refund) = self.refund {
+			if let Err(e) = check_refund(refund, self.max_refund, self.native_token_pool) {
+				errors.push(e);
+			}
+		}
+
+		if let (Some(leaves), Some(leaf_index)) = (&self.leaves, self.leaf_index) {
+			if leaf_index >= leaves.len() as u64 {
+				let message = format!(
+					"`leafIndex` {} is out of bounds for {} leaves",
+					leaf_index,
+					leaves.len()
+				);
+				errors.push(OperationError::new_with_message(
+					OpStatusCode::InvalidLeafIndex,
+					message,
+				));
+			}
+		}
+		if let (Some(leaves), Some(commitment), None, true) = (
+			&self.leaves,
+			&self.commitment,
+			self.leaf_index,
+			self.discover_leaf_index,
+		) {
+			if let Err(e) = find_leaf_index(leaves, commitment) {
+				errors.push(e);
+			}
+		}
+		if let Err(e) = ensure_proving_backend(self.backend.unwrap_or(Backend::Arkworks)) {
+			errors.push(e);
+		}
+		let defaults = NoteDefaults::of(NoteVersion::V1, NoteProtocol::Mixer);
+		if let Err(e) = ensure_supported_circuit(
+			NoteProtocol::Mixer,
+			self.curve.unwrap_or(defaults.curve),
+			self.width.unwrap_or(defaults.width),
+			self.exponentiation.unwrap_or(defaults.exponentiation),
+		) {
+			errors.push(e);
+		}
+		errors
+	}
+
 	pub fn build(self) -> Result<MixerProofPayload, OperationError> {
 		let pk = self.pk.ok_or(OpStatusCode::InvalidProvingKey)?;
 		let recipient = self.recipient.ok_or(OpStatusCode::InvalidRecipient)?;
 		let relayer = self.relayer.ok_or(OpStatusCode::InvalidRelayer)?;
-		let leaf_index = self.leaf_index.ok_or(OpStatusCode::InvalidLeafIndex)?;
 		let secret = self.secret.ok_or(OpStatusCode::InvalidNoteSecrets)?;
 		let nullifier = self.nullifier.ok_or(OpStatusCode::InvalidNoteSecrets)?;
 		let leaves = self.leaves.ok_or(OpStatusCode::InvalidLeaves)?;
+		let leaf_index = match (self.leaf_index, &self.commitment) {
+			(Some(leaf_index), _) => leaf_index,
+			(None, Some(commitment)) if self.discover_leaf_index => find_leaf_index(&leaves, commitment)?,
+			_ => return Err(OpStatusCode::InvalidLeafIndex.into()),
+		};
 		let fee = self.fee.ok_or(OpStatusCode::InvalidFee)?;
 		let refund = self.refund.ok_or(OpStatusCode::InvalidRefund)?;
+		check_refund(refund, self.max_refund, self.native_token_pool)?;
 
-		let exponentiation = self.exponentiation.unwrap_or(5);
-		let width = self.width.unwrap_or(3);
-		let curve = self.curve.unwrap_or(Curve::Bn254);
+		let defaults = NoteDefaults::of(NoteVersion::V1, NoteProtocol::Mixer);
+		let exponentiation = self.exponentiation.unwrap_or(defaults.exponentiation);
+		let width = self.width.unwrap_or(defaults.width);
+		let curve = self.curve.unwrap_or(defaults.curve);
 		let backend = self.backend.unwrap_or(Backend::Arkworks);
+		let tree_depth = self.tree_depth.unwrap_or(TREE_HEIGHT);
+		ensure_supported_circuit(NoteProtocol::Mixer, curve, width, exponentiation)?;
 
-		let processed_relayer = truncate_and_pad(&relayer);
-		let processed_recipient = truncate_and_pad(&recipient);
+		let default_encoding = match self.chain_type {
+			Some(ChainType::Evm) => AccountEncoding::Evm,
+			_ => AccountEncoding::Substrate,
+		};
+		let processed_relayer = self
+			.relayer_encoding
+			.unwrap_or(default_encoding)
+			.encode(&relayer, OpStatusCode::InvalidRelayer)?;
+		let processed_recipient = self
+			.recipient_encoding
+			.unwrap_or(default_encoding)
+			.encode(&recipient, OpStatusCode::InvalidRecipient)?;
 
 		Ok(MixerProofPayload {
 			exponentiation,
@@ -132,11 +354,69 @@ impl MixerProofInput {
 			chain_id: 0,
 			leaves,
 			leaf_index,
+			tree_depth,
+			rng_seed: self.rng_seed,
 		})
 	}
 }
 
-pub fn create_proof(mixer_proof_input: MixerProofPayload, rng: &mut OsRng) -> Result<MixerProof, OperationError> {
+/// The leaf of the secrets has to be the one at `leaf_index`, a path to
+/// another leaf gives a proof that doesn't verify
+fn ensure_leaf_in_tree(payload: &MixerProofPayload) -> Result<(), OperationError> {
+	let leaf_index = payload.leaf_index;
+	let not_in_tree = |message: String| {
+		let mut error = OperationError::new_with_message(OpStatusCode::LeafNotInTree, message);
+		error.data = Some(format!(
+			"{{\"leafIndex\":{},\"leafCount\":{}}}",
+			leaf_index,
+			payload.leaves.len()
+		));
+		error
+	};
+	let leaf = payload.leaves.get(leaf_index as usize).ok_or_else(|| {
+		not_in_tree(format!(
+			"Leaf index {} is out of bounds for {} leaves",
+			leaf_index,
+			payload.leaves.len()
+		))
+	})?;
+	let secrets = [&payload.secret[..], &payload.nullifier[..]].concat();
+	let note_leaf = get_leaf_with_private_raw(
+		payload.curve,
+		payload.width,
+		payload.exponentiation,
+		HashFunction::Poseidon,
+		&secrets,
+	)?;
+	if !ct_eq(leaf, &note_leaf.leaf_bytes) {
+		return Err(not_in_tree(format!(
+			"The leaf at index {} isn't the leaf of the note",
+			leaf_index
+		)));
+	}
+	Ok(())
+}
+
+pub fn create_proof<R: RngCore + CryptoRng>(
+	mixer_proof_input: MixerProofPayload,
+	rng: &mut R,
+) -> Result<MixerProof, OperationError> {
+	let pk_len = mixer_proof_input.pk.len();
+	let proof_error = |e: ark_crypto_primitives::Error| {
+		prover_error_cause(&e, pk_len).unwrap_or_else(|| {
+			let mut error: OperationError = OpStatusCode::InvalidProofParameters.into();
+			error.data = Some(e.to_string());
+			error
+		})
+	};
+	ensure_supported_circuit(
+		NoteProtocol::Mixer,
+		mixer_proof_input.curve,
+		mixer_proof_input.width,
+		mixer_proof_input.exponentiation,
+	)?;
+	ensure_leaf_in_tree(&mixer_proof_input)?;
+
 	let MixerProofPayload {
 		recipient,
 		relayer,
@@ -151,11 +431,15 @@ pub fn create_proof(mixer_proof_input: MixerProofPayload, rng: &mut OsRng) -> Re
 		curve,
 		exponentiation,
 		width,
+		tree_depth,
 		..
 	} = mixer_proof_input;
+	ensure_proving_backend(backend)?;
+	ensure_tree_depth(tree_depth)?;
+	let _span = tracing::info_span!("mixer_proof", %curve, tree_depth).entered();
 
-	let mixer_proof = match (backend, curve, exponentiation, width) {
-		(Backend::Arkworks, Curve::Bn254, 5, 3) => MixerR1CSProverBn254_30::create_proof(
+	let mixer_proof = with_tree_depth!(tree_depth, DEPTH => match (backend, curve, exponentiation, width) {
+		(Backend::Arkworks, Curve::Bn254, 5, 3) => MixerR1CSProver::<Bn254, DEPTH>::create_proof(
 			ArkCurve::Bn254,
 			secret,
 			nullifier,
@@ -169,7 +453,7 @@ pub fn create_proof(mixer_proof_input: MixerProofPayload, rng: &mut OsRng) -> Re
 			DEFAULT_LEAF,
 			rng,
 		),
-		(Backend::Arkworks, Curve::Bls381, 5, 3) => MixerR1CSProverBls381_30::create_proof(
+		(Backend::Arkworks, Curve::Bls381, 5, 3) => MixerR1CSProver::<Bls12_381, DEPTH>::create_proof(
 			ArkCurve::Bls381,
 			secret,
 			nullifier,
@@ -184,12 +468,8 @@ pub fn create_proof(mixer_proof_input: MixerProofPayload, rng: &mut OsRng) -> Re
 			rng,
 		),
 		_ => return Err(OpStatusCode::UnsupportedParameterCombination.into()),
-	}
-	.map_err(|e| {
-		let mut error: OperationError = OpStatusCode::InvalidProofParameters.into();
-		error.data = Some(e.to_string());
-		error
-	})?;
+	})
+	.map_err(proof_error)?;
 
 	Ok(MixerProof {
 		proof: mixer_proof.proof,
@@ -199,3 +479,72 @@ pub fn create_proof(mixer_proof_input: MixerProofPayload, rng: &mut OsRng) -> Re
 		leaf: mixer_proof.leaf_raw,
 	})
 }
+
+/// Witness of the withdrawal, from the circuit `create_proof` proves
+pub fn create_witness(mixer_proof_input: MixerProofPayload) -> Result<Witness, OperationError> {
+	let circuit_error = |e: ark_crypto_primitives::Error| {
+		OperationError::new_with_message(OpStatusCode::WitnessGenerationFailed, e.to_string())
+	};
+	ensure_supported_circuit(
+		NoteProtocol::Mixer,
+		mixer_proof_input.curve,
+		mixer_proof_input.width,
+		mixer_proof_input.exponentiation,
+	)?;
+	ensure_leaf_in_tree(&mixer_proof_input)?;
+
+	let MixerProofPayload {
+		recipient,
+		relayer,
+		leaves,
+		leaf_index,
+		fee,
+		refund,
+		secret,
+		nullifier,
+		backend,
+		curve,
+		exponentiation,
+		width,
+		tree_depth,
+		..
+	} = mixer_proof_input;
+	ensure_proving_backend(backend)?;
+	ensure_tree_depth(tree_depth)?;
+
+	with_tree_depth!(tree_depth, DEPTH => match (backend, curve, exponentiation, width) {
+		(Backend::Arkworks, Curve::Bn254, 5, 3) => {
+			let (circuit, ..) = MixerR1CSProver::<Bn254, DEPTH>::setup_circuit_with_privates_raw(
+				ArkCurve::Bn254,
+				secret,
+				nullifier,
+				&leaves,
+				leaf_index,
+				recipient,
+				relayer,
+				fee,
+				refund,
+				DEFAULT_LEAF,
+			)
+			.map_err(circuit_error)?;
+			Witness::synthesize::<Bn254Fr, _>(circuit)
+		}
+		(Backend::Arkworks, Curve::Bls381, 5, 3) => {
+			let (circuit, ..) = MixerR1CSProver::<Bls12_381, DEPTH>::setup_circuit_with_privates_raw(
+				ArkCurve::Bls381,
+				secret,
+				nullifier,
+				&leaves,
+				leaf_index,
+				recipient,
+				relayer,
+				fee,
+				refund,
+				DEFAULT_LEAF,
+			)
+			.map_err(circuit_error)?;
+			Witness::synthesize::<Bls381Fr, _>(circuit)
+		}
+		_ => Err(OpStatusCode::UnsupportedParameterCombination.into()),
+	})
+}
@@ -1,62 +1,484 @@
 #![allow(clippy::unused_unit)]
 
-use crate::proof::mixer::{MixerProof, MixerProofInput, MixerProofPayload};
-use crate::proof::vanchor::{VAnchorProof, VAnchorProofInput, VAnchorProofPayload};
+use crate::proof::debug::{record_last_bundle, DebugBundle};
+use crate::proof::ext_data::ExtData;
+use crate::proof::mixer::{MixerProof, MixerProofInput, MixerProofPayload, MixerProofPayloadState};
+use crate::proof::refresh::{PartialWithdrawal, RefreshCommitment};
+use crate::proof::telemetry::TelemetryRecorder;
+use crate::proof::vanchor::{VAnchorProof, VAnchorProofInput, VAnchorProofPayload, VAnchorProofPayloadState};
 use core::convert::TryFrom;
 
-use ark_bls12_381::Bls12_381;
+use ark_bls12_381::{Bls12_381, Fr as Bls381Fr};
 use ark_bn254::{Bn254, Fr as Bn254Fr};
 use ark_ff::{BigInteger, PrimeField};
+use ark_relations::r1cs::SynthesisError;
+use ark_serialize::SerializationError;
 use arkworks_native_gadgets::merkle_tree::SparseMerkleTree;
 use arkworks_native_gadgets::poseidon::Poseidon;
-use arkworks_setups::common::{
-	setup_keys_unchecked, setup_params, setup_tree_and_create_path, verify_unchecked_raw, Leaf,
-};
+use arkworks_setups::common::{setup_keys_unchecked, setup_tree_and_create_path, verify_unchecked_raw, Leaf};
+use arkworks_setups::r1cs::mixer::MixerR1CSProver;
+use arkworks_setups::r1cs::vanchor::VAnchorR1CSProver;
 use arkworks_setups::Curve as ArkCurve;
 use js_sys::{Array, JsString, Uint8Array};
+use parity_scale_codec::{Decode, Encode};
 use rand::rngs::OsRng;
+use rand::{CryptoRng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
 use wasm_bindgen::__rt::std::collections::btree_map::BTreeMap;
 // https://github.com/rustwasm/wasm-bindgen/issues/2231#issuecomment-656293288
 use wasm_bindgen::convert::FromWasmAbi;
 use wasm_bindgen::prelude::*;
-
+use wasm_bindgen::JsCast;
+
+use crate::address::{parse_evm_address, ss58_decode, AccountEncoding};
+use crate::field::is_canonical;
+use crate::hash::poseidon_params;
+use crate::memory::{LiveObject, ObjectKind};
+use crate::merkle::{ensure_leaf_count, leaves_from_js, leaves_from_packed, MerkleTree};
+use crate::note::amount::{amount_field_error, parse_amount_field, parse_signed_amount};
 use crate::note::JsNote;
+use crate::proof::groth16::parse_curve;
 use crate::types::{
-	Backend, Curve, Indices, Leaves, NoteProtocol, OpStatusCode, OperationError, Protocol, Uint8Arrayx32, WasmCurve,
+	parse_chain_id, Backend, ChainType, Curve, ExtDataEncoding, HashFunction, Indices, Leaves, NoteProtocol,
+	OpStatusCode, OperationError, OperationErrorArray, Protocol, TargetRuntime, TypedChainId, Uint8Arrayx32, WasmCurve,
+	WasmExtDataEncoding, WasmPublicInputs, WasmTargetRuntime,
 };
+use crate::utils::monotonic_now;
 use crate::utxo::JsUtxo;
-use crate::{
-	MixerR1CSProverBn254_30, VAnchorR1CSProverBn254_30_16_16_2, VAnchorR1CSProverBn254_30_16_2_2,
-	VAnchorR1CSProverBn254_30_2_16_2, VAnchorR1CSProverBn254_30_2_2_2, DEFAULT_LEAF, TREE_HEIGHT,
-};
+use crate::{DEFAULT_LEAF, INS_16, INS_2, OUTS_2, TREE_HEIGHT};
+
+/// Merkle tree depths there is a circuit setup for, the depth is part of the
+/// proving key so it has to match the tree of the target pallet/contract
+pub const SUPPORTED_TREE_DEPTHS: [usize; 4] = [20, 26, 30, 32];
+/// `(protocol, curve, width, exponentiation)` of the circuits proofs can be
+/// generated for
+pub const SUPPORTED_CIRCUITS: [(NoteProtocol, Curve, usize, i8); 3] = [
+	(NoteProtocol::Mixer, Curve::Bn254, 3, 5),
+	(NoteProtocol::Mixer, Curve::Bls381, 3, 5),
+	(NoteProtocol::VAnchor, Curve::Bn254, 5, 5),
+];
+
+/// Evaluate `$body` with the const `$depth` bound to `$tree_depth`, the tree
+/// depth must have been checked with `ensure_tree_depth` beforehand
+macro_rules! with_tree_depth {
+	($tree_depth:expr, $depth:ident => $body:expr) => {
+		match $tree_depth {
+			20 => {
+				const $depth: usize = 20;
+				$body
+			}
+			26 => {
+				const $depth: usize = 26;
+				$body
+			}
+			30 => {
+				const $depth: usize = 30;
+				$body
+			}
+			32 => {
+				const $depth: usize = 32;
+				$body
+			}
+			_ => unreachable!("unsupported tree depth"),
+		}
+	};
+}
+
+/// Evaluate `$body` with the const `$anchors` bound to `$anchor_count`, the
+/// root set size must be one of `vanchor::SUPPORTED_VANCHOR_COUNT`
+macro_rules! with_anchor_count {
+	($anchor_count:expr, $anchors:ident => $body:expr) => {
+		match $anchor_count {
+			2 => {
+				const $anchors: usize = crate::ANCHOR_COUNT_2;
+				$body
+			}
+			4 => {
+				const $anchors: usize = crate::ANCHOR_COUNT_4;
+				$body
+			}
+			8 => {
+				const $anchors: usize = crate::ANCHOR_COUNT_8;
+				$body
+			}
+			16 => {
+				const $anchors: usize = crate::ANCHOR_COUNT_16;
+				$body
+			}
+			_ => unreachable!("unsupported anchor count"),
+		}
+	};
+}
 
+pub mod aggregate;
+pub mod anchor;
+pub mod ceremony;
+pub mod circom;
+pub mod config;
+pub mod debug;
+pub mod delegate;
 pub mod ext_data;
+pub mod fee;
+pub mod groth16;
+pub mod key_registry;
 pub mod mixer;
+pub mod refresh;
+pub mod relayer;
+pub mod root_window;
+pub mod telemetry;
 pub mod vanchor;
+pub mod verifying_key;
+pub mod witness;
 
 #[cfg(test)]
 mod test;
 
 #[cfg(test)]
-mod test_utils;
+pub mod test_utils;
+
+/// RNG of the prover, `OsRng` unless a seed was set on the proof input in
+/// which case proofs are reproducible byte for byte
+pub enum ProverRng {
+	Os(OsRng),
+	Seeded(Box<ChaCha20Rng>),
+}
+
+impl ProverRng {
+	pub fn new(seed: Option<[u8; 32]>) -> Self {
+		match seed {
+			Some(seed) => ProverRng::Seeded(Box::new(ChaCha20Rng::from_seed(seed))),
+			None => ProverRng::Os(OsRng),
+		}
+	}
+}
+
+impl RngCore for ProverRng {
+	fn next_u32(&mut self) -> u32 {
+		match self {
+			ProverRng::Os(rng) => rng.next_u32(),
+			ProverRng::Seeded(rng) => rng.next_u32(),
+		}
+	}
+
+	fn next_u64(&mut self) -> u64 {
+		match self {
+			ProverRng::Os(rng) => rng.next_u64(),
+			ProverRng::Seeded(rng) => rng.next_u64(),
+		}
+	}
+
+	fn fill_bytes(&mut self, dest: &mut [u8]) {
+		match self {
+			ProverRng::Os(rng) => rng.fill_bytes(dest),
+			ProverRng::Seeded(rng) => rng.fill_bytes(dest),
+		}
+	}
+
+	fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+		match self {
+			ProverRng::Os(rng) => rng.try_fill_bytes(dest),
+			ProverRng::Seeded(rng) => rng.try_fill_bytes(dest),
+		}
+	}
+}
+
+impl CryptoRng for ProverRng {}
 
 pub fn truncate_and_pad(t: &[u8]) -> Vec<u8> {
-	let mut truncated_bytes = t[..20].to_vec();
-	truncated_bytes.extend_from_slice(&[0u8; 12]);
+	let mut truncated_bytes = t[..t.len().min(20)].to_vec();
+	truncated_bytes.resize(32, 0);
 	truncated_bytes
 }
 
+/// Only the Arkworks circuits are proven from a proof input, Circom circuits
+/// are proven from their witness with a [`circom::CircomProvingKey`]
+pub fn ensure_proving_backend(backend: Backend) -> Result<(), OperationError> {
+	match backend {
+		Backend::Arkworks => Ok(()),
+		Backend::Circom => {
+			let message = "Circom circuits are proven from the witness of their witness calculator, use \
+			               CircomProvingKey.prove"
+				.to_string();
+			Err(OperationError::new_with_message(
+				OpStatusCode::UnsupportedBackend,
+				message,
+			))
+		}
+	}
+}
+
+pub fn ensure_tree_depth(tree_depth: usize) -> Result<(), OperationError> {
+	if SUPPORTED_TREE_DEPTHS.contains(&tree_depth) {
+		return Ok(());
+	}
+	let message = format!(
+		"Tree depth {} isn't supported, the supported depths are {:?}",
+		tree_depth, SUPPORTED_TREE_DEPTHS
+	);
+	Err(OperationError::new_with_message(
+		OpStatusCode::UnsupportedParameterCombination,
+		message,
+	))
+}
+
+pub fn ensure_supported_circuit(
+	protocol: NoteProtocol,
+	curve: Curve,
+	width: usize,
+	exponentiation: i8,
+) -> Result<(), OperationError> {
+	if SUPPORTED_CIRCUITS.contains(&(protocol, curve, width, exponentiation)) {
+		return Ok(());
+	}
+	let supported = SUPPORTED_CIRCUITS
+		.iter()
+		.filter(|circuit| circuit.0 == protocol)
+		.map(|(_, curve, width, exponentiation)| format!("{} x{}^{}", curve, width, exponentiation))
+		.collect::<Vec<_>>()
+		.join(", ");
+	let message = format!(
+		"There is no {} circuit for curve {}, width {} and exponentiation {}, the supported circuits are {}",
+		protocol, curve, width, exponentiation, supported
+	);
+	Err(OperationError::new_with_message(
+		OpStatusCode::UnsupportedParameterCombination,
+		message,
+	))
+}
+
+/// Whether the 32 bytes `value` is below the modulus of `curve`'s scalar
+/// field, a public input that isn't would be reduced by the verifier
+pub fn is_field_element(curve: Curve, value: &[u8]) -> bool {
+	match curve {
+		Curve::Bn254 => is_canonical::<Bn254Fr>(value),
+		Curve::Bls381 => is_canonical::<Bls381Fr>(value),
+	}
+}
+
+/// The error of the arkworks prover when its cause is known: a proving key
+/// of `pk_len` bytes that doesn't deserialize, or a circuit that can't be
+/// synthesized. Other errors are left to the caller
+pub fn prover_error_cause(error: &ark_crypto_primitives::Error, pk_len: usize) -> Option<OperationError> {
+	if let Some(e) = error.downcast_ref::<SerializationError>() {
+		let message = format!("The proving key doesn't deserialize: {}", e);
+		let mut error = OperationError::new_with_message(OpStatusCode::InvalidProvingKey, message);
+		error.data = Some(format!("{{\"pkBytes\":{}}}", pk_len));
+		return Some(error);
+	}
+	error.downcast_ref::<SynthesisError>().map(|e| {
+		let mut error = OperationError::new_with_message(OpStatusCode::WitnessGenerationFailed, e.to_string());
+		error.data = Some("{\"phase\":\"prove\"}".to_string());
+		error
+	})
+}
+
+/// Check the root set of an anchor proof: `expected_m` roots, each 32 bytes
+/// and an element of `curve`'s scalar field, a root above the modulus is a
+/// `PublicInputOverflow`. The error `data` holds the index of the first
+/// invalid root.
+pub fn validate_roots(roots: &[Vec<u8>], expected_m: usize, curve: Curve) -> Result<(), OperationError> {
+	if roots.len() != expected_m {
+		let message = format!("Expected {} roots, got {}", expected_m, roots.len());
+		return Err(OperationError::new_with_message(OpStatusCode::InvalidRoots, message));
+	}
+	for (index, root) in roots.iter().enumerate() {
+		let (code, problem) = if root.len() != 32 {
+			(
+				OpStatusCode::InvalidRoots,
+				format!("is {} bytes instead of 32", root.len()),
+			)
+		} else if !is_field_element(curve, root) {
+			let problem = format!("is not an element of the {} scalar field", curve);
+			(OpStatusCode::PublicInputOverflow, problem)
+		} else {
+			continue;
+		};
+		let message = format!("Root {} {}", index, problem);
+		let mut error = OperationError::new_with_message(code, message);
+		error.data = Some(format!("{{\"index\":{}}}", index));
+		return Err(error);
+	}
+	Ok(())
+}
+
+/// The `m` roots of an anchor proof: the local root first, then the roots of
+/// the neighbors, padded with copies of the local root when the bridge has
+/// fewer than `m - 1` neighbors. The roots are checked as by
+/// [`validate_roots`].
+pub fn pack_roots(
+	local_root: &[u8],
+	neighbor_roots: &[Vec<u8>],
+	m: usize,
+	curve: Curve,
+) -> Result<Vec<Vec<u8>>, OperationError> {
+	if m == 0 || neighbor_roots.len() > m - 1 {
+		let message = format!(
+			"{} neighbor roots don't fit in a set of {} roots",
+			neighbor_roots.len(),
+			m
+		);
+		return Err(OperationError::new_with_message(OpStatusCode::InvalidRoots, message));
+	}
+	let mut roots = Vec::with_capacity(m);
+	roots.push(local_root.to_vec());
+	roots.extend_from_slice(neighbor_roots);
+	roots.resize(m, local_root.to_vec());
+	validate_roots(&roots, m, curve)?;
+	Ok(roots)
+}
+
+pub(crate) fn roots_from_js(roots: &Leaves) -> Vec<Vec<u8>> {
+	Array::from(roots)
+		.iter()
+		.map(|root| Uint8Array::new(&root).to_vec())
+		.collect()
+}
+
+/// Check `roots` before proving, see [`validate_roots`]
+#[wasm_bindgen(js_name = validateRoots)]
+pub fn js_validate_roots(roots: Leaves, expected_m: usize, curve: Option<WasmCurve>) -> Result<(), JsValue> {
+	validate_roots(&roots_from_js(&roots), expected_m, parse_curve(curve)?)?;
+	Ok(())
+}
+
+/// The root set for `setRoots` of a proof with `m` roots, from the root of
+/// the local tree and the latest roots of its neighbors, see [`pack_roots`]
+#[wasm_bindgen(js_name = packRoots)]
+pub fn js_pack_roots(
+	local_root: Uint8Array,
+	neighbor_roots: Leaves,
+	m: usize,
+	curve: Option<WasmCurve>,
+) -> Result<Leaves, JsValue> {
+	let roots = pack_roots(
+		&local_root.to_vec(),
+		&roots_from_js(&neighbor_roots),
+		m,
+		parse_curve(curve)?,
+	)?;
+	let roots: Array = roots.iter().map(|root| Uint8Array::from(root.as_slice())).collect();
+	Ok(roots.unchecked_into())
+}
+
+/// The circuits hash the leaves and the tree with Poseidon, `MiMC220` notes
+/// can only be used to compute their leaves
+pub fn ensure_proving_hash_function(hash_function: HashFunction) -> Result<(), OperationError> {
+	match hash_function {
+		HashFunction::Poseidon => Ok(()),
+		HashFunction::MiMC220 => {
+			let message = "MiMC220 notes can't be proven, the circuits hash with Poseidon".to_string();
+			Err(OperationError::new_with_message(
+				OpStatusCode::UnsupportedParameterCombination,
+				message,
+			))
+		}
+	}
+}
+
 #[derive(Debug, Clone)]
 pub enum ProofOutput {
 	Mixer(MixerProof),
 	VAnchor(VAnchorProof),
 }
 
+impl ProofOutput {
+	/// Public inputs in circuit order, as big endian field elements
+	pub fn public_inputs(&self) -> &[Vec<u8>] {
+		match self {
+			ProofOutput::Mixer(proof) => &proof.public_inputs,
+			ProofOutput::VAnchor(proof) => &proof.public_inputs,
+		}
+	}
+
+	/// Roots the proof was generated against, big endian: the root of the
+	/// mixer tree, or the local root followed by the neighbor roots
+	pub fn roots(&self) -> Vec<Vec<u8>> {
+		match self {
+			ProofOutput::Mixer(proof) => vec![proof.root.clone()],
+			// `public_amount, ext_data_hash, nullifiers.., commitments (2),
+			// chain_id, roots..`
+			ProofOutput::VAnchor(proof) => {
+				let roots_start = proof.input_utxos.len() + 5;
+				proof.public_inputs.get(roots_start..).unwrap_or_default().to_vec()
+			}
+		}
+	}
+
+	/// Public inputs as the `target` verifier takes them, each padded to 32
+	/// bytes.
+	///
+	/// Both runtimes use the circuit order, `[nullifier_hash, root,
+	/// arbitrary_input]` for the mixer and `[public_amount, ext_data_hash,
+	/// nullifiers.., commitments.., chain_id, roots..]` for the VAnchor. The
+	/// Substrate pallets read the elements as little endian arkworks field
+	/// elements while EVM verifiers take big endian `uint256` words.
+	pub fn public_inputs_for(&self, target: TargetRuntime) -> Result<Vec<[u8; 32]>, OperationError> {
+		let protocol_matches = matches!(
+			(self, target),
+			(ProofOutput::Mixer(_), TargetRuntime::SubstrateMixer)
+				| (
+					ProofOutput::VAnchor(_),
+					TargetRuntime::SubstrateVAnchor | TargetRuntime::EvmVAnchor
+				)
+		);
+		if !protocol_matches {
+			let message = match target {
+				TargetRuntime::EvmAnchor => {
+					"EvmAnchor verifies the proofs of the anchor circuit, not of this one".to_string()
+				}
+				_ => format!("{} doesn't verify this proof", target),
+			};
+			return Err(OperationError::new_with_message(
+				OpStatusCode::InvalidTargetRuntime,
+				message,
+			));
+		}
+		encode_public_inputs(self.public_inputs(), target)
+	}
+}
+
+/// `public_inputs`, big endian field elements, padded to 32 bytes in the
+/// byte order of `target`
+pub fn encode_public_inputs(public_inputs: &[Vec<u8>], target: TargetRuntime) -> Result<Vec<[u8; 32]>, OperationError> {
+	public_inputs
+		.iter()
+		.map(|input| {
+			if input.len() > 32 {
+				return Err(OperationError::from(OpStatusCode::InvalidProof));
+			}
+			let mut element = [0u8; 32];
+			element[32 - input.len()..].copy_from_slice(input);
+			if !target.is_evm() {
+				element.reverse();
+			}
+			Ok(element)
+		})
+		.collect()
+}
+
 #[derive(Debug, Clone)]
 #[wasm_bindgen]
 pub struct JsProofOutput {
 	#[wasm_bindgen(skip)]
 	pub inner: ProofOutput,
+	/// When the proof was made, from [`monotonic_now`]
+	#[wasm_bindgen(skip)]
+	pub generated_at: f64,
+}
+
+impl JsProofOutput {
+	pub fn new(inner: ProofOutput) -> Self {
+		Self {
+			inner,
+			generated_at: monotonic_now(),
+		}
+	}
+
+	/// Whether one of the roots of the proof isn't among `current_roots`, the
+	/// roots the chain accepts, so that submitting it would fail
+	pub fn is_likely_stale(&self, current_roots: &[Vec<u8>]) -> bool {
+		self.inner.roots().iter().any(|root| !current_roots.contains(root))
+	}
 }
 
 #[wasm_bindgen]
@@ -89,6 +511,49 @@ impl JsProofOutput {
 			_ => Err(OpStatusCode::InvalidNoteProtocol.into()),
 		}
 	}
+
+	/// Roots the proof was generated against, the local root first
+	#[wasm_bindgen(getter)]
+	pub fn roots(&self) -> Leaves {
+		let roots: Array = self
+			.inner
+			.roots()
+			.iter()
+			.map(|root| Uint8Array::from(root.as_slice()))
+			.collect();
+		Leaves::from(JsValue::from(roots))
+	}
+
+	/// Milliseconds since the epoch when the proof was made, never less than
+	/// the time of an earlier proof
+	#[wasm_bindgen(getter, js_name = generatedAt)]
+	pub fn js_generated_at(&self) -> f64 {
+		self.generated_at
+	}
+
+	/// Whether the chain no longer accepts a root of the proof, given the
+	/// roots of the local and neighbor root histories. A stale proof should
+	/// be generated again rather than submitted
+	#[wasm_bindgen(js_name = isLikelyStale)]
+	pub fn js_is_likely_stale(&self, current_roots: Leaves) -> bool {
+		self.is_likely_stale(&roots_from_js(&current_roots))
+	}
+
+	/// Public inputs for the `target` verifier, `Uint8Array`s for Substrate
+	/// targets and `0x` prefixed `uint256` words for EVM ones
+	#[wasm_bindgen(js_name = publicInputsFor)]
+	pub fn public_inputs_for(&self, target: WasmTargetRuntime) -> Result<WasmPublicInputs, JsValue> {
+		let target: TargetRuntime = JsValue::from(target)
+			.as_string()
+			.ok_or(OpStatusCode::InvalidTargetRuntime)?
+			.parse()?;
+		let inputs = self.inner.public_inputs_for(target)?;
+		let inputs = inputs.iter().map(|input| match target.is_evm() {
+			true => JsValue::from(format!("0x{}", hex::encode(input))),
+			false => Uint8Array::from(&input[..]).into(),
+		});
+		Ok(inputs.collect::<Array>().unchecked_into())
+	}
 }
 
 pub fn generic_of_jsval<T: FromWasmAbi<Abi = u32>>(js: JsValue, classname: &str) -> Result<T, JsValue> {
@@ -136,7 +601,9 @@ impl LeavesMapInput {
 
 	#[wasm_bindgen(js_name=setChainLeaves)]
 	pub fn set_chain_leaves(&mut self, chain_id: u64, leaves: Leaves) -> Result<(), JsValue> {
-		let leaves: Vec<_> = Array::from(&leaves)
+		let leaves = Array::from(&leaves);
+		ensure_leaf_count(leaves.length() as usize)?;
+		let leaves: Vec<_> = leaves
 			.to_vec()
 			.into_iter()
 			.map(|v| Uint8Array::new_with_byte_offset_and_length(&v, 0, 32))
@@ -151,6 +618,19 @@ impl LeavesMapInput {
 
 		Ok(())
 	}
+
+	/// Leaves of `chain_id` packed back to back in one array, `leafSize`
+	/// bytes each, for leaf sets too large for an array of arrays
+	#[wasm_bindgen(js_name=setChainLeavesPacked)]
+	pub fn set_chain_leaves_packed(
+		&mut self,
+		chain_id: u64,
+		leaves: Uint8Array,
+		leaf_size: usize,
+	) -> Result<(), JsValue> {
+		self.leaves.insert(chain_id, leaves_from_packed(&leaves, leaf_size)?);
+		Ok(())
+	}
 }
 
 #[derive(Debug, Clone)]
@@ -159,6 +639,12 @@ pub enum ProofInput {
 	VAnchor(Box<VAnchorProofPayload>),
 }
 
+#[derive(Encode, Decode)]
+enum ProofInputState {
+	Mixer(MixerProofPayloadState),
+	VAnchor(VAnchorProofPayloadState),
+}
+
 impl ProofInput {
 	pub fn mixer_input(&self) -> Result<MixerProofPayload, OperationError> {
 		match self {
@@ -185,6 +671,27 @@ impl ProofInput {
 			}
 		}
 	}
+
+	/// SCALE encode the inputs, so they can be assembled on one machine and
+	/// proven on another (e.g. an air-gapped prover). The encoding holds the
+	/// proving key and the spent secrets, so it must be handled like a note.
+	pub fn serialize(&self) -> Vec<u8> {
+		match self {
+			ProofInput::Mixer(payload) => ProofInputState::Mixer(payload.as_ref().into()),
+			ProofInput::VAnchor(payload) => ProofInputState::VAnchor(payload.as_ref().into()),
+		}
+		.encode()
+	}
+
+	pub fn deserialize(input: &[u8]) -> Result<Self, OperationError> {
+		let state = ProofInputState::decode(&mut &*input)
+			.map_err(|e| OperationError::new_with_message(OpStatusCode::DeserializationFailed, e.to_string()))?;
+		let proof_input = match state {
+			ProofInputState::Mixer(state) => ProofInput::Mixer(Box::new(state.into())),
+			ProofInputState::VAnchor(state) => ProofInput::VAnchor(Box::new(state.try_into()?)),
+		};
+		Ok(proof_input)
+	}
 }
 
 #[allow(unused_macros)]
@@ -200,6 +707,19 @@ pub struct JsProofInput {
 	#[wasm_bindgen(skip)]
 	pub inner: ProofInput,
 }
+
+#[wasm_bindgen]
+impl JsProofInput {
+	pub fn serialize(&self) -> Uint8Array {
+		Uint8Array::from(self.inner.serialize().as_slice())
+	}
+
+	pub fn deserialize(input: Uint8Array) -> Result<JsProofInput, JsValue> {
+		let inner = ProofInput::deserialize(&input.to_vec())?;
+		Ok(Self { inner })
+	}
+}
+
 #[derive(Debug, Clone)]
 pub enum ProofInputBuilder {
 	Mixer(Box<MixerProofInput>),
@@ -227,6 +747,31 @@ impl ProofInputBuilder {
 		}
 	}
 
+	/// Send the refreshed amount to a newly generated note
+	pub fn refresh_commitment(&mut self, refresh_commitment: &RefreshCommitment) -> Result<(), OperationError> {
+		match self {
+			Self::VAnchor(input) => {
+				input.output_utxos = Some(refresh_commitment.output_utxos()?);
+				Ok(())
+			}
+			_ => Err(OpStatusCode::ProofInputFieldInstantiationProtocolInvalid.into()),
+		}
+	}
+
+	/// Spend the withdrawal note and send the change to a new note
+	pub fn partial_withdrawal(&mut self, withdrawal: &PartialWithdrawal) -> Result<(), OperationError> {
+		match self {
+			Self::VAnchor(input) => {
+				input.input_utxos = Some(withdrawal.input_utxos.clone());
+				input.indices = Some(withdrawal.indices.clone());
+				input.output_utxos = Some(withdrawal.change.output_utxos()?);
+				input.public_amount = Some(withdrawal.public_amount);
+				Ok(())
+			}
+			_ => Err(OpStatusCode::ProofInputFieldInstantiationProtocolInvalid.into()),
+		}
+	}
+
 	pub fn roots(&mut self, roots: Vec<Vec<u8>>) -> Result<(), OperationError> {
 		match self {
 			ProofInputBuilder::VAnchor(value) => {
@@ -252,6 +797,7 @@ impl ProofInputBuilder {
 			ProofInputBuilder::Mixer(input) => {
 				input.secret = Some(leaf.secret_bytes);
 				input.nullifier = Some(leaf.nullifier_bytes);
+				input.commitment = Some(leaf.leaf_bytes);
 				Ok(())
 			}
 			_ => Err(OpStatusCode::ProofInputFieldInstantiationProtocolInvalid.into()),
@@ -298,6 +844,40 @@ impl ProofInputBuilder {
 		}
 	}
 
+	/// Set a 20 bytes EVM address as recipient, it's encoded as a left padded
+	/// `uint256` like the Solidity verifier does
+	pub fn recipient_evm(&mut self, address: [u8; 20]) -> Result<(), OperationError> {
+		match self {
+			Self::Mixer(input) => {
+				input.recipient = Some(address.to_vec());
+				input.recipient_encoding = Some(AccountEncoding::Evm);
+				Ok(())
+			}
+			_ => Err(OpStatusCode::ProofInputFieldInstantiationProtocolInvalid.into()),
+		}
+	}
+
+	pub fn relayer_evm(&mut self, address: [u8; 20]) -> Result<(), OperationError> {
+		match self {
+			Self::Mixer(input) => {
+				input.relayer = Some(address.to_vec());
+				input.relayer_encoding = Some(AccountEncoding::Evm);
+				Ok(())
+			}
+			_ => Err(OpStatusCode::ProofInputFieldInstantiationProtocolInvalid.into()),
+		}
+	}
+
+	pub fn chain_type(&mut self, chain_type: ChainType) -> Result<(), OperationError> {
+		match self {
+			Self::Mixer(input) => {
+				input.chain_type = Some(chain_type);
+				Ok(())
+			}
+			_ => Err(OpStatusCode::ProofInputFieldInstantiationProtocolInvalid.into()),
+		}
+	}
+
 	pub fn relayer(&mut self, relayer: Vec<u8>) -> Result<(), OperationError> {
 		match self {
 			Self::Mixer(input) => {
@@ -328,6 +908,16 @@ impl ProofInputBuilder {
 		}
 	}
 
+	pub fn discover_leaf_index(&mut self) -> Result<(), OperationError> {
+		match self {
+			Self::Mixer(input) => {
+				input.discover_leaf_index = true;
+				Ok(())
+			}
+			_ => Err(OpStatusCode::ProofInputFieldInstantiationProtocolInvalid.into()),
+		}
+	}
+
 	pub fn fee(&mut self, fee: u128) -> Result<(), OperationError> {
 		match self {
 			Self::Mixer(input) => {
@@ -348,6 +938,35 @@ impl ProofInputBuilder {
 		}
 	}
 
+	pub fn max_refund(&mut self, max_refund: u128) -> Result<(), OperationError> {
+		match self {
+			Self::Mixer(input) => {
+				input.max_refund = Some(max_refund);
+				Ok(())
+			}
+			_ => Err(OpStatusCode::ProofInputFieldInstantiationProtocolInvalid.into()),
+		}
+	}
+
+	pub fn native_token_pool(&mut self) -> Result<(), OperationError> {
+		match self {
+			Self::Mixer(input) => {
+				input.native_token_pool = true;
+				Ok(())
+			}
+			_ => Err(OpStatusCode::ProofInputFieldInstantiationProtocolInvalid.into()),
+		}
+	}
+
+	/// Decimals of the native token of the target chain, when its chain type
+	/// tells them
+	pub fn native_decimals(&self) -> Option<u8> {
+		match self {
+			Self::Mixer(input) => input.chain_type.and_then(ChainType::native_decimals),
+			_ => None,
+		}
+	}
+
 	pub fn pk(&mut self, pk: Vec<u8>) -> Result<(), OperationError> {
 		match self {
 			ProofInputBuilder::Mixer(input) => {
@@ -408,6 +1027,31 @@ impl ProofInputBuilder {
 		Ok(())
 	}
 
+	pub fn tree_depth(&mut self, tree_depth: usize) -> Result<(), OperationError> {
+		ensure_tree_depth(tree_depth)?;
+		match self {
+			ProofInputBuilder::Mixer(input) => {
+				input.tree_depth = Some(tree_depth);
+			}
+			ProofInputBuilder::VAnchor(input) => {
+				input.tree_depth = Some(tree_depth);
+			}
+		}
+		Ok(())
+	}
+
+	pub fn rng_seed(&mut self, seed: [u8; 32]) -> Result<(), OperationError> {
+		match self {
+			ProofInputBuilder::Mixer(input) => {
+				input.rng_seed = Some(seed);
+			}
+			ProofInputBuilder::VAnchor(input) => {
+				input.rng_seed = Some(seed);
+			}
+		}
+		Ok(())
+	}
+
 	pub fn chain_id(&mut self, chain_id: u128) -> Result<(), OperationError> {
 		match self {
 			ProofInputBuilder::Mixer(input) => {
@@ -419,13 +1063,27 @@ impl ProofInputBuilder {
 		}
 		Ok(())
 	}
+
+	/// Dry-run of `build`: reports every missing or invalid field for the
+	/// builder's protocol without consuming it
+	pub fn validate(&self) -> Vec<OperationError> {
+		match self {
+			ProofInputBuilder::Mixer(input) => input.validate(),
+			ProofInputBuilder::VAnchor(input) => input.validate(),
+		}
+	}
 	/* Shared fields  [VAnchor,Anchor,Mixer] */
 }
+/// Builder of a `JsProofInput`. Like the `JsNoteBuilder` its setters return
+/// the builder to chain the next call on, a setter that throws consumes it
 #[wasm_bindgen]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct JsProofInputBuilder {
 	#[wasm_bindgen(skip)]
 	pub inner: ProofInputBuilder,
+	/// The withdrawal of the last `setPartialWithdrawal`
+	#[wasm_bindgen(skip)]
+	pub partial_withdrawal: Option<PartialWithdrawal>,
 }
 #[wasm_bindgen]
 impl JsProofInputBuilder {
@@ -445,44 +1103,112 @@ impl JsProofInputBuilder {
 
 		Ok(JsProofInputBuilder {
 			inner: proof_input_builder,
+			partial_withdrawal: None,
 		})
 	}
 
+	/// Builder for spending `note`, with the curve, width, exponentiation,
+	/// backend, chain and secrets taken from the note. What's left to set are
+	/// the leaves, the leaf index (or `discoverLeafIndex`), the recipient, the
+	/// relayer, the fee, the refund and the proving key. Only mixer notes are
+	/// read this way, VAnchor inputs come from `setInputUtxos`
+	#[wasm_bindgen(js_name = fromNote)]
+	pub fn from_note(note: &JsNote) -> Result<JsProofInputBuilder, OperationError> {
+		if note.protocol != NoteProtocol::Mixer {
+			let message = format!("Can't build a proof input from a {} note", note.protocol);
+			return Err(OperationError::new_with_message(
+				OpStatusCode::InvalidNoteProtocol,
+				message,
+			));
+		}
+		// Fail here rather than at `build` for notes no circuit matches
+		let defaults = note.defaults();
+		ensure_supported_circuit(
+			NoteProtocol::Mixer,
+			note.curve.unwrap_or(defaults.curve),
+			note.width.unwrap_or(defaults.width),
+			note.exponentiation.unwrap_or(defaults.exponentiation),
+		)?;
+		let mut builder = JsProofInputBuilder {
+			inner: ProofInputBuilder::Mixer(Default::default()),
+			partial_withdrawal: None,
+		};
+		builder.set_meta_data(note)?;
+		let leaf = note.get_leaf_and_nullifier()?;
+		builder.inner.secrets(leaf.mixer_leaf()?)?;
+		Ok(builder)
+	}
+
 	#[wasm_bindgen(js_name = setRoots)]
-	pub fn set_roots(&mut self, roots: Leaves) -> Result<(), JsValue> {
-		let rs: Vec<Vec<u8>> = Array::from(&roots)
-			.to_vec()
-			.into_iter()
-			.map(|v| Uint8Array::new_with_byte_offset_and_length(&v, 0, 32))
-			.map(Uint8Arrayx32::try_from)
-			.collect::<Result<Vec<_>, _>>()
-			.map_err(|_| OpStatusCode::InvalidLeaves)?
-			.into_iter()
-			.map(|v| v.0.to_vec())
-			.collect();
-		self.inner.roots(rs)?;
-		Ok(())
+	pub fn set_roots(mut self, roots: Leaves) -> Result<JsProofInputBuilder, JsValue> {
+		// Kept as given, `validate` reports the roots of the wrong length
+		self.inner.roots(roots_from_js(&roots))?;
+		Ok(self)
 	}
 
 	#[wasm_bindgen(js_name = setRecipient)]
-	pub fn set_recipient(&mut self, recipient: JsString) -> Result<(), JsValue> {
+	pub fn set_recipient(mut self, recipient: JsString) -> Result<JsProofInputBuilder, JsValue> {
 		let r: String = recipient.into();
 		let recipient = hex::decode(r).map_err(|_| OpStatusCode::InvalidRecipient)?;
 		self.inner.recipient(recipient)?;
-		Ok(())
+		Ok(self)
 	}
 
 	#[wasm_bindgen(js_name = setRelayer)]
-	pub fn set_relayer(&mut self, relayer: JsString) -> Result<(), JsValue> {
+	pub fn set_relayer(mut self, relayer: JsString) -> Result<JsProofInputBuilder, JsValue> {
 		let r: String = relayer.into();
 		let relayer = hex::decode(r).map_err(|_| OpStatusCode::DeserializationFailed)?;
 		self.inner.relayer(relayer)?;
-		Ok(())
+		Ok(self)
+	}
+
+	/// Set the recipient from a `0x` prefixed EVM address, mixed case
+	/// addresses are checked against their EIP-55 checksum
+	#[wasm_bindgen(js_name = setRecipientEvm)]
+	pub fn set_recipient_evm(mut self, address: JsString) -> Result<JsProofInputBuilder, JsValue> {
+		let address: String = address.into();
+		self.inner.recipient_evm(parse_evm_address(&address)?)?;
+		Ok(self)
+	}
+
+	#[wasm_bindgen(js_name = setRelayerEvm)]
+	pub fn set_relayer_evm(mut self, address: JsString) -> Result<JsProofInputBuilder, JsValue> {
+		let address: String = address.into();
+		self.inner.relayer_evm(parse_evm_address(&address)?)?;
+		Ok(self)
+	}
+
+	/// Set the recipient from an SS58 address, optionally checking the
+	/// network prefix
+	#[wasm_bindgen(js_name = setRecipientSs58)]
+	pub fn set_recipient_ss58(
+		mut self,
+		address: JsString,
+		expected_prefix: Option<u16>,
+	) -> Result<JsProofInputBuilder, JsValue> {
+		let address: String = address.into();
+		let (recipient, _) = ss58_decode(&address, expected_prefix)?;
+		self.inner.recipient(recipient)?;
+		Ok(self)
+	}
+
+	#[wasm_bindgen(js_name = setRelayerSs58)]
+	pub fn set_relayer_ss58(
+		mut self,
+		address: JsString,
+		expected_prefix: Option<u16>,
+	) -> Result<JsProofInputBuilder, JsValue> {
+		let address: String = address.into();
+		let (relayer, _) = ss58_decode(&address, expected_prefix)?;
+		self.inner.relayer(relayer)?;
+		Ok(self)
 	}
 
 	#[wasm_bindgen(js_name = setLeaves)]
-	pub fn set_leaves(&mut self, leaves: Leaves) -> Result<(), JsValue> {
-		let ls: Vec<_> = Array::from(&leaves)
+	pub fn set_leaves(mut self, leaves: Leaves) -> Result<JsProofInputBuilder, JsValue> {
+		let leaves = Array::from(&leaves);
+		ensure_leaf_count(leaves.length() as usize)?;
+		let ls: Vec<_> = leaves
 			.to_vec()
 			.into_iter()
 			.map(|v| Uint8Array::new_with_byte_offset_and_length(&v, 0, 32))
@@ -493,23 +1219,64 @@ impl JsProofInputBuilder {
 			.map(|v| v.0.to_vec())
 			.collect();
 		self.inner.leaves_list(ls)?;
-		Ok(())
+		Ok(self)
+	}
+
+	/// The leaves packed back to back in one array, `leafSize` bytes each.
+	/// An array of a million `Uint8Array`s takes far more memory than the
+	/// leaves themselves, the packed array is copied into wasm in chunks
+	#[wasm_bindgen(js_name = setLeavesPacked)]
+	pub fn set_leaves_packed(mut self, leaves: Uint8Array, leaf_size: usize) -> Result<JsProofInputBuilder, JsValue> {
+		self.inner.leaves_list(leaves_from_packed(&leaves, leaf_size)?)?;
+		Ok(self)
 	}
 
 	#[wasm_bindgen(js_name = setOutputUtxos)]
-	pub fn set_output_utxos(&mut self, utxo1: JsUtxo, utxo2: JsUtxo) -> Result<(), JsValue> {
+	pub fn set_output_utxos(mut self, utxo1: JsUtxo, utxo2: JsUtxo) -> Result<JsProofInputBuilder, JsValue> {
 		self.inner.set_output_utxos([utxo1, utxo2])?;
-		Ok(())
+		Ok(self)
+	}
+
+	#[wasm_bindgen(js_name = setRefreshCommitment)]
+	pub fn set_refresh_commitment(
+		mut self,
+		refresh_commitment: &RefreshCommitment,
+	) -> Result<JsProofInputBuilder, JsValue> {
+		self.inner.refresh_commitment(refresh_commitment)?;
+		Ok(self)
+	}
+
+	/// Withdraw `amount` from `note` paying `fee` to the relayer, the
+	/// `partialWithdrawal` getter then holds the change note and the
+	/// `ExtData` amounts
+	#[wasm_bindgen(js_name = setPartialWithdrawal)]
+	pub fn set_partial_withdrawal(
+		mut self,
+		note: &JsNote,
+		amount: JsString,
+		fee: Option<JsString>,
+		refund: Option<JsString>,
+	) -> Result<JsProofInputBuilder, JsValue> {
+		let withdrawal = PartialWithdrawal::js_new(note, amount, fee, refund)?;
+		self.inner.partial_withdrawal(&withdrawal)?;
+		self.partial_withdrawal = Some(withdrawal);
+		Ok(self)
+	}
+
+	/// The withdrawal computed by `setPartialWithdrawal`, if it was called
+	#[wasm_bindgen(getter, js_name = partialWithdrawal)]
+	pub fn partial_withdrawal(&self) -> Option<PartialWithdrawal> {
+		self.partial_withdrawal.clone()
 	}
 
 	#[wasm_bindgen(js_name = setLeavesMap)]
-	pub fn set_leaves_map(&mut self, leaves_input: LeavesMapInput) -> Result<(), JsValue> {
+	pub fn set_leaves_map(mut self, leaves_input: LeavesMapInput) -> Result<JsProofInputBuilder, JsValue> {
 		self.inner.leaves_map(leaves_input.leaves)?;
-		Ok(())
+		Ok(self)
 	}
 
 	#[wasm_bindgen(js_name = setIndices)]
-	pub fn set_indices(&mut self, indices: Indices) -> Result<(), JsValue> {
+	pub fn set_indices(mut self, indices: Indices) -> Result<JsProofInputBuilder, JsValue> {
 		let indices: Vec<_> = Array::from(&indices)
 			.to_vec()
 			.into_iter()
@@ -522,99 +1289,166 @@ impl JsProofInputBuilder {
 			.into_iter()
 			.collect();
 		self.inner.leaf_indices(indices)?;
-		Ok(())
+		Ok(self)
 	}
 
 	#[wasm_bindgen(js_name = setLeafIndex)]
-	pub fn set_leaf_index(&mut self, leaf_index: JsString) -> Result<(), JsValue> {
+	pub fn set_leaf_index(mut self, leaf_index: JsString) -> Result<JsProofInputBuilder, JsValue> {
 		let leaf_index: String = leaf_index.into();
 		let leaf_index = leaf_index
 			.as_str()
 			.parse()
 			.map_err(|_| OpStatusCode::InvalidLeafIndex)?;
 		self.inner.leaf_index(leaf_index)?;
-		Ok(())
+		Ok(self)
 	}
 
+	/// Find the leaf index of the note in the leaves when building instead of
+	/// taking it from `setLeafIndex`, the build fails with `NotFoundInLeaves`
+	/// if the note's leaf isn't there
+	#[wasm_bindgen(js_name = discoverLeafIndex)]
+	pub fn discover_leaf_index(mut self) -> Result<JsProofInputBuilder, JsValue> {
+		self.inner.discover_leaf_index()?;
+		Ok(self)
+	}
+
+	/// Fee in base units, decimal or `0x` hex, or as a token amount like
+	/// `0.1` when the token's `denomination` is given
 	#[wasm_bindgen(js_name = setFee)]
-	pub fn set_fee(&mut self, fee: JsString) -> Result<(), JsValue> {
-		let fee: String = fee.into();
-		let fee = fee.as_str().parse().map_err(|_| OpStatusCode::InvalidFee)?;
+	pub fn set_fee(mut self, fee: JsString, denomination: Option<u8>) -> Result<JsProofInputBuilder, JsValue> {
+		let fee = parse_amount_field(&String::from(fee), denomination, OpStatusCode::InvalidFee)?;
 		self.inner.fee(fee)?;
-		Ok(())
+		Ok(self)
 	}
 
+	/// Refund in base units, or as a token amount when `denomination` is given
 	#[wasm_bindgen(js_name = setRefund)]
-	pub fn set_refund(&mut self, refund: JsString) -> Result<(), JsValue> {
-		let refund: String = refund.into();
-		let refund = refund.as_str().parse().map_err(|_| OpStatusCode::InvalidRefund)?;
+	pub fn set_refund(mut self, refund: JsString, denomination: Option<u8>) -> Result<JsProofInputBuilder, JsValue> {
+		let refund = parse_amount_field(&String::from(refund), denomination, OpStatusCode::InvalidRefund)?;
 		self.inner.refund(refund)?;
-		Ok(())
+		Ok(self)
+	}
+
+	/// Refund in the native token of the target chain, as a token amount like
+	/// `0.002` or in base units. The decimals are the chain's, from the
+	/// chain type of the note given with `setNote`, unless given
+	#[wasm_bindgen(js_name = setNativeRefund)]
+	pub fn set_native_refund(mut self, refund: JsString, decimals: Option<u8>) -> Result<JsProofInputBuilder, JsValue> {
+		let decimals = decimals.or_else(|| self.inner.native_decimals());
+		let refund = parse_amount_field(&String::from(refund), decimals, OpStatusCode::InvalidRefund)?;
+		self.inner.refund(refund)?;
+		Ok(self)
+	}
+
+	/// Highest refund the relayer policy allows, read as `setNativeRefund`
+	/// reads the refund. A larger refund fails with `InvalidRefund`
+	#[wasm_bindgen(js_name = setMaxRefund)]
+	pub fn set_max_refund(
+		mut self,
+		max_refund: JsString,
+		decimals: Option<u8>,
+	) -> Result<JsProofInputBuilder, JsValue> {
+		let decimals = decimals.or_else(|| self.inner.native_decimals());
+		let max_refund = parse_amount_field(&String::from(max_refund), decimals, OpStatusCode::InvalidRefund)?;
+		self.inner.max_refund(max_refund)?;
+		Ok(self)
+	}
+
+	/// The pool pays out the native token, its contract rejects any refund
+	#[wasm_bindgen(js_name = setNativeTokenPool)]
+	pub fn set_native_token_pool(mut self) -> Result<JsProofInputBuilder, JsValue> {
+		self.inner.native_token_pool()?;
+		Ok(self)
+	}
+
+	/// Depth of the merkle tree the leaves belong to, defaults to 30
+	#[wasm_bindgen(js_name = setTreeDepth)]
+	pub fn set_tree_depth(mut self, tree_depth: u32) -> Result<JsProofInputBuilder, JsValue> {
+		self.inner.tree_depth(tree_depth as usize)?;
+		Ok(self)
 	}
 
 	#[wasm_bindgen(js_name = setPk)]
-	pub fn set_pk(&mut self, pk: JsString) -> Result<(), JsValue> {
+	pub fn set_pk(mut self, pk: JsString) -> Result<JsProofInputBuilder, JsValue> {
 		let p: String = pk.into();
 		let proving_key = hex::decode(p).map_err(|_| OpStatusCode::InvalidProvingKey)?;
 		self.inner.pk(proving_key)?;
-		Ok(())
+		Ok(self)
 	}
 
+	/// Seed the prover's RNG with 32 bytes so the same input always gives the
+	/// same proof, only meant for tests
+	#[wasm_bindgen(js_name = setRngSeed)]
+	pub fn set_rng_seed(mut self, seed: Uint8Array) -> Result<JsProofInputBuilder, JsValue> {
+		let seed: [u8; 32] = seed.to_vec().try_into().map_err(|_| {
+			OperationError::new_with_message(
+				OpStatusCode::InvalidArrayLength,
+				"The seed must be 32 bytes".to_string(),
+			)
+		})?;
+		self.inner.rng_seed(seed)?;
+		Ok(self)
+	}
+
+	/// Signed public amount, negative for withdrawals, parsed like the fee
 	#[wasm_bindgen]
-	pub fn public_amount(&mut self, public_amount: JsString) -> Result<(), JsValue> {
-		let pa: String = public_amount.into();
-		let pa: i128 = pa.parse().map_err(|_| OpStatusCode::InvalidPublicAmount)?;
+	pub fn public_amount(
+		mut self,
+		public_amount: JsString,
+		denomination: Option<u8>,
+	) -> Result<JsProofInputBuilder, JsValue> {
+		let pa = parse_signed_amount(&String::from(public_amount), denomination)
+			.map_err(amount_field_error(OpStatusCode::InvalidPublicAmount))?;
 		self.inner.public_amount(pa)?;
-		Ok(())
+		Ok(self)
 	}
 
 	#[wasm_bindgen]
-	pub fn chain_id(&mut self, chain_id: JsString) -> Result<(), JsValue> {
+	pub fn chain_id(mut self, chain_id: JsString) -> Result<JsProofInputBuilder, JsValue> {
 		let chain_id: String = chain_id.into();
-		let chain_id = chain_id.parse().map_err(|_| OpStatusCode::InvalidChainId)?;
-		self.inner.chain_id(chain_id)?;
-		Ok(())
+		let chain_id = parse_chain_id(&chain_id)?;
+		self.inner.chain_id(u128::from(chain_id))?;
+		Ok(self)
 	}
 
 	fn set_meta_data(&mut self, note: &JsNote) -> Result<(), OperationError> {
-		let exponentiation = note.exponentiation.unwrap_or(5);
+		let defaults = note.defaults();
+		let exponentiation = note.exponentiation.unwrap_or(defaults.exponentiation);
 		let backend = note.backend.unwrap_or(Backend::Circom);
-		let curve = note.curve.unwrap_or(Curve::Bn254);
-		let width = note.width.unwrap_or(3);
+		let curve = note.curve.unwrap_or(defaults.curve);
+		let width = note.width.unwrap_or(defaults.width);
+		ensure_proving_hash_function(note.hash_function.unwrap_or(defaults.hash_function))?;
+		// Wrong identifying data would only show as a rejected transaction
+		note.identifying_data()?;
 
-		let chain_id = note
-			.target_chain_id
-			.parse()
-			.map_err(|_| OpStatusCode::InvalidTargetChain)?;
+		let chain_id = parse_chain_id(&note.target_chain_id).map_err(|_| OpStatusCode::InvalidTargetChain)?;
 
 		self.inner.exponentiation(exponentiation)?;
 		self.inner.backend(backend)?;
 		self.inner.width(width)?;
 		self.inner.curve(curve)?;
-		self.inner.chain_id(chain_id)?;
+		self.inner.chain_id(chain_id.into())?;
+		if let Some(chain_type) = TypedChainId::from_u64(chain_id)
+			.ok()
+			.and_then(|chain_id| chain_id.chain_type)
+		{
+			self.inner.chain_type(chain_type)?;
+		}
 		Ok(())
 	}
 
 	#[wasm_bindgen(js_name = setNote)]
-	pub fn set_metadata_from_note(&mut self, note: &JsNote) -> Result<(), JsValue> {
-		// For the Mixer/Anchor secrets live in the the note
-		// For the VAnchor there is a call `set_notes` that will set UTXOs in the
-		// `ProofInput::VAnchor(VAnchorProofInput)`
-		match self.inner {
-			ProofInputBuilder::Mixer(_) => self.set_meta_data(note)?,
-			_ => return Err(OpStatusCode::InvalidNoteProtocol.into()),
-		}
+	pub fn set_metadata_from_note(mut self, note: &JsNote) -> Result<JsProofInputBuilder, JsValue> {
+		self.apply_note(note)?;
+		Ok(self)
+	}
 
-		#[allow(clippy::single_match)]
-		match self.inner {
-			ProofInputBuilder::Mixer(_) => {
-				let leaf = note.get_leaf_and_nullifier()?;
-				let mixer_leaf = leaf.mixer_leaf()?;
-				self.inner.secrets(mixer_leaf)?
-			}
-			_ => {}
-		}
-		Ok(())
+	/// Returns an array of `OperationError`s for the missing/invalid fields, an
+	/// empty array means the input is ready to be built
+	#[wasm_bindgen]
+	pub fn validate(&self) -> OperationErrorArray {
+		let errors: Array = self.inner.validate().into_iter().map(JsValue::from).collect();
+		errors.unchecked_into()
 	}
 
 	#[wasm_bindgen]
@@ -625,32 +1459,76 @@ impl JsProofInputBuilder {
 
 	/// Set utxos for vanchor
 	#[wasm_bindgen(js_name=setInputUtxos)]
-	pub fn set_input_utxos(&mut self, input_utxos: Array) -> Result<(), JsValue> {
+	pub fn set_input_utxos(mut self, input_utxos: Array) -> Result<JsProofInputBuilder, JsValue> {
 		let utxos: Vec<JsUtxo> = input_utxos
 			.iter()
 			.map(|v| js_utxo_of_jsval(v).ok_or(OpStatusCode::InvalidInputUtxoConfig))
 			.collect::<Result<Vec<JsUtxo>, _>>()?;
+		let chain_id = utxos.first().map(JsUtxo::chain_id_raw).ok_or_else(|| {
+			OperationError::new_with_message(
+				OpStatusCode::InvalidInputUtxoConfig,
+				"No input UTXOs were given".to_string(),
+			)
+		})?;
 
 		self.inner.exponentiation(5)?;
 		self.inner.backend(Backend::Arkworks)?;
 		self.inner.width(5)?;
 		self.inner.curve(Curve::Bn254)?;
-		self.inner.chain_id(utxos[0].chain_id_raw().into())?;
+		self.inner.chain_id(chain_id.into())?;
 
 		self.inner.set_input_utxos(utxos)?;
-		Ok(())
+		Ok(self)
 	}
 
 	#[wasm_bindgen(js_name=setExtDatahash)]
-	pub fn set_ext_data_hash(&mut self, ex_data_hash: JsString) -> Result<(), JsValue> {
+	pub fn set_ext_data_hash(mut self, ex_data_hash: JsString) -> Result<JsProofInputBuilder, JsValue> {
 		let ex_data_hash: String = ex_data_hash.into();
 		let bytes = hex::decode(&ex_data_hash).map_err(|_| OpStatusCode::InvalidExtDataHash)?;
 
 		self.inner.ext_data_hash(bytes)?;
-		Ok(())
+		Ok(self)
+	}
+
+	/// Set the `extDataHash` from the `ExtData` sent with the transaction,
+	/// hashed as the verifier on the target chain does
+	#[wasm_bindgen(js_name = setExtData)]
+	pub fn set_ext_data(
+		mut self,
+		ext_data: &ExtData,
+		encoding: WasmExtDataEncoding,
+	) -> Result<JsProofInputBuilder, JsValue> {
+		let encoding: ExtDataEncoding = JsValue::from(encoding)
+			.as_string()
+			.ok_or(OpStatusCode::InvalidExtDataHash)?
+			.parse()?;
+		self.inner.ext_data_hash(ext_data.hash_with_encoding(encoding)?)?;
+		Ok(self)
 	}
 }
 impl JsProofInputBuilder {
+	/// Metadata and secrets of `note`, see `setNote`
+	pub fn apply_note(&mut self, note: &JsNote) -> Result<(), OperationError> {
+		// For the Mixer/Anchor secrets live in the the note
+		// For the VAnchor there is a call `set_notes` that will set UTXOs in the
+		// `ProofInput::VAnchor(VAnchorProofInput)`
+		match self.inner {
+			ProofInputBuilder::Mixer(_) => self.set_meta_data(note)?,
+			_ => return Err(OpStatusCode::InvalidNoteProtocol.into()),
+		}
+
+		#[allow(clippy::single_match)]
+		match self.inner {
+			ProofInputBuilder::Mixer(_) => {
+				let leaf = note.get_leaf_and_nullifier()?;
+				let mixer_leaf = leaf.mixer_leaf()?;
+				self.inner.secrets(mixer_leaf)?
+			}
+			_ => {}
+		}
+		Ok(())
+	}
+
 	pub fn build(self) -> Result<ProofInput, OperationError> {
 		let proof_input = match self.inner {
 			ProofInputBuilder::Mixer(mixer_proof_input) => {
@@ -690,8 +1568,7 @@ impl MTBn254X5 {
 			.map(|v| Bn254Fr::from_be_bytes_mod_order(v.0.as_ref()))
 			.collect();
 
-		let curve = ArkCurve::Bn254;
-		let params3 = setup_params::<Bn254Fr>(curve, 5, 3);
+		let params3 = poseidon_params::<Bn254Fr>(5, 3);
 		let poseidon3 = Poseidon::new(params3);
 
 		let (tree, _) = setup_tree_and_create_path::<Bn254Fr, Poseidon<Bn254Fr>, TREE_HEIGHT>(
@@ -712,25 +1589,16 @@ impl MTBn254X5 {
 
 	#[wasm_bindgen]
 	pub fn insert(&mut self, leaves: Leaves) -> Result<(), JsValue> {
-		let mut leaves_bt = BTreeMap::<_, Bn254Fr>::new();
-		Array::from(&leaves)
-			.to_vec()
-			.into_iter()
-			.map(|v| Uint8Array::new_with_byte_offset_and_length(&v, 0, 32))
-			.map(Uint8Arrayx32::try_from)
-			.collect::<Result<Vec<_>, _>>()
-			.map_err(|_| OpStatusCode::InvalidLeaves)?
+		// Leaves are appended after the ones already in the last level of the tree
+		let last_level_index = MerkleTree::capacity() - 1;
+		let next_index = self.inner.tree.keys().filter(|k| **k >= last_level_index).count() as u32;
+		let leaves_bt: BTreeMap<u32, Bn254Fr> = leaves_from_js(&leaves)?
 			.iter()
-			.for_each(|leaf| {
-				leaves_bt
-					.insert(
-						leaves_bt.len() as u32 + 1_u32,
-						Bn254Fr::from_be_bytes_mod_order(leaf.0.as_ref()),
-					)
-					.unwrap();
-			});
-
-		let params3 = setup_params::<Bn254Fr>(ArkCurve::Bn254, 5, 3);
+			.enumerate()
+			.map(|(i, leaf)| (next_index + i as u32, Bn254Fr::from_be_bytes_mod_order(leaf)))
+			.collect();
+
+		let params3 = poseidon_params::<Bn254Fr>(5, 3);
 		let poseidon3 = Poseidon::new(params3);
 		self.inner
 			.insert_batch(&leaves_bt, &poseidon3)
@@ -763,6 +1631,8 @@ pub struct JsProvingKeys {
 	pub pk: Vec<u8>,
 	#[wasm_bindgen(skip)]
 	pub vk: Vec<u8>,
+	#[wasm_bindgen(skip)]
+	pub live: LiveObject,
 }
 #[wasm_bindgen]
 impl JsProvingKeys {
@@ -773,9 +1643,19 @@ impl JsProvingKeys {
 
 	#[wasm_bindgen(getter)]
 	pub fn vk(&self) -> Uint8Array {
-		Uint8Array::from(self.pk.as_slice())
+		Uint8Array::from(self.vk.as_slice())
 	}
 }
+fn setup_vanchor_keys<const HEIGHT: usize, const ANCHOR_CT: usize, const INS: usize>() -> (Vec<u8>, Vec<u8>) {
+	let c = VAnchorR1CSProver::<Bn254, HEIGHT, ANCHOR_CT, INS, OUTS_2>::setup_random_circuit(
+		ArkCurve::Bn254,
+		DEFAULT_LEAF,
+		&mut OsRng,
+	)
+	.expect("Failed to create a circuit");
+	setup_keys_unchecked::<Bn254, _, _>(c, &mut OsRng).expect("failed to generate keys")
+}
+
 #[wasm_bindgen(js_name = setupKeys)]
 pub fn setup_keys(
 	protocol: Protocol,
@@ -783,65 +1663,92 @@ pub fn setup_keys(
 	anchor_count: Option<u32>,
 	in_count: Option<u32>,
 	out_count: Option<u32>,
+) -> Result<JsProvingKeys, JsValue> {
+	setup_keys_with_tree_depth(TREE_HEIGHT as u32, protocol, curve, anchor_count, in_count, out_count)
+}
+
+/// `setupKeys` for a tree of `tree_depth` levels, one of the depths
+/// `JsProofInputBuilder::setTreeDepth` takes
+#[wasm_bindgen(js_name = setupKeysWithTreeDepth)]
+pub fn setup_keys_with_tree_depth(
+	tree_depth: u32,
+	protocol: Protocol,
+	curve: Option<WasmCurve>,
+	anchor_count: Option<u32>,
+	in_count: Option<u32>,
+	out_count: Option<u32>,
 ) -> Result<JsProvingKeys, JsValue> {
 	let curve: Curve = match curve {
-		Some(curve) => JsValue::from(curve).as_string().unwrap().parse().unwrap(),
+		Some(curve) => JsValue::from(curve)
+			.as_string()
+			.ok_or(OpStatusCode::InvalidCurve)?
+			.parse()
+			.map_err(|_| OpStatusCode::InvalidCurve)?,
 		None => Curve::Bn254,
 	};
 	let anchor_count = anchor_count.unwrap_or(2);
 	let in_count = in_count.unwrap_or(2);
 	let out_count = out_count.unwrap_or(2);
-	let note_protocol: NoteProtocol = JsValue::from(protocol).as_string().unwrap().parse().unwrap();
-	let (pk, vk) = match (note_protocol, curve, anchor_count, in_count, out_count) {
-		(NoteProtocol::Mixer, ..) => {
-			let (c, ..) = MixerR1CSProverBn254_30::setup_random_circuit(ArkCurve::Bn254, DEFAULT_LEAF, &mut OsRng)
+	let tree_depth = tree_depth as usize;
+	ensure_tree_depth(tree_depth)?;
+	let note_protocol: NoteProtocol = JsValue::from(protocol)
+		.as_string()
+		.ok_or(OpStatusCode::InvalidNoteProtocol)?
+		.parse()
+		.map_err(|_| OpStatusCode::InvalidNoteProtocol)?;
+	let (pk, vk) = with_tree_depth!(tree_depth, DEPTH => match (note_protocol, curve, anchor_count, in_count, out_count) {
+		(NoteProtocol::Mixer, Curve::Bn254, ..) => {
+			let (c, ..) = MixerR1CSProver::<Bn254, DEPTH>::setup_random_circuit(ArkCurve::Bn254, DEFAULT_LEAF, &mut OsRng)
 				.expect("Failed to create a circuit");
 			let (pk, vk) = setup_keys_unchecked::<Bn254, _, _>(c, &mut OsRng).expect("failed to generate keys");
 			(pk, vk)
 		}
-		(NoteProtocol::VAnchor, Curve::Bn254, 2, 2, 2) => {
-			let c = VAnchorR1CSProverBn254_30_2_2_2::setup_random_circuit(ArkCurve::Bn254, DEFAULT_LEAF, &mut OsRng)
-				.expect("Failed to create a circuit");
-			let (pk, vk) = setup_keys_unchecked::<Bn254, _, _>(c, &mut OsRng).expect("failed to generate keys");
-			(pk, vk)
-		}
-		(NoteProtocol::VAnchor, Curve::Bn254, 2, 16, 2) => {
-			let c = VAnchorR1CSProverBn254_30_2_16_2::setup_random_circuit(ArkCurve::Bn254, DEFAULT_LEAF, &mut OsRng)
-				.expect("Failed to create a circuit");
-			let (pk, vk) = setup_keys_unchecked::<Bn254, _, _>(c, &mut OsRng).expect("failed to generate keys");
+		(NoteProtocol::Mixer, Curve::Bls381, ..) => {
+			let (c, ..) =
+				MixerR1CSProver::<Bls12_381, DEPTH>::setup_random_circuit(ArkCurve::Bls381, DEFAULT_LEAF, &mut OsRng)
+					.expect("Failed to create a circuit");
+			let (pk, vk) = setup_keys_unchecked::<Bls12_381, _, _>(c, &mut OsRng).expect("failed to generate keys");
 			(pk, vk)
 		}
-		(NoteProtocol::VAnchor, Curve::Bn254, 16, 2, 2) => {
-			let c = VAnchorR1CSProverBn254_30_16_2_2::setup_random_circuit(ArkCurve::Bn254, DEFAULT_LEAF, &mut OsRng)
-				.expect("Failed to create a circuit");
-			let (pk, vk) = setup_keys_unchecked::<Bn254, _, _>(c, &mut OsRng).expect("failed to generate keys");
-			(pk, vk)
+		(NoteProtocol::VAnchor, Curve::Bn254, 2 | 4 | 8 | 16, 2 | 16, 2) => {
+			with_anchor_count!(anchor_count, ANCHORS => match in_count {
+				2 => setup_vanchor_keys::<DEPTH, ANCHORS, INS_2>(),
+				_ => setup_vanchor_keys::<DEPTH, ANCHORS, INS_16>(),
+			})
 		}
-		(NoteProtocol::VAnchor, Curve::Bn254, 16, 16, 2) => {
-			let c = VAnchorR1CSProverBn254_30_16_16_2::setup_random_circuit(ArkCurve::Bn254, DEFAULT_LEAF, &mut OsRng)
-				.expect("Failed to create a circuit");
-			let (pk, vk) = setup_keys_unchecked::<Bn254, _, _>(c, &mut OsRng).expect("failed to generate keys");
-			(pk, vk)
+		_ => {
+			let message = format!(
+				"No circuit for {} on {} with {} anchors, {} inputs and {} outputs",
+				note_protocol, curve, anchor_count, in_count, out_count
+			);
+			return Err(OperationError::new_with_message(OpStatusCode::UnsupportedParameterCombination, message).into());
 		}
-		_ => return Err(JsValue::from(JsString::from("Unsupported input"))),
-	};
-	Ok(JsProvingKeys { pk, vk })
+	});
+	let live = LiveObject::new(ObjectKind::ProvingKeys, pk.len() + vk.len());
+	Ok(JsProvingKeys { pk, vk, live })
 }
+/// Prove the input, see `exportDebugBundle` to report a failing proof and
+/// `setProofTelemetryHook` to measure proofs
 #[wasm_bindgen]
 pub fn generate_proof_js(proof_input: JsProofInput) -> Result<JsProofOutput, JsValue> {
-	let mut rng = OsRng;
-	let proof_input_value = proof_input.inner;
-	match proof_input_value {
+	let mut bundle = DebugBundle::new(&proof_input.inner);
+	let telemetry = TelemetryRecorder::start(&proof_input.inner);
+	let started_at = js_sys::Date::now();
+	let output = match proof_input.inner {
 		ProofInput::Mixer(mixer_proof_input) => {
-			mixer::create_proof(*mixer_proof_input, &mut rng).map(|v| JsProofOutput {
-				inner: ProofOutput::Mixer(v),
-			})
+			let mut rng = ProverRng::new(mixer_proof_input.rng_seed);
+			mixer::create_proof(*mixer_proof_input, &mut rng).map(ProofOutput::Mixer)
 		}
 		ProofInput::VAnchor(vanchor_proof_input) => {
-			vanchor::create_proof(*vanchor_proof_input, &mut rng).map(|v| JsProofOutput {
-				inner: ProofOutput::VAnchor(v),
-			})
+			let mut rng = ProverRng::new(vanchor_proof_input.rng_seed);
+			vanchor::create_proof(*vanchor_proof_input, &mut rng).map(ProofOutput::VAnchor)
 		}
+	};
+	let prove_ms = js_sys::Date::now() - started_at;
+	bundle.finish(prove_ms, &output);
+	record_last_bundle(bundle);
+	if let Some(telemetry) = telemetry {
+		telemetry.finish(prove_ms, &output);
 	}
-	.map_err(|e| e.into())
+	output.map(JsProofOutput::new).map_err(|e| e.into())
 }
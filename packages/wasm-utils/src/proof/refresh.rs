@@ -0,0 +1,261 @@
+use js_sys::{JsString, Uint8Array};
+use wasm_bindgen::prelude::*;
+
+use crate::entropy::SecretRng;
+use crate::note::amount::{parse_amount_field, NoteAmount};
+use crate::note::{mixer, vanchor, JsNote};
+use crate::types::{Backend, Curve, NoteProtocol, OpStatusCode, OperationError};
+use crate::utxo::JsUtxo;
+
+/// A freshly generated note together with its leaf commitment.
+///
+/// Used to send the remainder of a withdrawal back into the pool as a new
+/// note, the note is generated from a template so it shares the chain,
+/// curve and hashing parameters of the note being spent.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct RefreshCommitment {
+	#[wasm_bindgen(skip)]
+	pub note: JsNote,
+	#[wasm_bindgen(skip)]
+	pub commitment: Vec<u8>,
+}
+
+impl RefreshCommitment {
+	/// Generate a new note with fresh secrets from `template`, for the VAnchor
+	/// the new note will hold `amount` (defaults to the template amount)
+	pub fn generate(template: &JsNote, amount: Option<u128>) -> Result<Self, OperationError> {
+		let defaults = template.defaults();
+		let exponentiation = template.exponentiation.unwrap_or(defaults.exponentiation);
+		let curve = template.curve.unwrap_or(defaults.curve);
+		let width = template.width.unwrap_or(defaults.width);
+		let mut note = template.clone();
+
+		match template.protocol {
+			NoteProtocol::Mixer => {
+				note.secrets = mixer::generate_secrets(exponentiation, width, curve, &mut SecretRng::new()?)?.to_vec();
+			}
+			NoteProtocol::VAnchor => {
+				let chain_id: u64 = template
+					.target_chain_id
+					.parse()
+					.map_err(|_| OpStatusCode::InvalidTargetChain)?;
+				let amount = match amount {
+					Some(amount) => amount,
+					None => template
+						.amount
+						.clone()
+						.unwrap_or_else(|| "0".to_string())
+						.parse()
+						.map_err(|_| OpStatusCode::InvalidAmount)?,
+				};
+				let utxo = vanchor::generate_secrets(
+					amount,
+					exponentiation,
+					width,
+					curve,
+					chain_id,
+					None,
+					&mut SecretRng::new()?,
+				)?;
+				note.index = None;
+				note.update_vanchor_utxo(utxo)?;
+			}
+		}
+
+		let commitment = note.get_leaf_and_nullifier()?.commitment().to_vec();
+		Ok(Self { note, commitment })
+	}
+
+	/// The output UTXOs that move the refreshed amount into the new note, the
+	/// second output is a zero amount UTXO on the same chain
+	pub fn output_utxos(&self) -> Result<[JsUtxo; 2], OperationError> {
+		let utxo = self.note.get_utxo()?;
+		let zero_utxo = JsUtxo::new(
+			self.note.curve.unwrap_or(Curve::Bn254),
+			Backend::Arkworks,
+			0,
+			utxo.get_chain_id_raw(),
+			None,
+			None,
+			None,
+			None,
+		)?;
+		Ok([utxo, zero_utxo])
+	}
+}
+
+#[allow(clippy::unused_unit)]
+#[wasm_bindgen]
+impl RefreshCommitment {
+	#[wasm_bindgen(constructor)]
+	pub fn new(template: &JsNote, amount: Option<JsString>) -> Result<RefreshCommitment, JsValue> {
+		let amount = match amount {
+			Some(amount) => {
+				let amount: String = amount.into();
+				Some(amount.parse().map_err(|_| OpStatusCode::InvalidAmount)?)
+			}
+			None => None,
+		};
+		Self::generate(template, amount).map_err(Into::into)
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn note(&self) -> JsNote {
+		self.note.clone()
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn commitment(&self) -> Uint8Array {
+		Uint8Array::from(self.commitment.as_slice())
+	}
+}
+
+/// Withdrawal of part of a VAnchor note, the remainder goes back into the
+/// pool as a change note.
+///
+/// The note pays `amount` to the recipient and `fee` to the relayer, so the
+/// change note holds `note amount - amount - fee`. The refund is paid by the
+/// relayer in the native token and doesn't come out of the note.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct PartialWithdrawal {
+	#[wasm_bindgen(skip)]
+	pub change: RefreshCommitment,
+	#[wasm_bindgen(skip)]
+	pub input_utxos: Vec<JsUtxo>,
+	#[wasm_bindgen(skip)]
+	pub indices: Vec<u64>,
+	#[wasm_bindgen(skip)]
+	pub ext_amount: i128,
+	#[wasm_bindgen(skip)]
+	pub public_amount: i128,
+	#[wasm_bindgen(skip)]
+	pub fee: u128,
+	#[wasm_bindgen(skip)]
+	pub refund: u128,
+}
+
+fn to_signed(amount: &NoteAmount) -> Result<i128, OperationError> {
+	i128::try_from(amount.inner).map_err(|_| {
+		let message = format!("Amount {} does not fit in an i128", amount);
+		OperationError::new_with_message(OpStatusCode::AmountOverflow, message)
+	})
+}
+
+impl PartialWithdrawal {
+	pub fn new(note: &JsNote, amount: u128, fee: u128, refund: u128) -> Result<Self, OperationError> {
+		if note.protocol != NoteProtocol::VAnchor {
+			let message = "Partial withdrawals are only supported for VAnchor notes".to_string();
+			return Err(OperationError::new_with_message(
+				OpStatusCode::InvalidNoteProtocol,
+				message,
+			));
+		}
+		let index = note.index.ok_or_else(|| {
+			let message = "The note needs its leaf index to be spent".to_string();
+			OperationError::new_with_message(OpStatusCode::InvalidUTXOIndex, message)
+		})?;
+
+		// The circuit spends the amount committed to in the UTXO
+		let input_utxo = note.get_utxo()?;
+		let value = NoteAmount::new(input_utxo.get_amount_raw());
+		let spent = NoteAmount::new(amount).checked_add(&NoteAmount::new(fee))?;
+		if spent > value {
+			let message = format!(
+				"Withdrawing {} with a fee of {} exceeds the note amount {}",
+				amount, fee, value
+			);
+			return Err(OperationError::new_with_message(
+				OpStatusCode::InsufficientBalance,
+				message,
+			));
+		}
+		let change_amount = value.checked_sub(&spent)?;
+
+		let ext_amount = -to_signed(&NoteAmount::new(amount))?;
+		let public_amount = -to_signed(&spent)?;
+
+		let zero_utxo = JsUtxo::new(
+			note.curve.unwrap_or(Curve::Bn254),
+			Backend::Arkworks,
+			0,
+			input_utxo.get_chain_id_raw(),
+			None,
+			None,
+			None,
+			Some(0),
+		)?;
+		let change = RefreshCommitment::generate(note, Some(change_amount.inner))?;
+
+		Ok(Self {
+			change,
+			input_utxos: vec![input_utxo, zero_utxo],
+			indices: vec![index, 0],
+			ext_amount,
+			public_amount,
+			fee,
+			refund,
+		})
+	}
+}
+
+#[allow(clippy::unused_unit)]
+#[wasm_bindgen]
+impl PartialWithdrawal {
+	#[wasm_bindgen(constructor)]
+	pub fn js_new(
+		note: &JsNote,
+		amount: JsString,
+		fee: Option<JsString>,
+		refund: Option<JsString>,
+	) -> Result<PartialWithdrawal, JsValue> {
+		let parse = |value: Option<JsString>, code: OpStatusCode| -> Result<u128, OperationError> {
+			match value {
+				Some(value) => parse_amount_field(&String::from(value), None, code),
+				None => Ok(0),
+			}
+		};
+		let amount = parse(Some(amount), OpStatusCode::InvalidAmount)?;
+		let fee = parse(fee, OpStatusCode::InvalidFee)?;
+		let refund = parse(refund, OpStatusCode::InvalidRefund)?;
+		Ok(Self::new(note, amount, fee, refund)?)
+	}
+
+	#[wasm_bindgen(getter)]
+	#[wasm_bindgen(js_name = changeNote)]
+	pub fn change_note(&self) -> JsNote {
+		self.change.note.clone()
+	}
+
+	#[wasm_bindgen(getter)]
+	#[wasm_bindgen(js_name = changeCommitment)]
+	pub fn change_commitment(&self) -> RefreshCommitment {
+		self.change.clone()
+	}
+
+	/// Signed amount leaving the pool to the recipient, as set in the `ExtData`
+	#[wasm_bindgen(getter)]
+	#[wasm_bindgen(js_name = extAmount)]
+	pub fn js_ext_amount(&self) -> JsString {
+		self.ext_amount.to_string().into()
+	}
+
+	#[wasm_bindgen(getter)]
+	#[wasm_bindgen(js_name = publicAmount)]
+	pub fn js_public_amount(&self) -> JsString {
+		self.public_amount.to_string().into()
+	}
+
+	#[wasm_bindgen(getter)]
+	#[wasm_bindgen(js_name = fee)]
+	pub fn js_fee(&self) -> JsString {
+		self.fee.to_string().into()
+	}
+
+	#[wasm_bindgen(getter)]
+	#[wasm_bindgen(js_name = refund)]
+	pub fn js_refund(&self) -> JsString {
+		self.refund.to_string().into()
+	}
+}
@@ -0,0 +1,583 @@
+use core::fmt;
+use core::str::FromStr;
+
+use js_sys::JsString;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::address::{parse_evm_address, ss58_decode, to_checksum_address};
+use crate::note::amount::parse_amount_field;
+use crate::proof::ext_data::ExtData;
+use crate::proof::mixer::MixerProof;
+use crate::proof::vanchor::VAnchorProof;
+use crate::proof::{JsProofOutput, ProofOutput};
+use crate::types::{ChainType, OpStatusCode, OperationError, TypedChainId};
+
+/// Version of the relayer payload schema
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RelayerSchemaVersion {
+	V1,
+}
+
+impl fmt::Display for RelayerSchemaVersion {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			RelayerSchemaVersion::V1 => write!(f, "v1"),
+		}
+	}
+}
+
+impl FromStr for RelayerSchemaVersion {
+	type Err = OpStatusCode;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"v1" => Ok(RelayerSchemaVersion::V1),
+			_ => Err(OpStatusCode::InvalidRelayerPayload),
+		}
+	}
+}
+
+/// Command sent to the relayer over HTTP or WebSocket, externally tagged by
+/// the kind of chain and the protocol, e.g. `{ "evm": { "vAnchor": {..} } }`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum WithdrawalRequest {
+	Evm(EvmCommand),
+	Substrate(SubstrateCommand),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum EvmCommand {
+	Mixer(EvmMixerRelayTx),
+	VAnchor(EvmVAnchorRelayTx),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum SubstrateCommand {
+	Mixer(SubstrateMixerRelayTx),
+	VAnchor(SubstrateVAnchorRelayTx),
+}
+
+/// EVM values are `0x` prefixed hex strings
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EvmMixerRelayTx {
+	pub chain: String,
+	pub contract: String,
+	pub proof: String,
+	pub root: String,
+	pub nullifier_hash: String,
+	pub recipient: String,
+	pub relayer: String,
+	pub fee: String,
+	pub refund: String,
+	/// Base asset to pay the recipient in, unwrapped from the bridge token
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub unwrap_token: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EvmVAnchorRelayTx {
+	pub chain: String,
+	pub contract: String,
+	pub proof_data: EvmProofData,
+	pub ext_data: EvmExtData,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EvmProofData {
+	pub proof: String,
+	pub public_amount: String,
+	pub roots: Vec<String>,
+	pub input_nullifiers: Vec<String>,
+	pub output_commitments: Vec<String>,
+	pub ext_data_hash: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EvmExtData {
+	pub recipient: String,
+	pub relayer: String,
+	pub ext_amount: String,
+	pub fee: String,
+	pub refund: String,
+	pub token: String,
+	pub encrypted_output1: String,
+	pub encrypted_output2: String,
+}
+
+/// Substrate values are byte arrays and numbers, accounts are SS58 addresses
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SubstrateMixerRelayTx {
+	pub chain: String,
+	pub id: u32,
+	pub proof: Vec<u8>,
+	pub root: Vec<u8>,
+	pub nullifier_hash: Vec<u8>,
+	pub recipient: String,
+	pub relayer: String,
+	pub fee: u128,
+	pub refund: u128,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SubstrateVAnchorRelayTx {
+	pub chain: String,
+	pub id: u32,
+	pub proof_data: SubstrateProofData,
+	pub ext_data: SubstrateExtData,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SubstrateProofData {
+	pub proof: Vec<u8>,
+	pub public_amount: Vec<u8>,
+	pub roots: Vec<Vec<u8>>,
+	pub input_nullifiers: Vec<Vec<u8>>,
+	pub output_commitments: Vec<Vec<u8>>,
+	pub ext_data_hash: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SubstrateExtData {
+	pub recipient: Vec<u8>,
+	pub relayer: Vec<u8>,
+	pub ext_amount: i128,
+	pub fee: u128,
+	pub refund: u128,
+	pub token: Vec<u8>,
+	pub encrypted_output1: Vec<u8>,
+	pub encrypted_output2: Vec<u8>,
+}
+
+fn hex_0x(bytes: &[u8]) -> String {
+	format!("0x{}", hex::encode(bytes))
+}
+
+/// Two's complement of a signed amount as a hex `int256`
+fn evm_int256(value: i128) -> String {
+	let mut bytes = if value < 0 { [0xffu8; 32] } else { [0u8; 32] };
+	bytes[16..].copy_from_slice(&value.to_be_bytes());
+	hex_0x(&bytes)
+}
+
+fn evm_address(bytes: &[u8], code: OpStatusCode) -> Result<String, OperationError> {
+	let address: [u8; 20] = bytes.try_into().map_err(|_| {
+		let message = format!("Expected a 20 bytes address, got {} bytes", bytes.len());
+		OperationError::new_with_message(code, message)
+	})?;
+	Ok(to_checksum_address(&address))
+}
+
+/// Split the VAnchor public inputs, laid out as
+/// `[publicAmount, extDataHash, nullifiers.., commitments.., chainId, roots..]`
+fn split_vanchor_public_inputs(proof: &VAnchorProof) -> Result<SubstrateProofData, OperationError> {
+	let ins = proof.input_utxos.len();
+	let outs = proof.output_utxos.len();
+	let public_inputs = &proof.public_inputs;
+	if public_inputs.len() <= 3 + ins + outs {
+		let message = format!(
+			"Expected more than {} public inputs, got {}",
+			3 + ins + outs,
+			public_inputs.len()
+		);
+		return Err(OperationError::new_with_message(
+			OpStatusCode::InvalidProofParameters,
+			message,
+		));
+	}
+	Ok(SubstrateProofData {
+		proof: proof.proof.clone(),
+		public_amount: public_inputs[0].clone(),
+		ext_data_hash: public_inputs[1].clone(),
+		input_nullifiers: public_inputs[2..2 + ins].to_vec(),
+		output_commitments: public_inputs[2 + ins..2 + ins + outs].to_vec(),
+		roots: public_inputs[3 + ins + outs..].to_vec(),
+	})
+}
+
+/// Account of the mixer payload, an EIP-55 address for EVM chains and an
+/// SS58 address for Substrate chains
+fn normalize_account(account: &str, is_evm: bool, code: OpStatusCode) -> Result<String, OperationError> {
+	let invalid = |e: OperationError| OperationError::new_with_message(code, e.error_message);
+	if is_evm {
+		let address = parse_evm_address(account).map_err(invalid)?;
+		Ok(to_checksum_address(&address))
+	} else {
+		ss58_decode(account, None).map_err(invalid)?;
+		Ok(account.to_string())
+	}
+}
+
+#[derive(Default)]
+pub struct WithdrawalRequestBuilder {
+	pub version: Option<RelayerSchemaVersion>,
+	/// Name of the chain in the relayer config
+	pub chain: Option<String>,
+	pub typed_chain_id: Option<TypedChainId>,
+	/// Contract address for EVM chains, tree id for Substrate chains
+	pub target: Option<String>,
+	pub proof: Option<ProofOutput>,
+	pub recipient: Option<String>,
+	pub relayer: Option<String>,
+	pub fee: Option<u128>,
+	pub refund: Option<u128>,
+	pub ext_data: Option<ExtData>,
+	pub unwrap_token: Option<String>,
+}
+
+impl WithdrawalRequestBuilder {
+	pub fn build(self) -> Result<WithdrawalRequest, OperationError> {
+		// Only one layout so far, later versions branch here
+		let RelayerSchemaVersion::V1 = self.version.unwrap_or(RelayerSchemaVersion::V1);
+		let chain = self.chain.ok_or(OpStatusCode::InvalidRelayerPayload)?;
+		let typed_chain_id = self.typed_chain_id.ok_or(OpStatusCode::InvalidChainId)?;
+		let target = self.target.ok_or(OpStatusCode::InvalidRelayerPayload)?;
+		let proof = self.proof.ok_or(OpStatusCode::InvalidProofParameters)?;
+
+		let is_evm = match typed_chain_id.chain_type {
+			Some(ChainType::Evm) => true,
+			Some(ChainType::Substrate)
+			| Some(ChainType::PolkadotParachain)
+			| Some(ChainType::KusamaParachain)
+			| Some(ChainType::RococoParachain) => false,
+			_ => {
+				let message = format!("No relayer payload for the chain {}", typed_chain_id);
+				return Err(OperationError::new_with_message(OpStatusCode::InvalidChainId, message));
+			}
+		};
+		let (contract, tree_id) = if is_evm {
+			let contract = parse_evm_address(&target)
+				.map_err(|e| OperationError::new_with_message(OpStatusCode::InvalidRelayerPayload, e.error_message))?;
+			(to_checksum_address(&contract), 0)
+		} else {
+			(
+				String::new(),
+				target.parse().map_err(|_| OpStatusCode::InvalidRelayerPayload)?,
+			)
+		};
+
+		// VAnchor withdrawals unwrap to the token of their ext data
+		let unwrap_token = match self.unwrap_token {
+			Some(token) if is_evm && matches!(proof, ProofOutput::Mixer(_)) => {
+				Some(normalize_account(&token, true, OpStatusCode::InvalidAddress)?)
+			}
+			Some(_) => {
+				let message = "Only EVM mixer withdrawals unwrap a token".to_string();
+				return Err(OperationError::new_with_message(
+					OpStatusCode::InvalidRelayerPayload,
+					message,
+				));
+			}
+			None => None,
+		};
+
+		let request = match proof {
+			ProofOutput::Mixer(MixerProof {
+				proof,
+				nullifier_hash,
+				root,
+				..
+			}) => {
+				let recipient = normalize_account(
+					&self.recipient.ok_or(OpStatusCode::InvalidRecipient)?,
+					is_evm,
+					OpStatusCode::InvalidRecipient,
+				)?;
+				let relayer = normalize_account(
+					&self.relayer.ok_or(OpStatusCode::InvalidRelayer)?,
+					is_evm,
+					OpStatusCode::InvalidRelayer,
+				)?;
+				let fee = self.fee.ok_or(OpStatusCode::InvalidFee)?;
+				let refund = self.refund.ok_or(OpStatusCode::InvalidRefund)?;
+				if is_evm {
+					WithdrawalRequest::Evm(EvmCommand::Mixer(EvmMixerRelayTx {
+						chain,
+						contract,
+						proof: hex_0x(&proof),
+						root: hex_0x(&root),
+						nullifier_hash: hex_0x(&nullifier_hash),
+						recipient,
+						relayer,
+						fee: format!("{:#x}", fee),
+						refund: format!("{:#x}", refund),
+						unwrap_token,
+					}))
+				} else {
+					WithdrawalRequest::Substrate(SubstrateCommand::Mixer(SubstrateMixerRelayTx {
+						chain,
+						id: tree_id,
+						proof,
+						root,
+						nullifier_hash,
+						recipient,
+						relayer,
+						fee,
+						refund,
+					}))
+				}
+			}
+			ProofOutput::VAnchor(vanchor_proof) => {
+				let ext_data = self.ext_data.ok_or(OpStatusCode::InvalidExtDataHash)?;
+				let proof_data = split_vanchor_public_inputs(&vanchor_proof)?;
+				if is_evm {
+					let hex_all = |values: &[Vec<u8>]| values.iter().map(|v| hex_0x(v)).collect();
+					WithdrawalRequest::Evm(EvmCommand::VAnchor(EvmVAnchorRelayTx {
+						chain,
+						contract,
+						proof_data: EvmProofData {
+							proof: hex_0x(&proof_data.proof),
+							public_amount: hex_0x(&proof_data.public_amount),
+							roots: hex_all(&proof_data.roots),
+							input_nullifiers: hex_all(&proof_data.input_nullifiers),
+							output_commitments: hex_all(&proof_data.output_commitments),
+							ext_data_hash: hex_0x(&proof_data.ext_data_hash),
+						},
+						ext_data: EvmExtData {
+							recipient: evm_address(&ext_data.recipient, OpStatusCode::InvalidRecipient)?,
+							relayer: evm_address(&ext_data.relayer, OpStatusCode::InvalidRelayer)?,
+							ext_amount: evm_int256(ext_data.ext_amount),
+							fee: format!("{:#x}", ext_data.fee),
+							refund: format!("{:#x}", ext_data.refund),
+							token: evm_address(&ext_data.token, OpStatusCode::InvalidTokenSymbol)?,
+							encrypted_output1: hex_0x(&ext_data.encrypted_output1),
+							encrypted_output2: hex_0x(&ext_data.encrypted_output2),
+						},
+					}))
+				} else {
+					WithdrawalRequest::Substrate(SubstrateCommand::VAnchor(SubstrateVAnchorRelayTx {
+						chain,
+						id: tree_id,
+						proof_data,
+						ext_data: SubstrateExtData {
+							recipient: ext_data.recipient,
+							relayer: ext_data.relayer,
+							ext_amount: ext_data.ext_amount,
+							fee: ext_data.fee,
+							refund: ext_data.refund,
+							token: ext_data.token,
+							encrypted_output1: ext_data.encrypted_output1,
+							encrypted_output2: ext_data.encrypted_output2,
+						},
+					}))
+				}
+			}
+		};
+		Ok(request)
+	}
+}
+
+#[wasm_bindgen(js_name = WithdrawalRequest)]
+pub struct JsWithdrawalRequest {
+	#[wasm_bindgen(skip)]
+	pub inner: WithdrawalRequest,
+}
+
+#[wasm_bindgen(js_class = WithdrawalRequest)]
+impl JsWithdrawalRequest {
+	/// Payload as a JSON string, amounts keep their full precision
+	#[wasm_bindgen(js_name = toJson)]
+	pub fn to_json(&self) -> Result<JsString, JsValue> {
+		let json = serde_json::to_string(&self.inner)
+			.map_err(|e| OperationError::new_with_message(OpStatusCode::SerializationFailed, e.to_string()))?;
+		Ok(json.into())
+	}
+}
+
+#[wasm_bindgen(js_name = WithdrawalRequestBuilder)]
+#[derive(Default)]
+pub struct JsWithdrawalRequestBuilder {
+	#[wasm_bindgen(skip)]
+	pub inner: WithdrawalRequestBuilder,
+}
+
+#[wasm_bindgen(js_class = WithdrawalRequestBuilder)]
+impl JsWithdrawalRequestBuilder {
+	#[wasm_bindgen(constructor)]
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	#[wasm_bindgen(js_name = setVersion)]
+	pub fn set_version(&mut self, version: JsString) -> Result<(), JsValue> {
+		let version: String = version.into();
+		self.inner.version = Some(version.parse()?);
+		Ok(())
+	}
+
+	/// Chain name in the relayer config and the typed chain id, the chain type
+	/// picks the EVM or Substrate payload
+	#[wasm_bindgen(js_name = setChain)]
+	pub fn set_chain(&mut self, chain: JsString, typed_chain_id: JsString) -> Result<(), JsValue> {
+		let typed_chain_id: String = typed_chain_id.into();
+		self.inner.typed_chain_id = Some(typed_chain_id.parse()?);
+		self.inner.chain = Some(chain.into());
+		Ok(())
+	}
+
+	/// Contract address for EVM chains, tree id for Substrate chains
+	#[wasm_bindgen(js_name = setTarget)]
+	pub fn set_target(&mut self, target: JsString) {
+		self.inner.target = Some(target.into());
+	}
+
+	#[wasm_bindgen(js_name = setProof)]
+	pub fn set_proof(&mut self, proof: &JsProofOutput) {
+		self.inner.proof = Some(proof.inner.clone());
+	}
+
+	#[wasm_bindgen(js_name = setRecipient)]
+	pub fn set_recipient(&mut self, recipient: JsString) {
+		self.inner.recipient = Some(recipient.into());
+	}
+
+	#[wasm_bindgen(js_name = setRelayer)]
+	pub fn set_relayer(&mut self, relayer: JsString) {
+		self.inner.relayer = Some(relayer.into());
+	}
+
+	#[wasm_bindgen(js_name = setFee)]
+	pub fn set_fee(&mut self, fee: JsString) -> Result<(), JsValue> {
+		let fee = parse_amount_field(&String::from(fee), None, OpStatusCode::InvalidFee)?;
+		self.inner.fee = Some(fee);
+		Ok(())
+	}
+
+	#[wasm_bindgen(js_name = setRefund)]
+	pub fn set_refund(&mut self, refund: JsString) -> Result<(), JsValue> {
+		let refund = parse_amount_field(&String::from(refund), None, OpStatusCode::InvalidRefund)?;
+		self.inner.refund = Some(refund);
+		Ok(())
+	}
+
+	/// Base asset to pay an EVM mixer withdrawal in, the anchor unwrapping
+	/// the bridge token
+	#[wasm_bindgen(js_name = setUnwrapToken)]
+	pub fn set_unwrap_token(&mut self, token: JsString) {
+		self.inner.unwrap_token = Some(token.into());
+	}
+
+	/// External data of a VAnchor transaction
+	#[wasm_bindgen(js_name = setExtData)]
+	pub fn set_ext_data(&mut self, ext_data: &ExtData) {
+		self.inner.ext_data = Some(ext_data.clone());
+	}
+
+	pub fn build(self) -> Result<JsWithdrawalRequest, JsValue> {
+		let inner = self.inner.build()?;
+		Ok(JsWithdrawalRequest { inner })
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use serde_json::{json, Value};
+	use wasm_bindgen_test::*;
+
+	use super::*;
+	use crate::address::ss58_encode;
+
+	const CONTRACT: &str = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+
+	fn mixer_proof() -> ProofOutput {
+		ProofOutput::Mixer(MixerProof {
+			proof: vec![1, 2],
+			nullifier_hash: vec![3; 32],
+			root: vec![4; 32],
+			public_inputs: vec![],
+			leaf: vec![],
+		})
+	}
+
+	#[wasm_bindgen_test]
+	fn should_build_evm_mixer_request() {
+		let builder = || WithdrawalRequestBuilder {
+			chain: Some("hermes".to_string()),
+			typed_chain_id: Some(TypedChainId::new(ChainType::Evm, 5001)),
+			target: Some(CONTRACT.to_lowercase()),
+			proof: Some(mixer_proof()),
+			recipient: Some(CONTRACT.to_string()),
+			relayer: Some(CONTRACT.to_lowercase()),
+			fee: Some(255),
+			refund: Some(0),
+			..Default::default()
+		};
+		let request = builder().build().unwrap();
+		let value: Value = serde_json::from_str(&serde_json::to_string(&request).unwrap()).unwrap();
+		assert_eq!(
+			value,
+			json!({
+				"evm": {
+					"mixer": {
+						"chain": "hermes",
+						"contract": CONTRACT,
+						"proof": "0x0102",
+						"root": format!("0x{}", "04".repeat(32)),
+						"nullifierHash": format!("0x{}", "03".repeat(32)),
+						"recipient": CONTRACT,
+						"relayer": CONTRACT,
+						"fee": "0xff",
+						"refund": "0x0",
+					}
+				}
+			})
+		);
+
+		let request = WithdrawalRequestBuilder {
+			unwrap_token: Some(CONTRACT.to_lowercase()),
+			..builder()
+		}
+		.build()
+		.unwrap();
+		let value: Value = serde_json::from_str(&serde_json::to_string(&request).unwrap()).unwrap();
+		assert_eq!(value["evm"]["mixer"]["unwrapToken"], CONTRACT);
+	}
+
+	#[wasm_bindgen_test]
+	fn should_build_substrate_mixer_request() {
+		let account = ss58_encode(&[7u8; 32], 42).unwrap();
+		let builder = |recipient: &str| WithdrawalRequestBuilder {
+			chain: Some("webb".to_string()),
+			typed_chain_id: Some(TypedChainId::new(ChainType::Substrate, 1080)),
+			target: Some("0".to_string()),
+			proof: Some(mixer_proof()),
+			recipient: Some(recipient.to_string()),
+			relayer: Some(account.clone()),
+			fee: Some(1),
+			refund: Some(0),
+			..Default::default()
+		};
+		let request = builder(&account).build().unwrap();
+		match &request {
+			WithdrawalRequest::Substrate(SubstrateCommand::Mixer(tx)) => {
+				assert_eq!(tx.id, 0);
+				assert_eq!(tx.root, vec![4; 32]);
+				assert_eq!(tx.recipient, account);
+			}
+			_ => panic!("Expected a Substrate mixer request"),
+		}
+		let json = serde_json::to_string(&request).unwrap();
+		assert_eq!(serde_json::from_str::<WithdrawalRequest>(&json).unwrap(), request);
+
+		let err = builder(CONTRACT).build().unwrap_err();
+		assert_eq!(err.code, OpStatusCode::InvalidRecipient);
+		let err = WithdrawalRequestBuilder {
+			unwrap_token: Some(CONTRACT.to_string()),
+			..builder(&account)
+		}
+		.build()
+		.unwrap_err();
+		assert_eq!(err.code, OpStatusCode::InvalidRelayerPayload);
+	}
+}
@@ -0,0 +1,187 @@
+//! Choice of the local root to prove against. Anchors only accept roots of
+//! their history window, the last roots of the tree, and a proof generated
+//! against the latest root the client knows of can have it evicted by new
+//! deposits before it lands. Proving against the newest root of the window
+//! the client's leaves lead to gives the proof the most time to land.
+use ark_bn254::Fr as Bn254Fr;
+use ark_ff::{BigInteger, PrimeField};
+use js_sys::{Array, Uint8Array};
+use wasm_bindgen::prelude::*;
+
+use crate::merkle::{leaves_from_js, MerkleTree};
+use crate::proof::roots_from_js;
+use crate::types::{Leaves, OpStatusCode, OperationError};
+
+/// A root of the history window containing the leaf to spend
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProvableRoot {
+	#[wasm_bindgen(skip)]
+	pub root: Vec<u8>,
+	/// Number of leaves of the tree with that root, the leaves to build the
+	/// merkle path from
+	#[wasm_bindgen(skip)]
+	pub leaf_count: usize,
+	/// Index of the root in the history window, oldest first
+	#[wasm_bindgen(skip)]
+	pub history_index: usize,
+	/// The root followed by the neighbor roots
+	#[wasm_bindgen(skip)]
+	pub roots: Vec<Vec<u8>>,
+}
+
+/// Pick the newest root of `local_root_history`, given oldest first, that a
+/// prefix of `leaves` containing the leaf at `leaf_index` hashes to
+pub fn select_provable_root(
+	local_root_history: &[Vec<u8>],
+	neighbor_roots: &[Vec<u8>],
+	leaves: &[[u8; 32]],
+	leaf_index: u64,
+) -> Result<ProvableRoot, OperationError> {
+	if leaf_index >= leaves.len() as u64 {
+		let message = format!(
+			"Leaf index {} is out of bounds for a tree with {} leaves",
+			leaf_index,
+			leaves.len()
+		);
+		return Err(OperationError::new_with_message(
+			OpStatusCode::InvalidLeafIndex,
+			message,
+		));
+	}
+	let leaves: Vec<Bn254Fr> = leaves
+		.iter()
+		.map(|leaf| Bn254Fr::from_be_bytes_mod_order(leaf))
+		.collect();
+	// Roots of the trees holding the leaf, by number of leaves
+	let first_count = leaf_index as usize + 1;
+	let mut tree = MerkleTree::new();
+	tree.insert_batch(&leaves[..first_count])?;
+	let mut prefix_roots = vec![tree.root()];
+	prefix_roots.extend(tree.insert_with_roots(&leaves[first_count..])?);
+	let prefix_roots: Vec<Vec<u8>> = prefix_roots.iter().map(|root| root.into_repr().to_bytes_be()).collect();
+
+	let (history_index, leaf_count) = local_root_history
+		.iter()
+		.enumerate()
+		.rev()
+		.find_map(|(history_index, root)| {
+			let position = prefix_roots.iter().position(|prefix_root| prefix_root == root)?;
+			Some((history_index, first_count + position))
+		})
+		.ok_or_else(|| {
+			let message = format!(
+				"None of the {} roots of the history window contains leaf {}, the leaves are out of date or \
+				 the window moved past them",
+				local_root_history.len(),
+				leaf_index
+			);
+			OperationError::new_with_message(OpStatusCode::InvalidRoots, message)
+		})?;
+	let root = local_root_history[history_index].clone();
+	let mut roots = vec![root.clone()];
+	roots.extend_from_slice(neighbor_roots);
+	Ok(ProvableRoot {
+		root,
+		leaf_count,
+		history_index,
+		roots,
+	})
+}
+
+#[allow(clippy::unused_unit)]
+#[wasm_bindgen]
+impl ProvableRoot {
+	#[wasm_bindgen(getter)]
+	pub fn root(&self) -> Uint8Array {
+		Uint8Array::from(self.root.as_slice())
+	}
+
+	#[wasm_bindgen(getter, js_name = leafCount)]
+	pub fn leaf_count(&self) -> usize {
+		self.leaf_count
+	}
+
+	#[wasm_bindgen(getter, js_name = historyIndex)]
+	pub fn history_index(&self) -> usize {
+		self.history_index
+	}
+
+	/// Roots of the proof, pad them with `packRoots` for more anchors
+	#[wasm_bindgen(getter)]
+	pub fn roots(&self) -> Leaves {
+		let roots: Array = self
+			.roots
+			.iter()
+			.map(|root| Uint8Array::from(root.as_slice()))
+			.collect();
+		Leaves::from(JsValue::from(roots))
+	}
+}
+
+/// The root of `localRootHistory` to prove the leaf at `leafIndex` against,
+/// the newest one consistent with `leaves`
+#[wasm_bindgen(js_name = selectProvableRoot)]
+pub fn js_select_provable_root(
+	local_root_history: Leaves,
+	neighbor_roots: Leaves,
+	leaves: Leaves,
+	leaf_index: u64,
+) -> Result<ProvableRoot, JsValue> {
+	Ok(select_provable_root(
+		&roots_from_js(&local_root_history),
+		&roots_from_js(&neighbor_roots),
+		&leaves_from_js(&leaves)?,
+		leaf_index,
+	)?)
+}
+
+#[cfg(test)]
+mod test {
+	use wasm_bindgen_test::*;
+
+	use super::*;
+
+	fn leaf(i: u8) -> [u8; 32] {
+		let mut leaf = [0u8; 32];
+		leaf[31] = i + 1;
+		leaf
+	}
+
+	#[wasm_bindgen_test]
+	fn should_select_the_newest_root_containing_the_leaf() {
+		let leaves: Vec<[u8; 32]> = (0..6).map(leaf).collect();
+		let fields: Vec<Bn254Fr> = leaves
+			.iter()
+			.map(|leaf| Bn254Fr::from_be_bytes_mod_order(leaf))
+			.collect();
+		let mut tree = MerkleTree::new();
+		let roots: Vec<Vec<u8>> = tree
+			.insert_with_roots(&fields)
+			.unwrap()
+			.iter()
+			.map(|root| root.into_repr().to_bytes_be())
+			.collect();
+		let neighbor_root = vec![9u8; 32];
+
+		// The window holds the roots after 3 to 5 leaves, the client knows 4
+		let history = roots[2..5].to_vec();
+		let selected = select_provable_root(&history, &[neighbor_root.clone()], &leaves[..4], 1).unwrap();
+		assert_eq!(selected.root, roots[3]);
+		assert_eq!(selected.leaf_count, 4);
+		assert_eq!(selected.history_index, 1);
+		assert_eq!(selected.roots, [roots[3].clone(), neighbor_root]);
+
+		// All the leaves lead to the newest root
+		let selected = select_provable_root(&history, &[], &leaves, 2).unwrap();
+		assert_eq!((selected.leaf_count, selected.history_index), (5, 2));
+		// A leaf newer than the window can't be proven
+		let error = select_provable_root(&history, &[], &leaves, 5).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::InvalidRoots);
+		// The window moved past the known leaves
+		let error = select_provable_root(&roots[4..], &[], &leaves[..3], 0).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::InvalidRoots);
+		let error = select_provable_root(&history, &[], &leaves[..3], 3).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::InvalidLeafIndex);
+	}
+}
@@ -0,0 +1,127 @@
+//! Performance reports of `generate_proof_js`, for dApps tracking how fast
+//! proofs are on their users' devices. Once a hook is set with
+//! `setProofTelemetryHook` every proof reports the size of its circuit, the
+//! time spent in each phase and the wasm heap it needed. Counting the
+//! constraints synthesizes the circuit once more before proving, so nothing
+//! is measured while no hook is set.
+use std::cell::RefCell;
+
+use js_sys::Function;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::memory::memory_stats;
+use crate::proof::witness::create_witness;
+use crate::proof::{ProofInput, ProofOutput};
+use crate::types::OperationError;
+
+thread_local! {
+	static HOOK: RefCell<Option<Function>> = RefCell::new(None);
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ProofTelemetry {
+	pub protocol: String,
+	pub curve: String,
+	pub tree_depth: usize,
+	/// Size of the circuit, unknown when the input doesn't satisfy it
+	pub constraints: Option<usize>,
+	pub variables: Option<usize>,
+	/// Synthesizing the circuit to count its constraints, in milliseconds
+	pub synthesis_ms: f64,
+	pub prove_ms: f64,
+	pub total_ms: f64,
+	/// Size of the wasm heap before the proof and after it, which is its peak
+	/// since the heap never shrinks
+	pub heap_bytes_before: usize,
+	pub peak_heap_bytes: usize,
+	/// Error code of a failed proof
+	pub error: Option<String>,
+}
+
+/// Measurements of a proof in progress
+pub struct TelemetryRecorder {
+	hook: Function,
+	telemetry: ProofTelemetry,
+	started_at: f64,
+}
+
+impl TelemetryRecorder {
+	/// Start measuring the proof of `input` if a hook is set, counting the
+	/// constraints of its circuit
+	pub fn start(input: &ProofInput) -> Option<Self> {
+		let hook = HOOK.with(|hook| hook.borrow().clone())?;
+		let started_at = js_sys::Date::now();
+		let heap_bytes_before = memory_stats().heap_bytes;
+		let (protocol, curve, tree_depth) = match input {
+			ProofInput::Mixer(input) => ("mixer", input.curve, input.tree_depth),
+			ProofInput::VAnchor(input) => ("vanchor", input.curve, input.tree_depth),
+		};
+		let witness = create_witness(input.clone()).ok();
+		Some(Self {
+			hook,
+			telemetry: ProofTelemetry {
+				protocol: protocol.to_string(),
+				curve: curve.to_string(),
+				tree_depth,
+				constraints: witness.as_ref().map(|witness| witness.num_constraints),
+				variables: witness.as_ref().map(|witness| witness.assignment.len()),
+				synthesis_ms: js_sys::Date::now() - started_at,
+				prove_ms: 0.0,
+				total_ms: 0.0,
+				heap_bytes_before,
+				peak_heap_bytes: 0,
+				error: None,
+			},
+			started_at,
+		})
+	}
+
+	/// Report the proof to the hook, errors thrown by the hook are ignored
+	pub fn finish(mut self, prove_ms: f64, outcome: &Result<ProofOutput, OperationError>) -> ProofTelemetry {
+		self.telemetry.prove_ms = prove_ms;
+		self.telemetry.total_ms = js_sys::Date::now() - self.started_at;
+		self.telemetry.peak_heap_bytes = memory_stats().heap_bytes;
+		if let Err(e) = outcome {
+			self.telemetry.error = Some(e.code.name());
+		}
+		if let Ok(telemetry) = JsValue::from_serde(&self.telemetry) {
+			let _ = self.hook.call1(&JsValue::NULL, &telemetry);
+		}
+		self.telemetry
+	}
+}
+
+#[wasm_bindgen(typescript_custom_section)]
+const PROOF_TELEMETRY: &str = r#"
+export interface ProofTelemetry {
+	protocol: NoteProtocol;
+	curve: Curve;
+	treeDepth: number;
+	constraints?: number;
+	variables?: number;
+	synthesisMs: number;
+	proveMs: number;
+	totalMs: number;
+	heapBytesBefore: number;
+	peakHeapBytes: number;
+	error?: ErrorCode;
+}
+"#;
+
+#[wasm_bindgen]
+extern "C" {
+	#[wasm_bindgen(typescript_type = "(telemetry: ProofTelemetry) => void")]
+	pub type ProofTelemetryHook;
+}
+
+/// Call `hook` with the `ProofTelemetry` of every proof `generate_proof_js`
+/// makes from now on, or stop reporting when not given. The report adds a
+/// synthesis of the circuit to every proof
+#[wasm_bindgen(js_name = setProofTelemetryHook)]
+pub fn set_proof_telemetry_hook(hook: Option<ProofTelemetryHook>) {
+	let hook = hook.map(|hook| hook.unchecked_into::<Function>());
+	HOOK.with(|current| *current.borrow_mut() = hook);
+}
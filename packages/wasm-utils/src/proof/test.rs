@@ -1,19 +1,44 @@
+use ark_bls12_381::Bls12_381;
 use ark_bn254::Bn254;
 use ark_ff::{BigInteger, PrimeField};
-use arkworks_setups::common::{setup_keys_unchecked, verify_unchecked_raw};
+use ark_groth16::VerifyingKey;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use arkworks_setups::common::{setup_keys_unchecked, verify, verify_unchecked_raw};
 use arkworks_setups::Curve;
-use js_sys::{Array, JsString, Uint8Array};
+use crypto_box::aead::Aead;
+use crypto_box::{PublicKey, SalsaBox, SecretKey};
+use ethabi::ParamType;
+use js_sys::{Array, JsString, Reflect, Uint8Array};
 use rand::rngs::OsRng;
-use wasm_bindgen::JsValue;
+use serde_json::json;
+use wasm_bindgen::{JsCast, JsValue};
 use wasm_bindgen_test::*;
 
+use crate::evm::{selector, vanchor_transact, VANCHOR_TRANSACT};
+use crate::note::{JsNote, JsNoteBuilder};
+use crate::proof::aggregate::AggregateWithdrawal;
+use crate::proof::config::ProofInputConfig;
+use crate::proof::debug::{export_debug_bundle, last_bundle, DebugBundle, DEBUG_BUNDLE_FORMAT};
+use crate::proof::delegate::{DelegatedProver, DelegatedRequest, DelegatedResponse};
+use crate::proof::ext_data::ExtData;
+use crate::proof::groth16::{JsGroth16Proof, EVM_PROOF_WORDS};
+use crate::proof::mixer::MixerProofPayload;
+use crate::proof::refresh::{PartialWithdrawal, RefreshCommitment};
+use crate::proof::telemetry::{set_proof_telemetry_hook, ProofTelemetry};
 use crate::proof::test_utils::{
 	generate_mixer_test_setup, generate_vanchor_test_setup_16_mixed_inputs,
 	generate_vanchor_test_setup_16_non_default_inputs, generate_vanchor_test_setup_2_inputs, generate_vanchor_utxo,
-	new_utxo_bn254_2_2, MixerTestSetup, VAnchorTestSetup, DECODED_SUBSTRATE_ADDRESS, MIXER_NOTE_V1_X5_5,
+	new_utxo_bn254_2_2, MixerTestSetup, VAnchorTestSetup, DECODED_SUBSTRATE_ADDRESS, MIXER_NOTE_V1_BLS381_X5_3,
+	MIXER_NOTE_V1_X5_5, VANCHOR_NOTE_V1_X5_4,
 };
-use crate::proof::{generate_proof_js, truncate_and_pad, JsProofInputBuilder, LeavesMapInput, MTBn254X5};
-use crate::types::{Indices, Leaves};
+use crate::proof::vanchor::VAnchorProof;
+use crate::proof::verifying_key::JsVerifyingKey;
+use crate::proof::witness::create_witness;
+use crate::proof::{
+	generate_proof_js, mixer, pack_roots, setup_keys, setup_keys_with_tree_depth, truncate_and_pad, validate_roots,
+	JsProofInput, JsProofInputBuilder, LeavesMapInput, MTBn254X5, ProofInput, ProofInputBuilder, ProofOutput,
+};
+use crate::types::{Backend, ChainType, Indices, Leaves, OpStatusCode, TargetRuntime, TypedChainId};
 use crate::utxo::JsUtxo;
 use crate::{VAnchorR1CSProverBn254_30_2_2_2, DEFAULT_LEAF};
 
@@ -51,6 +76,568 @@ fn mixer_js_setup() {
 	assert_eq!(hex::encode(&mixer_input.leaves[0]), hex::encode(leaf_bytes));
 }
 
+#[wasm_bindgen_test]
+fn should_validate_mixer_proof_input() {
+	let MixerTestSetup {
+		mut proof_input_builder,
+		..
+	} = generate_mixer_test_setup(DECODED_SUBSTRATE_ADDRESS, DECODED_SUBSTRATE_ADDRESS, MIXER_NOTE_V1_X5_5);
+	assert!(proof_input_builder.inner.validate().is_empty());
+	assert_eq!(proof_input_builder.validate().length(), 0);
+
+	proof_input_builder = proof_input_builder.set_leaf_index(JsString::from("1")).unwrap();
+	let errors = proof_input_builder.inner.validate();
+	assert_eq!(errors.len(), 1);
+	assert_eq!(errors[0].code, OpStatusCode::InvalidLeafIndex);
+}
+
+#[wasm_bindgen_test]
+fn should_build_mixer_proof_input_from_an_object() {
+	let MixerTestSetup {
+		proof_input_builder,
+		leaf_bytes,
+		..
+	} = generate_mixer_test_setup(DECODED_SUBSTRATE_ADDRESS, DECODED_SUBSTRATE_ADDRESS, MIXER_NOTE_V1_X5_5);
+	let pk = match &proof_input_builder.inner {
+		ProofInputBuilder::Mixer(input) => input.pk.clone().unwrap(),
+		_ => unreachable!(),
+	};
+	let config = json!({
+		"protocol": "mixer",
+		"note": MIXER_NOTE_V1_X5_5,
+		"leaves": [format!("0x{}", hex::encode(&leaf_bytes))],
+		"leafIndex": 0,
+		"recipient": DECODED_SUBSTRATE_ADDRESS,
+		"relayer": DECODED_SUBSTRATE_ADDRESS,
+		"fee": "5",
+		"refund": "1",
+		"pk": hex::encode(&pk),
+	});
+	let builder = JsProofInputBuilder::from_object(JsValue::from_serde(&config).unwrap().unchecked_into()).unwrap();
+	// Same input as the one of the setters
+	assert_eq!(
+		format!("{:?}", builder.inner),
+		format!("{:?}", proof_input_builder.inner)
+	);
+
+	// Every problem is reported at once, a field that fails to parse isn't
+	// reported missing as well
+	let config = ProofInputConfig {
+		protocol: "mixer".to_string(),
+		note: Some(MIXER_NOTE_V1_X5_5.to_string()),
+		leaves: Some(vec![hex::encode(&leaf_bytes)]),
+		leaf_index: Some(0),
+		recipient: Some("0x8eb24319393716668d768dcec29356ae9cffe28".to_string()),
+		relayer: Some(DECODED_SUBSTRATE_ADDRESS.to_string()),
+		fee: Some("five".to_string()),
+		refund: Some("1".to_string()),
+		..Default::default()
+	};
+	let errors = config.to_builder().unwrap_err();
+	let codes: Vec<_> = errors.iter().map(|error| error.code.clone()).collect();
+	assert_eq!(codes, [
+		OpStatusCode::InvalidRecipient,
+		OpStatusCode::InvalidFee,
+		OpStatusCode::InvalidProvingKey
+	]);
+	assert_eq!(errors[1].data.as_deref(), Some(r#"{"field":"fee"}"#));
+
+	// Unknown fields are rejected rather than ignored
+	let typo = json!({ "protocol": "mixer", "leafIdx": 0 });
+	let errors = JsProofInputBuilder::from_object(JsValue::from_serde(&typo).unwrap().unchecked_into()).unwrap_err();
+	let errors = Array::from(&errors);
+	assert_eq!(errors.length(), 1);
+	let code = Reflect::get(&errors.get(0), &JsValue::from("code")).unwrap();
+	assert_eq!(code.as_string(), Some(OpStatusCode::DeserializationFailed.name()));
+}
+
+#[wasm_bindgen_test]
+fn should_check_native_refunds() {
+	let setup = || generate_mixer_test_setup(DECODED_SUBSTRATE_ADDRESS, DECODED_SUBSTRATE_ADDRESS, MIXER_NOTE_V1_X5_5);
+	// Refunds of EVM chains are in ether
+	let mut proof_input_builder = setup().proof_input_builder;
+	proof_input_builder.inner.chain_type(ChainType::Evm).unwrap();
+	proof_input_builder = proof_input_builder
+		.set_native_refund(JsString::from("0.002"), None)
+		.unwrap();
+	proof_input_builder = proof_input_builder
+		.set_max_refund(JsString::from("0.001"), None)
+		.unwrap();
+	let errors = proof_input_builder.inner.validate();
+	assert_eq!(errors.len(), 1);
+	assert_eq!(errors[0].code, OpStatusCode::InvalidRefund);
+	assert_eq!(errors[0].data.as_deref(), Some(r#"{"maxRefund":"1000000000000000"}"#));
+
+	// Substrate chains don't tell the decimals of their token
+	let mut proof_input_builder = setup().proof_input_builder;
+	proof_input_builder.inner.chain_type(ChainType::Substrate).unwrap();
+	assert!(proof_input_builder
+		.clone()
+		.set_native_refund(JsString::from("0.5"), None)
+		.is_err());
+	proof_input_builder = proof_input_builder
+		.set_native_refund(JsString::from("0.5"), Some(12))
+		.unwrap();
+	proof_input_builder = proof_input_builder
+		.set_max_refund(JsString::from("0.5"), Some(12))
+		.unwrap();
+	assert!(proof_input_builder.inner.validate().is_empty());
+	let build = |builder: &JsProofInputBuilder| {
+		let inner = builder.inner.clone();
+		JsProofInputBuilder {
+			inner,
+			partial_withdrawal: None,
+		}
+		.build()
+	};
+	let proof_input = build(&proof_input_builder).unwrap();
+	assert_eq!(proof_input.mixer_input().unwrap().refund, 500_000_000_000);
+
+	// Native token pools take no refund
+	proof_input_builder = proof_input_builder.set_native_token_pool().unwrap();
+	let error = build(&proof_input_builder).unwrap_err();
+	assert_eq!(error.code, OpStatusCode::InvalidRefund);
+	proof_input_builder = proof_input_builder
+		.set_native_refund(JsString::from("0"), None)
+		.unwrap();
+	assert!(build(&proof_input_builder).is_ok());
+}
+
+#[wasm_bindgen_test]
+fn should_report_missing_vanchor_proof_input_fields() {
+	let protocol = JsValue::from("vanchor").into();
+	let mut proof_input_builder = JsProofInputBuilder::new(protocol).unwrap();
+	proof_input_builder = proof_input_builder.set_pk(JsString::from("0000")).unwrap();
+	proof_input_builder = proof_input_builder.chain_id(JsString::from("0")).unwrap();
+
+	let codes: Vec<OpStatusCode> = proof_input_builder
+		.inner
+		.validate()
+		.into_iter()
+		.map(|e| e.code)
+		.collect();
+	assert_eq!(codes, vec![
+		OpStatusCode::InvalidInputUtxoConfig,
+		OpStatusCode::InvalidLeaves,
+		OpStatusCode::InvalidExtDataHash,
+		OpStatusCode::InvalidRoots,
+		OpStatusCode::InvalidIndices,
+		OpStatusCode::InvalidPublicAmount,
+		OpStatusCode::InvalidOutputUtxoConfig,
+	]);
+}
+
+#[wasm_bindgen_test]
+fn should_pad_roots_with_the_local_root() {
+	let bn254 = crate::types::Curve::Bn254;
+	let local_root = vec![1u8; 32];
+	let neighbor_roots = vec![vec![2u8; 32], vec![3u8; 32]];
+	let roots = pack_roots(&local_root, &neighbor_roots, 4, bn254).unwrap();
+	assert_eq!(roots, [
+		local_root.clone(),
+		neighbor_roots[0].clone(),
+		neighbor_roots[1].clone(),
+		local_root.clone()
+	]);
+	assert_eq!(pack_roots(&local_root, &[], 2, bn254).unwrap(), [
+		local_root.clone(),
+		local_root.clone()
+	]);
+	assert_eq!(
+		pack_roots(&local_root, &neighbor_roots[..1], 2, bn254).unwrap().len(),
+		2
+	);
+
+	// Too many neighbors, or an invalid one
+	let error = pack_roots(&local_root, &neighbor_roots, 2, bn254).unwrap_err();
+	assert_eq!(error.code, OpStatusCode::InvalidRoots);
+	assert_eq!(
+		pack_roots(&local_root, &[], 0, bn254).unwrap_err().code,
+		OpStatusCode::InvalidRoots
+	);
+	let error = pack_roots(&local_root, &[vec![0xff; 32]], 2, bn254).unwrap_err();
+	assert_eq!(error.data.as_deref(), Some(r#"{"index":1}"#));
+}
+
+#[wasm_bindgen_test]
+fn should_report_the_invalid_root_index() {
+	let bn254 = crate::types::Curve::Bn254;
+	let root = DEFAULT_LEAF.to_vec();
+	assert!(validate_roots(&[root.clone(), root.clone()], 2, bn254).is_ok());
+
+	let error = validate_roots(&[root.clone()], 2, bn254).unwrap_err();
+	assert_eq!(error.code, OpStatusCode::InvalidRoots);
+	assert_eq!(error.data, None);
+
+	let error = validate_roots(&[root.clone(), root[..31].to_vec()], 2, bn254).unwrap_err();
+	assert_eq!(error.code, OpStatusCode::InvalidRoots);
+	assert_eq!(error.data.as_deref(), Some(r#"{"index":1}"#));
+
+	// Above the modulus of both scalar fields
+	let error = validate_roots(&[vec![0xff; 32], root.clone()], 2, crate::types::Curve::Bls381).unwrap_err();
+	assert_eq!(error.code, OpStatusCode::PublicInputOverflow);
+	assert_eq!(error.data.as_deref(), Some(r#"{"index":0}"#));
+
+	// A short root given to the builder is reported rather than truncated
+	let protocol = JsValue::from("vanchor").into();
+	let mut proof_input_builder = JsProofInputBuilder::new(protocol).unwrap();
+	let roots: Array = vec![Uint8Array::from(root.as_slice()), Uint8Array::from(&root[..16])]
+		.into_iter()
+		.collect();
+	proof_input_builder = proof_input_builder
+		.set_roots(Leaves::from(JsValue::from(roots)))
+		.unwrap();
+	let errors = proof_input_builder.inner.validate();
+	let error = errors.iter().find(|e| e.code == OpStatusCode::InvalidRoots).unwrap();
+	assert_eq!(error.data.as_deref(), Some(r#"{"index":1}"#));
+}
+
+#[wasm_bindgen_test]
+fn should_generate_refresh_commitment() {
+	let template = JsNote::deserialize(MIXER_NOTE_V1_X5_5).unwrap();
+	let refresh = RefreshCommitment::generate(&template, None).unwrap();
+	assert_ne!(refresh.note.secrets, template.secrets);
+	assert_eq!(refresh.commitment, refresh.note.get_leaf_commitment().unwrap().to_vec());
+
+	let template = JsNote::deserialize(VANCHOR_NOTE_V1_X5_4).unwrap();
+	let refresh = RefreshCommitment::generate(&template, Some(4)).unwrap();
+	assert_eq!(refresh.note.amount, Some("4".to_string()));
+	assert_eq!(refresh.commitment, refresh.note.get_leaf_commitment().unwrap().to_vec());
+	assert_ne!(refresh.commitment, template.get_leaf_commitment().unwrap().to_vec());
+
+	let protocol = JsValue::from("vanchor").into();
+	let mut proof_input_builder = JsProofInputBuilder::new(protocol).unwrap();
+	proof_input_builder = proof_input_builder.set_refresh_commitment(&refresh).unwrap();
+	let output_utxos = match &proof_input_builder.inner {
+		ProofInputBuilder::VAnchor(input) => input.output_utxos.clone().unwrap(),
+		_ => unreachable!(),
+	};
+	assert_eq!(output_utxos[0].get_commitment(), refresh.commitment);
+	assert_eq!(output_utxos[0].get_amount_raw(), 4);
+	assert_eq!(output_utxos[1].get_amount_raw(), 0);
+
+	let protocol = JsValue::from("mixer").into();
+	let proof_input_builder = JsProofInputBuilder::new(protocol).unwrap();
+	assert!(proof_input_builder.set_refresh_commitment(&refresh).is_err());
+}
+
+#[wasm_bindgen_test]
+fn should_split_partial_withdrawal_into_change_note() {
+	let note = JsNoteBuilder::new()
+		.protocol(JsValue::from("vanchor").into())
+		.unwrap()
+		.version(JsValue::from("v1").into())
+		.unwrap()
+		.source_chain_id(JsString::from("3"))
+		.target_chain_id(JsString::from("3"))
+		.source_identifying_data(JsString::from("3"))
+		.target_identifying_data(JsString::from("3"))
+		.amount(JsString::from("10"))
+		.index(JsString::from("10"))
+		.unwrap()
+		.build()
+		.unwrap();
+
+	let protocol = JsValue::from("vanchor").into();
+	let proof_input_builder = JsProofInputBuilder::new(protocol).unwrap();
+	assert!(proof_input_builder.partial_withdrawal().is_none());
+	let proof_input_builder = proof_input_builder
+		.set_partial_withdrawal(&note, JsString::from("6"), Some(JsString::from("1")), None)
+		.unwrap();
+	let withdrawal = proof_input_builder.partial_withdrawal().unwrap();
+
+	assert_eq!(withdrawal.ext_amount, -6);
+	assert_eq!(withdrawal.public_amount, -7);
+	assert_eq!(withdrawal.change.note.amount, Some("3".to_string()));
+	assert_eq!(withdrawal.indices, vec![10, 0]);
+
+	let input = match &proof_input_builder.inner {
+		ProofInputBuilder::VAnchor(input) => input.clone(),
+		_ => unreachable!(),
+	};
+	let in_amount: u128 = input
+		.input_utxos
+		.unwrap()
+		.iter()
+		.map(|utxo| utxo.get_amount_raw())
+		.sum();
+	let out_amount: u128 = input
+		.output_utxos
+		.unwrap()
+		.iter()
+		.map(|utxo| utxo.get_amount_raw())
+		.sum();
+	assert_eq!(in_amount as i128 + input.public_amount.unwrap(), out_amount as i128);
+
+	let error = PartialWithdrawal::new(&note, 10, 1, 0).err().unwrap();
+	assert_eq!(error.code, OpStatusCode::InsufficientBalance);
+	let error = PartialWithdrawal::new(&note, 1, u128::MAX, 0).err().unwrap();
+	assert_eq!(error.code, OpStatusCode::AmountOverflow);
+	let mixer_note = JsNote::deserialize(MIXER_NOTE_V1_X5_5).unwrap();
+	let error = PartialWithdrawal::new(&mixer_note, 1, 0, 0).err().unwrap();
+	assert_eq!(error.code, OpStatusCode::InvalidNoteProtocol);
+}
+
+#[wasm_bindgen_test]
+fn should_encode_evm_recipient_and_relayer() {
+	let MixerTestSetup {
+		mut proof_input_builder,
+		..
+	} = generate_mixer_test_setup(DECODED_SUBSTRATE_ADDRESS, DECODED_SUBSTRATE_ADDRESS, MIXER_NOTE_V1_X5_5);
+	let recipient = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+	let relayer = "fb6916095ca1df60bb79ce92ce3ea74c37c5d359";
+	assert!(proof_input_builder
+		.clone()
+		.set_recipient_evm(JsString::from(recipient.replace("aAeb", "AAeb")))
+		.is_err());
+	proof_input_builder = proof_input_builder
+		.set_recipient_evm(JsString::from(recipient))
+		.unwrap();
+	// A 20 bytes relayer is encoded as an EVM address once the target chain is an
+	// EVM chain
+	proof_input_builder = proof_input_builder.set_relayer(JsString::from(relayer)).unwrap();
+	proof_input_builder.inner.chain_type(ChainType::Evm).unwrap();
+
+	let proof_input = proof_input_builder.build().unwrap();
+	let mixer_input = proof_input.mixer_input().unwrap();
+	assert_eq!(
+		hex::encode(mixer_input.recipient),
+		format!("{}{}", "00".repeat(12), &recipient[2..].to_lowercase())
+	);
+	assert_eq!(
+		hex::encode(mixer_input.relayer),
+		format!("{}{}", "00".repeat(12), relayer)
+	);
+}
+
+#[wasm_bindgen_test]
+fn should_build_mixer_proof_input_from_note() {
+	let note = JsNote::deserialize(MIXER_NOTE_V1_X5_5).unwrap();
+	let mut proof_input_builder = JsProofInputBuilder::from_note(&note).unwrap();
+	// Same as mirroring the note by hand
+	let mut mirrored = JsProofInputBuilder::new(JsValue::from("mixer").into()).unwrap();
+	mirrored = mirrored.set_metadata_from_note(&note).unwrap();
+	assert_eq!(format!("{:?}", proof_input_builder), format!("{:?}", mirrored));
+	let codes: Vec<OpStatusCode> = proof_input_builder
+		.inner
+		.validate()
+		.into_iter()
+		.map(|e| e.code)
+		.collect();
+	assert_eq!(codes, vec![
+		OpStatusCode::InvalidProvingKey,
+		OpStatusCode::InvalidRecipient,
+		OpStatusCode::InvalidRelayer,
+		OpStatusCode::InvalidLeafIndex,
+		OpStatusCode::InvalidLeaves,
+		OpStatusCode::InvalidFee,
+		OpStatusCode::InvalidRefund,
+	]);
+
+	let leaves: Array = vec![note.get_leaf_commitment().unwrap()].into_iter().collect();
+	proof_input_builder = proof_input_builder
+		.set_leaves(Leaves::from(JsValue::from(leaves)))
+		.unwrap();
+	proof_input_builder = proof_input_builder.set_leaf_index(JsString::from("0")).unwrap();
+	proof_input_builder = proof_input_builder
+		.set_recipient(JsString::from(DECODED_SUBSTRATE_ADDRESS))
+		.unwrap();
+	proof_input_builder = proof_input_builder
+		.set_relayer(JsString::from(DECODED_SUBSTRATE_ADDRESS))
+		.unwrap();
+	proof_input_builder = proof_input_builder.set_fee(JsString::from("5"), None).unwrap();
+	proof_input_builder = proof_input_builder.set_refund(JsString::from("1"), None).unwrap();
+	proof_input_builder = proof_input_builder.set_pk(JsString::from("0000")).unwrap();
+	assert!(proof_input_builder.inner.validate().is_empty());
+	let mixer_input = proof_input_builder.build().unwrap().mixer_input().unwrap();
+	assert_eq!(mixer_input.width, 3);
+	assert_eq!(mixer_input.backend, note.backend.unwrap());
+
+	let vanchor_note = JsNote::deserialize(VANCHOR_NOTE_V1_X5_4).unwrap();
+	let error = JsProofInputBuilder::from_note(&vanchor_note).unwrap_err();
+	assert_eq!(error.code, OpStatusCode::InvalidNoteProtocol);
+	let wide_note = JsNote::deserialize(&MIXER_NOTE_V1_X5_5.replace("width=3", "width=4")).unwrap();
+	assert!(JsProofInputBuilder::from_note(&wide_note).is_err());
+}
+
+#[wasm_bindgen_test]
+fn should_discover_the_leaf_index() {
+	let note = JsNote::deserialize(MIXER_NOTE_V1_X5_5).unwrap();
+	let leaf = note.get_leaf_commitment().unwrap().to_vec();
+	let other = truncate_and_pad(&[7u8; 32]);
+	let builder_with_leaves = |leaves: Vec<&[u8]>| {
+		let mut proof_input_builder = JsProofInputBuilder::from_note(&note).unwrap();
+		let leaves: Array = leaves.into_iter().map(Uint8Array::from).collect();
+		proof_input_builder = proof_input_builder
+			.set_leaves(Leaves::from(JsValue::from(leaves)))
+			.unwrap();
+		proof_input_builder = proof_input_builder
+			.set_recipient(JsString::from(DECODED_SUBSTRATE_ADDRESS))
+			.unwrap();
+		proof_input_builder = proof_input_builder
+			.set_relayer(JsString::from(DECODED_SUBSTRATE_ADDRESS))
+			.unwrap();
+		proof_input_builder = proof_input_builder.set_fee(JsString::from("0"), None).unwrap();
+		proof_input_builder = proof_input_builder.set_refund(JsString::from("0"), None).unwrap();
+		proof_input_builder = proof_input_builder.set_pk(JsString::from("0000")).unwrap();
+		proof_input_builder = proof_input_builder.discover_leaf_index().unwrap();
+		proof_input_builder
+	};
+
+	let proof_input_builder = builder_with_leaves(vec![&other, &leaf, &leaf]);
+	assert!(proof_input_builder.inner.validate().is_empty());
+	let mixer_input = proof_input_builder.build().unwrap().mixer_input().unwrap();
+	assert_eq!(mixer_input.leaf_index, 1);
+
+	// An index that is set wins
+	let mut proof_input_builder = builder_with_leaves(vec![&other, &leaf]);
+	proof_input_builder = proof_input_builder.set_leaf_index(JsString::from("0")).unwrap();
+	assert_eq!(
+		proof_input_builder.build().unwrap().mixer_input().unwrap().leaf_index,
+		0
+	);
+
+	let proof_input_builder = builder_with_leaves(vec![&other]);
+	let codes: Vec<OpStatusCode> = proof_input_builder
+		.inner
+		.validate()
+		.into_iter()
+		.map(|e| e.code)
+		.collect();
+	assert_eq!(codes, vec![OpStatusCode::NotFoundInLeaves]);
+	assert_eq!(
+		proof_input_builder.build().unwrap_err().code,
+		OpStatusCode::NotFoundInLeaves
+	);
+}
+
+#[wasm_bindgen_test]
+fn should_reject_identifying_data_of_another_chain_type() {
+	let substrate = TypedChainId::new(ChainType::Substrate, 2).to_string();
+	let evm = TypedChainId::new(ChainType::Evm, 5).to_string();
+	let address = "0x38e7aa90c77f86747fab355eecaa0c2e4c3a463d";
+	let note = |target_chain_id: &str, target_identifying_data: &str| {
+		let mut note = JsNote::deserialize(MIXER_NOTE_V1_X5_5).unwrap();
+		note.target_chain_id = target_chain_id.to_string();
+		note.target_identifying_data = target_identifying_data.to_string();
+		note
+	};
+
+	assert!(JsProofInputBuilder::from_note(&note(&substrate, "2")).is_ok());
+	assert!(JsProofInputBuilder::from_note(&note(&evm, address)).is_ok());
+	for (chain_id, data) in [(&substrate, address), (&evm, "2"), (&substrate, "tree")] {
+		let error = JsProofInputBuilder::from_note(&note(chain_id, data)).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::InvalidIdentifyingData, "{}", data);
+	}
+}
+
+#[wasm_bindgen_test]
+fn should_reject_mimc_note_proof_input() {
+	let note = JsNote::deserialize(&MIXER_NOTE_V1_X5_5.replace("hf=Poseidon", "hf=MiMC220")).unwrap();
+	let protocol = JsValue::from("mixer").into();
+	let proof_input_builder = JsProofInputBuilder::new(protocol).unwrap();
+	assert!(proof_input_builder.set_metadata_from_note(&note).is_err());
+}
+
+#[wasm_bindgen_test]
+fn should_fail_to_prove_with_circom_backend() {
+	let MixerTestSetup {
+		mut proof_input_builder,
+		..
+	} = generate_mixer_test_setup(DECODED_SUBSTRATE_ADDRESS, DECODED_SUBSTRATE_ADDRESS, MIXER_NOTE_V1_X5_5);
+	proof_input_builder.inner.backend(Backend::Circom).unwrap();
+
+	let errors = proof_input_builder.inner.validate();
+	assert_eq!(errors.len(), 1);
+	assert_eq!(errors[0].code, OpStatusCode::UnsupportedBackend);
+
+	let proof_input = proof_input_builder.build().unwrap();
+	let error = mixer::create_proof(proof_input.mixer_input().unwrap(), &mut OsRng).unwrap_err();
+	assert_eq!(error.code, OpStatusCode::UnsupportedBackend);
+}
+
+#[wasm_bindgen_test]
+fn should_export_a_redacted_debug_bundle() {
+	let MixerTestSetup {
+		mut proof_input_builder,
+		leaf_bytes,
+		..
+	} = generate_mixer_test_setup(DECODED_SUBSTRATE_ADDRESS, DECODED_SUBSTRATE_ADDRESS, MIXER_NOTE_V1_X5_5);
+	proof_input_builder.inner.backend(Backend::Circom).unwrap();
+	let proof_input = proof_input_builder.build_js().unwrap();
+	let secret = hex::encode(proof_input.inner.mixer_input().unwrap().secret);
+	assert!(generate_proof_js(proof_input).is_err());
+
+	let bytes = export_debug_bundle().unwrap().unwrap().to_vec();
+	let json = String::from_utf8(bytes).unwrap();
+	assert!(!json.contains('\n'));
+	assert!(!json.contains(&secret));
+	let bundle: DebugBundle = serde_json::from_str(&json).unwrap();
+	assert_eq!(bundle.format, DEBUG_BUNDLE_FORMAT);
+	assert_eq!(bundle.protocol, "mixer");
+	assert_eq!(bundle.parameters["backend"], "Circom");
+	assert_eq!(bundle.inputs["secret"], "<redacted>");
+	assert_eq!(bundle.inputs["leaves"][0], hex::encode(leaf_bytes));
+	assert_eq!(bundle.inputs["fee"], "5");
+	assert_eq!(bundle.pk_hash.len(), 128);
+	assert!(bundle.prove_ms.is_some());
+	assert!(bundle.error.unwrap().starts_with("UnsupportedBackend"));
+	assert_eq!(bundle.public_inputs, None);
+}
+
+#[wasm_bindgen_test]
+fn should_reject_proof_input_without_circuit() {
+	let MixerTestSetup {
+		mut proof_input_builder,
+		..
+	} = generate_mixer_test_setup(DECODED_SUBSTRATE_ADDRESS, DECODED_SUBSTRATE_ADDRESS, MIXER_NOTE_V1_X5_5);
+	proof_input_builder.inner.width(4).unwrap();
+
+	let errors = proof_input_builder.inner.validate();
+	assert_eq!(errors.len(), 1);
+	assert_eq!(errors[0].code, OpStatusCode::UnsupportedParameterCombination);
+	let error = proof_input_builder.build().unwrap_err();
+	assert_eq!(error.code, OpStatusCode::UnsupportedParameterCombination);
+}
+
+#[wasm_bindgen_test]
+fn generate_mixer_proof_with_tree_depth_20() {
+	let MixerTestSetup {
+		mut proof_input_builder,
+		..
+	} = generate_mixer_test_setup(DECODED_SUBSTRATE_ADDRESS, DECODED_SUBSTRATE_ADDRESS, MIXER_NOTE_V1_X5_5);
+	let keys = setup_keys_with_tree_depth(20, JsValue::from("mixer").into(), None, None, None, None).unwrap();
+	proof_input_builder = proof_input_builder
+		.set_pk(JsString::from(hex::encode(&keys.pk)))
+		.unwrap();
+	assert!(proof_input_builder.clone().set_tree_depth(24).is_err());
+	proof_input_builder = proof_input_builder.set_tree_depth(20).unwrap();
+
+	let proof_input = proof_input_builder.build_js().unwrap();
+	let proof = generate_proof_js(proof_input).unwrap().mixer_proof().unwrap();
+
+	let is_valid_proof = verify_unchecked_raw::<Bn254>(&proof.public_inputs, &keys.vk, &proof.proof).unwrap();
+	assert!(is_valid_proof);
+}
+
+#[wasm_bindgen_test]
+fn should_reject_invalid_setup_keys_input() {
+	let code = |error: JsValue| Reflect::get(&error, &JsValue::from("code")).unwrap().as_string();
+	let mixer = || JsValue::from("mixer").into();
+	let error = setup_keys_with_tree_depth(20, JsValue::from("mixr").into(), None, None, None, None).err();
+	assert_eq!(code(error.unwrap()), Some(OpStatusCode::InvalidNoteProtocol.name()));
+	let curve = Some(JsValue::from("Bn256").into());
+	let error = setup_keys_with_tree_depth(20, mixer(), curve, None, None, None).err();
+	assert_eq!(code(error.unwrap()), Some(OpStatusCode::InvalidCurve.name()));
+	let error = setup_keys_with_tree_depth(20, JsValue::from("vanchor").into(), None, Some(3), None, None).err();
+	assert_eq!(
+		code(error.unwrap()),
+		Some(OpStatusCode::UnsupportedParameterCombination.name())
+	);
+	let error = setup_keys_with_tree_depth(24, mixer(), None, None, None, None).err();
+	assert!(error.is_some());
+}
+
 #[wasm_bindgen_test]
 fn generate_mixer_proof() {
 	let MixerTestSetup {
@@ -64,6 +651,394 @@ fn generate_mixer_proof() {
 
 	let is_valid_proof = verify_unchecked_raw::<Bn254>(&proof.public_inputs, &vk, &proof.proof).unwrap();
 	assert!(is_valid_proof);
+	let bundle = last_bundle().unwrap();
+	let public_inputs: Vec<String> = proof.public_inputs.iter().map(hex::encode).collect();
+	assert_eq!(bundle.public_inputs, Some(public_inputs));
+	assert_eq!(bundle.error, None);
+}
+
+#[wasm_bindgen_test]
+fn should_report_proof_telemetry() {
+	let MixerTestSetup {
+		proof_input_builder, ..
+	} = generate_mixer_test_setup(DECODED_SUBSTRATE_ADDRESS, DECODED_SUBSTRATE_ADDRESS, MIXER_NOTE_V1_X5_5);
+	let proof_input = proof_input_builder.build().unwrap();
+	let witness = create_witness(proof_input.clone()).unwrap();
+	assert!(witness.num_constraints > 0);
+
+	let hook = js_sys::Function::new_with_args("telemetry", "globalThis.proofTelemetry = telemetry");
+	set_proof_telemetry_hook(Some(JsValue::from(hook).unchecked_into()));
+	let output = generate_proof_js(JsProofInput {
+		inner: proof_input.clone(),
+	});
+	set_proof_telemetry_hook(None);
+	assert!(output.is_ok());
+
+	let telemetry: ProofTelemetry = Reflect::get(&js_sys::global(), &JsValue::from_str("proofTelemetry"))
+		.unwrap()
+		.into_serde()
+		.unwrap();
+	assert_eq!(telemetry.protocol, "mixer");
+	assert_eq!(telemetry.tree_depth, TREE_DEPTH);
+	assert_eq!(telemetry.constraints, Some(witness.num_constraints));
+	assert_eq!(telemetry.variables, Some(witness.assignment.len()));
+	assert!(telemetry.prove_ms > 0.0 && telemetry.total_ms >= telemetry.prove_ms + telemetry.synthesis_ms);
+	assert!(telemetry.peak_heap_bytes >= telemetry.heap_bytes_before);
+	assert_eq!(telemetry.error, None);
+
+	// Nothing is reported once the hook is removed
+	Reflect::delete_property(&js_sys::global(), &JsValue::from_str("proofTelemetry")).unwrap();
+	generate_proof_js(JsProofInput { inner: proof_input }).unwrap();
+	assert!(!Reflect::has(&js_sys::global(), &JsValue::from_str("proofTelemetry")).unwrap());
+}
+
+#[wasm_bindgen_test]
+fn should_record_the_roots_of_a_proof() {
+	let VAnchorTestSetup {
+		proof_input_builder, ..
+	} = generate_vanchor_test_setup_2_inputs();
+	let proof_input = proof_input_builder.build().unwrap();
+	let roots = proof_input.vanchor_input().unwrap().roots;
+	let before = crate::utils::monotonic_now();
+	let output = generate_proof_js(JsProofInput { inner: proof_input }).unwrap();
+	assert!(output.generated_at >= before);
+	assert_eq!(output.inner.roots(), roots);
+
+	assert!(!output.is_likely_stale(&roots));
+	// The local root left the history window
+	let mut current_roots: Vec<Vec<u8>> = roots.iter().filter(|root| **root != roots[0]).cloned().collect();
+	assert!(output.is_likely_stale(&current_roots));
+	current_roots.push(roots[0].clone());
+	current_roots.push(vec![7u8; 32]);
+	assert!(!output.is_likely_stale(&current_roots));
+}
+
+#[wasm_bindgen_test]
+fn should_generate_the_witness_of_a_mixer_proof() {
+	let MixerTestSetup {
+		proof_input_builder, ..
+	} = generate_mixer_test_setup(DECODED_SUBSTRATE_ADDRESS, DECODED_SUBSTRATE_ADDRESS, MIXER_NOTE_V1_X5_5);
+	let proof_input = proof_input_builder.build().unwrap();
+	let witness = create_witness(proof_input.clone()).unwrap();
+	let proof = generate_proof_js(JsProofInput { inner: proof_input })
+		.unwrap()
+		.mixer_proof()
+		.unwrap();
+	assert_eq!(witness.public_inputs(), proof.public_inputs);
+	assert_eq!(witness.assignment[0], [vec![1], vec![0; 31]].concat());
+
+	let wtns = witness.to_wtns();
+	assert_eq!(&wtns[..4], b"wtns");
+	assert_eq!(wtns[4..12], [2, 0, 0, 0, 2, 0, 0, 0]);
+	// Header section: 32 bytes field elements of the Bn254 scalar field
+	assert_eq!(wtns[12..24], [1, 0, 0, 0, 40, 0, 0, 0, 0, 0, 0, 0]);
+	assert_eq!(wtns[24..28], [32, 0, 0, 0]);
+	assert_eq!(wtns[60..64], (witness.length() as u32).to_le_bytes());
+	assert_eq!(wtns.len(), 64 + 12 + 32 * witness.length());
+}
+
+#[wasm_bindgen_test]
+fn should_report_the_cause_of_a_mixer_proof_failure() {
+	let MixerTestSetup {
+		proof_input_builder, ..
+	} = generate_mixer_test_setup(DECODED_SUBSTRATE_ADDRESS, DECODED_SUBSTRATE_ADDRESS, MIXER_NOTE_V1_X5_5);
+	let mixer_input = proof_input_builder.build().unwrap().mixer_input().unwrap();
+	let prove = |input: MixerProofPayload| {
+		generate_proof_js(JsProofInput {
+			inner: ProofInput::Mixer(Box::new(input)),
+		})
+	};
+	let error_of = |input: MixerProofPayload| {
+		let error = create_witness(ProofInput::Mixer(Box::new(input))).unwrap_err();
+		(error.code, error.data)
+	};
+
+	let mut input = mixer_input.clone();
+	input.leaf_index = 1;
+	assert_eq!(
+		error_of(input),
+		(
+			OpStatusCode::LeafNotInTree,
+			Some(r#"{"leafIndex":1,"leafCount":1}"#.to_string())
+		)
+	);
+	let mut input = mixer_input.clone();
+	input.leaves = vec![DEFAULT_LEAF.to_vec()];
+	assert_eq!(error_of(input).0, OpStatusCode::LeafNotInTree);
+
+	let mut input = mixer_input;
+	input.pk = vec![0, 0];
+	let error = prove(input).err().unwrap();
+	let property = |key: &str| Reflect::get(&error, &JsValue::from_str(key)).unwrap().as_string();
+	assert_eq!(property("code").as_deref(), Some("InvalidProvingKey"));
+	let data = Reflect::get(&error, &JsValue::from_str("data")).unwrap();
+	assert_eq!(Reflect::get(&data, &JsValue::from_str("pkBytes")).unwrap(), 2);
+}
+
+#[wasm_bindgen_test]
+fn should_delegate_a_mixer_proof() {
+	let MixerTestSetup {
+		proof_input_builder,
+		vk,
+		..
+	} = generate_mixer_test_setup(DECODED_SUBSTRATE_ADDRESS, DECODED_SUBSTRATE_ADDRESS, MIXER_NOTE_V1_X5_5);
+	let proof_input = proof_input_builder.build().unwrap();
+	let prover_secret = SecretKey::generate(&mut OsRng);
+	let mut delegated_prover = DelegatedProver::new(
+		vk,
+		*prover_secret.public_key().as_bytes(),
+		SecretKey::generate(&mut OsRng),
+	);
+	let request = delegated_prover.request(proof_input.clone(), &mut OsRng).unwrap();
+
+	// The prover opens the request with its key and the device's
+	let (nonce, rest) = request.split_at(24);
+	let device_key: [u8; 32] = rest[..32].try_into().unwrap();
+	let plaintext = SalsaBox::new(&PublicKey::from(device_key), &prover_secret)
+		.decrypt(nonce.into(), &rest[32..])
+		.unwrap();
+	let request: DelegatedRequest = serde_json::from_slice(&plaintext).unwrap();
+	assert_eq!(request.protocol, "mixer");
+	assert!(hex::decode(&request.witness).unwrap().starts_with(b"wtns"));
+	let proof = generate_proof_js(JsProofInput { inner: proof_input })
+		.unwrap()
+		.mixer_proof()
+		.unwrap();
+	let public_inputs: Vec<String> = proof.public_inputs.iter().map(hex::encode).collect();
+	assert_eq!(request.public_inputs, public_inputs);
+
+	let response = |id: &str, proof: &[u8]| DelegatedResponse {
+		id: id.to_string(),
+		proof: hex::encode(proof),
+	};
+	let error = delegated_prover.accept(&response("00", &proof.proof)).unwrap_err();
+	assert_eq!(error.code, OpStatusCode::InvalidDelegatedProof);
+	// A well formed proof with its A and C points swapped
+	let mut forged = proof.proof.clone();
+	assert_eq!(forged.len(), 128);
+	let (a, rest) = forged.split_at_mut(32);
+	a.swap_with_slice(&mut rest[64..]);
+	let error = delegated_prover.accept(&response(&request.id, &forged)).unwrap_err();
+	assert_eq!(error.code, OpStatusCode::ProofVerificationFailed);
+	assert_eq!(error.data, Some(format!(r#"{{"id":"{}"}}"#, request.id)));
+
+	let output = delegated_prover.accept(&response(&request.id, &proof.proof)).unwrap();
+	let mixer_proof = match output {
+		ProofOutput::Mixer(mixer_proof) => mixer_proof,
+		_ => panic!("expected a mixer proof"),
+	};
+	assert_eq!(mixer_proof.nullifier_hash, proof.nullifier_hash);
+	assert_eq!(mixer_proof.root, proof.root);
+	assert_eq!(mixer_proof.leaf, proof.leaf);
+	// Accepted once
+	let error = delegated_prover
+		.accept(&response(&request.id, &proof.proof))
+		.unwrap_err();
+	assert_eq!(error.code, OpStatusCode::InvalidDelegatedProof);
+}
+
+#[wasm_bindgen_test]
+fn should_withdraw_several_mixer_notes() {
+	let first = JsNote::deserialize(MIXER_NOTE_V1_X5_5).unwrap();
+	let mut second = RefreshCommitment::generate(&first, None).unwrap().note;
+	second.amount = Some("30".to_string());
+	let leaves: Vec<Vec<u8>> = [&second, &first]
+		.iter()
+		.map(|note| note.get_leaf_commitment().unwrap().to_vec())
+		.collect();
+	let keys = setup_keys_with_tree_depth(20, JsValue::from("mixer").into(), None, None, None, None).unwrap();
+	let account = hex::decode(DECODED_SUBSTRATE_ADDRESS).unwrap();
+
+	let withdrawal =
+		AggregateWithdrawal::generate(&[first, second], &leaves, &account, &account, 8, 1, &keys.pk, Some(20)).unwrap();
+	let fees: Vec<(u64, u128, u128)> = withdrawal
+		.withdrawals
+		.iter()
+		.map(|w| (w.leaf_index, w.fee, w.refund))
+		.collect();
+	assert_eq!(fees, [(0, 6, 1), (1, 2, 0)]);
+	assert_eq!(withdrawal.summary.payout, "32");
+	for w in &withdrawal.withdrawals {
+		assert!(verify_unchecked_raw::<Bn254>(&w.proof.public_inputs, &keys.vk, &w.proof.proof).unwrap());
+	}
+}
+
+#[wasm_bindgen_test]
+fn generate_deterministic_mixer_proof() {
+	let MixerTestSetup {
+		mut proof_input_builder,
+		vk,
+		..
+	} = generate_mixer_test_setup(DECODED_SUBSTRATE_ADDRESS, DECODED_SUBSTRATE_ADDRESS, MIXER_NOTE_V1_X5_5);
+	assert!(proof_input_builder
+		.clone()
+		.set_rng_seed(Uint8Array::from(&[1u8; 31][..]))
+		.is_err());
+	proof_input_builder = proof_input_builder
+		.set_rng_seed(Uint8Array::from(&[1u8; 32][..]))
+		.unwrap();
+
+	let proof_input = proof_input_builder.build().unwrap();
+	let prove = |inner: ProofInput| {
+		generate_proof_js(JsProofInput { inner })
+			.unwrap()
+			.mixer_proof()
+			.unwrap()
+	};
+	let proof = prove(proof_input.clone());
+	assert_eq!(proof.proof, prove(proof_input).proof);
+
+	let is_valid_proof = verify_unchecked_raw::<Bn254>(&proof.public_inputs, &vk, &proof.proof).unwrap();
+	assert!(is_valid_proof);
+}
+
+#[wasm_bindgen_test]
+fn should_prove_serialized_proof_input() {
+	let MixerTestSetup {
+		mut proof_input_builder,
+		vk,
+		..
+	} = generate_mixer_test_setup(DECODED_SUBSTRATE_ADDRESS, DECODED_SUBSTRATE_ADDRESS, MIXER_NOTE_V1_X5_5);
+	proof_input_builder = proof_input_builder
+		.set_rng_seed(Uint8Array::from(&[2u8; 32][..]))
+		.unwrap();
+	let proof_input = proof_input_builder.build().unwrap();
+
+	// The prover only sees the encoded inputs
+	let encoded = proof_input.serialize();
+	let decoded = ProofInput::deserialize(&encoded).unwrap();
+	assert_eq!(decoded.serialize(), encoded);
+	let prove = |inner: ProofInput| {
+		generate_proof_js(JsProofInput { inner })
+			.unwrap()
+			.mixer_proof()
+			.unwrap()
+	};
+	let proof = prove(decoded);
+	assert_eq!(proof.proof, prove(proof_input).proof);
+	assert!(verify_unchecked_raw::<Bn254>(&proof.public_inputs, &vk, &proof.proof).unwrap());
+
+	let VAnchorTestSetup {
+		proof_input_builder, ..
+	} = generate_vanchor_test_setup_2_inputs();
+	let vanchor_input = proof_input_builder.build().unwrap().vanchor_input().unwrap();
+	let encoded = ProofInput::VAnchor(Box::new(vanchor_input.clone())).serialize();
+	let decoded = ProofInput::deserialize(&encoded).unwrap().vanchor_input().unwrap();
+	assert_eq!(
+		decoded.input_utxos[1].get_nullifier(),
+		vanchor_input.input_utxos[1].get_nullifier()
+	);
+	assert_eq!(
+		decoded.output_utxos[0].get_commitment(),
+		vanchor_input.output_utxos[0].get_commitment()
+	);
+	assert_eq!(decoded.leaves, vanchor_input.leaves);
+
+	let error = ProofInput::deserialize(&encoded[..encoded.len() - 1]).err().unwrap();
+	assert_eq!(error.code, OpStatusCode::DeserializationFailed);
+}
+
+#[wasm_bindgen_test]
+fn should_export_verifying_key() {
+	let MixerTestSetup {
+		proof_input_builder,
+		vk,
+		..
+	} = generate_mixer_test_setup(DECODED_SUBSTRATE_ADDRESS, DECODED_SUBSTRATE_ADDRESS, MIXER_NOTE_V1_X5_5);
+	let proof = generate_proof_js(proof_input_builder.build_js().unwrap())
+		.unwrap()
+		.mixer_proof()
+		.unwrap();
+
+	let verifying_key = JsVerifyingKey::new(crate::types::Curve::Bn254, &vk).unwrap();
+	assert_eq!(verifying_key.public_input_count(), proof.public_inputs.len());
+
+	// The Substrate encoding is checked and compressed, and still verifies
+	let substrate = verifying_key.to_substrate();
+	assert!(substrate.len() < vk.len());
+	let public_inputs: Vec<_> = proof
+		.public_inputs
+		.iter()
+		.map(|input| ark_bn254::Fr::from_be_bytes_mod_order(input))
+		.collect();
+	assert!(verify::<Bn254>(&public_inputs, &substrate, &proof.proof).unwrap());
+	let reparsed = JsVerifyingKey::new(crate::types::Curve::Bn254, &substrate).unwrap();
+	assert_eq!(reparsed.fingerprint(), verifying_key.fingerprint());
+
+	let words = verifying_key.to_solidity().unwrap();
+	assert_eq!(words.len(), 2 + 3 * 4 + 2 * (proof.public_inputs.len() + 1));
+
+	let error = JsVerifyingKey::new(crate::types::Curve::Bn254, &vk[1..]).err().unwrap();
+	assert_eq!(error.code, OpStatusCode::InvalidVerifyingKey);
+
+	// The points of the uncompressed encoding are checked too
+	let mut off_curve = vk.clone();
+	off_curve[0] ^= 1;
+	let error = JsVerifyingKey::new(crate::types::Curve::Bn254, &off_curve)
+		.err()
+		.unwrap();
+	assert_eq!(error.code, OpStatusCode::InvalidVerifyingKey);
+
+	let mut no_inputs = VerifyingKey::<Bn254>::deserialize_uncompressed(&vk[..]).unwrap();
+	no_inputs.gamma_abc_g1.clear();
+	let mut bytes = Vec::new();
+	no_inputs.serialize_uncompressed(&mut bytes).unwrap();
+	let error = JsVerifyingKey::new(crate::types::Curve::Bn254, &bytes).err().unwrap();
+	assert_eq!(error.code, OpStatusCode::InvalidVerifyingKey);
+}
+
+#[wasm_bindgen_test]
+fn should_convert_proof_encodings() {
+	let MixerTestSetup {
+		proof_input_builder,
+		vk,
+		..
+	} = generate_mixer_test_setup(DECODED_SUBSTRATE_ADDRESS, DECODED_SUBSTRATE_ADDRESS, MIXER_NOTE_V1_X5_5);
+	let proof = generate_proof_js(proof_input_builder.build_js().unwrap())
+		.unwrap()
+		.mixer_proof()
+		.unwrap();
+
+	let groth16_proof = JsGroth16Proof::from_compressed(crate::types::Curve::Bn254, &proof.proof).unwrap();
+	assert_eq!(groth16_proof.to_compressed(), proof.proof);
+
+	let uncompressed = groth16_proof.to_uncompressed();
+	assert_eq!(uncompressed.len(), 2 * proof.proof.len());
+	let from_uncompressed = JsGroth16Proof::from_uncompressed(crate::types::Curve::Bn254, &uncompressed).unwrap();
+	assert_eq!(from_uncompressed, groth16_proof);
+
+	let evm = groth16_proof.to_evm().unwrap();
+	assert_eq!(evm.len(), EVM_PROOF_WORDS);
+	let from_evm = JsGroth16Proof::from_evm_bytes(&evm.concat()).unwrap();
+	assert_eq!(from_evm.to_compressed(), proof.proof);
+	assert!(verify_unchecked_raw::<Bn254>(&proof.public_inputs, &vk, &from_evm.to_compressed()).unwrap());
+
+	// Swapping the real and imaginary parts of `b` leaves the subgroup
+	let mut swapped = evm.clone();
+	swapped.swap(2, 3);
+	let error = JsGroth16Proof::from_evm_bytes(&swapped.concat()).err().unwrap();
+	assert_eq!(error.code, OpStatusCode::InvalidProof);
+	let error = JsGroth16Proof::from_evm_bytes(&evm.concat()[1..]).err().unwrap();
+	assert_eq!(error.code, OpStatusCode::InvalidArrayLength);
+}
+
+#[wasm_bindgen_test]
+fn generate_mixer_proof_bls381() {
+	let MixerTestSetup {
+		proof_input_builder,
+		vk,
+		..
+	} = generate_mixer_test_setup(
+		DECODED_SUBSTRATE_ADDRESS,
+		DECODED_SUBSTRATE_ADDRESS,
+		MIXER_NOTE_V1_BLS381_X5_3,
+	);
+
+	let proof_input = proof_input_builder.build_js().unwrap();
+	let proof = generate_proof_js(proof_input).unwrap().mixer_proof().unwrap();
+
+	let is_valid_proof = verify_unchecked_raw::<Bls12_381>(&proof.public_inputs, &vk, &proof.proof).unwrap();
+	assert!(is_valid_proof);
 }
 
 #[wasm_bindgen_test]
@@ -77,7 +1052,7 @@ fn should_generate_vanchor_proof_input() {
 
 	let mut leaves_map = LeavesMapInput::new();
 	leaves_map
-		.set_chain_leaves(0, Leaves::from(JsValue::from(leaves.clone())))
+		.set_chain_leaves(0, Leaves::from(JsValue::from(leaves)))
 		.unwrap();
 
 	let indices: Array = vec![JsValue::from("0"), JsValue::from("0")].into_iter().collect();
@@ -87,27 +1062,27 @@ fn should_generate_vanchor_proof_input() {
 	]
 	.into_iter()
 	.collect();
-	proof_input_builder.set_leaves_map(leaves_map).unwrap();
+	proof_input_builder = proof_input_builder.set_leaves_map(leaves_map).unwrap();
 
-	proof_input_builder
+	proof_input_builder = proof_input_builder
 		.set_indices(Indices::from(JsValue::from(indices)))
 		.unwrap();
-	proof_input_builder
+	proof_input_builder = proof_input_builder
 		.set_roots(Leaves::from(JsValue::from(roots)))
 		.unwrap();
 
-	proof_input_builder.set_pk(JsString::from("0000")).unwrap();
-	proof_input_builder.public_amount(JsString::from("10")).unwrap();
-	proof_input_builder.chain_id(JsString::from("0")).unwrap();
-	proof_input_builder.set_ext_data_hash(JsString::from("1111")).unwrap();
+	proof_input_builder = proof_input_builder.set_pk(JsString::from("0000")).unwrap();
+	proof_input_builder = proof_input_builder.public_amount(JsString::from("10"), None).unwrap();
+	proof_input_builder = proof_input_builder.chain_id(JsString::from("0")).unwrap();
+	proof_input_builder = proof_input_builder.set_ext_data_hash(JsString::from("1111")).unwrap();
 
-	let input_utxos: Array = vec![JsValue::from(vanchor_utxo.clone())].into_iter().collect();
+	let input_utxos: Array = vec![JsValue::from(vanchor_utxo)].into_iter().collect();
 
 	let output_1 = new_utxo_bn254_2_2(crate::types::Curve::Bn254, 10, 0);
 	let output_2 = new_utxo_bn254_2_2(crate::types::Curve::Bn254, 10, 3);
 
-	proof_input_builder.set_input_utxos(input_utxos).unwrap();
-	proof_input_builder.set_output_utxos(output_1, output_2).unwrap();
+	proof_input_builder = proof_input_builder.set_input_utxos(input_utxos).unwrap();
+	proof_input_builder = proof_input_builder.set_output_utxos(output_1, output_2).unwrap();
 	let proof_builder = proof_input_builder.build_js().unwrap();
 	let vanchor_proof_input_payload = proof_builder.inner.vanchor_input().unwrap();
 	assert_eq!(vanchor_proof_input_payload.public_amount, 10);
@@ -115,7 +1090,7 @@ fn should_generate_vanchor_proof_input() {
 	assert_eq!(vanchor_proof_input_payload.indices, [0u64, 0u64].to_vec());
 	assert_eq!(
 		hex::encode(&vanchor_proof_input_payload.roots[0]),
-		hex::encode([0u8; 32].to_vec())
+		hex::encode([0u8; 32])
 	);
 	assert_eq!(vanchor_proof_input_payload.roots.len(), 2);
 	assert_eq!(vanchor_proof_input_payload.backend.to_string(), "Arkworks");
@@ -137,7 +1112,7 @@ fn should_fail_to_generate_vanchor_proof_input_with_invalid_amounts() {
 
 	let mut leaves_map = LeavesMapInput::new();
 	leaves_map
-		.set_chain_leaves(0, Leaves::from(JsValue::from(leaves.clone())))
+		.set_chain_leaves(0, Leaves::from(JsValue::from(leaves)))
 		.unwrap();
 
 	let indices: Array = vec![JsValue::from("0"), JsValue::from("0")].into_iter().collect();
@@ -147,27 +1122,27 @@ fn should_fail_to_generate_vanchor_proof_input_with_invalid_amounts() {
 	]
 	.into_iter()
 	.collect();
-	proof_input_builder.set_leaves_map(leaves_map).unwrap();
+	proof_input_builder = proof_input_builder.set_leaves_map(leaves_map).unwrap();
 
-	proof_input_builder
+	proof_input_builder = proof_input_builder
 		.set_indices(Indices::from(JsValue::from(indices)))
 		.unwrap();
-	proof_input_builder
+	proof_input_builder = proof_input_builder
 		.set_roots(Leaves::from(JsValue::from(roots)))
 		.unwrap();
 
-	proof_input_builder.set_pk(JsString::from("0000")).unwrap();
-	proof_input_builder.public_amount(JsString::from("10")).unwrap();
-	proof_input_builder.chain_id(JsString::from("0")).unwrap();
-	proof_input_builder.set_ext_data_hash(JsString::from("1111")).unwrap();
+	proof_input_builder = proof_input_builder.set_pk(JsString::from("0000")).unwrap();
+	proof_input_builder = proof_input_builder.public_amount(JsString::from("10"), None).unwrap();
+	proof_input_builder = proof_input_builder.chain_id(JsString::from("0")).unwrap();
+	proof_input_builder = proof_input_builder.set_ext_data_hash(JsString::from("1111")).unwrap();
 
-	let input_utxos: Array = vec![JsValue::from(utxo.clone())].into_iter().collect();
+	let input_utxos: Array = vec![JsValue::from(utxo)].into_iter().collect();
 
 	let output_1 = new_utxo_bn254_2_2(crate::types::Curve::Bn254, 10, 0);
 	let output_2 = new_utxo_bn254_2_2(crate::types::Curve::Bn254, 10, 0);
 
-	proof_input_builder.set_input_utxos(input_utxos).unwrap();
-	proof_input_builder.set_output_utxos(output_1, output_2).unwrap();
+	proof_input_builder = proof_input_builder.set_input_utxos(input_utxos).unwrap();
+	proof_input_builder = proof_input_builder.set_output_utxos(output_1, output_2).unwrap();
 
 	let proof_builder = proof_input_builder.build();
 	let mut message = "".to_string();
@@ -191,7 +1166,7 @@ fn should_fail_to_generate_vanchor_proof_input_with_inconsistent_notes_chain_id(
 
 	let mut leaves_map = LeavesMapInput::new();
 	leaves_map
-		.set_chain_leaves(0, Leaves::from(JsValue::from(leaves.clone())))
+		.set_chain_leaves(0, Leaves::from(JsValue::from(leaves)))
 		.unwrap();
 
 	let indices: Array = vec![JsValue::from("0"), JsValue::from("1")].into_iter().collect();
@@ -201,30 +1176,28 @@ fn should_fail_to_generate_vanchor_proof_input_with_inconsistent_notes_chain_id(
 	]
 	.into_iter()
 	.collect();
-	proof_input_builder.set_leaves_map(leaves_map).unwrap();
+	proof_input_builder = proof_input_builder.set_leaves_map(leaves_map).unwrap();
 
-	proof_input_builder
+	proof_input_builder = proof_input_builder
 		.set_indices(Indices::from(JsValue::from(indices)))
 		.unwrap();
-	proof_input_builder
+	proof_input_builder = proof_input_builder
 		.set_roots(Leaves::from(JsValue::from(roots)))
 		.unwrap();
 
-	proof_input_builder.set_pk(JsString::from("0000")).unwrap();
-	proof_input_builder.public_amount(JsString::from("10")).unwrap();
-	proof_input_builder.chain_id(JsString::from("0")).unwrap();
-	proof_input_builder.set_ext_data_hash(JsString::from("1111")).unwrap();
+	proof_input_builder = proof_input_builder.set_pk(JsString::from("0000")).unwrap();
+	proof_input_builder = proof_input_builder.public_amount(JsString::from("10"), None).unwrap();
+	proof_input_builder = proof_input_builder.chain_id(JsString::from("0")).unwrap();
+	proof_input_builder = proof_input_builder.set_ext_data_hash(JsString::from("1111")).unwrap();
 
-	let input_utxos: Array = vec![JsValue::from(utxo1.clone()), JsValue::from(utxo2.clone())]
-		.into_iter()
-		.collect();
+	let input_utxos: Array = vec![JsValue::from(utxo1), JsValue::from(utxo2)].into_iter().collect();
 
 	let output_1 = new_utxo_bn254_2_2(crate::types::Curve::Bn254, 10, 0);
 	let output_2 = new_utxo_bn254_2_2(crate::types::Curve::Bn254, 10, 3);
 
-	proof_input_builder.set_output_utxos(output_1, output_2).unwrap();
+	proof_input_builder = proof_input_builder.set_output_utxos(output_1, output_2).unwrap();
 
-	proof_input_builder.set_input_utxos(input_utxos).unwrap();
+	proof_input_builder = proof_input_builder.set_input_utxos(input_utxos).unwrap();
 	let proof_builder = proof_input_builder.build();
 	let mut message = "".to_string();
 	if let Err(e) = proof_builder {
@@ -247,7 +1220,7 @@ fn should_fail_to_generate_vanchor_proof_input_with_inconsistent_notes_indices()
 
 	let mut leaves_map = LeavesMapInput::new();
 	leaves_map
-		.set_chain_leaves(0, Leaves::from(JsValue::from(leaves.clone())))
+		.set_chain_leaves(0, Leaves::from(JsValue::from(leaves)))
 		.unwrap();
 
 	let indices: Array = vec![JsValue::from("0"), JsValue::from("1")].into_iter().collect();
@@ -257,29 +1230,27 @@ fn should_fail_to_generate_vanchor_proof_input_with_inconsistent_notes_indices()
 	]
 	.into_iter()
 	.collect();
-	proof_input_builder.set_leaves_map(leaves_map).unwrap();
+	proof_input_builder = proof_input_builder.set_leaves_map(leaves_map).unwrap();
 
-	proof_input_builder
+	proof_input_builder = proof_input_builder
 		.set_indices(Indices::from(JsValue::from(indices)))
 		.unwrap();
-	proof_input_builder
+	proof_input_builder = proof_input_builder
 		.set_roots(Leaves::from(JsValue::from(roots)))
 		.unwrap();
 
-	proof_input_builder.set_pk(JsString::from("0000")).unwrap();
-	proof_input_builder.public_amount(JsString::from("10")).unwrap();
-	proof_input_builder.chain_id(JsString::from("0")).unwrap();
-	proof_input_builder.set_ext_data_hash(JsString::from("1111")).unwrap();
+	proof_input_builder = proof_input_builder.set_pk(JsString::from("0000")).unwrap();
+	proof_input_builder = proof_input_builder.public_amount(JsString::from("10"), None).unwrap();
+	proof_input_builder = proof_input_builder.chain_id(JsString::from("0")).unwrap();
+	proof_input_builder = proof_input_builder.set_ext_data_hash(JsString::from("1111")).unwrap();
 
-	let input_utxos: Array = vec![JsValue::from(utxo1.clone()), JsValue::from(utxo2.clone())]
-		.into_iter()
-		.collect();
+	let input_utxos: Array = vec![JsValue::from(utxo1), JsValue::from(utxo2)].into_iter().collect();
 
 	let output_1 = new_utxo_bn254_2_2(crate::types::Curve::Bn254, 20, 0);
 	let output_2 = new_utxo_bn254_2_2(crate::types::Curve::Bn254, 20, 3);
 
-	proof_input_builder.set_input_utxos(input_utxos).unwrap();
-	proof_input_builder.set_output_utxos(output_1, output_2).unwrap();
+	proof_input_builder = proof_input_builder.set_input_utxos(input_utxos).unwrap();
+	proof_input_builder = proof_input_builder.set_output_utxos(output_1, output_2).unwrap();
 
 	let proof_builder = proof_input_builder.build();
 	let mut message = "".to_string();
@@ -292,6 +1263,15 @@ fn should_fail_to_generate_vanchor_proof_input_with_inconsistent_notes_indices()
 	assert_eq!(message, expected_error_message)
 }
 
+#[wasm_bindgen_test]
+fn should_reject_empty_input_utxos() {
+	let protocol = JsValue::from("vanchor").into();
+	let proof_input_builder = JsProofInputBuilder::new(protocol).unwrap();
+	let error = proof_input_builder.set_input_utxos(Array::new()).err().unwrap();
+	let code = Reflect::get(&error, &JsValue::from("code")).unwrap();
+	assert_eq!(code.as_string(), Some(OpStatusCode::InvalidInputUtxoConfig.name()));
+}
+
 #[wasm_bindgen_test]
 fn should_fail_to_proof_with_1_input() {
 	let utxo = generate_vanchor_utxo(30, 0, Some(0));
@@ -303,7 +1283,7 @@ fn should_fail_to_proof_with_1_input() {
 
 	let mut leaves_map = LeavesMapInput::new();
 	leaves_map
-		.set_chain_leaves(0, Leaves::from(JsValue::from(leaves.clone())))
+		.set_chain_leaves(0, Leaves::from(JsValue::from(leaves)))
 		.unwrap();
 
 	let indices: Array = vec![JsValue::from("0"), JsValue::from("0")].into_iter().collect();
@@ -313,37 +1293,38 @@ fn should_fail_to_proof_with_1_input() {
 	]
 	.into_iter()
 	.collect();
-	proof_input_builder.set_leaves_map(leaves_map).unwrap();
+	proof_input_builder = proof_input_builder.set_leaves_map(leaves_map).unwrap();
 
-	proof_input_builder
+	proof_input_builder = proof_input_builder
 		.set_indices(Indices::from(JsValue::from(indices)))
 		.unwrap();
-	proof_input_builder
+	proof_input_builder = proof_input_builder
 		.set_roots(Leaves::from(JsValue::from(roots)))
 		.unwrap();
 
-	proof_input_builder.set_pk(JsString::from("0000")).unwrap();
-	proof_input_builder.public_amount(JsString::from("10")).unwrap();
-	proof_input_builder.chain_id(JsString::from("0")).unwrap();
-	proof_input_builder.set_ext_data_hash(JsString::from("1111")).unwrap();
+	proof_input_builder = proof_input_builder.set_pk(JsString::from("0000")).unwrap();
+	proof_input_builder = proof_input_builder.public_amount(JsString::from("10"), None).unwrap();
+	proof_input_builder = proof_input_builder.chain_id(JsString::from("0")).unwrap();
+	proof_input_builder = proof_input_builder.set_ext_data_hash(JsString::from("1111")).unwrap();
 
-	let input_utxos: Array = vec![JsValue::from(utxo.clone())].into_iter().collect();
+	let input_utxos: Array = vec![JsValue::from(utxo)].into_iter().collect();
 
 	let output_1 = new_utxo_bn254_2_2(crate::types::Curve::Bn254, 20, 0);
 	let output_2 = new_utxo_bn254_2_2(crate::types::Curve::Bn254, 20, 3);
 
-	proof_input_builder.set_input_utxos(input_utxos).unwrap();
-	proof_input_builder.set_output_utxos(output_1, output_2).unwrap();
+	proof_input_builder = proof_input_builder.set_input_utxos(input_utxos).unwrap();
+	proof_input_builder = proof_input_builder.set_output_utxos(output_1, output_2).unwrap();
 
 	let proof_input = proof_input_builder.build_js().unwrap();
 	let proof = generate_proof_js(proof_input);
 	let mut message = "".to_string();
 	if let Err(e) = proof {
-		message = e.as_string().unwrap();
+		let property = |key: &str| Reflect::get(&e, &JsValue::from_str(key)).unwrap().as_string();
+		assert_eq!(property("code").as_deref(), Some("InvalidProofParameters"));
+		message = property("message").unwrap();
 	}
 	let expected_error_message =
-		"Code 24, message proof::vanchor: Input set has 1 UTXOs while the supported set length should be one of [2, 16], data {}"
-			.to_string();
+		"proof::vanchor: Input set has 1 UTXOs while the supported set length should be one of [2, 16]".to_string();
 	assert_eq!(message, expected_error_message)
 }
 
@@ -361,7 +1342,7 @@ fn should_fail_to_proof_with_3_inputs() {
 
 	let mut leaves_map = LeavesMapInput::new();
 	leaves_map
-		.set_chain_leaves(0, Leaves::from(JsValue::from(leaves.clone())))
+		.set_chain_leaves(0, Leaves::from(JsValue::from(leaves)))
 		.unwrap();
 
 	let indices: Array = vec![JsValue::from("0"), JsValue::from("1")].into_iter().collect();
@@ -371,43 +1352,40 @@ fn should_fail_to_proof_with_3_inputs() {
 	]
 	.into_iter()
 	.collect();
-	proof_input_builder.set_leaves_map(leaves_map).unwrap();
+	proof_input_builder = proof_input_builder.set_leaves_map(leaves_map).unwrap();
 
-	proof_input_builder
+	proof_input_builder = proof_input_builder
 		.set_indices(Indices::from(JsValue::from(indices)))
 		.unwrap();
-	proof_input_builder
+	proof_input_builder = proof_input_builder
 		.set_roots(Leaves::from(JsValue::from(roots)))
 		.unwrap();
 
-	proof_input_builder.set_pk(JsString::from("0000")).unwrap();
-	proof_input_builder.public_amount(JsString::from("10")).unwrap();
-	proof_input_builder.chain_id(JsString::from("0")).unwrap();
-	proof_input_builder.set_ext_data_hash(JsString::from("1111")).unwrap();
+	proof_input_builder = proof_input_builder.set_pk(JsString::from("0000")).unwrap();
+	proof_input_builder = proof_input_builder.public_amount(JsString::from("10"), None).unwrap();
+	proof_input_builder = proof_input_builder.chain_id(JsString::from("0")).unwrap();
+	proof_input_builder = proof_input_builder.set_ext_data_hash(JsString::from("1111")).unwrap();
 
-	let input_utxos: Array = vec![
-		JsValue::from(utxo1.clone()),
-		JsValue::from(utxo2.clone()),
-		JsValue::from(utxo3.clone()),
-	]
-	.into_iter()
-	.collect();
+	let input_utxos: Array = vec![JsValue::from(utxo1), JsValue::from(utxo2), JsValue::from(utxo3)]
+		.into_iter()
+		.collect();
 
 	let output_1 = new_utxo_bn254_2_2(crate::types::Curve::Bn254, 20, 0);
 	let output_2 = new_utxo_bn254_2_2(crate::types::Curve::Bn254, 20, 3);
 
-	proof_input_builder.set_input_utxos(input_utxos).unwrap();
-	proof_input_builder.set_output_utxos(output_1, output_2).unwrap();
+	proof_input_builder = proof_input_builder.set_input_utxos(input_utxos).unwrap();
+	proof_input_builder = proof_input_builder.set_output_utxos(output_1, output_2).unwrap();
 
 	let proof_builder = proof_input_builder.build_js().unwrap();
 	let proof = generate_proof_js(proof_builder);
 	let mut message = "".to_string();
 	if let Err(e) = proof {
-		message = e.as_string().unwrap();
+		let property = |key: &str| Reflect::get(&e, &JsValue::from_str(key)).unwrap().as_string();
+		assert_eq!(property("code").as_deref(), Some("InvalidProofParameters"));
+		message = property("message").unwrap();
 	}
 	let expected_error_message =
-		"Code 24, message proof::vanchor: Input set has 3 UTXOs while the supported set length should be one of [2, 16], data {}"
-			.to_string();
+		"proof::vanchor: Input set has 3 UTXOs while the supported set length should be one of [2, 16]".to_string();
 	assert_eq!(message, expected_error_message)
 }
 #[wasm_bindgen_test]
@@ -426,6 +1404,103 @@ fn generate_vanchor_proof_2_inputs() {
 	assert!(is_valid_proof);
 }
 
+#[wasm_bindgen_test]
+fn should_generate_the_witness_of_a_vanchor_proof() {
+	let VAnchorTestSetup {
+		proof_input_builder, ..
+	} = generate_vanchor_test_setup_2_inputs();
+	let proof_input = proof_input_builder.build().unwrap();
+	let vanchor_input = proof_input.vanchor_input().unwrap();
+	let witness = create_witness(proof_input).unwrap();
+	let public_inputs = witness.public_inputs();
+	// [public_amount, ext_data_hash, nullifiers.., commitments.., chain_id,
+	// roots..]
+	assert_eq!(public_inputs.len(), 9);
+	assert_eq!(public_inputs[1], [1u8; 32]);
+	for (commitment, utxo) in public_inputs[4..6].iter().zip(&vanchor_input.output_utxos) {
+		assert_eq!(commitment, &utxo.get_commitment());
+	}
+	assert_eq!(public_inputs[7..], vanchor_input.roots[..]);
+
+	// The payload's indices are the ones proven, not the UTXOs' own
+	let mut moved = vanchor_input.clone();
+	moved.indices[0] += 1;
+	let error = create_witness(ProofInput::VAnchor(Box::new(moved))).unwrap_err();
+	assert_eq!(error.code, OpStatusCode::LeafNotInTree);
+	assert!(error.data.unwrap().starts_with(r#"{"inputIndex":0,"#));
+
+	let mut vanchor_input = vanchor_input;
+	// Neither root is the one of the inputs
+	vanchor_input.roots = vec![vec![0; 32]; 2];
+	let error = create_witness(ProofInput::VAnchor(Box::new(vanchor_input))).unwrap_err();
+	assert_eq!(error.code, OpStatusCode::RootMismatch);
+	assert!(error.data.unwrap().starts_with(r#"{"inputIndex":0,"chainId":"#));
+}
+
+#[wasm_bindgen_test]
+fn should_encode_vanchor_transact_calldata() {
+	let VAnchorTestSetup {
+		proof_input_builder, ..
+	} = generate_vanchor_test_setup_2_inputs();
+	let proof_input = proof_input_builder.build_js().unwrap();
+	let proof = generate_proof_js(proof_input).unwrap().vanchor_proof().unwrap();
+	let ext_data = ExtData {
+		recipient: vec![1u8; 20],
+		relayer: vec![2u8; 20],
+		token: vec![3u8; 20],
+		..Default::default()
+	};
+
+	let calldata = vanchor_transact(&proof, &ext_data).unwrap();
+	assert_eq!(calldata[..4], selector(VANCHOR_TRANSACT));
+	let proof_type = ParamType::Tuple(vec![
+		ParamType::Bytes,
+		ParamType::Bytes,
+		ParamType::Array(Box::new(ParamType::FixedBytes(32))),
+		ParamType::FixedArray(Box::new(ParamType::FixedBytes(32)), 2),
+		ParamType::Uint(256),
+		ParamType::FixedBytes(32),
+	]);
+	let args = match ethabi::decode(&[proof_type], &calldata[4..]).unwrap().remove(0) {
+		ethabi::Token::Tuple(args) => args,
+		_ => unreachable!(),
+	};
+	let evm_proof = JsGroth16Proof::from_compressed(crate::types::Curve::Bn254, &proof.proof)
+		.unwrap()
+		.to_evm_bytes()
+		.unwrap();
+	assert_eq!(args[0].clone().into_bytes().unwrap(), evm_proof);
+	assert_eq!(args[1].clone().into_bytes().unwrap(), proof.public_inputs[7..].concat());
+	let nullifiers = args[2].clone().into_array().unwrap();
+	assert_eq!(nullifiers.len(), 2);
+	assert_eq!(
+		nullifiers[0].clone().into_fixed_bytes().unwrap(),
+		proof.public_inputs[2]
+	);
+	assert_eq!(args[5].clone().into_fixed_bytes().unwrap(), proof.public_inputs[1]);
+}
+
+#[wasm_bindgen_test]
+fn generate_vanchor_proof_4_roots() {
+	let VAnchorTestSetup {
+		mut proof_input_builder,
+		..
+	} = generate_vanchor_test_setup_2_inputs();
+	let keys = setup_keys(JsValue::from("vanchor").into(), None, Some(4), None, None).unwrap();
+	let root = match &proof_input_builder.inner {
+		ProofInputBuilder::VAnchor(input) => input.roots.clone().unwrap()[0].clone(),
+		_ => unreachable!(),
+	};
+	proof_input_builder.inner.roots(vec![root; 4]).unwrap();
+	proof_input_builder.inner.pk(keys.pk).unwrap();
+
+	let proof_input = proof_input_builder.build_js().unwrap();
+	let proof = generate_proof_js(proof_input).unwrap().vanchor_proof().unwrap();
+	let is_valid_proof = verify_unchecked_raw::<Bn254>(&proof.public_inputs, &keys.vk, &proof.proof).unwrap();
+
+	assert!(is_valid_proof);
+}
+
 #[wasm_bindgen_test]
 fn generate_vanchor_proof_16_inputs() {
 	let VAnchorTestSetup {
@@ -480,7 +1555,7 @@ fn should_generate_a_valid_proof_for_already_used_merkle_tree() {
 		.set_chain_leaves(0, Leaves::from(JsValue::from(leaves.clone())))
 		.unwrap();
 	// Create the tree
-	let tree = MTBn254X5::new(Leaves::from(JsValue::from(leaves.clone())), JsString::from("0")).unwrap();
+	let tree = MTBn254X5::new(Leaves::from(JsValue::from(leaves)), JsString::from("0")).unwrap();
 	let indices: Array = vec![JsValue::from("16"), JsValue::from("0")].into_iter().collect();
 	let roots: Array = vec![
 		Uint8Array::from(tree.inner.root().into_repr().to_bytes_be().as_slice()),
@@ -488,34 +1563,34 @@ fn should_generate_a_valid_proof_for_already_used_merkle_tree() {
 	]
 	.into_iter()
 	.collect();
-	proof_input_builder.set_leaves_map(leaves_map).unwrap();
+	proof_input_builder = proof_input_builder.set_leaves_map(leaves_map).unwrap();
 
-	proof_input_builder
+	proof_input_builder = proof_input_builder
 		.set_indices(Indices::from(JsValue::from(indices)))
 		.unwrap();
-	proof_input_builder
+	proof_input_builder = proof_input_builder
 		.set_roots(Leaves::from(JsValue::from(roots)))
 		.unwrap();
 
-	proof_input_builder.set_pk(JsString::from("0000")).unwrap();
-	proof_input_builder.public_amount(JsString::from("10")).unwrap();
-	proof_input_builder.chain_id(JsString::from("0")).unwrap();
-	proof_input_builder.set_ext_data_hash(JsString::from("1111")).unwrap();
+	proof_input_builder = proof_input_builder.set_pk(JsString::from("0000")).unwrap();
+	proof_input_builder = proof_input_builder.public_amount(JsString::from("10"), None).unwrap();
+	proof_input_builder = proof_input_builder.chain_id(JsString::from("0")).unwrap();
+	proof_input_builder = proof_input_builder.set_ext_data_hash(JsString::from("1111")).unwrap();
 
-	let notes: Array = vec![JsValue::from(new_utxo.clone()), JsValue::from(dummy_input_utxo)]
+	let notes: Array = vec![JsValue::from(new_utxo), JsValue::from(dummy_input_utxo)]
 		.into_iter()
 		.collect();
 
 	let output_1 = new_utxo_bn254_2_2(crate::types::Curve::Bn254, 10, 0);
 	let output_2 = new_utxo_bn254_2_2(crate::types::Curve::Bn254, 10, 3);
 
-	proof_input_builder.set_input_utxos(notes).unwrap();
-	proof_input_builder.set_output_utxos(output_1, output_2).unwrap();
+	proof_input_builder = proof_input_builder.set_input_utxos(notes).unwrap();
+	proof_input_builder = proof_input_builder.set_output_utxos(output_1, output_2).unwrap();
 
 	let c = VAnchorR1CSProverBn254_30_2_2_2::setup_random_circuit(Curve::Bn254, DEFAULT_LEAF, &mut OsRng).unwrap();
 	let (pk, vk) = setup_keys_unchecked::<Bn254, _, _>(c, &mut OsRng).unwrap();
 
-	proof_input_builder.set_pk(JsString::from(hex::encode(pk))).unwrap();
+	proof_input_builder = proof_input_builder.set_pk(JsString::from(hex::encode(pk))).unwrap();
 
 	let proof_input = proof_input_builder.build_js().unwrap();
 
@@ -524,3 +1599,31 @@ fn should_generate_a_valid_proof_for_already_used_merkle_tree() {
 
 	assert!(is_valid_proof);
 }
+
+#[wasm_bindgen_test]
+fn should_order_public_inputs_for_target_runtime() {
+	let output = ProofOutput::VAnchor(VAnchorProof {
+		proof: vec![],
+		public_inputs: vec![vec![1], [vec![0; 31], vec![2]].concat()],
+		output_utxos: vec![],
+		input_utxos: vec![],
+		public_amount: [0; 32],
+	});
+
+	let evm = output.public_inputs_for(TargetRuntime::EvmVAnchor).unwrap();
+	assert_eq!(evm[0][31], 1);
+	assert_eq!(evm[1][31], 2);
+	let substrate = output.public_inputs_for(TargetRuntime::SubstrateVAnchor).unwrap();
+	assert_eq!(substrate[0][0], 1);
+	assert_eq!(substrate[1][0], 2);
+	assert!(substrate.iter().all(|input| input[1..] == [0; 31]));
+
+	let error = output.public_inputs_for(TargetRuntime::SubstrateMixer).unwrap_err();
+	assert_eq!(error.code, OpStatusCode::InvalidTargetRuntime);
+	let error = output.public_inputs_for(TargetRuntime::EvmAnchor).unwrap_err();
+	assert_eq!(error.code, OpStatusCode::InvalidTargetRuntime);
+	assert_eq!(
+		"EvmMixer".parse::<TargetRuntime>().unwrap_err(),
+		OpStatusCode::InvalidTargetRuntime
+	);
+}
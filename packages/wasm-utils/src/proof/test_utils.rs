@@ -1,5 +1,6 @@
 use std::collections::BTreeMap;
 
+use ark_bls12_381::Bls12_381;
 use ark_bn254::{Bn254, Fr as Bn254Fr};
 use ark_ff::{BigInteger, PrimeField, Zero};
 use arkworks_native_gadgets::poseidon::Poseidon;
@@ -14,11 +15,12 @@ use crate::proof::{JsProofInputBuilder, LeavesMapInput, ProofInputBuilder, VAnch
 use crate::types::{Backend, Curve, Indices, Leaves, Protocol, WasmCurve, BE};
 use crate::utxo::JsUtxo;
 use crate::{
-	MixerR1CSProverBn254_30, VAnchorR1CSProverBn254_30_2_16_2, VAnchorR1CSProverBn254_30_2_2_2, DEFAULT_LEAF,
-	TREE_HEIGHT,
+	MixerR1CSProverBls381_30, MixerR1CSProverBn254_30, VAnchorR1CSProverBn254_30_2_16_2,
+	VAnchorR1CSProverBn254_30_2_2_2, DEFAULT_LEAF, TREE_HEIGHT,
 };
 
 pub const MIXER_NOTE_V1_X5_5:&str  = "webb://v1:mixer/2:2/2:2/fd717cfe463b3ffec71ee6b7606bbd0179170510abf41c9f16c1d20ca9923f0e:18b6b080e6a43262f00f6fb3da0d2409c4871b8f26d89d5c8836358e1af5a41c/?curve=Bn254&width=3&exp=5&hf=Poseidon&backend=Arkworks&token=EDG&denom=18&amount=10&index=10";
+pub const MIXER_NOTE_V1_BLS381_X5_3:&str  = "webb://v1:mixer/2:2/2:2/0c8e1bed9bfd4a2a4b7306dbc12cbf8ef07bd2e1d3e7f0a8e9b0b1fc4fb26a4d:3f19e3b2b0e4d1bf65b6b15c1a0b0fc8d39b7a0a6ce3a50ee9b1d3c3a5c5a7e1/?curve=Bls381&width=3&exp=5&hf=Poseidon&backend=Arkworks&token=EDG&denom=18&amount=10&index=10";
 pub const VANCHOR_NOTE_V1_X5_4:&str  ="webb://v1:vanchor/2:3/2:3/0300000000000000000000000000000000000000000000000000000000000000:0a00000000000000000000000000000000000000000000000000000000000000:7798d054444ec463be7d41ad834147b5b2c468182c7cd6a601aec29a273fca05:bf5d780608f5b8a8db1dc87356a225a0324a1db61903540daaedd54ab10a4124/?curve=Bn254&width=5&exp=5&hf=Poseidon&backend=Arkworks&token=EDG&denom=18&amount=10&index=10";
 
 pub const DECODED_SUBSTRATE_ADDRESS: &str = "644277e80e74baf70c59aeaa038b9e95b400377d1fd09c87a6f8071bce185129";
@@ -26,8 +28,8 @@ pub fn new_utxo_bn254_2_2(curve: Curve, amount: u128, chain_id: u64) -> JsUtxo {
 	let curve: WasmCurve = JsValue::from(curve.to_string()).into();
 	let backend: BE = JsValue::from(Backend::Arkworks.to_string()).into();
 	JsUtxo::construct(
-		curve.clone(),
-		backend.clone(),
+		curve,
+		backend,
 		JsString::from(amount.to_string()),
 		JsString::from(chain_id.to_string()),
 		None,
@@ -59,10 +61,20 @@ pub fn generate_mixer_test_setup(
 	recipient_decoded_ss58: &str,
 	note: &str,
 ) -> MixerTestSetup {
-	let (c, ..) = MixerR1CSProverBn254_30::setup_random_circuit(ArkCurve::Bn254, DEFAULT_LEAF, &mut OsRng).unwrap();
-	let (pk, vk) = setup_keys_unchecked::<Bn254, _, _>(c, &mut OsRng).unwrap();
-	let index = 0;
 	let note = JsNote::js_deserialize(JsString::from(note)).unwrap();
+	let (pk, vk) = match note.curve.unwrap_or(Curve::Bn254) {
+		Curve::Bn254 => {
+			let (c, ..) =
+				MixerR1CSProverBn254_30::setup_random_circuit(ArkCurve::Bn254, DEFAULT_LEAF, &mut OsRng).unwrap();
+			setup_keys_unchecked::<Bn254, _, _>(c, &mut OsRng).unwrap()
+		}
+		Curve::Bls381 => {
+			let (c, ..) =
+				MixerR1CSProverBls381_30::setup_random_circuit(ArkCurve::Bls381, DEFAULT_LEAF, &mut OsRng).unwrap();
+			setup_keys_unchecked::<Bls12_381, _, _>(c, &mut OsRng).unwrap()
+		}
+	};
+	let index = 0;
 	let leaf = note.get_leaf_commitment().unwrap();
 	let leaf_bytes: Vec<u8> = leaf.to_vec();
 
@@ -70,20 +82,20 @@ pub fn generate_mixer_test_setup(
 	let protocol: Protocol = JsValue::from("mixer").into();
 	let mut js_builder = JsProofInputBuilder::new(protocol).unwrap();
 
-	js_builder.set_leaf_index(JsString::from("0")).unwrap();
-	js_builder.set_leaves(Leaves::from(JsValue::from(leaves_ua))).unwrap();
+	js_builder = js_builder.set_leaf_index(JsString::from("0")).unwrap();
+	js_builder = js_builder.set_leaves(Leaves::from(JsValue::from(leaves_ua))).unwrap();
 
-	js_builder.set_fee(JsString::from("5")).unwrap();
-	js_builder.set_refund(JsString::from("1")).unwrap();
+	js_builder = js_builder.set_fee(JsString::from("5"), None).unwrap();
+	js_builder = js_builder.set_refund(JsString::from("1"), None).unwrap();
 
-	js_builder.set_relayer(JsString::from(relayer_decoded_ss58)).unwrap();
-	js_builder
+	js_builder = js_builder.set_relayer(JsString::from(relayer_decoded_ss58)).unwrap();
+	js_builder = js_builder
 		.set_recipient(JsString::from(recipient_decoded_ss58))
 		.unwrap();
 
-	js_builder.set_pk(JsString::from(hex::encode(&pk))).unwrap();
+	js_builder = js_builder.set_pk(JsString::from(hex::encode(&pk))).unwrap();
 
-	js_builder.set_metadata_from_note(&note).unwrap();
+	js_builder = js_builder.set_metadata_from_note(&note).unwrap();
 
 	MixerTestSetup {
 		relayer: hex::decode(relayer_decoded_ss58).unwrap(),
@@ -159,14 +171,14 @@ pub fn generate_vanchor_test_js_setup() -> VAnchorTestSetup {
 	let note1_com: Vec<u8> = input_utxo1.get_commitment();
 	let note2_com: Vec<u8> = input_utxo2.get_commitment();
 	// Insert commitments
-	let leaves_f: Vec<_> = vec![note1_com, note2_com]
+	let leaves_f: Vec<_> = [note1_com, note2_com]
 		.iter()
-		.map(|c| Bn254Fr::from_be_bytes_mod_order(&c))
+		.map(|c| Bn254Fr::from_be_bytes_mod_order(c))
 		.collect();
 	// tree 0
 	let (_tree0, in_path0) = setup_tree_and_create_path::<Bn254Fr, Poseidon<Bn254Fr>, TREE_HEIGHT>(
 		&poseidon3,
-		&vec![leaves_f[0].clone()],
+		&[leaves_f[0]],
 		index,
 		&DEFAULT_LEAF,
 	)
@@ -175,7 +187,7 @@ pub fn generate_vanchor_test_js_setup() -> VAnchorTestSetup {
 	// tree 1
 	let (_tree1, in_path1) = setup_tree_and_create_path::<Bn254Fr, Poseidon<Bn254Fr>, TREE_HEIGHT>(
 		&poseidon3,
-		&vec![leaves_f[1].clone()],
+		&[leaves_f[1]],
 		index,
 		&DEFAULT_LEAF,
 	)
@@ -188,8 +200,8 @@ pub fn generate_vanchor_test_js_setup() -> VAnchorTestSetup {
 
 	let mut js_builder = JsProofInputBuilder::new(JsValue::from("vanchor").into()).unwrap();
 
-	js_builder.set_pk(JsString::from(hex::encode(pk))).unwrap();
-	js_builder.set_roots(Leaves::from(JsValue::from(roots_array))).unwrap();
+	js_builder = js_builder.set_pk(JsString::from(hex::encode(pk))).unwrap();
+	js_builder = js_builder.set_roots(Leaves::from(JsValue::from(roots_array))).unwrap();
 	// leaves
 	let mut leaves_map = LeavesMapInput::new();
 	let leaves_ua: Array = vec![input_utxo1.commitment(), input_utxo2.commitment()]
@@ -198,16 +210,16 @@ pub fn generate_vanchor_test_js_setup() -> VAnchorTestSetup {
 	leaves_map
 		.set_chain_leaves(chain_id, Leaves::from(JsValue::from(leaves_ua)))
 		.unwrap();
-	js_builder.set_leaves_map(leaves_map).unwrap();
-	js_builder.public_amount(JsString::from("10")).unwrap();
-	js_builder.chain_id(JsString::from(chain_id.to_string())).unwrap();
+	js_builder = js_builder.set_leaves_map(leaves_map).unwrap();
+	js_builder = js_builder.public_amount(JsString::from("10"), None).unwrap();
+	js_builder = js_builder.chain_id(JsString::from(chain_id.to_string())).unwrap();
 	let indices: Array = vec![JsValue::from("0"), JsValue::from("1")].iter().collect();
-	js_builder.set_indices(Indices::from(JsValue::from(indices))).unwrap();
+	js_builder = js_builder.set_indices(Indices::from(JsValue::from(indices))).unwrap();
 	let input_utxos: Array = vec![JsValue::from(input_utxo1.clone()), JsValue::from(input_utxo2.clone())]
 		.iter()
 		.collect();
-	js_builder.set_input_utxos(input_utxos).unwrap();
-	js_builder.set_output_utxos(output_1, output_2).unwrap();
+	js_builder = js_builder.set_input_utxos(input_utxos).unwrap();
+	js_builder = js_builder.set_output_utxos(output_1, output_2).unwrap();
 	// Assert the utxo chain id
 	let note_1_chain_id = input_utxo1.chain_id_raw();
 	let note_2_chain_id = input_utxo2.chain_id_raw();
@@ -236,16 +248,9 @@ pub fn generate_vanchor_test_setup_2_inputs() -> VAnchorTestSetup {
 	let in_chain_id = 0;
 	let in_amount_fr = Bn254Fr::from(in_amount);
 
-	let mut in_utxo1 = VAnchorR1CSProverBn254_30_2_2_2::new_utxo(
-		curve,
-		in_chain_id,
-		in_amount_fr.clone(),
-		Some(0),
-		None,
-		None,
-		&mut rng,
-	)
-	.unwrap();
+	let mut in_utxo1 =
+		VAnchorR1CSProverBn254_30_2_2_2::new_utxo(curve, in_chain_id, in_amount_fr, Some(0), None, None, &mut rng)
+			.unwrap();
 	in_utxo1.set_index(index);
 
 	let mut in_utxo2 =
@@ -258,11 +263,11 @@ pub fn generate_vanchor_test_setup_2_inputs() -> VAnchorTestSetup {
 
 	let mut proof_builder = ProofInputBuilder::VAnchor(Box::new(VAnchorProofInput::default()));
 
-	let leaf0 = in_utxo1.commitment.clone();
-	let leaf1 = in_utxo2.commitment.clone();
+	let leaf0 = in_utxo1.commitment;
+	let leaf1 = in_utxo2.commitment;
 	let (tree, _) = setup_tree_and_create_path::<Bn254Fr, Poseidon<Bn254Fr>, TREE_HEIGHT>(
 		&tree_hasher,
-		&vec![leaf0, leaf1],
+		&[leaf0, leaf1],
 		0,
 		&DEFAULT_LEAF,
 	)
@@ -271,8 +276,8 @@ pub fn generate_vanchor_test_setup_2_inputs() -> VAnchorTestSetup {
 	let in_root_set = [root; 2].iter().map(|x| x.into_repr().to_bytes_be()).collect();
 
 	let mut leave_map: BTreeMap<u64, Vec<Vec<u8>>> = BTreeMap::new();
-	let leaves: Vec<_> = vec![leaf0, leaf1].iter().map(|x| x.into_repr().to_bytes_be()).collect();
-	leave_map.insert(0, leaves.clone());
+	let leaves: Vec<_> = [leaf0, leaf1].iter().map(|x| x.into_repr().to_bytes_be()).collect();
+	leave_map.insert(0, leaves);
 	proof_builder.public_amount(public_amount).unwrap();
 	proof_builder.ext_data_hash([1u8; 32].to_vec()).unwrap();
 	proof_builder.leaf_indices(vec![0, 1]).unwrap();
@@ -302,7 +307,10 @@ pub fn generate_vanchor_test_setup_2_inputs() -> VAnchorTestSetup {
 	proof_builder.pk(pk).unwrap();
 
 	VAnchorTestSetup {
-		proof_input_builder: JsProofInputBuilder { inner: proof_builder },
+		proof_input_builder: JsProofInputBuilder {
+			inner: proof_builder,
+			partial_withdrawal: None,
+		},
 		notes: vec![],
 		roots_raw: vec![],
 		vk,
@@ -326,13 +334,13 @@ pub fn generate_vanchor_test_setup_16_non_default_inputs() -> VAnchorTestSetup {
 	let mut next_utxo_index = 0;
 	let mut indices = vec![];
 	loop {
-		if &inputs.len() == &16 {
+		if inputs.len() == 16 {
 			break;
 		}
 		let mut utxo = VAnchorR1CSProverBn254_30_2_16_2::new_utxo(
 			curve,
 			in_chain_id,
-			in_amount_fr.clone(),
+			in_amount_fr,
 			Some(next_utxo_index),
 			None,
 			None,
@@ -361,19 +369,13 @@ pub fn generate_vanchor_test_setup_16_non_default_inputs() -> VAnchorTestSetup {
 
 	let mut leave_map: BTreeMap<u64, Vec<Vec<u8>>> = BTreeMap::new();
 	let leaves: Vec<_> = leaves.iter().map(|x| x.into_repr().to_bytes_be()).collect();
-	leave_map.insert(0, leaves.clone());
+	leave_map.insert(0, leaves);
 	proof_builder.public_amount(public_amount).unwrap();
 	proof_builder.ext_data_hash([1u8; 32].to_vec()).unwrap();
 	proof_builder.leaf_indices(indices).unwrap();
 	proof_builder.leaves_map(leave_map).unwrap();
 	proof_builder
-		.set_input_utxos(
-			inputs
-				.clone()
-				.into_iter()
-				.map(|u| JsUtxo::new_from_bn254_utxo(u))
-				.collect(),
-		)
+		.set_input_utxos(inputs.into_iter().map(JsUtxo::new_from_bn254_utxo).collect())
 		.unwrap();
 
 	let c = VAnchorR1CSProverBn254_30_2_16_2::setup_random_circuit(ArkCurve::Bn254, DEFAULT_LEAF, &mut OsRng).unwrap();
@@ -394,7 +396,10 @@ pub fn generate_vanchor_test_setup_16_non_default_inputs() -> VAnchorTestSetup {
 	proof_builder.pk(pk).unwrap();
 
 	VAnchorTestSetup {
-		proof_input_builder: JsProofInputBuilder { inner: proof_builder },
+		proof_input_builder: JsProofInputBuilder {
+			inner: proof_builder,
+			partial_withdrawal: None,
+		},
 		notes: vec![],
 		roots_raw: vec![],
 		vk,
@@ -424,7 +429,7 @@ pub fn generate_vanchor_test_setup_16_mixed_inputs() -> VAnchorTestSetup {
 		let utxo = VAnchorR1CSProverBn254_30_2_16_2::new_utxo(
 			curve,
 			in_chain_id,
-			in_amount_fr.clone(),
+			in_amount_fr,
 			Some(next_utxo_index),
 			None,
 			None,
@@ -470,19 +475,13 @@ pub fn generate_vanchor_test_setup_16_mixed_inputs() -> VAnchorTestSetup {
 
 	let mut leave_map: BTreeMap<u64, Vec<Vec<u8>>> = BTreeMap::new();
 	let leaves: Vec<_> = leaves.iter().map(|x| x.into_repr().to_bytes_be()).collect();
-	leave_map.insert(0, leaves.clone());
+	leave_map.insert(0, leaves);
 	proof_builder.public_amount(public_amount).unwrap();
 	proof_builder.ext_data_hash([1u8; 32].to_vec()).unwrap();
 	proof_builder.leaf_indices(indices).unwrap();
 	proof_builder.leaves_map(leave_map).unwrap();
 	proof_builder
-		.set_input_utxos(
-			inputs
-				.clone()
-				.into_iter()
-				.map(|u| JsUtxo::new_from_bn254_utxo(u))
-				.collect(),
-		)
+		.set_input_utxos(inputs.into_iter().map(JsUtxo::new_from_bn254_utxo).collect())
 		.unwrap();
 
 	let c = VAnchorR1CSProverBn254_30_2_16_2::setup_random_circuit(ArkCurve::Bn254, DEFAULT_LEAF, &mut OsRng).unwrap();
@@ -503,7 +502,10 @@ pub fn generate_vanchor_test_setup_16_mixed_inputs() -> VAnchorTestSetup {
 		.unwrap();
 
 	VAnchorTestSetup {
-		proof_input_builder: JsProofInputBuilder { inner: proof_builder },
+		proof_input_builder: JsProofInputBuilder {
+			inner: proof_builder,
+			partial_withdrawal: None,
+		},
 		notes: vec![],
 		roots_raw: vec![],
 		vk,
@@ -1,22 +1,34 @@
 use core::convert::TryInto;
 use js_sys::{Array, JsString, Uint8Array};
-use wasm_bindgen::__rt::std::collections::btree_map::BTreeMap;
+use parity_scale_codec::{Decode, Encode};
+use wasm_bindgen::__rt::std::collections::btree_map::{BTreeMap, Entry};
 // https://github.com/rustwasm/wasm-bindgen/issues/2231#issuecomment-656293288
 use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
 
-use ark_bn254::Fr as Bn254Fr;
-use ark_crypto_primitives::Error;
+use ark_bn254::{Bn254, Fr as Bn254Fr};
 use ark_ff::{BigInteger, PrimeField};
+use arkworks_native_gadgets::poseidon::Poseidon;
+use arkworks_setups::common::{setup_tree_and_create_path, VAnchorProof as ArkVAnchorProof};
+use arkworks_setups::r1cs::vanchor::VAnchorR1CSProver;
 use arkworks_setups::utxo::Utxo;
 use arkworks_setups::{Curve as ArkCurve, VAnchorProver};
-use rand::rngs::OsRng;
+use rand::{CryptoRng, RngCore};
 
-use crate::types::{Backend, Curve, OpStatusCode, OperationError};
+use crate::hash::poseidon_params;
+use crate::merkle::compute_root;
+use crate::note::versioning::NoteDefaults;
+use crate::proof::witness::Witness;
+use crate::proof::{
+	ensure_proving_backend, ensure_supported_circuit, ensure_tree_depth, is_field_element, prover_error_cause,
+	validate_roots,
+};
+use crate::types::{Backend, Curve, JsUtxoArray, NoteProtocol, NoteVersion, OpStatusCode, OperationError, StringArray};
 use crate::utxo::JsUtxo;
-use crate::{VAnchorR1CSProverBn254_30_2_16_2, VAnchorR1CSProverBn254_30_2_2_2, DEFAULT_LEAF};
+use crate::{DEFAULT_LEAF, INS_16, INS_2, OUTS_2, TREE_HEIGHT};
 
-const SUPPORTED_INPUT_COUNT: [usize; 2] = [2, 16];
-const SUPPORTED_VANCHOR_COUNT: [usize; 3] = [2, 8, 16];
+pub const SUPPORTED_INPUT_COUNT: [usize; 2] = [2, 16];
+pub const SUPPORTED_VANCHOR_COUNT: [usize; 4] = [2, 4, 8, 16];
 
 #[wasm_bindgen]
 #[derive(Debug, Clone)]
@@ -36,27 +48,27 @@ pub struct VAnchorProof {
 impl VAnchorProof {
 	#[wasm_bindgen(getter)]
 	#[wasm_bindgen(js_name = publicInputs)]
-	pub fn public_inputs_raw(&self) -> Array {
+	pub fn public_inputs_raw(&self) -> StringArray {
 		let inputs: Array = self
 			.public_inputs
 			.iter()
 			.map(|x| JsString::from(hex::encode(x)))
 			.collect();
-		inputs
+		inputs.unchecked_into()
 	}
 
 	#[wasm_bindgen(getter)]
 	#[wasm_bindgen(js_name = outputUtxos)]
-	pub fn output_utxos(&self) -> Array {
+	pub fn output_utxos(&self) -> JsUtxoArray {
 		let inputs: Array = self.output_utxos.clone().into_iter().map(JsValue::from).collect();
-		inputs
+		inputs.unchecked_into()
 	}
 
 	#[wasm_bindgen(getter)]
 	#[wasm_bindgen(js_name = inputUtxos)]
-	pub fn inputs_utxos(&self) -> Array {
+	pub fn inputs_utxos(&self) -> JsUtxoArray {
 		let inputs: Array = self.input_utxos.clone().into_iter().map(JsValue::from).collect();
-		inputs
+		inputs.unchecked_into()
 	}
 
 	#[wasm_bindgen(getter)]
@@ -95,6 +107,100 @@ pub struct VAnchorProofPayload {
 	pub public_amount: i128,
 	// Utxos that are being created
 	pub output_utxos: [JsUtxo; 2],
+	// Depth of the merkle trees
+	pub tree_depth: usize,
+	/// Seed of the prover's RNG, `OsRng` is used when it's not set
+	pub rng_seed: Option<[u8; 32]>,
+}
+
+/// SCALE encoded form of [`VAnchorProofPayload`], the UTXOs are kept in their
+/// string serialization
+#[derive(Encode, Decode)]
+pub(crate) struct VAnchorProofPayloadState {
+	exponentiation: i8,
+	width: u64,
+	curve: Curve,
+	backend: Backend,
+	pk: Vec<u8>,
+	leaves: BTreeMap<u64, Vec<Vec<u8>>>,
+	ext_data_hash: Vec<u8>,
+	roots: Vec<Vec<u8>>,
+	input_utxos: Vec<Vec<u8>>,
+	indices: Vec<u64>,
+	chain_id: u64,
+	public_amount: i128,
+	output_utxos: [Vec<u8>; 2],
+	tree_depth: u64,
+	rng_seed: Option<[u8; 32]>,
+}
+
+fn encode_utxo(utxo: &JsUtxo) -> Vec<u8> {
+	utxo.to_string().into_bytes()
+}
+
+fn decode_utxo(utxo: &[u8]) -> Result<JsUtxo, OperationError> {
+	let utxo = core::str::from_utf8(utxo).map_err(|_| OperationError::from(OpStatusCode::DeserializationFailed))?;
+	// `JsUtxo::from_str` indexes the 8 `&` separated fields directly
+	if utxo.split('&').count() != 8 {
+		return Err(OpStatusCode::DeserializationFailed.into());
+	}
+	JsUtxo::deserialize(utxo)
+}
+
+impl From<&VAnchorProofPayload> for VAnchorProofPayloadState {
+	fn from(payload: &VAnchorProofPayload) -> Self {
+		Self {
+			exponentiation: payload.exponentiation,
+			width: payload.width as u64,
+			curve: payload.curve,
+			backend: payload.backend,
+			pk: payload.pk.clone(),
+			leaves: payload.leaves.clone(),
+			ext_data_hash: payload.ext_data_hash.clone(),
+			roots: payload.roots.clone(),
+			input_utxos: payload.input_utxos.iter().map(encode_utxo).collect(),
+			indices: payload.indices.clone(),
+			chain_id: payload.chain_id,
+			public_amount: payload.public_amount,
+			output_utxos: [
+				encode_utxo(&payload.output_utxos[0]),
+				encode_utxo(&payload.output_utxos[1]),
+			],
+			tree_depth: payload.tree_depth as u64,
+			rng_seed: payload.rng_seed,
+		}
+	}
+}
+
+impl TryFrom<VAnchorProofPayloadState> for VAnchorProofPayload {
+	type Error = OperationError;
+
+	fn try_from(state: VAnchorProofPayloadState) -> Result<Self, Self::Error> {
+		let input_utxos = state
+			.input_utxos
+			.iter()
+			.map(|utxo| decode_utxo(utxo))
+			.collect::<Result<Vec<_>, _>>()?;
+		let [output_0, output_1] = state.output_utxos;
+
+		Ok(Self {
+			exponentiation: state.exponentiation,
+			width: state.width as usize,
+			curve: state.curve,
+			backend: state.backend,
+			pk: state.pk,
+			leaves: state.leaves,
+			ext_data_hash: state.ext_data_hash,
+			roots: state.roots,
+			input_utxos,
+			indices: state.indices,
+			chain_id: state.chain_id,
+			public_amount: state.public_amount,
+			output_utxos: [decode_utxo(&output_0)?, decode_utxo(&output_1)?],
+			tree_depth: state.tree_depth as usize,
+			rng_seed: state.rng_seed,
+		})
+	}
 }
 
 #[derive(Debug, Clone, Default)]
@@ -119,9 +225,115 @@ pub struct VAnchorProofInput {
 	pub public_amount: Option<i128>,
 	// Utxos that are being created
 	pub output_utxos: Option<[JsUtxo; 2]>,
+	// Depth of the merkle trees
+	pub tree_depth: Option<usize>,
+	pub rng_seed: Option<[u8; 32]>,
 }
 
 impl VAnchorProofInput {
+	/// Checks the input without building it, collecting an error for every
+	/// missing or invalid field instead of failing on the first one
+	pub fn validate(&self) -> Vec<OperationError> {
+		let mut errors: Vec<OperationError> = Vec::new();
+		let mut missing = |is_set: bool, code: OpStatusCode, field: &str| {
+			if !is_set {
+				errors.push(OperationError::new_with_message(
+					code,
+					format!("`{}` is not set", field),
+				));
+			}
+		};
+		missing(self.pk.is_some(), OpStatusCode::InvalidProvingKey, "pk");
+		missing(
+			self.input_utxos.is_some(),
+			OpStatusCode::InvalidInputUtxoConfig,
+			"inputUtxos",
+		);
+		missing(self.leaves.is_some(), OpStatusCode::InvalidLeaves, "leaves");
+		missing(
+			self.ext_data_hash.is_some(),
+			OpStatusCode::InvalidExtDataHash,
+			"extDataHash",
+		);
+		missing(self.roots.is_some(), OpStatusCode::InvalidRoots, "roots");
+		missing(self.chain_id.is_some(), OpStatusCode::InvalidChainId, "chainId");
+		missing(self.indices.is_some(), OpStatusCode::InvalidIndices, "indices");
+		missing(
+			self.public_amount.is_some(),
+			OpStatusCode::InvalidPublicAmount,
+			"publicAmount",
+		);
+		missing(
+			self.output_utxos.is_some(),
+			OpStatusCode::InvalidOutputUtxoConfig,
+			"outputUtxos",
+		);
+
+		if let Some(roots) = &self.roots {
+			if !SUPPORTED_VANCHOR_COUNT.contains(&roots.len()) {
+				let message = format!(
+					"`roots` has {} entries while the supported set length should be one of {:?}",
+					roots.len(),
+					SUPPORTED_VANCHOR_COUNT
+				);
+				errors.push(OperationError::new_with_message(OpStatusCode::InvalidRoots, message));
+			} else if let Err(e) = validate_roots(roots, roots.len(), self.curve.unwrap_or(Curve::Bn254)) {
+				errors.push(e);
+			}
+		}
+		if let Some(input_utxos) = &self.input_utxos {
+			if !SUPPORTED_INPUT_COUNT.contains(&input_utxos.len()) {
+				let message = format!(
+					"`inputUtxos` has {} UTXOs while the supported set length should be one of {:?}",
+					input_utxos.len(),
+					SUPPORTED_INPUT_COUNT
+				);
+				errors.push(OperationError::new_with_message(
+					OpStatusCode::InvalidInputUtxoConfig,
+					message,
+				));
+			}
+			if let Some(indices) = &self.indices {
+				if indices.len() != input_utxos.len() {
+					let message = format!(
+						"`indices` has {} entries while there are {} input UTXOs",
+						indices.len(),
+						input_utxos.len()
+					);
+					errors.push(OperationError::new_with_message(OpStatusCode::InvalidIndices, message));
+				}
+			}
+		}
+		if let (Some(leaves), Some(indices), Some(chain_id)) = (&self.leaves, &self.indices, self.chain_id) {
+			let chain_leaves_len = leaves.get(&(chain_id as u64)).map(|l| l.len()).unwrap_or(0) as u64;
+			let out_of_bounds: Vec<u64> = indices
+				.iter()
+				.copied()
+				.filter(|index| *index != 0 && *index >= chain_leaves_len)
+				.collect();
+			if !out_of_bounds.is_empty() {
+				let message = format!(
+					"`indices` {:?} are out of bounds for {} leaves on chain {}",
+					out_of_bounds, chain_leaves_len, chain_id
+				);
+				errors.push(OperationError::new_with_message(OpStatusCode::InvalidIndices, message));
+			}
+		}
+		if let Err(e) = ensure_proving_backend(self.backend.unwrap_or(Backend::Arkworks)) {
+			errors.push(e);
+		}
+		let defaults = NoteDefaults::of(NoteVersion::V1, NoteProtocol::VAnchor);
+		if let Err(e) = ensure_supported_circuit(
+			NoteProtocol::VAnchor,
+			self.curve.unwrap_or(defaults.curve),
+			self.width.unwrap_or(defaults.width),
+			self.exponentiation.unwrap_or(defaults.exponentiation),
+		) {
+			errors.push(e);
+		}
+		errors
+	}
+
 	pub fn build(self) -> Result<VAnchorProofPayload, OperationError> {
 		let pk = self.pk.ok_or(OpStatusCode::InvalidProvingKey)?;
 		let input_utxos = self.input_utxos.ok_or(OpStatusCode::InvalidInputUtxoConfig)?;
@@ -133,10 +345,20 @@ impl VAnchorProofInput {
 		let public_amount = self.public_amount.ok_or(OpStatusCode::InvalidPublicAmount)?;
 		let output_utxos = self.output_utxos.ok_or(OpStatusCode::InvalidOutputUtxoConfig)?;
 
-		let exponentiation = self.exponentiation.unwrap_or(5);
-		let width = self.width.unwrap_or(3);
-		let curve = self.curve.unwrap_or(Curve::Bn254);
+		let defaults = NoteDefaults::of(NoteVersion::V1, NoteProtocol::VAnchor);
+		let exponentiation = self.exponentiation.unwrap_or(defaults.exponentiation);
+		let width = self.width.unwrap_or(defaults.width);
+		let curve = self.curve.unwrap_or(defaults.curve);
 		let backend = self.backend.unwrap_or(Backend::Arkworks);
+		let tree_depth = self.tree_depth.unwrap_or(TREE_HEIGHT);
+		ensure_supported_circuit(NoteProtocol::VAnchor, curve, width, exponentiation)?;
+		validate_roots(&roots, roots.len(), curve)?;
+		if ext_data_hash.len() == 32 && !is_field_element(curve, &ext_data_hash) {
+			let message = format!("The ext data hash is not an element of the {} scalar field", curve);
+			let mut error = OperationError::new_with_message(OpStatusCode::PublicInputOverflow, message);
+			error.data = Some("{\"input\":\"extDataHash\"}".to_string());
+			return Err(error);
+		}
 
 		// Input UTXO should have the same chain_id
 		// For default UTXOS the amount and the index should be `0`
@@ -175,8 +397,8 @@ impl VAnchorProofInput {
 			let mut op: OperationError =
 				OperationError::new_with_message(OpStatusCode::InvalidProofParameters, message);
 			op.data = Some(format!(
-				"{{ duplicateIndices:{:?} , invalidChainId:{:?} }}",
-				invalid_utxo_chain_id_indices, invalid_utxo_dublicate_nullifiers
+				r#"{{ "duplicateIndices": {:?}, "invalidChainId": {:?} }}"#,
+				invalid_utxo_dublicate_nullifiers, invalid_utxo_chain_id_indices
 			));
 			return Err(op);
 		}
@@ -204,7 +426,7 @@ impl VAnchorProofInput {
 			);
 			let mut oe = OperationError::new_with_message(OpStatusCode::InvalidProofParameters, message);
 			oe.data = Some(format!(
-				"{{ inputAmount:{} ,outputAmount:{}, publicAmount: {}}}",
+				r#"{{ "inputAmount": "{}", "outputAmount": "{}", "publicAmount": "{}" }}"#,
 				in_amount, out_amount, public_amount
 			));
 			return Err(oe);
@@ -223,45 +445,152 @@ impl VAnchorProofInput {
 			chain_id: chain_id.try_into().unwrap(),
 			public_amount,
 			output_utxos,
+			tree_depth,
+			rng_seed: self.rng_seed,
 		})
 	}
 }
 
-pub fn create_proof(vanchor_proof_input: VAnchorProofPayload, rng: &mut OsRng) -> Result<VAnchorProof, OperationError> {
-	let VAnchorProofPayload {
+/// Prove with the circuit for a `HEIGHT` deep tree, `ANCHOR_CT` roots and
+/// `INS` input UTXOs
+#[allow(clippy::too_many_arguments)]
+fn prove<const HEIGHT: usize, const ANCHOR_CT: usize, const INS: usize, R: RngCore + CryptoRng>(
+	chain_id: u64,
+	public_amount: i128,
+	ext_data_hash: Vec<u8>,
+	roots: Vec<Vec<u8>>,
+	indices: Vec<u64>,
+	leaves: BTreeMap<u64, Vec<Vec<u8>>>,
+	in_utxos: &[JsUtxo],
+	out_utxos: [Utxo<Bn254Fr>; OUTS_2],
+	pk: Vec<u8>,
+	rng: &mut R,
+) -> Result<ArkVAnchorProof, OperationError> {
+	let in_utxos = in_utxos
+		.iter()
+		.map(|utxo| utxo.get_bn254_utxo())
+		.collect::<Result<Vec<_>, _>>()?
+		.try_into()
+		.map_err(|_| OpStatusCode::InvalidNoteSecrets)?;
+	let indices = indices.try_into().map_err(|_| OpStatusCode::InvalidIndices)?;
+	let roots = roots.try_into().map_err(|_| OpStatusCode::InvalidRoots)?;
+	let pk_len = pk.len();
+
+	VAnchorR1CSProver::<Bn254, HEIGHT, ANCHOR_CT, INS, OUTS_2>::create_proof(
+		ArkCurve::Bn254,
+		chain_id,
 		public_amount,
-		backend,
-		curve,
-		width,
-		input_utxos,
+		ext_data_hash,
+		roots,
 		indices,
 		leaves,
-		exponentiation,
-		roots,
+		in_utxos,
+		out_utxos,
 		pk,
-		chain_id,
-		output_utxos,
-		ext_data_hash,
-	} = vanchor_proof_input;
-	let public_amount_bytes = Bn254Fr::from(public_amount)
-		.into_repr()
-		.to_bytes_be()
+		DEFAULT_LEAF,
+		rng,
+	)
+	.map_err(|e| {
+		prover_error_cause(&e, pk_len).unwrap_or_else(|| {
+			let message = format!("proof::vanchor:  {}", e);
+			OperationError::new_with_message(OpStatusCode::InvalidProofParameters, message)
+		})
+	})
+}
+
+/// Witness of the transaction, from the same circuit as
+/// `VAnchorR1CSProver::create_proof`
+#[allow(clippy::too_many_arguments)]
+fn witness<const HEIGHT: usize, const ANCHOR_CT: usize, const INS: usize>(
+	chain_id: u64,
+	public_amount: i128,
+	ext_data_hash: Vec<u8>,
+	roots: Vec<Vec<u8>>,
+	indices: Vec<u64>,
+	leaves: BTreeMap<u64, Vec<Vec<u8>>>,
+	in_utxos: &[JsUtxo],
+	out_utxos: [Utxo<Bn254Fr>; OUTS_2],
+) -> Result<Witness, OperationError> {
+	let in_utxos: [Utxo<Bn254Fr>; INS] = in_utxos
+		.iter()
+		.map(|utxo| utxo.get_bn254_utxo())
+		.collect::<Result<Vec<_>, _>>()?
 		.try_into()
-		.expect("proof::vanchor: Failed to wrap public amount to bytes");
+		.map_err(|_| OpStatusCode::InvalidNoteSecrets)?;
+	let indices: [u64; INS] = indices.try_into().map_err(|_| OpStatusCode::InvalidIndices)?;
+	let roots: [Vec<u8>; ANCHOR_CT] = roots.try_into().map_err(|_| OpStatusCode::InvalidRoots)?;
+	let circuit_error = |e: ark_crypto_primitives::Error| {
+		let message = format!("proof::vanchor:  {}", e);
+		OperationError::new_with_message(OpStatusCode::WitnessGenerationFailed, message)
+	};
+	if let Some(utxo) = in_utxos.iter().find(|utxo| utxo.chain_id_raw != chain_id) {
+		let message = format!(
+			"proof::vanchor: Input UTXO of chain {} spent on chain {}",
+			utxo.chain_id_raw, chain_id
+		);
+		return Err(OperationError::new_with_message(
+			OpStatusCode::InvalidProofParameters,
+			message,
+		));
+	}
+
+	let tree_hasher = Poseidon::new(poseidon_params::<Bn254Fr>(5, 3));
+	let mut paths = Vec::with_capacity(INS);
+	for utxo in &in_utxos {
+		// Empty UTXOs are proven against a tree of their own, their
+		// membership isn't checked
+		let tree_leaves = if utxo.amount == Bn254Fr::from(0u64) {
+			vec![utxo.commitment]
+		} else {
+			leaves
+				.get(&utxo.chain_id_raw)
+				.ok_or(OpStatusCode::InvalidLeaves)?
+				.iter()
+				.map(|leaf| Bn254Fr::from_be_bytes_mod_order(leaf))
+				.collect()
+		};
+		let (_, path) = setup_tree_and_create_path::<Bn254Fr, Poseidon<Bn254Fr>, HEIGHT>(
+			&tree_hasher,
+			&tree_leaves,
+			utxo.index.unwrap_or_default(),
+			&DEFAULT_LEAF,
+		)
+		.map_err(circuit_error)?;
+		paths.push(path);
+	}
+
+	let circuit = VAnchorR1CSProver::<Bn254, HEIGHT, ANCHOR_CT, INS, OUTS_2>::setup_circuit(
+		Bn254Fr::from(chain_id),
+		Bn254Fr::from(public_amount),
+		Bn254Fr::from_be_bytes_mod_order(&ext_data_hash),
+		in_utxos,
+		indices.map(Bn254Fr::from),
+		paths,
+		roots.map(|root| Bn254Fr::from_be_bytes_mod_order(&root)),
+		out_utxos,
+		Poseidon::new(poseidon_params::<Bn254Fr>(5, 2)),
+		tree_hasher,
+		Poseidon::new(poseidon_params::<Bn254Fr>(5, 4)),
+		Poseidon::new(poseidon_params::<Bn254Fr>(5, 5)),
+	)
+	.map_err(circuit_error)?;
+	Witness::synthesize::<Bn254Fr, _>(circuit)
+}
+
+/// Checks the input and root counts against the supported circuits
+fn ensure_input_shape(in_utxos: &[JsUtxo], indices: &[u64], roots: &[Vec<u8>]) -> Result<(), OperationError> {
 	// Insure UTXO set has the required/supported input count
-	let in_utxos: Vec<JsUtxo> = if SUPPORTED_INPUT_COUNT.contains(&input_utxos.len()) {
-		input_utxos
-	} else {
+	if !SUPPORTED_INPUT_COUNT.contains(&in_utxos.len()) {
 		let message = format!(
 			"proof::vanchor: Input set has {} UTXOs while the supported set length should be one of {:?}",
-			&input_utxos.len(),
+			&in_utxos.len(),
 			&SUPPORTED_INPUT_COUNT,
 		);
 		return Err(OperationError::new_with_message(
 			OpStatusCode::InvalidProofParameters,
 			message,
 		));
-	};
+	}
 	// Insure the length of the indices
 	if indices.len() != in_utxos.len() {
 		let message = format!(
@@ -285,7 +614,113 @@ pub fn create_proof(vanchor_proof_input: VAnchorProofPayload, rng: &mut OsRng) -
 			OpStatusCode::InvalidProofParameters,
 			message,
 		));
-	};
+	}
+	Ok(())
+}
+
+/// Checks that every non-empty input UTXO is the leaf at `indices[i]` in the
+/// tree of its chain, and that the root of that tree is one of `roots`. The
+/// root of a chain is computed once, however many inputs it holds
+fn ensure_inputs_in_tree(
+	in_utxos: &[JsUtxo],
+	indices: &[u64],
+	leaves: &BTreeMap<u64, Vec<Vec<u8>>>,
+	roots: &[Vec<u8>],
+	tree_depth: usize,
+) -> Result<(), OperationError> {
+	let mut chain_trees: BTreeMap<u64, (Vec<[u8; 32]>, Bn254Fr)> = BTreeMap::new();
+	for (input_index, (utxo, &leaf_index)) in in_utxos.iter().zip(indices).enumerate() {
+		if utxo.get_amount_raw() == 0 {
+			continue;
+		}
+		let chain_id = utxo.get_chain_id_raw();
+		let (chain_leaves, root) = match chain_trees.entry(chain_id) {
+			Entry::Occupied(tree) => tree.into_mut(),
+			Entry::Vacant(entry) => {
+				let chain_leaves: Vec<[u8; 32]> = leaves
+					.get(&chain_id)
+					.ok_or(OpStatusCode::InvalidLeaves)?
+					.iter()
+					.map(|leaf| {
+						Bn254Fr::from_be_bytes_mod_order(leaf)
+							.into_repr()
+							.to_bytes_be()
+							.try_into()
+							.expect("proof::vanchor: Bn254 elements are 32 bytes")
+					})
+					.collect();
+				let root = compute_root::<Bn254Fr>(5, 3, &chain_leaves, tree_depth)?;
+				entry.insert((chain_leaves, root))
+			}
+		};
+		let commitment = utxo.get_commitment();
+		if chain_leaves.get(leaf_index as usize).map(|leaf| &leaf[..]) != Some(&commitment[..]) {
+			let message = format!(
+				"proof::vanchor: Input {} isn't the leaf at index {} of chain {}",
+				input_index, leaf_index, chain_id
+			);
+			let mut error = OperationError::new_with_message(OpStatusCode::LeafNotInTree, message);
+			error.data = Some(format!(
+				"{{\"inputIndex\":{},\"leafIndex\":{}}}",
+				input_index, leaf_index
+			));
+			return Err(error);
+		}
+		if !roots.iter().any(|r| Bn254Fr::from_be_bytes_mod_order(r) == *root) {
+			let root = hex::encode(root.into_repr().to_bytes_be());
+			let message = format!(
+				"proof::vanchor: The tree of input {} has root 0x{}, which isn't one of the roots",
+				input_index, root
+			);
+			let mut error = OperationError::new_with_message(OpStatusCode::RootMismatch, message);
+			error.data = Some(format!(
+				"{{\"inputIndex\":{},\"chainId\":\"{}\",\"root\":\"0x{}\"}}",
+				input_index, chain_id, root
+			));
+			return Err(error);
+		}
+	}
+	Ok(())
+}
+
+pub fn create_proof<R: RngCore + CryptoRng>(
+	vanchor_proof_input: VAnchorProofPayload,
+	rng: &mut R,
+) -> Result<VAnchorProof, OperationError> {
+	let VAnchorProofPayload {
+		public_amount,
+		backend,
+		curve,
+		width,
+		input_utxos,
+		indices,
+		leaves,
+		exponentiation,
+		roots,
+		pk,
+		chain_id,
+		output_utxos,
+		ext_data_hash,
+		tree_depth,
+		..
+	} = vanchor_proof_input;
+	ensure_proving_backend(backend)?;
+	ensure_tree_depth(tree_depth)?;
+	let _span = tracing::info_span!(
+		"vanchor_proof",
+		inputs = input_utxos.len(),
+		anchors = roots.len(),
+		tree_depth
+	)
+	.entered();
+	let public_amount_bytes = Bn254Fr::from(public_amount)
+		.into_repr()
+		.to_bytes_be()
+		.try_into()
+		.expect("proof::vanchor: Failed to wrap public amount to bytes");
+	ensure_input_shape(&input_utxos, &indices, &roots)?;
+	ensure_inputs_in_tree(&input_utxos, &indices, &leaves, &roots, tree_depth)?;
+	let in_utxos = input_utxos;
 	// Initialize the output notes vec
 	let utxos_out = output_utxos
 		.iter()
@@ -293,63 +728,35 @@ pub fn create_proof(vanchor_proof_input: VAnchorProofPayload, rng: &mut OsRng) -
 		.collect::<Result<Vec<_>, OpStatusCode>>()?
 		.try_into()
 		.map_err(|_| OpStatusCode::InvalidProofParameters)?;
-	let proof = match (backend, curve, roots.len()) {
-		(Backend::Arkworks, Curve::Bn254, 2) => match (exponentiation, width, in_utxos.len()) {
-			(5, 5, 2) => {
-				let utxos_in: [Utxo<Bn254Fr>; 2] = [in_utxos[0].get_bn254_utxo()?, in_utxos[1].get_bn254_utxo()?];
-				let indices = indices.try_into().map_err(|_| OpStatusCode::InvalidIndices)?;
-				let roots = roots.try_into().map_err(|_| OpStatusCode::InvalidRoots)?;
-				VAnchorR1CSProverBn254_30_2_2_2::create_proof(
-					ArkCurve::Bn254,
+	let proof = match (backend, curve, exponentiation, width) {
+		(Backend::Arkworks, Curve::Bn254, 5, 5) => with_tree_depth!(tree_depth, DEPTH => {
+			with_anchor_count!(roots.len(), ANCHORS => match in_utxos.len() {
+				2 => prove::<DEPTH, ANCHORS, INS_2, R>(
 					chain_id,
 					public_amount,
 					ext_data_hash,
 					roots,
 					indices,
 					leaves,
-					utxos_in,
+					&in_utxos,
 					utxos_out,
 					pk,
-					DEFAULT_LEAF,
 					rng,
-				)
-			}
-			(5, 5, 16) => {
-				let in_utxos = in_utxos
-					.iter()
-					.map(|utxo| utxo.get_bn254_utxo())
-					.collect::<Result<Vec<_>, _>>()?;
-				let utxos_slice = in_utxos.try_into().map_err(|_| OpStatusCode::InvalidNoteSecrets)?;
-				let indices = indices.try_into().map_err(|_| OpStatusCode::InvalidIndices)?;
-				let roots = roots.try_into().map_err(|_| OpStatusCode::InvalidRoots)?;
-
-				VAnchorR1CSProverBn254_30_2_16_2::create_proof(
-					ArkCurve::Bn254,
+				),
+				_ => prove::<DEPTH, ANCHORS, INS_16, R>(
 					chain_id,
 					public_amount,
 					ext_data_hash,
 					roots,
 					indices,
 					leaves,
-					utxos_slice,
+					&in_utxos,
 					utxos_out,
 					pk,
-					DEFAULT_LEAF,
 					rng,
-				)
-			}
-			_ => {
-				let message = format!(
-						"proof::vanchor: The proofing setup for backend {} curve {} width {} exp {} input size {} isn't implemented!",
-						backend,
-						curve,
-						width,
-						exponentiation,
-						&in_utxos.len(),
-					);
-				Err(Error::from(message))
-			}
-		},
+				),
+			})
+		}),
 		_ => {
 			let message = format!(
 				"proof::vanchor: The proofing setup for backend {} curve {} width {} exp {} input size {} isn't implemented!",
@@ -359,13 +766,12 @@ pub fn create_proof(vanchor_proof_input: VAnchorProofPayload, rng: &mut OsRng) -
 				exponentiation,
 				&in_utxos.len(),
 			);
-			Err(Error::from(message))
+			Err(OperationError::new_with_message(
+				OpStatusCode::InvalidProofParameters,
+				message,
+			))
 		}
-	}
-	.map_err(|e| {
-		let message = format!("proof::vanchor:  {}", e);
-		OperationError::new_with_message(OpStatusCode::InvalidProofParameters, message)
-	})?;
+	}?;
 	Ok(VAnchorProof {
 		proof: proof.proof,
 		public_inputs: proof.public_inputs_raw,
@@ -374,3 +780,60 @@ pub fn create_proof(vanchor_proof_input: VAnchorProofPayload, rng: &mut OsRng) -
 		public_amount: public_amount_bytes,
 	})
 }
+
+/// Witness of the transaction, from the circuit `create_proof` proves
+pub fn create_witness(vanchor_proof_input: VAnchorProofPayload) -> Result<Witness, OperationError> {
+	let VAnchorProofPayload {
+		public_amount,
+		backend,
+		curve,
+		width,
+		input_utxos,
+		indices,
+		leaves,
+		exponentiation,
+		roots,
+		chain_id,
+		output_utxos,
+		ext_data_hash,
+		tree_depth,
+		..
+	} = vanchor_proof_input;
+	ensure_proving_backend(backend)?;
+	ensure_tree_depth(tree_depth)?;
+	ensure_input_shape(&input_utxos, &indices, &roots)?;
+	ensure_inputs_in_tree(&input_utxos, &indices, &leaves, &roots, tree_depth)?;
+	let utxos_out = output_utxos
+		.iter()
+		.map(|js_utx| js_utx.get_bn254_utxo())
+		.collect::<Result<Vec<_>, OpStatusCode>>()?
+		.try_into()
+		.map_err(|_| OpStatusCode::InvalidProofParameters)?;
+	match (backend, curve, exponentiation, width) {
+		(Backend::Arkworks, Curve::Bn254, 5, 5) => with_tree_depth!(tree_depth, DEPTH => {
+			with_anchor_count!(roots.len(), ANCHORS => match input_utxos.len() {
+				2 => witness::<DEPTH, ANCHORS, INS_2>(
+					chain_id,
+					public_amount,
+					ext_data_hash,
+					roots,
+					indices,
+					leaves,
+					&input_utxos,
+					utxos_out,
+				),
+				_ => witness::<DEPTH, ANCHORS, INS_16>(
+					chain_id,
+					public_amount,
+					ext_data_hash,
+					roots,
+					indices,
+					leaves,
+					&input_utxos,
+					utxos_out,
+				),
+			})
+		}),
+		_ => Err(OpStatusCode::UnsupportedParameterCombination.into()),
+	}
+}
@@ -0,0 +1,180 @@
+use ark_bls12_381::Bls12_381;
+use ark_bn254::Bn254;
+use ark_ec::short_weierstrass_jacobian::GroupAffine;
+use ark_ec::{PairingEngine, SWModelParameters};
+use ark_groth16::VerifyingKey;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use js_sys::{Array, JsString, Uint8Array};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::evm::keccak256;
+use crate::proof::groth16::{g1_words, g2_words, parse_curve};
+use crate::types::{Curve, OpStatusCode, OperationError, StringArray, WasmCurve};
+
+#[derive(Clone)]
+pub enum VerifyingKeyInner {
+	Bn254(Box<VerifyingKey<Bn254>>),
+	Bls381(Box<VerifyingKey<Bls12_381>>),
+}
+
+/// Groth16 verifying key, used to check a deployment's key before trusting
+/// proofs verified by it
+#[wasm_bindgen(js_name = VerifyingKey)]
+#[derive(Clone)]
+pub struct JsVerifyingKey {
+	#[wasm_bindgen(skip)]
+	pub inner: VerifyingKeyInner,
+}
+
+// Accept the compressed encoding as well as the uncompressed one `setupKeys`
+// outputs, the points of both are checked to be in their subgroup
+fn parse<E: PairingEngine>(vk: &[u8]) -> Result<VerifyingKey<E>, OperationError> {
+	let vk = VerifyingKey::<E>::deserialize(vk)
+		.or_else(|_| VerifyingKey::<E>::deserialize_uncompressed(vk))
+		.map_err(|e| OperationError::new_with_message(OpStatusCode::InvalidVerifyingKey, e.to_string()))?;
+	// One point for the constant term, then one per public input
+	if vk.gamma_abc_g1.is_empty() {
+		let message = "The verifying key has no `gamma_abc_g1` point".to_string();
+		return Err(OperationError::new_with_message(
+			OpStatusCode::InvalidVerifyingKey,
+			message,
+		));
+	}
+	Ok(vk)
+}
+
+// The uncompressed encoding's subgroup check assumes the point is on the
+// curve, which it doesn't check
+fn ensure_on_curve<G1: SWModelParameters, G2: SWModelParameters>(
+	g1: &[&GroupAffine<G1>],
+	g2: &[&GroupAffine<G2>],
+) -> Result<(), OperationError> {
+	if g1.iter().all(|point| point.is_on_curve()) && g2.iter().all(|point| point.is_on_curve()) {
+		return Ok(());
+	}
+	let message = "A point of the verifying key isn't on the curve".to_string();
+	Err(OperationError::new_with_message(
+		OpStatusCode::InvalidVerifyingKey,
+		message,
+	))
+}
+
+fn parse_checked<G1, G2, E>(vk: &[u8]) -> Result<VerifyingKey<E>, OperationError>
+where
+	G1: SWModelParameters,
+	G2: SWModelParameters,
+	E: PairingEngine<G1Affine = GroupAffine<G1>, G2Affine = GroupAffine<G2>>,
+{
+	let vk = parse::<E>(vk)?;
+	let g1: Vec<_> = std::iter::once(&vk.alpha_g1).chain(&vk.gamma_abc_g1).collect();
+	ensure_on_curve(&g1, &[&vk.beta_g2, &vk.gamma_g2, &vk.delta_g2])?;
+	Ok(vk)
+}
+
+impl JsVerifyingKey {
+	pub fn new(curve: Curve, vk: &[u8]) -> Result<Self, OperationError> {
+		let inner = match curve {
+			Curve::Bn254 => VerifyingKeyInner::Bn254(Box::new(parse_checked(vk)?)),
+			Curve::Bls381 => VerifyingKeyInner::Bls381(Box::new(parse_checked(vk)?)),
+		};
+		Ok(Self { inner })
+	}
+
+	pub fn curve(&self) -> Curve {
+		match self.inner {
+			VerifyingKeyInner::Bn254(_) => Curve::Bn254,
+			VerifyingKeyInner::Bls381(_) => Curve::Bls381,
+		}
+	}
+
+	pub fn public_input_count(&self) -> usize {
+		let gamma_abc_len = match &self.inner {
+			VerifyingKeyInner::Bn254(vk) => vk.gamma_abc_g1.len(),
+			VerifyingKeyInner::Bls381(vk) => vk.gamma_abc_g1.len(),
+		};
+		gamma_abc_len - 1
+	}
+
+	/// Compressed, checked arkworks encoding, as stored by the Substrate
+	/// verifier pallets
+	pub fn to_substrate(&self) -> Vec<u8> {
+		let mut bytes = Vec::new();
+		match &self.inner {
+			VerifyingKeyInner::Bn254(vk) => vk.serialize(&mut bytes),
+			VerifyingKeyInner::Bls381(vk) => vk.serialize(&mut bytes),
+		}
+		.expect("serializing to a vec can't fail");
+		bytes
+	}
+
+	/// `keccak256` of the Substrate encoding, which doesn't depend on the
+	/// encoding the key was parsed from
+	pub fn fingerprint(&self) -> [u8; 32] {
+		keccak256(&self.to_substrate())
+	}
+
+	/// The `uint256` words taken by the Solidity verifier constructor:
+	/// `alpha1, beta2, gamma2, delta2, IC[0..=n]`, G2 points with their
+	/// imaginary parts first. Only Bn254 keys can be verified on the EVM.
+	pub fn to_solidity(&self) -> Result<Vec<[u8; 32]>, OperationError> {
+		let vk = match &self.inner {
+			VerifyingKeyInner::Bn254(vk) => vk,
+			VerifyingKeyInner::Bls381(_) => {
+				let message = "Solidity verifiers only support Bn254".to_string();
+				return Err(OperationError::new_with_message(OpStatusCode::InvalidCurve, message));
+			}
+		};
+
+		let mut words = g1_words(&vk.alpha_g1);
+		words.extend(g2_words(&vk.beta_g2));
+		words.extend(g2_words(&vk.gamma_g2));
+		words.extend(g2_words(&vk.delta_g2));
+		for point in &vk.gamma_abc_g1 {
+			words.extend(g1_words(point));
+		}
+		Ok(words)
+	}
+}
+
+#[allow(clippy::unused_unit)]
+#[wasm_bindgen(js_class = VerifyingKey)]
+impl JsVerifyingKey {
+	/// Parse arkworks verifying key bytes, Bn254 unless `curve` is set
+	#[wasm_bindgen(constructor)]
+	pub fn js_new(vk: Uint8Array, curve: Option<WasmCurve>) -> Result<JsVerifyingKey, JsValue> {
+		Ok(JsVerifyingKey::new(parse_curve(curve)?, &vk.to_vec())?)
+	}
+
+	#[wasm_bindgen(getter, js_name = curve)]
+	pub fn js_curve(&self) -> WasmCurve {
+		self.curve().into()
+	}
+
+	#[wasm_bindgen(getter, js_name = publicInputCount)]
+	pub fn js_public_input_count(&self) -> usize {
+		self.public_input_count()
+	}
+
+	/// Hex `keccak256` fingerprint of the key
+	#[wasm_bindgen(js_name = fingerprint)]
+	pub fn js_fingerprint(&self) -> JsString {
+		hex::encode(self.fingerprint()).into()
+	}
+
+	#[wasm_bindgen(js_name = toSubstrate)]
+	pub fn js_to_substrate(&self) -> Uint8Array {
+		Uint8Array::from(self.to_substrate().as_slice())
+	}
+
+	/// `0x` prefixed `uint256` words for the Solidity verifier constructor
+	#[wasm_bindgen(js_name = toSolidity)]
+	pub fn js_to_solidity(&self) -> Result<StringArray, JsValue> {
+		let words = self.to_solidity()?;
+		let words: Array = words
+			.iter()
+			.map(|word| JsValue::from(format!("0x{}", hex::encode(word))))
+			.collect();
+		Ok(words.unchecked_into())
+	}
+}
@@ -0,0 +1,143 @@
+//! Witness generation without proving, for external provers (a GPU prover
+//! service, rapidsnark, ...). The witness is computed in the browser from the
+//! same proof input as `generate_proof_js`, and only the assignment leaves
+//! it, the proving key isn't needed.
+//!
+//! The assignment is in arkworks' variable order: the constant one, the
+//! public inputs, then the private witness. It is serialized in the iden3
+//! `.wtns` format, field elements 32 bytes little endian.
+use ark_ff::{BigInteger, FpParameters, PrimeField};
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem};
+use js_sys::{Array, JsString, Uint8Array};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::proof::{mixer, vanchor, JsProofInput, ProofInput};
+use crate::types::{OpStatusCode, OperationError, StringArray};
+
+pub(crate) const WTNS_MAGIC: &[u8; 4] = b"wtns";
+const WTNS_VERSION: u32 = 2;
+pub(crate) const WTNS_HEADER_SECTION: u32 = 1;
+pub(crate) const WTNS_DATA_SECTION: u32 = 2;
+
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Witness {
+	/// Modulus of the scalar field, little endian
+	#[wasm_bindgen(skip)]
+	pub modulus: Vec<u8>,
+	/// Number of instance variables, the constant one included
+	#[wasm_bindgen(skip)]
+	pub num_instance: usize,
+	/// Every variable, little endian
+	#[wasm_bindgen(skip)]
+	pub assignment: Vec<Vec<u8>>,
+	#[wasm_bindgen(skip)]
+	pub num_constraints: usize,
+}
+
+impl Witness {
+	/// Synthesize `circuit` and keep its assignment. Fails if the constraints
+	/// aren't satisfied, the proof would be rejected anyway
+	pub fn synthesize<F: PrimeField, C: ConstraintSynthesizer<F>>(circuit: C) -> Result<Self, OperationError> {
+		let synthesis_error = |e: ark_relations::r1cs::SynthesisError| {
+			OperationError::new_with_message(OpStatusCode::WitnessGenerationFailed, e.to_string())
+		};
+		let cs = ConstraintSystem::<F>::new_ref();
+		circuit.generate_constraints(cs.clone()).map_err(synthesis_error)?;
+		if let Some(constraint) = cs.which_is_unsatisfied().map_err(synthesis_error)? {
+			let message = format!("The input doesn't satisfy constraint {}", constraint);
+			let mut error = OperationError::new_with_message(OpStatusCode::WitnessGenerationFailed, message);
+			error.data = Some(format!("{{\"constraint\":{:?}}}", constraint));
+			return Err(error);
+		}
+		let cs = cs.borrow().ok_or(OpStatusCode::WitnessGenerationFailed)?;
+		let to_bytes = |value: &F| value.into_repr().to_bytes_le();
+		Ok(Witness {
+			modulus: F::Params::MODULUS.to_bytes_le(),
+			num_instance: cs.instance_assignment.len(),
+			assignment: cs
+				.instance_assignment
+				.iter()
+				.chain(cs.witness_assignment.iter())
+				.map(to_bytes)
+				.collect(),
+			num_constraints: cs.num_constraints,
+		})
+	}
+
+	/// Public inputs in circuit order, big endian like the proofs'
+	pub fn public_inputs(&self) -> Vec<Vec<u8>> {
+		self.assignment[1..self.num_instance]
+			.iter()
+			.map(|value| value.iter().rev().copied().collect())
+			.collect()
+	}
+
+	/// The assignment in the `.wtns` format
+	pub fn to_wtns(&self) -> Vec<u8> {
+		let n8 = self.modulus.len();
+		let mut header = Vec::with_capacity(8 + n8);
+		header.extend((n8 as u32).to_le_bytes());
+		header.extend(&self.modulus);
+		header.extend((self.assignment.len() as u32).to_le_bytes());
+		let data = self.assignment.concat();
+
+		let mut bytes = Vec::with_capacity(12 + 2 * 12 + header.len() + data.len());
+		bytes.extend(WTNS_MAGIC);
+		bytes.extend(WTNS_VERSION.to_le_bytes());
+		bytes.extend(2u32.to_le_bytes());
+		for (section, content) in [(WTNS_HEADER_SECTION, header), (WTNS_DATA_SECTION, data)] {
+			bytes.extend(section.to_le_bytes());
+			bytes.extend((content.len() as u64).to_le_bytes());
+			bytes.extend(content);
+		}
+		bytes
+	}
+}
+
+/// Witness of `input`, the same checks as proving apply
+pub fn create_witness(input: ProofInput) -> Result<Witness, OperationError> {
+	match input {
+		ProofInput::Mixer(input) => mixer::create_witness(*input),
+		ProofInput::VAnchor(input) => vanchor::create_witness(*input),
+	}
+}
+
+#[wasm_bindgen]
+impl Witness {
+	#[wasm_bindgen(getter)]
+	#[wasm_bindgen(js_name = publicInputs)]
+	pub fn js_public_inputs(&self) -> StringArray {
+		let inputs: Array = self
+			.public_inputs()
+			.iter()
+			.map(|x| JsString::from(hex::encode(x)))
+			.collect();
+		inputs.unchecked_into()
+	}
+
+	/// Number of variables, the constant one included
+	#[wasm_bindgen(getter)]
+	pub fn length(&self) -> usize {
+		self.assignment.len()
+	}
+
+	/// Number of constraints of the circuit
+	#[wasm_bindgen(getter, js_name = numConstraints)]
+	pub fn js_num_constraints(&self) -> usize {
+		self.num_constraints
+	}
+
+	/// The assignment in the iden3 `.wtns` format
+	pub fn serialize(&self) -> Uint8Array {
+		Uint8Array::from(self.to_wtns().as_slice())
+	}
+}
+
+/// Compute the witness of `proof_input` without proving, to hand over to an
+/// external prover
+#[wasm_bindgen]
+pub fn generate_witness(proof_input: JsProofInput) -> Result<Witness, JsValue> {
+	Ok(create_witness(proof_input.inner)?)
+}
@@ -0,0 +1,228 @@
+use crypto_box::{SecretKey, KEY_SIZE};
+use js_sys::{Array, Uint8Array};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::crypto::{decrypt_utxo_with, keypair_secret, NONCE_LENGTH, UTXO_ENVELOPE_LENGTH};
+use crate::keypair::JsKeypair;
+use crate::types::{JsUtxoArray, OpStatusCode, OperationError};
+use crate::utxo::JsUtxo;
+
+/// Size of an encrypted VAnchor output, including the poly1305 tag
+pub const ENCRYPTED_OUTPUT_LENGTH: usize = NONCE_LENGTH + KEY_SIZE + UTXO_ENVELOPE_LENGTH + 16;
+/// Size of the commitment of a leaf
+pub const COMMITMENT_LENGTH: usize = 32;
+
+/// Trial-decrypts encrypted outputs from chain events with a viewing key to
+/// recover the UTXOs sent to it
+#[wasm_bindgen(js_name = NoteScanner)]
+pub struct NoteScanner {
+	secret_key: Vec<u8>,
+	encryption_secret: SecretKey,
+	scanned: u64,
+}
+
+impl NoteScanner {
+	pub fn new(keypair: &JsKeypair) -> Result<Self, OperationError> {
+		let (secret_key, encryption_secret) = keypair_secret(keypair)?;
+		Ok(Self {
+			secret_key,
+			encryption_secret,
+			scanned: 0,
+		})
+	}
+
+	/// Scan `(leaf index, on-chain commitment, encrypted output)` triples,
+	/// returning the UTXOs that decrypt with the viewing key with their leaf
+	/// index set. Outputs for other keys are skipped, and so are outputs
+	/// whose UTXO isn't the commitment of the leaf: anyone can post an
+	/// output that decrypts to an amount that was never deposited.
+	pub fn scan<'a, I>(&mut self, outputs: I) -> Vec<JsUtxo>
+	where
+		I: IntoIterator<Item = (u64, &'a [u8], &'a [u8])>,
+	{
+		let mut found = Vec::new();
+		for (index, commitment, encrypted) in outputs {
+			self.scanned += 1;
+			if let Ok(mut utxo) = decrypt_utxo_with(encrypted, &self.secret_key, &self.encryption_secret) {
+				if utxo.get_commitment() != commitment {
+					continue;
+				}
+				utxo.set_index(index);
+				found.push(utxo);
+			}
+		}
+		found
+	}
+
+	/// Scan outputs packed back to back, the first one being the leaf at
+	/// `start_index`, with their 32 bytes commitments packed the same way
+	pub fn scan_packed(
+		&mut self,
+		packed: &[u8],
+		commitments: &[u8],
+		start_index: u64,
+	) -> Result<Vec<JsUtxo>, OperationError> {
+		if packed.len() % ENCRYPTED_OUTPUT_LENGTH != 0
+			|| commitments.len() != packed.len() / ENCRYPTED_OUTPUT_LENGTH * COMMITMENT_LENGTH
+		{
+			let message = format!(
+				"Packed outputs should be a multiple of {} bytes, with {} bytes of commitment per output",
+				ENCRYPTED_OUTPUT_LENGTH, COMMITMENT_LENGTH
+			);
+			return Err(OperationError::new_with_message(
+				OpStatusCode::InvalidArrayLength,
+				message,
+			));
+		}
+		let outputs = packed
+			.chunks(ENCRYPTED_OUTPUT_LENGTH)
+			.zip(commitments.chunks(COMMITMENT_LENGTH))
+			.enumerate()
+			.map(|(i, (output, commitment))| (start_index + i as u64, commitment, output));
+		Ok(self.scan(outputs))
+	}
+}
+
+#[allow(clippy::unused_unit)]
+#[wasm_bindgen(js_class = NoteScanner)]
+impl NoteScanner {
+	#[wasm_bindgen(constructor)]
+	pub fn js_new(keypair: &JsKeypair) -> Result<NoteScanner, JsValue> {
+		Ok(NoteScanner::new(keypair)?)
+	}
+
+	/// Scan an array of encrypted outputs and the array of their leaves'
+	/// commitments, the first one being the leaf at `startIndex`. Returns the
+	/// recovered `JsUtxo`s.
+	#[wasm_bindgen(js_name = scan)]
+	pub fn js_scan(&mut self, outputs: Array, commitments: Array, start_index: u64) -> Result<JsUtxoArray, JsValue> {
+		if outputs.length() != commitments.length() {
+			let message = format!(
+				"{} encrypted outputs for {} commitments",
+				outputs.length(),
+				commitments.length()
+			);
+			return Err(OperationError::new_with_message(OpStatusCode::InvalidArrayLength, message).into());
+		}
+		let to_bytes =
+			|array: Array| -> Vec<Vec<u8>> { array.iter().map(|item| Uint8Array::new(&item).to_vec()).collect() };
+		let outputs = to_bytes(outputs);
+		let commitments = to_bytes(commitments);
+		let outputs = outputs
+			.iter()
+			.zip(&commitments)
+			.enumerate()
+			.map(|(i, (output, commitment))| (start_index + i as u64, commitment.as_slice(), output.as_slice()));
+		let found: Array = self.scan(outputs).into_iter().map(JsValue::from).collect();
+		Ok(found.unchecked_into())
+	}
+
+	/// Scan outputs and commitments concatenated into single buffers, which
+	/// avoids crossing the JS boundary once per output
+	#[wasm_bindgen(js_name = scanPacked)]
+	pub fn js_scan_packed(
+		&mut self,
+		packed: Uint8Array,
+		commitments: Uint8Array,
+		start_index: u64,
+	) -> Result<JsUtxoArray, JsValue> {
+		let found = self.scan_packed(&packed.to_vec(), &commitments.to_vec(), start_index)?;
+		let found: Array = found.into_iter().map(JsValue::from).collect();
+		Ok(found.unchecked_into())
+	}
+
+	/// Number of outputs scanned so far
+	#[wasm_bindgen(getter)]
+	pub fn scanned(&self) -> u64 {
+		self.scanned
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use rand::rngs::OsRng;
+	use wasm_bindgen_test::*;
+
+	use super::*;
+	use crate::crypto::encrypt_utxo;
+	use crate::types::{Backend, Curve};
+
+	fn encrypted_output(keypair: &JsKeypair, amount: u128) -> (JsUtxo, Vec<u8>) {
+		let utxo = JsUtxo::new(
+			Curve::Bn254,
+			Backend::Arkworks,
+			amount,
+			1,
+			None,
+			None,
+			keypair.secret_key(),
+			None,
+		)
+		.unwrap();
+		let encrypted = encrypt_utxo(&utxo, &keypair.encryption_key(), &mut OsRng).unwrap();
+		(utxo, encrypted)
+	}
+
+	#[wasm_bindgen_test]
+	fn should_recover_own_outputs_with_indices() {
+		let wallet = JsKeypair::new(&mut OsRng);
+		let stranger = JsKeypair::new(&mut OsRng);
+
+		let (first, first_output) = encrypted_output(&wallet, 5);
+		let (stranger_utxo, stranger_output) = encrypted_output(&stranger, 7);
+		let (second, second_output) = encrypted_output(&wallet, 9);
+		let packed = [first_output, stranger_output, second_output].concat();
+		assert_eq!(packed.len(), 3 * ENCRYPTED_OUTPUT_LENGTH);
+		let commitments = [
+			first.get_commitment(),
+			stranger_utxo.get_commitment(),
+			second.get_commitment(),
+		]
+		.concat();
+
+		let mut scanner = NoteScanner::new(&wallet).unwrap();
+		let found = scanner.scan_packed(&packed, &commitments, 10).unwrap();
+		assert_eq!(scanner.scanned(), 3);
+		assert_eq!(found.len(), 2);
+		assert_eq!(found[0].get_index(), Some(10));
+		assert_eq!(found[0].get_commitment(), first.get_commitment());
+		assert_eq!(found[1].get_index(), Some(12));
+		assert_eq!(found[1].get_amount_raw(), second.get_amount_raw());
+
+		assert_eq!(
+			scanner.scan_packed(&packed[1..], &commitments, 0).err().unwrap().code,
+			OpStatusCode::InvalidArrayLength
+		);
+		assert_eq!(
+			scanner.scan_packed(&packed, &commitments[32..], 0).err().unwrap().code,
+			OpStatusCode::InvalidArrayLength
+		);
+	}
+
+	#[wasm_bindgen_test]
+	fn should_skip_outputs_that_are_not_the_leaf_commitment() {
+		let wallet = JsKeypair::new(&mut OsRng);
+		let (deposited, _) = encrypted_output(&wallet, 5);
+		// An output anyone can encrypt to the wallet, claiming a larger amount
+		// under the leaf of the deposit
+		let forged = JsUtxo::new(
+			Curve::Bn254,
+			Backend::Arkworks,
+			5_000_000,
+			1,
+			Some(deposited.get_blinding()),
+			None,
+			wallet.secret_key(),
+			None,
+		)
+		.unwrap();
+		let forged_output = encrypt_utxo(&forged, &wallet.encryption_key(), &mut OsRng).unwrap();
+
+		let mut scanner = NoteScanner::new(&wallet).unwrap();
+		let commitment = deposited.get_commitment();
+		let found = scanner.scan([(0, commitment.as_slice(), forged_output.as_slice())]);
+		assert_eq!(scanner.scanned(), 1);
+		assert!(found.is_empty());
+	}
+}
@@ -0,0 +1,246 @@
+//! Arguments of the Substrate pallet deposit calls, SCALE encoded here so the
+//! polkadot-js side only wraps and signs them. Both `mixer.deposit` and
+//! `anchor.deposit` take `(tree_id: u32, leaf: Element)`, they differ in the
+//! leaf the note secrets hash to.
+use core::convert::TryInto;
+use core::fmt;
+use core::str::FromStr;
+
+use js_sys::{JsString, Uint8Array};
+use parity_scale_codec::{Decode, Encode};
+use wasm_bindgen::prelude::*;
+
+use crate::hash::poseidon_hash_raw;
+use crate::note::JsNote;
+use crate::types::{HashFunction, NoteProtocol, OpStatusCode, OperationError, TypedChainId};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DepositPallet {
+	Mixer,
+	Anchor,
+}
+
+impl fmt::Display for DepositPallet {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			DepositPallet::Mixer => write!(f, "mixer"),
+			DepositPallet::Anchor => write!(f, "anchor"),
+		}
+	}
+}
+
+impl FromStr for DepositPallet {
+	type Err = OperationError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"mixer" => Ok(DepositPallet::Mixer),
+			"anchor" => Ok(DepositPallet::Anchor),
+			_ => {
+				let message = format!("No deposit call for pallet {}", s);
+				Err(OperationError::new_with_message(
+					OpStatusCode::InvalidNoteProtocol,
+					message,
+				))
+			}
+		}
+	}
+}
+
+/// Arguments of `deposit(tree_id, leaf)`, in call order
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DepositArgs {
+	pub tree_id: u32,
+	pub leaf: [u8; 32],
+}
+
+fn secret_bytes(secret: &[u8]) -> Result<[u8; 32], OperationError> {
+	secret.try_into().map_err(|_| {
+		let message = format!("Expected a 32 bytes note secret, got {} bytes", secret.len());
+		OperationError::new_with_message(OpStatusCode::InvalidNoteSecrets, message)
+	})
+}
+
+/// Anchor leaf of a mixer note, `poseidon(target_chain_id, nullifier, secret)`
+/// with the width 4 hasher, as in `AnchorProver::create_leaf_with_privates`
+fn anchor_leaf(note: &JsNote) -> Result<[u8; 32], OperationError> {
+	let params = note.leaf_params()?;
+	if params.hash_function != HashFunction::Poseidon {
+		let message = format!("Anchor leaves are hashed with Poseidon, not {}", params.hash_function);
+		return Err(OperationError::new_with_message(
+			OpStatusCode::InvalidHasFunction,
+			message,
+		));
+	}
+	let chain_id: TypedChainId = note.target_chain_id.parse()?;
+	let mut chain_id_bytes = [0u8; 32];
+	chain_id_bytes[24..].copy_from_slice(&chain_id.to_u64().to_be_bytes());
+	let secret = secret_bytes(&note.secrets[0])?;
+	let nullifier = secret_bytes(&note.secrets[1])?;
+	poseidon_hash_raw(params.curve, params.exponentiation, 4, &[
+		chain_id_bytes,
+		nullifier,
+		secret,
+	])
+}
+
+/// Deposit arguments of a mixer note. The tree id defaults to the note's
+/// target identifying data, which Substrate notes set to the tree id.
+pub fn deposit_args(note: &JsNote, pallet: DepositPallet, tree_id: Option<u32>) -> Result<DepositArgs, OperationError> {
+	if note.protocol != NoteProtocol::Mixer {
+		let message = format!("{} notes can't be deposited with {}.deposit", note.protocol, pallet);
+		return Err(OperationError::new_with_message(
+			OpStatusCode::InvalidNoteProtocol,
+			message,
+		));
+	}
+	let tree_id = match tree_id {
+		Some(tree_id) => tree_id,
+		None => note.target_identifying_data.parse().map_err(|_| {
+			let message = format!(
+				"The target identifying data {:?} isn't a tree id",
+				note.target_identifying_data
+			);
+			OperationError::new_with_message(OpStatusCode::InvalidTargetIdentifyingData, message)
+		})?,
+	};
+	let leaf = match pallet {
+		DepositPallet::Mixer => {
+			let leaf = note.get_leaf_and_nullifier()?.mixer_leaf()?.leaf_bytes;
+			secret_bytes(&leaf)?
+		}
+		DepositPallet::Anchor => anchor_leaf(note)?,
+	};
+	Ok(DepositArgs { tree_id, leaf })
+}
+
+#[wasm_bindgen(typescript_custom_section)]
+const DEPOSIT_PALLET: &str = "export type DepositPallet = 'mixer' | 'anchor';";
+
+#[wasm_bindgen]
+extern "C" {
+	#[wasm_bindgen(typescript_type = "DepositPallet")]
+	pub type WasmDepositPallet;
+}
+
+/// A `deposit` call of the mixer or anchor pallet, built from a note
+#[wasm_bindgen]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DepositCall {
+	#[wasm_bindgen(skip)]
+	pub pallet: DepositPallet,
+	#[wasm_bindgen(skip)]
+	pub args: DepositArgs,
+}
+
+impl DepositCall {
+	/// Encoded `Call`: the pallet and call indices of the runtime followed by
+	/// the arguments
+	pub fn encode_call(&self, pallet_index: u8, call_index: u8) -> Vec<u8> {
+		let mut call = vec![pallet_index, call_index];
+		self.args.encode_to(&mut call);
+		call
+	}
+}
+
+#[allow(clippy::unused_unit)]
+#[wasm_bindgen]
+impl DepositCall {
+	/// Deposit call of a mixer note for `pallet`, `mixer` unless set. The tree
+	/// id defaults to the note's target identifying data.
+	#[wasm_bindgen(constructor)]
+	pub fn new(note: &JsNote, pallet: Option<WasmDepositPallet>, tree_id: Option<u32>) -> Result<DepositCall, JsValue> {
+		let pallet = match pallet {
+			Some(pallet) => JsValue::from(pallet)
+				.as_string()
+				.ok_or(OpStatusCode::InvalidNoteProtocol)?
+				.parse()?,
+			None => DepositPallet::Mixer,
+		};
+		let args = deposit_args(note, pallet, tree_id)?;
+		Ok(DepositCall { pallet, args })
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn pallet(&self) -> WasmDepositPallet {
+		JsValue::from(self.pallet.to_string()).into()
+	}
+
+	#[wasm_bindgen(getter, js_name = treeId)]
+	pub fn tree_id(&self) -> u32 {
+		self.args.tree_id
+	}
+
+	/// `0x` prefixed hex leaf, as taken by `api.tx.<pallet>.deposit`
+	#[wasm_bindgen(getter)]
+	pub fn leaf(&self) -> JsString {
+		format!("0x{}", hex::encode(self.args.leaf)).into()
+	}
+
+	/// SCALE encoded `(tree_id, leaf)`
+	#[wasm_bindgen(getter)]
+	pub fn args(&self) -> Uint8Array {
+		Uint8Array::from(self.args.encode().as_slice())
+	}
+
+	/// SCALE encoded call for `api.tx(call)`, the indices are the position of
+	/// the pallet in the runtime and of `deposit` in the pallet
+	#[wasm_bindgen(js_name = encodeCall)]
+	pub fn js_encode_call(&self, pallet_index: u8, call_index: u8) -> Uint8Array {
+		Uint8Array::from(self.encode_call(pallet_index, call_index).as_slice())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use ark_bn254::Bn254;
+	use arkworks_setups::r1cs::anchor::AnchorR1CSProver;
+	use arkworks_setups::{AnchorProver, Curve as ArkCurve};
+	use wasm_bindgen_test::*;
+
+	use super::*;
+	use crate::{ANCHOR_COUNT, TREE_HEIGHT};
+
+	const MIXER_NOTE: &str = "webb://v1:mixer/2:2/2:7/fd717cfe463b3ffec71ee6b7606bbd0179170510abf41c9f16c1d20ca9923f0e:18b6b080e6a43262f00f6fb3da0d2409c4871b8f26d89d5c8836358e1af5a41c/?curve=Bn254&width=3&exp=5&hf=Poseidon&backend=Arkworks&token=EDG&denom=18&amount=10";
+
+	#[wasm_bindgen_test]
+	fn should_encode_deposit_calls() {
+		let note = JsNote::deserialize(MIXER_NOTE).unwrap();
+		let call = DepositCall::new(&note, None, None).unwrap();
+		let leaf = note.get_leaf_and_nullifier().unwrap().mixer_leaf().unwrap().leaf_bytes;
+		assert_eq!(call.args.tree_id, 7);
+		assert_eq!(call.args.leaf.to_vec(), leaf);
+
+		let mut args = 7u32.to_le_bytes().to_vec();
+		args.extend_from_slice(&leaf);
+		assert_eq!(call.args().to_vec(), args);
+		assert_eq!(DepositArgs::decode(&mut args.as_slice()).unwrap(), call.args);
+		assert_eq!(call.encode_call(40, 0), [vec![40, 0], args].concat());
+
+		let call = DepositCall::new(&note, Some(JsValue::from("mixer").into()), Some(3)).unwrap();
+		assert_eq!(call.tree_id(), 3);
+	}
+
+	#[wasm_bindgen_test]
+	fn should_hash_anchor_leaves_with_the_target_chain() {
+		let note = JsNote::deserialize(MIXER_NOTE).unwrap();
+		let args = deposit_args(&note, DepositPallet::Anchor, None).unwrap();
+		let expected = AnchorR1CSProver::<Bn254, TREE_HEIGHT, ANCHOR_COUNT>::create_leaf_with_privates(
+			ArkCurve::Bn254,
+			2,
+			note.secrets[0].clone(),
+			note.secrets[1].clone(),
+		)
+		.unwrap();
+		assert_eq!(args.leaf.to_vec(), expected.leaf_bytes);
+		assert_ne!(args, deposit_args(&note, DepositPallet::Mixer, None).unwrap());
+
+		let vanchor_note = JsNote {
+			protocol: NoteProtocol::VAnchor,
+			..note
+		};
+		let error = deposit_args(&vanchor_note, DepositPallet::Mixer, None).err().unwrap();
+		assert_eq!(error.code, OpStatusCode::InvalidNoteProtocol);
+		assert!("vanchor".parse::<DepositPallet>().is_err());
+	}
+}
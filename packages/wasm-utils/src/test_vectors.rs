@@ -0,0 +1,465 @@
+//! Canonical JSON fixtures for the Solidity and Substrate implementations of
+//! the protocol, built with the `testvectors` feature. Every secret, key and
+//! proof comes from one seed, so the same seed gives the same file byte for
+//! byte on any machine and every repository can check itself against it.
+//! The fixtures are Bn254 mixer notes, the curve both verifiers use, a
+//! withdrawal of the first one from the Substrate mixer, and a withdrawal of
+//! the same secrets from an EVM anchor. The keys come from a seeded setup,
+//! nothing here should be used for real deposits.
+use ark_bn254::{Bn254, Fr as Bn254Fr};
+use ark_ff::{BigInteger, PrimeField};
+use arkworks_setups::common::setup_keys_unchecked;
+use arkworks_setups::r1cs::anchor::AnchorR1CSProver;
+use arkworks_setups::r1cs::mixer::MixerR1CSProver;
+use arkworks_setups::Curve as ArkCurve;
+use js_sys::{JsString, Uint8Array};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::merkle::compute_root;
+use crate::note::metadata::NoteMetadata;
+use crate::note::{mixer, JsNote};
+use crate::proof::anchor::{self, anchor_leaf, AnchorProofInput};
+use crate::proof::mixer::{create_proof, MixerProofInput};
+use crate::proof::{ensure_tree_depth, ProverRng};
+use crate::types::{
+	AnchorMode, Backend, ChainType, Curve, HashFunction, NoteProtocol, NoteVersion, OpStatusCode, OperationError,
+	TypedChainId,
+};
+use crate::{ANCHOR_COUNT, DEFAULT_LEAF, TREE_HEIGHT};
+
+/// Version of the fixture layout, bumped when a field changes meaning
+pub const TEST_VECTORS_VERSION: u32 = 2;
+
+/// Exponentiation and width of the mixer leaf hash
+const MIXER_PARAMS: (i8, usize) = (5, 3);
+/// Exponentiation and width of the merkle tree hash
+const TREE_PARAMS: (i8, u8) = (5, 3);
+/// Fee and refund of the withdrawals, in base units
+const FEE: u128 = 10_000_000_000_000_000;
+const REFUND: u128 = 5_000_000_000_000_000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct NoteVector {
+	/// Serialized note, without a checksum
+	pub note: String,
+	pub secret: String,
+	pub nullifier: String,
+	pub leaf: String,
+	pub nullifier_hash: String,
+}
+
+/// Tree of all the note leaves, in order
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TreeVector {
+	pub depth: usize,
+	pub leaves: Vec<String>,
+	pub root: String,
+}
+
+/// Withdrawal of the first note from the Substrate mixer, to 32 bytes
+/// accounts
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ProofVector {
+	pub leaf_index: u64,
+	pub recipient: String,
+	pub relayer: String,
+	pub fee: String,
+	pub refund: String,
+	/// Nullifier hash, root and arbitrary input
+	pub public_inputs: Vec<String>,
+	pub proof: String,
+}
+
+/// Withdrawal from a `FixedDepositAnchor` of protocol-solidity, proven with
+/// the anchor circuit. The secrets of every note are deposited on the chain
+/// of the notes, whose typed chain id the leaves commit to, and the first
+/// deposit is withdrawn to 20 bytes addresses
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AnchorVector {
+	pub chain_id: String,
+	pub leaves: Vec<String>,
+	/// Root of the leaves, then the root of the empty linked anchor
+	pub roots: Vec<String>,
+	pub leaf_index: u64,
+	pub recipient: String,
+	pub relayer: String,
+	pub refresh_commitment: String,
+	pub fee: String,
+	pub refund: String,
+	/// Nullifier hash, external data hash, chain id and roots
+	pub public_inputs: Vec<String>,
+	pub proof: String,
+	/// Uncompressed verifying key of the anchor circuit
+	pub vk: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TestVectors {
+	pub version: u32,
+	pub seed: String,
+	pub curve: String,
+	pub notes: Vec<NoteVector>,
+	pub tree: TreeVector,
+	/// Uncompressed verifying key of the mixer proof
+	pub vk: String,
+	pub proof: ProofVector,
+	pub anchor: AnchorVector,
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+	format!("0x{}", hex::encode(bytes))
+}
+
+fn from_hex(value: &str) -> Vec<u8> {
+	hex::decode(value.trim_start_matches("0x")).unwrap_or_default()
+}
+
+/// `count` mixer notes on Bn254, drawing their secrets from `rng`. The notes
+/// target the same EVM chain and are numbered by their index
+pub fn note_vectors(rng: &mut ChaCha20Rng, count: u32) -> Result<Vec<NoteVector>, OperationError> {
+	let (exponentiation, width) = MIXER_PARAMS;
+	let chain_id = notes_chain_id().to_string();
+	let mut notes = Vec::with_capacity(count as usize);
+	for index in 0..count {
+		let secrets = mixer::generate_secrets(exponentiation, width, Curve::Bn254, rng)?;
+		let leaf = mixer::get_leaf_with_private_raw(
+			Curve::Bn254,
+			width,
+			exponentiation,
+			HashFunction::Poseidon,
+			&secrets.concat(),
+		)?;
+		let note = JsNote {
+			scheme: "webb://".to_string(),
+			namespace: None,
+			protocol: NoteProtocol::Mixer,
+			version: NoteVersion::V1,
+			source_chain_id: chain_id.clone(),
+			target_chain_id: chain_id.clone(),
+			source_identifying_data: String::new(),
+			target_identifying_data: String::new(),
+			secrets: secrets.to_vec(),
+			curve: Some(Curve::Bn254),
+			exponentiation: Some(exponentiation),
+			width: Some(width),
+			token_symbol: Some("WEBB".to_string()),
+			amount: Some("1000000000000000000".to_string()),
+			denomination: Some(18),
+			wrapped_token: None,
+			wrapping_fee: None,
+			backend: Some(Backend::Arkworks),
+			hash_function: Some(HashFunction::Poseidon),
+			index: Some(u64::from(index)),
+			metadata: NoteMetadata::default(),
+		};
+		notes.push(NoteVector {
+			note: note.to_string(),
+			secret: to_hex(&leaf.secret_bytes),
+			nullifier: to_hex(&leaf.nullifier_bytes),
+			leaf: to_hex(&leaf.leaf_bytes),
+			nullifier_hash: to_hex(&leaf.nullifier_hash_bytes),
+		});
+	}
+	Ok(notes)
+}
+
+/// Chain the notes target, and the EVM anchor is on
+fn notes_chain_id() -> TypedChainId {
+	TypedChainId::new(ChainType::Evm, 1)
+}
+
+fn leaf_bytes(leaf: &[u8]) -> Result<[u8; 32], OperationError> {
+	leaf.try_into().map_err(|_| OpStatusCode::InvalidLeaves.into())
+}
+
+/// Merkle tree of depth `depth` holding the leaves of `notes`
+pub fn tree_vector(notes: &[NoteVector], depth: usize) -> Result<TreeVector, OperationError> {
+	let mut leaves = Vec::with_capacity(notes.len());
+	for note in notes {
+		leaves.push(leaf_bytes(&from_hex(&note.leaf))?);
+	}
+	let root = compute_root::<Bn254Fr>(TREE_PARAMS.0, TREE_PARAMS.1, &leaves, depth)?;
+	Ok(TreeVector {
+		depth,
+		leaves: notes.iter().map(|note| note.leaf.clone()).collect(),
+		root: to_hex(&root.into_repr().to_bytes_be()),
+	})
+}
+
+/// Proving and verifying keys of the Bn254 mixer circuit of depth
+/// `tree_depth`, set up from `rng`
+pub fn mixer_keys(rng: &mut ChaCha20Rng, tree_depth: usize) -> Result<(Vec<u8>, Vec<u8>), OperationError> {
+	ensure_tree_depth(tree_depth)?;
+	let setup_error = |e: ark_crypto_primitives::Error| {
+		OperationError::new_with_message(OpStatusCode::InvalidProvingKey, e.to_string())
+	};
+	with_tree_depth!(tree_depth, DEPTH => {
+		let (circuit, ..) = MixerR1CSProver::<Bn254, DEPTH>::setup_random_circuit(ArkCurve::Bn254, DEFAULT_LEAF, rng)
+			.map_err(setup_error)?;
+		setup_keys_unchecked::<Bn254, _, _>(circuit, rng).map_err(setup_error)
+	})
+}
+
+/// Proving and verifying keys of the anchor circuit of depth `tree_depth`
+/// with `ANCHOR_COUNT` roots, set up from `rng`
+pub fn anchor_keys(rng: &mut ChaCha20Rng, tree_depth: usize) -> Result<(Vec<u8>, Vec<u8>), OperationError> {
+	ensure_tree_depth(tree_depth)?;
+	let setup_error = |e: ark_crypto_primitives::Error| {
+		OperationError::new_with_message(OpStatusCode::InvalidProvingKey, e.to_string())
+	};
+	with_tree_depth!(tree_depth, DEPTH => {
+		let (circuit, ..) = AnchorR1CSProver::<Bn254, DEPTH, ANCHOR_COUNT>::setup_random_circuit(
+			ArkCurve::Bn254,
+			DEFAULT_LEAF,
+			rng,
+		)
+		.map_err(setup_error)?;
+		setup_keys_unchecked::<Bn254, _, _>(circuit, rng).map_err(setup_error)
+	})
+}
+
+/// `count` random accounts of `len` bytes drawn from `rng`
+fn accounts<const COUNT: usize>(rng: &mut ChaCha20Rng, len: usize) -> [Vec<u8>; COUNT] {
+	[(); COUNT].map(|_| (0..len).map(|_| rng.gen()).collect())
+}
+
+/// Withdrawal of the first leaf of `tree` with `notes[0]` from the Substrate
+/// mixer. The 32 bytes accounts are drawn from `rng`, and so is the seed of
+/// the prover
+pub fn proof_vector(
+	rng: &mut ChaCha20Rng,
+	notes: &[NoteVector],
+	tree: &TreeVector,
+	pk: &[u8],
+) -> Result<ProofVector, OperationError> {
+	let note = notes.first().ok_or(OpStatusCode::InvalidNoteSecrets)?;
+	let [recipient, relayer] = accounts(rng, 32);
+	let (exponentiation, width) = MIXER_PARAMS;
+	let payload = MixerProofInput {
+		exponentiation: Some(exponentiation),
+		width: Some(width),
+		curve: Some(Curve::Bn254),
+		backend: Some(Backend::Arkworks),
+		secret: Some(from_hex(&note.secret)),
+		nullifier: Some(from_hex(&note.nullifier)),
+		recipient: Some(recipient.clone()),
+		relayer: Some(relayer.clone()),
+		pk: Some(pk.to_vec()),
+		refund: Some(REFUND),
+		fee: Some(FEE),
+		leaves: Some(tree.leaves.iter().map(|leaf| from_hex(leaf)).collect()),
+		leaf_index: Some(0),
+		tree_depth: Some(tree.depth),
+		rng_seed: Some(rng.gen()),
+		..Default::default()
+	}
+	.build()?;
+	let proof = create_proof(payload.clone(), &mut ProverRng::new(payload.rng_seed))?;
+	Ok(ProofVector {
+		leaf_index: 0,
+		recipient: to_hex(&recipient),
+		relayer: to_hex(&relayer),
+		fee: FEE.to_string(),
+		refund: REFUND.to_string(),
+		public_inputs: proof.public_inputs.iter().map(|input| to_hex(input)).collect(),
+		proof: to_hex(&proof.proof),
+	})
+}
+
+/// Deposits of the secrets of `notes` in an EVM anchor, in a tree of depth
+/// `tree_depth`, and the `EvmCompat` withdrawal of the first one. The
+/// addresses, the refresh commitment and the seed of the prover are drawn
+/// from `rng`
+pub fn anchor_vector(
+	rng: &mut ChaCha20Rng,
+	notes: &[NoteVector],
+	tree_depth: usize,
+	pk: &[u8],
+	vk: &[u8],
+) -> Result<AnchorVector, OperationError> {
+	let note = notes.first().ok_or(OpStatusCode::InvalidNoteSecrets)?;
+	let chain_id = notes_chain_id().to_u64();
+	let mut leaves = Vec::with_capacity(notes.len());
+	for note in notes {
+		let leaf = anchor_leaf(chain_id, &from_hex(&note.secret), &from_hex(&note.nullifier))?;
+		leaves.push(leaf_bytes(&leaf.leaf_bytes)?);
+	}
+	let (exponentiation, width) = TREE_PARAMS;
+	let roots = [
+		compute_root::<Bn254Fr>(exponentiation, width, &leaves, tree_depth)?,
+		compute_root::<Bn254Fr>(exponentiation, width, &[], tree_depth)?,
+	]
+	.map(|root| root.into_repr().to_bytes_be());
+	let [recipient, relayer] = accounts(rng, 20);
+	let refresh_commitment = Bn254Fr::from_be_bytes_mod_order(&rng.gen::<[u8; 32]>())
+		.into_repr()
+		.to_bytes_be();
+	let payload = AnchorProofInput {
+		curve: Some(Curve::Bn254),
+		backend: Some(Backend::Arkworks),
+		secret: Some(from_hex(&note.secret)),
+		nullifier: Some(from_hex(&note.nullifier)),
+		recipient: Some(recipient.clone()),
+		relayer: Some(relayer.clone()),
+		refresh_commitment: Some(refresh_commitment.clone()),
+		pk: Some(pk.to_vec()),
+		refund: Some(REFUND),
+		fee: Some(FEE),
+		chain_id: Some(chain_id),
+		leaves: Some(leaves.iter().map(|leaf| leaf.to_vec()).collect()),
+		leaf_index: Some(0),
+		roots: Some(roots.to_vec()),
+		tree_depth: Some(tree_depth),
+		rng_seed: Some(rng.gen()),
+		anchor_mode: Some(AnchorMode::EvmCompat),
+	}
+	.build()?;
+	let proof = anchor::create_proof(payload.clone(), &mut ProverRng::new(payload.rng_seed))?;
+	Ok(AnchorVector {
+		chain_id: chain_id.to_string(),
+		leaves: leaves.iter().map(|leaf| to_hex(leaf)).collect(),
+		roots: roots.iter().map(|root| to_hex(root)).collect(),
+		leaf_index: 0,
+		recipient: to_hex(&recipient),
+		relayer: to_hex(&relayer),
+		refresh_commitment: to_hex(&refresh_commitment),
+		fee: FEE.to_string(),
+		refund: REFUND.to_string(),
+		public_inputs: proof.public_inputs.iter().map(|input| to_hex(input)).collect(),
+		proof: to_hex(&proof.proof),
+		vk: to_hex(vk),
+	})
+}
+
+/// Notes, their tree, the mixer keys and withdrawal, then the anchor keys
+/// and withdrawal, all drawn in that order from a ChaCha20 RNG seeded with
+/// `seed`
+pub fn test_vectors(seed: [u8; 32], note_count: u32, tree_depth: usize) -> Result<TestVectors, OperationError> {
+	let mut rng = ChaCha20Rng::from_seed(seed);
+	let notes = note_vectors(&mut rng, note_count)?;
+	let tree = tree_vector(&notes, tree_depth)?;
+	let (pk, vk) = mixer_keys(&mut rng, tree_depth)?;
+	let proof = proof_vector(&mut rng, &notes, &tree, &pk)?;
+	let (anchor_pk, anchor_vk) = anchor_keys(&mut rng, tree_depth)?;
+	let anchor = anchor_vector(&mut rng, &notes, tree_depth, &anchor_pk, &anchor_vk)?;
+	Ok(TestVectors {
+		version: TEST_VECTORS_VERSION,
+		seed: to_hex(&seed),
+		curve: Curve::Bn254.to_string(),
+		notes,
+		tree,
+		vk: to_hex(&vk),
+		proof,
+		anchor,
+	})
+}
+
+/// Fixtures are pretty printed with the fields in declaration order, the
+/// same value always prints the same
+fn to_json<T: Serialize>(value: &T) -> Result<String, OperationError> {
+	serde_json::to_string_pretty(value)
+		.map_err(|e| OperationError::new_with_message(OpStatusCode::SerializationFailed, e.to_string()))
+}
+
+fn parse_seed(seed: Uint8Array) -> Result<[u8; 32], OperationError> {
+	seed.to_vec().try_into().map_err(|_| {
+		OperationError::new_with_message(
+			OpStatusCode::InvalidArrayLength,
+			"The seed must be 32 bytes".to_string(),
+		)
+	})
+}
+
+/// JSON of `count` mixer notes with their leaves and nullifier hashes, from
+/// a 32 bytes seed
+#[wasm_bindgen(js_name = generateNoteVectors)]
+pub fn js_generate_note_vectors(seed: Uint8Array, count: u32) -> Result<JsString, JsValue> {
+	let mut rng = ChaCha20Rng::from_seed(parse_seed(seed)?);
+	Ok(to_json(&note_vectors(&mut rng, count)?)?.into())
+}
+
+/// JSON of the notes, their tree, and the withdrawals from the Substrate
+/// mixer and from an EVM anchor with their verifying keys, from a 32 bytes
+/// seed. The tree depth defaults to 30
+#[wasm_bindgen(js_name = generateTestVectors)]
+pub fn js_generate_test_vectors(
+	seed: Uint8Array,
+	note_count: u32,
+	tree_depth: Option<u32>,
+) -> Result<JsString, JsValue> {
+	let tree_depth = tree_depth.map(|depth| depth as usize).unwrap_or(TREE_HEIGHT);
+	Ok(to_json(&test_vectors(parse_seed(seed)?, note_count, tree_depth)?)?.into())
+}
+
+#[cfg(test)]
+mod test {
+	use arkworks_setups::common::verify_unchecked_raw;
+	use wasm_bindgen_test::*;
+
+	use super::*;
+	use crate::nullifier::note_nullifier;
+	use crate::proof::anchor::ext_data_hash;
+
+	#[wasm_bindgen_test]
+	fn should_generate_the_same_notes_from_the_same_seed() {
+		let notes = |seed: u8| to_json(&note_vectors(&mut ChaCha20Rng::from_seed([seed; 32]), 3).unwrap()).unwrap();
+		assert_eq!(notes(1), notes(1));
+		assert_ne!(notes(1), notes(2));
+
+		let vectors = note_vectors(&mut ChaCha20Rng::from_seed([1; 32]), 3).unwrap();
+		for vector in &vectors {
+			let note = JsNote::deserialize(&vector.note).unwrap();
+			assert_eq!(to_hex(&note.get_leaf_commitment().unwrap().to_vec()), vector.leaf);
+			assert_eq!(to_hex(&note_nullifier(&note).unwrap()), vector.nullifier_hash);
+		}
+	}
+
+	#[wasm_bindgen_test]
+	fn should_generate_verifiable_proofs() {
+		let vectors = test_vectors([7; 32], 2, 20).unwrap();
+		assert_eq!(vectors.tree.leaves.len(), 2);
+		let public_inputs = |inputs: &[String]| inputs.iter().map(|input| from_hex(input)).collect::<Vec<_>>();
+
+		let proof = &vectors.proof;
+		assert_eq!(proof.public_inputs[0], vectors.notes[0].nullifier_hash);
+		assert_eq!(proof.public_inputs[1], vectors.tree.root);
+		assert!(verify_unchecked_raw::<Bn254>(
+			&public_inputs(&proof.public_inputs),
+			&from_hex(&vectors.vk),
+			&from_hex(&proof.proof)
+		)
+		.unwrap());
+
+		// The external data hash of the EVM withdrawal is the one the Solidity
+		// anchors hash
+		let anchor = &vectors.anchor;
+		assert_eq!(anchor.leaves.len(), 2);
+		assert_eq!(anchor.public_inputs[0], vectors.notes[0].nullifier_hash);
+		assert_eq!(anchor.public_inputs[3..], anchor.roots[..]);
+		let word = |address: &str| [vec![0u8; 12], from_hex(address)].concat();
+		let hash = ext_data_hash(
+			AnchorMode::EvmCompat,
+			&word(&anchor.recipient),
+			&word(&anchor.relayer),
+			FEE,
+			REFUND,
+			&from_hex(&anchor.refresh_commitment).try_into().unwrap(),
+		);
+		let ext_data_hash = Bn254Fr::from_be_bytes_mod_order(&hash).into_repr().to_bytes_be();
+		assert_eq!(anchor.public_inputs[1], to_hex(&ext_data_hash));
+		assert!(verify_unchecked_raw::<Bn254>(
+			&public_inputs(&anchor.public_inputs),
+			&from_hex(&anchor.vk),
+			&from_hex(&anchor.proof)
+		)
+		.unwrap());
+	}
+}
@@ -5,85 +5,50 @@ use core::ops::Deref;
 use core::str::FromStr;
 
 use arkworks_setups::Curve as ArkCurve;
-use js_sys::{JsString, Uint8Array};
+use js_sys::{Array, JsString, Object, Reflect, Uint8Array, JSON};
+use parity_scale_codec::{Decode, Encode};
 use wasm_bindgen::__rt::core::fmt::Formatter;
 use wasm_bindgen::prelude::*;
 
-/// Final Operation Error
-#[cfg(not(test))]
-#[wasm_bindgen]
-#[derive(PartialEq, Eq, Debug)]
-pub struct OperationError {
-	#[wasm_bindgen(skip)]
-	pub code: OpStatusCode,
-	#[wasm_bindgen(skip)]
-	pub error_message: String,
-	#[wasm_bindgen(skip)]
-	pub data: Option<String>,
-}
-
-#[allow(clippy::unused_unit)]
-#[cfg(not(test))]
-#[wasm_bindgen]
-impl OperationError {
-	#[wasm_bindgen(js_name = code)]
-	#[wasm_bindgen(getter)]
-	pub fn code(&self) -> JsValue {
-		JsValue::from(self.code.clone() as u32)
-	}
-
-	// For backward compatibility
-	#[wasm_bindgen(js_name = error_message)]
-	#[wasm_bindgen(getter)]
-	pub fn error_message(&self) -> JsString {
-		JsString::from(self.error_message.clone())
-	}
-
-	#[wasm_bindgen(js_name = message)]
-	#[wasm_bindgen(getter)]
-	pub fn message(&self) -> JsString {
-		JsString::from(self.error_message.clone())
-	}
-
-	#[wasm_bindgen(js_name = data)]
-	#[wasm_bindgen(getter)]
-	pub fn data(&self) -> JsString {
-		match self.data.clone() {
-			None => JsString::from("{}"),
-			Some(e) => JsString::from(e),
-		}
-	}
-}
-/// For tests this will have a custom JsValue conversion
-#[cfg(test)]
+/// Final Operation Error, thrown to JS as an `OperationError` object
 #[derive(PartialEq, Eq, Debug)]
 pub struct OperationError {
 	pub code: OpStatusCode,
 	pub error_message: String,
+	/// JSON details of the error, passed to JS as a string if it isn't valid
+	/// JSON
 	pub data: Option<String>,
 }
-#[cfg(test)]
-impl OperationError {
-	pub fn code(&self) -> JsValue {
-		JsValue::from(self.code.clone() as u32)
-	}
-
-	pub fn error_message(&self) -> JsString {
-		JsString::from(self.error_message.clone())
-	}
 
-	pub fn data(&self) -> JsString {
-		match self.data.clone() {
-			None => JsString::from("{}"),
-			Some(e) => JsString::from(e),
-		}
-	}
+#[wasm_bindgen(typescript_custom_section)]
+const OPERATION_ERROR: &str = r#"
+export type ErrorCode = keyof typeof OpStatusCode;
+
+export interface OperationError {
+	code: ErrorCode;
+	message: string;
+	data?: any;
+	/** @deprecated the numeric code the error had before `code` was its name */
+	statusCode: OpStatusCode;
+	/** @deprecated same as `message` */
+	error_message: string;
 }
+"#;
 
-#[cfg(test)]
 impl From<OperationError> for JsValue {
 	fn from(e: OperationError) -> Self {
-		JsValue::from(e.to_string())
+		let error = Object::new();
+		let set = |key: &str, value: JsValue| {
+			Reflect::set(&error, &JsValue::from_str(key), &value).expect("setting a property of an object can't fail");
+		};
+		set("code", e.code.name().into());
+		set("statusCode", (e.code as u32).into());
+		set("message", e.error_message.as_str().into());
+		set("error_message", e.error_message.into());
+		if let Some(data) = e.data {
+			set("data", JSON::parse(&data).unwrap_or_else(|_| data.into()));
+		}
+		error.into()
 	}
 }
 
@@ -93,8 +58,8 @@ impl fmt::Display for OperationError {
 			f,
 			"Code {}, message {}, data {}",
 			self.code.clone() as u32,
-			self.error_message.clone(),
-			self.data()
+			self.error_message,
+			self.data.as_deref().unwrap_or("{}")
 		)
 	}
 }
@@ -120,13 +85,38 @@ pub enum Chain {
 	Rinkeby,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+impl Chain {
+	/// The known chain with this typed chain id, they are all EVM chains
+	pub fn from_typed_chain_id(typed_chain_id: TypedChainId) -> Option<Self> {
+		match (typed_chain_id.chain_type?, typed_chain_id.id) {
+			(ChainType::Evm, 4) => Some(Chain::Rinkeby),
+			(ChainType::Evm, 1337) => Some(Chain::Ganache),
+			(ChainType::Evm, 2021) => Some(Chain::Edgeware),
+			(ChainType::Evm, 2022) => Some(Chain::Beresheet),
+			(ChainType::Evm, 1666700000) => Some(Chain::HarmonyTestShard1),
+			_ => None,
+		}
+	}
+
+	/// Stable identifier of the chain for translation keys
+	pub fn key(&self) -> &'static str {
+		match self {
+			Chain::Edgeware => "edgeware",
+			Chain::Ganache => "ganache",
+			Chain::Beresheet => "beresheet",
+			Chain::HarmonyTestShard1 => "harmonyTestShard1",
+			Chain::Rinkeby => "rinkeby",
+		}
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Encode, Decode)]
 pub enum Backend {
 	Arkworks,
 	Circom,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Encode, Decode)]
 pub enum Curve {
 	Bls381,
 	Bn254,
@@ -135,7 +125,9 @@ pub enum Curve {
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum HashFunction {
 	Poseidon,
-	MiMCTornado,
+	/// MiMC with 220 rounds and the arkworks-utils constants, for mixer
+	/// leaves only: the trees and the circuits hash with Poseidon
+	MiMC220,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -144,6 +136,163 @@ pub enum NoteProtocol {
 	VAnchor,
 }
 
+/// Type of chain, encoded in bytes 2 and 3 of a typed chain id
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChainType {
+	Evm,
+	Substrate,
+	PolkadotParachain,
+	KusamaParachain,
+	RococoParachain,
+	Cosmos,
+	Solana,
+}
+
+impl ChainType {
+	pub fn to_bytes(self) -> [u8; 2] {
+		match self {
+			ChainType::Evm => [1, 0],
+			ChainType::Substrate => [2, 0],
+			ChainType::PolkadotParachain => [3, 1],
+			ChainType::KusamaParachain => [3, 2],
+			ChainType::RococoParachain => [3, 3],
+			ChainType::Cosmos => [4, 0],
+			ChainType::Solana => [5, 0],
+		}
+	}
+
+	/// Decimals of the gas token, `None` for chains whose gas token depends
+	/// on the chain rather than on its type
+	pub fn native_decimals(self) -> Option<u8> {
+		match self {
+			ChainType::Evm => Some(18),
+			ChainType::Solana => Some(9),
+			ChainType::Substrate
+			| ChainType::PolkadotParachain
+			| ChainType::KusamaParachain
+			| ChainType::RococoParachain
+			| ChainType::Cosmos => None,
+		}
+	}
+
+	pub fn from_bytes(bytes: [u8; 2]) -> Option<Self> {
+		match bytes {
+			[1, 0] => Some(ChainType::Evm),
+			[2, 0] => Some(ChainType::Substrate),
+			[3, 1] => Some(ChainType::PolkadotParachain),
+			[3, 2] => Some(ChainType::KusamaParachain),
+			[3, 3] => Some(ChainType::RococoParachain),
+			[4, 0] => Some(ChainType::Cosmos),
+			[5, 0] => Some(ChainType::Solana),
+			_ => None,
+		}
+	}
+}
+
+/// Chain id prefixed with the type of the chain, it's laid out as a big
+/// endian u64 of 2 zero bytes, 2 bytes of chain type and a 4 bytes chain id.
+/// Chain ids without a chain type (zero type bytes) are kept for older notes
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TypedChainId {
+	pub chain_type: Option<ChainType>,
+	pub id: u32,
+}
+
+impl TypedChainId {
+	pub fn new(chain_type: ChainType, id: u32) -> Self {
+		Self {
+			chain_type: Some(chain_type),
+			id,
+		}
+	}
+
+	pub fn to_u64(self) -> u64 {
+		let mut bytes = [0u8; 8];
+		if let Some(chain_type) = self.chain_type {
+			bytes[2..4].copy_from_slice(&chain_type.to_bytes());
+		}
+		bytes[4..].copy_from_slice(&self.id.to_be_bytes());
+		u64::from_be_bytes(bytes)
+	}
+}
+
+impl TypedChainId {
+	/// Split a typed chain id into its chain type and id, with an error
+	/// telling which part is out of range
+	pub fn from_u64(typed_chain_id: u64) -> Result<Self, OperationError> {
+		let invalid = |message: String| OperationError::new_with_message(OpStatusCode::InvalidChainId, message);
+		let bytes = typed_chain_id.to_be_bytes();
+		if bytes[..2] != [0, 0] {
+			return Err(invalid(format!(
+				"Chain id {:#x} doesn't fit the 48 bits of a typed chain id",
+				typed_chain_id
+			)));
+		}
+		let chain_type = match [bytes[2], bytes[3]] {
+			[0, 0] => None,
+			type_bytes => Some(ChainType::from_bytes(type_bytes).ok_or_else(|| {
+				invalid(format!(
+					"Chain id {:#x} has the unknown chain type {:#06x}",
+					typed_chain_id,
+					u16::from_be_bytes(type_bytes)
+				))
+			})?),
+		};
+		let id = u32::from_be_bytes(bytes[4..].try_into().unwrap());
+		Ok(Self { chain_type, id })
+	}
+}
+
+impl TryFrom<u64> for TypedChainId {
+	type Error = OpStatusCode;
+
+	fn try_from(typed_chain_id: u64) -> Result<Self, Self::Error> {
+		Self::from_u64(typed_chain_id).map_err(|e| e.code)
+	}
+}
+
+/// How the VAnchor verifier on the target chain encodes `ExtData` before
+/// hashing it
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExtDataEncoding {
+	Evm,
+	Substrate,
+}
+
+/// Verifier a proof's public inputs are submitted to, which decides their
+/// order and encoding
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TargetRuntime {
+	SubstrateMixer,
+	SubstrateVAnchor,
+	EvmVAnchor,
+	/// protocol-solidity's `FixedDepositAnchor`, which takes the proofs of
+	/// `proof::anchor`. There's no EVM mixer, EVM deposits are withdrawn
+	/// from the anchors
+	EvmAnchor,
+}
+
+impl TargetRuntime {
+	/// EVM verifiers take big endian `uint256` words, the Substrate pallets
+	/// little endian field elements
+	pub fn is_evm(self) -> bool {
+		matches!(self, TargetRuntime::EvmVAnchor | TargetRuntime::EvmAnchor)
+	}
+}
+
+/// How the anchor prover hashes the recipient, relayer, fee, refund and
+/// refresh commitment of a withdrawal into its external data public input.
+/// The leaves and the merkle tree are Poseidon hashed in both modes
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Encode, Decode)]
+pub enum AnchorMode {
+	/// `keccak256` of the accounts, the SCALE encoded fee and refund and the
+	/// refresh commitment, as the Substrate anchor pallet hashes them
+	Substrate,
+	/// `keccak256(abi.encode(extData))` of the `ExtData` struct of
+	/// protocol-solidity's `FixedDepositAnchor`
+	EvmCompat,
+}
+
 impl fmt::Display for NoteVersion {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		match self {
@@ -163,6 +312,442 @@ impl FromStr for NoteVersion {
 	}
 }
 
+impl fmt::Display for ChainType {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			ChainType::Evm => write!(f, "Evm"),
+			ChainType::Substrate => write!(f, "Substrate"),
+			ChainType::PolkadotParachain => write!(f, "PolkadotParachain"),
+			ChainType::KusamaParachain => write!(f, "KusamaParachain"),
+			ChainType::RococoParachain => write!(f, "RococoParachain"),
+			ChainType::Cosmos => write!(f, "Cosmos"),
+			ChainType::Solana => write!(f, "Solana"),
+		}
+	}
+}
+
+impl fmt::Display for TypedChainId {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.to_u64())
+	}
+}
+
+impl FromStr for ChainType {
+	type Err = OpStatusCode;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"Evm" => Ok(ChainType::Evm),
+			"Substrate" => Ok(ChainType::Substrate),
+			"PolkadotParachain" => Ok(ChainType::PolkadotParachain),
+			"KusamaParachain" => Ok(ChainType::KusamaParachain),
+			"RococoParachain" => Ok(ChainType::RococoParachain),
+			"Cosmos" => Ok(ChainType::Cosmos),
+			"Solana" => Ok(ChainType::Solana),
+			_ => Err(OpStatusCode::InvalidChainId),
+		}
+	}
+}
+
+fn invalid_chain_id(s: &str) -> OperationError {
+	let message = format!(
+		"Invalid chain id {:?}, expected a decimal or 0x prefixed hex typed chain id, or <chain type>:<id>",
+		s
+	);
+	OperationError::new_with_message(OpStatusCode::InvalidChainId, message)
+}
+
+/// Parse a chain id given in decimal, `0x` prefixed hex or as a chain type
+/// and id such as `Evm:5`. Only the last form is checked as a typed chain
+/// id, notes written before typed chain ids hold u64 chain ids of any value.
+pub fn parse_chain_id(s: &str) -> Result<u64, OperationError> {
+	if s.contains(':') {
+		return s.parse::<TypedChainId>().map(TypedChainId::to_u64);
+	}
+	match s.strip_prefix("0x") {
+		Some(hex) => u64::from_str_radix(hex, 16),
+		None => s.parse(),
+	}
+	.map_err(|_| invalid_chain_id(s))
+}
+
+impl FromStr for TypedChainId {
+	type Err = OperationError;
+
+	/// Parse the decimal or `0x` prefixed hex representation of the u64, or
+	/// a chain type and id such as `Evm:5`
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		if let Some((chain_type, id)) = s.split_once(':') {
+			let chain_type: ChainType = chain_type.parse().map_err(|_| invalid_chain_id(s))?;
+			let id = id.parse().map_err(|_| invalid_chain_id(s))?;
+			return Ok(TypedChainId::new(chain_type, id));
+		}
+		Self::from_u64(parse_chain_id(s)?)
+	}
+}
+
+/// What a note's identifying data points at on its chain: a Substrate tree
+/// id, an EVM contract address or a 32 bytes resource id
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum IdentifyingData {
+	TreeId(u32),
+	ContractAddress([u8; 20]),
+	Resource([u8; 32]),
+}
+
+impl IdentifyingData {
+	/// Check the identifying data can live on a chain of `chain_type`, a
+	/// chain id without a type is accepted with any identifying data
+	pub fn ensure_chain_type(&self, chain_type: Option<ChainType>) -> Result<(), OperationError> {
+		let chain_type = match chain_type {
+			Some(chain_type) => chain_type,
+			None => return Ok(()),
+		};
+		let fits = match self {
+			IdentifyingData::Resource(_) => true,
+			IdentifyingData::ContractAddress(_) => chain_type == ChainType::Evm,
+			IdentifyingData::TreeId(_) => matches!(
+				chain_type,
+				ChainType::Substrate
+					| ChainType::PolkadotParachain
+					| ChainType::KusamaParachain
+					| ChainType::RococoParachain
+			),
+		};
+		if fits {
+			return Ok(());
+		}
+		let kind = match self {
+			IdentifyingData::TreeId(_) => "a tree id",
+			IdentifyingData::ContractAddress(_) => "a contract address",
+			IdentifyingData::Resource(_) => "a resource id",
+		};
+		let message = format!("{} chains aren't identified by {}: {}", chain_type, kind, self);
+		Err(OperationError::new_with_message(
+			OpStatusCode::InvalidIdentifyingData,
+			message,
+		))
+	}
+}
+
+impl fmt::Display for IdentifyingData {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			IdentifyingData::TreeId(tree_id) => write!(f, "{}", tree_id),
+			IdentifyingData::ContractAddress(address) => write!(f, "0x{}", hex::encode(address)),
+			IdentifyingData::Resource(resource_id) => write!(f, "0x{}", hex::encode(resource_id)),
+		}
+	}
+}
+
+impl FromStr for IdentifyingData {
+	type Err = OperationError;
+
+	/// A decimal tree id, or a `0x` prefixed 20 bytes address or 32 bytes
+	/// resource id
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let invalid = || {
+			let message = format!("Unknown identifying data: {:?}", s);
+			OperationError::new_with_message(OpStatusCode::InvalidIdentifyingData, message)
+		};
+		match s.strip_prefix("0x") {
+			Some(hex) => {
+				let bytes = hex::decode(hex).map_err(|_| invalid())?;
+				match bytes.len() {
+					20 => Ok(IdentifyingData::ContractAddress(bytes.try_into().unwrap())),
+					32 => Ok(IdentifyingData::Resource(bytes.try_into().unwrap())),
+					_ => Err(invalid()),
+				}
+			}
+			None if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) => {
+				s.parse().map(IdentifyingData::TreeId).map_err(|_| invalid())
+			}
+			None => Err(invalid()),
+		}
+	}
+}
+
+/// System on the chain a resource lives in, the right-aligned first 26 bytes
+/// of the resource id
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TargetSystem {
+	ContractAddress([u8; 20]),
+	TreeId(u32),
+}
+
+/// Resource id of the Webb bridge: the 26 bytes target system followed by the
+/// 6 bytes typed chain id (2 bytes chain type, 4 bytes chain id)
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ResourceId {
+	pub target_system: TargetSystem,
+	pub typed_chain_id: TypedChainId,
+}
+
+impl ResourceId {
+	pub fn new(target_system: TargetSystem, typed_chain_id: TypedChainId) -> Result<Self, OperationError> {
+		let identifying_data = match target_system {
+			TargetSystem::ContractAddress(address) => IdentifyingData::ContractAddress(address),
+			TargetSystem::TreeId(tree_id) => IdentifyingData::TreeId(tree_id),
+		};
+		if typed_chain_id.chain_type.is_none() {
+			let message = format!("Resource ids need a chain type, {} has none", typed_chain_id.id);
+			return Err(OperationError::new_with_message(
+				OpStatusCode::InvalidIdentifyingData,
+				message,
+			));
+		}
+		identifying_data.ensure_chain_type(typed_chain_id.chain_type)?;
+		Ok(Self {
+			target_system,
+			typed_chain_id,
+		})
+	}
+
+	pub fn to_bytes(&self) -> [u8; 32] {
+		let mut bytes = [0u8; 32];
+		match self.target_system {
+			TargetSystem::ContractAddress(address) => bytes[6..26].copy_from_slice(&address),
+			TargetSystem::TreeId(tree_id) => bytes[22..26].copy_from_slice(&tree_id.to_be_bytes()),
+		}
+		bytes[26..].copy_from_slice(&self.typed_chain_id.to_u64().to_be_bytes()[2..]);
+		bytes
+	}
+
+	/// Read a resource id, the chain type tells how to read the target system
+	pub fn from_bytes(bytes: [u8; 32]) -> Result<Self, OperationError> {
+		let invalid = |reason: &str| {
+			let message = format!("Invalid resource id 0x{}: {}", hex::encode(bytes), reason);
+			OperationError::new_with_message(OpStatusCode::InvalidIdentifyingData, message)
+		};
+		let mut typed_chain_id = [0u8; 8];
+		typed_chain_id[2..].copy_from_slice(&bytes[26..]);
+		let typed_chain_id =
+			TypedChainId::try_from(u64::from_be_bytes(typed_chain_id)).map_err(|_| invalid("unknown chain type"))?;
+		let target_system = match typed_chain_id.chain_type {
+			Some(ChainType::Evm) if bytes[..6] == [0; 6] => {
+				TargetSystem::ContractAddress(bytes[6..26].try_into().unwrap())
+			}
+			Some(
+				ChainType::Substrate
+				| ChainType::PolkadotParachain
+				| ChainType::KusamaParachain
+				| ChainType::RococoParachain,
+			) if bytes[..22] == [0; 22] => TargetSystem::TreeId(u32::from_be_bytes(bytes[22..26].try_into().unwrap())),
+			_ => return Err(invalid("no target system for the chain type")),
+		};
+		ResourceId::new(target_system, typed_chain_id)
+	}
+}
+
+impl fmt::Display for ResourceId {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "0x{}", hex::encode(self.to_bytes()))
+	}
+}
+
+impl FromStr for ResourceId {
+	type Err = OperationError;
+
+	/// Parse the `0x` prefixed hex of the 32 bytes
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.parse()? {
+			IdentifyingData::Resource(bytes) => ResourceId::from_bytes(bytes),
+			_ => {
+				let message = format!("Not a resource id: {}", s);
+				Err(OperationError::new_with_message(
+					OpStatusCode::InvalidIdentifyingData,
+					message,
+				))
+			}
+		}
+	}
+}
+
+#[wasm_bindgen(js_name = ResourceId)]
+#[derive(Clone, Debug)]
+pub struct JsResourceId {
+	#[wasm_bindgen(skip)]
+	pub inner: ResourceId,
+}
+
+#[wasm_bindgen(js_class = ResourceId)]
+impl JsResourceId {
+	/// Resource id of a contract, e.g. an anchor, on an EVM chain
+	#[wasm_bindgen(js_name = fromContractAddress)]
+	pub fn from_contract_address(address: JsString, typed_chain_id: &JsTypedChainId) -> Result<JsResourceId, JsValue> {
+		let address: String = address.into();
+		let address = match address.parse()? {
+			IdentifyingData::ContractAddress(address) => address,
+			_ => return Err(OpStatusCode::InvalidIdentifyingData.into()),
+		};
+		let inner = ResourceId::new(TargetSystem::ContractAddress(address), typed_chain_id.inner)?;
+		Ok(JsResourceId { inner })
+	}
+
+	/// Resource id of a tree on a Substrate chain
+	#[wasm_bindgen(js_name = fromTreeId)]
+	pub fn from_tree_id(tree_id: u32, typed_chain_id: &JsTypedChainId) -> Result<JsResourceId, JsValue> {
+		let inner = ResourceId::new(TargetSystem::TreeId(tree_id), typed_chain_id.inner)?;
+		Ok(JsResourceId { inner })
+	}
+
+	/// Parse the `0x` prefixed hex of a resource id
+	pub fn parse(resource_id: JsString) -> Result<JsResourceId, JsValue> {
+		let resource_id: String = resource_id.into();
+		let inner: ResourceId = resource_id.parse()?;
+		Ok(JsResourceId { inner })
+	}
+
+	#[wasm_bindgen(getter, js_name = typedChainId)]
+	pub fn typed_chain_id(&self) -> JsTypedChainId {
+		self.inner.typed_chain_id.into()
+	}
+
+	/// The contract address (`0x` prefixed) or the tree id (decimal)
+	#[wasm_bindgen(getter, js_name = targetSystem)]
+	pub fn target_system(&self) -> JsString {
+		let identifying_data = match self.inner.target_system {
+			TargetSystem::ContractAddress(address) => IdentifyingData::ContractAddress(address),
+			TargetSystem::TreeId(tree_id) => IdentifyingData::TreeId(tree_id),
+		};
+		identifying_data.to_string().into()
+	}
+
+	#[wasm_bindgen(js_name = toBytes)]
+	pub fn to_bytes(&self) -> Uint8Array {
+		Uint8Array::from(self.inner.to_bytes().as_ref())
+	}
+
+	#[wasm_bindgen(js_name = toString)]
+	pub fn to_js_string(&self) -> JsString {
+		self.inner.to_string().into()
+	}
+}
+
+#[wasm_bindgen(js_name = TypedChainId)]
+#[derive(Clone, Debug)]
+pub struct JsTypedChainId {
+	#[wasm_bindgen(skip)]
+	pub inner: TypedChainId,
+}
+
+#[wasm_bindgen(js_class = TypedChainId)]
+impl JsTypedChainId {
+	#[wasm_bindgen(js_name = fromEvm)]
+	pub fn from_evm(chain_id: u32) -> JsTypedChainId {
+		TypedChainId::new(ChainType::Evm, chain_id).into()
+	}
+
+	#[wasm_bindgen(js_name = fromSubstrate)]
+	pub fn from_substrate(chain_id: u32) -> JsTypedChainId {
+		TypedChainId::new(ChainType::Substrate, chain_id).into()
+	}
+
+	#[wasm_bindgen(js_name = fromCosmos)]
+	pub fn from_cosmos(chain_id: u32) -> JsTypedChainId {
+		TypedChainId::new(ChainType::Cosmos, chain_id).into()
+	}
+
+	/// Parse a typed chain id as found in notes
+	pub fn parse(typed_chain_id: JsString) -> Result<JsTypedChainId, JsValue> {
+		let typed_chain_id: String = typed_chain_id.into();
+		let inner: TypedChainId = typed_chain_id.parse()?;
+		Ok(inner.into())
+	}
+
+	/// Chain type name, `undefined` for chain ids without a type
+	#[wasm_bindgen(getter)]
+	#[wasm_bindgen(js_name = chainType)]
+	pub fn chain_type(&self) -> Option<JsString> {
+		self.inner.chain_type.map(|chain_type| chain_type.to_string().into())
+	}
+
+	#[wasm_bindgen(getter)]
+	#[wasm_bindgen(js_name = chainId)]
+	pub fn chain_id(&self) -> u32 {
+		self.inner.id
+	}
+
+	/// Decimal representation of the typed chain id, as stored in notes
+	#[wasm_bindgen(js_name = toString)]
+	pub fn to_js_string(&self) -> JsString {
+		self.inner.to_string().into()
+	}
+}
+
+impl From<TypedChainId> for JsTypedChainId {
+	fn from(inner: TypedChainId) -> Self {
+		Self { inner }
+	}
+}
+
+impl fmt::Display for ExtDataEncoding {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			ExtDataEncoding::Evm => write!(f, "Evm"),
+			ExtDataEncoding::Substrate => write!(f, "Substrate"),
+		}
+	}
+}
+
+impl FromStr for ExtDataEncoding {
+	type Err = OpStatusCode;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"Evm" => Ok(ExtDataEncoding::Evm),
+			"Substrate" => Ok(ExtDataEncoding::Substrate),
+			_ => Err(OpStatusCode::InvalidExtDataHash),
+		}
+	}
+}
+
+impl fmt::Display for TargetRuntime {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			TargetRuntime::SubstrateMixer => write!(f, "SubstrateMixer"),
+			TargetRuntime::SubstrateVAnchor => write!(f, "SubstrateVAnchor"),
+			TargetRuntime::EvmVAnchor => write!(f, "EvmVAnchor"),
+			TargetRuntime::EvmAnchor => write!(f, "EvmAnchor"),
+		}
+	}
+}
+
+impl FromStr for TargetRuntime {
+	type Err = OpStatusCode;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"SubstrateMixer" => Ok(TargetRuntime::SubstrateMixer),
+			"SubstrateVAnchor" => Ok(TargetRuntime::SubstrateVAnchor),
+			"EvmVAnchor" => Ok(TargetRuntime::EvmVAnchor),
+			"EvmAnchor" => Ok(TargetRuntime::EvmAnchor),
+			_ => Err(OpStatusCode::InvalidTargetRuntime),
+		}
+	}
+}
+
+impl fmt::Display for AnchorMode {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			AnchorMode::Substrate => write!(f, "Substrate"),
+			AnchorMode::EvmCompat => write!(f, "EvmCompat"),
+		}
+	}
+}
+
+impl FromStr for AnchorMode {
+	type Err = OpStatusCode;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"Substrate" => Ok(AnchorMode::Substrate),
+			"EvmCompat" => Ok(AnchorMode::EvmCompat),
+			_ => Err(OpStatusCode::InvalidAnchorMode),
+		}
+	}
+}
+
 impl fmt::Display for Backend {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		match self {
@@ -209,7 +794,7 @@ impl fmt::Display for HashFunction {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		match self {
 			HashFunction::Poseidon => write!(f, "Poseidon"),
-			HashFunction::MiMCTornado => write!(f, "MiMCTornado"),
+			HashFunction::MiMC220 => write!(f, "MiMC220"),
 		}
 	}
 }
@@ -241,7 +826,7 @@ impl FromStr for HashFunction {
 	fn from_str(s: &str) -> Result<Self, Self::Err> {
 		match s {
 			"Poseidon" => Ok(HashFunction::Poseidon),
-			"MiMCTornado" => Ok(HashFunction::MiMCTornado),
+			"MiMC220" => Ok(HashFunction::MiMC220),
 			_ => Err(OpStatusCode::InvalidHasFunction),
 		}
 	}
@@ -256,6 +841,9 @@ impl From<Curve> for ArkCurve {
 	}
 }
 
+/// Error codes, exported to JS so UIs can map the `code` of an
+/// `OperationError` to their own messages
+#[wasm_bindgen]
 #[derive(Debug, Eq, PartialEq, Clone)]
 #[repr(u32)]
 pub enum OpStatusCode {
@@ -367,6 +955,68 @@ pub enum OpStatusCode {
 	InvalidOutputUtxoConfig = 55,
 	InvalidExtDataHash = 56,
 	InvalidInputUtxoConfig = 57,
+	/// Invalid account address
+	InvalidAddress = 58,
+	/// Invalid relayer payload
+	InvalidRelayerPayload = 59,
+	/// Amount arithmetic overflowed or underflowed
+	AmountOverflow = 60,
+	/// Notes don't hold enough funds for the requested amount
+	InsufficientBalance = 61,
+	/// Ciphertext could not be decrypted with the given key
+	DecryptionFailed = 62,
+	/// Invalid or incomplete VAnchor keypair
+	InvalidKeypair = 63,
+	/// Verifying key bytes could not be parsed
+	InvalidVerifyingKey = 64,
+	/// Unknown target runtime, or one that doesn't verify the proof's protocol
+	InvalidTargetRuntime = 65,
+	/// Unknown log level
+	InvalidLogLevel = 66,
+	/// Amount has more decimals than the token
+	AmountPrecisionLoss = 67,
+	/// Invalid note backup envelope or metadata
+	InvalidNoteMetadata = 68,
+	/// The note's commitment isn't one of the leaves
+	NotFoundInLeaves = 69,
+	/// Identifying data that can't be parsed or doesn't match the chain type
+	InvalidIdentifyingData = 70,
+	/// A delegated prover's response that doesn't answer the request or whose
+	/// proof doesn't verify
+	InvalidDelegatedProof = 71,
+	/// A note backup that isn't one, or from an unsupported version
+	InvalidBackup = 72,
+	/// An input larger than the limits, or than the memory left
+	LimitExceeded = 73,
+	/// A field element that doesn't parse or isn't below the modulus
+	InvalidFieldElement = 74,
+	/// A chain event that isn't one of the expected events or doesn't decode
+	InvalidEvent = 75,
+	/// A trusted setup contribution that doesn't follow from the previous key
+	InvalidContribution = 76,
+	/// A deposit of another tool that Webb circuits can't spend or that
+	/// doesn't fit the other tool's format
+	IncompatibleDeposit = 77,
+	/// A note URI scheme or authority namespace that can't be written in a URI
+	InvalidNoteFormat = 78,
+	/// A note whose `check` value isn't the checksum of the rest of it, a
+	/// truncated or mistyped copy
+	NoteChecksumMismatch = 79,
+	/// Unknown anchor mode
+	InvalidAnchorMode = 80,
+	/// The circuit couldn't be synthesized from the input, or the input
+	/// doesn't satisfy its constraints
+	WitnessGenerationFailed = 81,
+	/// The tree of an input doesn't have any of the roots the proof is made
+	/// against
+	RootMismatch = 82,
+	/// The leaf of a note or UTXO isn't at its index in the leaves
+	LeafNotInTree = 83,
+	/// A public input that is 32 bytes but not below the scalar field
+	/// modulus, the verifier would reduce it to another value
+	PublicInputOverflow = 84,
+	/// A proof that doesn't verify against its public inputs
+	ProofVerificationFailed = 85,
 }
 
 #[wasm_bindgen]
@@ -399,28 +1049,59 @@ extern "C" {
 	#[wasm_bindgen(typescript_type = "Indices")]
 	pub type Indices;
 
+	#[wasm_bindgen(typescript_type = "ExtDataEncoding")]
+	pub type WasmExtDataEncoding;
+
+	#[wasm_bindgen(typescript_type = "TargetRuntime")]
+	pub type WasmTargetRuntime;
+
+	#[wasm_bindgen(extends = Array, typescript_type = "Array<string>")]
+	pub type StringArray;
+
+	#[wasm_bindgen(extends = Array, typescript_type = "Array<JsUtxo>")]
+	pub type JsUtxoArray;
+
+	#[wasm_bindgen(extends = Array, typescript_type = "Array<JsNote>")]
+	pub type JsNoteArray;
+
+	#[wasm_bindgen(extends = Array, typescript_type = "Array<OperationError>")]
+	pub type OperationErrorArray;
+
+	#[wasm_bindgen(extends = Array, typescript_type = "PublicInputs")]
+	pub type WasmPublicInputs;
 }
 
 #[wasm_bindgen(typescript_custom_section)]
-const NOTE_PROTOCOL: &str = "type NoteProtocol = 'mixer' | 'vanchor' ";
+const NOTE_PROTOCOL: &str = "export type NoteProtocol = 'mixer' | 'vanchor';";
+
+#[wasm_bindgen(typescript_custom_section)]
+const LEAVES: &str = "export type Leaves = Array<Uint8Array>;";
+
+#[wasm_bindgen(typescript_custom_section)]
+const INDICES: &str = "export type Indices = Array<number>;";
 
 #[wasm_bindgen(typescript_custom_section)]
-const LEAVES: &str = "type Leaves = Array<Uint8Array>;";
+const HF: &str = "export type HashFunction = 'Poseidon' | 'MiMC220'";
 
 #[wasm_bindgen(typescript_custom_section)]
-const INDICES: &str = "type Indices = Array<number>;";
+const CURVE: &str = "export type Curve = 'Bls381' | 'Bn254'";
 
 #[wasm_bindgen(typescript_custom_section)]
-const HF: &str = "type HashFunction = 'Poseidon' | 'MiMCTornado'";
+const VERSION: &str = "export type Version = 'v1'";
 
 #[wasm_bindgen(typescript_custom_section)]
-const CURVE: &str = "type Curve = 'Bls381' | 'Bn254'";
+const BE: &str = "export type Backend = 'Arkworks' | 'Circom'";
 
 #[wasm_bindgen(typescript_custom_section)]
-const VERSION: &str = "type Version = 'v1'";
+const EXT_DATA_ENCODING: &str = "export type ExtDataEncoding = 'Evm' | 'Substrate'";
 
 #[wasm_bindgen(typescript_custom_section)]
-const BE: &str = "type Backend = 'Arkworks' | 'Circom'";
+const TARGET_RUNTIME: &str =
+	"export type TargetRuntime = 'SubstrateMixer' | 'SubstrateVAnchor' | 'EvmVAnchor' | 'EvmAnchor'";
+
+// Substrate targets take raw bytes, EVM targets `0x` prefixed hex
+#[wasm_bindgen(typescript_custom_section)]
+const PUBLIC_INPUTS: &str = "export type PublicInputs = Array<Uint8Array> | Array<string>;";
 
 pub struct Uint8Arrayx32(pub [u8; 32]);
 
@@ -443,6 +1124,13 @@ impl TryFrom<Uint8Array> for Uint8Arrayx32 {
 		Ok(Self(bytes))
 	}
 }
+impl OpStatusCode {
+	/// Name of the variant, the `code` JS sees
+	pub fn name(&self) -> String {
+		format!("{:?}", self)
+	}
+}
+
 impl From<OpStatusCode> for String {
 	fn from(e: OpStatusCode) -> Self {
 		match e {
@@ -506,6 +1194,34 @@ impl From<OpStatusCode> for String {
 			OpStatusCode::InvalidOutputUtxoConfig => "Invalid output UTXO config",
 			OpStatusCode::InvalidExtDataHash => "Invalid external data hash",
 			OpStatusCode::InvalidInputUtxoConfig => "Invalid input UTXO config",
+			OpStatusCode::InvalidAddress => "Invalid address",
+			OpStatusCode::InvalidRelayerPayload => "Invalid relayer payload",
+			OpStatusCode::AmountOverflow => "Amount overflow",
+			OpStatusCode::InsufficientBalance => "Insufficient balance",
+			OpStatusCode::DecryptionFailed => "Decryption failed",
+			OpStatusCode::InvalidKeypair => "Invalid keypair",
+			OpStatusCode::InvalidVerifyingKey => "Invalid verifying key",
+			OpStatusCode::InvalidTargetRuntime => "Invalid target runtime",
+			OpStatusCode::InvalidLogLevel => "Invalid log level",
+			OpStatusCode::AmountPrecisionLoss => "Amount precision loss",
+			OpStatusCode::InvalidNoteMetadata => "Invalid note metadata",
+			OpStatusCode::NotFoundInLeaves => "Leaf not found in the leaves",
+			OpStatusCode::InvalidIdentifyingData => "Invalid identifying data",
+			OpStatusCode::InvalidDelegatedProof => "Invalid delegated proof",
+			OpStatusCode::InvalidBackup => "Invalid note backup",
+			OpStatusCode::LimitExceeded => "Size limit exceeded",
+			OpStatusCode::InvalidFieldElement => "Invalid field element",
+			OpStatusCode::InvalidEvent => "Invalid event",
+			OpStatusCode::InvalidContribution => "Invalid setup contribution",
+			OpStatusCode::IncompatibleDeposit => "Incompatible deposit",
+			OpStatusCode::InvalidNoteFormat => "Invalid note format",
+			OpStatusCode::NoteChecksumMismatch => "Note checksum mismatch",
+			OpStatusCode::InvalidAnchorMode => "Invalid anchor mode",
+			OpStatusCode::WitnessGenerationFailed => "Witness generation failed",
+			OpStatusCode::RootMismatch => "Root mismatch",
+			OpStatusCode::LeafNotInTree => "Leaf not in tree",
+			OpStatusCode::PublicInputOverflow => "Public input overflow",
+			OpStatusCode::ProofVerificationFailed => "Proof verification failed",
 		}
 		.to_string()
 	}
@@ -598,3 +1314,92 @@ impl From<NoteProtocol> for Protocol {
 pub fn main() {
 	console_error_panic_hook::set_once();
 }
+
+#[cfg(test)]
+mod test {
+	use wasm_bindgen_test::*;
+
+	use super::*;
+
+	#[wasm_bindgen_test]
+	fn should_convert_operation_error_to_js_object() {
+		let property = |error: &JsValue, key: &str| Reflect::get(error, &JsValue::from_str(key)).unwrap();
+
+		let error = JsValue::from(OperationError::from(OpStatusCode::InvalidCurve));
+		assert_eq!(property(&error, "code"), "InvalidCurve");
+		assert_eq!(property(&error, "message"), "Invalid curve");
+		assert_eq!(property(&error, "statusCode"), OpStatusCode::InvalidCurve as u32);
+		assert_eq!(property(&error, "error_message"), "Invalid curve");
+		assert!(!Reflect::has(&error, &JsValue::from_str("data")).unwrap());
+
+		let mut error = OperationError::new_with_message(OpStatusCode::InvalidProofParameters, "Mismatch".to_string());
+		error.data = Some(r#"{ "inputAmount": "1" }"#.to_string());
+		let error = JsValue::from(error);
+		assert_eq!(property(&property(&error, "data"), "inputAmount"), "1");
+
+		let mut error = OperationError::from(OpStatusCode::InvalidProof);
+		error.data = Some("not json".to_string());
+		assert_eq!(property(&JsValue::from(error), "data"), "not json");
+	}
+
+	#[wasm_bindgen_test]
+	fn should_parse_identifying_data_for_the_chain_type() {
+		let address = "0x38e7aa90c77f86747fab355eecaa0c2e4c3a463d";
+		let resource_id = format!("0x{}", "00".repeat(26) + "0100000007e9");
+		assert_eq!("3".parse::<IdentifyingData>().unwrap(), IdentifyingData::TreeId(3));
+		for data in [address, &resource_id, "3"] {
+			assert_eq!(data.parse::<IdentifyingData>().unwrap().to_string(), data);
+		}
+		for data in ["", "-1", "tree", "0x38e7", "0xzz", "4294967296"] {
+			let error = data.parse::<IdentifyingData>().unwrap_err();
+			assert_eq!(error.code, OpStatusCode::InvalidIdentifyingData);
+		}
+
+		let tree_id = IdentifyingData::TreeId(3);
+		let address: IdentifyingData = address.parse().unwrap();
+		let resource_id: IdentifyingData = resource_id.parse().unwrap();
+		assert!(tree_id.ensure_chain_type(Some(ChainType::Substrate)).is_ok());
+		assert!(tree_id.ensure_chain_type(Some(ChainType::KusamaParachain)).is_ok());
+		assert!(tree_id.ensure_chain_type(Some(ChainType::Evm)).is_err());
+		assert!(address.ensure_chain_type(Some(ChainType::Evm)).is_ok());
+		let error = address.ensure_chain_type(Some(ChainType::Substrate)).unwrap_err();
+		assert_eq!(error.code, OpStatusCode::InvalidIdentifyingData);
+		for chain_type in [None, Some(ChainType::Evm), Some(ChainType::Cosmos)] {
+			assert!(resource_id.ensure_chain_type(chain_type).is_ok());
+		}
+		assert!(address.ensure_chain_type(None).is_ok());
+	}
+
+	#[wasm_bindgen_test]
+	fn should_pack_resource_ids() {
+		let address = "0x38e7aa90c77f86747fab355eecaa0c2e4c3a463d";
+		let evm = JsTypedChainId::from_evm(5);
+		let resource_id = JsResourceId::from_contract_address(address.into(), &evm).unwrap();
+		let expected = format!("0x000000000000{}010000000005", &address[2..]);
+		assert_eq!(String::from(resource_id.to_js_string()), expected);
+		assert_eq!(expected.parse::<ResourceId>().unwrap(), resource_id.inner);
+		assert_eq!(String::from(resource_id.target_system()), address);
+
+		let substrate = JsTypedChainId::from_substrate(1080);
+		let resource_id = JsResourceId::from_tree_id(9, &substrate).unwrap();
+		let expected = format!("0x{}00000009020000000438", "00".repeat(22));
+		assert_eq!(resource_id.inner.to_string(), expected);
+		assert_eq!(
+			ResourceId::from_bytes(resource_id.inner.to_bytes()).unwrap(),
+			resource_id.inner
+		);
+		assert_eq!(resource_id.typed_chain_id().chain_id(), 1080);
+
+		// A tree id on an EVM chain, and a contract with no room for the address
+		assert!(JsResourceId::from_tree_id(9, &evm).is_err());
+		let untyped = TypedChainId {
+			chain_type: None,
+			id: 5,
+		};
+		assert!(ResourceId::new(TargetSystem::TreeId(9), untyped).is_err());
+		let invalid = format!("0x01{}0100000005", "00".repeat(25));
+		let error = invalid.parse::<ResourceId>().unwrap_err();
+		assert_eq!(error.code, OpStatusCode::InvalidIdentifyingData);
+		assert!("3".parse::<ResourceId>().is_err());
+	}
+}
@@ -1,6 +1,31 @@
+use std::cell::Cell;
+
 use wasm_bindgen::JsValue;
 
+thread_local! {
+	static LAST_TIMESTAMP: Cell<f64> = Cell::new(0.0);
+}
+
 pub fn to_rust_string<T: Into<JsValue>>(js_castable: T) -> String {
 	let js_value: JsValue = js_castable.into();
 	js_value.as_string().unwrap()
 }
+
+/// Compares two byte strings in a time that only depends on their lengths
+pub fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+	if a.len() != b.len() {
+		return false;
+	}
+	let diff = a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y));
+	diff == 0
+}
+
+/// Milliseconds since the epoch that never go back, `Date.now()` held at its
+/// highest value so far when the system clock is set back
+pub fn monotonic_now() -> f64 {
+	LAST_TIMESTAMP.with(|last| {
+		let now = js_sys::Date::now().max(last.get());
+		last.set(now);
+		now
+	})
+}
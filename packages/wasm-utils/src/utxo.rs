@@ -6,14 +6,17 @@ use ark_bn254::Fr as Bn254Fr;
 use ark_ff::{BigInteger, PrimeField};
 use ark_std::UniformRand;
 use arkworks_native_gadgets::poseidon::Poseidon;
-use arkworks_setups::common::setup_params;
 use arkworks_setups::utxo::Utxo;
 use arkworks_setups::{Curve as ArkCurve, VAnchorProver};
 use js_sys::{JsString, Uint8Array};
 use rand::rngs::OsRng;
+use rand::RngCore;
 use wasm_bindgen::prelude::*;
 
-use crate::types::{Backend, Curve, OpStatusCode, OperationError, WasmCurve, BE};
+use crate::entropy::SecretRng;
+use crate::hash::poseidon_params;
+use crate::note::vanchor;
+use crate::types::{parse_chain_id, Backend, Curve, OpStatusCode, OperationError, WasmCurve, BE};
 use crate::VAnchorR1CSProverBn254_30_2_2_2;
 
 #[derive(Clone)]
@@ -57,11 +60,16 @@ impl JsUtxo {
 		private_key: Option<Vec<u8>>,
 		index: Option<u64>,
 	) -> Result<JsUtxo, OperationError> {
-		let mut rng = OsRng;
+		let mut rng = SecretRng::new()?;
 		let utxo = match (curve, backend) {
 			(Curve::Bn254, Backend::Arkworks) => {
-				// If blinding wasn't passed, create it
-				let blinding = blinding.unwrap_or_else(|| Bn254Fr::rand(&mut rng).into_repr().to_bytes_be());
+				// If blinding wasn't passed, create it. It is 31 random bytes, as
+				// protocol-solidity draws it, so it fits the encrypted output
+				let blinding = blinding.unwrap_or_else(|| {
+					let mut blinding = vec![0u8; 32];
+					rng.fill_bytes(&mut blinding[1..]);
+					blinding
+				});
 
 				match private_key {
 					// If a private key was passed, generate the public key from the private key.
@@ -87,12 +95,13 @@ impl JsUtxo {
 								index,
 							),
 							// If neither key has been configured, simply create a utxo with a random private key.
-							None => VAnchorR1CSProverBn254_30_2_2_2::create_random_utxo(
+							None => VAnchorR1CSProverBn254_30_2_2_2::create_utxo(
 								ArkCurve::Bn254,
 								chain_id,
 								amount,
 								index,
-								&mut rng,
+								Bn254Fr::rand(&mut rng).into_repr().to_bytes_be(),
+								blinding,
 							),
 						}
 					}
@@ -181,7 +190,7 @@ impl JsUtxo {
 	pub fn get_nullifier(&self) -> Result<Vec<u8>, OpStatusCode> {
 		match &self.inner {
 			JsUtxoInner::Bn254(bn254_utxo) => {
-				let params4 = setup_params::<Bn254Fr>(ArkCurve::Bn254, 5, 4);
+				let params4 = poseidon_params::<Bn254Fr>(5, 4);
 				let hasher4 = Poseidon::<Bn254Fr>::new(params4);
 				let nullifier = bn254_utxo.calculate_nullifier(&hasher4);
 				match nullifier {
@@ -207,6 +216,61 @@ impl JsUtxo {
 	pub fn deserialize(utxo_str: &str) -> Result<JsUtxo, OperationError> {
 		utxo_str.parse().map_err(Into::into)
 	}
+
+	/// Secrets as stored in a VAnchor note:
+	/// `chain_id:amount:secret_key:blinding`
+	pub fn to_note_secrets(&self) -> Result<Vec<Vec<u8>>, OperationError> {
+		let secret_key = self.get_secret_key().ok_or_else(|| {
+			let message = "A note can only hold a UTXO with a secret key".to_string();
+			OperationError::new_with_message(OpStatusCode::InvalidNoteSecrets, message)
+		})?;
+
+		Ok(vec![
+			self.get_chain_id_bytes(),
+			self.get_amount(),
+			secret_key,
+			self.get_blinding(),
+		])
+	}
+
+	/// Rebuild the UTXO from the secrets of a VAnchor note, see
+	/// [`JsUtxo::to_note_secrets`]
+	pub fn from_note_secrets(
+		curve: Curve,
+		width: usize,
+		exponentiation: i8,
+		secrets: &[Vec<u8>],
+		index: Option<u64>,
+	) -> Result<JsUtxo, OperationError> {
+		let invalid =
+			|message: &str| OperationError::new_with_message(OpStatusCode::InvalidNoteSecrets, message.to_string());
+		let (chain_id, amount, secret_key, blinding) = match secrets {
+			[chain_id, amount, secret_key, blinding] => (chain_id, amount, secret_key, blinding),
+			_ => return Err(invalid("VAnchor secrets length should be 4 in length")),
+		};
+		if chain_id.len() < 8 || amount.len() < 16 {
+			return Err(invalid("VAnchor chain id or amount secret is too short"));
+		}
+
+		let mut chain_id_slice = [0u8; 8];
+		chain_id_slice.copy_from_slice(&chain_id[chain_id.len() - 8..]);
+		let chain_id = u64::from_be_bytes(chain_id_slice);
+
+		let mut amount_slice = [0u8; 16];
+		amount_slice.copy_from_slice(&amount[amount.len() - 16..]);
+		let amount = u128::from_be_bytes(amount_slice);
+
+		vanchor::get_leaf_with_private_raw(
+			curve,
+			width,
+			exponentiation,
+			Some(secret_key.clone()),
+			Some(blinding.clone()),
+			chain_id,
+			amount,
+			index,
+		)
+	}
 }
 
 #[wasm_bindgen]
@@ -244,7 +308,7 @@ impl JsUtxo {
 			.parse()
 			.map_err(|_| OpStatusCode::InvalidBackend)?;
 		let chain_id: String = chain_id.into();
-		let chain_id = chain_id.parse().map_err(|_| OpStatusCode::InvalidChainId)?;
+		let chain_id = parse_chain_id(&chain_id)?;
 		let amount: String = amount.into();
 		let amount: u128 = amount.parse().map_err(|_| OpStatusCode::InvalidAmount)?;
 		let index = match index {
@@ -321,9 +385,8 @@ impl JsUtxo {
 	}
 
 	#[wasm_bindgen(getter)]
-	pub fn index(&self) -> JsValue {
-		let index = self.get_index().unwrap_or(0);
-		JsValue::from(index)
+	pub fn index(&self) -> u64 {
+		self.get_index().unwrap_or(0)
 	}
 
 	#[wasm_bindgen(js_name = setIndex)]
@@ -0,0 +1,465 @@
+//! Message protocol between the JS host and wasm-utils running inside a
+//! worker. Commands and responses are plain objects tagged by `type`, e.g.
+//! `{ type: 'generateNote', id: 1, note: { .. } }`, amounts and bytes travel
+//! as strings so they survive `postMessage` and JSON without loss.
+use js_sys::{Function, Reflect};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::note::amount::parse_amount_field;
+use crate::note::{JsNote, JsNoteBuilder};
+use crate::proof::{mixer, JsProofInputBuilder, ProofInput, ProofInputBuilder, ProverRng};
+use crate::types::{OpStatusCode, OperationError};
+
+/// Parameters of a new note, every field mirrors a `JsNoteBuilder` setter
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct NoteParams {
+	pub protocol: String,
+	pub version: String,
+	pub source_chain_id: String,
+	pub target_chain_id: String,
+	#[serde(default)]
+	pub source_identifying_data: String,
+	#[serde(default)]
+	pub target_identifying_data: String,
+	pub backend: Option<String>,
+	pub hash_function: Option<String>,
+	pub curve: Option<String>,
+	pub width: Option<String>,
+	pub exponentiation: Option<String>,
+	pub denomination: Option<String>,
+	pub amount: Option<String>,
+	pub token_symbol: Option<String>,
+	pub index: Option<String>,
+}
+
+/// Inputs of a mixer withdrawal proof, bytes are hex encoded
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MixerProofParams {
+	pub note: String,
+	pub leaves: Vec<String>,
+	pub leaf_index: u64,
+	pub recipient: String,
+	pub relayer: String,
+	pub fee: String,
+	pub refund: String,
+	pub pk: String,
+	pub tree_depth: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "protocol", rename_all = "camelCase")]
+pub enum ProofParams {
+	Mixer(MixerProofParams),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum WorkerCommand {
+	GenerateNote { id: u32, note: NoteParams },
+	GenerateProof { id: u32, proof: ProofParams },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum WorkerResult {
+	Note {
+		note: String,
+	},
+	#[serde(rename_all = "camelCase")]
+	MixerProof {
+		proof: String,
+		nullifier_hash: String,
+		root: String,
+		public_inputs: Vec<String>,
+		leaf: String,
+	},
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ProgressStage {
+	Building,
+	Proving,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum WorkerResponse {
+	Progress {
+		id: u32,
+		stage: ProgressStage,
+	},
+	Result {
+		id: u32,
+		result: WorkerResult,
+	},
+	#[serde(rename_all = "camelCase")]
+	Error {
+		id: u32,
+		code: String,
+		message: String,
+	},
+}
+
+fn to_js<T: Serialize>(value: &T) -> Result<JsValue, OperationError> {
+	JsValue::from_serde(value)
+		.map_err(|e| OperationError::new_with_message(OpStatusCode::SerializationFailed, e.to_string()))
+}
+
+impl WorkerCommand {
+	pub fn id(&self) -> u32 {
+		match self {
+			WorkerCommand::GenerateNote { id, .. } | WorkerCommand::GenerateProof { id, .. } => *id,
+		}
+	}
+
+	pub fn to_js(&self) -> Result<JsValue, OperationError> {
+		to_js(self)
+	}
+
+	pub fn from_js(value: &JsValue) -> Result<Self, OperationError> {
+		value
+			.into_serde()
+			.map_err(|e| OperationError::new_with_message(OpStatusCode::DeserializationFailed, e.to_string()))
+	}
+}
+
+impl WorkerResponse {
+	pub fn to_js(&self) -> Result<JsValue, OperationError> {
+		to_js(self)
+	}
+
+	pub fn from_js(value: &JsValue) -> Result<Self, OperationError> {
+		value
+			.into_serde()
+			.map_err(|e| OperationError::new_with_message(OpStatusCode::DeserializationFailed, e.to_string()))
+	}
+
+	/// Error response from an error thrown by the wasm API, which is either an
+	/// `OperationError` or a plain message
+	fn from_js_error(id: u32, error: JsValue) -> Self {
+		let property = |key: &str| {
+			Reflect::get(&error, &JsValue::from_str(key))
+				.ok()
+				.and_then(|value| value.as_string())
+		};
+		let code = property("code").unwrap_or_else(|| OpStatusCode::Unknown.name());
+		let message = error.as_string().or_else(|| property("message")).unwrap_or_default();
+		WorkerResponse::Error { id, code, message }
+	}
+}
+
+fn invalid<E>(code: OpStatusCode) -> impl Fn(E) -> OperationError {
+	move |_| code.clone().into()
+}
+
+fn generate_note(params: NoteParams) -> Result<WorkerResult, JsValue> {
+	let mut builder = JsNoteBuilder::new();
+	builder.protocol = Some(
+		params
+			.protocol
+			.parse()
+			.map_err(invalid(OpStatusCode::InvalidNoteProtocol))?,
+	);
+	builder.version = Some(
+		params
+			.version
+			.parse()
+			.map_err(invalid(OpStatusCode::InvalidNoteVersion))?,
+	);
+	builder.source_chain_id = Some(params.source_chain_id);
+	builder.target_chain_id = Some(params.target_chain_id);
+	builder.source_identifying_data = Some(params.source_identifying_data);
+	builder.target_identifying_data = Some(params.target_identifying_data);
+	builder.backend = params
+		.backend
+		.map(|backend| backend.parse())
+		.transpose()
+		.map_err(invalid(OpStatusCode::InvalidBackend))?;
+	builder.hash_function = params
+		.hash_function
+		.map(|hash_function| hash_function.parse())
+		.transpose()
+		.map_err(invalid(OpStatusCode::InvalidHasFunction))?;
+	builder.curve = params
+		.curve
+		.map(|curve| curve.parse())
+		.transpose()
+		.map_err(invalid(OpStatusCode::InvalidCurve))?;
+	builder.width = params
+		.width
+		.map(|width| width.parse())
+		.transpose()
+		.map_err(invalid(OpStatusCode::InvalidWidth))?;
+	builder.exponentiation = params
+		.exponentiation
+		.map(|exponentiation| exponentiation.parse())
+		.transpose()
+		.map_err(invalid(OpStatusCode::InvalidExponentiation))?;
+	builder.denomination = params
+		.denomination
+		.map(|denomination| denomination.parse())
+		.transpose()
+		.map_err(invalid(OpStatusCode::InvalidDenomination))?;
+	builder.index = params
+		.index
+		.map(|index| index.parse())
+		.transpose()
+		.map_err(invalid(OpStatusCode::InvalidUTXOIndex))?;
+	builder.amount = params.amount;
+	builder.token_symbol = params.token_symbol;
+
+	let note = builder.build()?;
+	Ok(WorkerResult::Note { note: note.to_string() })
+}
+
+fn generate_proof(id: u32, params: ProofParams, report: &dyn Fn(WorkerResponse)) -> Result<WorkerResult, JsValue> {
+	let ProofParams::Mixer(params) = params;
+	report(WorkerResponse::Progress {
+		id,
+		stage: ProgressStage::Building,
+	});
+	let decode = |value: &str, code: OpStatusCode| hex::decode(value).map_err(|_| OperationError::from(code));
+	let note = JsNote::deserialize(&params.note)?;
+
+	let mut builder = JsProofInputBuilder {
+		inner: ProofInputBuilder::Mixer(Default::default()),
+		partial_withdrawal: None,
+	};
+	builder.apply_note(&note)?;
+	let leaves = params
+		.leaves
+		.iter()
+		.map(|leaf| decode(leaf, OpStatusCode::InvalidLeaves))
+		.collect::<Result<Vec<_>, _>>()?;
+	builder.inner.leaves_list(leaves)?;
+	builder.inner.leaf_index(params.leaf_index)?;
+	builder
+		.inner
+		.recipient(decode(&params.recipient, OpStatusCode::InvalidRecipient)?)?;
+	builder
+		.inner
+		.relayer(decode(&params.relayer, OpStatusCode::InvalidRelayer)?)?;
+	builder
+		.inner
+		.fee(parse_amount_field(&params.fee, None, OpStatusCode::InvalidFee)?)?;
+	builder
+		.inner
+		.refund(parse_amount_field(&params.refund, None, OpStatusCode::InvalidRefund)?)?;
+	builder.inner.pk(decode(&params.pk, OpStatusCode::InvalidProvingKey)?)?;
+	if let Some(tree_depth) = params.tree_depth {
+		builder.inner.tree_depth(tree_depth)?;
+	}
+	let payload = match builder.build()? {
+		ProofInput::Mixer(payload) => *payload,
+		_ => return Err(OpStatusCode::InvalidNoteProtocol.into()),
+	};
+
+	report(WorkerResponse::Progress {
+		id,
+		stage: ProgressStage::Proving,
+	});
+	let mut rng = ProverRng::new(payload.rng_seed);
+	let proof = mixer::create_proof(payload, &mut rng)?;
+	Ok(WorkerResult::MixerProof {
+		proof: hex::encode(&proof.proof),
+		nullifier_hash: hex::encode(&proof.nullifier_hash),
+		root: hex::encode(&proof.root),
+		public_inputs: proof.public_inputs.iter().map(hex::encode).collect(),
+		leaf: hex::encode(&proof.leaf),
+	})
+}
+
+/// Run a command and build its response, failures are reported as an error
+/// response instead of being thrown
+pub fn handle_command(command: WorkerCommand, report: &dyn Fn(WorkerResponse)) -> WorkerResponse {
+	let id = command.id();
+	let result = match command {
+		WorkerCommand::GenerateNote { note, .. } => generate_note(note),
+		WorkerCommand::GenerateProof { proof, .. } => generate_proof(id, proof, report),
+	};
+	match result {
+		Ok(result) => WorkerResponse::Result { id, result },
+		Err(e) => WorkerResponse::from_js_error(id, e),
+	}
+}
+
+#[wasm_bindgen(typescript_custom_section)]
+const WORKER_MESSAGES: &str = r#"
+export interface NoteParams {
+	protocol: NoteProtocol;
+	version: Version;
+	sourceChainId: string;
+	targetChainId: string;
+	sourceIdentifyingData?: string;
+	targetIdentifyingData?: string;
+	backend?: Backend;
+	hashFunction?: HashFunction;
+	curve?: Curve;
+	width?: string;
+	exponentiation?: string;
+	denomination?: string;
+	amount?: string;
+	tokenSymbol?: string;
+	index?: string;
+}
+
+export interface MixerProofParams {
+	protocol: 'mixer';
+	note: string;
+	leaves: Array<string>;
+	leafIndex: number;
+	recipient: string;
+	relayer: string;
+	fee: string;
+	refund: string;
+	pk: string;
+	treeDepth?: number;
+}
+
+export type ProofParams = MixerProofParams;
+
+export type WorkerCommand =
+	| { type: 'generateNote'; id: number; note: NoteParams }
+	| { type: 'generateProof'; id: number; proof: ProofParams };
+
+export type WorkerResult =
+	| { kind: 'note'; note: string }
+	| {
+			kind: 'mixerProof';
+			proof: string;
+			nullifierHash: string;
+			root: string;
+			publicInputs: Array<string>;
+			leaf: string;
+	  };
+
+export type ProgressStage = 'building' | 'proving';
+
+export type WorkerResponse =
+	| { type: 'progress'; id: number; stage: ProgressStage }
+	| { type: 'result'; id: number; result: WorkerResult }
+	| { type: 'error'; id: number; code: ErrorCode; message: string };
+"#;
+
+#[wasm_bindgen]
+extern "C" {
+	#[wasm_bindgen(typescript_type = "WorkerCommand")]
+	pub type WasmWorkerCommand;
+
+	#[wasm_bindgen(typescript_type = "WorkerResponse")]
+	pub type WasmWorkerResponse;
+
+	#[wasm_bindgen(typescript_type = "(response: WorkerResponse) => void")]
+	pub type WorkerProgressCallback;
+}
+
+/// Entry point of the worker, takes a `WorkerCommand` object and resolves to a
+/// `WorkerResponse` object. `onProgress` receives the progress responses
+#[wasm_bindgen(js_name = handleWorkerCommand)]
+pub fn handle_worker_command(
+	command: WasmWorkerCommand,
+	on_progress: Option<WorkerProgressCallback>,
+) -> Result<WasmWorkerResponse, JsValue> {
+	let command = WorkerCommand::from_js(&command)?;
+	let report = |response: WorkerResponse| {
+		if let (Some(on_progress), Ok(response)) = (&on_progress, response.to_js()) {
+			let _ = on_progress.unchecked_ref::<Function>().call1(&JsValue::NULL, &response);
+		}
+	};
+	let response = handle_command(command, &report);
+	Ok(response.to_js()?.unchecked_into())
+}
+
+#[cfg(test)]
+mod test {
+	use core::cell::RefCell;
+
+	use wasm_bindgen_test::*;
+
+	use super::*;
+	use crate::proof::test_utils::{DECODED_SUBSTRATE_ADDRESS, MIXER_NOTE_V1_X5_5};
+
+	fn note_params() -> NoteParams {
+		NoteParams {
+			protocol: "mixer".to_string(),
+			version: "v1".to_string(),
+			source_chain_id: "2".to_string(),
+			target_chain_id: "2".to_string(),
+			curve: Some("Bn254".to_string()),
+			width: Some("3".to_string()),
+			exponentiation: Some("5".to_string()),
+			denomination: Some("18".to_string()),
+			amount: Some("1".to_string()),
+			token_symbol: Some("EDG".to_string()),
+			..Default::default()
+		}
+	}
+
+	#[wasm_bindgen_test]
+	fn should_round_trip_worker_messages() {
+		let command = WorkerCommand::GenerateNote {
+			id: 7,
+			note: note_params(),
+		};
+		let js_command = command.to_js().unwrap();
+		assert_eq!(
+			Reflect::get(&js_command, &JsValue::from_str("type")).unwrap(),
+			JsValue::from_str("generateNote")
+		);
+		assert_eq!(WorkerCommand::from_js(&js_command).unwrap(), command);
+
+		let response = WorkerResponse::Progress {
+			id: 7,
+			stage: ProgressStage::Proving,
+		};
+		assert_eq!(WorkerResponse::from_js(&response.to_js().unwrap()).unwrap(), response);
+		assert!(WorkerCommand::from_js(&JsValue::from_str("generateNote")).is_err());
+	}
+
+	#[wasm_bindgen_test]
+	fn should_handle_worker_commands() {
+		let command = WorkerCommand::GenerateNote {
+			id: 1,
+			note: note_params(),
+		};
+		match handle_command(command, &|_| {}) {
+			WorkerResponse::Result {
+				id: 1,
+				result: WorkerResult::Note { note },
+			} => {
+				JsNote::deserialize(&note).unwrap();
+			}
+			response => panic!("Unexpected response {:?}", response),
+		}
+
+		let progress = RefCell::new(Vec::new());
+		let command = WorkerCommand::GenerateProof {
+			id: 2,
+			proof: ProofParams::Mixer(MixerProofParams {
+				note: MIXER_NOTE_V1_X5_5.to_string(),
+				leaves: vec![],
+				leaf_index: 0,
+				recipient: DECODED_SUBSTRATE_ADDRESS.to_string(),
+				relayer: DECODED_SUBSTRATE_ADDRESS.to_string(),
+				fee: "0".to_string(),
+				refund: "0".to_string(),
+				pk: "not hex".to_string(),
+				tree_depth: None,
+			}),
+		};
+		let response = handle_command(command, &|response| progress.borrow_mut().push(response));
+		assert_eq!(progress.into_inner(), vec![WorkerResponse::Progress {
+			id: 2,
+			stage: ProgressStage::Building
+		}]);
+		match response {
+			WorkerResponse::Error { id: 2, code, .. } => assert_eq!(code, "InvalidProvingKey"),
+			response => panic!("Unexpected response {:?}", response),
+		}
+	}
+}
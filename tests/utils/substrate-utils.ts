@@ -225,24 +225,21 @@ export async function fetchRPCTreeLeaves(api: ApiPromise, treeId: string | numbe
 }
 
 export async function depositMixerBnX5_3(api: ApiPromise, depositor: KeyringPair) {
-  let noteBuilder = new JsNoteBuilder();
-  noteBuilder.protocol('mixer');
-  noteBuilder.version('v1');
-
-  noteBuilder.sourceChainId('1');
-  noteBuilder.targetChainId('1');
-  noteBuilder.sourceIdentifyingData('3');
-  noteBuilder.targetIdentifyingData('3');
-
-  noteBuilder.tokenSymbol('WEBB');
-  noteBuilder.amount('1');
-  noteBuilder.denomination('18');
-
-  noteBuilder.backend('Arkworks');
-  noteBuilder.hashFunction('Poseidon');
-  noteBuilder.curve('Bn254');
-  noteBuilder.width('3');
-  noteBuilder.exponentiation('5');
+  const noteBuilder = new JsNoteBuilder()
+    .protocol('mixer')
+    .version('v1')
+    .sourceChainId('1')
+    .targetChainId('1')
+    .sourceIdentifyingData('3')
+    .targetIdentifyingData('3')
+    .tokenSymbol('WEBB')
+    .amount('1')
+    .denomination('18')
+    .backend('Arkworks')
+    .hashFunction('Poseidon')
+    .curve('Bn254')
+    .width('3')
+    .exponentiation('5');
   const note = noteBuilder.build();
   const leaf = note.getLeafCommitment();
 
@@ -297,19 +294,19 @@ export async function withdrawMixerBnX5_3(
       0,
       leafCount - 1
     );
-  const proofInputBuilder = new JsProofInputBuilder('mixer');
+  let proofInputBuilder = new JsProofInputBuilder('mixer');
   const leafHex = u8aToHex(note.getLeafCommitment());
-  proofInputBuilder.setNote(note);
-  proofInputBuilder.setLeaves(leaves);
+  proofInputBuilder = proofInputBuilder.setNote(note);
+  proofInputBuilder = proofInputBuilder.setLeaves(leaves);
   const leafIndex = leaves.findIndex((l) => u8aToHex(l) === leafHex);
 
-  proofInputBuilder.setLeafIndex(String(leafIndex));
+  proofInputBuilder = proofInputBuilder.setLeafIndex(String(leafIndex));
 
-  proofInputBuilder.setFee('0');
-  proofInputBuilder.setRefund('0');
+  proofInputBuilder = proofInputBuilder.setFee('0');
+  proofInputBuilder = proofInputBuilder.setRefund('0');
 
-  proofInputBuilder.setRecipient(addressHex.replace('0x', ''));
-  proofInputBuilder.setRelayer(relayerAddressHex.replace('0x', ''));
+  proofInputBuilder = proofInputBuilder.setRecipient(addressHex.replace('0x', ''));
+  proofInputBuilder = proofInputBuilder.setRelayer(relayerAddressHex.replace('0x', ''));
   const pkPath = path.join(
     // tests path
     process.cwd(),
@@ -323,7 +320,7 @@ export async function withdrawMixerBnX5_3(
 
   const pk = fs.readFileSync(pkPath);
 
-  proofInputBuilder.setPk(pk.toString('hex'));
+  proofInputBuilder = proofInputBuilder.setPk(pk.toString('hex'));
 
   const proofInput = proofInputBuilder.build_js();
 